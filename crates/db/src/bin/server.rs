@@ -39,6 +39,11 @@ fn output_to_result(output: ReplOutput) -> serde_json::Value {
     match output {
         ReplOutput::Rows { schema, rows } => {
             let columns: Vec<String> = schema.fields.iter().map(|f| f.name.clone()).collect();
+            let column_types: Vec<String> = schema
+                .fields
+                .iter()
+                .map(|f| format!("{:?}", f.data_type))
+                .collect();
             let rows_serialized: Vec<Vec<SerializableValue>> = rows
                 .into_iter()
                 .map(|row| {
@@ -50,6 +55,7 @@ fn output_to_result(output: ReplOutput) -> serde_json::Value {
                 .collect();
             serde_json::json!({
                 "columns": columns,
+                "column_types": column_types,
                 "rows": rows_serialized
             })
         }
@@ -96,6 +102,29 @@ async fn handle_client(mut stream: TcpStream, engine: Arc<Mutex<Engine>>) -> Res
                         },
                     }
                 }
+                "stats" => {
+                    let txns = engine_guard.txn_stats();
+                    let locks = engine_guard.lock_stats();
+                    Response {
+                        status: "ok".to_string(),
+                        result: Some(serde_json::json!({
+                            "transactions": {
+                                "begun": txns.begun,
+                                "committed": txns.committed,
+                                "aborted": txns.aborted,
+                                "deadlock_aborted": txns.deadlock_aborted,
+                                "avg_duration_us": txns.avg_duration.as_micros() as u64,
+                                "p50_duration_us": txns.p50_duration.as_micros() as u64,
+                                "p99_duration_us": txns.p99_duration.as_micros() as u64,
+                            },
+                            "locks": {
+                                "shared_grants": locks.shared_grants,
+                                "exclusive_grants": locks.exclusive_grants,
+                            },
+                        })),
+                        error: None,
+                    }
+                }
                 "ping" => Response {
                     status: "ok".to_string(),
                     result: Some(serde_json::json!({"version": env!("CARGO_PKG_VERSION")})),
@@ -6,6 +6,7 @@ pub enum MetaCommand {
     Help,
     Tables,
     Schema { table: String },
+    Strict { enabled: Option<bool> },
 }
 
 impl fmt::Display for MetaCommand {
@@ -15,6 +16,10 @@ impl fmt::Display for MetaCommand {
             MetaCommand::Help => write!(f, "help"),
             MetaCommand::Tables => write!(f, "tables"),
             MetaCommand::Schema { table } => write!(f, "schema {}", table),
+            MetaCommand::Strict { enabled: None } => write!(f, "strict"),
+            MetaCommand::Strict {
+                enabled: Some(enabled),
+            } => write!(f, "strict {}", if *enabled { "on" } else { "off" }),
         }
     }
 }
@@ -33,6 +38,17 @@ pub fn parse_meta_command(input: &str) -> Option<MetaCommand> {
         }
         "\\help" | ".help" | "help" => return Some(MetaCommand::Help),
         "\\tables" | ".tables" => return Some(MetaCommand::Tables),
+        "\\strict" | ".strict" => return Some(MetaCommand::Strict { enabled: None }),
+        "\\strict on" | ".strict on" => {
+            return Some(MetaCommand::Strict {
+                enabled: Some(true),
+            });
+        }
+        "\\strict off" | ".strict off" => {
+            return Some(MetaCommand::Strict {
+                enabled: Some(false),
+            });
+        }
         _ => {}
     }
 
@@ -61,6 +77,12 @@ mod tests {
         assert_eq!(parse_meta_command("exit"), Some(MetaCommand::Quit));
         assert_eq!(parse_meta_command("\\help"), Some(MetaCommand::Help));
         assert_eq!(parse_meta_command(".tables"), Some(MetaCommand::Tables));
+        assert_eq!(
+            parse_meta_command("\\strict on"),
+            Some(MetaCommand::Strict {
+                enabled: Some(true)
+            })
+        );
     }
 
     #[test]
@@ -1,34 +1,124 @@
-use std::collections::HashSet;
-use std::fs::File;
+use std::collections::{HashMap, HashSet};
+use std::fs;
 use std::path::{Path, PathBuf};
-use std::sync::Arc;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
 
 use anyhow::{Context, Result, anyhow, bail};
-use query::execution::operator::evaluate_expr;
+use query::execution::operator::{ExecutionResult, evaluate_expr};
+use query::execution::{PhysicalOperator, Privileges, SessionRandom, csv, explain_physical};
 use query::{
-    Catalog, ColumnDef, DataType, Executor, Expr, Field, LogicalPlan, PhysicalPlanner,
-    RecoveryManager, Schema, TableHeap, TableInfo, Tuple, Value, sql_to_logical_plan,
+    CancellationToken, Catalog, ChangeEvent, ChangeFeed, CheckConstraint, Collation, ColumnDef,
+    CopyDirection, CopyOptions, DEFAULT_WORK_MEM, DataType, Executor, Expr, Field, GrantDef,
+    IndexStats, InsertConflict, LogicalPlan, PhysicalPlanner, PlanLimits, Privilege, PrivilegeSpec,
+    RecoveryManager, Returning, Schema, SequenceDef, Setting, SharedCatalog, TableHeap, TableInfo,
+    TableStats, TempUsage, TextFormat, TransactionControl, Tuple, UniqueConstraint, Value,
+    sql_to_expr, sql_to_logical_plan,
 };
 use serde::{Deserialize, Serialize};
-use storage::{BufferPoolManager, DiskManager};
-use txn::{DeadlockPolicy, LockManager};
-use wal::{LogManager, TransactionManager};
+use storage::{BufferPoolManager, DiskManager, PageId};
+use txn::{DeadlockPolicy, LockManager, LockStats};
+use wal::{LogManager, TransactionManager, TxnStats};
 
 use crate::printer::ReplOutput;
 
 const DEFAULT_POOL_SIZE: usize = 64;
 
 pub struct Engine {
-    catalog: Catalog,
+    catalog: SharedCatalog,
     buffer_pool: BufferPoolManager,
-    #[allow(dead_code)]
     log_manager: Arc<LogManager>,
-    #[allow(dead_code)]
     lock_manager: Arc<LockManager>,
     txn_manager: TransactionManager,
     recovery: RecoveryManager,
-    #[allow(dead_code)]
     wal_path: PathBuf,
+    session: Option<SessionTransaction>,
+    /// Churn fraction that triggers a background stats refresh; `None` keeps
+    /// statistics manual (`ANALYZE TABLE`).
+    auto_analyze: Option<f64>,
+    /// Fragmentation at which an index is rebuilt after a commit; `None`
+    /// leaves indexes as they are.
+    auto_reindex: Option<f64>,
+    stats_workers: Vec<JoinHandle<()>>,
+    cancellation: Option<CancellationToken>,
+    plan_limits: PlanLimits,
+    /// Bytes of rows a sort or a join's hash table holds in memory before
+    /// spilling, set with `SET work_mem`.
+    work_mem: usize,
+    temp_usage: TempUsage,
+    strict_mode: bool,
+    /// Whether table heaps lock the rows they change rather than their
+    /// whole pages.
+    row_locks: bool,
+    warnings: Vec<String>,
+    random: SessionRandom,
+    /// Role whose privileges statements are checked against; `None` runs
+    /// them unchecked, as the database owner.
+    role: Option<String>,
+    checkpoint_policy: CheckpointPolicy,
+    commits_since_checkpoint: usize,
+    /// Held while a statement, commit, or rollback runs so the background
+    /// checkpointer flushes pages only between them.
+    checkpoint_gate: Arc<Mutex<()>>,
+    checkpointer: Option<BackgroundCheckpointer>,
+    /// Pages of tables dropped by each open transaction. They are freed
+    /// once it ends, when nothing can still write to them.
+    pending_frees: HashMap<wal::TxnId, Vec<PageId>>,
+    /// Catalog entries as they were before each schema change of an open
+    /// transaction, put back in reverse order if the change is rolled back.
+    catalog_undo: HashMap<wal::TxnId, Vec<CatalogUndo>>,
+}
+
+/// When the engine takes a checkpoint on its own. Each trigger is off when
+/// `None`; the default policy only checkpoints on request.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CheckpointPolicy {
+    /// Checkpoint after this many committed transactions.
+    pub every_commits: Option<usize>,
+    /// Checkpoint from a background thread this often.
+    pub interval: Option<Duration>,
+    /// Checkpoint after a commit that leaves more dirty pages than this in
+    /// the buffer pool.
+    pub max_dirty_pages: Option<usize>,
+}
+
+/// Thread taking the interval checkpoints; it exits once `stop` is dropped.
+struct BackgroundCheckpointer {
+    stop: mpsc::Sender<()>,
+    worker: JoinHandle<()>,
+}
+
+impl BackgroundCheckpointer {
+    fn stop(self) {
+        drop(self.stop);
+        let _ = self.worker.join();
+    }
+}
+
+/// Transaction opened with `BEGIN`; statements passed to `execute_sql` run
+/// inside it until `COMMIT` or `ROLLBACK`.
+struct SessionTransaction {
+    txn: wal::TransactionHandle,
+    /// Name, last log record, and catalog undo depth of each savepoint.
+    savepoints: Vec<(String, wal::Lsn, usize)>,
+}
+
+/// A catalog entry before a statement changed it. Row changes are undone
+/// through the log, but the catalog is not logged per transaction.
+enum CatalogUndo {
+    /// The table under this name; `None` if there was none.
+    Table {
+        name: String,
+        before: Option<TableInfo>,
+    },
+    /// The sequence under this name; `None` if there was none.
+    Sequence {
+        name: String,
+        before: Option<SequenceDef>,
+    },
+    Privileges(Privileges),
 }
 
 impl Engine {
@@ -37,6 +127,7 @@ impl Engine {
     }
 
     pub fn new_with_pool(db_path: &Path, pool_size: usize) -> Result<Self> {
+        crate::upgrade::upgrade_if_needed(db_path)?;
         let disk_manager = DiskManager::open(db_path).context("open database file")?;
         let wal_path = db_path.with_extension("wal");
         let log_manager = Arc::new(LogManager::open(&wal_path).context("open wal file")?);
@@ -45,6 +136,9 @@ impl Engine {
             pool_size,
             Some(Arc::clone(&log_manager)),
         );
+        if cfg!(debug_assertions) {
+            buffer_pool.enable_pin_tracking()?;
+        }
         let lock_manager = Arc::new(LockManager::new(DeadlockPolicy::Timeout(
             std::time::Duration::from_secs(1),
         )));
@@ -56,13 +150,32 @@ impl Engine {
         let catalog_path = wal_path.with_extension("catalog");
 
         let mut engine = Self {
-            catalog: Catalog::new(),
+            catalog: SharedCatalog::default(),
             buffer_pool,
             log_manager,
             lock_manager,
             txn_manager,
             recovery,
             wal_path,
+            session: None,
+            auto_analyze: None,
+            auto_reindex: None,
+            stats_workers: Vec::new(),
+            cancellation: None,
+            plan_limits: PlanLimits::default(),
+            work_mem: DEFAULT_WORK_MEM,
+            temp_usage: TempUsage::default(),
+            strict_mode: false,
+            row_locks: false,
+            random: SessionRandom::new(),
+            warnings: Vec::new(),
+            role: None,
+            checkpoint_policy: CheckpointPolicy::default(),
+            commits_since_checkpoint: 0,
+            checkpoint_gate: Arc::new(Mutex::new(())),
+            checkpointer: None,
+            pending_frees: HashMap::new(),
+            catalog_undo: HashMap::new(),
         };
 
         engine.recovery.recover(&engine.buffer_pool)?;
@@ -70,85 +183,899 @@ impl Engine {
         Ok(engine)
     }
 
+    /// Flushes every dirty page, logs the catalog, appends a checkpoint
+    /// record to the log, and deletes the log segments recovery no longer
+    /// needs.
     pub fn checkpoint(&mut self) -> Result<()> {
-        self.buffer_pool
+        let _gate = self
+            .checkpoint_gate
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        Self::write_checkpoint(
+            &self.buffer_pool,
+            &self.log_manager,
+            &self.catalog,
+            &self.wal_path.with_extension("catalog"),
+        )?;
+        self.commits_since_checkpoint = 0;
+        Ok(())
+    }
+
+    fn write_checkpoint(
+        buffer_pool: &BufferPoolManager,
+        log_manager: &LogManager,
+        catalog: &SharedCatalog,
+        catalog_path: &Path,
+    ) -> Result<()> {
+        buffer_pool
             .flush_all_pages_with_mode(storage::FlushMode::Force)
             .context("flush pages for checkpoint")?;
-        self.log_manager.force_flush().context("force flush WAL")?;
-        self.persist_catalog()?;
+        Self::write_catalog(log_manager, catalog_path, &catalog.read())?;
+        // every page was just written back, so none needs redo from before
+        // the checkpoint
+        let lsn = log_manager
+            .checkpoint(Vec::new())
+            .context("log checkpoint")?;
+        log_manager.truncate_before(lsn).context("truncate log")?;
+        Ok(())
+    }
+
+    /// Takes checkpoints according to `policy` from now on. Commit-count and
+    /// dirty-page triggers are checked after each commit; an interval runs a
+    /// background thread that checkpoints between statements.
+    pub fn set_checkpoint_policy(&mut self, policy: CheckpointPolicy) {
+        if let Some(checkpointer) = self.checkpointer.take() {
+            checkpointer.stop();
+        }
+        if let Some(interval) = policy.interval {
+            self.checkpointer = Some(self.spawn_checkpointer(interval));
+        }
+        self.checkpoint_policy = policy;
+        self.commits_since_checkpoint = 0;
+    }
+
+    pub fn checkpoint_policy(&self) -> &CheckpointPolicy {
+        &self.checkpoint_policy
+    }
+
+    fn spawn_checkpointer(&self, interval: Duration) -> BackgroundCheckpointer {
+        let (stop, stopped) = mpsc::channel::<()>();
+        let buffer_pool = self.buffer_pool.clone();
+        let log_manager = Arc::clone(&self.log_manager);
+        let catalog = self.catalog.clone();
+        let catalog_path = self.wal_path.with_extension("catalog");
+        let gate = Arc::clone(&self.checkpoint_gate);
+        let worker = thread::spawn(move || {
+            while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                let _gate = gate.lock().unwrap_or_else(|e| e.into_inner());
+                if let Err(e) =
+                    Self::write_checkpoint(&buffer_pool, &log_manager, &catalog, &catalog_path)
+                {
+                    eprintln!("WARN: background checkpoint failed: {}", e);
+                }
+            }
+        });
+        BackgroundCheckpointer { stop, worker }
+    }
+
+    /// Checkpoints once a trigger of the policy has been reached.
+    fn checkpoint_if_due(&mut self) -> Result<()> {
+        self.commits_since_checkpoint += 1;
+        let policy = &self.checkpoint_policy;
+        let mut due = policy
+            .every_commits
+            .is_some_and(|commits| self.commits_since_checkpoint >= commits);
+        if let Some(max_dirty_pages) = policy.max_dirty_pages {
+            let dirty_pages = self
+                .buffer_pool
+                .dirty_page_count()
+                .map_err(|err| anyhow!(err))?;
+            due |= dirty_pages > max_dirty_pages;
+        }
+        if due {
+            self.checkpoint()?;
+        }
         Ok(())
     }
 
-    fn persist_catalog(&self) -> Result<()> {
+    /// Returns a handle to the catalog that other threads can read while
+    /// this engine runs a statement, without taking the engine itself. The
+    /// statements run through the engine are still serialized.
+    pub fn shared_catalog(&self) -> SharedCatalog {
+        self.catalog.clone()
+    }
+
+    /// Logs a snapshot of the catalog and then rewrites the catalog file.
+    /// The catalog is not stored in pages, so recovery reads the schema back
+    /// from the last snapshot in the log; a crash while the file is being
+    /// written loses nothing.
+    fn persist_catalog(&self, catalog: &Catalog) -> Result<()> {
         let catalog_path = self.wal_path.with_extension("catalog");
-        self._persist_catalog(&catalog_path)
+        Self::write_catalog(&self.log_manager, &catalog_path, catalog)
+    }
+
+    fn write_catalog(
+        log_manager: &LogManager,
+        catalog_path: &Path,
+        catalog: &Catalog,
+    ) -> Result<()> {
+        let snapshot = Self::serialize_catalog(catalog)?;
+        let lsn = log_manager
+            .append(wal::LogRecord::catalog(0, snapshot.clone()))
+            .context("log catalog snapshot")?;
+        log_manager.flush(lsn).context("flush catalog snapshot")?;
+        fs::write(catalog_path, snapshot).context("write catalog file")?;
+        Ok(())
     }
 
     pub fn begin_transaction(&mut self) -> Result<wal::TransactionHandle> {
         self.txn_manager.begin().context("begin transaction")
     }
 
+    /// Begins a transaction that rejects every statement that would write.
+    /// It takes only shared locks and writes nothing to the log.
+    pub fn begin_read_only_transaction(&mut self) -> wal::TransactionHandle {
+        self.txn_manager.begin_read_only()
+    }
+
+    /// Begins a `REPEATABLE READ` transaction. Its reads see the rows as
+    /// they were when it began, plus its own changes, and never wait for
+    /// writers; changing a row another transaction changed since then
+    /// fails with a retryable serialization error. Begun while a transaction
+    /// that changed rows with no snapshot open is still running, it locks
+    /// what it reads like any other transaction.
+    pub fn begin_snapshot_transaction(&mut self) -> Result<wal::TransactionHandle> {
+        let txn = self.begin_transaction()?;
+        self.txn_manager.take_snapshot(&txn);
+        Ok(txn)
+    }
+
+    pub fn lock_stats(&self) -> LockStats {
+        self.lock_manager.stats()
+    }
+
+    pub fn txn_stats(&self) -> TxnStats {
+        self.txn_manager.stats()
+    }
+
+    /// Row changes of the transactions committed at or after `from_lsn`, in
+    /// commit order. Only durable commits are seen, so a consumer can poll
+    /// and resume from the last event's `commit_lsn + 1`. Fails once a
+    /// checkpoint has deleted the part of the log those changes were in.
+    pub fn changes_since(&self, from_lsn: wal::Lsn) -> Result<Vec<ChangeEvent>> {
+        ChangeFeed::new(&self.wal_path)
+            .changes_since(from_lsn)
+            .context("read change feed")
+    }
+
     pub fn execute_sql_in_transaction(
         &mut self,
         sql: &str,
         txn: &wal::TransactionHandle,
     ) -> Result<ReplOutput> {
-        let plan = sql_to_logical_plan(sql)?;
+        self.execute_prepared_in_transaction(sql, &[], txn)
+    }
+
+    /// [`Engine::execute_prepared`] in the client transaction `txn`.
+    pub fn execute_prepared_in_transaction(
+        &mut self,
+        sql: &str,
+        params: &[Value],
+        txn: &wal::TransactionHandle,
+    ) -> Result<ReplOutput> {
+        let mut plan = sql_to_logical_plan(sql)?;
+        plan.bind_parameters(params)?;
+        if let LogicalPlan::Transaction { control } = &plan {
+            bail!(
+                "{} is not supported inside an explicit transaction",
+                control
+            );
+        }
         let txn_manager = self.txn_manager.clone();
-        txn_manager.with_transaction(txn, || self.execute_plan(plan))
+        txn_manager.with_transaction(txn, || self.execute_statement(plan))
     }
 
     pub fn commit_transaction(&mut self, txn: &wal::TransactionHandle) -> Result<()> {
-        self.txn_manager.commit(txn).context("commit transaction")?;
-        Ok(())
+        {
+            let gate = Arc::clone(&self.checkpoint_gate);
+            let _gate = gate.lock().unwrap_or_else(|e| e.into_inner());
+            self.txn_manager.commit(txn).context("commit transaction")?;
+            self.catalog_undo.remove(&txn.lock().txn_id);
+            self.settle_row_counts(txn, TableHeap::commit_row_count)?;
+            self.settle_row_counts(txn, TableHeap::forget_writes)?;
+            self.free_dropped_pages(txn)?;
+            self.reindex_fragmented()?;
+        }
+        self.schedule_stats_refresh();
+        self.checkpoint_if_due()
     }
 
     pub fn abort_transaction(&mut self, txn: &wal::TransactionHandle) -> Result<()> {
+        let gate = Arc::clone(&self.checkpoint_gate);
+        let _gate = gate.lock().unwrap_or_else(|e| e.into_inner());
         self.txn_manager.abort(txn).context("abort transaction")?;
+        let txn_id = txn.lock().txn_id;
+        let restored = self.undo_catalog_changes(txn_id, 0)?;
         self.recovery
             .rollback_transaction(&self.buffer_pool, txn)
             .context("rollback transaction")?;
+        self.txn_manager.finish_rollback(txn);
+        self.reattach_restored_tables(txn_id, &restored)?;
+        self.settle_row_counts(txn, TableHeap::abort_row_count)?;
+        self.settle_row_counts(txn, TableHeap::forget_writes)?;
+        // dropped tables the rollback put back keep their pages
+        self.free_dropped_pages(txn)
+    }
+
+    /// Runs `f` in a new transaction, which commits when `f` returns `Ok`
+    /// and rolls back when it returns `Err`, whose error is then returned.
+    pub fn transaction<T>(
+        &mut self,
+        f: impl FnOnce(&mut Transaction<'_>) -> Result<T>,
+    ) -> Result<T> {
+        let handle = self.begin_transaction()?;
+        let result = f(&mut Transaction {
+            engine: self,
+            handle: Arc::clone(&handle),
+        });
+        match result {
+            Ok(value) => {
+                self.commit_transaction(&handle)?;
+                Ok(value)
+            }
+            Err(error) => match self.abort_transaction(&handle) {
+                Ok(()) => Err(error),
+                Err(abort_error) => {
+                    Err(error.context(format!("rollback also failed: {:#}", abort_error)))
+                }
+            },
+        }
+    }
+
+    /// Logs and frees the pages of the tables `txn` dropped, and of its
+    /// temporary tables, which end with it. Redo skips the older records
+    /// for those pages, since they may be reused by then.
+    fn free_dropped_pages(&mut self, txn: &wal::TransactionHandle) -> Result<()> {
+        let txn_id = txn.lock().txn_id;
+        let mut page_ids = self.pending_frees.remove(&txn_id).unwrap_or_default();
+        for table in self.catalog.write().drop_temp_tables(txn_id) {
+            page_ids.extend(table.page_ids().map_err(|err| anyhow!(err))?);
+        }
+        self.free_pages(page_ids)
+    }
+
+    fn free_pages(&self, page_ids: Vec<PageId>) -> Result<()> {
+        if page_ids.is_empty() {
+            return Ok(());
+        }
+        let lsn = self
+            .log_manager
+            .append(wal::LogRecord::free_pages(0, page_ids.clone()))
+            .context("log freed pages")?;
+        self.log_manager.flush(lsn).context("flush freed pages")?;
+        self.buffer_pool
+            .free_pages(&page_ids)
+            .context("free dropped pages")
+    }
+
+    /// Applies `settle` to `txn`'s bookkeeping on each table, e.g. folding
+    /// its inserts and deletes into, or dropping them from, the maintained
+    /// row count.
+    fn settle_row_counts(
+        &self,
+        txn: &wal::TransactionHandle,
+        settle: fn(&TableHeap, wal::TxnId) -> ExecutionResult<()>,
+    ) -> Result<()> {
+        let txn_id = txn.lock().txn_id;
+        let catalog = self.catalog.read();
+        for table in catalog.tables().chain(catalog.temp_tables(txn_id)) {
+            settle(&table.heap, txn_id).map_err(|err| anyhow!(err))?;
+        }
         Ok(())
     }
 
     pub fn execute_sql(&mut self, sql: &str) -> Result<ReplOutput> {
-        let plan = sql_to_logical_plan(sql)?;
-        let txn = self.txn_manager.begin().context("begin transaction")?;
+        self.execute_prepared(sql, &[])
+    }
+
+    /// Runs `sql` with its `?` placeholders bound, in order, to `params`.
+    /// The values never become SQL text, so strings need no quoting or
+    /// escaping; the statement fails unless one value is bound per
+    /// placeholder.
+    pub fn execute_prepared(&mut self, sql: &str, params: &[Value]) -> Result<ReplOutput> {
+        let mut plan = sql_to_logical_plan(sql)?;
+        plan.bind_parameters(params)?;
+        if let LogicalPlan::Transaction { control } = plan {
+            return self.execute_transaction_control(control);
+        }
+        if let Some(session) = &self.session {
+            let txn = Arc::clone(&session.txn);
+            return self.execute_in_session(&txn, plan);
+        }
+        // a statement that only reads logs nothing, so it still runs while
+        // the disk is full
+        let txn = if plan.is_read_only() {
+            self.txn_manager.begin_read_only()
+        } else {
+            self.txn_manager.begin().context("begin transaction")?
+        };
         let txn_manager = self.txn_manager.clone();
-        let result = txn_manager.with_transaction(&txn, || self.execute_plan(plan));
+        let result = txn_manager.with_transaction(&txn, || self.execute_statement(plan));
 
         match result {
             Ok(output) => {
-                self.txn_manager
-                    .commit(&txn)
-                    .context("commit transaction")?;
+                self.commit_transaction(&txn)?;
                 Ok(output)
             }
             Err(error) => {
-                self.txn_manager.abort(&txn).context("abort transaction")?;
-                self.recovery
-                    .rollback_transaction(&self.buffer_pool, &txn)
-                    .context("rollback transaction")?;
+                self.abort_transaction(&txn)?;
                 Err(error)
             }
         }
     }
 
+    /// Runs one statement and, in debug builds, fails it if it left a page
+    /// pinned so the leak surfaces with the page id and pin site.
+    fn execute_statement(&mut self, plan: LogicalPlan) -> Result<ReplOutput> {
+        let gate = Arc::clone(&self.checkpoint_gate);
+        let _gate = gate.lock().unwrap_or_else(|e| e.into_inner());
+        let sequences = self.catalog.read().sequences().clone();
+        let random = self.random.clone();
+        let result = sequences.with_current(|| random.with_current(|| self.execute_plan(plan)));
+        self.buffer_pool
+            .check_pins_released()
+            .map_err(|err| anyhow!(err))?;
+        // persisted before the values are returned, so a restart never
+        // hands them out again
+        if sequences.take_advanced() {
+            self.persist_catalog(&self.catalog.read())?;
+        }
+        result
+    }
+
+    /// Refreshes a table's statistics in the background once the rows
+    /// modified since its last ANALYZE reach `churn_threshold` of its row
+    /// count (e.g. `0.1` for 10%). `None` turns the refresh off.
+    pub fn set_auto_analyze(&mut self, churn_threshold: Option<f64>) {
+        self.auto_analyze = churn_threshold;
+    }
+
+    pub fn table_stats(&self, table_name: &str) -> Option<TableStats> {
+        self.catalog
+            .read()
+            .table(table_name)
+            .and_then(|table| table.stats.stats())
+    }
+
+    /// Rebuilds an index after a commit once its
+    /// [`IndexStats::fragmentation`] reaches `fragmentation` (e.g. `0.3` when
+    /// nearly a third of its leaf pages are surplus; merges keep it under
+    /// about `0.5`). `None` turns the rebuild off.
+    pub fn set_auto_reindex(&mut self, fragmentation: Option<f64>) {
+        self.auto_reindex = fragmentation;
+    }
+
+    /// The shape of each index on `table_name`, by index name.
+    pub fn index_stats(&self, table_name: &str) -> Result<Vec<(String, IndexStats)>> {
+        let catalog = self.catalog.read();
+        let table = catalog
+            .table(table_name)
+            .ok_or_else(|| anyhow!("table {} not found", table_name))?;
+        table
+            .indexes
+            .iter()
+            .map(|index| {
+                let stats = index.index.stats().map_err(|err| anyhow!(err))?;
+                Ok((index.name.clone(), stats))
+            })
+            .collect()
+    }
+
+    /// Rebuilds the indexes whose fragmentation reached the
+    /// `set_auto_reindex` threshold and frees their old pages. An index is
+    /// only measured once a leaf's worth of its entries has been deleted
+    /// since it was last measured.
+    fn reindex_fragmented(&mut self) -> Result<()> {
+        let Some(threshold) = self.auto_reindex else {
+            return Ok(());
+        };
+        let mut catalog = self.catalog.write();
+        let mut freed = Vec::new();
+        for table_name in catalog.table_names() {
+            let Some(table) = catalog.table_mut(&table_name) else {
+                continue;
+            };
+            let mut fragmented = Vec::new();
+            for index in &table.indexes {
+                if index.index.deletes() < index.index.max_leaf_entries() {
+                    continue;
+                }
+                index.index.take_deletes();
+                let stats = index.index.stats().map_err(|err| anyhow!(err))?;
+                if stats.fragmentation() >= threshold {
+                    fragmented.push(index.name.clone());
+                }
+            }
+            for index_name in fragmented {
+                freed.extend(
+                    table
+                        .rebuild_index(&index_name)
+                        .map_err(|err| anyhow!(err))?,
+                );
+            }
+        }
+        drop(catalog);
+        self.free_pages(freed)
+    }
+
+    /// Blocks until every background stats refresh has finished.
+    pub fn wait_for_stats_refresh(&mut self) {
+        for worker in self.stats_workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+
+    fn schedule_stats_refresh(&mut self) {
+        let Some(churn_threshold) = self.auto_analyze else {
+            return;
+        };
+        self.stats_workers.retain(|worker| !worker.is_finished());
+        let catalog = self.catalog.read();
+        for table in catalog.tables() {
+            if table.stats.is_stale(churn_threshold)
+                && let Some(worker) = table
+                    .stats
+                    .refresh_in_background(table.heap.clone(), table.indexes.clone())
+            {
+                self.stats_workers.push(worker);
+            }
+        }
+    }
+
+    /// Rejects queries exceeding `limits` at planning time.
+    pub fn set_plan_limits(&mut self, limits: PlanLimits) {
+        self.plan_limits = limits;
+    }
+
+    /// Sets the bytes of rows a sort or a join's hash table may hold in
+    /// memory, as `SET work_mem` does. A sort over more spills its rows to
+    /// temporary pages.
+    pub fn set_work_mem(&mut self, bytes: usize) {
+        self.work_mem = bytes;
+    }
+
+    pub fn work_mem(&self) -> usize {
+        self.work_mem
+    }
+
+    /// Temporary pages written by sorts that outgrew `work_mem`, since the
+    /// engine was opened.
+    pub fn temp_pages_written(&self) -> u64 {
+        self.temp_usage.pages()
+    }
+
+    fn physical_planner<'c>(&self, catalog: &'c Catalog) -> PhysicalPlanner<'c> {
+        PhysicalPlanner::new(catalog)
+            .with_limits(self.plan_limits)
+            .with_work_mem(self.work_mem)
+            .with_temp_usage(self.temp_usage.clone())
+    }
+
+    /// In strict mode statements whose result depends on storage order, such
+    /// as LIMIT without ORDER BY, still run but record a warning.
+    pub fn set_strict_mode(&mut self, strict: bool) {
+        self.strict_mode = strict;
+    }
+
+    pub fn strict_mode(&self) -> bool {
+        self.strict_mode
+    }
+
+    /// Locks the rows statements read, update and delete instead of their
+    /// whole pages, so transactions touching different rows of one page do
+    /// not wait for each other. Applies to every table, including ones created
+    /// later; inserts still lock the page they add to.
+    pub fn set_row_locks(&mut self, enabled: bool) {
+        self.row_locks = enabled;
+        for table in self.catalog.read().tables() {
+            table.heap.set_row_locks(enabled);
+        }
+    }
+
+    pub fn row_locks(&self) -> bool {
+        self.row_locks
+    }
+
+    /// Seeds the generator behind `RANDOM()`, so the same statements return
+    /// the same values after each call with the same seed. `None` goes back
+    /// to an unpredictable seed.
+    pub fn set_random_seed(&mut self, seed: Option<u64>) {
+        self.random.reseed(seed);
+    }
+
+    /// Runs later statements as `role`, which needs privileges granted with
+    /// `GRANT` for each table and column it reads or writes and cannot change
+    /// the schema. `None` goes back to running statements unchecked.
+    pub fn set_role(&mut self, role: Option<&str>) {
+        self.role = role.map(str::to_string);
+    }
+
+    pub fn role(&self) -> Option<&str> {
+        self.role.as_deref()
+    }
+
+    /// Returns and clears the warnings recorded since the last call.
+    pub fn take_warnings(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.warnings)
+    }
+
+    /// Runs `f` with `token` attached to the statements it executes. Once the
+    /// token is cancelled the running statement fails with
+    /// `ExecutionError::Cancelled` and its transaction is rolled back.
+    pub fn with_cancellation<T>(
+        &mut self,
+        token: CancellationToken,
+        f: impl FnOnce(&mut Self) -> T,
+    ) -> T {
+        let previous = self.cancellation.replace(token);
+        let result = f(self);
+        self.cancellation = previous;
+        result
+    }
+
+    /// Returns true while a `BEGIN` block is open.
+    pub fn in_transaction(&self) -> bool {
+        self.session.is_some()
+    }
+
+    fn execute_in_session(
+        &mut self,
+        txn: &wal::TransactionHandle,
+        plan: LogicalPlan,
+    ) -> Result<ReplOutput> {
+        // a failed statement is undone on its own; the block stays open
+        let statement_lsn = txn.lock().last_lsn.unwrap_or_default();
+        let statement_undo = self.catalog_undo_depth(txn);
+        let txn_manager = self.txn_manager.clone();
+        let result = txn_manager.with_transaction(txn, || self.execute_statement(plan));
+        if result.is_err() {
+            self.rollback_session_to(txn, statement_lsn, statement_undo)?;
+        }
+        result
+    }
+
+    fn execute_transaction_control(&mut self, control: TransactionControl) -> Result<ReplOutput> {
+        let message = match control {
+            TransactionControl::Begin {
+                read_only,
+                snapshot,
+            } => {
+                if self.session.is_some() {
+                    bail!("a transaction is already in progress");
+                }
+                let txn = match (read_only, snapshot) {
+                    (true, _) => self.begin_read_only_transaction(),
+                    (false, true) => self.begin_snapshot_transaction()?,
+                    (false, false) => self.begin_transaction()?,
+                };
+                if read_only && snapshot {
+                    self.txn_manager.take_snapshot(&txn);
+                }
+                self.session = Some(SessionTransaction {
+                    txn,
+                    savepoints: Vec::new(),
+                });
+                "BEGIN"
+            }
+            TransactionControl::Commit => {
+                let session = self
+                    .session
+                    .take()
+                    .ok_or_else(|| anyhow!("no transaction is in progress"))?;
+                self.commit_transaction(&session.txn)?;
+                "COMMIT"
+            }
+            TransactionControl::Rollback => {
+                let session = self
+                    .session
+                    .take()
+                    .ok_or_else(|| anyhow!("no transaction is in progress"))?;
+                let txn_id = session.txn.lock().txn_id;
+                let written = self.tables_written_after(txn_id, 0)?;
+                self.abort_transaction(&session.txn)?;
+                let replaced = self.rebuild_indexes(&written)?;
+                self.free_pages(replaced)?;
+                "ROLLBACK"
+            }
+            TransactionControl::Savepoint(name) => {
+                let session = self
+                    .session
+                    .as_mut()
+                    .ok_or_else(|| anyhow!("SAVEPOINT can only be used in transaction blocks"))?;
+                let (lsn, txn_id) = {
+                    let txn = session.txn.lock();
+                    (txn.last_lsn.unwrap_or_default(), txn.txn_id)
+                };
+                let undo_depth = self.catalog_undo.get(&txn_id).map_or(0, Vec::len);
+                session.savepoints.push((name, lsn, undo_depth));
+                "SAVEPOINT"
+            }
+            TransactionControl::RollbackToSavepoint(name) => {
+                let session = self.session.as_mut().ok_or_else(|| {
+                    anyhow!("ROLLBACK TO SAVEPOINT can only be used in transaction blocks")
+                })?;
+                let position = find_savepoint(&session.savepoints, &name)?;
+                // the savepoint survives; any created after it are discarded
+                session.savepoints.truncate(position + 1);
+                let (_, lsn, undo_depth) = session.savepoints[position];
+                let txn = Arc::clone(&session.txn);
+                self.rollback_session_to(&txn, lsn, undo_depth)?;
+                "ROLLBACK"
+            }
+            TransactionControl::ReleaseSavepoint(name) => {
+                let session = self.session.as_mut().ok_or_else(|| {
+                    anyhow!("RELEASE SAVEPOINT can only be used in transaction blocks")
+                })?;
+                let position = find_savepoint(&session.savepoints, &name)?;
+                session.savepoints.truncate(position);
+                "RELEASE"
+            }
+        };
+        Ok(ReplOutput::Message(message.to_string()))
+    }
+
+    /// Undoes `txn`'s changes logged after `lsn` and its catalog changes
+    /// after the first `undo_depth`.
+    fn rollback_session_to(
+        &mut self,
+        txn: &wal::TransactionHandle,
+        lsn: wal::Lsn,
+        undo_depth: usize,
+    ) -> Result<()> {
+        let txn_id = txn.lock().txn_id;
+        let restored = self.undo_catalog_changes(txn_id, undo_depth)?;
+        self.recovery
+            .rollback_to_lsn(&self.buffer_pool, txn, lsn)
+            .context("rollback to savepoint")?;
+        self.reattach_restored_tables(txn_id, &restored)?;
+        self.settle_row_counts(txn, TableHeap::invalidate_row_count)?;
+        let written = self.tables_written_after(txn_id, lsn)?;
+        let mut replaced = self.rebuild_indexes(&written)?;
+        {
+            let mut catalog = self.catalog.write();
+            for table in catalog.temp_tables_mut(txn_id) {
+                if table
+                    .heap
+                    .written_after(txn_id, lsn)
+                    .map_err(|err| anyhow!(err))?
+                {
+                    replaced.extend(table.rebuild_indexes().map_err(|err| anyhow!(err))?);
+                }
+            }
+        }
+        // a table the rest of the rollback puts back may still use them
+        self.pending_frees
+            .entry(txn_id)
+            .or_default()
+            .extend(replaced);
+        Ok(())
+    }
+
+    fn catalog_undo_depth(&self, txn: &wal::TransactionHandle) -> usize {
+        self.catalog_undo
+            .get(&txn.lock().txn_id)
+            .map_or(0, Vec::len)
+    }
+
+    /// Records the catalog entries `plan` may change, so that rolling back
+    /// the current transaction can put them back. Temporary tables are left
+    /// out; they end with the transaction anyway.
+    fn record_catalog_undo(&mut self, plan: &LogicalPlan) {
+        let Some(txn_id) = wal::current_txn_id() else {
+            return;
+        };
+        let (table_names, sequence_name, privileges): (Vec<&str>, Option<&str>, bool) = match plan {
+            LogicalPlan::CreateTable {
+                table_name,
+                temporary: false,
+                ..
+            }
+            | LogicalPlan::AlterTableAddColumn { table_name, .. }
+            | LogicalPlan::AlterTableAlterColumnType { table_name, .. }
+            | LogicalPlan::AlterTableAddCheck { table_name, .. }
+            | LogicalPlan::CreateIndex { table_name, .. } => (vec![table_name], None, false),
+            LogicalPlan::DropTable { table_name, .. }
+            | LogicalPlan::AlterTableRenameColumn { table_name, .. }
+            | LogicalPlan::AlterTableDropColumn { table_name, .. } => {
+                (vec![table_name], None, true)
+            }
+            LogicalPlan::AlterTableRename {
+                table_name,
+                new_table_name,
+            } => (vec![table_name, new_table_name], None, true),
+            LogicalPlan::CreateSequence { sequence_name, .. }
+            | LogicalPlan::DropSequence { sequence_name, .. } => {
+                (Vec::new(), Some(sequence_name), false)
+            }
+            LogicalPlan::Grant { .. } | LogicalPlan::Revoke { .. } => (Vec::new(), None, true),
+            _ => return,
+        };
+        let catalog = self.catalog.read();
+        let undo = self.catalog_undo.entry(txn_id).or_default();
+        for name in table_names {
+            if catalog.is_temp_table(name) {
+                continue;
+            }
+            undo.push(CatalogUndo::Table {
+                name: name.to_string(),
+                before: catalog
+                    .tables()
+                    .find(|table| table.name.eq_ignore_ascii_case(name))
+                    .cloned(),
+            });
+        }
+        if let Some(name) = sequence_name {
+            undo.push(CatalogUndo::Sequence {
+                name: name.to_string(),
+                before: catalog
+                    .sequences()
+                    .definitions()
+                    .into_iter()
+                    .find(|def| def.name.eq_ignore_ascii_case(name)),
+            });
+        }
+        if privileges {
+            undo.push(CatalogUndo::Privileges(catalog.privileges().clone()));
+        }
+    }
+
+    /// Puts back, newest first, the catalog entries `txn_id` changed after
+    /// its first `keep` recorded changes, and returns the names of the
+    /// tables put back. Runs before the log undoes the rows, while the pages
+    /// of the entries it replaces can still be read; those are freed with
+    /// the transaction.
+    fn undo_catalog_changes(&mut self, txn_id: wal::TxnId, keep: usize) -> Result<Vec<String>> {
+        let undo = match self.catalog_undo.get_mut(&txn_id) {
+            Some(undo) if undo.len() > keep => undo.split_off(keep),
+            _ => return Ok(Vec::new()),
+        };
+        let mut catalog = self.catalog.write();
+        let pending = self.pending_frees.entry(txn_id).or_default();
+        let mut restored = Vec::new();
+        for change in undo.into_iter().rev() {
+            match change {
+                CatalogUndo::Table { name, before } => {
+                    if let Some(current) = catalog
+                        .tables()
+                        .find(|table| table.name.eq_ignore_ascii_case(&name))
+                    {
+                        for page_id in current.page_ids().map_err(|err| anyhow!(err))? {
+                            if !pending.contains(&page_id) {
+                                pending.push(page_id);
+                            }
+                        }
+                    }
+                    if before.is_some() {
+                        restored.push(name.clone());
+                    }
+                    catalog.restore_table(&name, before);
+                }
+                CatalogUndo::Sequence { name, before } => {
+                    let _ = catalog.sequences().drop_sequence(&name);
+                    if let Some(def) = before {
+                        catalog
+                            .sequences()
+                            .create(def)
+                            .map_err(|err| anyhow!(err))?;
+                    }
+                }
+                CatalogUndo::Privileges(before) => *catalog.privileges_mut() = before,
+            }
+        }
+        self.persist_catalog(&catalog)?;
+        Ok(restored)
+    }
+
+    /// Rebuilds the indexes of the tables [`Self::undo_catalog_changes`] put
+    /// back, once the log has undone their rows, and takes their pages off
+    /// the ones to free.
+    fn reattach_restored_tables(&mut self, txn_id: wal::TxnId, names: &[String]) -> Result<()> {
+        let mut kept = HashSet::new();
+        let mut replaced = Vec::new();
+        {
+            let mut catalog = self.catalog.write();
+            for name in names {
+                if let Some(table) = catalog.table_mut(name) {
+                    replaced.extend(table.rebuild_indexes().map_err(|err| anyhow!(err))?);
+                    kept.extend(table.page_ids().map_err(|err| anyhow!(err))?);
+                }
+            }
+        }
+        let pending = self.pending_frees.entry(txn_id).or_default();
+        for page_id in replaced {
+            if !pending.contains(&page_id) {
+                pending.push(page_id);
+            }
+        }
+        pending.retain(|page_id| !kept.contains(page_id));
+        Ok(())
+    }
+
+    /// Names of the tables `txn_id` changed rows of after its log reached
+    /// `lsn`, whose indexes a rollback to `lsn` leaves stale.
+    fn tables_written_after(&self, txn_id: wal::TxnId, lsn: wal::Lsn) -> Result<Vec<String>> {
+        let mut names = Vec::new();
+        for table in self.catalog.read().tables() {
+            if table
+                .heap
+                .written_after(txn_id, lsn)
+                .map_err(|err| anyhow!(err))?
+            {
+                names.push(table.name.clone());
+            }
+        }
+        Ok(names)
+    }
+
+    /// Index pages are not logged, so the indexes of `table_names` are
+    /// rebuilt from the heap after their changes have been undone. Returns
+    /// the pages of the replaced trees, for the caller to free.
+    fn rebuild_indexes(&mut self, table_names: &[String]) -> Result<Vec<PageId>> {
+        let mut catalog = self.catalog.write();
+        let mut replaced = Vec::new();
+        for table_name in table_names {
+            if let Some(table) = catalog.table_mut(table_name) {
+                replaced.extend(table.rebuild_indexes().map_err(|err| anyhow!(err))?);
+            }
+        }
+        Ok(replaced)
+    }
+
     pub fn list_tables(&self) -> Vec<String> {
-        self.catalog.table_names()
+        self.catalog.read().table_names()
     }
 
-    fn execute_plan(&mut self, plan: LogicalPlan) -> Result<ReplOutput> {
+    fn execute_plan(&mut self, mut plan: LogicalPlan) -> Result<ReplOutput> {
+        if let Some(token) = &self.cancellation {
+            token.check().map_err(|err| anyhow!(err))?;
+        }
+        if wal::current_txn_read_only() && !plan.is_read_only() {
+            bail!("cannot modify data in a read-only transaction");
+        }
+        if let Some(role) = &self.role {
+            PhysicalPlanner::new(&self.catalog.read())
+                .check_privileges(&plan, role)
+                .map_err(|err| anyhow!(err))?;
+        }
+        self.physical_planner(&self.catalog.read())
+            .run_subqueries(&mut plan)
+            .map_err(|err| anyhow!(err))?;
+        if self.strict_mode && plan.has_unordered_limit() {
+            self.warnings
+                .push("LIMIT without ORDER BY returns an arbitrary subset of rows".to_string());
+        }
+        self.record_catalog_undo(&plan);
         match plan {
             LogicalPlan::CreateTable {
                 table_name,
                 columns,
+                constraints,
+                checks,
+                if_not_exists,
+                temporary,
+            } => self.create_table(
+                &table_name,
+                &columns,
+                &constraints,
+                checks,
                 if_not_exists,
-            } => self.create_table(&table_name, &columns, if_not_exists),
+                temporary,
+            ),
             LogicalPlan::DropTable {
                 table_name,
                 if_exists,
             } => self.drop_table(&table_name, if_exists),
+            LogicalPlan::Analyze { table_name } => self.analyze_table(&table_name),
             LogicalPlan::AlterTableRename {
                 table_name,
                 new_table_name,
@@ -166,35 +1093,99 @@ impl Engine {
                 table_name,
                 column_name,
             } => self.alter_table_drop_column(&table_name, &column_name),
+            LogicalPlan::AlterTableAlterColumnType {
+                table_name,
+                column_name,
+                data_type,
+                using,
+            } => self.alter_table_alter_column_type(
+                &table_name,
+                &column_name,
+                data_type,
+                using.as_ref(),
+            ),
+            LogicalPlan::AlterTableAddCheck { table_name, check } => {
+                self.alter_table_add_check(&table_name, check)
+            }
             LogicalPlan::CreateIndex {
                 table_name,
                 index_name,
-                column_name,
+                column_names,
                 if_not_exists,
                 unique,
             } => self.create_index(
                 &table_name,
                 &index_name,
-                &column_name,
+                &column_names,
                 if_not_exists,
                 unique,
             ),
+            LogicalPlan::CreateSequence {
+                sequence_name,
+                start,
+                increment,
+                if_not_exists,
+            } => self.create_sequence(&sequence_name, start, increment, if_not_exists),
+            LogicalPlan::DropSequence {
+                sequence_name,
+                if_exists,
+            } => self.drop_sequence(&sequence_name, if_exists),
+            LogicalPlan::Grant {
+                table_names,
+                privileges,
+                roles,
+            } => self.change_privileges(&table_names, &privileges, &roles, true),
+            LogicalPlan::Revoke {
+                table_names,
+                privileges,
+                roles,
+            } => self.change_privileges(&table_names, &privileges, &roles, false),
             LogicalPlan::Insert {
                 table_name,
                 columns,
                 values,
+                source,
+                on_conflict,
+                returning,
                 ..
-            } => self.insert_rows(&table_name, columns.as_deref(), &values),
-            LogicalPlan::Delete {
-                table_name, filter, ..
-            } => self.delete_rows(&table_name, filter.as_ref()),
+            } => self.insert_rows(
+                &table_name,
+                columns.as_deref(),
+                &values,
+                source.as_deref(),
+                on_conflict,
+                returning.as_ref(),
+            ),
+            LogicalPlan::Delete { .. } => self.execute_delete(plan),
+            LogicalPlan::Copy {
+                table_name,
+                columns,
+                direction: CopyDirection::To,
+                path,
+                options,
+            } => self.copy_to(&table_name, columns.as_deref(), &path, &options),
+            LogicalPlan::Copy {
+                table_name,
+                columns,
+                direction: CopyDirection::From,
+                path,
+                options,
+            } => self.copy_from(&table_name, columns.as_deref(), &path, &options),
+            LogicalPlan::Set {
+                setting: Setting::WorkMem(bytes),
+            } => {
+                self.set_work_mem(bytes);
+                Ok(ReplOutput::Message("SET".to_string()))
+            }
             LogicalPlan::Update { .. } => self.execute_update(plan),
+            LogicalPlan::Explain { input } => self.explain(&input),
             _ => self.execute_query(plan),
         }
     }
 
     pub fn table_schema(&self, table_name: &str) -> Option<Schema> {
         self.catalog
+            .read()
             .table(table_name)
             .map(|table| table.schema.visible_schema())
     }
@@ -203,10 +1194,15 @@ impl Engine {
         &mut self,
         table_name: &str,
         columns: &[ColumnDef],
+        constraints: &[UniqueConstraint],
+        checks: Vec<CheckConstraint>,
         if_not_exists: bool,
+        temporary: bool,
     ) -> Result<ReplOutput> {
-        if self.catalog.table(table_name).is_some() {
+        let mut catalog = self.catalog.write();
+        if let Some(existing) = catalog.table(table_name) {
             if if_not_exists {
+                check_schema_drift(existing, columns)?;
                 return Ok(ReplOutput::Message("OK".to_string()));
             }
             bail!("table {} already exists", table_name);
@@ -228,6 +1224,7 @@ impl Engine {
         let heap = TableHeap::create(self.buffer_pool.clone())
             .map_err(|err| anyhow!(err))
             .context("create table heap")?;
+        heap.set_row_locks(self.row_locks);
         let mut table =
             TableInfo::with_columns(table_name.to_string(), schema, columns.to_vec(), heap);
         for column in columns {
@@ -242,31 +1239,53 @@ impl Engine {
                     .map_err(|err| anyhow!(err))?;
             }
         }
-        self.catalog.register_table_info(table);
-        self.persist_catalog()?;
-        Ok(ReplOutput::Message("OK".to_string()))
-    }
+        for constraint in constraints {
+            let index_name = constraint.name.clone().unwrap_or_else(|| {
+                let suffix = if constraint.is_primary { "pk" } else { "uk" };
+                format!("{}_{}_{}", table_name, constraint.columns.join("_"), suffix)
+            });
+            let column_names: Vec<&str> = constraint.columns.iter().map(|c| c.as_str()).collect();
+            table
+                .create_composite_index(index_name, column_names, true, constraint.is_primary)
+                .map_err(|err| anyhow!(err))?;
+        }
+        for check in checks {
+            table.add_check(check).map_err(|err| anyhow!(err))?;
+        }
+        if temporary {
+            let txn_id = wal::current_txn_id()
+                .ok_or_else(|| anyhow!("temporary tables need a transaction"))?;
+            catalog.register_temp_table(txn_id, table);
+            return Ok(ReplOutput::Message("OK".to_string()));
+        }
+        catalog.register_table_info(table);
+        self.persist_catalog(&catalog)?;
+        Ok(ReplOutput::Message("OK".to_string()))
+    }
 
     fn create_index(
         &mut self,
         table_name: &str,
         index_name: &str,
-        column_name: &str,
+        column_names: &[String],
         if_not_exists: bool,
         unique: bool,
     ) -> Result<ReplOutput> {
-        let table = match self.catalog.table(table_name) {
+        let mut catalog = self.catalog.write();
+        let table = match catalog.table(table_name) {
             Some(t) => t,
             None => bail!("table {} does not exist", table_name),
         };
 
-        let column_exists = table.columns.iter().any(|c| c.name == column_name);
-        if !column_exists {
-            bail!(
-                "column {} does not exist in table {}",
-                column_name,
-                table_name
-            );
+        for column_name in column_names {
+            let column_exists = table.columns.iter().any(|c| c.name == *column_name);
+            if !column_exists {
+                bail!(
+                    "column {} does not exist in table {}",
+                    column_name,
+                    table_name
+                );
+            }
         }
 
         let index_exists = table.indexes.iter().any(|i| i.name == index_name);
@@ -281,21 +1300,77 @@ impl Engine {
             );
         }
 
-        let table_mut = self
-            .catalog
+        let table_mut = catalog
             .table_mut(table_name)
             .ok_or_else(|| anyhow!("table {} does not exist", table_name))?;
         table_mut
-            .create_index(index_name, column_name, unique, false)
+            .create_composite_index(
+                index_name,
+                column_names.iter().map(String::as_str).collect(),
+                unique,
+                false,
+            )
             .map_err(|err| anyhow!(err))?;
-        self.persist_catalog()?;
+        self.persist_catalog(&catalog)?;
         Ok(ReplOutput::Message("OK".to_string()))
     }
 
     fn drop_table(&mut self, table_name: &str, if_exists: bool) -> Result<ReplOutput> {
-        match self.catalog.drop_table(table_name) {
+        let mut catalog = self.catalog.write();
+        let page_ids = match catalog.table(table_name) {
+            Some(table) => table.page_ids().map_err(|err| anyhow!(err))?,
+            None => Vec::new(),
+        };
+        let temporary = catalog.is_temp_table(table_name);
+        match catalog.drop_table(table_name) {
+            Ok(()) => {
+                if !temporary {
+                    self.persist_catalog(&catalog)?;
+                }
+                drop(catalog);
+                match wal::current_txn_id() {
+                    Some(txn_id) => self
+                        .pending_frees
+                        .entry(txn_id)
+                        .or_default()
+                        .extend(page_ids),
+                    None => self.free_pages(page_ids)?,
+                }
+                Ok(ReplOutput::Message("OK".to_string()))
+            }
+            Err(_) if if_exists => Ok(ReplOutput::Message("OK".to_string())),
+            Err(err) => Err(anyhow!(err)),
+        }
+    }
+
+    fn create_sequence(
+        &mut self,
+        sequence_name: &str,
+        start: i64,
+        increment: i64,
+        if_not_exists: bool,
+    ) -> Result<ReplOutput> {
+        let catalog = self.catalog.write();
+        if if_not_exists && catalog.sequences().contains(sequence_name) {
+            return Ok(ReplOutput::Message("OK".to_string()));
+        }
+        catalog
+            .sequences()
+            .create(SequenceDef {
+                name: sequence_name.to_string(),
+                next_value: start,
+                increment,
+            })
+            .map_err(|err| anyhow!(err))?;
+        self.persist_catalog(&catalog)?;
+        Ok(ReplOutput::Message("OK".to_string()))
+    }
+
+    fn drop_sequence(&mut self, sequence_name: &str, if_exists: bool) -> Result<ReplOutput> {
+        let catalog = self.catalog.write();
+        match catalog.sequences().drop_sequence(sequence_name) {
             Ok(()) => {
-                self.persist_catalog()?;
+                self.persist_catalog(&catalog)?;
                 Ok(ReplOutput::Message("OK".to_string()))
             }
             Err(_) if if_exists => Ok(ReplOutput::Message("OK".to_string())),
@@ -303,11 +1378,80 @@ impl Engine {
         }
     }
 
+    fn change_privileges(
+        &mut self,
+        table_names: &[String],
+        privileges: &[PrivilegeSpec],
+        roles: &[String],
+        grant: bool,
+    ) -> Result<ReplOutput> {
+        let mut catalog = self.catalog.write();
+        let mut grants = Vec::new();
+        for table_name in table_names {
+            let table = catalog
+                .table(table_name)
+                .ok_or_else(|| anyhow!("table {} not found", table_name))?;
+            for spec in privileges {
+                let columns = spec
+                    .columns
+                    .as_ref()
+                    .map(|columns| {
+                        columns
+                            .iter()
+                            .map(|column| {
+                                table
+                                    .schema
+                                    .fields
+                                    .iter()
+                                    .find(|field| {
+                                        field.visible && field.name.eq_ignore_ascii_case(column)
+                                    })
+                                    .map(|field| field.name.clone())
+                                    .ok_or_else(|| {
+                                        anyhow!("column {} not found in {}", column, table_name)
+                                    })
+                            })
+                            .collect::<Result<Vec<_>>>()
+                    })
+                    .transpose()?;
+                for role in roles {
+                    grants.push(GrantDef {
+                        role: role.clone(),
+                        table: table.name.clone(),
+                        privilege: spec.privilege,
+                        columns: columns.clone(),
+                    });
+                }
+            }
+        }
+        for def in grants {
+            if grant {
+                catalog.privileges_mut().grant(def);
+            } else {
+                catalog.privileges_mut().revoke(&def);
+            }
+        }
+        self.persist_catalog(&catalog)?;
+        Ok(ReplOutput::Message(
+            if grant { "GRANT" } else { "REVOKE" }.to_string(),
+        ))
+    }
+
+    fn analyze_table(&mut self, table_name: &str) -> Result<ReplOutput> {
+        let catalog = self.catalog.read();
+        let table = catalog
+            .table(table_name)
+            .ok_or_else(|| anyhow!("table {} not found", table_name))?;
+        table.analyze().map_err(|err| anyhow!(err))?;
+        Ok(ReplOutput::Message("ANALYZE".to_string()))
+    }
+
     fn alter_table_rename(&mut self, table_name: &str, new_table_name: &str) -> Result<ReplOutput> {
-        self.catalog
+        let mut catalog = self.catalog.write();
+        catalog
             .rename_table(table_name, new_table_name)
             .map_err(|err| anyhow!(err))?;
-        self.persist_catalog()?;
+        self.persist_catalog(&catalog)?;
         Ok(ReplOutput::Message("OK".to_string()))
     }
 
@@ -317,10 +1461,11 @@ impl Engine {
         old_column_name: &str,
         new_column_name: &str,
     ) -> Result<ReplOutput> {
-        self.catalog
+        let mut catalog = self.catalog.write();
+        catalog
             .rename_column(table_name, old_column_name, new_column_name)
             .map_err(|err| anyhow!(err))?;
-        self.persist_catalog()?;
+        self.persist_catalog(&catalog)?;
         Ok(ReplOutput::Message("OK".to_string()))
     }
 
@@ -329,10 +1474,11 @@ impl Engine {
         table_name: &str,
         column_def: &ColumnDef,
     ) -> Result<ReplOutput> {
-        self.catalog
+        let mut catalog = self.catalog.write();
+        catalog
             .add_column(table_name, column_def.clone())
             .map_err(|err| anyhow!(err))?;
-        self.persist_catalog()?;
+        self.persist_catalog(&catalog)?;
         Ok(ReplOutput::Message("OK".to_string()))
     }
 
@@ -341,10 +1487,48 @@ impl Engine {
         table_name: &str,
         column_name: &str,
     ) -> Result<ReplOutput> {
-        self.catalog
+        let mut catalog = self.catalog.write();
+        catalog
             .drop_column(table_name, column_name)
             .map_err(|err| anyhow!(err))?;
-        self.persist_catalog()?;
+        self.persist_catalog(&catalog)?;
+        Ok(ReplOutput::Message("OK".to_string()))
+    }
+
+    fn alter_table_alter_column_type(
+        &mut self,
+        table_name: &str,
+        column_name: &str,
+        data_type: DataType,
+        using: Option<&Expr>,
+    ) -> Result<ReplOutput> {
+        let mut catalog = self.catalog.write();
+        let replaced = catalog
+            .alter_column_type(table_name, column_name, data_type, using)
+            .map_err(|err| anyhow!(err))?;
+        self.persist_catalog(&catalog)?;
+        drop(catalog);
+        match wal::current_txn_id() {
+            Some(txn_id) => self
+                .pending_frees
+                .entry(txn_id)
+                .or_default()
+                .extend(replaced),
+            None => self.free_pages(replaced)?,
+        }
+        Ok(ReplOutput::Message("OK".to_string()))
+    }
+
+    fn alter_table_add_check(
+        &mut self,
+        table_name: &str,
+        check: CheckConstraint,
+    ) -> Result<ReplOutput> {
+        let mut catalog = self.catalog.write();
+        catalog
+            .add_check(table_name, check)
+            .map_err(|err| anyhow!(err))?;
+        self.persist_catalog(&catalog)?;
         Ok(ReplOutput::Message("OK".to_string()))
     }
 
@@ -353,80 +1537,273 @@ impl Engine {
         table_name: &str,
         columns: Option<&[String]>,
         values: &[Vec<Expr>],
+        source: Option<&LogicalPlan>,
+        on_conflict: InsertConflict,
+        returning: Option<&Returning>,
     ) -> Result<ReplOutput> {
-        let table = self
-            .catalog
+        let catalog = self.catalog.read();
+        let table = catalog
             .table(table_name)
             .ok_or_else(|| anyhow!("table {} not found", table_name))?;
         let schema = &table.schema;
         let column_indices = resolve_column_indices(schema, columns)?;
-
-        let mut inserted = 0;
-        for row in values {
-            if row.len() != column_indices.len() {
-                bail!(
-                    "expected {} values, got {}",
-                    column_indices.len(),
-                    row.len()
-                );
-            }
-            let mut values = vec![Value::Null; schema.fields.len()];
-            for (expr, column_index) in row.iter().zip(column_indices.iter()) {
-                let value = evaluate_insert_expr(expr)?;
-                values[*column_index] = value;
+        let default_value =
+            |index: usize| evaluate_insert_expr(&table.default_expr(&schema.fields[index].name));
+        // INSERT ... SELECT reads every source row before writing any, so a
+        // query over the target table does not see its own inserts
+        let rows = match source {
+            Some(source) => {
+                let root = self
+                    .physical_planner(&catalog)
+                    .plan(source)
+                    .map_err(|err| anyhow!(err))?;
+                let mut executor = self.executor(root);
+                executor
+                    .execute()
+                    .map_err(|err| anyhow!(err))?
+                    .into_iter()
+                    .map(|tuple| tuple.values().to_vec())
+                    .collect::<Vec<_>>()
             }
-            for (idx, field) in schema.fields.iter().enumerate() {
-                if !field.visible {
-                    continue;
-                }
-                if values[idx].is_null() && !field.nullable {
-                    let column_def = table.columns.iter().find(|c| c.name == field.name);
-                    if column_def.map(|c| c.auto_increment).unwrap_or(false) {
-                        continue;
+            None => values
+                .iter()
+                .map(|row| {
+                    if row.len() != column_indices.len() {
+                        bail!(
+                            "expected {} values, got {}",
+                            column_indices.len(),
+                            row.len()
+                        );
                     }
-                    bail!("missing value for non-nullable column {}", field.name);
-                }
+                    row.iter()
+                        .zip(&column_indices)
+                        .map(|(expr, &index)| match expr {
+                            Expr::Default => default_value(index),
+                            expr => evaluate_insert_expr(expr),
+                        })
+                        .collect()
+                })
+                .collect::<Result<Vec<Vec<Value>>>>()?,
+        };
+
+        let inserted = insert_value_rows(table, &column_indices, rows, on_conflict)?;
+        match returning {
+            Some(returning) => {
+                let (schema, rows) = self
+                    .physical_planner(&catalog)
+                    .project_returning(table_name, returning, &inserted)
+                    .map_err(|err| anyhow!(err))?;
+                Ok(ReplOutput::Rows { schema, rows })
             }
-            let tuple = Tuple::new(values);
-            table.insert_tuple(&tuple).map_err(|err| anyhow!(err))?;
-            inserted += 1;
+            None => Ok(ReplOutput::Message(format!("INSERT 0 {}", inserted.len()))),
         }
+    }
+
+    /// Writes the rows of `table_name` to a CSV file.
+    fn copy_to(
+        &mut self,
+        table_name: &str,
+        columns: Option<&[String]>,
+        path: &str,
+        options: &CopyOptions,
+    ) -> Result<ReplOutput> {
+        let catalog = self.catalog.read();
+        let table = catalog
+            .table(table_name)
+            .ok_or_else(|| anyhow!("table {} not found", table_name))?;
+        let column_indices = resolve_column_indices(&table.schema, columns)?;
+        let scan = LogicalPlan::Scan {
+            table_name: table_name.to_string(),
+            alias: None,
+            schema: None,
+            sample: None,
+        };
+        let root = self
+            .physical_planner(&catalog)
+            .plan(&scan)
+            .map_err(|err| anyhow!(err))?;
+        let rows = self.executor(root).execute().map_err(|err| anyhow!(err))?;
 
-        Ok(ReplOutput::Message(format!("INSERT 0 {}", inserted)))
+        let mut out = String::new();
+        if options.header {
+            let names = column_indices
+                .iter()
+                .map(|&index| Some(&table.schema.fields[index].name));
+            csv::write_record(names, options, &mut out);
+        }
+        for row in &rows {
+            let fields = column_indices
+                .iter()
+                .map(|&index| csv::format_value(&row.values()[index]));
+            csv::write_record(fields, options, &mut out);
+        }
+        fs::write(path, out).with_context(|| format!("write {}", path))?;
+        Ok(ReplOutput::Message(format!("COPY {}", rows.len())))
     }
 
-    fn delete_rows(&mut self, table_name: &str, filter: Option<&Expr>) -> Result<ReplOutput> {
-        let table = self
-            .catalog
+    /// Inserts the rows of a CSV file into `table_name`.
+    fn copy_from(
+        &mut self,
+        table_name: &str,
+        columns: Option<&[String]>,
+        path: &str,
+        options: &CopyOptions,
+    ) -> Result<ReplOutput> {
+        let input = fs::read_to_string(path).with_context(|| format!("read {}", path))?;
+        let catalog = self.catalog.read();
+        let table = catalog
             .table(table_name)
             .ok_or_else(|| anyhow!("table {} not found", table_name))?;
-        let deleted = table.delete_tuples(filter).map_err(|err| anyhow!(err))?;
+        let column_indices = resolve_column_indices(&table.schema, columns)?;
+        let mut records = csv::parse_records(&input, options).map_err(|err| anyhow!(err))?;
+        if options.header && !records.is_empty() {
+            records.remove(0);
+        }
+        let rows = records
+            .iter()
+            .enumerate()
+            .map(|(line, record)| {
+                if record.len() != column_indices.len() {
+                    bail!(
+                        "record {}: expected {} fields, got {}",
+                        line + 1 + usize::from(options.header),
+                        column_indices.len(),
+                        record.len()
+                    );
+                }
+                record
+                    .iter()
+                    .zip(&column_indices)
+                    .map(|(field, &index)| {
+                        csv::parse_value(field.as_deref(), &table.schema.fields[index])
+                            .map_err(|err| anyhow!(err))
+                    })
+                    .collect()
+            })
+            .collect::<Result<Vec<Vec<Value>>>>()?;
+        let inserted = insert_value_rows(table, &column_indices, rows, InsertConflict::Abort)?;
+        Ok(ReplOutput::Message(format!("COPY {}", inserted.len())))
+    }
+
+    fn execute_delete(&mut self, plan: LogicalPlan) -> Result<ReplOutput> {
+        let root = self
+            .physical_planner(&self.catalog.read())
+            .plan(&plan)
+            .map_err(|err| anyhow!(err))?;
+        let mut executor = self.executor(root);
+        let rows = executor.execute().map_err(|err| anyhow!(err))?;
+        let deleted = match rows.first().and_then(|row| row.get(0)) {
+            Some(Value::Integer(deleted)) => *deleted,
+            _ => 0,
+        };
         Ok(ReplOutput::Message(format!("DELETE {}", deleted)))
     }
 
     fn execute_update(&mut self, plan: LogicalPlan) -> Result<ReplOutput> {
-        let root = PhysicalPlanner::new(&self.catalog)
+        let root = self
+            .physical_planner(&self.catalog.read())
             .plan(&plan)
             .map_err(|err| anyhow!(err))?;
-        let mut executor = Executor::new(root);
+        let mut executor = self.executor(root);
         let rows = executor.execute().map_err(|err| anyhow!(err))?;
         Ok(ReplOutput::Message(format!("UPDATE {}", rows.len())))
     }
 
     fn execute_query(&mut self, plan: LogicalPlan) -> Result<ReplOutput> {
-        let schema = plan.schema();
-        let root = PhysicalPlanner::new(&self.catalog)
-            .plan(&plan)
+        let (root, schema) = self
+            .physical_planner(&self.catalog.read())
+            .plan_with_schema(&plan)
             .map_err(|err| anyhow!(err))?;
-        let mut executor = Executor::new(root);
+        let mut executor = self.executor(root);
         let rows = executor.execute().map_err(|err| anyhow!(err))?;
         Ok(ReplOutput::Rows { schema, rows })
     }
 
-    fn _persist_catalog(&self, path: &Path) -> Result<()> {
+    fn explain(&self, plan: &LogicalPlan) -> Result<ReplOutput> {
+        let catalog = self.catalog.read();
+        let planner = self.physical_planner(&catalog).with_estimates();
+        // INSERT runs outside the operator tree; its rows come from VALUES
+        // or from a planned query
+        let lines = match plan {
+            LogicalPlan::Insert {
+                table_name,
+                values,
+                source,
+                on_conflict,
+                ..
+            } => {
+                let input = match source {
+                    Some(source) => {
+                        let root = planner.plan(source).map_err(|err| anyhow!(err))?;
+                        explain_physical(root.as_ref())
+                    }
+                    None => vec![format!("Values: {} rows", values.len())],
+                };
+                std::iter::once(format!("{}: {}", on_conflict.verb(), table_name))
+                    .chain(input.into_iter().map(|line| format!("  {line}")))
+                    .collect()
+            }
+            _ => {
+                let root = planner.plan(plan).map_err(|err| anyhow!(err))?;
+                explain_physical(root.as_ref())
+            }
+        };
+        let schema = Schema::new(vec![Field {
+            name: "plan".to_string(),
+            table: None,
+            data_type: DataType::Text,
+            nullable: false,
+            visible: true,
+        }]);
+        let rows = lines
+            .into_iter()
+            .map(|line| Tuple::new(vec![Value::String(line)]))
+            .collect();
+        Ok(ReplOutput::Rows { schema, rows })
+    }
+
+    /// Parses and plans `sql` and resolves it against the catalog, without
+    /// executing it or starting a transaction. Unknown tables and columns
+    /// and mistyped INSERT values are reported as errors.
+    pub fn validate(&self, sql: &str) -> Result<()> {
+        let plan = sql_to_logical_plan(sql)?;
+        let catalog = self.catalog.read();
+        let planner = self.physical_planner(&catalog);
+        if let Some(role) = &self.role {
+            planner
+                .check_privileges(&plan, role)
+                .map_err(|err| anyhow!(err))?;
+        }
+        planner.validate(&plan).map_err(|err| anyhow!(err))
+    }
+
+    fn executor(&self, root: Box<dyn PhysicalOperator>) -> Executor {
+        let executor = Executor::new(root);
+        match &self.cancellation {
+            Some(token) => executor.with_cancellation(token.clone()),
+            None => executor,
+        }
+    }
+
+    fn serialize_catalog(catalog: &Catalog) -> Result<Vec<u8>> {
         #[derive(Serialize)]
         struct SerializedCatalog {
             tables: Vec<SerializedTable>,
+            sequences: Vec<SerializedSequence>,
+            grants: Vec<SerializedGrant>,
+        }
+        #[derive(Serialize)]
+        struct SerializedGrant {
+            role: String,
+            table: String,
+            privilege: String,
+            columns: Option<Vec<String>>,
+        }
+        #[derive(Serialize)]
+        struct SerializedSequence {
+            name: String,
+            next_value: i64,
+            increment: i64,
         }
         #[derive(Serialize)]
         struct SerializedTable {
@@ -434,6 +1811,7 @@ impl Engine {
             first_page_id: u64,
             columns: Vec<SerializedColumn>,
             indexes: Vec<SerializedIndex>,
+            checks: Vec<SerializedCheck>,
         }
         #[derive(Serialize)]
         struct SerializedColumn {
@@ -444,6 +1822,8 @@ impl Engine {
             unique: bool,
             default_value: Option<SerializedDefaultValue>,
             auto_increment: bool,
+            text_format: Option<TextFormat>,
+            collation: Option<Collation>,
         }
 
         #[derive(Serialize, Clone)]
@@ -479,8 +1859,14 @@ impl Engine {
             is_primary: bool,
         }
 
+        #[derive(Serialize)]
+        struct SerializedCheck {
+            name: Option<String>,
+            sql: String,
+        }
+
         let mut tables = Vec::new();
-        for table in self.catalog.tables() {
+        for table in catalog.tables() {
             let columns: Vec<SerializedColumn> = table
                 .columns
                 .iter()
@@ -492,6 +1878,8 @@ impl Engine {
                     unique: c.unique,
                     default_value: c.default_value.as_ref().map(|v| v.clone().into()),
                     auto_increment: c.auto_increment,
+                    text_format: c.text_format,
+                    collation: c.collation,
                 })
                 .collect();
 
@@ -508,28 +1896,80 @@ impl Engine {
                 })
                 .collect();
 
+            let checks: Vec<SerializedCheck> = table
+                .checks
+                .iter()
+                .map(|check| SerializedCheck {
+                    name: check.name.clone(),
+                    sql: check.sql.clone(),
+                })
+                .collect();
+
             tables.push(SerializedTable {
                 name: table.name.clone(),
                 first_page_id,
                 columns,
                 indexes,
+                checks,
             });
         }
 
-        let catalog_data = SerializedCatalog { tables };
-        let file = File::create(path).context("create catalog file")?;
-        serde_json::to_writer_pretty(file, &catalog_data)?;
-        Ok(())
+        let sequences = catalog
+            .sequences()
+            .definitions()
+            .into_iter()
+            .map(|sequence| SerializedSequence {
+                name: sequence.name,
+                next_value: sequence.next_value,
+                increment: sequence.increment,
+            })
+            .collect();
+        let grants = catalog
+            .privileges()
+            .definitions()
+            .into_iter()
+            .map(|grant| SerializedGrant {
+                role: grant.role,
+                table: grant.table,
+                privilege: grant.privilege.to_string(),
+                columns: grant.columns,
+            })
+            .collect();
+        let catalog_data = SerializedCatalog {
+            tables,
+            sequences,
+            grants,
+        };
+        Ok(serde_json::to_vec_pretty(&catalog_data)?)
     }
 
     fn load_catalog(&mut self, path: &Path) -> Result<()> {
-        if !path.exists() {
-            return Ok(());
-        }
+        let snapshot = match self.recovery.latest_catalog()? {
+            Some(snapshot) => snapshot,
+            None if path.exists() => fs::read(path).context("read catalog file")?,
+            None => return Ok(()),
+        };
 
         #[derive(Deserialize)]
         struct SerializedCatalog {
             tables: Vec<SerializedTable>,
+            #[serde(default)]
+            sequences: Vec<SerializedSequence>,
+            #[serde(default)]
+            grants: Vec<SerializedGrant>,
+        }
+        #[derive(Deserialize)]
+        struct SerializedGrant {
+            role: String,
+            table: String,
+            privilege: String,
+            columns: Option<Vec<String>>,
+        }
+        #[derive(Deserialize)]
+        struct SerializedSequence {
+            name: String,
+            next_value: i64,
+            increment: i64,
         }
         #[derive(Deserialize)]
         struct SerializedTable {
@@ -537,6 +1977,8 @@ impl Engine {
             first_page_id: u64,
             columns: Vec<SerializedColumn>,
             indexes: Vec<SerializedIndex>,
+            #[serde(default)]
+            checks: Vec<SerializedCheck>,
         }
         #[derive(Deserialize)]
         struct SerializedColumn {
@@ -548,6 +1990,10 @@ impl Engine {
             default_value: Option<SerializedDefaultValue>,
             #[serde(default)]
             auto_increment: bool,
+            #[serde(default)]
+            text_format: Option<TextFormat>,
+            #[serde(default)]
+            collation: Option<Collation>,
         }
 
         #[derive(Deserialize, Clone)]
@@ -583,32 +2029,28 @@ impl Engine {
             is_primary: bool,
         }
 
-        let file = File::open(path).context("open catalog file")?;
+        #[derive(Deserialize)]
+        struct SerializedCheck {
+            name: Option<String>,
+            sql: String,
+        }
+
         let catalog_data: SerializedCatalog =
-            serde_json::from_reader(file).context("parse catalog")?;
+            serde_json::from_slice(&snapshot).context("parse catalog")?;
 
         for table_data in catalog_data.tables {
-            let columns: Result<Vec<ColumnDef>, _> = table_data
+            let columns: Result<Vec<ColumnDef>> = table_data
                 .columns
                 .iter()
                 .map(|c| {
-                    let data_type = match c.data_type.as_str() {
-                        "Integer" => DataType::Integer,
-                        "BigInt" => DataType::BigInt,
-                        "Text" => DataType::Text,
-                        "Boolean" => DataType::Boolean,
-                        "Real" => DataType::Real,
-                        "Timestamp" => DataType::Timestamp,
-                        "Blob" => DataType::Blob,
-                        _ => {
-                            return Err(anyhow!(
-                                "unknown data type '{}' for column '{}' in table '{}'",
-                                c.data_type,
-                                c.name,
-                                table_data.name
-                            ));
-                        }
-                    };
+                    let data_type = data_type_from_name(&c.data_type).ok_or_else(|| {
+                        anyhow!(
+                            "unknown data type '{}' for column '{}' in table '{}'",
+                            c.data_type,
+                            c.name,
+                            table_data.name
+                        )
+                    })?;
                     Ok(ColumnDef {
                         name: c.name.clone(),
                         data_type,
@@ -617,6 +2059,8 @@ impl Engine {
                         unique: c.unique,
                         default_value: c.default_value.as_ref().map(|v| (*v).clone().into()),
                         auto_increment: c.auto_increment,
+                        text_format: c.text_format,
+                        collation: c.collation,
                     })
                 })
                 .collect();
@@ -637,6 +2081,7 @@ impl Engine {
 
             let heap = TableHeap::load(table_data.first_page_id, self.buffer_pool.clone())
                 .map_err(|e| anyhow!("failed to load table heap: {}", e))?;
+            heap.set_row_locks(self.row_locks);
 
             let mut table = TableInfo::with_columns(table_data.name.clone(), schema, columns, heap);
 
@@ -654,23 +2099,208 @@ impl Engine {
                     .map_err(|e| anyhow!(e))?;
             }
 
-            self.catalog.register_table_info(table);
+            for check in table_data.checks {
+                let expr = sql_to_expr(&check.sql).with_context(|| {
+                    format!(
+                        "failed to plan CHECK ({}) on table '{}'",
+                        check.sql, table_data.name
+                    )
+                })?;
+                table.checks.push(CheckConstraint {
+                    name: check.name,
+                    sql: check.sql,
+                    expr,
+                });
+            }
+
+            self.catalog.write().register_table_info(table);
+        }
+
+        for sequence in catalog_data.sequences {
+            self.catalog
+                .read()
+                .sequences()
+                .create(SequenceDef {
+                    name: sequence.name,
+                    next_value: sequence.next_value,
+                    increment: sequence.increment,
+                })
+                .map_err(|err| anyhow!(err))?;
+        }
+
+        for grant in catalog_data.grants {
+            let privilege = Privilege::from_name(&grant.privilege).ok_or_else(|| {
+                anyhow!(
+                    "unknown privilege '{}' granted on table '{}'",
+                    grant.privilege,
+                    grant.table
+                )
+            })?;
+            self.catalog.write().privileges_mut().grant(GrantDef {
+                role: grant.role,
+                table: grant.table,
+                privilege,
+                columns: grant.columns,
+            });
         }
 
         Ok(())
     }
 }
 
+/// The transaction a closure passed to [`Engine::transaction`] runs in.
+pub struct Transaction<'a> {
+    engine: &'a mut Engine,
+    handle: wal::TransactionHandle,
+}
+
+impl Transaction<'_> {
+    /// Runs a statement that returns rows, such as `SELECT`, and returns
+    /// their values.
+    pub fn query(&mut self, sql: &str) -> Result<Vec<Vec<Value>>> {
+        match self.engine.execute_sql_in_transaction(sql, &self.handle)? {
+            ReplOutput::Rows { rows, .. } => {
+                Ok(rows.iter().map(|row| row.values().to_vec()).collect())
+            }
+            ReplOutput::Message(message) => bail!("statement returned no rows: {}", message),
+        }
+    }
+
+    /// Runs a statement that returns no rows and returns how many rows it
+    /// inserted, updated or deleted; 0 for any other statement.
+    pub fn execute(&mut self, sql: &str) -> Result<u64> {
+        match self.engine.execute_sql_in_transaction(sql, &self.handle)? {
+            ReplOutput::Message(message) => Ok(rows_affected(&message)),
+            ReplOutput::Rows { .. } => bail!("statement returned rows; use query instead"),
+        }
+    }
+}
+
 impl Drop for Engine {
     fn drop(&mut self) {
         use storage::FlushMode;
+        self.wait_for_stats_refresh();
+        if let Some(checkpointer) = self.checkpointer.take() {
+            checkpointer.stop();
+        }
+        if let Some(session) = self.session.take()
+            && let Err(e) = self.abort_transaction(&session.txn)
+        {
+            eprintln!("WARN: failed to roll back open transaction: {}", e);
+        }
         let _ = self.buffer_pool.flush_all_pages_with_mode(FlushMode::Force);
-        if let Err(e) = self.persist_catalog() {
+        if let Err(e) = self.persist_catalog(&self.catalog.read()) {
             eprintln!("WARN: failed to persist catalog: {}", e);
         }
     }
 }
 
+/// Parses a column type as the catalog file names it.
+pub(crate) fn data_type_from_name(name: &str) -> Option<DataType> {
+    match name {
+        "Integer" => Some(DataType::Integer),
+        "BigInt" => Some(DataType::BigInt),
+        "Text" => Some(DataType::Text),
+        "Boolean" => Some(DataType::Boolean),
+        "Real" => Some(DataType::Real),
+        "Timestamp" => Some(DataType::Timestamp),
+        "Blob" => Some(DataType::Blob),
+        _ => None,
+    }
+}
+
+/// Rejects `CREATE TABLE IF NOT EXISTS` when the declared columns no longer
+/// match the stored table. Columns added later by `ALTER TABLE` are allowed.
+fn check_schema_drift(existing: &TableInfo, declared: &[ColumnDef]) -> Result<()> {
+    for column in declared {
+        let field = existing
+            .schema
+            .fields
+            .iter()
+            .find(|field| field.visible && field.name.eq_ignore_ascii_case(&column.name))
+            .ok_or_else(|| {
+                anyhow!(
+                    "table {} already exists without column {}",
+                    existing.name,
+                    column.name
+                )
+            })?;
+        if field.data_type != column.data_type {
+            bail!(
+                "table {} already exists with column {} as {:?}, but {:?} was declared",
+                existing.name,
+                column.name,
+                field.data_type,
+                column.data_type
+            );
+        }
+    }
+    Ok(())
+}
+
+fn find_savepoint(savepoints: &[(String, wal::Lsn, usize)], name: &str) -> Result<usize> {
+    savepoints
+        .iter()
+        .rposition(|(savepoint, _, _)| savepoint.eq_ignore_ascii_case(name))
+        .ok_or_else(|| anyhow!("savepoint {} does not exist", name))
+}
+
+/// Inserts `rows`, whose values fill the columns at `column_indices`; the
+/// other columns get their defaults. Returns the rows as they were stored,
+/// leaving out those a conflict skipped.
+fn insert_value_rows(
+    table: &TableInfo,
+    column_indices: &[usize],
+    rows: Vec<Vec<Value>>,
+    on_conflict: InsertConflict,
+) -> Result<Vec<Tuple>> {
+    let schema = &table.schema;
+    let default_value =
+        |index: usize| evaluate_insert_expr(&table.default_expr(&schema.fields[index].name));
+    let mut inserted = Vec::new();
+    for row in rows {
+        if row.len() != column_indices.len() {
+            bail!(
+                "expected {} values, got {}",
+                column_indices.len(),
+                row.len()
+            );
+        }
+        let mut values = (0..schema.fields.len())
+            .map(|index| {
+                if column_indices.contains(&index) {
+                    Ok(Value::Null)
+                } else {
+                    default_value(index)
+                }
+            })
+            .collect::<Result<Vec<_>>>()?;
+        for (value, column_index) in row.into_iter().zip(column_indices.iter()) {
+            values[*column_index] = value;
+        }
+        let tuple = Tuple::new(values);
+        if let Some((_, stored)) = table
+            .insert_tuple_on_conflict(&tuple, on_conflict)
+            .map_err(|err| anyhow!(err))?
+        {
+            inserted.push(stored);
+        }
+    }
+    Ok(inserted)
+}
+
+/// Row count of a command tag such as `INSERT 0 3`, `UPDATE 2` or
+/// `DELETE 1`; 0 for other messages.
+fn rows_affected(message: &str) -> u64 {
+    let counted = ["INSERT ", "UPDATE ", "DELETE "]
+        .iter()
+        .any(|command| message.starts_with(command));
+    match message.split_whitespace().last() {
+        Some(count) if counted => count.parse().unwrap_or(0),
+        _ => 0,
+    }
+}
+
 fn resolve_column_indices(schema: &Schema, columns: Option<&[String]>) -> Result<Vec<usize>> {
     let mut indices = Vec::new();
     let mut seen = HashSet::new();
@@ -772,6 +2402,7 @@ pub fn tables_to_output(tables: &[String]) -> ReplOutput {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use query::ChangeOp;
     use std::env;
     use std::fs;
     use std::path::PathBuf;
@@ -802,7 +2433,13 @@ mod tests {
         fn drop(&mut self) {
             let _ = fs::remove_file(&self.path);
             let _ = fs::remove_file(self.path.with_extension("wal"));
+            let _ = fs::remove_file(self.path.with_extension("wal.master"));
+            // segments the log grew into past its first
+            for segment in 1..64 {
+                let _ = fs::remove_file(self.path.with_extension(format!("wal.{segment}")));
+            }
             let _ = fs::remove_file(self.path.with_extension("catalog"));
+            let _ = fs::remove_file(self.path.with_extension("db.dwb"));
         }
     }
 
@@ -870,38 +2507,3248 @@ mod tests {
     }
 
     #[test]
-    fn alter_table_rejects_invalid_operations() {
-        let db = TestDb::new("alter_invalid");
-        let mut engine = Engine::new(&db.path).expect("engine init");
-
-        engine
-            .execute_sql("CREATE TABLE people (id INT PRIMARY KEY, name TEXT);")
-            .expect("create table");
+    fn check_constraints_reject_rows_after_reopen() {
+        let db = TestDb::new("check_constraints");
+        {
+            let mut engine = Engine::new(&db.path).expect("engine init");
+            engine
+                .execute_sql(
+                    "CREATE TABLE slots (id INT PRIMARY KEY, qty INT CHECK (qty >= 0), \
+                     start_time TEXT, end_time TEXT, \
+                     CONSTRAINT slots_range CHECK (start_time < end_time));",
+                )
+                .expect("create table");
+            engine
+                .execute_sql(
+                    "INSERT INTO slots VALUES (1, 1, '2024-01-01 10:00', '2024-01-01 11:00');",
+                )
+                .expect("valid row");
+            let err = engine
+                .execute_sql(
+                    "INSERT INTO slots VALUES (2, 1, '2024-01-01 12:00', '2024-01-01 11:00');",
+                )
+                .expect_err("end before start");
+            assert!(matches!(
+                err.downcast_ref::<query::execution::ExecutionError>(),
+                Some(query::execution::ExecutionError::ConstraintViolation { constraint, .. }) if constraint == "slots_range"
+            ));
+            assert!(
+                engine
+                    .execute_sql("INSERT INTO slots VALUES (3, -1, NULL, NULL);")
+                    .is_err()
+            );
+        }
 
+        let mut engine = Engine::new(&db.path).expect("engine reopen");
         assert!(
             engine
-                .execute_sql("ALTER TABLE missing RENAME TO users;")
+                .execute_sql("UPDATE slots SET end_time = '2024-01-01 09:00' WHERE id = 1;")
                 .is_err()
         );
         assert!(
             engine
-                .execute_sql("ALTER TABLE people RENAME COLUMN missing TO name2;")
+                .execute_sql("ALTER TABLE slots DROP COLUMN start_time;")
                 .is_err()
         );
-        assert!(
+        engine
+            .execute_sql("INSERT INTO slots VALUES (4, 0, NULL, '2024-01-01 09:00');")
+            .expect("NULL check result passes");
+        let output = engine
+            .execute_sql("SELECT end_time FROM slots WHERE id = 1;")
+            .expect("select slot");
+        match output {
+            ReplOutput::Rows { rows, .. } => {
+                assert_eq!(
+                    rows[0].values()[0],
+                    Value::String("2024-01-01 11:00".to_string())
+                );
+            }
+            _ => panic!("expected rows output"),
+        }
+    }
+
+    #[test]
+    fn added_check_constraint_is_validated_enforced_and_kept() {
+        let db = TestDb::new("add_check_constraint");
+        let violates = |err: &anyhow::Error| {
+            matches!(
+                err.downcast_ref::<query::execution::ExecutionError>(),
+                Some(query::execution::ExecutionError::ConstraintViolation { constraint, .. }) if constraint == "slots_range"
+            )
+        };
+        let add_check =
+            "ALTER TABLE slots ADD CONSTRAINT slots_range CHECK (start_time < end_time);";
+        {
+            let mut engine = Engine::new(&db.path).expect("engine init");
             engine
-                .execute_sql("ALTER TABLE people ADD COLUMN name TEXT;")
-                .is_err()
-        );
-        assert!(
+                .execute_sql(
+                    "CREATE TABLE slots (id INT PRIMARY KEY, start_time TEXT, end_time TEXT);",
+                )
+                .expect("create table");
             engine
-                .execute_sql("ALTER TABLE people DROP COLUMN id;")
-                .is_err()
-        );
-        assert!(
+                .execute_sql("INSERT INTO slots VALUES (1, '10:00', '09:00');")
+                .expect("no check yet");
+            let err = engine.execute_sql(add_check).expect_err("stored row fails");
+            assert!(violates(&err));
+
             engine
-                .execute_sql("ALTER TABLE people ADD COLUMN age INT, DROP COLUMN name;")
-                .is_err()
-        );
+                .execute_sql("UPDATE slots SET end_time = '11:00' WHERE id = 1;")
+                .expect("fix row");
+            engine.execute_sql("BEGIN;").expect("begin");
+            engine.execute_sql(add_check).expect("add check");
+            engine.execute_sql("ROLLBACK;").expect("rollback");
+            engine
+                .execute_sql("INSERT INTO slots VALUES (2, '10:00', '09:00');")
+                .expect("check rolled back");
+            engine
+                .execute_sql("DELETE FROM slots WHERE id = 2;")
+                .expect("delete row");
+
+            engine.execute_sql(add_check).expect("add check");
+            assert!(engine.execute_sql(add_check).is_err());
+        }
+
+        let mut engine = Engine::new(&db.path).expect("engine reopen");
+        let err = engine
+            .execute_sql("INSERT INTO slots VALUES (3, '10:00', '09:00');")
+            .expect_err("end before start");
+        assert!(violates(&err));
+    }
+
+    #[test]
+    fn alter_column_type_rewrites_rows() {
+        let db = TestDb::new("alter_column_type");
+        {
+            let mut engine = Engine::new(&db.path).expect("engine init");
+            engine
+                .execute_sql("CREATE TABLE items (id INT PRIMARY KEY, code TEXT, note TEXT);")
+                .expect("create table");
+            engine
+                .execute_sql("INSERT INTO items VALUES (1, '10', 'a'), (2, '20', 'b');")
+                .expect("insert rows");
+            engine
+                .execute_sql("ALTER TABLE items ALTER COLUMN id TYPE BIGINT;")
+                .expect("widen id");
+            engine
+                .execute_sql(
+                    "ALTER TABLE items ALTER COLUMN code TYPE INT USING CAST(code AS INT);",
+                )
+                .expect("convert code");
+            assert!(
+                engine
+                    .execute_sql("ALTER TABLE items ALTER COLUMN note TYPE INT;")
+                    .is_err()
+            );
+        }
+
+        let mut engine = Engine::new(&db.path).expect("engine reopen");
+        let output = engine
+            .execute_sql("SELECT code, note FROM items WHERE id = 2;")
+            .expect("select item");
+        match output {
+            ReplOutput::Rows { rows, .. } => {
+                assert_eq!(rows.len(), 1);
+                assert_eq!(rows[0].values()[0], Value::Integer(20));
+                assert_eq!(rows[0].values()[1], Value::String("b".to_string()));
+            }
+            _ => panic!("expected rows output"),
+        }
+        let catalog = engine.catalog.read();
+        let table = catalog.table("items").expect("items table");
+        assert_eq!(table.schema.fields[0].data_type, DataType::BigInt);
+        assert_eq!(table.schema.fields[1].data_type, DataType::Integer);
+        assert_eq!(table.schema.fields[2].data_type, DataType::Text);
+    }
+
+    #[test]
+    fn auto_analyze_refreshes_stats_after_churn() {
+        let db = TestDb::new("auto_analyze");
+        let mut engine = Engine::new(&db.path).expect("engine init");
+        engine
+            .execute_sql("CREATE TABLE events (id INT);")
+            .expect("create table");
+        let rows: Vec<String> = (0..10).map(|id| format!("({})", id)).collect();
+        engine
+            .execute_sql(&format!("INSERT INTO events VALUES {};", rows.join(", ")))
+            .expect("insert rows");
+        engine
+            .execute_sql("ANALYZE TABLE events;")
+            .expect("analyze");
+        assert_eq!(engine.table_stats("events").unwrap().row_count, 10);
+
+        engine.set_auto_analyze(Some(0.5));
+        engine
+            .execute_sql("INSERT INTO events VALUES (10), (11);")
+            .expect("insert below threshold");
+        engine.wait_for_stats_refresh();
+        assert_eq!(engine.table_stats("events").unwrap().row_count, 10);
+
+        engine
+            .execute_sql("INSERT INTO events VALUES (12), (13), (14);")
+            .expect("insert past threshold");
+        engine.wait_for_stats_refresh();
+        assert_eq!(engine.table_stats("events").unwrap().row_count, 15);
+    }
+
+    #[test]
+    fn auto_reindex_rebuilds_a_fragmented_index() {
+        let db = TestDb::new("auto_reindex");
+        let mut engine = Engine::new(&db.path).expect("engine init");
+        engine
+            .execute_sql("CREATE TABLE events (id INT PRIMARY KEY, kind TEXT);")
+            .expect("create table");
+        // ids in scattered order, so leaves split in the middle and get
+        // allocated out of key order
+        let rows: Vec<String> = (0..1500)
+            .map(|i| format!("({}, 'k{}')", (i * 7919) % 1500, i % 7))
+            .collect();
+        engine
+            .execute_sql(&format!("INSERT INTO events VALUES {};", rows.join(", ")))
+            .expect("insert rows");
+        engine
+            .execute_sql("DELETE FROM events WHERE id % 10 < 4;")
+            .expect("delete rows");
+
+        // merges keep the leaves at least half full, but no fuller
+        let primary_key = |engine: &Engine| engine.index_stats("events").unwrap()[0].1;
+        let churned = primary_key(&engine);
+        assert_eq!(churned.entries, 900);
+        assert!(churned.fragmentation() > 0.3, "{churned:?}");
+        assert!(churned.backward_links > 0, "{churned:?}");
+
+        engine.set_auto_reindex(Some(0.3));
+        engine
+            .execute_sql("DELETE FROM events WHERE id = 4;")
+            .expect("delete one row");
+        let rebuilt = primary_key(&engine);
+        assert_eq!(rebuilt.entries, 899);
+        assert!(rebuilt.leaf_pages < churned.leaf_pages, "{rebuilt:?}");
+        assert!(rebuilt.fragmentation() < 0.3, "{rebuilt:?}");
+        assert_eq!(rebuilt.backward_links, 0);
+        engine.catalog.read().table("events").unwrap().indexes[0]
+            .index
+            .verify()
+            .expect("rebuilt index is well formed");
+
+        let output = engine
+            .execute_sql("SELECT kind FROM events WHERE id = 1495;")
+            .expect("select by key");
+        let ReplOutput::Rows { rows, .. } = output else {
+            panic!("expected rows output");
+        };
+        assert_eq!(rows.len(), 1);
+    }
+
+    #[test]
+    fn explain_shows_estimates_once_the_table_is_analyzed() {
+        let db = TestDb::new("explain_estimates");
+        let mut engine = Engine::new(&db.path).expect("engine init");
+        engine
+            .execute_sql("CREATE TABLE events (id INT PRIMARY KEY, kind TEXT);")
+            .expect("create table");
+        let rows: Vec<String> = (0..300)
+            .map(|id| format!("({}, 'k{}')", id, id % 7))
+            .collect();
+        engine
+            .execute_sql(&format!("INSERT INTO events VALUES {};", rows.join(", ")))
+            .expect("insert rows");
+        // the estimate on the plan line starting with `operator`
+        let estimate = |engine: &mut Engine, sql: &str, operator: &str| {
+            let ReplOutput::Rows { rows, .. } = engine
+                .execute_sql(&format!("EXPLAIN {}", sql))
+                .expect("explain")
+            else {
+                panic!("expected rows output");
+            };
+            let lines = rows
+                .iter()
+                .map(|row| row.values()[0].as_str().expect("plan line").to_string())
+                .collect::<Vec<_>>();
+            let line = lines
+                .iter()
+                .find(|line| line.trim_start().starts_with(operator))
+                .unwrap_or_else(|| panic!("no {} in {:?}", operator, lines));
+            line.find("(rows=").map(|start| line[start..].to_string())
+        };
+
+        let sql = "SELECT * FROM events WHERE id = 5;";
+        assert_eq!(estimate(&mut engine, sql, "IndexScan"), None);
+
+        engine
+            .execute_sql("ANALYZE TABLE events;")
+            .expect("analyze");
+        let selective = estimate(&mut engine, sql, "IndexScan").expect("estimate");
+        assert!(selective.starts_with("(rows=1 "), "{selective}");
+        let sql = "SELECT * FROM events WHERE kind <> 'k1';";
+        let broad = estimate(&mut engine, sql, "Filter").expect("estimate");
+        assert!(broad.starts_with("(rows=270 "), "{broad}");
+        let scan = estimate(&mut engine, sql, "SeqScan").expect("estimate");
+        assert!(scan.starts_with("(rows=300 "), "{scan}");
+        let range = estimate(
+            &mut engine,
+            "SELECT * FROM events WHERE id >= 5;",
+            "IndexScan",
+        );
+        assert!(range.expect("estimate").starts_with("(rows=100 "));
+    }
+
+    #[test]
+    fn cancelled_statement_is_rolled_back() {
+        let db = TestDb::new("cancelled_statement");
+        let mut engine = Engine::new(&db.path).expect("engine init");
+        engine
+            .execute_sql("CREATE TABLE jobs (id INT);")
+            .expect("create table");
+
+        let token = CancellationToken::new();
+        token.cancel();
+        let err = engine
+            .with_cancellation(token, |engine| {
+                engine.execute_sql("INSERT INTO jobs VALUES (1);")
+            })
+            .expect_err("cancelled insert");
+        assert!(matches!(
+            err.downcast_ref::<query::execution::ExecutionError>(),
+            Some(query::execution::ExecutionError::Cancelled)
+        ));
+
+        match engine.execute_sql("SELECT id FROM jobs;").expect("select") {
+            ReplOutput::Rows { rows, .. } => assert!(rows.is_empty()),
+            _ => panic!("expected rows output"),
+        }
+    }
+
+    #[test]
+    fn update_from_joins_source_table() {
+        let db = TestDb::new("update_from");
+        let mut engine = Engine::new(&db.path).expect("engine init");
+        for sql in [
+            "CREATE TABLE orders (id INT, status TEXT);",
+            "CREATE TABLE tickets (id INT, order_id INT, status TEXT);",
+            "INSERT INTO orders VALUES (1, 'PAID'), (2, 'PENDING'), (3, 'PAID');",
+            "INSERT INTO tickets VALUES (10, 1, 'RESERVED'), (11, 2, 'RESERVED'), \
+             (12, 3, 'RESERVED'), (13, 3, 'RESERVED');",
+        ] {
+            engine.execute_sql(sql).expect("setup");
+        }
+
+        let output = engine
+            .execute_sql(
+                "UPDATE tickets SET status = 'ISSUED' FROM orders \
+                 WHERE tickets.order_id = orders.id AND orders.status = 'PAID';",
+            )
+            .expect("update from");
+        assert!(matches!(output, ReplOutput::Message(ref msg) if msg == "UPDATE 3"));
+
+        match engine
+            .execute_sql("SELECT id, status FROM tickets;")
+            .expect("select tickets")
+        {
+            ReplOutput::Rows { rows, .. } => {
+                let statuses: Vec<(Value, Value)> = rows
+                    .iter()
+                    .map(|row| (row.values()[0].clone(), row.values()[1].clone()))
+                    .collect();
+                assert_eq!(
+                    statuses,
+                    vec![
+                        (Value::Integer(10), Value::String("ISSUED".to_string())),
+                        (Value::Integer(11), Value::String("RESERVED".to_string())),
+                        (Value::Integer(12), Value::String("ISSUED".to_string())),
+                        (Value::Integer(13), Value::String("ISSUED".to_string())),
+                    ]
+                );
+            }
+            _ => panic!("expected rows output"),
+        }
+    }
+
+    #[test]
+    fn delete_using_removes_joined_rows() {
+        let db = TestDb::new("delete_using");
+        let mut engine = Engine::new(&db.path).expect("engine init");
+        for sql in [
+            "CREATE TABLE orders (id INT, status TEXT);",
+            "CREATE TABLE tickets (id INT PRIMARY KEY, order_id INT);",
+            "INSERT INTO orders VALUES (1, 'CANCELLED'), (2, 'PAID'), (3, 'CANCELLED');",
+            "INSERT INTO tickets VALUES (10, 1), (11, 2), (12, 3), (13, 3), (14, 4);",
+        ] {
+            engine.execute_sql(sql).expect("setup");
+        }
+
+        let output = engine
+            .execute_sql(
+                "DELETE FROM tickets USING orders \
+                 WHERE tickets.order_id = orders.id AND orders.status = 'CANCELLED';",
+            )
+            .expect("delete using");
+        assert!(matches!(output, ReplOutput::Message(ref msg) if msg == "DELETE 3"));
+
+        match engine
+            .execute_sql("SELECT id FROM tickets;")
+            .expect("select tickets")
+        {
+            ReplOutput::Rows { rows, .. } => {
+                let ids: Vec<Value> = rows.iter().map(|row| row.values()[0].clone()).collect();
+                assert_eq!(ids, vec![Value::Integer(11), Value::Integer(14)]);
+            }
+            _ => panic!("expected rows output"),
+        }
+        // the primary key index no longer holds the deleted rows
+        engine
+            .execute_sql("INSERT INTO tickets VALUES (12, 2);")
+            .expect("reuse deleted key");
+    }
+
+    #[test]
+    fn alter_table_rejects_invalid_operations() {
+        let db = TestDb::new("alter_invalid");
+        let mut engine = Engine::new(&db.path).expect("engine init");
+
+        engine
+            .execute_sql("CREATE TABLE people (id INT PRIMARY KEY, name TEXT);")
+            .expect("create table");
+
+        assert!(
+            engine
+                .execute_sql("ALTER TABLE missing RENAME TO users;")
+                .is_err()
+        );
+        assert!(
+            engine
+                .execute_sql("ALTER TABLE people RENAME COLUMN missing TO name2;")
+                .is_err()
+        );
+        assert!(
+            engine
+                .execute_sql("ALTER TABLE people ADD COLUMN name TEXT;")
+                .is_err()
+        );
+        assert!(
+            engine
+                .execute_sql("ALTER TABLE people DROP COLUMN id;")
+                .is_err()
+        );
+        assert!(
+            engine
+                .execute_sql("ALTER TABLE people ADD COLUMN age INT, DROP COLUMN name;")
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn unique_column_constraint_creates_enforcing_index() {
+        let db = TestDb::new("unique_email");
+        let mut engine = Engine::new(&db.path).expect("engine init");
+
+        engine
+            .execute_sql(
+                "CREATE TABLE users (id INT PRIMARY KEY, google_sub TEXT UNIQUE NOT NULL, \
+                 email TEXT UNIQUE NOT NULL);",
+            )
+            .expect("create table");
+        {
+            let catalog = engine.catalog.read();
+            let table = catalog.table("users").expect("users table");
+            let index = table
+                .indexes
+                .iter()
+                .find(|index| index.columns == ["email"])
+                .expect("email index");
+            assert!(index.unique);
+        }
+
+        engine
+            .execute_sql("INSERT INTO users VALUES (1, 'sub-1', 'dup@example.com');")
+            .expect("insert first user");
+        let err = engine
+            .execute_sql("INSERT INTO users VALUES (2, 'sub-2', 'dup@example.com');")
+            .expect_err("duplicate email");
+        match err.downcast_ref::<query::execution::ExecutionError>() {
+            Some(query::execution::ExecutionError::ConstraintViolation { constraint, .. }) => {
+                assert_eq!(constraint, "users_email_uk");
+            }
+            other => panic!("expected constraint violation, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn composite_unique_table_constraint_is_enforced() {
+        let db = TestDb::new("unique_composite");
+        let mut engine = Engine::new(&db.path).expect("engine init");
+
+        engine
+            .execute_sql(
+                "CREATE TABLE seats (event_id INT, seat TEXT, holder TEXT, \
+                 UNIQUE (event_id, seat));",
+            )
+            .expect("create table");
+        engine
+            .execute_sql("INSERT INTO seats VALUES (1, 'A1', 'ada');")
+            .expect("insert seat");
+        engine
+            .execute_sql("INSERT INTO seats VALUES (2, 'A1', 'bob');")
+            .expect("same seat, other event");
+        assert!(
+            engine
+                .execute_sql("INSERT INTO seats VALUES (1, 'A1', 'eve');")
+                .is_err()
+        );
+
+        drop(engine);
+        let mut engine = Engine::new(&db.path).expect("engine reopen");
+        assert!(
+            engine
+                .execute_sql("INSERT INTO seats VALUES (2, 'A1', 'eve');")
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn full_disk_fails_writes_until_space_is_freed() {
+        let db = TestDb::new("disk_full");
+        let mut engine = Engine::new(&db.path).expect("engine init");
+        engine
+            .execute_sql("CREATE TABLE notes (id INT PRIMARY KEY, body TEXT);")
+            .expect("create table");
+        engine
+            .execute_sql("INSERT INTO notes VALUES (1, 'kept');")
+            .expect("insert before the disk fills");
+
+        let files = db.path.with_extension("");
+        wal::fault::fill_disk(&files);
+        let err = engine
+            .execute_sql("INSERT INTO notes VALUES (2, 'unconfirmed');")
+            .expect_err("commit on a full disk");
+        assert!(format!("{err:#}").contains("disk full"), "{err:#}");
+        // reads go on while the disk is full
+        assert_eq!(count_rows(&mut engine, "SELECT id FROM notes;"), 2);
+
+        wal::fault::free_disk(&files);
+        engine
+            .execute_sql("INSERT INTO notes VALUES (3, 'after');")
+            .expect("insert once space is freed");
+        drop(engine);
+
+        // the commit that failed was logged, and written with the next one
+        let mut engine = Engine::new(&db.path).expect("engine reopen");
+        assert_eq!(count_rows(&mut engine, "SELECT id FROM notes;"), 3);
+    }
+
+    #[test]
+    fn nocase_unique_column_rejects_emails_differing_in_case() {
+        let db = TestDb::new("unique_nocase");
+        let mut engine = Engine::new(&db.path).expect("engine init");
+
+        engine
+            .execute_sql(
+                "CREATE TABLE users (id INT PRIMARY KEY, email TEXT COLLATE NOCASE, \
+                 UNIQUE (email));",
+            )
+            .expect("create table");
+        engine
+            .execute_sql("INSERT INTO users VALUES (1, 'A@X.com');")
+            .expect("insert first user");
+        let err = engine
+            .execute_sql("INSERT INTO users VALUES (2, 'a@x.com');")
+            .expect_err("email differing only in case");
+        assert!(matches!(
+            err.downcast_ref::<query::execution::ExecutionError>(),
+            Some(query::execution::ExecutionError::ConstraintViolation { .. })
+        ));
+
+        // comparisons still see the stored text
+        let email = "SELECT id FROM users WHERE email = ";
+        assert_eq!(count_rows(&mut engine, &format!("{email}'A@X.com';")), 1);
+        assert_eq!(count_rows(&mut engine, &format!("{email}'a@x.com';")), 0);
+
+        drop(engine);
+        let mut engine = Engine::new(&db.path).expect("engine reopen");
+        assert!(
+            engine
+                .execute_sql("INSERT INTO users VALUES (3, 'a@X.COM');")
+                .is_err()
+        );
+        assert!(
+            engine
+                .execute_sql("CREATE TABLE t (n INT COLLATE NOCASE);")
+                .is_err()
+        );
+    }
+
+    fn count_rows(engine: &mut Engine, sql: &str) -> usize {
+        match engine.execute_sql(sql).expect("select") {
+            ReplOutput::Rows { rows, .. } => rows.len(),
+            _ => panic!("expected rows output"),
+        }
+    }
+
+    #[test]
+    fn read_only_transaction_rejects_writes_without_exclusive_locks() {
+        let db = TestDb::new("read_only_txn");
+        let mut engine = Engine::new(&db.path).expect("engine init");
+        engine
+            .execute_sql("CREATE TABLE items (id INT PRIMARY KEY, name TEXT);")
+            .expect("create table");
+        engine
+            .execute_sql("INSERT INTO items VALUES (1, 'a'), (2, 'b');")
+            .expect("insert rows");
+
+        let before = engine.lock_stats();
+        engine.execute_sql("BEGIN READ ONLY;").expect("begin");
+        assert_eq!(count_rows(&mut engine, "SELECT * FROM items;"), 2);
+        let err = engine
+            .execute_sql("INSERT INTO items VALUES (3, 'c');")
+            .expect_err("write in read-only transaction");
+        assert!(err.to_string().contains("read-only transaction"));
+        assert!(engine.execute_sql("CREATE TABLE other (id INT);").is_err());
+        assert_eq!(count_rows(&mut engine, "SELECT * FROM items;"), 2);
+        engine.execute_sql("COMMIT;").expect("commit");
+
+        // NEXTVAL advances the sequence, so it is a write too
+        engine
+            .execute_sql("CREATE SEQUENCE ids;")
+            .expect("create sequence");
+        engine.execute_sql("BEGIN READ ONLY;").expect("begin");
+        let err = engine
+            .execute_sql("SELECT id FROM items WHERE id = NEXTVAL('ids');")
+            .expect_err("nextval in read-only transaction");
+        assert!(err.to_string().contains("read-only transaction"));
+        assert!(engine.execute_sql("SELECT NEXTVAL('ids');").is_err());
+        engine.execute_sql("COMMIT;").expect("commit");
+        match engine
+            .execute_sql("SELECT NEXTVAL('ids');")
+            .expect("nextval")
+        {
+            ReplOutput::Rows { rows, .. } => assert_eq!(rows[0].values(), &[Value::Integer(1)]),
+            _ => panic!("expected rows output"),
+        }
+        assert_eq!(
+            engine.lock_stats().exclusive_grants,
+            before.exclusive_grants
+        );
+
+        engine
+            .execute_sql("INSERT INTO items VALUES (3, 'c');")
+            .expect("writes allowed again");
+        assert_eq!(count_rows(&mut engine, "SELECT * FROM items;"), 3);
+    }
+
+    #[test]
+    fn txn_stats_count_commits_aborts_and_lock_timeouts() {
+        let db = TestDb::new("txn_stats");
+        let mut engine = Engine::new(&db.path).expect("engine init");
+        engine
+            .execute_sql("CREATE TABLE items (id INT PRIMARY KEY, name TEXT);")
+            .expect("create table");
+        engine
+            .execute_sql("INSERT INTO items VALUES (1, 'a');")
+            .expect("insert");
+        assert!(
+            engine
+                .execute_sql("INSERT INTO items VALUES (1, 'b');")
+                .is_err()
+        );
+        engine.execute_sql("BEGIN;").expect("begin");
+        engine
+            .execute_sql("INSERT INTO items VALUES (2, 'b');")
+            .expect("insert");
+        engine.execute_sql("ROLLBACK;").expect("rollback");
+
+        let stats = engine.txn_stats();
+        assert_eq!(stats.begun, 4);
+        assert_eq!(stats.committed, 2);
+        assert_eq!(stats.aborted, 2);
+        assert_eq!(stats.deadlock_aborted, 0);
+        assert!(stats.avg_duration > Duration::ZERO);
+        assert!(stats.avg_duration < Duration::from_secs(5));
+        assert!(stats.p50_duration <= stats.p99_duration);
+
+        let holder = engine.begin_transaction().expect("begin holder");
+        engine
+            .execute_sql_in_transaction("UPDATE items SET name = 'x' WHERE id = 1;", &holder)
+            .expect("update");
+        let waiter = engine.begin_transaction().expect("begin waiter");
+        assert!(
+            engine
+                .execute_sql_in_transaction("UPDATE items SET name = 'y' WHERE id = 1;", &waiter)
+                .is_err()
+        );
+        engine.abort_transaction(&waiter).expect("abort waiter");
+        engine.commit_transaction(&holder).expect("commit holder");
+
+        let stats = engine.txn_stats();
+        assert_eq!(stats.begun, 6);
+        assert_eq!(stats.committed, 3);
+        assert_eq!(stats.aborted, 3);
+        assert_eq!(stats.deadlock_aborted, 1);
+    }
+
+    #[test]
+    fn row_locks_let_transactions_update_other_rows_of_a_page() {
+        let db = TestDb::new("engine_row_locks");
+        let mut engine = Engine::new(&db.path).expect("engine init");
+        engine
+            .execute_sql("CREATE TABLE seats (id INT PRIMARY KEY, holder TEXT);")
+            .expect("create table");
+        engine
+            .execute_sql("INSERT INTO seats VALUES (1, 'none'), (2, 'none');")
+            .expect("insert rows");
+        let update = |engine: &mut Engine, txn: &wal::TransactionHandle, id: i64| {
+            engine.execute_sql_in_transaction(
+                &format!("UPDATE seats SET holder = 'txn' WHERE id = {};", id),
+                txn,
+            )
+        };
+
+        // page locks: the second row waits for the first row's page
+        let holder = engine.begin_transaction().expect("begin holder");
+        update(&mut engine, &holder, 1).expect("update first row");
+        let other = engine.begin_transaction().expect("begin other");
+        assert!(update(&mut engine, &other, 2).is_err());
+        engine.abort_transaction(&other).expect("abort other");
+        engine.abort_transaction(&holder).expect("abort holder");
+
+        engine.set_row_locks(true);
+        let holder = engine.begin_transaction().expect("begin holder");
+        update(&mut engine, &holder, 1).expect("update first row");
+        let other = engine.begin_transaction().expect("begin other");
+        update(&mut engine, &other, 2).expect("update second row");
+        // the same row still waits
+        let same = engine.begin_transaction().expect("begin same");
+        assert!(update(&mut engine, &same, 1).is_err());
+        engine.abort_transaction(&same).expect("abort same");
+        engine.commit_transaction(&other).expect("commit other");
+        engine.commit_transaction(&holder).expect("commit holder");
+        assert_eq!(
+            count_rows(&mut engine, "SELECT * FROM seats WHERE holder = 'txn';"),
+            2
+        );
+
+        // tables created afterwards lock rows too
+        engine
+            .execute_sql("CREATE TABLE later (id INT PRIMARY KEY);")
+            .expect("create later table");
+        let catalog = engine.shared_catalog();
+        assert!(
+            catalog
+                .read()
+                .table("later")
+                .expect("later table")
+                .heap
+                .row_locks()
+        );
+    }
+
+    #[test]
+    fn snapshot_transaction_reads_rows_as_of_its_start() {
+        let db = TestDb::new("snapshot_txn");
+        let mut engine = Engine::new(&db.path).expect("engine init");
+        engine
+            .execute_sql("CREATE TABLE items (id INT PRIMARY KEY, name TEXT);")
+            .expect("create table");
+        engine
+            .execute_sql("INSERT INTO items VALUES (1, 'a'), (2, 'b');")
+            .expect("insert rows");
+
+        let names = |engine: &mut Engine, txn: &wal::TransactionHandle, sql: &str| match engine
+            .execute_sql_in_transaction(sql, txn)
+            .expect("select")
+        {
+            ReplOutput::Rows { rows, .. } => rows
+                .iter()
+                .map(|row| row.values()[0].clone())
+                .collect::<Vec<_>>(),
+            _ => panic!("expected rows output"),
+        };
+        let text = |names: &[&str]| {
+            names
+                .iter()
+                .map(|name| Value::String(name.to_string()))
+                .collect::<Vec<_>>()
+        };
+        let all = "SELECT name FROM items ORDER BY id;";
+
+        let reader = engine.begin_snapshot_transaction().expect("begin reader");
+        assert_eq!(names(&mut engine, &reader, all), text(&["a", "b"]));
+
+        // the writer holds exclusive locks, which the reader does not wait on
+        let writer = engine.begin_transaction().expect("begin writer");
+        for sql in [
+            "INSERT INTO items VALUES (3, 'c');",
+            "UPDATE items SET name = 'changed' WHERE id = 1;",
+            "DELETE FROM items WHERE id = 2;",
+        ] {
+            engine
+                .execute_sql_in_transaction(sql, &writer)
+                .expect("write");
+        }
+        assert_eq!(names(&mut engine, &reader, all), text(&["a", "b"]));
+        assert_eq!(
+            names(&mut engine, &reader, "SELECT name FROM items WHERE id = 2;"),
+            text(&["b"])
+        );
+        assert_eq!(
+            names(&mut engine, &reader, "SELECT COUNT(*) FROM items;"),
+            vec![Value::Integer(2)]
+        );
+
+        // committing does not change what the snapshot sees
+        engine.commit_transaction(&writer).expect("commit writer");
+        assert_eq!(names(&mut engine, &reader, all), text(&["a", "b"]));
+
+        // and the reader may not overwrite a change it never saw
+        let err = engine
+            .execute_sql_in_transaction("UPDATE items SET name = 'x' WHERE id = 1;", &reader)
+            .expect_err("update of a row changed after the snapshot");
+        assert!(matches!(
+            err.downcast_ref::<query::execution::ExecutionError>(),
+            Some(query::execution::ExecutionError::SerializationFailure)
+        ));
+        engine.abort_transaction(&reader).expect("abort reader");
+
+        // a new snapshot sees the committed changes, and its own
+        engine
+            .execute_sql("BEGIN ISOLATION LEVEL REPEATABLE READ;")
+            .expect("begin");
+        engine
+            .execute_sql("INSERT INTO items VALUES (4, 'd');")
+            .expect("insert own row");
+        let rows = match engine.execute_sql(all).expect("select") {
+            ReplOutput::Rows { rows, .. } => rows,
+            _ => panic!("expected rows output"),
+        };
+        let rows: Vec<_> = rows.iter().map(|row| row.values()[0].clone()).collect();
+        assert_eq!(rows, text(&["changed", "c", "d"]));
+        engine.execute_sql("COMMIT;").expect("commit");
+        assert_eq!(count_rows(&mut engine, "SELECT * FROM items;"), 3);
+    }
+
+    #[test]
+    fn snapshot_reads_on_indexed_predicates_see_the_snapshot() {
+        let db = TestDb::new("snapshot_indexed");
+        let mut engine = Engine::new(&db.path).expect("engine init");
+        for sql in [
+            "CREATE TABLE items (id INT PRIMARY KEY, category TEXT, name TEXT);",
+            "CREATE INDEX items_category ON items (category);",
+            "INSERT INTO items VALUES (1, 'tools', 'hammer'), (2, 'tools', 'saw'), \
+             (3, 'toys', 'ball');",
+        ] {
+            engine.execute_sql(sql).expect("setup");
+        }
+        let names = |engine: &mut Engine, txn: &wal::TransactionHandle, sql: &str| match engine
+            .execute_sql_in_transaction(sql, txn)
+            .expect("select")
+        {
+            ReplOutput::Rows { rows, .. } => rows
+                .iter()
+                .map(|row| row.values()[0].clone())
+                .collect::<Vec<_>>(),
+            _ => panic!("expected rows output"),
+        };
+        let text = |names: &[&str]| {
+            names
+                .iter()
+                .map(|name| Value::String(name.to_string()))
+                .collect::<Vec<_>>()
+        };
+
+        let reader = engine.begin_snapshot_transaction().expect("begin reader");
+        let tools = "SELECT name FROM items WHERE category = 'tools' ORDER BY id;";
+        assert_eq!(names(&mut engine, &reader, tools), text(&["hammer", "saw"]));
+
+        // move rows between index keys, and add and remove some
+        engine
+            .execute_sql("UPDATE items SET category = 'toys' WHERE id = 1;")
+            .expect("move row");
+        engine
+            .execute_sql("INSERT INTO items VALUES (4, 'tools', 'drill');")
+            .expect("insert");
+        engine
+            .execute_sql("DELETE FROM items WHERE id = 2;")
+            .expect("delete");
+
+        assert_eq!(names(&mut engine, &reader, tools), text(&["hammer", "saw"]));
+        assert_eq!(
+            names(
+                &mut engine,
+                &reader,
+                "SELECT name FROM items WHERE category = 'toys';"
+            ),
+            text(&["ball"])
+        );
+        assert_eq!(
+            names(
+                &mut engine,
+                &reader,
+                "SELECT name FROM items WHERE id BETWEEN 2 AND 4 ORDER BY id;"
+            ),
+            text(&["saw", "ball"])
+        );
+        assert_eq!(
+            names(
+                &mut engine,
+                &reader,
+                "SELECT name FROM items WHERE id IN (SELECT id FROM items \
+                 WHERE category = 'tools') ORDER BY id;"
+            ),
+            text(&["hammer", "saw"])
+        );
+        engine.commit_transaction(&reader).expect("commit reader");
+
+        // outside the snapshot the index answers with the latest rows
+        let rows = match engine.execute_sql(tools).expect("select") {
+            ReplOutput::Rows { rows, .. } => rows,
+            _ => panic!("expected rows output"),
+        };
+        let rows: Vec<_> = rows.iter().map(|row| row.values()[0].clone()).collect();
+        assert_eq!(rows, text(&["drill"]));
+    }
+
+    #[test]
+    fn order_by_limit_is_deterministic_and_strict_mode_warns_without_it() {
+        let db = TestDb::new("order_by_limit");
+        let mut engine = Engine::new(&db.path).expect("engine init");
+        engine
+            .execute_sql("CREATE TABLE items (id INT PRIMARY KEY, name TEXT, price INT);")
+            .expect("create table");
+        engine
+            .execute_sql(
+                "INSERT INTO items VALUES (1, 'a', 30), (2, 'b', 10), (3, 'c', NULL), (4, 'd', 20);",
+            )
+            .expect("insert rows");
+
+        let ids = |engine: &mut Engine, sql: &str| match engine.execute_sql(sql).expect("select") {
+            ReplOutput::Rows { rows, .. } => rows
+                .iter()
+                .map(|row| row.values()[0].clone())
+                .collect::<Vec<_>>(),
+            _ => panic!("expected rows output"),
+        };
+        assert_eq!(
+            ids(
+                &mut engine,
+                "SELECT id FROM items ORDER BY price DESC LIMIT 2;"
+            ),
+            vec![Value::Integer(1), Value::Integer(4)]
+        );
+        assert_eq!(
+            ids(
+                &mut engine,
+                "SELECT name, id FROM items ORDER BY price LIMIT 2 OFFSET 1;"
+            ),
+            vec![
+                Value::String("d".to_string()),
+                Value::String("a".to_string())
+            ]
+        );
+        assert_eq!(
+            ids(
+                &mut engine,
+                "SELECT id FROM items ORDER BY price LIMIT 10 OFFSET 2;"
+            ),
+            vec![Value::Integer(1), Value::Integer(3)]
+        );
+        assert_eq!(
+            ids(
+                &mut engine,
+                "SELECT name FROM items ORDER BY id DESC LIMIT 1;"
+            ),
+            vec![Value::String("d".to_string())]
+        );
+
+        assert_eq!(count_rows(&mut engine, "SELECT * FROM items LIMIT 1;"), 1);
+        assert!(engine.take_warnings().is_empty());
+
+        engine.set_strict_mode(true);
+        assert_eq!(count_rows(&mut engine, "SELECT * FROM items LIMIT 1;"), 1);
+        let warnings = engine.take_warnings();
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("LIMIT without ORDER BY"));
+        assert_eq!(
+            count_rows(&mut engine, "SELECT * FROM items ORDER BY id LIMIT 1;"),
+            1
+        );
+        assert!(engine.take_warnings().is_empty());
+    }
+
+    #[test]
+    fn explain_shows_how_dml_finds_its_rows() {
+        let db = TestDb::new("explain_dml");
+        let mut engine = Engine::new(&db.path).expect("engine init");
+        engine
+            .execute_sql("CREATE TABLE events (id INT PRIMARY KEY, x INT);")
+            .expect("create table");
+        engine
+            .execute_sql("INSERT INTO events VALUES (1, 0), (5, 0), (9, 0);")
+            .expect("insert rows");
+        let mut explain = |sql: &str| match engine.execute_sql(&format!("EXPLAIN {sql}")).unwrap() {
+            ReplOutput::Rows { rows, .. } => rows
+                .iter()
+                .map(|row| match &row.values()[0] {
+                    Value::String(line) => line.clone(),
+                    other => panic!("unexpected plan value {:?}", other),
+                })
+                .collect::<Vec<_>>(),
+            _ => panic!("expected rows output"),
+        };
+
+        assert_eq!(
+            explain("UPDATE events SET x = 1 WHERE id = 5;"),
+            vec![
+                "Update: events set x = 1",
+                "  IndexScan: events using events_id_pk (= 5)",
+            ]
+        );
+        assert_eq!(
+            explain("DELETE FROM events WHERE id > 4 AND x = 0;"),
+            vec![
+                "Delete: events",
+                "  IndexScan: events using events_id_pk (> 4) filter: (x = 0)",
+            ]
+        );
+        assert_eq!(
+            explain("DELETE FROM events WHERE x = 0;"),
+            vec!["Delete: events filter: (x = 0)", "  SeqScan: events"]
+        );
+        assert_eq!(
+            explain("INSERT INTO events VALUES (2, 0), (3, 0);"),
+            vec!["Insert: events", "  Values: 2 rows"]
+        );
+        assert_eq!(
+            explain("INSERT INTO events SELECT id + 10, x FROM events WHERE id = 1;")[2],
+            "    IndexScan: events using events_id_pk (= 1)"
+        );
+
+        // explaining changes nothing, and the index-driven statements
+        // change exactly the rows they match
+        assert_eq!(count_rows(&mut engine, "SELECT * FROM events;"), 3);
+        assert!(matches!(
+            engine.execute_sql("UPDATE events SET x = 1 WHERE id = 5;").unwrap(),
+            ReplOutput::Message(message) if message == "UPDATE 1"
+        ));
+        assert!(matches!(
+            engine.execute_sql("DELETE FROM events WHERE id > 4 AND x = 0;").unwrap(),
+            ReplOutput::Message(message) if message == "DELETE 1"
+        ));
+        assert_eq!(
+            count_rows(&mut engine, "SELECT * FROM events WHERE x = 1;"),
+            1
+        );
+        assert_eq!(count_rows(&mut engine, "SELECT * FROM events;"), 2);
+    }
+
+    #[test]
+    fn explain_shows_the_more_selective_index() {
+        let db = TestDb::new("explain_index_choice");
+        let mut engine = Engine::new(&db.path).expect("engine init");
+        engine
+            .execute_sql("CREATE TABLE items (id INT PRIMARY KEY, category TEXT, sku TEXT);")
+            .expect("create table");
+        engine
+            .execute_sql("CREATE INDEX items_category ON items (category);")
+            .expect("create index");
+        engine
+            .execute_sql("CREATE UNIQUE INDEX items_sku ON items (sku);")
+            .expect("create index");
+        engine
+            .execute_sql(
+                "INSERT INTO items VALUES (1, 'tools', 'a-1'), (2, 'tools', 'a-2'), (3, 'toys', 'b-1');",
+            )
+            .expect("insert rows");
+
+        let sql = "SELECT id FROM items WHERE category = 'tools' AND sku = 'a-2';";
+        let plan = match engine.execute_sql(&format!("EXPLAIN {}", sql)).unwrap() {
+            ReplOutput::Rows { rows, .. } => rows
+                .iter()
+                .map(|row| match &row.values()[0] {
+                    Value::String(line) => line.clone(),
+                    other => panic!("unexpected plan value {:?}", other),
+                })
+                .collect::<Vec<_>>(),
+            _ => panic!("expected rows output"),
+        };
+        assert_eq!(plan.len(), 2, "{plan:?}");
+        assert!(
+            plan[1].contains("IndexScan: items using items_sku"),
+            "{plan:?}"
+        );
+        assert!(plan[1].contains("filter: (category = 'tools')"), "{plan:?}");
+        assert_eq!(count_rows(&mut engine, sql), 1);
+        assert_eq!(
+            count_rows(
+                &mut engine,
+                "SELECT id FROM items WHERE category = 'toys' AND sku = 'a-2';"
+            ),
+            0
+        );
+        // the primary key range is read from the index, LIKE checked per row
+        let range = "SELECT id FROM items WHERE id >= 2 AND sku LIKE 'a%';";
+        assert_eq!(count_rows(&mut engine, range), 1);
+        assert_eq!(
+            count_rows(
+                &mut engine,
+                "SELECT id FROM items WHERE id = 3 AND sku LIKE 'a%';"
+            ),
+            0
+        );
+    }
+
+    #[test]
+    fn analyzed_distinct_keys_change_the_index_choice() {
+        let db = TestDb::new("analyzed_index_choice");
+        let mut engine = Engine::new(&db.path).expect("engine init");
+        engine
+            .execute_sql("CREATE TABLE orders (id INT PRIMARY KEY, status TEXT, region TEXT);")
+            .expect("create table");
+        engine
+            .execute_sql("CREATE INDEX orders_status ON orders (status);")
+            .expect("create index");
+        engine
+            .execute_sql("CREATE INDEX orders_region ON orders (region);")
+            .expect("create index");
+        let rows = (0..100)
+            .map(|id| format!("({}, 's{}', 'r{}')", id, id % 2, id % 50))
+            .collect::<Vec<_>>()
+            .join(", ");
+        engine
+            .execute_sql(&format!("INSERT INTO orders VALUES {};", rows))
+            .expect("insert rows");
+
+        let sql = "SELECT id FROM orders WHERE status = 's1' AND region = 'r1';";
+        let scan =
+            |engine: &mut Engine| match engine.execute_sql(&format!("EXPLAIN {}", sql)).unwrap() {
+                ReplOutput::Rows { rows, .. } => rows
+                    .iter()
+                    .find_map(|row| match &row.values()[0] {
+                        Value::String(line) if line.contains("IndexScan") => Some(line.clone()),
+                        _ => None,
+                    })
+                    .expect("index scan"),
+                _ => panic!("expected rows output"),
+            };
+        // unanalyzed, both equalities keep a tenth of the rows
+        assert!(
+            scan(&mut engine).contains("using orders_status"),
+            "{}",
+            scan(&mut engine)
+        );
+        // 2 statuses against 50 regions
+        engine
+            .execute_sql("ANALYZE TABLE orders;")
+            .expect("analyze");
+        let analyzed = scan(&mut engine);
+        assert!(analyzed.contains("using orders_region"), "{analyzed}");
+        assert_eq!(count_rows(&mut engine, sql), 2);
+    }
+
+    #[test]
+    fn composite_index_answers_predicates_on_its_leading_columns() {
+        let db = TestDb::new("composite_index_scan");
+        let mut engine = Engine::new(&db.path).expect("engine init");
+        engine
+            .execute_sql(
+                "CREATE TABLE tickets (id INT PRIMARY KEY, order_id INT, ticket_type_id INT);",
+            )
+            .expect("create table");
+        engine
+            .execute_sql("CREATE INDEX tickets_order_type ON tickets (order_id, ticket_type_id);")
+            .expect("create index");
+        engine
+            .execute_sql(
+                "INSERT INTO tickets VALUES (1, 1, 1), (2, 1, 2), (3, 1, 2), (4, 2, 1), \
+                 (5, 2, 2), (6, 1, NULL), (7, NULL, 2), (8, 1, 3);",
+            )
+            .expect("insert rows");
+
+        let ids = |engine: &mut Engine, sql: &str| match engine.execute_sql(sql).expect("select") {
+            ReplOutput::Rows { rows, .. } => rows
+                .iter()
+                .map(|row| row.values()[0].clone())
+                .collect::<Vec<_>>(),
+            _ => panic!("expected rows output"),
+        };
+        let explain = |engine: &mut Engine, sql: &str| {
+            ids(engine, &format!("EXPLAIN {sql}"))
+                .into_iter()
+                .map(|line| match line {
+                    Value::String(line) => line,
+                    other => panic!("unexpected plan value {other:?}"),
+                })
+                .collect::<Vec<_>>()
+        };
+        let int_ids = |values: &[i64]| {
+            values
+                .iter()
+                .map(|id| Value::Integer(*id))
+                .collect::<Vec<_>>()
+        };
+
+        let sql = "SELECT id FROM tickets WHERE ticket_type_id = 2 AND order_id = 1 ORDER BY id;";
+        let plan = explain(&mut engine, sql);
+        assert!(
+            plan.iter().any(
+                |line| line.ends_with("IndexScan: tickets using tickets_order_type (= (1, 2))")
+            ),
+            "{plan:?}"
+        );
+        assert_eq!(ids(&mut engine, sql), int_ids(&[2, 3]));
+
+        // equality on the first column, range on the second
+        let sql = "SELECT id FROM tickets WHERE order_id = 1 AND ticket_type_id >= 2 ORDER BY id;";
+        let plan = explain(&mut engine, sql);
+        assert!(
+            plan.iter()
+                .any(|line| line.contains("using tickets_order_type (>= (1, 2) AND <= (1))")),
+            "{plan:?}"
+        );
+        assert_eq!(ids(&mut engine, sql), int_ids(&[2, 3, 8]));
+        let sql = "SELECT id FROM tickets WHERE order_id = 1 AND ticket_type_id < 2 ORDER BY id;";
+        assert_eq!(ids(&mut engine, sql), int_ids(&[1]));
+        let sql = "SELECT id FROM tickets WHERE order_id = 1 ORDER BY id;";
+        assert_eq!(ids(&mut engine, sql), int_ids(&[1, 2, 3, 6, 8]));
+        let sql = "SELECT id FROM tickets WHERE order_id = 1 AND ticket_type_id BETWEEN 2 AND 3 \
+                   ORDER BY id;";
+        let plan = explain(&mut engine, sql);
+        assert!(
+            plan.iter()
+                .any(|line| line.contains("using tickets_order_type (>= (1, 2) AND <= (1, 3))")),
+            "{plan:?}"
+        );
+        assert_eq!(ids(&mut engine, sql), int_ids(&[2, 3, 8]));
+
+        // an IN list on the first column scans one range per value
+        let sql = "SELECT id FROM tickets WHERE order_id IN (2, 1) AND ticket_type_id > 1 \
+                   ORDER BY id;";
+        let plan = explain(&mut engine, sql);
+        assert!(
+            plan.iter().any(|line| line.ends_with(
+                "using tickets_order_type (> (1, 1) AND <= (1) OR > (2, 1) AND <= (2))"
+            )),
+            "{plan:?}"
+        );
+        assert_eq!(ids(&mut engine, sql), int_ids(&[2, 3, 5, 8]));
+        let sql = "SELECT id FROM tickets WHERE order_id IN (1, 2) AND ticket_type_id IN (1, 3) \
+                   ORDER BY id;";
+        let plan = explain(&mut engine, sql);
+        assert!(
+            plan.iter().any(|line| line.ends_with(
+                "using tickets_order_type (= (1, 1) OR = (1, 3) OR = (2, 1) OR = (2, 3))"
+            )),
+            "{plan:?}"
+        );
+        assert_eq!(ids(&mut engine, sql), int_ids(&[1, 4, 8]));
+
+        // the second column alone is not a prefix of the key
+        let sql = "SELECT id FROM tickets WHERE ticket_type_id = 2 ORDER BY id;";
+        let plan = explain(&mut engine, sql);
+        assert!(
+            plan.iter().any(|line| line.contains("SeqScan: tickets")),
+            "{plan:?}"
+        );
+        assert_eq!(ids(&mut engine, sql), int_ids(&[2, 3, 5, 7]));
+    }
+
+    #[test]
+    fn default_keyword_fills_the_column_default() {
+        let db = TestDb::new("default_keyword");
+        let mut engine = Engine::new(&db.path).expect("engine init");
+        engine
+            .execute_sql(
+                "CREATE TABLE items (id INT PRIMARY KEY, qty INT DEFAULT 5, note TEXT, \
+                 label TEXT DEFAULT 'new');",
+            )
+            .expect("create table");
+        engine
+            .execute_sql("INSERT INTO items (id) VALUES (1);")
+            .expect("insert omitting columns");
+        engine
+            .execute_sql("INSERT INTO items VALUES (2, DEFAULT, DEFAULT, DEFAULT);")
+            .expect("insert with DEFAULT");
+        engine
+            .execute_sql("INSERT INTO items (id, qty, label) VALUES (3, 9, 'old');")
+            .expect("insert");
+        engine
+            .execute_sql(
+                "UPDATE items SET qty = DEFAULT, note = DEFAULT, label = 'x' WHERE id = 3;",
+            )
+            .expect("update with DEFAULT");
+
+        let ReplOutput::Rows { rows, .. } = engine
+            .execute_sql("SELECT qty, note, label FROM items ORDER BY id;")
+            .expect("select")
+        else {
+            panic!("expected rows output");
+        };
+        let rows = rows
+            .iter()
+            .map(|row| row.values().to_vec())
+            .collect::<Vec<_>>();
+        let defaults = vec![
+            Value::Integer(5),
+            Value::Null,
+            Value::String("new".to_string()),
+        ];
+        assert_eq!(rows[0], defaults);
+        assert_eq!(rows[1], defaults);
+        assert_eq!(
+            rows[2],
+            vec![
+                Value::Integer(5),
+                Value::Null,
+                Value::String("x".to_string())
+            ]
+        );
+
+        assert!(engine.execute_sql("SELECT DEFAULT FROM items;").is_err());
+        assert!(
+            engine
+                .execute_sql("INSERT INTO items VALUES (4, DEFAULT, DEFAULT, DEFAULT, DEFAULT);")
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn copy_round_trip_keeps_null_apart_from_empty_string() {
+        let db = TestDb::new("copy_csv");
+        let csv_path = db.path.with_extension("csv");
+        let mut engine = Engine::new(&db.path).expect("engine init");
+        engine
+            .execute_sql("CREATE TABLE notes (id INT PRIMARY KEY, body TEXT);")
+            .expect("create table");
+        engine
+            .execute_sql(r"INSERT INTO notes VALUES (1, NULL), (2, ''), (3, '\N'), (4, 'a,b');")
+            .expect("insert");
+
+        for (suffix, options) in [("", "CSV"), ("_n", r"NULL AS '\N' CSV HEADER")] {
+            let copy = format!("notes_copy{}", suffix);
+            engine
+                .execute_sql(&format!(
+                    "CREATE TABLE {} (id INT PRIMARY KEY, body TEXT);",
+                    copy
+                ))
+                .expect("create copy");
+            let exported = engine
+                .execute_sql(&format!(
+                    "COPY notes TO '{}' {};",
+                    csv_path.display(),
+                    options
+                ))
+                .expect("copy to");
+            assert!(matches!(exported, ReplOutput::Message(ref message) if message == "COPY 4"));
+            engine
+                .execute_sql(&format!(
+                    "COPY {} FROM '{}' {};",
+                    copy,
+                    csv_path.display(),
+                    options
+                ))
+                .expect("copy from");
+
+            let ReplOutput::Rows { rows, .. } = engine
+                .execute_sql(&format!("SELECT id, body FROM {} ORDER BY id;", copy))
+                .expect("select")
+            else {
+                panic!("expected rows output");
+            };
+            let bodies = rows
+                .iter()
+                .map(|row| row.values()[1].clone())
+                .collect::<Vec<_>>();
+            assert_eq!(
+                bodies,
+                vec![
+                    Value::Null,
+                    Value::String(String::new()),
+                    Value::String(r"\N".to_string()),
+                    Value::String("a,b".to_string()),
+                ],
+                "{}",
+                fs::read_to_string(&csv_path).unwrap_or_default()
+            );
+        }
+        let _ = fs::remove_file(csv_path);
+    }
+
+    #[test]
+    fn change_feed_refuses_changes_a_checkpoint_deleted() {
+        let db = TestDb::new("change_feed_truncated");
+        let mut engine = Engine::new(&db.path).expect("engine init");
+        engine
+            .execute_sql("CREATE TABLE notes (id INT PRIMARY KEY, body TEXT);")
+            .expect("create table");
+        let body = "x".repeat(2000);
+        let mut next_id = 0;
+        while !db.path.with_extension("wal.1").exists() {
+            let rows: Vec<String> = (next_id..next_id + 50)
+                .map(|id| format!("({}, '{}')", id, body))
+                .collect();
+            engine
+                .execute_sql(&format!("INSERT INTO notes VALUES {};", rows.join(", ")))
+                .expect("insert rows");
+            next_id += 50;
+        }
+        assert!(!engine.changes_since(0).expect("changes").is_empty());
+
+        engine.checkpoint().expect("checkpoint");
+        let start_lsn = engine.log_manager.start_lsn();
+        assert!(start_lsn > 0, "the first log segment was not deleted");
+        let err = engine.changes_since(0).expect_err("changes were deleted");
+        assert!(
+            format!("{err:#}").contains("no longer in the log"),
+            "{err:#}"
+        );
+
+        // changes logged after the checkpoint are still read
+        let resume_lsn = engine.log_manager.flushed_lsn();
+        engine
+            .execute_sql("INSERT INTO notes VALUES (-1, 'after');")
+            .expect("insert after checkpoint");
+        let events = engine.changes_since(resume_lsn).expect("changes");
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].key, vec![Value::Integer(-1)]);
+    }
+
+    #[test]
+    fn committed_row_changes_appear_on_the_change_feed() {
+        let db = TestDb::new("change_feed");
+        let mut engine = Engine::new(&db.path).expect("engine init");
+        engine
+            .execute_sql("CREATE TABLE orders (id INT PRIMARY KEY, item TEXT, qty INT);")
+            .expect("create table");
+        engine
+            .execute_sql("INSERT INTO orders VALUES (1, 'ticket', 2);")
+            .expect("insert");
+
+        let events = engine.changes_since(0).expect("changes");
+        assert_eq!(events.len(), 1);
+        let insert = &events[0];
+        assert_eq!(insert.table, "orders");
+        assert_eq!(insert.op, ChangeOp::Insert);
+        assert_eq!(insert.key, vec![Value::Integer(1)]);
+        assert_eq!(
+            insert.values,
+            Some(Tuple::new(vec![
+                Value::Integer(1),
+                Value::String("ticket".to_string()),
+                Value::Integer(2),
+            ]))
+        );
+
+        // rolled-back changes, whole or to a savepoint, never appear
+        engine.execute_sql("BEGIN;").expect("begin");
+        engine
+            .execute_sql("INSERT INTO orders VALUES (2, 'poster', 1);")
+            .expect("insert");
+        engine.execute_sql("ROLLBACK;").expect("rollback");
+        engine.execute_sql("BEGIN;").expect("begin");
+        engine
+            .execute_sql("UPDATE orders SET qty = 3 WHERE id = 1;")
+            .expect("update");
+        engine
+            .execute_sql("SAVEPOINT before_delete;")
+            .expect("savepoint");
+        engine
+            .execute_sql("DELETE FROM orders WHERE id = 1;")
+            .expect("delete");
+        engine
+            .execute_sql("ROLLBACK TO SAVEPOINT before_delete;")
+            .expect("rollback to savepoint");
+        engine
+            .execute_sql("INSERT INTO orders VALUES (3, 'badge', 5);")
+            .expect("insert");
+        engine.execute_sql("COMMIT;").expect("commit");
+        engine
+            .execute_sql("DELETE FROM orders WHERE id = 3;")
+            .expect("delete");
+
+        let events = engine
+            .changes_since(insert.commit_lsn + 1)
+            .expect("changes");
+        let summary = events
+            .iter()
+            .map(|event| (event.op, event.key.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            vec![
+                (ChangeOp::Update, vec![Value::Integer(1)]),
+                (ChangeOp::Insert, vec![Value::Integer(3)]),
+                (ChangeOp::Delete, vec![Value::Integer(3)]),
+            ]
+        );
+        assert_eq!(events[0].commit_lsn, events[1].commit_lsn);
+        assert!(events[2].commit_lsn > events[1].commit_lsn);
+        assert_eq!(
+            events[0].values.as_ref().map(|row| row.values()[2].clone()),
+            Some(Value::Integer(3))
+        );
+        assert_eq!(events[2].values, None);
+    }
+
+    #[test]
+    fn dropping_a_table_frees_its_pages_for_reuse() {
+        let db = TestDb::new("drop_reuse");
+        let create_and_fill = |engine: &mut Engine| {
+            engine
+                .execute_sql("CREATE TABLE logs (id INT PRIMARY KEY, body TEXT);")
+                .expect("create table");
+            engine
+                .execute_sql("CREATE INDEX logs_body ON logs (body);")
+                .expect("create index");
+            for batch in 0..4 {
+                let rows = (0..50)
+                    .map(|i| {
+                        let id = batch * 50 + i;
+                        format!("({id}, '{}')", "x".repeat(100) + &id.to_string())
+                    })
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                engine
+                    .execute_sql(&format!("INSERT INTO logs VALUES {rows};"))
+                    .expect("insert rows");
+            }
+        };
+
+        let mut engine = Engine::new(&db.path).expect("engine init");
+        create_and_fill(&mut engine);
+        engine.execute_sql("DROP TABLE logs;").expect("drop table");
+        let file_len = fs::metadata(&db.path).expect("metadata").len();
+        for _ in 0..5 {
+            create_and_fill(&mut engine);
+            engine.execute_sql("DROP TABLE logs;").expect("drop table");
+        }
+        assert_eq!(fs::metadata(&db.path).expect("metadata").len(), file_len);
+
+        // blobs written to reused pages survive recovery, even though the
+        // log still holds the dropped tables' changes to those pages;
+        // reopening reads every blob back to rebuild the primary key
+        let hex = "AB".repeat(6000);
+        engine
+            .execute_sql("CREATE TABLE files (id INT PRIMARY KEY, data BLOB);")
+            .expect("create table");
+        for id in 0..3 {
+            engine
+                .execute_sql(&format!("INSERT INTO files VALUES ({id}, X'{hex}');"))
+                .expect("insert blob");
+        }
+        std::mem::forget(engine);
+        let mut engine = Engine::new(&db.path).expect("engine reopen");
+        match engine
+            .execute_sql("SELECT COUNT(*) FROM files;")
+            .expect("count")
+        {
+            ReplOutput::Rows { rows, .. } => assert_eq!(rows[0].values()[0], Value::Integer(3)),
+            _ => panic!("expected rows output"),
+        }
+    }
+
+    #[test]
+    fn rollback_undoes_schema_changes() {
+        let db = TestDb::new("ddl_rollback");
+        let mut engine = Engine::new(&db.path).expect("engine init");
+        engine
+            .execute_sql("CREATE TABLE t (id INT PRIMARY KEY, name TEXT);")
+            .expect("create table");
+        engine
+            .execute_sql("INSERT INTO t VALUES (1, 'a'), (2, 'b');")
+            .expect("insert rows");
+
+        for sql in [
+            "BEGIN;",
+            "CREATE TABLE x (id INT);",
+            "ALTER TABLE t ADD COLUMN extra INT;",
+            "CREATE INDEX t_name ON t (name);",
+            "GRANT SELECT ON t TO reader;",
+            "ROLLBACK;",
+        ] {
+            engine.execute_sql(sql).expect(sql);
+        }
+        assert!(engine.execute_sql("SELECT id FROM x;").is_err());
+        assert!(engine.execute_sql("SELECT extra FROM t;").is_err());
+        let indexes = engine.index_stats("t").expect("index stats");
+        assert!(indexes.iter().all(|(name, _)| name != "t_name"));
+        engine.set_role(Some("reader"));
+        assert!(engine.execute_sql("SELECT id FROM t;").is_err());
+        engine.set_role(None);
+
+        // a dropped table comes back with its rows and working indexes
+        for sql in ["BEGIN;", "DROP TABLE t;", "ROLLBACK;"] {
+            engine.execute_sql(sql).expect(sql);
+        }
+        assert_eq!(count_rows(&mut engine, "SELECT id FROM t;"), 2);
+        assert_eq!(
+            count_rows(&mut engine, "SELECT name FROM t WHERE id = 2;"),
+            1
+        );
+        assert!(
+            engine
+                .execute_sql("INSERT INTO t VALUES (1, 'again');")
+                .is_err()
+        );
+
+        drop(engine);
+        let mut engine = Engine::new(&db.path).expect("engine reopen");
+        assert!(engine.execute_sql("SELECT id FROM x;").is_err());
+        assert!(engine.execute_sql("SELECT extra FROM t;").is_err());
+        assert_eq!(count_rows(&mut engine, "SELECT id FROM t;"), 2);
+    }
+
+    #[test]
+    fn rolled_back_create_table_frees_its_pages() {
+        let db = TestDb::new("ddl_rollback_pages");
+        let mut engine = Engine::new(&db.path).expect("engine init");
+        let fill = |engine: &mut Engine| {
+            let rows = (0..60)
+                .map(|i| format!("({i}, '{}')", "x".repeat(100)))
+                .collect::<Vec<_>>()
+                .join(", ");
+            for sql in [
+                "BEGIN;".to_string(),
+                "CREATE TABLE scratch (id INT PRIMARY KEY, body TEXT);".to_string(),
+                format!("INSERT INTO scratch VALUES {rows};"),
+                "ROLLBACK;".to_string(),
+            ] {
+                engine.execute_sql(&sql).expect("scratch table");
+            }
+        };
+        fill(&mut engine);
+        let file_len = fs::metadata(&db.path).expect("metadata").len();
+        for _ in 0..2 {
+            fill(&mut engine);
+        }
+        assert_eq!(fs::metadata(&db.path).expect("metadata").len(), file_len);
+
+        assert!(engine.execute_sql("SELECT id FROM scratch;").is_err());
+    }
+
+    #[test]
+    fn savepoints_and_failed_statements_keep_earlier_schema_changes() {
+        let db = TestDb::new("ddl_savepoint");
+        let mut engine = Engine::new(&db.path).expect("engine init");
+        engine
+            .execute_sql("CREATE TABLE t (id INT PRIMARY KEY);")
+            .expect("create table");
+        engine
+            .execute_sql("INSERT INTO t VALUES (1);")
+            .expect("insert row");
+
+        for sql in [
+            "BEGIN;",
+            "ALTER TABLE t ADD COLUMN a INT;",
+            "SAVEPOINT s;",
+            "ALTER TABLE t RENAME TO u;",
+            "CREATE SEQUENCE ids;",
+            "ROLLBACK TO SAVEPOINT s;",
+        ] {
+            engine.execute_sql(sql).expect(sql);
+        }
+        assert_eq!(count_rows(&mut engine, "SELECT a FROM t;"), 1);
+        assert!(engine.execute_sql("SELECT id FROM u;").is_err());
+        assert!(engine.execute_sql("SELECT NEXTVAL('ids');").is_err());
+
+        // a failed statement is undone on its own
+        assert!(engine.execute_sql("CREATE TABLE t (id INT);").is_err());
+        engine
+            .execute_sql("CREATE TABLE y (id INT);")
+            .expect("create table");
+        engine.execute_sql("COMMIT;").expect("commit");
+
+        drop(engine);
+        let mut engine = Engine::new(&db.path).expect("engine reopen");
+        assert_eq!(count_rows(&mut engine, "SELECT a FROM t;"), 1);
+        assert_eq!(count_rows(&mut engine, "SELECT id FROM y;"), 0);
+        assert!(engine.execute_sql("SELECT id FROM u;").is_err());
+    }
+
+    #[test]
+    fn in_subquery_runs_as_a_semi_join_on_the_outer_index() {
+        let db = TestDb::new("semi_join");
+        let mut engine = Engine::new(&db.path).expect("engine init");
+        engine
+            .execute_sql("CREATE TABLE events (id INT PRIMARY KEY, title TEXT, venue TEXT);")
+            .expect("create table");
+        engine
+            .execute_sql("CREATE TABLE tickets (id INT PRIMARY KEY, event_id INT, seat TEXT);")
+            .expect("create table");
+        engine
+            .execute_sql(
+                "INSERT INTO events VALUES (1, 'opera', 'hall'), (2, 'ballet', 'park'), \
+                 (3, 'circus', 'hall'), (4, 'choir', 'hall');",
+            )
+            .expect("insert events");
+        engine
+            .execute_sql(
+                "INSERT INTO tickets VALUES (1, 3, 'A1'), (2, 1, 'A2'), (3, 3, 'B1'), \
+                 (4, NULL, 'B2'), (5, 9, 'C1');",
+            )
+            .expect("insert tickets");
+
+        let ids = |engine: &mut Engine, sql: &str| match engine.execute_sql(sql).expect("select") {
+            ReplOutput::Rows { rows, .. } => rows
+                .iter()
+                .map(|row| row.values()[0].clone())
+                .collect::<Vec<_>>(),
+            _ => panic!("expected rows output"),
+        };
+        let explain = |engine: &mut Engine, sql: &str| {
+            ids(engine, &format!("EXPLAIN {sql}"))
+                .into_iter()
+                .map(|line| match line {
+                    Value::String(line) => line,
+                    other => panic!("unexpected plan value {other:?}"),
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // each event is returned once even though event 3 has two tickets
+        let sql = "SELECT id FROM events WHERE id IN (SELECT event_id FROM tickets) ORDER BY id;";
+        let plan = explain(&mut engine, sql);
+        assert!(
+            plan.iter()
+                .any(|line| line.contains("IndexSemiJoin: events using")),
+            "{plan:?}"
+        );
+        assert_eq!(
+            ids(&mut engine, sql),
+            vec![Value::Integer(1), Value::Integer(3)]
+        );
+
+        // a non-indexed key falls back to a hash set of the subquery's values
+        let sql = "SELECT id FROM events WHERE venue IN (SELECT seat FROM tickets) \
+                   OR id = 0 ORDER BY id;";
+        assert_eq!(ids(&mut engine, sql), Vec::<Value>::new());
+        engine
+            .execute_sql("INSERT INTO tickets VALUES (6, 2, 'park');")
+            .expect("insert ticket");
+        let sql = "SELECT id FROM events WHERE venue IN (SELECT seat FROM tickets) ORDER BY id;";
+        let plan = explain(&mut engine, sql);
+        assert!(
+            plan.iter().any(|line| line.contains("HashSemiJoin")),
+            "{plan:?}"
+        );
+        assert_eq!(ids(&mut engine, sql), vec![Value::Integer(2)]);
+
+        // the other conjuncts still filter the joined rows
+        let sql = "SELECT id FROM events WHERE venue = 'hall' AND \
+                   id IN (SELECT event_id FROM tickets WHERE seat LIKE 'B%') ORDER BY id;";
+        assert_eq!(ids(&mut engine, sql), vec![Value::Integer(3)]);
+
+        // NOT IN is UNKNOWN for every row once the subquery returns a NULL
+        let sql = "SELECT id FROM events WHERE id NOT IN (SELECT event_id FROM tickets);";
+        assert_eq!(ids(&mut engine, sql), Vec::<Value>::new());
+        let sql = "SELECT id FROM events WHERE id NOT IN \
+                   (SELECT event_id FROM tickets WHERE event_id IS NOT NULL) ORDER BY id;";
+        assert_eq!(ids(&mut engine, sql), vec![Value::Integer(4)]);
+        assert!(
+            engine
+                .execute_sql(
+                    "SELECT id FROM events WHERE id IN (SELECT id, event_id FROM tickets);"
+                )
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn order_by_and_group_by_accept_select_list_positions() {
+        let db = TestDb::new("ordinals");
+        let mut engine = Engine::new(&db.path).expect("engine init");
+        engine
+            .execute_sql("CREATE TABLE events (id INT PRIMARY KEY, title TEXT, venue TEXT);")
+            .expect("create table");
+        engine
+            .execute_sql(
+                "INSERT INTO events VALUES (1, 'opera', 'hall'), (2, 'ballet', 'park'), \
+                 (3, 'circus', 'hall');",
+            )
+            .expect("insert rows");
+        let values = |engine: &mut Engine, sql: &str| match engine.execute_sql(sql).expect(sql) {
+            ReplOutput::Rows { rows, .. } => rows
+                .iter()
+                .map(|row| row.values().to_vec())
+                .collect::<Vec<_>>(),
+            _ => panic!("expected rows output"),
+        };
+
+        let titles = values(&mut engine, "SELECT id, title FROM events ORDER BY 2;");
+        assert_eq!(
+            titles.iter().map(|row| row[0].clone()).collect::<Vec<_>>(),
+            vec![Value::Integer(2), Value::Integer(3), Value::Integer(1)]
+        );
+        assert_eq!(
+            values(
+                &mut engine,
+                "SELECT venue, COUNT(*) FROM events GROUP BY 1 ORDER BY 2 DESC, 1;"
+            ),
+            vec![
+                vec![Value::String("hall".to_string()), Value::Integer(2)],
+                vec![Value::String("park".to_string()), Value::Integer(1)],
+            ]
+        );
+        assert_eq!(
+            values(
+                &mut engine,
+                "SELECT id * -1 FROM events ORDER BY 1 LIMIT 1;"
+            ),
+            vec![vec![Value::Integer(-3)]]
+        );
+        assert!(
+            engine
+                .execute_sql("SELECT id FROM events ORDER BY 2;")
+                .is_err()
+        );
+        assert!(
+            engine
+                .execute_sql("SELECT * FROM events ORDER BY 1;")
+                .is_err()
+        );
+        assert!(
+            engine
+                .execute_sql("SELECT COUNT(*) FROM events GROUP BY 1;")
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn datetime_columns_store_canonical_text() {
+        let db = TestDb::new("datetime_columns");
+        let mut engine = Engine::new(&db.path).expect("engine init");
+        engine
+            .execute_sql("CREATE TABLE slots (id INT PRIMARY KEY, starts DATETIME, day DATE);")
+            .expect("create table");
+        engine
+            .execute_sql(
+                "INSERT INTO slots VALUES (1, '2025-6-10 9:00', '2025-6-10'), (2, '2025-06-09 18:30:00', '2025-06-09');",
+            )
+            .expect("insert rows");
+        let err = engine
+            .execute_sql("INSERT INTO slots VALUES (3, '2025-02-30 10:00', NULL);")
+            .expect_err("invalid date");
+        assert!(err.to_string().contains("starts DATETIME"), "{err}");
+        engine
+            .execute_sql("UPDATE slots SET starts = '2025-6-8 7:5' WHERE id = 2;")
+            .expect("update");
+        assert!(
+            engine
+                .execute_sql("UPDATE slots SET day = '2025-06-08 07:05' WHERE id = 2;")
+                .is_err()
+        );
+        drop(engine);
+
+        let mut engine = Engine::new(&db.path).expect("reopen");
+        assert!(
+            engine
+                .execute_sql("INSERT INTO slots VALUES (4, 'soon', NULL);")
+                .is_err()
+        );
+        let starts = match engine
+            .execute_sql("SELECT starts FROM slots ORDER BY starts;")
+            .unwrap()
+        {
+            ReplOutput::Rows { rows, .. } => rows
+                .iter()
+                .map(|row| row.values()[0].clone())
+                .collect::<Vec<_>>(),
+            _ => panic!("expected rows output"),
+        };
+        assert_eq!(
+            starts,
+            vec![
+                Value::String("2025-06-08 07:05:00".to_string()),
+                Value::String("2025-06-10 09:00:00".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn sum_and_avg_stay_exact_past_i64() {
+        let db = TestDb::new("wide_sum");
+        let mut engine = Engine::new(&db.path).expect("engine init");
+        engine
+            .execute_sql("CREATE TABLE sales (id INT PRIMARY KEY, region TEXT, price BIGINT);")
+            .expect("create table");
+        engine
+            .execute_sql(
+                "INSERT INTO sales VALUES (1, 'eu', 9223372036854775807), (2, 'eu', 9223372036854775807), (3, 'us', 3), (4, 'us', 4), (5, 'eu', 1), (6, 'asia', 9223372036854775807), (7, 'asia', 9223372036854775807), (8, 'asia', -9223372036854775807);",
+            )
+            .expect("insert rows");
+
+        let rows = |engine: &mut Engine, sql: &str| match engine.execute_sql(sql).unwrap() {
+            ReplOutput::Rows { rows, .. } => rows
+                .iter()
+                .map(|row| row.values().to_vec())
+                .collect::<Vec<_>>(),
+            _ => panic!("expected rows output"),
+        };
+        // i64::MAX + i64::MAX + 1 has no exact REAL, so it is an error
+        let err = engine
+            .execute_sql("SELECT SUM(price) FROM sales WHERE region = 'eu';")
+            .expect_err("sum past i64");
+        assert!(err.to_string().contains("integer overflow in SUM"), "{err}");
+        assert_eq!(
+            rows(
+                &mut engine,
+                "SELECT AVG(price) FROM sales WHERE region = 'eu';"
+            ),
+            vec![vec![Value::Float(6_148_914_691_236_517_205.0)]]
+        );
+        // a running sum may leave the range as long as the total is back in
+        assert_eq!(
+            rows(
+                &mut engine,
+                "SELECT SUM(price) FROM sales WHERE region = 'asia';"
+            ),
+            vec![vec![Value::Integer(i64::MAX)]]
+        );
+        assert_eq!(
+            rows(
+                &mut engine,
+                "SELECT SUM(price), AVG(price), COUNT(*) FROM sales WHERE region = 'us';"
+            ),
+            vec![vec![
+                Value::Integer(7),
+                Value::Float(3.5),
+                Value::Integer(2)
+            ]]
+        );
+        assert_eq!(
+            rows(
+                &mut engine,
+                "SELECT SUM(price), COUNT(*) FROM sales WHERE region = 'none';"
+            ),
+            vec![vec![Value::Null, Value::Integer(0)]]
+        );
+    }
+
+    #[test]
+    fn division_by_zero_errors_unless_guarded() {
+        let db = TestDb::new("division_by_zero");
+        let mut engine = Engine::new(&db.path).expect("engine init");
+        engine
+            .execute_sql("CREATE TABLE ticket_types (id INT PRIMARY KEY, sold INT, capacity INT);")
+            .expect("create table");
+        engine
+            .execute_sql("INSERT INTO ticket_types VALUES (1, 5, 10), (2, 0, 0);")
+            .expect("insert rows");
+
+        let err = engine
+            .execute_sql("SELECT sold / capacity FROM ticket_types;")
+            .expect_err("division by zero");
+        assert!(matches!(
+            err.downcast_ref::<query::execution::ExecutionError>(),
+            Some(query::execution::ExecutionError::DivisionByZero)
+        ));
+
+        match engine
+            .execute_sql(
+                "SELECT COALESCE(sold / NULLIF(capacity, 0), 0) FROM ticket_types ORDER BY id;",
+            )
+            .expect("guarded division")
+        {
+            ReplOutput::Rows { rows, .. } => {
+                let values = rows
+                    .iter()
+                    .map(|row| row.values()[0].clone())
+                    .collect::<Vec<_>>();
+                assert_eq!(values, vec![Value::Float(0.5), Value::Integer(0)]);
+            }
+            _ => panic!("expected rows output"),
+        }
+    }
+
+    #[test]
+    fn validate_resolves_statements_without_executing() {
+        let db = TestDb::new("validate_only");
+        let mut engine = Engine::new(&db.path).expect("engine init");
+        engine
+            .execute_sql("CREATE TABLE events (id INT PRIMARY KEY, name TEXT);")
+            .expect("create table");
+
+        let err = engine
+            .validate("SELECT nope FROM events")
+            .expect_err("unknown column");
+        assert!(err.to_string().contains("column nope not found"), "{err}");
+        assert!(engine.validate("SELECT name FROM missing").is_err());
+        assert!(engine.validate("UPDATE events SET nope = 1").is_err());
+        assert!(
+            engine
+                .validate("INSERT INTO events VALUES ('one', 'launch')")
+                .is_err()
+        );
+        assert!(engine.validate("CREATE TABLE events (id INT)").is_err());
+
+        engine
+            .validate("INSERT INTO events VALUES (1, 'launch')")
+            .expect("valid insert");
+        engine
+            .validate("DELETE FROM events WHERE id = 1")
+            .expect("valid delete");
+        assert_eq!(count_rows(&mut engine, "SELECT * FROM events;"), 0);
+    }
+
+    #[test]
+    fn select_without_from_returns_one_row() {
+        let db = TestDb::new("select_without_from");
+        let mut engine = Engine::new(&db.path).expect("engine init");
+
+        match engine
+            .execute_sql("SELECT 1+1 AS two, 'x';")
+            .expect("select")
+        {
+            ReplOutput::Rows { schema, rows } => {
+                assert_eq!(schema.fields[0].name, "two");
+                assert_eq!(rows.len(), 1);
+                assert_eq!(
+                    rows[0].values(),
+                    &[Value::Integer(2), Value::String("x".to_string())]
+                );
+            }
+            _ => panic!("expected rows output"),
+        }
+        match engine
+            .execute_sql("SELECT CURRENT_TIMESTAMP;")
+            .expect("select")
+        {
+            ReplOutput::Rows { rows, .. } => {
+                assert!(matches!(rows[0].values()[0], Value::Timestamp(secs) if secs > 0))
+            }
+            _ => panic!("expected rows output"),
+        }
+        assert_eq!(count_rows(&mut engine, "SELECT 1 WHERE 1 = 0;"), 0);
+    }
+
+    #[test]
+    fn insert_select_copies_query_rows() {
+        let db = TestDb::new("insert_select");
+        let mut engine = Engine::new(&db.path).expect("engine init");
+        engine
+            .execute_sql("CREATE TABLE tags (id INT PRIMARY KEY, name TEXT);")
+            .expect("create table");
+
+        match engine
+            .execute_sql(
+                "INSERT INTO tags SELECT * FROM (VALUES (1, 'a'), (2, 'b')) AS v(id, name);",
+            )
+            .expect("insert from values")
+        {
+            ReplOutput::Message(message) => assert_eq!(message, "INSERT 0 2"),
+            _ => panic!("expected message output"),
+        }
+        engine
+            .execute_sql("INSERT INTO tags (id, name) SELECT id + 10, name FROM tags;")
+            .expect("insert from own table");
+        assert_eq!(count_rows(&mut engine, "SELECT * FROM tags;"), 4);
+        assert_eq!(
+            count_rows(&mut engine, "SELECT * FROM tags WHERE id = 12;"),
+            1
+        );
+        assert!(
+            engine
+                .execute_sql("INSERT INTO tags SELECT id FROM tags;")
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn insert_returning_shows_generated_ids_and_defaults() {
+        let db = TestDb::new("insert_returning");
+        let mut engine = Engine::new(&db.path).expect("engine init");
+        engine
+            .execute_sql(
+                "CREATE TABLE events (id INTEGER PRIMARY KEY AUTOINCREMENT, \
+                 title TEXT NOT NULL, status TEXT DEFAULT 'draft');",
+            )
+            .expect("create table");
+
+        let ReplOutput::Rows { schema, rows } = engine
+            .execute_sql("INSERT INTO events (title) VALUES ('Concert'), ('Talk') RETURNING *;")
+            .expect("insert returning")
+        else {
+            panic!("expected rows output");
+        };
+        let names: Vec<_> = schema
+            .fields
+            .iter()
+            .map(|field| field.name.as_str())
+            .collect();
+        assert_eq!(names, ["id", "title", "status"]);
+        let row = |id, title: &str| {
+            vec![
+                Value::Integer(id),
+                Value::String(title.to_string()),
+                Value::String("draft".to_string()),
+            ]
+        };
+        let rows: Vec<_> = rows.iter().map(|tuple| tuple.values().to_vec()).collect();
+        assert_eq!(rows, [row(1, "Concert"), row(2, "Talk")]);
+
+        let ReplOutput::Rows { schema, rows } = engine
+            .execute_sql(
+                "INSERT INTO events (title) VALUES ('Party') RETURNING id, status AS state;",
+            )
+            .expect("insert returning columns")
+        else {
+            panic!("expected rows output");
+        };
+        assert_eq!(schema.fields[1].name, "state");
+        assert_eq!(
+            rows[0].values(),
+            [Value::Integer(3), Value::String("draft".to_string())]
+        );
+        assert!(
+            engine
+                .execute_sql("INSERT INTO events (title) VALUES ('Quiz') RETURNING missing;")
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn execute_prepared_binds_parameters_by_position() {
+        let db = TestDb::new("execute_prepared");
+        let mut engine = Engine::new(&db.path).expect("engine init");
+        engine
+            .execute_sql("CREATE TABLE people (id INT PRIMARY KEY, name TEXT);")
+            .expect("create table");
+        engine
+            .execute_prepared(
+                "INSERT INTO people VALUES (?, ?), (?, ?);",
+                &[
+                    Value::Integer(1),
+                    Value::String("O'Brien".to_string()),
+                    Value::Integer(2),
+                    Value::String("'; DROP TABLE people; --".to_string()),
+                ],
+            )
+            .expect("insert with parameters");
+
+        let names = |engine: &mut Engine, sql: &str, params: &[Value]| match engine
+            .execute_prepared(sql, params)
+            .expect("select with parameters")
+        {
+            ReplOutput::Rows { rows, .. } => rows
+                .iter()
+                .map(|row| row.values()[0].clone())
+                .collect::<Vec<_>>(),
+            _ => panic!("expected rows output"),
+        };
+        assert_eq!(
+            names(
+                &mut engine,
+                "SELECT id FROM people WHERE name = ?;",
+                &[Value::String("O'Brien".to_string())]
+            ),
+            [Value::Integer(1)]
+        );
+        assert_eq!(
+            names(
+                &mut engine,
+                "SELECT name FROM people WHERE id = ?;",
+                &[Value::Integer(2)]
+            ),
+            [Value::String("'; DROP TABLE people; --".to_string())]
+        );
+
+        let plan = names(
+            &mut engine,
+            "EXPLAIN SELECT name FROM people WHERE id = ?;",
+            &[Value::Integer(2)],
+        );
+        assert!(
+            plan.iter()
+                .any(|line| matches!(line, Value::String(line) if line.contains("IndexScan"))),
+            "{plan:?}"
+        );
+        assert!(
+            engine
+                .execute_prepared("SELECT name FROM people WHERE id = ?;", &[])
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn transaction_closure_commits_on_ok_and_rolls_back_on_err() {
+        let db = TestDb::new("transaction_closure");
+        let mut engine = Engine::new(&db.path).expect("engine init");
+        engine
+            .execute_sql("CREATE TABLE seats (id INT PRIMARY KEY, holder TEXT);")
+            .expect("create table");
+
+        let booked = engine
+            .transaction(|tx| {
+                tx.execute("INSERT INTO seats VALUES (1, 'ann'), (2, NULL);")?;
+                let updated = tx.execute("UPDATE seats SET holder = 'bob' WHERE id = 2;")?;
+                let rows = tx.query("SELECT holder FROM seats ORDER BY id;")?;
+                Ok((updated, rows))
+            })
+            .expect("committed transaction");
+        assert_eq!(
+            booked,
+            (
+                1,
+                vec![
+                    vec![Value::String("ann".to_string())],
+                    vec![Value::String("bob".to_string())],
+                ]
+            )
+        );
+
+        let error = engine
+            .transaction::<()>(|tx| {
+                tx.execute("DELETE FROM seats WHERE id = 1;")?;
+                tx.execute("INSERT INTO seats VALUES (3, 'cy');")?;
+                bail!("payment declined")
+            })
+            .expect_err("failed transaction");
+        assert_eq!(error.to_string(), "payment declined");
+        assert!(
+            engine
+                .transaction(|tx| tx.execute("SELECT * FROM seats;"))
+                .is_err()
+        );
+
+        let ids = engine
+            .transaction(|tx| tx.query("SELECT id FROM seats ORDER BY id;"))
+            .expect("read back");
+        assert_eq!(ids, vec![vec![Value::Integer(1)], vec![Value::Integer(2)]]);
+    }
+
+    #[test]
+    fn insert_or_ignore_and_or_replace_resolve_unique_conflicts() {
+        let db = TestDb::new("insert_or");
+        let mut engine = Engine::new(&db.path).expect("engine init");
+        engine
+            .execute_sql("CREATE TABLE users (id INT PRIMARY KEY, email TEXT UNIQUE, name TEXT);")
+            .expect("create table");
+        engine
+            .execute_sql("INSERT INTO users VALUES (1, 'a@x', 'Ann'), (2, 'b@x', 'Bob');")
+            .expect("insert rows");
+        let mut message = |sql: &str| match engine.execute_sql(sql).expect("insert") {
+            ReplOutput::Message(message) => message,
+            _ => panic!("expected message output"),
+        };
+
+        assert_eq!(
+            message("INSERT OR IGNORE INTO users VALUES (1, 'c@x', 'Cid');"),
+            "INSERT 0 0"
+        );
+        assert_eq!(
+            message("INSERT OR IGNORE INTO users VALUES (3, 'c@x', 'Cid'), (4, 'c@x', 'Dan');"),
+            "INSERT 0 1"
+        );
+        // the new row takes the id of one row and the email of another
+        assert_eq!(
+            message("INSERT OR REPLACE INTO users VALUES (1, 'b@x', 'Eve');"),
+            "INSERT 0 1"
+        );
+        assert!(
+            engine
+                .execute_sql("INSERT INTO users VALUES (1, 'z@x', 'Fay');")
+                .is_err()
+        );
+        match engine
+            .execute_sql("SELECT id, email, name FROM users ORDER BY id;")
+            .expect("select")
+        {
+            ReplOutput::Rows { rows, .. } => assert_eq!(
+                rows.iter()
+                    .map(|row| row.values().to_vec())
+                    .collect::<Vec<_>>(),
+                vec![
+                    vec![
+                        Value::Integer(1),
+                        Value::String("b@x".to_string()),
+                        Value::String("Eve".to_string()),
+                    ],
+                    vec![
+                        Value::Integer(3),
+                        Value::String("c@x".to_string()),
+                        Value::String("Cid".to_string()),
+                    ],
+                ]
+            ),
+            _ => panic!("expected rows output"),
+        }
+        assert_eq!(
+            count_rows(&mut engine, "SELECT * FROM users WHERE email = 'a@x';"),
+            0
+        );
+    }
+
+    #[test]
+    fn like_escapes_match_wildcards_literally() {
+        let db = TestDb::new("like_escape");
+        let mut engine = Engine::new(&db.path).expect("engine init");
+        engine
+            .execute_sql("CREATE TABLE promos (id INT PRIMARY KEY, title TEXT);")
+            .expect("create table");
+        engine
+            .execute_sql(
+                "INSERT INTO promos VALUES (1, '50% off'), (2, '500 seats'), (3, 'early_bird');",
+            )
+            .expect("insert rows");
+
+        assert_eq!(
+            count_rows(&mut engine, "SELECT * FROM promos WHERE title LIKE '50%';"),
+            2
+        );
+        assert_eq!(
+            count_rows(
+                &mut engine,
+                "SELECT * FROM promos WHERE title LIKE '50\\%%';"
+            ),
+            1
+        );
+        assert_eq!(
+            count_rows(
+                &mut engine,
+                "SELECT * FROM promos WHERE title LIKE '50!%%' ESCAPE '!';"
+            ),
+            1
+        );
+        assert_eq!(
+            count_rows(
+                &mut engine,
+                "SELECT * FROM promos WHERE title NOT LIKE '%\\_%';"
+            ),
+            2
+        );
+        assert!(
+            engine
+                .execute_sql("SELECT * FROM promos WHERE title LIKE '50!' ESCAPE '!';")
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn tablesample_returns_a_reproducible_fraction_of_rows() {
+        let db = TestDb::new("tablesample");
+        let mut engine = Engine::new(&db.path).expect("engine init");
+        engine
+            .execute_sql("CREATE TABLE events (id INT, name TEXT);")
+            .expect("create table");
+        let rows: Vec<String> = (0..2000)
+            .map(|id| format!("({}, 'event {}')", id, id))
+            .collect();
+        engine
+            .execute_sql(&format!("INSERT INTO events VALUES {};", rows.join(", ")))
+            .expect("insert rows");
+
+        let mut sample = |sql: &str| match engine.execute_sql(sql).expect("sample") {
+            ReplOutput::Rows { rows, .. } => rows,
+            _ => panic!("expected rows output"),
+        };
+        let sql = "SELECT id FROM events TABLESAMPLE BERNOULLI (10 PERCENT) REPEATABLE (42);";
+        let first = sample(sql);
+        assert!((100..=300).contains(&first.len()), "{}", first.len());
+        assert_eq!(sample(sql), first);
+        assert_ne!(
+            sample("SELECT id FROM events TABLESAMPLE BERNOULLI (10 PERCENT) REPEATABLE (7);"),
+            first
+        );
+
+        let pages = "SELECT id FROM events AS e TABLESAMPLE SYSTEM (50) REPEATABLE (3) \
+                     WHERE e.id >= 0;";
+        let sampled = sample(pages);
+        assert!(sampled.len() < 2000);
+        assert_eq!(sample(pages), sampled);
+        assert!(sample("SELECT * FROM events TABLESAMPLE (0 PERCENT);").is_empty());
+        assert_eq!(
+            sample("SELECT * FROM events TABLESAMPLE (100);").len(),
+            2000
+        );
+
+        let plan = sample("EXPLAIN SELECT COUNT(*) FROM events TABLESAMPLE (10) REPEATABLE (1);");
+        assert!(
+            plan.iter().any(|row| matches!(
+                &row.values()[0],
+                Value::String(line)
+                    if line.trim() == "SeqScan: events TABLESAMPLE BERNOULLI (10 PERCENT) REPEATABLE (1)"
+            )),
+            "{plan:?}"
+        );
+        assert!(
+            engine
+                .execute_sql("SELECT * FROM events TABLESAMPLE (150 PERCENT);")
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn sequences_never_reissue_values_across_restarts() {
+        let db = TestDb::new("sequences");
+        let next = |engine: &mut Engine, sql: &str| match engine.execute_sql(sql).expect("nextval")
+        {
+            ReplOutput::Rows { rows, .. } => rows[0].values()[0].clone(),
+            _ => panic!("expected rows output"),
+        };
+        {
+            let mut engine = Engine::new(&db.path).expect("engine init");
+            engine
+                .execute_sql("CREATE SEQUENCE order_ids INCREMENT BY 10 START WITH 100;")
+                .expect("create sequence");
+            engine
+                .execute_sql("CREATE TABLE orders (id INT PRIMARY KEY, note TEXT);")
+                .expect("create table");
+            assert!(engine.execute_sql("SELECT CURRVAL('order_ids');").is_err());
+            assert_eq!(
+                next(&mut engine, "SELECT NEXTVAL('order_ids');"),
+                Value::Integer(100)
+            );
+            engine
+                .execute_sql(
+                    "INSERT INTO orders VALUES (NEXTVAL('order_ids'), 'a'), \
+                     (NEXTVAL('order_ids'), 'b');",
+                )
+                .expect("insert with nextval");
+            assert_eq!(
+                next(&mut engine, "SELECT CURRVAL('order_ids');"),
+                Value::Integer(120)
+            );
+
+            // values taken by a rolled back statement are not handed out again
+            engine.execute_sql("BEGIN;").expect("begin");
+            assert_eq!(
+                next(&mut engine, "SELECT NEXTVAL('ORDER_IDS');"),
+                Value::Integer(130)
+            );
+            engine.execute_sql("ROLLBACK;").expect("rollback");
+            assert!(engine.execute_sql("CREATE SEQUENCE order_ids;").is_err());
+            // skip Drop, as a crash would, so only what NEXTVAL persisted
+            // survives
+            std::mem::forget(engine);
+        }
+
+        let mut engine = Engine::new(&db.path).expect("engine reopen");
+        assert!(engine.execute_sql("SELECT CURRVAL('order_ids');").is_err());
+        assert_eq!(
+            next(&mut engine, "SELECT NEXTVAL('order_ids');"),
+            Value::Integer(140)
+        );
+        engine
+            .execute_sql("DROP SEQUENCE order_ids;")
+            .expect("drop sequence");
+        assert!(engine.execute_sql("SELECT NEXTVAL('order_ids');").is_err());
+        engine
+            .execute_sql("DROP SEQUENCE IF EXISTS order_ids;")
+            .expect("drop missing sequence");
+    }
+
+    #[test]
+    fn random_repeats_its_values_under_the_same_seed() {
+        let db = TestDb::new("random_seed");
+        let mut engine = Engine::new(&db.path).expect("engine init");
+        let draw = |engine: &mut Engine| {
+            (0..3)
+                .map(
+                    |_| match engine.execute_sql("SELECT RANDOM();").expect("random") {
+                        ReplOutput::Rows { rows, .. } => match rows[0].values()[0] {
+                            Value::Float(value) => {
+                                assert!((0.0..1.0).contains(&value), "{}", value);
+                                value.to_bits()
+                            }
+                            ref other => panic!("expected a float, got {:?}", other),
+                        },
+                        _ => panic!("expected rows output"),
+                    },
+                )
+                .collect::<Vec<_>>()
+        };
+
+        engine.set_random_seed(Some(42));
+        let seeded = draw(&mut engine);
+        engine.set_random_seed(Some(42));
+        assert_eq!(draw(&mut engine), seeded);
+        engine.set_random_seed(Some(7));
+        assert_ne!(draw(&mut engine), seeded);
+
+        engine.set_random_seed(None);
+        let unseeded = draw(&mut engine);
+        engine.set_random_seed(None);
+        assert_ne!(draw(&mut engine), unseeded);
+    }
+
+    #[test]
+    fn work_mem_decides_whether_a_sort_spills() {
+        let db = TestDb::new("work_mem");
+        let mut engine = Engine::new(&db.path).expect("engine init");
+        engine
+            .execute_sql("CREATE TABLE events (id INT PRIMARY KEY, label TEXT);")
+            .expect("create table");
+        let values = (0..2_000)
+            .map(|id| format!("({}, 'event {}')", id, (id * 7_919) % 2_000))
+            .collect::<Vec<_>>()
+            .join(", ");
+        engine
+            .execute_sql(&format!("INSERT INTO events VALUES {};", values))
+            .expect("insert rows");
+        let sorted = |engine: &mut Engine| match engine
+            .execute_sql("SELECT id, label FROM events ORDER BY label DESC, id;")
+            .expect("select")
+        {
+            ReplOutput::Rows { rows, .. } => rows,
+            _ => panic!("expected rows output"),
+        };
+
+        let in_memory = sorted(&mut engine);
+        assert_eq!(in_memory.len(), 2_000);
+        assert_eq!(engine.temp_pages_written(), 0);
+
+        engine
+            .execute_sql("SET work_mem = '64kB';")
+            .expect("lower work_mem");
+        assert_eq!(engine.work_mem(), 64 * 1024);
+        assert_eq!(sorted(&mut engine), in_memory);
+        let spilled = engine.temp_pages_written();
+        assert!(spilled > 0);
+
+        engine
+            .execute_sql("SET work_mem = '64MB';")
+            .expect("raise work_mem");
+        assert_eq!(sorted(&mut engine), in_memory);
+        assert_eq!(engine.temp_pages_written(), spilled);
+
+        let err = engine
+            .execute_sql("SET work_mem = '64 parsecs';")
+            .expect_err("unknown unit");
+        assert!(err.to_string().contains("unit"), "{}", err);
+        assert_eq!(engine.work_mem(), 64 * 1024 * 1024);
+    }
+
+    #[test]
+    fn roles_need_granted_privileges_for_tables_and_columns() {
+        let db = TestDb::new("privileges");
+        let denied = |engine: &mut Engine, sql: &str| {
+            let err = engine
+                .execute_sql(sql)
+                .expect_err("statement should be denied");
+            assert!(err.to_string().contains("permission denied"), "{}", err);
+        };
+        {
+            let mut engine = Engine::new(&db.path).expect("engine init");
+            engine
+                .execute_sql("CREATE TABLE users (id INT PRIMARY KEY, name TEXT, email TEXT);")
+                .expect("create table");
+            engine
+                .execute_sql("INSERT INTO users VALUES (1, 'ada', 'ada@example.com');")
+                .expect("insert");
+            engine
+                .execute_sql("GRANT SELECT (id, name), UPDATE (name) ON users TO analyst;")
+                .expect("grant");
+
+            engine.set_role(Some("analyst"));
+            assert_eq!(
+                count_rows(&mut engine, "SELECT u.name FROM users u WHERE id = 1;"),
+                1
+            );
+            assert_eq!(count_rows(&mut engine, "SELECT COUNT(*) FROM users;"), 1);
+            denied(&mut engine, "SELECT * FROM users;");
+            denied(
+                &mut engine,
+                "SELECT name FROM users WHERE email LIKE '%@%';",
+            );
+            assert!(engine.validate("SELECT email FROM users;").is_err());
+            engine
+                .execute_sql("UPDATE users SET name = 'ada l' WHERE id = 1;")
+                .expect("granted update");
+            denied(&mut engine, "UPDATE users SET email = 'x' WHERE id = 1;");
+            denied(&mut engine, "DELETE FROM users;");
+            denied(
+                &mut engine,
+                "INSERT INTO users VALUES (2, 'bob', 'bob@example.com');",
+            );
+            denied(&mut engine, "GRANT SELECT ON users TO analyst;");
+            denied(&mut engine, "DROP TABLE users;");
+
+            engine.set_role(None);
+            engine
+                .execute_sql("REVOKE SELECT (name) ON users FROM analyst;")
+                .expect("revoke column");
+        }
+
+        // grants survive a restart
+        let mut engine = Engine::new(&db.path).expect("engine reopen");
+        engine.set_role(Some("ANALYST"));
+        assert_eq!(count_rows(&mut engine, "SELECT id FROM users;"), 1);
+        denied(&mut engine, "SELECT name FROM users;");
+        engine.set_role(Some("guest"));
+        denied(&mut engine, "SELECT COUNT(*) FROM users;");
+    }
+
+    #[test]
+    fn insert_or_replace_needs_delete_privilege() {
+        let db = TestDb::new("replace_privileges");
+        let mut engine = Engine::new(&db.path).expect("engine init");
+        engine
+            .execute_sql("CREATE TABLE users (id INT PRIMARY KEY, name TEXT);")
+            .expect("create table");
+        engine
+            .execute_sql("INSERT INTO users VALUES (1, 'ada');")
+            .expect("insert");
+        engine
+            .execute_sql("GRANT INSERT ON users TO loader;")
+            .expect("grant");
+
+        engine.set_role(Some("loader"));
+        engine
+            .execute_sql("INSERT INTO users VALUES (2, 'bob');")
+            .expect("granted insert");
+        let err = engine
+            .execute_sql("INSERT OR REPLACE INTO users VALUES (1, 'mallory');")
+            .expect_err("replace deletes the existing row");
+        assert!(err.to_string().contains("DELETE privilege"), "{}", err);
+
+        engine.set_role(None);
+        let rows = match engine
+            .execute_sql("SELECT name FROM users WHERE id = 1;")
+            .expect("select")
+        {
+            ReplOutput::Rows { rows, .. } => rows,
+            other => panic!("unexpected output: {:?}", other),
+        };
+        assert_eq!(rows[0].values()[0], Value::String("ada".to_string()));
+
+        engine
+            .execute_sql("GRANT DELETE ON users TO loader;")
+            .expect("grant delete");
+        engine.set_role(Some("loader"));
+        engine
+            .execute_sql("INSERT OR REPLACE INTO users VALUES (1, 'ada l');")
+            .expect("replace with delete granted");
+    }
+
+    /// Engine holding `users (id, name, email)` and `orders (id, user_id,
+    /// total)`, one row each, for the privilege tests.
+    fn privileges_engine(db: &TestDb) -> Engine {
+        let mut engine = Engine::new(&db.path).expect("engine init");
+        for sql in [
+            "CREATE TABLE users (id INT PRIMARY KEY, name TEXT, email TEXT);",
+            "CREATE TABLE orders (id INT PRIMARY KEY, user_id INT, total INT);",
+            "INSERT INTO users VALUES (1, 'ada', 'ada@example.com');",
+            "INSERT INTO orders VALUES (10, 1, 99);",
+        ] {
+            engine.execute_sql(sql).expect("setup");
+        }
+        engine
+    }
+
+    fn assert_denied(engine: &mut Engine, sql: &str) {
+        let err = engine
+            .execute_sql(sql)
+            .expect_err("statement should be denied");
+        assert!(
+            err.to_string().contains("permission denied"),
+            "{sql}: {err}"
+        );
+    }
+
+    #[test]
+    fn update_and_delete_need_select_on_the_columns_they_read() {
+        let db = TestDb::new("privileges_writes");
+        let mut engine = privileges_engine(&db);
+        for sql in [
+            "GRANT SELECT (id), UPDATE (name), DELETE ON users TO clerk;",
+            "GRANT SELECT (user_id) ON orders TO clerk;",
+        ] {
+            engine.execute_sql(sql).expect("grant");
+        }
+
+        engine.set_role(Some("clerk"));
+        engine
+            .execute_sql("UPDATE users SET name = 'ada l' WHERE id = 1;")
+            .expect("granted update");
+        // every assigned column needs UPDATE
+        assert_denied(&mut engine, "UPDATE users SET name = 'x', email = 'x';");
+        // the filter and the new values read columns
+        assert_denied(
+            &mut engine,
+            "UPDATE users SET name = 'x' WHERE email = 'a';",
+        );
+        assert_denied(&mut engine, "UPDATE users SET name = email;");
+        engine
+            .execute_sql(
+                "UPDATE users SET name = 'buyer' FROM orders \
+                 WHERE orders.user_id = users.id;",
+            )
+            .expect("update reading a granted column of FROM");
+        assert_denied(
+            &mut engine,
+            "UPDATE users SET name = 'big' FROM orders \
+             WHERE orders.user_id = users.id AND orders.total > 50;",
+        );
+
+        assert_denied(&mut engine, "DELETE FROM users WHERE email LIKE '%';");
+        assert_denied(&mut engine, "DELETE FROM orders;");
+        assert_denied(
+            &mut engine,
+            "DELETE FROM users USING orders \
+             WHERE orders.user_id = users.id AND orders.total > 50;",
+        );
+        engine
+            .execute_sql("DELETE FROM users USING orders WHERE orders.user_id = users.id;")
+            .expect("delete reading a granted column of USING");
+        engine.set_role(None);
+        assert_eq!(count_rows(&mut engine, "SELECT id FROM users;"), 0);
+    }
+
+    #[test]
+    fn roles_cannot_change_the_schema_or_grants() {
+        let db = TestDb::new("privileges_ddl");
+        let mut engine = privileges_engine(&db);
+        engine
+            .execute_sql("GRANT ALL ON users TO owner;")
+            .expect("grant all");
+
+        engine.set_role(Some("owner"));
+        for sql in [
+            "CREATE TABLE notes (id INT PRIMARY KEY);",
+            "CREATE INDEX users_name ON users (name);",
+            "ALTER TABLE users ADD COLUMN phone TEXT;",
+            "ALTER TABLE users RENAME TO people;",
+            "DROP TABLE users;",
+            "ANALYZE TABLE users;",
+            "GRANT SELECT ON users TO guest;",
+            "REVOKE DELETE ON users FROM owner;",
+        ] {
+            assert_denied(&mut engine, sql);
+        }
+        engine.set_role(None);
+        assert!(engine.table_schema("users").is_some());
+        assert!(engine.table_schema("notes").is_none());
+    }
+
+    #[test]
+    fn grant_and_revoke_change_what_a_role_may_do() {
+        let db = TestDb::new("privileges_grant_revoke");
+        let mut engine = privileges_engine(&db);
+        engine
+            .execute_sql("GRANT ALL ON users TO staff;")
+            .expect("grant all");
+        engine
+            .execute_sql("REVOKE DELETE ON users FROM staff;")
+            .expect("revoke delete");
+
+        engine.set_role(Some("staff"));
+        engine
+            .execute_sql("INSERT INTO users VALUES (2, 'bob', 'bob@example.com');")
+            .expect("insert kept");
+        assert_eq!(count_rows(&mut engine, "SELECT * FROM users;"), 2);
+        assert_denied(&mut engine, "DELETE FROM users WHERE id = 2;");
+
+        // revoking the table revokes its column grants too
+        engine.set_role(None);
+        engine
+            .execute_sql("GRANT SELECT (email) ON users TO staff;")
+            .expect("grant column");
+        engine
+            .execute_sql("REVOKE SELECT ON users FROM staff;")
+            .expect("revoke table");
+        engine.set_role(Some("staff"));
+        assert_denied(&mut engine, "SELECT email FROM users;");
+        assert_denied(&mut engine, "SELECT COUNT(*) FROM users;");
+
+        // a column grant allows only that column, for one role
+        engine.set_role(None);
+        engine
+            .execute_sql("GRANT SELECT (name) ON users TO staff, auditor;")
+            .expect("grant to two roles");
+        engine
+            .execute_sql("REVOKE SELECT (name) ON users FROM auditor;")
+            .expect("revoke one role");
+        engine.set_role(Some("staff"));
+        assert_eq!(count_rows(&mut engine, "SELECT name FROM users;"), 2);
+        assert_denied(&mut engine, "SELECT id FROM users;");
+        engine.set_role(Some("auditor"));
+        assert_denied(&mut engine, "SELECT name FROM users;");
+    }
+
+    #[test]
+    fn reads_through_subqueries_and_joins_need_select() {
+        let db = TestDb::new("privileges_reads");
+        let mut engine = privileges_engine(&db);
+        engine
+            .execute_sql("GRANT SELECT ON users TO analyst;")
+            .expect("grant");
+
+        engine.set_role(Some("analyst"));
+        assert_eq!(count_rows(&mut engine, "SELECT * FROM users;"), 1);
+        for sql in [
+            "SELECT * FROM users WHERE id IN (SELECT user_id FROM orders);",
+            "SELECT * FROM users WHERE id NOT IN (SELECT user_id FROM orders);",
+            "SELECT name, (SELECT COUNT(*) FROM orders) FROM users;",
+            "SELECT * FROM users WHERE id = (SELECT MAX(user_id) FROM orders);",
+            "SELECT u.name FROM users u JOIN orders o ON o.user_id = u.id;",
+            "SELECT * FROM (SELECT user_id FROM orders) AS o;",
+            "EXPLAIN SELECT u.name FROM users u JOIN orders o ON o.user_id = u.id;",
+        ] {
+            assert_denied(&mut engine, sql);
+        }
+
+        // a column grant on the joined table allows reading that column only
+        engine.set_role(None);
+        engine
+            .execute_sql("GRANT SELECT (user_id) ON orders TO analyst;")
+            .expect("grant column");
+        engine.set_role(Some("analyst"));
+        assert_eq!(
+            count_rows(
+                &mut engine,
+                "SELECT * FROM users WHERE id IN (SELECT user_id FROM orders);"
+            ),
+            1
+        );
+        assert_eq!(
+            count_rows(
+                &mut engine,
+                "SELECT u.name FROM users u JOIN orders o ON o.user_id = u.id;"
+            ),
+            1
+        );
+        for sql in [
+            "SELECT u.name, o.total FROM users u JOIN orders o ON o.user_id = u.id;",
+            "SELECT * FROM users u JOIN orders o ON o.user_id = u.id;",
+            "SELECT * FROM users WHERE id IN (SELECT user_id FROM orders WHERE total > 1);",
+            "SELECT name, (SELECT SUM(total) FROM orders) FROM users;",
+            "SELECT * FROM (SELECT * FROM orders) AS o;",
+        ] {
+            assert_denied(&mut engine, sql);
+        }
+    }
+
+    #[test]
+    fn values_rows_cannot_read_tables_through_subqueries() {
+        let db = TestDb::new("privileges_values");
+        let mut engine = Engine::new(&db.path).expect("engine init");
+        for sql in [
+            "CREATE TABLE pub (id INT PRIMARY KEY, note TEXT);",
+            "CREATE TABLE secrets (id INT PRIMARY KEY, secret TEXT);",
+            "INSERT INTO secrets VALUES (1, 'top-secret');",
+            "GRANT INSERT, SELECT ON pub TO guest;",
+        ] {
+            engine.execute_sql(sql).expect("setup");
+        }
+
+        engine.set_role(Some("guest"));
+        assert_denied(&mut engine, "SELECT (SELECT secret FROM secrets);");
+        assert_denied(
+            &mut engine,
+            "SELECT * FROM (VALUES ((SELECT secret FROM secrets))) AS t(x);",
+        );
+        assert_denied(
+            &mut engine,
+            "INSERT INTO pub VALUES (1, (SELECT secret FROM secrets));",
+        );
+        assert_eq!(
+            count_rows(&mut engine, "SELECT * FROM (VALUES (1), (2)) AS t(x);"),
+            2
+        );
+        engine
+            .execute_sql("INSERT INTO pub VALUES (2, (SELECT note FROM pub));")
+            .expect("subquery on a granted table");
+        assert_eq!(count_rows(&mut engine, "SELECT * FROM pub;"), 1);
+    }
+
+    #[test]
+    fn create_table_recovers_from_the_log_after_a_crash() {
+        let db = TestDb::new("ddl_recovery");
+        {
+            let mut engine = Engine::new(&db.path).expect("engine init");
+            engine
+                .execute_sql("CREATE TABLE accounts (id INT PRIMARY KEY, owner TEXT);")
+                .expect("create table");
+            engine
+                .execute_sql("INSERT INTO accounts VALUES (1, 'ada');")
+                .expect("insert");
+            engine
+                .execute_sql("CREATE INDEX accounts_owner ON accounts (owner);")
+                .expect("create index");
+            // crash with no checkpoint while the catalog file is rewritten
+            std::mem::forget(engine);
+            fs::write(db.path.with_extension("catalog"), b"{\"tables\": [").expect("tear file");
+        }
+
+        let mut engine = Engine::new(&db.path).expect("engine reopen");
+        assert_eq!(engine.list_tables(), vec!["accounts".to_string()]);
+        assert_eq!(
+            count_rows(&mut engine, "SELECT * FROM accounts WHERE owner = 'ada';"),
+            1
+        );
+        let catalog = engine.shared_catalog();
+        assert!(
+            catalog
+                .read()
+                .table("accounts")
+                .is_some_and(|table| table.index_for_column("owner").is_some())
+        );
+    }
+
+    fn checkpoint_records(db: &TestDb) -> usize {
+        let mut reader = wal::LogReader::open(db.path.with_extension("wal")).expect("open wal");
+        let mut checkpoints = 0;
+        while let Some(record) = reader.next_record().expect("read wal") {
+            if record.record_type == wal::LogRecordType::Checkpoint {
+                checkpoints += 1;
+            }
+        }
+        checkpoints
+    }
+
+    fn last_checkpoint(db: &TestDb) -> Option<wal::Lsn> {
+        wal::LogReader::open(db.path.with_extension("wal"))
+            .expect("open wal")
+            .last_checkpoint()
+    }
+
+    #[test]
+    fn checkpoint_policy_checkpoints_after_every_hundred_commits() {
+        let db = TestDb::new("checkpoint_commits");
+        let mut engine = Engine::new(&db.path).expect("engine init");
+        engine
+            .execute_sql("CREATE TABLE events (id INT PRIMARY KEY);")
+            .expect("create table");
+        engine.set_checkpoint_policy(CheckpointPolicy {
+            every_commits: Some(100),
+            ..CheckpointPolicy::default()
+        });
+        for id in 0..99 {
+            engine
+                .execute_sql(&format!("INSERT INTO events VALUES ({id});"))
+                .expect("insert");
+        }
+        assert_eq!(last_checkpoint(&db), None);
+
+        engine
+            .execute_sql("INSERT INTO events VALUES (99);")
+            .expect("insert");
+        let first = last_checkpoint(&db).expect("first checkpoint");
+
+        // the first checkpoint's log segments may be deleted by the second,
+        // so checkpoints are told apart by LSN rather than counted
+        for id in 100..199 {
+            engine
+                .execute_sql(&format!("INSERT INTO events VALUES ({id});"))
+                .expect("insert");
+        }
+        assert_eq!(last_checkpoint(&db), Some(first));
+        engine
+            .execute_sql("INSERT INTO events VALUES (199);")
+            .expect("insert");
+        assert!(last_checkpoint(&db).is_some_and(|second| second > first));
+    }
+
+    #[test]
+    fn checkpoint_policy_checkpoints_on_dirty_pages_and_in_the_background() {
+        let db = TestDb::new("checkpoint_triggers");
+        let mut engine = Engine::new(&db.path).expect("engine init");
+        engine
+            .execute_sql("CREATE TABLE events (id INT PRIMARY KEY);")
+            .expect("create table");
+        engine.set_checkpoint_policy(CheckpointPolicy {
+            max_dirty_pages: Some(0),
+            ..CheckpointPolicy::default()
+        });
+        engine
+            .execute_sql("INSERT INTO events VALUES (1);")
+            .expect("insert");
+        assert_eq!(checkpoint_records(&db), 1);
+
+        engine.set_checkpoint_policy(CheckpointPolicy {
+            interval: Some(Duration::from_millis(10)),
+            ..CheckpointPolicy::default()
+        });
+        std::thread::sleep(Duration::from_millis(200));
+        engine.set_checkpoint_policy(CheckpointPolicy::default());
+        assert!(checkpoint_records(&db) >= 2);
+        engine
+            .execute_sql("INSERT INTO events VALUES (2);")
+            .expect("insert");
+        assert_eq!(count_rows(&mut engine, "SELECT * FROM events;"), 2);
+    }
+
+    #[test]
+    fn temporary_table_is_seen_only_by_its_transaction_and_dropped_at_its_end() {
+        let db = TestDb::new("temp_table");
+        let mut engine = Engine::new(&db.path).expect("engine init");
+        engine
+            .execute_sql("CREATE TABLE items (id INT PRIMARY KEY, name TEXT);")
+            .expect("create table");
+        engine.execute_sql("BEGIN;").expect("begin");
+        engine
+            .execute_sql("CREATE TEMPORARY TABLE staging (id INT PRIMARY KEY, name TEXT);")
+            .expect("create temporary table");
+        engine
+            .execute_sql("INSERT INTO staging VALUES (1, 'a'), (2, 'b'), (3, 'c');")
+            .expect("stage rows");
+        engine
+            .execute_sql("DELETE FROM staging WHERE id = 2;")
+            .expect("clean staged rows");
+        engine
+            .execute_sql("INSERT INTO items SELECT * FROM staging;")
+            .expect("copy staged rows");
+        assert_eq!(count_rows(&mut engine, "SELECT * FROM staging;"), 2);
+        assert!(!engine.list_tables().contains(&"staging".to_string()));
+
+        let other = engine.begin_transaction().expect("begin other");
+        let err = engine
+            .execute_sql_in_transaction("SELECT * FROM staging;", &other)
+            .expect_err("other transaction sees the temporary table");
+        assert!(err.to_string().contains("staging"), "{err}");
+        engine.commit_transaction(&other).expect("commit other");
+
+        engine.execute_sql("COMMIT;").expect("commit");
+        assert!(engine.execute_sql("SELECT * FROM staging;").is_err());
+        assert_eq!(count_rows(&mut engine, "SELECT * FROM items;"), 2);
+
+        // a rolled-back transaction drops its temporary tables too, and the
+        // name is free again afterwards
+        engine.execute_sql("BEGIN;").expect("begin");
+        engine
+            .execute_sql("CREATE TEMP TABLE staging (id INT);")
+            .expect("create temporary table");
+        engine.execute_sql("ROLLBACK;").expect("rollback");
+        engine
+            .execute_sql("CREATE TEMP TABLE staging (id INT) ON COMMIT DROP;")
+            .expect("create temporary table outside a block");
+        assert!(engine.execute_sql("SELECT * FROM staging;").is_err());
+        drop(engine);
+
+        let mut engine = Engine::new(&db.path).expect("reopen");
+        assert_eq!(engine.list_tables(), vec!["items".to_string()]);
+        assert_eq!(count_rows(&mut engine, "SELECT * FROM items;"), 2);
+    }
+
+    #[test]
+    fn count_star_uses_maintained_row_count_across_rollbacks() {
+        let db = TestDb::new("count_star");
+        let mut engine = Engine::new(&db.path).expect("engine init");
+        engine
+            .execute_sql("CREATE TABLE orders (id INT PRIMARY KEY, total INT);")
+            .expect("create table");
+        engine
+            .execute_sql("INSERT INTO orders VALUES (1, 10), (2, 20), (3, 30);")
+            .expect("insert rows");
+
+        let counts = |engine: &mut Engine| {
+            let mut count = |sql: &str| match engine.execute_sql(sql).expect("count") {
+                ReplOutput::Rows { rows, .. } => rows[0].values()[0].clone(),
+                _ => panic!("expected rows output"),
+            };
+            let maintained = count("SELECT COUNT(*) FROM orders;");
+            let scanned = count("SELECT COUNT(*) FROM orders WHERE 1 = 1;");
+            assert_eq!(maintained, scanned);
+            maintained
+        };
+        assert_eq!(counts(&mut engine), Value::Integer(3));
+
+        match engine
+            .execute_sql("EXPLAIN SELECT COUNT(*) FROM orders;")
+            .expect("explain")
+        {
+            ReplOutput::Rows { rows, .. } => assert!(
+                rows.iter().any(|row| matches!(
+                    &row.values()[0],
+                    Value::String(line) if line.trim() == "CountRows: table=orders"
+                )),
+                "{rows:?}"
+            ),
+            _ => panic!("expected rows output"),
+        }
+
+        engine.execute_sql("BEGIN;").expect("begin");
+        engine
+            .execute_sql("INSERT INTO orders VALUES (4, 40), (5, 50);")
+            .expect("insert in transaction");
+        engine
+            .execute_sql("DELETE FROM orders WHERE id = 1;")
+            .expect("delete in transaction");
+        assert_eq!(counts(&mut engine), Value::Integer(4));
+        engine.execute_sql("ROLLBACK;").expect("rollback");
+        assert_eq!(counts(&mut engine), Value::Integer(3));
+
+        assert!(
+            engine
+                .execute_sql("INSERT INTO orders VALUES (6, 60), (1, 70);")
+                .is_err()
+        );
+        assert_eq!(counts(&mut engine), Value::Integer(3));
+
+        engine.execute_sql("BEGIN;").expect("begin");
+        engine
+            .execute_sql("INSERT INTO orders VALUES (7, 70);")
+            .expect("insert before savepoint");
+        engine.execute_sql("SAVEPOINT s;").expect("savepoint");
+        engine
+            .execute_sql("INSERT INTO orders VALUES (8, 80);")
+            .expect("insert after savepoint");
+        engine
+            .execute_sql("ROLLBACK TO SAVEPOINT s;")
+            .expect("rollback to savepoint");
+        assert_eq!(counts(&mut engine), Value::Integer(4));
+        engine.execute_sql("COMMIT;").expect("commit");
+        assert_eq!(counts(&mut engine), Value::Integer(4));
+
+        engine
+            .execute_sql("DELETE FROM orders WHERE total > 20;")
+            .expect("delete rows");
+        assert_eq!(counts(&mut engine), Value::Integer(2));
+    }
+
+    #[test]
+    fn transaction_control_reports_session_state() {
+        let db = TestDb::new("savepoints");
+        let mut engine = Engine::new(&db.path).expect("engine init");
+        engine
+            .execute_sql("CREATE TABLE items (id INT PRIMARY KEY, name TEXT);")
+            .expect("create table");
+
+        let message = |output: ReplOutput| match output {
+            ReplOutput::Message(message) => message,
+            _ => panic!("expected message output"),
+        };
+        assert_eq!(message(engine.execute_sql("BEGIN;").unwrap()), "BEGIN");
+        assert!(engine.in_transaction());
+        engine
+            .execute_sql("INSERT INTO items VALUES (1, 'kept');")
+            .expect("insert kept");
+        assert_eq!(
+            message(engine.execute_sql("SAVEPOINT a;").unwrap()),
+            "SAVEPOINT"
+        );
+        engine
+            .execute_sql("INSERT INTO items VALUES (2, 'undone');")
+            .expect("insert undone");
+        assert_eq!(
+            message(engine.execute_sql("ROLLBACK TO SAVEPOINT a;").unwrap()),
+            "ROLLBACK"
+        );
+        assert_eq!(count_rows(&mut engine, "SELECT * FROM items;"), 1);
+
+        let err = engine
+            .execute_sql("ROLLBACK TO SAVEPOINT missing;")
+            .expect_err("unknown savepoint");
+        assert!(err.to_string().contains("savepoint missing does not exist"));
+
+        // the undone key is free again once its index entry is gone
+        engine
+            .execute_sql("INSERT INTO items VALUES (2, 'again');")
+            .expect("reinsert");
+        assert_eq!(message(engine.execute_sql("COMMIT;").unwrap()), "COMMIT");
+        assert!(!engine.in_transaction());
+        assert_eq!(count_rows(&mut engine, "SELECT * FROM items;"), 2);
+        assert!(engine.execute_sql("COMMIT;").is_err());
+    }
+
+    #[test]
+    fn rollback_discards_transaction_block() {
+        let db = TestDb::new("rollback_block");
+        let mut engine = Engine::new(&db.path).expect("engine init");
+        engine
+            .execute_sql("CREATE TABLE items (id INT PRIMARY KEY, name TEXT);")
+            .expect("create table");
+        engine
+            .execute_sql("INSERT INTO items VALUES (1, 'committed');")
+            .expect("insert");
+
+        engine.execute_sql("BEGIN;").expect("begin");
+        engine
+            .execute_sql("INSERT INTO items VALUES (2, 'pending');")
+            .expect("insert pending");
+        // a failing statement is undone without ending the block
+        assert!(
+            engine
+                .execute_sql("INSERT INTO items VALUES (1, 'duplicate');")
+                .is_err()
+        );
+        assert!(engine.in_transaction());
+        assert_eq!(count_rows(&mut engine, "SELECT * FROM items;"), 2);
+        engine.execute_sql("ROLLBACK;").expect("rollback");
+        assert_eq!(count_rows(&mut engine, "SELECT * FROM items;"), 1);
+    }
+
+    #[test]
+    fn rollback_rebuilds_only_the_indexes_it_touched() {
+        let db = TestDb::new("rollback_touched_indexes");
+        let mut engine = Engine::new(&db.path).expect("engine init");
+        engine
+            .execute_sql("CREATE TABLE items (id INT PRIMARY KEY, name TEXT);")
+            .expect("create items");
+        engine
+            .execute_sql("CREATE TABLE other (id INT PRIMARY KEY);")
+            .expect("create other");
+        engine
+            .execute_sql("INSERT INTO other VALUES (1), (2);")
+            .expect("insert other");
+        let index_pages = |engine: &Engine, table: &str| {
+            engine.catalog.read().table(table).unwrap().indexes[0]
+                .index
+                .page_ids()
+                .unwrap()
+        };
+        let untouched = index_pages(&engine, "other");
+
+        let rollback_once = |engine: &mut Engine| {
+            engine.execute_sql("BEGIN;").expect("begin");
+            engine
+                .execute_sql("INSERT INTO items VALUES (1, 'a'), (2, 'b');")
+                .expect("insert items");
+            engine.execute_sql("SAVEPOINT s;").expect("savepoint");
+            engine
+                .execute_sql("INSERT INTO items VALUES (3, 'c');")
+                .expect("insert after savepoint");
+            engine
+                .execute_sql("ROLLBACK TO SAVEPOINT s;")
+                .expect("rollback to savepoint");
+            assert_eq!(count_rows(engine, "SELECT * FROM items WHERE id = 3;"), 0);
+            engine.execute_sql("ROLLBACK;").expect("rollback");
+        };
+        rollback_once(&mut engine);
+        assert_eq!(index_pages(&engine, "other"), untouched);
+        assert_eq!(
+            count_rows(&mut engine, "SELECT * FROM items WHERE id = 1;"),
+            0
+        );
+        assert_eq!(
+            count_rows(&mut engine, "SELECT * FROM other WHERE id = 2;"),
+            1
+        );
+
+        // the replaced trees are freed, so repeated rollbacks reuse pages
+        let file_len = || fs::metadata(&db.path).expect("db file").len();
+        engine.checkpoint().expect("checkpoint");
+        let settled = file_len();
+        for _ in 0..10 {
+            rollback_once(&mut engine);
+        }
+        engine.checkpoint().expect("checkpoint");
+        assert_eq!(file_len(), settled);
+    }
+
+    #[test]
+    fn create_table_if_not_exists_detects_drift() {
+        let db = TestDb::new("schema_drift");
+        let create = "CREATE TABLE IF NOT EXISTS users (id INT PRIMARY KEY, email TEXT);";
+        {
+            let mut engine = Engine::new(&db.path).expect("engine init");
+            engine.execute_sql(create).expect("create table");
+        }
+
+        let mut engine = Engine::new(&db.path).expect("engine reopen");
+        engine.execute_sql(create).expect("identical definition");
+
+        let err = engine
+            .execute_sql("CREATE TABLE IF NOT EXISTS users (id INT PRIMARY KEY, email INT);")
+            .expect_err("changed column type");
+        assert!(err.to_string().contains("column email as Text"));
+        assert!(
+            engine
+                .execute_sql("CREATE TABLE IF NOT EXISTS users (id INT, phone TEXT);")
+                .is_err()
+        );
+
+        engine
+            .execute_sql("ALTER TABLE users ADD COLUMN phone TEXT;")
+            .expect("add column");
+        engine
+            .execute_sql(create)
+            .expect("added columns are not drift");
     }
 }
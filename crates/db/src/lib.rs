@@ -4,3 +4,4 @@ pub mod history;
 pub mod printer;
 pub mod repl;
 pub mod sql;
+mod upgrade;
@@ -11,6 +11,7 @@ use crate::printer::print_output;
 use crate::sql::split_statements;
 
 const PRIMARY_PROMPT: &str = "rdbms> ";
+const TRANSACTION_PROMPT: &str = "rdbms*> ";
 const CONTINUATION_PROMPT: &str = "...> ";
 
 pub fn run_repl(engine: &mut Engine) -> Result<()> {
@@ -27,10 +28,12 @@ pub fn run_repl(engine: &mut Engine) -> Result<()> {
     let mut buffer = String::new();
 
     loop {
-        let prompt = if buffer.trim().is_empty() {
-            PRIMARY_PROMPT
-        } else {
+        let prompt = if !buffer.trim().is_empty() {
             CONTINUATION_PROMPT
+        } else if engine.in_transaction() {
+            TRANSACTION_PROMPT
+        } else {
+            PRIMARY_PROMPT
         };
         let line = match editor.readline(prompt) {
             Ok(line) => line,
@@ -62,10 +65,15 @@ pub fn run_repl(engine: &mut Engine) -> Result<()> {
                         break;
                     }
                 }
-                None => match engine.execute_sql(&statement) {
-                    Ok(output) => print_output(&output),
-                    Err(err) => eprintln!("Error: {}", err),
-                },
+                None => {
+                    match engine.execute_sql(&statement) {
+                        Ok(output) => print_output(&output),
+                        Err(err) => eprintln!("Error: {}", err),
+                    }
+                    for warning in engine.take_warnings() {
+                        eprintln!("WARNING: {}", warning);
+                    }
+                }
             }
         }
 
@@ -87,7 +95,7 @@ pub fn run_repl(engine: &mut Engine) -> Result<()> {
     Ok(())
 }
 
-fn handle_meta_command(engine: &Engine, command: MetaCommand) -> Result<bool> {
+fn handle_meta_command(engine: &mut Engine, command: MetaCommand) -> Result<bool> {
     match command {
         MetaCommand::Quit => Ok(true),
         MetaCommand::Help => {
@@ -106,6 +114,12 @@ fn handle_meta_command(engine: &Engine, command: MetaCommand) -> Result<bool> {
             }
             Ok(false)
         }
+        MetaCommand::Strict { enabled } => {
+            let enabled = enabled.unwrap_or(!engine.strict_mode());
+            engine.set_strict_mode(enabled);
+            println!("Strict mode {}", if enabled { "on" } else { "off" });
+            Ok(false)
+        }
     }
 }
 
@@ -115,5 +129,6 @@ fn print_help() {
     println!("  \\help            Show this message");
     println!("  \\tables          List tables");
     println!("  \\schema <table>  Show table schema");
+    println!("  \\strict [on|off] Warn about order-dependent results");
     println!("\nEnter SQL statements terminated by ';'.");
 }
@@ -0,0 +1,249 @@
+//! Converts database files written by earlier format versions into the
+//! current one before the engine opens them.
+//!
+//! Version 3 moved every page's contents to make room for a page checksum,
+//! so old pages cannot be patched in place. Instead every table's rows are
+//! read with the old layout and written to a new file; indexes need no
+//! copying because they are rebuilt from the heap whenever the catalog is
+//! loaded. The old log is not replayed: its records name offsets in the old
+//! layout. A database is converted only when its log ends at a checkpoint
+//! with no transaction left unfinished, so every change it holds is already
+//! in the old pages; any other log is refused and the files are left as
+//! they are.
+//!
+//! The old data and catalog files are kept next to the new ones with a
+//! `.v<version>` suffix, and once the copy succeeds the old log and
+//! double-write file are renamed the same way. If the conversion fails or
+//! is interrupted, it starts over from the kept copies on the next open.
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, anyhow, bail};
+use query::TableHeap;
+use query::execution::legacy_heap::LegacyHeapFile;
+use query::schema::{Field, Schema};
+use serde_json::Value as JsonValue;
+use storage::{BufferPoolManager, DiskManager, FORMAT_VERSION, FlushMode};
+use wal::{LogReader, LogRecordType, with_suffix};
+
+use crate::engine::data_type_from_name;
+
+const UPGRADE_POOL_SIZE: usize = 64;
+
+/// Converts the database at `db_path` if it was written by an earlier
+/// format version. Files in the current or a newer version are left for
+/// the disk manager to accept or refuse.
+pub(crate) fn upgrade_if_needed(db_path: &Path) -> Result<()> {
+    let version =
+        match DiskManager::stored_format_version(db_path).context("read database header")? {
+            Some(version) if version < FORMAT_VERSION => version,
+            _ => return Ok(()),
+        };
+    let wal_path = db_path.with_extension("wal");
+    ensure_log_settled(&wal_path)
+        .with_context(|| format!("convert database from format version {}", version))?;
+    let suffix = format!(".v{}", version);
+    let catalog_path = db_path.with_extension("catalog");
+    let old_db = with_suffix(db_path, &suffix);
+    let old_catalog = with_suffix(&catalog_path, &suffix);
+
+    // Hard links keep the old files under their new names while the
+    // originals stay in place until the converted ones replace them.
+    if !old_db.exists() {
+        fs::hard_link(db_path, &old_db).context("keep old database file")?;
+    }
+    if catalog_path.exists() && !old_catalog.exists() {
+        fs::hard_link(&catalog_path, &old_catalog).context("keep old catalog file")?;
+    }
+    let mut catalog: JsonValue = if old_catalog.exists() {
+        serde_json::from_slice(&fs::read(&old_catalog).context("read old catalog file")?)
+            .context("parse old catalog file")?
+    } else {
+        serde_json::json!({ "tables": [] })
+    };
+
+    let new_db = with_suffix(db_path, ".upgrade");
+    let new_catalog = with_suffix(&catalog_path, ".upgrade");
+    remove_if_present(&new_db)?;
+    remove_if_present(&with_suffix(&new_db, ".dwb"))?;
+    copy_tables(&old_db, &new_db, &mut catalog)
+        .with_context(|| format!("convert database from format version {}", version))?;
+    remove_if_present(&with_suffix(&new_db, ".dwb"))?;
+
+    fs::write(
+        &new_catalog,
+        serde_json::to_vec_pretty(&catalog).context("serialize catalog")?,
+    )
+    .context("write converted catalog file")?;
+
+    // The log and the double-write file describe pages in the old layout.
+    for path in [
+        with_suffix(db_path, ".dwb"),
+        wal_path.clone(),
+        with_suffix(&wal_path, ".master"),
+    ]
+    .into_iter()
+    .chain(wal_segments(&wal_path)?)
+    {
+        if path.exists() {
+            fs::rename(&path, with_suffix(&path, &suffix))
+                .with_context(|| format!("move {} aside", path.display()))?;
+        }
+    }
+    fs::rename(&new_catalog, &catalog_path).context("replace catalog file")?;
+    fs::rename(&new_db, db_path).context("replace database file")?;
+    Ok(())
+}
+
+/// Fails unless the old log ends at a checkpoint and every transaction in
+/// it has ended. Its records cannot be replayed into the converted file, so
+/// changes after the checkpoint, or a transaction recovery would roll back,
+/// would otherwise be lost or kept half done.
+fn ensure_log_settled(wal_path: &Path) -> Result<()> {
+    let mut reader = LogReader::open(wal_path).context("open old log")?;
+    let mut unfinished = HashSet::new();
+    let mut after_checkpoint = false;
+    while let Some(record) = reader.next_record().context("read old log")? {
+        match record.record_type {
+            LogRecordType::Checkpoint => {
+                after_checkpoint = false;
+                continue;
+            }
+            LogRecordType::Begin => {
+                unfinished.insert(record.txn_id);
+            }
+            LogRecordType::End => {
+                unfinished.remove(&record.txn_id);
+            }
+            _ => {}
+        }
+        after_checkpoint = true;
+    }
+    if reader.offset() < reader.end_lsn().context("read old log")? {
+        bail!(
+            "the old log ends in a record this build cannot read; \
+             checkpoint it with the build that wrote it first"
+        );
+    }
+    if !unfinished.is_empty() {
+        bail!(
+            "the old log has {} unfinished transaction(s); \
+             checkpoint it with the build that wrote it first",
+            unfinished.len()
+        );
+    }
+    if after_checkpoint {
+        bail!(
+            "the old log has records after its last checkpoint; \
+             checkpoint it with the build that wrote it first"
+        );
+    }
+    Ok(())
+}
+
+/// Copies every table's rows from the old file into a new one, pointing
+/// each catalog entry at its new heap.
+fn copy_tables(old_db: &Path, new_db: &Path, catalog: &mut JsonValue) -> Result<()> {
+    let old = LegacyHeapFile::open(old_db)?;
+    let buffer_pool = BufferPoolManager::new(
+        DiskManager::open(new_db).context("create converted database file")?,
+        UPGRADE_POOL_SIZE,
+    );
+    let tables = catalog
+        .get_mut("tables")
+        .and_then(JsonValue::as_array_mut)
+        .ok_or_else(|| anyhow!("catalog has no table list"))?;
+    for table in tables {
+        let name = table["name"]
+            .as_str()
+            .ok_or_else(|| anyhow!("catalog table has no name"))?
+            .to_string();
+        let first_page_id = table["first_page_id"]
+            .as_u64()
+            .ok_or_else(|| anyhow!("table '{}' has no first page", name))?;
+        let schema = table_schema(&name, table)?;
+        let rows = old
+            .read_table(first_page_id, &schema)
+            .with_context(|| format!("read table '{}'", name))?;
+        let heap = TableHeap::create(buffer_pool.clone())?;
+        for row in &rows {
+            heap.insert_tuple(row, &schema)
+                .with_context(|| format!("copy a row of table '{}'", name))?;
+        }
+        let new_first_page_id = heap
+            .first_page_id()?
+            .ok_or_else(|| anyhow!("table '{}' has no first page", name))?;
+        table["first_page_id"] = new_first_page_id.into();
+    }
+    buffer_pool
+        .flush_all_pages_with_mode(FlushMode::Force)
+        .context("flush converted database file")?;
+    Ok(())
+}
+
+fn table_schema(name: &str, table: &JsonValue) -> Result<Schema> {
+    let columns = table["columns"]
+        .as_array()
+        .ok_or_else(|| anyhow!("table '{}' has no columns", name))?;
+    let fields = columns
+        .iter()
+        .map(|column| {
+            let column_name = column["name"].as_str().unwrap_or_default();
+            let type_name = column["data_type"].as_str().unwrap_or_default();
+            let data_type = data_type_from_name(type_name).ok_or_else(|| {
+                anyhow!(
+                    "unknown data type '{}' for column '{}' in table '{}'",
+                    type_name,
+                    column_name,
+                    name
+                )
+            })?;
+            Ok(Field {
+                name: column_name.to_string(),
+                table: Some(name.to_string()),
+                data_type,
+                nullable: column["nullable"].as_bool().unwrap_or(true),
+                visible: true,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(Schema::new(fields))
+}
+
+/// Lists the log's later segments, which are named `<log>.<number>`.
+fn wal_segments(wal_path: &Path) -> Result<Vec<PathBuf>> {
+    let dir = match wal_path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let Some(prefix) = wal_path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .map(|name| format!("{}.", name))
+    else {
+        return Ok(Vec::new());
+    };
+    let mut segments = Vec::new();
+    for entry in fs::read_dir(dir).context("list database directory")? {
+        let path = entry.context("list database directory")?.path();
+        let is_segment = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.strip_prefix(&prefix))
+            .is_some_and(|number| number.parse::<u64>().is_ok());
+        if is_segment {
+            segments.push(path);
+        }
+    }
+    Ok(segments)
+}
+
+fn remove_if_present(path: &Path) -> Result<()> {
+    match fs::remove_file(path) {
+        Ok(()) => Ok(()),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(err) => Err(err).with_context(|| format!("remove {}", path.display())),
+    }
+}
@@ -0,0 +1,304 @@
+use std::fs;
+use std::path::Path;
+
+use db::engine::Engine;
+use db::printer::ReplOutput;
+use query::Value;
+use storage::{DiskManager, FORMAT_VERSION, PAGE_SIZE};
+use tempfile::TempDir;
+use wal::{LogManager, LogRecord};
+
+// Layout of files before format version 3: contents follow an 8-byte LSN.
+const OLD_CONTENT_OFFSET: usize = 8;
+const OLD_HEAP_SLOTS_OFFSET: usize = OLD_CONTENT_OFFSET + 16;
+const OLD_BLOB_PAYLOAD_OFFSET: usize = OLD_CONTENT_OFFSET + 12;
+const HEADER_MAGIC: u64 = 0xD15CAD0BADC0FFEE;
+
+fn rows(engine: &mut Engine, sql: &str) -> Vec<Vec<Value>> {
+    match engine.execute_sql(sql).unwrap() {
+        ReplOutput::Rows { rows, .. } => rows.iter().map(|row| row.values().to_vec()).collect(),
+        ReplOutput::Message(message) => panic!("expected rows, got {}", message),
+    }
+}
+
+/// Header page: version 1 files carry only next_page_id.
+fn old_header(version: u32, next_page_id: u64) -> Vec<u8> {
+    let mut page = vec![0u8; PAGE_SIZE];
+    page[..8].copy_from_slice(&next_page_id.to_le_bytes());
+    if version > 1 {
+        page[8..16].copy_from_slice(&HEADER_MAGIC.to_le_bytes());
+        page[16..20].copy_from_slice(&version.to_le_bytes());
+        page[20..24].copy_from_slice(&(PAGE_SIZE as u32).to_le_bytes());
+    }
+    page
+}
+
+/// Heap page holding `tuples`; `None` is a deleted slot.
+fn old_heap_page(tuples: &[Option<Vec<u8>>]) -> Vec<u8> {
+    let mut page = vec![0u8; PAGE_SIZE];
+    let mut free = PAGE_SIZE;
+    for (slot, tuple) in tuples.iter().enumerate() {
+        let (offset, len) = match tuple {
+            Some(tuple) => {
+                free -= tuple.len();
+                page[free..free + tuple.len()].copy_from_slice(tuple);
+                (free as u32, tuple.len() as u32)
+            }
+            None => (free as u32, 0),
+        };
+        let at = OLD_HEAP_SLOTS_OFFSET + slot * 8;
+        page[at..at + 4].copy_from_slice(&offset.to_le_bytes());
+        page[at + 4..at + 8].copy_from_slice(&len.to_le_bytes());
+    }
+    page[OLD_CONTENT_OFFSET + 8..OLD_CONTENT_OFFSET + 12]
+        .copy_from_slice(&(tuples.len() as u32).to_le_bytes());
+    page[OLD_CONTENT_OFFSET + 12..OLD_CONTENT_OFFSET + 16]
+        .copy_from_slice(&(free as u32).to_le_bytes());
+    page
+}
+
+fn old_blob_page(next_page_id: u64, payload: &[u8]) -> Vec<u8> {
+    let mut page = vec![0u8; PAGE_SIZE];
+    page[OLD_CONTENT_OFFSET..OLD_CONTENT_OFFSET + 8].copy_from_slice(&next_page_id.to_le_bytes());
+    page[OLD_CONTENT_OFFSET + 8..OLD_BLOB_PAYLOAD_OFFSET]
+        .copy_from_slice(&(payload.len() as u32).to_le_bytes());
+    page[OLD_BLOB_PAYLOAD_OFFSET..OLD_BLOB_PAYLOAD_OFFSET + payload.len()].copy_from_slice(payload);
+    page
+}
+
+/// Fixed-layout tuple field: a null byte, then the value.
+fn fixed_int(value: i32) -> Vec<u8> {
+    let mut bytes = vec![0];
+    bytes.extend_from_slice(&value.to_le_bytes());
+    bytes
+}
+
+fn fixed_text(value: &str) -> Vec<u8> {
+    let mut bytes = vec![0];
+    bytes.extend_from_slice(&(value.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(value.as_bytes());
+    bytes
+}
+
+fn column(name: &str, data_type: &str, primary_key: bool) -> serde_json::Value {
+    serde_json::json!({
+        "name": name,
+        "data_type": data_type,
+        "nullable": !primary_key,
+        "primary_key": primary_key,
+        "unique": primary_key,
+        "default_value": null,
+        "auto_increment": false
+    })
+}
+
+/// Logs one finished transaction, then a checkpoint unless `checkpoint`
+/// is false, then `after` more records.
+fn write_old_log(db_path: &Path, checkpoint: bool, after: usize) {
+    let log = LogManager::open(db_path.with_extension("wal")).unwrap();
+    let begin = log.append(LogRecord::begin(0, 1, None)).unwrap();
+    let commit = log.append(LogRecord::commit(0, 1, Some(begin))).unwrap();
+    log.append(LogRecord::end(0, 1, Some(commit))).unwrap();
+    if checkpoint {
+        log.checkpoint(Vec::new()).unwrap();
+    }
+    for txn_id in 2..2 + after as u64 {
+        log.append(LogRecord::begin(0, txn_id, None)).unwrap();
+    }
+    log.force_flush().unwrap();
+}
+
+fn write_old_database(db_path: &Path, pages: Vec<Vec<u8>>, tables: Vec<serde_json::Value>) {
+    fs::write(db_path, pages.concat()).unwrap();
+    fs::write(
+        db_path.with_extension("catalog"),
+        serde_json::to_vec(&serde_json::json!({ "tables": tables })).unwrap(),
+    )
+    .unwrap();
+}
+
+#[test]
+fn test_unversioned_database_is_converted_on_open() {
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("test.db");
+
+    let ada = [fixed_int(1), fixed_text("ada"), vec![0, 1]].concat();
+    let bob = [fixed_int(2), vec![1], vec![0, 0]].concat();
+    let blob: Vec<u8> = (0..6000u32).map(|i| (i % 251) as u8).collect();
+    let payload_capacity = PAGE_SIZE - OLD_BLOB_PAYLOAD_OFFSET;
+    let mut out_of_line = vec![0, 1];
+    out_of_line.extend_from_slice(&3u64.to_le_bytes());
+    out_of_line.extend_from_slice(&(blob.len() as u32).to_le_bytes());
+    let file_row = [fixed_int(7), out_of_line].concat();
+    write_old_database(
+        &db_path,
+        vec![
+            old_header(1, 5),
+            old_heap_page(&[Some(ada), None, Some(bob)]),
+            old_heap_page(&[Some(file_row)]),
+            old_blob_page(4, &blob[..payload_capacity]),
+            old_blob_page(0, &blob[payload_capacity..]),
+        ],
+        vec![
+            serde_json::json!({
+                "name": "users",
+                "first_page_id": 1,
+                "columns": [
+                    column("id", "Integer", true),
+                    column("name", "Text", false),
+                    column("active", "Boolean", false)
+                ],
+                "indexes": [
+                    { "name": "users_pkey", "columns": ["id"], "unique": true, "is_primary": true }
+                ]
+            }),
+            serde_json::json!({
+                "name": "files",
+                "first_page_id": 2,
+                "columns": [column("id", "Integer", true), column("data", "Blob", false)],
+                "indexes": [
+                    { "name": "files_pkey", "columns": ["id"], "unique": true, "is_primary": true }
+                ]
+            }),
+        ],
+    );
+    // a log that ends at a checkpoint holds nothing the old pages lack
+    write_old_log(&db_path, true, 0);
+
+    {
+        let mut engine = Engine::new(&db_path).unwrap();
+        assert_eq!(
+            rows(
+                &mut engine,
+                "SELECT id, name, active FROM users ORDER BY id"
+            ),
+            vec![
+                vec![
+                    Value::Integer(1),
+                    Value::String("ada".to_string()),
+                    Value::Boolean(true)
+                ],
+                vec![Value::Integer(2), Value::Null, Value::Boolean(false)],
+            ]
+        );
+        assert_eq!(
+            rows(&mut engine, "SELECT data FROM files WHERE id = 7"),
+            vec![vec![Value::Blob(blob.clone())]]
+        );
+        // indexes are rebuilt from the copied rows
+        assert!(
+            engine
+                .execute_sql("INSERT INTO users VALUES (1, 'again', TRUE)")
+                .is_err()
+        );
+        engine
+            .execute_sql("INSERT INTO users VALUES (3, 'cy', TRUE)")
+            .unwrap();
+    }
+
+    assert_eq!(
+        DiskManager::stored_format_version(&db_path).unwrap(),
+        Some(FORMAT_VERSION)
+    );
+    for kept in ["test.db.v1", "test.catalog.v1", "test.wal.v1"] {
+        assert!(temp_dir.path().join(kept).exists(), "{} was not kept", kept);
+    }
+
+    let mut engine = Engine::new(&db_path).unwrap();
+    assert_eq!(
+        rows(&mut engine, "SELECT id FROM users ORDER BY id"),
+        vec![
+            vec![Value::Integer(1)],
+            vec![Value::Integer(2)],
+            vec![Value::Integer(3)]
+        ]
+    );
+}
+
+#[test]
+fn test_version_2_database_with_mixed_tuple_layouts_is_converted() {
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("test.db");
+
+    // version 2 wrote compact tuples but kept reading fixed ones
+    let fixed = [fixed_int(1), fixed_text("ada")].concat();
+    let mut compact = vec![0x80, 2, 0b00, 2 << 1];
+    compact.extend_from_slice(&2u32.to_le_bytes());
+    compact.extend_from_slice(b"cy");
+    write_old_database(
+        &db_path,
+        vec![
+            old_header(2, 2),
+            old_heap_page(&[Some(fixed), Some(compact)]),
+        ],
+        vec![serde_json::json!({
+            "name": "users",
+            "first_page_id": 1,
+            "columns": [column("id", "Integer", true), column("name", "Text", false)],
+            "indexes": []
+        })],
+    );
+
+    let mut engine = Engine::new(&db_path).unwrap();
+    assert_eq!(
+        rows(&mut engine, "SELECT id, name FROM users ORDER BY id"),
+        vec![
+            vec![Value::Integer(1), Value::String("ada".to_string())],
+            vec![Value::Integer(2), Value::String("cy".to_string())],
+        ]
+    );
+    assert!(temp_dir.path().join("test.db.v2").exists());
+}
+
+#[test]
+fn test_database_with_changes_after_the_last_checkpoint_is_not_converted() {
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("test.db");
+    let old_catalog = || {
+        write_old_database(
+            &db_path,
+            vec![old_header(2, 2), old_heap_page(&[Some(fixed_int(1))])],
+            vec![serde_json::json!({
+                "name": "users",
+                "first_page_id": 1,
+                "columns": [column("id", "Integer", true)],
+                "indexes": []
+            })],
+        )
+    };
+    let log_files = ["test.wal", "test.wal.master"];
+
+    for (checkpoint, after, reason) in [
+        (false, 0, "after its last checkpoint"),
+        (true, 1, "unfinished transaction"),
+    ] {
+        for name in log_files {
+            let _ = fs::remove_file(temp_dir.path().join(name));
+        }
+        old_catalog();
+        write_old_log(&db_path, checkpoint, after);
+        let Err(err) = Engine::new(&db_path) else {
+            panic!("old log is not settled");
+        };
+        assert!(format!("{:#}", err).contains(reason), "{:#}", err);
+        assert_eq!(
+            DiskManager::stored_format_version(&db_path).unwrap(),
+            Some(2)
+        );
+        assert!(!temp_dir.path().join("test.db.v2").exists());
+    }
+
+    // a log in a record layout this build cannot read is refused as well
+    for name in log_files {
+        let _ = fs::remove_file(temp_dir.path().join(name));
+    }
+    fs::write(db_path.with_extension("wal"), b"not a current log").unwrap();
+    let Err(err) = Engine::new(&db_path) else {
+        panic!("old log is unreadable");
+    };
+    assert!(format!("{:#}", err).contains("cannot read"), "{:#}", err);
+    assert_eq!(
+        DiskManager::stored_format_version(&db_path).unwrap(),
+        Some(2)
+    );
+}
@@ -0,0 +1,218 @@
+//! Logical change feed read back from the WAL.
+//!
+//! Every row a transaction inserts, updates or deletes is logged as a
+//! `RowChange` record in the transaction's log chain. The feed replays the
+//! log and hands out the changes of each committed transaction once its
+//! commit record is durable, in commit order. Changes undone by a rollback,
+//! whole or to a savepoint, never appear.
+
+use crate::execution::encoding::{decode_value, encode_value};
+use crate::execution::operator::{ExecutionError, ExecutionResult};
+use crate::execution::tuple::{Tuple, Value};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use wal::{LogPayload, LogReader, LogRecordType, Lsn, TxnId};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeOp {
+    Insert,
+    Update,
+    Delete,
+}
+
+impl ChangeOp {
+    fn to_byte(self) -> u8 {
+        match self {
+            ChangeOp::Insert => 1,
+            ChangeOp::Update => 2,
+            ChangeOp::Delete => 3,
+        }
+    }
+
+    fn from_byte(byte: u8) -> ExecutionResult<Self> {
+        match byte {
+            1 => Ok(ChangeOp::Insert),
+            2 => Ok(ChangeOp::Update),
+            3 => Ok(ChangeOp::Delete),
+            other => Err(change_error(&format!("unknown operation {}", other))),
+        }
+    }
+}
+
+/// One committed row change.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChangeEvent {
+    /// LSN of the commit record of the transaction that made the change; a
+    /// reader resumes from `commit_lsn + 1`.
+    pub commit_lsn: Lsn,
+    pub txn_id: TxnId,
+    pub table: String,
+    pub op: ChangeOp,
+    /// Primary key of the row before the change, or all of its columns when
+    /// the table has no primary key. An insert has no earlier row, so its key
+    /// comes from the new row.
+    pub key: Vec<Value>,
+    /// The row after the change; `None` for a delete.
+    pub values: Option<Tuple>,
+}
+
+pub struct ChangeFeed {
+    log_path: PathBuf,
+}
+
+impl ChangeFeed {
+    pub fn new(log_path: impl AsRef<Path>) -> Self {
+        Self {
+            log_path: log_path.as_ref().to_path_buf(),
+        }
+    }
+
+    /// Returns the changes of every transaction whose commit record is at or
+    /// after `from_lsn`, in commit order and, within a transaction, in the
+    /// order they were made. Fails when a checkpoint has already deleted
+    /// part of the log those changes were in.
+    pub fn changes_since(&self, from_lsn: Lsn) -> ExecutionResult<Vec<ChangeEvent>> {
+        let mut reader = LogReader::open(&self.log_path).map_err(map_wal_error)?;
+        let start_lsn = reader.offset();
+        if from_lsn < start_lsn {
+            return Err(truncated_error(from_lsn, start_lsn));
+        }
+        let mut begun = HashSet::new();
+        let mut pending: HashMap<TxnId, Vec<(Lsn, ChangeEvent)>> = HashMap::new();
+        let mut events = Vec::new();
+        while let Some(record) = reader.next_record().map_err(map_wal_error)? {
+            match (&record.record_type, &record.payload) {
+                (LogRecordType::Begin, _) => {
+                    begun.insert(record.txn_id);
+                }
+                (LogRecordType::RowChange, LogPayload::RowChange { change }) => {
+                    let event = decode_change(change, record.txn_id)?;
+                    pending
+                        .entry(record.txn_id)
+                        .or_default()
+                        .push((record.lsn, event));
+                }
+                // each compensation record undoes the changes logged after
+                // the point it rolls back to
+                (LogRecordType::Compensation, LogPayload::Compensation { undo_next_lsn, .. }) => {
+                    if let Some(changes) = pending.get_mut(&record.txn_id) {
+                        let kept = undo_next_lsn.unwrap_or(0);
+                        changes.retain(|(lsn, _)| *lsn <= kept);
+                    }
+                }
+                (LogRecordType::Commit, _) => {
+                    let changes = pending.remove(&record.txn_id).unwrap_or_default();
+                    if record.lsn >= from_lsn {
+                        // its earlier changes went with the deleted segments
+                        if !begun.contains(&record.txn_id) {
+                            return Err(truncated_error(record.lsn, start_lsn));
+                        }
+                        events.extend(changes.into_iter().map(|(_, mut event)| {
+                            event.commit_lsn = record.lsn;
+                            event
+                        }));
+                    }
+                }
+                (LogRecordType::Abort | LogRecordType::End, _) => {
+                    pending.remove(&record.txn_id);
+                }
+                _ => {}
+            }
+        }
+        Ok(events)
+    }
+}
+
+/// Logs a change to a row of `table` in the current transaction.
+pub(crate) fn log_change(
+    table: &str,
+    op: ChangeOp,
+    key: &[Value],
+    values: Option<&Tuple>,
+) -> ExecutionResult<()> {
+    wal::log_row_change(encode_change(table, op, key, values)).map_err(map_wal_error)?;
+    Ok(())
+}
+
+/// ```text
+/// op:u8 table_len:u32 table key_count:u32 { value }* has_values:u8
+/// [ value_count:u32 { value }* ]
+/// ```
+fn encode_change(table: &str, op: ChangeOp, key: &[Value], values: Option<&Tuple>) -> Vec<u8> {
+    let mut out = vec![op.to_byte()];
+    out.extend_from_slice(&(table.len() as u32).to_be_bytes());
+    out.extend_from_slice(table.as_bytes());
+    encode_values(key, &mut out);
+    match values {
+        Some(tuple) => {
+            out.push(1);
+            encode_values(tuple.values(), &mut out);
+        }
+        None => out.push(0),
+    }
+    out
+}
+
+fn encode_values(values: &[Value], out: &mut Vec<u8>) {
+    out.extend_from_slice(&(values.len() as u32).to_be_bytes());
+    for value in values {
+        encode_value(value, out);
+    }
+}
+
+fn decode_change(bytes: &[u8], txn_id: TxnId) -> ExecutionResult<ChangeEvent> {
+    let mut input = bytes;
+    let op = ChangeOp::from_byte(take(&mut input, 1)?[0])?;
+    let table_len = take_u32(&mut input)? as usize;
+    let table = String::from_utf8(take(&mut input, table_len)?.to_vec())
+        .map_err(|_| change_error("table name is not UTF-8"))?;
+    let key = decode_values(&mut input)?;
+    let values = match take(&mut input, 1)?[0] {
+        0 => None,
+        _ => Some(Tuple::new(decode_values(&mut input)?)),
+    };
+    if !input.is_empty() {
+        return Err(change_error("trailing bytes"));
+    }
+    Ok(ChangeEvent {
+        commit_lsn: 0,
+        txn_id,
+        table,
+        op,
+        key,
+        values,
+    })
+}
+
+fn decode_values(input: &mut &[u8]) -> ExecutionResult<Vec<Value>> {
+    let count = take_u32(input)? as usize;
+    (0..count).map(|_| decode_value(input)).collect()
+}
+
+fn take<'a>(input: &mut &'a [u8], len: usize) -> ExecutionResult<&'a [u8]> {
+    if input.len() < len {
+        return Err(change_error("record truncated"));
+    }
+    let (head, rest) = input.split_at(len);
+    *input = rest;
+    Ok(head)
+}
+
+fn take_u32(input: &mut &[u8]) -> ExecutionResult<u32> {
+    Ok(u32::from_be_bytes(take(input, 4)?.try_into().unwrap()))
+}
+
+fn change_error(message: &str) -> ExecutionError {
+    ExecutionError::Execution(format!("invalid row change record: {}", message))
+}
+
+fn truncated_error(lsn: Lsn, start_lsn: Lsn) -> ExecutionError {
+    ExecutionError::Execution(format!(
+        "changes at lsn {} are no longer in the log, which now starts at lsn {}",
+        lsn, start_lsn
+    ))
+}
+
+fn map_wal_error(err: wal::WalError) -> ExecutionError {
+    ExecutionError::Execution(format!("wal error: {}", err))
+}
@@ -0,0 +1,261 @@
+use crate::execution::operator::{
+    compare_values, evaluate_expr, ExecutionError, ExecutionResult, PhysicalOperator,
+};
+use crate::execution::tuple::{Tuple, Value};
+use crate::expr::Expr;
+use crate::logical_plan::{AggregateExpr, AggregateFunction};
+use crate::schema::Schema;
+use std::any::Any;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+/// Materializes its input into groups keyed by `group_by` and emits one row
+/// per group: the group key values followed by one value per aggregate.
+/// Without GROUP BY the whole input is a single group, so an empty input
+/// still yields one row.
+pub struct Aggregate {
+    child: Box<dyn PhysicalOperator>,
+    group_by: Vec<Expr>,
+    aggregates: Vec<AggregateExpr>,
+    input_schema: Schema,
+    results: Vec<Tuple>,
+    position: usize,
+}
+
+impl Aggregate {
+    pub fn new(
+        child: Box<dyn PhysicalOperator>,
+        group_by: Vec<Expr>,
+        aggregates: Vec<AggregateExpr>,
+        input_schema: Schema,
+    ) -> Self {
+        Self {
+            child,
+            group_by,
+            aggregates,
+            input_schema,
+            results: Vec::new(),
+            position: 0,
+        }
+    }
+
+    pub fn child(&self) -> &dyn PhysicalOperator {
+        &*self.child
+    }
+
+    fn new_accumulators(&self) -> Vec<Accumulator> {
+        self.aggregates
+            .iter()
+            .map(|aggregate| Accumulator::new(aggregate.func))
+            .collect()
+    }
+}
+
+impl PhysicalOperator for Aggregate {
+    fn open(&mut self) -> ExecutionResult<()> {
+        self.child.open()?;
+        let mut group_index: HashMap<Vec<Value>, usize> = HashMap::new();
+        let mut groups: Vec<(Vec<Value>, Vec<Accumulator>)> = Vec::new();
+        while let Some(tuple) = self.child.next()? {
+            let keys = self
+                .group_by
+                .iter()
+                .map(|expr| evaluate_expr(expr, &tuple, &self.input_schema))
+                .collect::<ExecutionResult<Vec<_>>>()?;
+            let index = match group_index.get(&keys) {
+                Some(index) => *index,
+                None => {
+                    group_index.insert(keys.clone(), groups.len());
+                    groups.push((keys, self.new_accumulators()));
+                    groups.len() - 1
+                }
+            };
+            for (aggregate, accumulator) in self.aggregates.iter().zip(&mut groups[index].1) {
+                let value = match aggregate.args.first() {
+                    None | Some(Expr::Wildcard) => None,
+                    Some(arg) => Some(evaluate_expr(arg, &tuple, &self.input_schema)?),
+                };
+                accumulator.update(value)?;
+            }
+        }
+
+        if groups.is_empty() && self.group_by.is_empty() {
+            groups.push((Vec::new(), self.new_accumulators()));
+        }
+        self.results = groups
+            .into_iter()
+            .map(|(mut values, accumulators)| {
+                for accumulator in accumulators {
+                    values.push(accumulator.finish()?);
+                }
+                Ok(Tuple::new(values))
+            })
+            .collect::<ExecutionResult<_>>()?;
+        self.position = 0;
+        Ok(())
+    }
+
+    fn next(&mut self) -> ExecutionResult<Option<Tuple>> {
+        if self.position >= self.results.len() {
+            return Ok(None);
+        }
+        let tuple = self.results[self.position].clone();
+        self.position += 1;
+        Ok(Some(tuple))
+    }
+
+    fn close(&mut self) -> ExecutionResult<()> {
+        self.results.clear();
+        self.position = 0;
+        self.child.close()
+    }
+
+    fn rewind(&mut self) -> ExecutionResult<()> {
+        self.position = 0;
+        Ok(())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn describe(&self) -> String {
+        let groups = self
+            .group_by
+            .iter()
+            .map(|expr| expr.to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+        let aggregates = self
+            .aggregates
+            .iter()
+            .map(AggregateExpr::output_name)
+            .collect::<Vec<_>>()
+            .join(", ");
+        format!("Aggregate: group_by=[{}], aggs=[{}]", groups, aggregates)
+    }
+
+    fn children(&self) -> Vec<&dyn PhysicalOperator> {
+        vec![&*self.child]
+    }
+}
+
+/// Running state for one aggregate in one group. Integer sums accumulate in
+/// `i128` so that a total back in the `i64` range is exact even when a
+/// running sum left it.
+enum Accumulator {
+    Count(i64),
+    Sum(Option<NumericSum>),
+    Avg { sum: Option<NumericSum>, count: i64 },
+    Min(Option<Value>),
+    Max(Option<Value>),
+}
+
+#[derive(Clone, Copy)]
+enum NumericSum {
+    Integer(i128),
+    Float(f64),
+}
+
+impl NumericSum {
+    fn add(current: Option<NumericSum>, value: &Value) -> ExecutionResult<NumericSum> {
+        let sum = current.unwrap_or(NumericSum::Integer(0));
+        Ok(match (sum, value) {
+            (NumericSum::Integer(total), Value::Integer(v)) => {
+                NumericSum::Integer(total.checked_add(i128::from(*v)).ok_or_else(|| {
+                    ExecutionError::Execution("integer overflow in SUM".to_string())
+                })?)
+            }
+            (NumericSum::Integer(total), Value::Float(v)) => NumericSum::Float(total as f64 + v),
+            (NumericSum::Float(total), Value::Integer(v)) => NumericSum::Float(total + *v as f64),
+            (NumericSum::Float(total), Value::Float(v)) => NumericSum::Float(total + v),
+            (_, other) => {
+                return Err(ExecutionError::Expression(format!(
+                    "cannot sum non-numeric value {:?}",
+                    other
+                )))
+            }
+        })
+    }
+
+    fn as_f64(self) -> f64 {
+        match self {
+            NumericSum::Integer(total) => total as f64,
+            NumericSum::Float(total) => total,
+        }
+    }
+}
+
+impl Accumulator {
+    fn new(func: AggregateFunction) -> Self {
+        match func {
+            AggregateFunction::Count => Accumulator::Count(0),
+            AggregateFunction::Sum => Accumulator::Sum(None),
+            AggregateFunction::Avg => Accumulator::Avg {
+                sum: None,
+                count: 0,
+            },
+            AggregateFunction::Min => Accumulator::Min(None),
+            AggregateFunction::Max => Accumulator::Max(None),
+        }
+    }
+
+    /// Folds one input row in. `None` is `COUNT(*)`'s row marker; NULL
+    /// arguments are skipped by every aggregate.
+    fn update(&mut self, value: Option<Value>) -> ExecutionResult<()> {
+        let value = match value {
+            None => {
+                if let Accumulator::Count(count) = self {
+                    *count += 1;
+                }
+                return Ok(());
+            }
+            Some(Value::Null) => return Ok(()),
+            Some(value) => value,
+        };
+        match self {
+            Accumulator::Count(count) => *count += 1,
+            Accumulator::Sum(sum) => *sum = Some(NumericSum::add(*sum, &value)?),
+            Accumulator::Avg { sum, count } => {
+                *sum = Some(NumericSum::add(*sum, &value)?);
+                *count += 1;
+            }
+            Accumulator::Min(current) => replace_if(current, value, Ordering::Less)?,
+            Accumulator::Max(current) => replace_if(current, value, Ordering::Greater)?,
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> ExecutionResult<Value> {
+        Ok(match self {
+            Accumulator::Count(count) => Value::Integer(count),
+            // a REAL could not hold the total exactly
+            Accumulator::Sum(Some(NumericSum::Integer(total))) => {
+                Value::Integer(i64::try_from(total).map_err(|_| {
+                    ExecutionError::Execution("integer overflow in SUM".to_string())
+                })?)
+            }
+            Accumulator::Sum(Some(NumericSum::Float(total))) => Value::Float(total),
+            Accumulator::Avg {
+                sum: Some(sum),
+                count,
+            } => Value::Float(sum.as_f64() / count as f64),
+            Accumulator::Min(Some(value)) | Accumulator::Max(Some(value)) => value,
+            Accumulator::Sum(None)
+            | Accumulator::Avg { sum: None, .. }
+            | Accumulator::Min(None)
+            | Accumulator::Max(None) => Value::Null,
+        })
+    }
+}
+
+fn replace_if(current: &mut Option<Value>, value: Value, wanted: Ordering) -> ExecutionResult<()> {
+    let replace = match current {
+        None => true,
+        Some(existing) => compare_values(&value, existing)? == Some(wanted),
+    };
+    if replace {
+        *current = Some(value);
+    }
+    Ok(())
+}
@@ -0,0 +1,46 @@
+use crate::execution::operator::{ExecutionError, ExecutionResult};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Shared flag that lets another thread stop a running statement. Operators
+/// are polled between tuples, so cancellation takes effect at the next row.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Release);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Acquire)
+    }
+
+    pub fn check(&self) -> ExecutionResult<()> {
+        if self.is_cancelled() {
+            Err(ExecutionError::Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Returns a guard that cancels the token when dropped, e.g. together
+    /// with the future of an abandoned request.
+    pub fn cancel_on_drop(&self) -> CancelOnDrop {
+        CancelOnDrop(self.clone())
+    }
+}
+
+pub struct CancelOnDrop(CancellationToken);
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        self.0.cancel();
+    }
+}
@@ -0,0 +1,133 @@
+//! Row and cost estimates shown by EXPLAIN.
+//!
+//! Costs are relative units: reading a page in order costs 1, fetching one
+//! by index costs more, and each row or expression evaluated adds a small
+//! CPU charge. They explain why the planner picked a plan; they do not
+//! predict run time.
+
+use crate::execution::operator::{ExecutionResult, PhysicalOperator};
+use crate::execution::tuple::Tuple;
+use crate::expr::{BinaryOperator, Expr, LiteralValue, UnaryOperator};
+use std::any::Any;
+
+pub const SEQ_PAGE_COST: f64 = 1.0;
+pub const RANDOM_PAGE_COST: f64 = 4.0;
+pub const CPU_TUPLE_COST: f64 = 0.01;
+pub const CPU_OPERATOR_COST: f64 = 0.0025;
+
+/// Selectivity of conditions the estimator knows nothing about.
+const DEFAULT_SELECTIVITY: f64 = 1.0 / 3.0;
+/// Selectivity of an equality on a column that is not unique.
+pub const EQUALITY_SELECTIVITY: f64 = 0.1;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Estimate {
+    pub rows: f64,
+    pub cost: f64,
+}
+
+/// Fraction of `rows` rows expected to satisfy `predicate`. `unique` tells
+/// whether a column holds distinct values, so an equality on it matches one
+/// row; an equality between two columns is treated as a join key.
+pub fn selectivity(predicate: &Expr, rows: f64, unique: &dyn Fn(&str) -> bool) -> f64 {
+    let one_row = if rows > 1.0 { 1.0 / rows } else { 1.0 };
+    let equality = |left: &Expr, right: &Expr| match (left, right) {
+        (Expr::Column { .. }, Expr::Column { .. }) => one_row,
+        (Expr::Column { name, .. }, _) | (_, Expr::Column { name, .. }) if unique(name) => one_row,
+        _ => EQUALITY_SELECTIVITY,
+    };
+    let selectivity = match predicate {
+        Expr::BinaryOp { left, op, right } => match op {
+            BinaryOperator::And => {
+                selectivity(left, rows, unique) * selectivity(right, rows, unique)
+            }
+            BinaryOperator::Or => {
+                let left = selectivity(left, rows, unique);
+                let right = selectivity(right, rows, unique);
+                left + right - left * right
+            }
+            BinaryOperator::Eq => equality(left, right),
+            BinaryOperator::NotEq => 1.0 - equality(left, right),
+            _ => DEFAULT_SELECTIVITY,
+        },
+        Expr::UnaryOp {
+            op: UnaryOperator::Not,
+            expr,
+        } => 1.0 - selectivity(expr, rows, unique),
+        Expr::Between { negated, .. } => negate(0.25, *negated),
+        Expr::In {
+            expr,
+            list,
+            negated,
+        } => {
+            let matched = list.iter().map(|value| equality(expr, value)).sum::<f64>();
+            negate(matched, *negated)
+        }
+        Expr::IsNull { negated, .. } => negate(EQUALITY_SELECTIVITY, *negated),
+        Expr::Literal(LiteralValue::Boolean(flag)) => f64::from(u8::from(*flag)),
+        _ => DEFAULT_SELECTIVITY,
+    };
+    selectivity.clamp(0.0, 1.0)
+}
+
+fn negate(selectivity: f64, negated: bool) -> f64 {
+    if negated {
+        1.0 - selectivity.min(1.0)
+    } else {
+        selectivity
+    }
+}
+
+/// Rows left after keeping `selectivity` of `rows`; at least one row unless
+/// the input is empty.
+pub fn scale_rows(rows: f64, selectivity: f64) -> f64 {
+    (rows * selectivity).max(rows.min(1.0))
+}
+
+/// Wraps an operator planned for EXPLAIN so that its line shows the
+/// estimate. Execution and `as_any` pass through to the wrapped operator.
+pub struct Estimated {
+    inner: Box<dyn PhysicalOperator>,
+    estimate: Estimate,
+}
+
+impl Estimated {
+    pub fn new(inner: Box<dyn PhysicalOperator>, estimate: Estimate) -> Self {
+        Self { inner, estimate }
+    }
+}
+
+impl PhysicalOperator for Estimated {
+    fn open(&mut self) -> ExecutionResult<()> {
+        self.inner.open()
+    }
+
+    fn next(&mut self) -> ExecutionResult<Option<Tuple>> {
+        self.inner.next()
+    }
+
+    fn close(&mut self) -> ExecutionResult<()> {
+        self.inner.close()
+    }
+
+    fn rewind(&mut self) -> ExecutionResult<()> {
+        self.inner.rewind()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self.inner.as_any()
+    }
+
+    fn describe(&self) -> String {
+        format!(
+            "{}  (rows={:.0} cost={:.2})",
+            self.inner.describe(),
+            self.estimate.rows,
+            self.estimate.cost
+        )
+    }
+
+    fn children(&self) -> Vec<&dyn PhysicalOperator> {
+        self.inner.children()
+    }
+}
@@ -0,0 +1,61 @@
+use crate::execution::operator::{ExecutionResult, PhysicalOperator};
+use crate::execution::schema_version::SchemaVersion;
+use crate::execution::seq_scan::TableHeap;
+use crate::execution::tuple::{Tuple, Value};
+use std::any::Any;
+
+/// Answers an unfiltered `COUNT(*)` from the heap's maintained row count
+/// instead of scanning the table.
+pub struct CountRows {
+    heap: TableHeap,
+    table_name: String,
+    count: Option<usize>,
+    schema_version: Option<SchemaVersion>,
+}
+
+impl CountRows {
+    pub fn new(heap: TableHeap, table_name: impl Into<String>) -> Self {
+        Self {
+            heap,
+            table_name: table_name.into(),
+            count: None,
+            schema_version: None,
+        }
+    }
+
+    /// Fails `open` once the counted table has changed since planning.
+    pub fn with_schema_version(mut self, version: SchemaVersion) -> Self {
+        self.schema_version = Some(version);
+        self
+    }
+}
+
+impl PhysicalOperator for CountRows {
+    fn open(&mut self) -> ExecutionResult<()> {
+        if let Some(version) = &self.schema_version {
+            version.check()?;
+        }
+        self.count = Some(self.heap.row_count()?);
+        Ok(())
+    }
+
+    fn next(&mut self) -> ExecutionResult<Option<Tuple>> {
+        Ok(self
+            .count
+            .take()
+            .map(|count| Tuple::new(vec![Value::Integer(count as i64)])))
+    }
+
+    fn close(&mut self) -> ExecutionResult<()> {
+        self.count = None;
+        Ok(())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn describe(&self) -> String {
+        format!("CountRows: table={}", self.table_name)
+    }
+}
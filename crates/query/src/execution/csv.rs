@@ -0,0 +1,159 @@
+//! CSV files read and written by `COPY`.
+//!
+//! Follows PostgreSQL's CSV rules: a field is NULL only when it is unquoted
+//! and equal to the NULL string, so any value that would read back as NULL,
+//! such as an empty string under the default empty NULL string, is quoted.
+
+use crate::execution::operator::{ExecutionError, ExecutionResult};
+use crate::execution::tuple::Value;
+use crate::logical_plan::CopyOptions;
+use crate::schema::{DataType, Field};
+
+/// Appends one record, ending in a newline. `None` fields are NULL.
+pub fn write_record<I, S>(fields: I, options: &CopyOptions, out: &mut String)
+where
+    I: IntoIterator<Item = Option<S>>,
+    S: AsRef<str>,
+{
+    for (position, field) in fields.into_iter().enumerate() {
+        if position > 0 {
+            out.push(options.delimiter);
+        }
+        let Some(field) = field else {
+            out.push_str(&options.null);
+            continue;
+        };
+        let field = field.as_ref();
+        let needs_quotes =
+            field == options.null || field.contains([options.delimiter, options.quote, '\r', '\n']);
+        if !needs_quotes {
+            out.push_str(field);
+            continue;
+        }
+        out.push(options.quote);
+        for c in field.chars() {
+            if c == options.quote {
+                out.push(options.quote);
+            }
+            out.push(c);
+        }
+        out.push(options.quote);
+    }
+    out.push('\n');
+}
+
+/// Splits `input` into records of fields, `None` for NULL. Quoted fields may
+/// span lines; lines end in `\n` or `\r\n`.
+pub fn parse_records(
+    input: &str,
+    options: &CopyOptions,
+) -> ExecutionResult<Vec<Vec<Option<String>>>> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut in_quotes = false;
+    let finish_field = |field: &mut String, quoted: &mut bool| {
+        let value = if !*quoted && *field == options.null {
+            None
+        } else {
+            Some(field.clone())
+        };
+        field.clear();
+        *quoted = false;
+        value
+    };
+    let mut chars = input.chars().peekable();
+    while let Some(c) = chars.next() {
+        if in_quotes {
+            if c != options.quote {
+                field.push(c);
+            } else if chars.peek() == Some(&options.quote) {
+                field.push(c);
+                chars.next();
+            } else {
+                in_quotes = false;
+            }
+        } else if c == options.quote {
+            in_quotes = true;
+            quoted = true;
+        } else if c == options.delimiter {
+            record.push(finish_field(&mut field, &mut quoted));
+        } else if c == '\n' || (c == '\r' && chars.peek() == Some(&'\n')) {
+            if c == '\r' {
+                chars.next();
+            }
+            record.push(finish_field(&mut field, &mut quoted));
+            records.push(std::mem::take(&mut record));
+        } else {
+            field.push(c);
+        }
+    }
+    if in_quotes {
+        return Err(ExecutionError::Execution(
+            "CSV input ends inside a quoted field".to_string(),
+        ));
+    }
+    if !field.is_empty() || quoted || !record.is_empty() {
+        record.push(finish_field(&mut field, &mut quoted));
+        records.push(record);
+    }
+    Ok(records)
+}
+
+/// Text of `value` in a CSV field; `None` for NULL.
+pub fn format_value(value: &Value) -> Option<String> {
+    match value {
+        Value::Null => None,
+        Value::Integer(number) | Value::Timestamp(number) => Some(number.to_string()),
+        Value::Float(number) => Some(number.to_string()),
+        Value::String(text) => Some(text.clone()),
+        Value::Boolean(flag) => Some(flag.to_string()),
+        Value::Blob(bytes) => Some(format!(
+            "\\x{}",
+            bytes
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect::<String>()
+        )),
+    }
+}
+
+/// Reads a CSV field as a value of `field`'s type.
+pub fn parse_value(text: Option<&str>, field: &Field) -> ExecutionResult<Value> {
+    let Some(text) = text else {
+        return Ok(Value::Null);
+    };
+    let invalid = || {
+        ExecutionError::Execution(format!(
+            "invalid {:?} value {:?} for column {}",
+            field.data_type, text, field.name
+        ))
+    };
+    match field.data_type {
+        DataType::Integer | DataType::BigInt => {
+            text.parse().map(Value::Integer).map_err(|_| invalid())
+        }
+        DataType::Timestamp => text.parse().map(Value::Timestamp).map_err(|_| invalid()),
+        DataType::Real => text.parse().map(Value::Float).map_err(|_| invalid()),
+        DataType::Text => Ok(Value::String(text.to_string())),
+        DataType::Boolean => match text.to_ascii_lowercase().as_str() {
+            "t" | "true" | "1" => Ok(Value::Boolean(true)),
+            "f" | "false" | "0" => Ok(Value::Boolean(false)),
+            _ => Err(invalid()),
+        },
+        DataType::Blob => {
+            let hex = text.strip_prefix("\\x").ok_or_else(invalid)?;
+            if hex.len() % 2 != 0 {
+                return Err(invalid());
+            }
+            (0..hex.len())
+                .step_by(2)
+                .map(|start| u8::from_str_radix(&hex[start..start + 2], 16))
+                .collect::<Result<Vec<_>, _>>()
+                .map(Value::Blob)
+                .map_err(|_| invalid())
+        }
+        DataType::Unknown => Err(invalid()),
+    }
+}
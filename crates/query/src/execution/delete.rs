@@ -0,0 +1,107 @@
+use crate::execution::operator::{ExecutionResult, PhysicalOperator};
+use crate::execution::planner::TableInfo;
+use crate::execution::schema_version::SchemaVersion;
+use crate::execution::seq_scan::SeqScan;
+use crate::execution::tuple::{Tuple, Value};
+use crate::execution::update::read_targets;
+use crate::expr::Expr;
+use crate::schema::Schema;
+use std::any::Any;
+
+/// Removes the rows of `table` matching `filter`, with their index entries,
+/// when opened. Its single row is the number of rows removed.
+pub struct Delete {
+    table: TableInfo,
+    version: SchemaVersion,
+    filter: Option<Expr>,
+    /// Reads the rows to delete when there is no source: the whole table,
+    /// or an index scan that checks `filter` itself.
+    scan: Box<dyn PhysicalOperator>,
+    source: Option<(Box<dyn PhysicalOperator>, Schema)>,
+    deleted: Option<usize>,
+}
+
+impl Delete {
+    pub fn new(table: TableInfo, filter: Option<Expr>) -> Self {
+        Self {
+            version: table.schema_version(),
+            scan: Box::new(SeqScan::new(table.heap.clone(), table.schema.clone())),
+            table,
+            filter,
+            source: None,
+            deleted: None,
+        }
+    }
+
+    /// Finds the rows to delete with `scan`, an index scan that applies the
+    /// whole filter.
+    pub fn with_scan(mut self, scan: Box<dyn PhysicalOperator>) -> Self {
+        self.scan = scan;
+        self.filter = None;
+        self
+    }
+
+    /// Joins the target against the rows of `source` (`DELETE ... USING`).
+    pub fn with_source(mut self, source: Box<dyn PhysicalOperator>, schema: Schema) -> Self {
+        self.source = Some((source, schema));
+        self
+    }
+}
+
+impl PhysicalOperator for Delete {
+    fn open(&mut self) -> ExecutionResult<()> {
+        self.version.check()?;
+        let deleted = match &mut self.source {
+            Some((source, schema)) => {
+                source.open()?;
+                let mut rows = Vec::new();
+                while let Some(tuple) = source.next()? {
+                    rows.push(tuple);
+                }
+                source.close()?;
+                self.table
+                    .delete_tuples_using(self.filter.as_ref(), schema, &rows)?
+            }
+            None => {
+                let rids: Vec<_> =
+                    read_targets(self.scan.as_mut(), self.filter.as_ref(), &self.table.schema)?
+                        .into_iter()
+                        .map(|(rid, _)| rid)
+                        .collect();
+                self.table.delete_rids(&rids)?
+            }
+        };
+        self.deleted = Some(deleted);
+        Ok(())
+    }
+
+    fn next(&mut self) -> ExecutionResult<Option<Tuple>> {
+        Ok(self
+            .deleted
+            .take()
+            .map(|deleted| Tuple::new(vec![Value::Integer(deleted as i64)])))
+    }
+
+    fn close(&mut self) -> ExecutionResult<()> {
+        self.deleted = None;
+        Ok(())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn describe(&self) -> String {
+        match &self.filter {
+            Some(filter) => format!("Delete: {} filter: {}", self.table.name, filter),
+            None => format!("Delete: {}", self.table.name),
+        }
+    }
+
+    fn children(&self) -> Vec<&dyn PhysicalOperator> {
+        match &self.source {
+            Some((source, _)) => vec![source.as_ref()],
+            None => vec![self.scan.as_ref()],
+        }
+    }
+}
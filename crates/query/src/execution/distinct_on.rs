@@ -0,0 +1,81 @@
+use crate::execution::operator::{evaluate_expr, ExecutionResult, PhysicalOperator};
+use crate::execution::tuple::{Tuple, Value};
+use crate::expr::Expr;
+use crate::schema::Schema;
+use std::any::Any;
+
+/// `DISTINCT ON (on)`: returns the first row of each run of input rows with
+/// equal `on` values, NULLs counting as equal. The input is sorted on `on`
+/// first, so each group is a single run.
+pub struct DistinctOn {
+    child: Box<dyn PhysicalOperator>,
+    on: Vec<Expr>,
+    schema: Schema,
+    /// The `on` values of the last row returned.
+    last_key: Option<Vec<Value>>,
+}
+
+impl DistinctOn {
+    pub fn new(child: Box<dyn PhysicalOperator>, on: Vec<Expr>, schema: Schema) -> Self {
+        Self {
+            child,
+            on,
+            schema,
+            last_key: None,
+        }
+    }
+
+    pub fn child(&self) -> &dyn PhysicalOperator {
+        &*self.child
+    }
+}
+
+impl PhysicalOperator for DistinctOn {
+    fn open(&mut self) -> ExecutionResult<()> {
+        self.last_key = None;
+        self.child.open()
+    }
+
+    fn next(&mut self) -> ExecutionResult<Option<Tuple>> {
+        while let Some(tuple) = self.child.next()? {
+            let key = self
+                .on
+                .iter()
+                .map(|expr| evaluate_expr(expr, &tuple, &self.schema))
+                .collect::<ExecutionResult<Vec<_>>>()?;
+            if self.last_key.as_ref() != Some(&key) {
+                self.last_key = Some(key);
+                return Ok(Some(tuple));
+            }
+        }
+        Ok(None)
+    }
+
+    fn close(&mut self) -> ExecutionResult<()> {
+        self.last_key = None;
+        self.child.close()
+    }
+
+    fn rewind(&mut self) -> ExecutionResult<()> {
+        self.last_key = None;
+        self.child.rewind()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn describe(&self) -> String {
+        let on = self
+            .on
+            .iter()
+            .map(|expr| expr.to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+        format!("DistinctOn: [{}]", on)
+    }
+
+    fn children(&self) -> Vec<&dyn PhysicalOperator> {
+        vec![&*self.child]
+    }
+}
@@ -0,0 +1,183 @@
+use crate::execution::operator::{ExecutionError, ExecutionResult};
+use crate::execution::tuple::{Tuple, Value};
+use crate::schema::{DataType, Field, Schema};
+
+/// Binary result encoding, an alternative to JSON for clients that decode
+/// many rows. Integers are big-endian and variable-length data is prefixed
+/// with a `u32` length:
+///
+/// ```text
+/// "RDBR" version:u8
+/// column_count:u16 { name_len:u32 name type:u8 }*
+/// row_count:u32 { value }*            (column_count values per row)
+/// value = tag:u8 payload              (payload depends on the tag)
+/// ```
+pub const RESULT_MAGIC: &[u8; 4] = b"RDBR";
+pub const RESULT_VERSION: u8 = 1;
+
+const TAG_NULL: u8 = 0;
+const TAG_INTEGER: u8 = 1;
+const TAG_FLOAT: u8 = 2;
+const TAG_STRING: u8 = 3;
+const TAG_BOOLEAN: u8 = 4;
+const TAG_TIMESTAMP: u8 = 5;
+const TAG_BLOB: u8 = 6;
+
+pub fn encode_result(schema: &Schema, rows: &[Tuple]) -> Vec<u8> {
+    let mut out = Vec::new();
+    out.extend_from_slice(RESULT_MAGIC);
+    out.push(RESULT_VERSION);
+    out.extend_from_slice(&(schema.fields.len() as u16).to_be_bytes());
+    for field in &schema.fields {
+        put_bytes(&mut out, field.name.as_bytes());
+        out.push(data_type_tag(&field.data_type));
+    }
+    out.extend_from_slice(&(rows.len() as u32).to_be_bytes());
+    for row in rows {
+        for value in row.values() {
+            encode_value(value, &mut out);
+        }
+    }
+    out
+}
+
+pub fn decode_result(bytes: &[u8]) -> ExecutionResult<(Schema, Vec<Tuple>)> {
+    let mut input = bytes;
+    if take(&mut input, RESULT_MAGIC.len())? != RESULT_MAGIC {
+        return Err(decode_error("missing result header"));
+    }
+    let version = take(&mut input, 1)?[0];
+    if version != RESULT_VERSION {
+        return Err(decode_error(&format!("unsupported version {}", version)));
+    }
+    let column_count = u16::from_be_bytes(take_array(&mut input)?) as usize;
+    let mut fields = Vec::with_capacity(column_count);
+    for _ in 0..column_count {
+        let name = String::from_utf8(take_bytes(&mut input)?.to_vec())
+            .map_err(|_| decode_error("column name is not UTF-8"))?;
+        let data_type = data_type_from_tag(take(&mut input, 1)?[0])?;
+        fields.push(Field {
+            name,
+            table: None,
+            data_type,
+            nullable: true,
+            visible: true,
+        });
+    }
+    let row_count = u32::from_be_bytes(take_array(&mut input)?) as usize;
+    let mut rows = Vec::with_capacity(row_count.min(input.len()));
+    for _ in 0..row_count {
+        let values = (0..column_count)
+            .map(|_| decode_value(&mut input))
+            .collect::<ExecutionResult<Vec<_>>>()?;
+        rows.push(Tuple::new(values));
+    }
+    if !input.is_empty() {
+        return Err(decode_error("trailing bytes after last row"));
+    }
+    Ok((Schema::new(fields), rows))
+}
+
+pub fn encode_value(value: &Value, out: &mut Vec<u8>) {
+    match value {
+        Value::Null => out.push(TAG_NULL),
+        Value::Integer(v) => {
+            out.push(TAG_INTEGER);
+            out.extend_from_slice(&v.to_be_bytes());
+        }
+        Value::Float(v) => {
+            out.push(TAG_FLOAT);
+            out.extend_from_slice(&v.to_be_bytes());
+        }
+        Value::String(v) => {
+            out.push(TAG_STRING);
+            put_bytes(out, v.as_bytes());
+        }
+        Value::Boolean(v) => {
+            out.push(TAG_BOOLEAN);
+            out.push(u8::from(*v));
+        }
+        Value::Timestamp(v) => {
+            out.push(TAG_TIMESTAMP);
+            out.extend_from_slice(&v.to_be_bytes());
+        }
+        Value::Blob(v) => {
+            out.push(TAG_BLOB);
+            put_bytes(out, v);
+        }
+    }
+}
+
+/// Decodes one value and advances `input` past it.
+pub fn decode_value(input: &mut &[u8]) -> ExecutionResult<Value> {
+    let tag = take(input, 1)?[0];
+    Ok(match tag {
+        TAG_NULL => Value::Null,
+        TAG_INTEGER => Value::Integer(i64::from_be_bytes(take_array(input)?)),
+        TAG_FLOAT => Value::Float(f64::from_be_bytes(take_array(input)?)),
+        TAG_STRING => Value::String(
+            String::from_utf8(take_bytes(input)?.to_vec())
+                .map_err(|_| decode_error("text value is not UTF-8"))?,
+        ),
+        TAG_BOOLEAN => Value::Boolean(take(input, 1)?[0] != 0),
+        TAG_TIMESTAMP => Value::Timestamp(i64::from_be_bytes(take_array(input)?)),
+        TAG_BLOB => Value::Blob(take_bytes(input)?.to_vec()),
+        other => return Err(decode_error(&format!("unknown value tag {}", other))),
+    })
+}
+
+fn data_type_tag(data_type: &DataType) -> u8 {
+    match data_type {
+        DataType::Integer => 1,
+        DataType::BigInt => 2,
+        DataType::Real => 3,
+        DataType::Text => 4,
+        DataType::Boolean => 5,
+        DataType::Timestamp => 6,
+        DataType::Blob => 7,
+        DataType::Unknown => 0,
+    }
+}
+
+fn data_type_from_tag(tag: u8) -> ExecutionResult<DataType> {
+    Ok(match tag {
+        0 => DataType::Unknown,
+        1 => DataType::Integer,
+        2 => DataType::BigInt,
+        3 => DataType::Real,
+        4 => DataType::Text,
+        5 => DataType::Boolean,
+        6 => DataType::Timestamp,
+        7 => DataType::Blob,
+        other => return Err(decode_error(&format!("unknown column type {}", other))),
+    })
+}
+
+fn put_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    out.extend_from_slice(&(bytes.len() as u32).to_be_bytes());
+    out.extend_from_slice(bytes);
+}
+
+fn take<'a>(input: &mut &'a [u8], len: usize) -> ExecutionResult<&'a [u8]> {
+    if input.len() < len {
+        return Err(decode_error("unexpected end of input"));
+    }
+    let (head, rest) = input.split_at(len);
+    *input = rest;
+    Ok(head)
+}
+
+fn take_array<const N: usize>(input: &mut &[u8]) -> ExecutionResult<[u8; N]> {
+    let mut array = [0u8; N];
+    array.copy_from_slice(take(input, N)?);
+    Ok(array)
+}
+
+fn take_bytes<'a>(input: &mut &'a [u8]) -> ExecutionResult<&'a [u8]> {
+    let len = u32::from_be_bytes(take_array(input)?) as usize;
+    take(input, len)
+}
+
+fn decode_error(message: &str) -> ExecutionError {
+    ExecutionError::Execution(format!("invalid binary result: {}", message))
+}
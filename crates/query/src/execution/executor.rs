@@ -1,19 +1,35 @@
+use crate::execution::cancel::CancellationToken;
 use crate::execution::operator::{ExecutionResult, PhysicalOperator};
 use crate::execution::tuple::Tuple;
 
 pub struct Executor {
     root: Box<dyn PhysicalOperator>,
+    cancellation: Option<CancellationToken>,
 }
 
 impl Executor {
     pub fn new(root: Box<dyn PhysicalOperator>) -> Self {
-        Self { root }
+        Self {
+            root,
+            cancellation: None,
+        }
+    }
+
+    /// Stops execution with `ExecutionError::Cancelled` once `token` is
+    /// cancelled.
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = Some(token);
+        self
     }
 
     pub fn execute(&mut self) -> ExecutionResult<Vec<Tuple>> {
+        self.check_cancelled()?;
         self.root.open()?;
         let mut output = Vec::new();
         let result = loop {
+            if let Err(error) = self.check_cancelled() {
+                break Err(error);
+            }
             match self.root.next() {
                 Ok(Some(tuple)) => output.push(tuple),
                 Ok(None) => break Ok(output),
@@ -27,4 +43,11 @@ impl Executor {
             (Ok(output), Ok(())) => Ok(output),
         }
     }
+
+    fn check_cancelled(&self) -> ExecutionResult<()> {
+        match &self.cancellation {
+            Some(token) => token.check(),
+            None => Ok(()),
+        }
+    }
 }
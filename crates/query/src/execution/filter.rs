@@ -45,7 +45,19 @@ impl PhysicalOperator for Filter {
         self.child.close()
     }
 
+    fn rewind(&mut self) -> ExecutionResult<()> {
+        self.child.rewind()
+    }
+
     fn as_any(&self) -> &dyn Any {
         self
     }
+
+    fn describe(&self) -> String {
+        format!("Filter: {}", self.predicate)
+    }
+
+    fn children(&self) -> Vec<&dyn PhysicalOperator> {
+        vec![&*self.child]
+    }
 }
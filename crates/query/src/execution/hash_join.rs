@@ -0,0 +1,332 @@
+use crate::execution::nested_loop_join::{collect_join_keys, join_key, join_schema};
+use crate::execution::operator::{evaluate_predicate, ExecutionResult, PhysicalOperator};
+use crate::execution::spill::{SpillReader, SpillWriter, TempUsage, DEFAULT_WORK_MEM};
+use crate::execution::tuple::{Tuple, Value};
+use crate::expr::{BinaryOperator, Expr};
+use crate::logical_plan::JoinType;
+use crate::schema::Schema;
+use std::any::Any;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, VecDeque};
+use std::hash::{Hash, Hasher};
+
+/// Temporary files each input is split into when the build side outgrows
+/// `work_mem`.
+const PARTITIONS: usize = 16;
+
+/// The column pair `(left, right)` when `predicate` is a single equality
+/// between a column of each side.
+pub(crate) fn equijoin_key(
+    predicate: &Expr,
+    left: &Schema,
+    right: &Schema,
+) -> Option<(usize, usize)> {
+    if !matches!(
+        predicate,
+        Expr::BinaryOp {
+            op: BinaryOperator::Eq,
+            ..
+        }
+    ) {
+        return None;
+    }
+    let mut keys = Vec::new();
+    collect_join_keys(predicate, left, right, &mut keys);
+    keys.pop()
+}
+
+/// Where the probe rows come from.
+enum ProbeSource {
+    Input,
+    Spilled(SpillReader),
+    Done,
+}
+
+/// `left JOIN right ON left.a = right.b`: reads the build side into a hash
+/// table keyed by its join column, then looks each row of the other side up
+/// in it. The right input is the build side unless `with_build_left` picks
+/// the left; a LEFT join always builds on the right, so that unmatched left
+/// rows can be padded as they are probed.
+///
+/// A build side over `work_mem` bytes is split with the probe side into
+/// temporary files by key hash, and the pairs are joined one at a time.
+pub struct HashJoin {
+    left: Box<dyn PhysicalOperator>,
+    right: Box<dyn PhysicalOperator>,
+    predicate: Expr,
+    join_type: JoinType,
+    combined_schema: Schema,
+    right_width: usize,
+    left_key: usize,
+    right_key: usize,
+    build_left: bool,
+    work_mem: usize,
+    temp_usage: TempUsage,
+    table: HashMap<Vec<Value>, Vec<Tuple>>,
+    probe_source: ProbeSource,
+    /// Spilled `(build, probe)` partitions not joined yet.
+    partitions: VecDeque<(SpillReader, SpillReader)>,
+    /// The probe row being joined, its key, and the next build row to try.
+    current: Option<(Tuple, Option<Vec<Value>>, usize)>,
+    /// Whether a build row has matched the current probe row.
+    matched: bool,
+}
+
+impl HashJoin {
+    /// `left_key` and `right_key` index the equated columns in `left_schema`
+    /// and `right_schema`; `predicate` is still checked on each match.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        left: Box<dyn PhysicalOperator>,
+        right: Box<dyn PhysicalOperator>,
+        predicate: Expr,
+        join_type: JoinType,
+        left_schema: Schema,
+        right_schema: Schema,
+        left_key: usize,
+        right_key: usize,
+    ) -> Self {
+        Self {
+            left,
+            right,
+            predicate,
+            join_type,
+            combined_schema: join_schema(&left_schema, &right_schema, join_type),
+            right_width: right_schema.fields.len(),
+            left_key,
+            right_key,
+            build_left: false,
+            work_mem: DEFAULT_WORK_MEM,
+            temp_usage: TempUsage::default(),
+            table: HashMap::new(),
+            probe_source: ProbeSource::Done,
+            partitions: VecDeque::new(),
+            current: None,
+            matched: false,
+        }
+    }
+
+    /// Builds the hash table on the left input instead, for an inner join
+    /// whose left side is the smaller.
+    pub fn with_build_left(mut self, build_left: bool) -> Self {
+        self.build_left = build_left && self.join_type == JoinType::Inner;
+        self
+    }
+
+    /// Bytes of build rows held in memory before both sides are partitioned
+    /// to temporary files.
+    pub fn with_work_mem(mut self, bytes: usize) -> Self {
+        self.work_mem = bytes;
+        self
+    }
+
+    /// Counts the pages of the partitions this join spills in `usage` too.
+    pub fn with_temp_usage(mut self, usage: TempUsage) -> Self {
+        self.temp_usage = usage;
+        self
+    }
+
+    fn build_key(&self) -> usize {
+        if self.build_left {
+            self.left_key
+        } else {
+            self.right_key
+        }
+    }
+
+    fn probe_key(&self) -> usize {
+        if self.build_left {
+            self.right_key
+        } else {
+            self.left_key
+        }
+    }
+
+    fn build_input(&mut self) -> &mut dyn PhysicalOperator {
+        if self.build_left {
+            self.left.as_mut()
+        } else {
+            self.right.as_mut()
+        }
+    }
+
+    fn probe_input(&mut self) -> &mut dyn PhysicalOperator {
+        if self.build_left {
+            self.right.as_mut()
+        } else {
+            self.left.as_mut()
+        }
+    }
+
+    fn insert(&mut self, tuple: Tuple) {
+        if let Some(key) = join_key(&tuple, std::iter::once(self.build_key())) {
+            self.table.entry(key).or_default().push(tuple);
+        }
+    }
+
+    /// Reads the build side into `table`, or into partitions once it
+    /// outgrows `work_mem`.
+    fn build(&mut self) -> ExecutionResult<()> {
+        let mut rows = Vec::new();
+        let mut memory = 0;
+        while let Some(tuple) = self.build_input().next()? {
+            memory += tuple.memory_size();
+            rows.push(tuple);
+            if memory > self.work_mem {
+                return self.partition(rows);
+            }
+        }
+        for tuple in rows {
+            self.insert(tuple);
+        }
+        self.probe_source = ProbeSource::Input;
+        Ok(())
+    }
+
+    /// Writes `rows`, the rest of the build side and the whole probe side to
+    /// `PARTITIONS` pairs of temporary files by key hash. Build rows with a
+    /// NULL key are dropped; such probe rows go to the first partition, where
+    /// nothing matches them.
+    fn partition(&mut self, rows: Vec<Tuple>) -> ExecutionResult<()> {
+        let mut build = Vec::with_capacity(PARTITIONS);
+        let mut probe = Vec::with_capacity(PARTITIONS);
+        for _ in 0..PARTITIONS {
+            build.push(SpillWriter::create()?);
+            probe.push(SpillWriter::create()?);
+        }
+        let build_key = self.build_key();
+        for tuple in rows {
+            if let Some(key) = join_key(&tuple, std::iter::once(build_key)) {
+                build[partition_of(&key)].write(tuple.values())?;
+            }
+        }
+        while let Some(tuple) = self.build_input().next()? {
+            if let Some(key) = join_key(&tuple, std::iter::once(build_key)) {
+                build[partition_of(&key)].write(tuple.values())?;
+            }
+        }
+        let probe_key = self.probe_key();
+        while let Some(tuple) = self.probe_input().next()? {
+            let partition =
+                join_key(&tuple, std::iter::once(probe_key)).map_or(0, |key| partition_of(&key));
+            probe[partition].write(tuple.values())?;
+        }
+        for (build, probe) in build.into_iter().zip(probe) {
+            self.partitions.push_back((
+                build.finish(&self.temp_usage)?,
+                probe.finish(&self.temp_usage)?,
+            ));
+        }
+        self.probe_source = ProbeSource::Done;
+        Ok(())
+    }
+
+    /// The next probe row, moving on to the next spilled partition when the
+    /// current one runs out.
+    fn next_probe(&mut self) -> ExecutionResult<Option<Tuple>> {
+        loop {
+            let tuple = match &mut self.probe_source {
+                ProbeSource::Input => self.probe_input().next()?,
+                ProbeSource::Spilled(reader) => reader.next()?,
+                ProbeSource::Done => None,
+            };
+            if tuple.is_some() {
+                return Ok(tuple);
+            }
+            let Some((mut build, probe)) = self.partitions.pop_front() else {
+                self.probe_source = ProbeSource::Done;
+                return Ok(None);
+            };
+            self.table.clear();
+            while let Some(tuple) = build.next()? {
+                self.insert(tuple);
+            }
+            self.probe_source = ProbeSource::Spilled(probe);
+        }
+    }
+
+    /// Ends the current probe row, returning it padded with NULLs when the
+    /// join is LEFT and no build row matched it.
+    fn finish_probe(&mut self) -> Option<Tuple> {
+        let (tuple, _, _) = self.current.take()?;
+        let matched = std::mem::take(&mut self.matched);
+        (self.join_type == JoinType::Left && !matched)
+            .then(|| tuple.concat(&Tuple::new(vec![Value::Null; self.right_width])))
+    }
+}
+
+fn partition_of(key: &[Value]) -> usize {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    (hasher.finish() % PARTITIONS as u64) as usize
+}
+
+impl PhysicalOperator for HashJoin {
+    fn open(&mut self) -> ExecutionResult<()> {
+        self.table.clear();
+        self.partitions.clear();
+        self.current = None;
+        self.matched = false;
+        self.left.open()?;
+        self.right.open()?;
+        self.build()
+    }
+
+    fn next(&mut self) -> ExecutionResult<Option<Tuple>> {
+        loop {
+            let Some((probe, key, position)) = &mut self.current else {
+                let Some(tuple) = self.next_probe()? else {
+                    return Ok(None);
+                };
+                let key = join_key(&tuple, std::iter::once(self.probe_key()));
+                self.current = Some((tuple, key, 0));
+                continue;
+            };
+            let candidate = key
+                .as_ref()
+                .and_then(|key| self.table.get(key))
+                .and_then(|rows| rows.get(*position));
+            let Some(build) = candidate else {
+                if let Some(padded) = self.finish_probe() {
+                    return Ok(Some(padded));
+                }
+                continue;
+            };
+            *position += 1;
+            let joined = if self.build_left {
+                build.concat(probe)
+            } else {
+                probe.concat(build)
+            };
+            if evaluate_predicate(&self.predicate, &joined, &self.combined_schema)? {
+                self.matched = true;
+                return Ok(Some(joined));
+            }
+        }
+    }
+
+    fn close(&mut self) -> ExecutionResult<()> {
+        self.table.clear();
+        self.partitions.clear();
+        self.probe_source = ProbeSource::Done;
+        self.current = None;
+        self.right.close()?;
+        self.left.close()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn describe(&self) -> String {
+        let build = if self.build_left { "left" } else { "right" };
+        match self.join_type {
+            JoinType::Left => format!("HashJoin: LEFT ON {} (build {})", self.predicate, build),
+            _ => format!("HashJoin: ON {} (build {})", self.predicate, build),
+        }
+    }
+
+    fn children(&self) -> Vec<&dyn PhysicalOperator> {
+        vec![&*self.left, &*self.right]
+    }
+}
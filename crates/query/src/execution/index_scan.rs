@@ -1,9 +1,13 @@
-use crate::execution::operator::{ExecutionResult, PhysicalOperator};
+use crate::execution::operator::{evaluate_predicate, ExecutionResult, PhysicalOperator};
+use crate::execution::schema_version::SchemaVersion;
 use crate::execution::seq_scan::{Rid, TableHeap};
 use crate::execution::tuple::Tuple;
-use crate::index::{BPlusTree, Index, IndexKey, IndexRange};
+use crate::expr::Expr;
+use crate::index::{BPlusTree, BPlusTreeIter, Index, IndexKey, IndexRange};
 use crate::schema::Schema;
 use std::any::Any;
+use std::collections::HashSet;
+use std::fmt;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct IndexPredicate {
@@ -19,6 +23,25 @@ impl IndexPredicate {
         }
     }
 
+    pub fn is_equality(&self) -> bool {
+        matches!(
+            (&self.lower, &self.upper),
+            (Some((lower, true)), Some((upper, true))) if lower == upper
+        )
+    }
+
+    /// Whether the predicate selects the NULL keys.
+    pub fn is_null(&self) -> bool {
+        self.is_equality() && matches!(&self.lower, Some((IndexKey::Null, _)))
+    }
+
+    /// The lower bound, unless it only skips the NULL keys.
+    pub fn lower_bound(&self) -> Option<&(IndexKey, bool)> {
+        self.lower
+            .as_ref()
+            .filter(|bound| **bound != (IndexKey::Null, false))
+    }
+
     pub fn to_range(&self) -> IndexRange {
         IndexRange {
             lower: self.lower.clone(),
@@ -27,13 +50,60 @@ impl IndexPredicate {
     }
 }
 
+impl fmt::Display for IndexPredicate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_null() {
+            return write!(f, "IS NULL");
+        }
+        if self.is_equality() {
+            if let Some((key, _)) = &self.lower {
+                return write!(f, "= {}", key.display());
+            }
+        }
+        let mut bounds = Vec::new();
+        if let Some((key, inclusive)) = self.lower_bound() {
+            bounds.push(format!(
+                "{} {}",
+                if *inclusive { ">=" } else { ">" },
+                key.display()
+            ));
+        }
+        if let Some((key, inclusive)) = &self.upper {
+            bounds.push(format!(
+                "{} {}",
+                if *inclusive { "<=" } else { "<" },
+                key.display()
+            ));
+        }
+        if bounds.is_empty() && self.lower.is_some() {
+            write!(f, "IS NOT NULL")
+        } else if bounds.is_empty() {
+            write!(f, "full")
+        } else {
+            write!(f, "{}", bounds.join(" AND "))
+        }
+    }
+}
+
 pub struct IndexScan {
     table_heap: TableHeap,
     schema: Schema,
     index: BPlusTree,
-    predicate: IndexPredicate,
-    rids: Vec<Rid>,
-    position: usize,
+    /// Ranges scanned one after another; a row is returned for the first
+    /// one it matches.
+    predicates: Vec<IndexPredicate>,
+    /// The matching entries of `predicates[next_predicate - 1]`, read as
+    /// rows are asked for.
+    entries: Option<BPlusTreeIter>,
+    next_predicate: usize,
+    /// Rows already returned, kept when there is more than one range.
+    returned: HashSet<Rid>,
+    last_rid: Option<Rid>,
+    schema_version: Option<SchemaVersion>,
+    index_name: Option<String>,
+    /// Conjuncts of the WHERE clause the index cannot answer, checked on
+    /// each fetched row.
+    residual: Option<Expr>,
 }
 
 impl IndexScan {
@@ -42,44 +112,138 @@ impl IndexScan {
         schema: Schema,
         index: BPlusTree,
         predicate: IndexPredicate,
+    ) -> Self {
+        Self::union(table_heap, schema, index, vec![predicate])
+    }
+
+    /// Scans the rows matching any of `predicates`, each once: the rows of
+    /// the first range in key order, then those of the next not seen yet.
+    pub fn union(
+        table_heap: TableHeap,
+        schema: Schema,
+        index: BPlusTree,
+        predicates: Vec<IndexPredicate>,
     ) -> Self {
         Self {
             table_heap,
             schema,
             index,
-            predicate,
-            rids: Vec::new(),
-            position: 0,
+            predicates,
+            entries: None,
+            next_predicate: 0,
+            returned: HashSet::new(),
+            last_rid: None,
+            schema_version: None,
+            index_name: None,
+            residual: None,
         }
     }
+
+    /// Fails `open` once the scanned table has changed since planning.
+    pub fn with_schema_version(mut self, version: SchemaVersion) -> Self {
+        self.schema_version = Some(version);
+        self
+    }
+
+    /// Returns only the fetched rows that satisfy `residual`.
+    pub fn with_residual(mut self, residual: Expr) -> Self {
+        self.residual = Some(residual);
+        self
+    }
+
+    pub fn residual(&self) -> Option<&Expr> {
+        self.residual.as_ref()
+    }
+
+    /// Starts reading the next range, leaving `entries` empty after the
+    /// last one.
+    fn advance(&mut self) -> ExecutionResult<()> {
+        self.entries = match self.predicates.get(self.next_predicate) {
+            Some(predicate) => Some(self.index.scan(predicate.to_range())?),
+            None => None,
+        };
+        self.next_predicate += 1;
+        Ok(())
+    }
+
+    /// Name shown for the index by EXPLAIN.
+    pub fn with_index_name(mut self, name: impl Into<String>) -> Self {
+        self.index_name = Some(name.into());
+        self
+    }
 }
 
 impl PhysicalOperator for IndexScan {
     fn open(&mut self) -> ExecutionResult<()> {
-        let range = self.predicate.to_range();
-        self.rids = self.index.range_scan(range)?;
-        self.position = 0;
+        if let Some(version) = &self.schema_version {
+            version.check()?;
+        }
+        self.returned.clear();
+        self.last_rid = None;
+        self.next_predicate = 0;
+        self.entries = None;
+        self.advance()?;
         Ok(())
     }
 
     fn next(&mut self) -> ExecutionResult<Option<Tuple>> {
-        while self.position < self.rids.len() {
-            let rid = self.rids[self.position];
-            self.position += 1;
-            if let Some(tuple) = self.table_heap.get_tuple(rid, &self.schema)? {
-                return Ok(Some(tuple));
+        while let Some(entries) = self.entries.as_mut() {
+            let Some(entry) = entries.next() else {
+                self.advance()?;
+                continue;
+            };
+            let rid = entry?.rid;
+            if self.predicates.len() > 1 && !self.returned.insert(rid) {
+                continue;
+            }
+            let Some(tuple) = self.table_heap.get_tuple(rid, &self.schema)? else {
+                continue;
+            };
+            match &self.residual {
+                Some(residual) if !evaluate_predicate(residual, &tuple, &self.schema)? => {}
+                _ => {
+                    self.last_rid = Some(rid);
+                    return Ok(Some(tuple));
+                }
             }
         }
         Ok(None)
     }
 
     fn close(&mut self) -> ExecutionResult<()> {
-        self.rids.clear();
-        self.position = 0;
+        self.entries = None;
+        self.returned.clear();
         Ok(())
     }
 
     fn as_any(&self) -> &dyn Any {
         self
     }
+
+    fn last_rid(&self) -> Option<Rid> {
+        self.last_rid
+    }
+
+    fn describe(&self) -> String {
+        let table = self
+            .schema
+            .fields
+            .first()
+            .and_then(|field| field.table.as_deref())
+            .unwrap_or("?");
+        let mut description = format!(
+            "IndexScan: {} using {} ({})",
+            table,
+            self.index_name.as_deref().unwrap_or("?"),
+            self.predicates
+                .iter()
+                .map(IndexPredicate::to_string)
+                .collect::<Vec<_>>()
+                .join(" OR ")
+        );
+        if let Some(residual) = &self.residual {
+            description.push_str(&format!(" filter: {}", residual));
+        }
+        description
+    }
 }
@@ -0,0 +1,318 @@
+//! Read-only access to tables in database files written before format
+//! version 3, so the engine can copy their rows into a file in the current
+//! format. These files keep each page's contents right after an 8-byte LSN
+//! with no checksum, and may still hold tuples in the fixed layout that
+//! predates the compact one, and out-of-line blobs stored without a
+//! checksum. The layouts here are frozen; nothing in this module writes.
+
+use crate::execution::operator::{ExecutionError, ExecutionResult};
+use crate::execution::seq_scan::{read_exact, read_u32, read_u64, read_varint, unzigzag};
+use crate::execution::tuple::{Tuple, Value};
+use crate::schema::{DataType, Field, Schema};
+use std::fs::File;
+use std::os::unix::fs::FileExt;
+use std::path::Path;
+use storage::{PageId, PAGE_SIZE};
+
+// Pages started with their LSN; everything else followed it.
+const CONTENT_OFFSET: usize = 8;
+// Heap page header: next page id (8), slot count (4), free space offset (4).
+const HEAP_HEADER_SIZE: usize = 16;
+const SLOT_SIZE: usize = 8;
+// Blob page header: next page id (8), payload length (4).
+const BLOB_HEADER_SIZE: usize = 12;
+const INVALID_PAGE_ID: PageId = 0;
+
+const BLOB_INLINE: u8 = 0;
+const BLOB_OUT_OF_LINE: u8 = 1;
+const BLOB_OUT_OF_LINE_CHECKSUMMED: u8 = 2;
+
+// First byte of a compact tuple. Fixed-layout tuples start with a per-field
+// null byte of 0 or 1, or are empty.
+const TUPLE_COMPACT: u8 = 0x80;
+
+/// A database file in format version 1 or 2, opened read-only.
+pub struct LegacyHeapFile {
+    file: File,
+    page_count: u64,
+}
+
+impl LegacyHeapFile {
+    pub fn open(path: &Path) -> ExecutionResult<Self> {
+        let file = File::open(path).map_err(|err| io_error("open", err))?;
+        let len = file.metadata().map_err(|err| io_error("stat", err))?.len();
+        Ok(Self {
+            file,
+            page_count: len / PAGE_SIZE as u64,
+        })
+    }
+
+    /// Returns the live rows of the table whose heap starts at
+    /// `first_page_id`, in heap order.
+    pub fn read_table(
+        &self,
+        first_page_id: PageId,
+        schema: &Schema,
+    ) -> ExecutionResult<Vec<Tuple>> {
+        let mut tuples = Vec::new();
+        let mut page_id = first_page_id;
+        let mut pages_seen = 0u64;
+        while page_id != INVALID_PAGE_ID {
+            pages_seen += 1;
+            if pages_seen > self.page_count {
+                return Err(ExecutionError::Execution(
+                    "table page chain loops".to_string(),
+                ));
+            }
+            let page = self.read_page(page_id)?;
+            let header = &page[CONTENT_OFFSET..CONTENT_OFFSET + HEAP_HEADER_SIZE];
+            let next_page_id = read_u64(&header[0..8]);
+            let slot_count = read_u32(&header[8..12]) as usize;
+            for slot_index in 0..slot_count {
+                let offset = CONTENT_OFFSET + HEAP_HEADER_SIZE + slot_index * SLOT_SIZE;
+                let slot = page.get(offset..offset + SLOT_SIZE).ok_or_else(|| {
+                    ExecutionError::Execution("slot offset outside page".to_string())
+                })?;
+                let tuple_offset = read_u32(&slot[0..4]) as usize;
+                let tuple_len = read_u32(&slot[4..8]) as usize;
+                if tuple_len == 0 {
+                    // deleted
+                    continue;
+                }
+                let data = page
+                    .get(tuple_offset..tuple_offset + tuple_len)
+                    .ok_or_else(|| {
+                        ExecutionError::Execution("failed to read tuple bytes".to_string())
+                    })?;
+                tuples.push(self.decode_tuple(schema, data)?);
+            }
+            page_id = next_page_id;
+        }
+        Ok(tuples)
+    }
+
+    fn read_page(&self, page_id: PageId) -> ExecutionResult<Vec<u8>> {
+        if page_id >= self.page_count {
+            return Err(ExecutionError::Execution(format!(
+                "page {} is past the end of the file",
+                page_id
+            )));
+        }
+        let mut page = vec![0u8; PAGE_SIZE];
+        self.file
+            .read_exact_at(&mut page, page_id * PAGE_SIZE as u64)
+            .map_err(|err| io_error("read page", err))?;
+        Ok(page)
+    }
+
+    fn read_blob(
+        &self,
+        first_page_id: PageId,
+        length: u32,
+        checksum: Option<u64>,
+    ) -> ExecutionResult<Vec<u8>> {
+        let length = length as usize;
+        let payload_capacity = PAGE_SIZE - CONTENT_OFFSET - BLOB_HEADER_SIZE;
+        let mut output = Vec::with_capacity(length);
+        let mut page_id = first_page_id;
+        while output.len() < length {
+            if page_id == INVALID_PAGE_ID {
+                return Err(ExecutionError::Execution(
+                    "blob payload length mismatch".to_string(),
+                ));
+            }
+            let page = self.read_page(page_id)?;
+            let header = &page[CONTENT_OFFSET..CONTENT_OFFSET + BLOB_HEADER_SIZE];
+            let payload_len = read_u32(&header[8..12]) as usize;
+            if payload_len == 0
+                || payload_len > payload_capacity
+                || output.len() + payload_len > length
+            {
+                return Err(ExecutionError::Execution(
+                    "blob payload length mismatch".to_string(),
+                ));
+            }
+            let payload_offset = CONTENT_OFFSET + BLOB_HEADER_SIZE;
+            output.extend_from_slice(&page[payload_offset..payload_offset + payload_len]);
+            page_id = read_u64(&header[0..8]);
+        }
+        if checksum.is_some_and(|checksum| checksum != blob_checksum(&output)) {
+            return Err(ExecutionError::Execution(
+                "blob checksum mismatch".to_string(),
+            ));
+        }
+        Ok(output)
+    }
+
+    fn decode_tuple(&self, schema: &Schema, data: &[u8]) -> ExecutionResult<Tuple> {
+        if data.first() != Some(&TUPLE_COMPACT) {
+            return self.decode_fixed_tuple(schema, data);
+        }
+        let mut cursor = 1usize;
+        let stored = usize::try_from(read_varint(data, &mut cursor)?)
+            .ok()
+            .filter(|stored| *stored <= schema.fields.len())
+            .ok_or_else(|| ExecutionError::Execution("tuple has too many fields".to_string()))?;
+        // columns added after the row was written are NULL
+        let stored_fields = &schema.fields[..stored];
+        let bitmap = read_exact(data, cursor, bitmap_len(stored_fields))?;
+        cursor += bitmap.len();
+        let bit_set = |bit: usize| bitmap[bit / 8] & (1 << (bit % 8)) != 0;
+
+        let mut values = Vec::with_capacity(schema.fields.len());
+        let mut bit = 0usize;
+        for field in stored_fields {
+            let null_bit = bit;
+            bit += 1;
+            if field.data_type == DataType::Boolean {
+                bit += 1;
+            }
+            if bit_set(null_bit) {
+                values.push(Value::Null);
+                continue;
+            }
+            let value = match field.data_type {
+                DataType::Integer | DataType::BigInt => {
+                    Value::Integer(unzigzag(read_varint(data, &mut cursor)?))
+                }
+                DataType::Boolean => Value::Boolean(bit_set(null_bit + 1)),
+                _ => self.decode_value(&field.data_type, data, &mut cursor)?,
+            };
+            values.push(value);
+        }
+        values.resize(schema.fields.len(), Value::Null);
+        Ok(Tuple::new(values))
+    }
+
+    /// Decodes a tuple in the fixed layout: a null byte per field, and
+    /// fixed-width integers and booleans.
+    fn decode_fixed_tuple(&self, schema: &Schema, data: &[u8]) -> ExecutionResult<Tuple> {
+        let mut cursor = 0usize;
+        let mut values = Vec::with_capacity(schema.fields.len());
+        for field in &schema.fields {
+            if cursor >= data.len() {
+                values.push(Value::Null);
+                continue;
+            }
+            let is_null = data[cursor] == 1;
+            cursor += 1;
+            if is_null {
+                values.push(Value::Null);
+                continue;
+            }
+            let value = match field.data_type {
+                DataType::Integer => {
+                    let bytes = read_exact(data, cursor, 4)?;
+                    cursor += 4;
+                    Value::Integer(read_u32(bytes) as i32 as i64)
+                }
+                DataType::BigInt => {
+                    let bytes = read_exact(data, cursor, 8)?;
+                    cursor += 8;
+                    Value::Integer(read_u64(bytes) as i64)
+                }
+                DataType::Boolean => {
+                    let flag = read_exact(data, cursor, 1)?[0];
+                    cursor += 1;
+                    Value::Boolean(flag != 0)
+                }
+                _ => self.decode_value(&field.data_type, data, &mut cursor)?,
+            };
+            values.push(value);
+        }
+        Ok(Tuple::new(values))
+    }
+
+    /// Decodes a REAL, TEXT, TIMESTAMP or BLOB value, which both tuple
+    /// layouts encode the same way, and advances `cursor` past it.
+    fn decode_value(
+        &self,
+        data_type: &DataType,
+        data: &[u8],
+        cursor: &mut usize,
+    ) -> ExecutionResult<Value> {
+        match data_type {
+            DataType::Real => {
+                let bytes = read_exact(data, *cursor, 8)?;
+                *cursor += 8;
+                Ok(Value::Float(f64::from_bits(read_u64(bytes))))
+            }
+            DataType::Text => {
+                let length = read_u32(read_exact(data, *cursor, 4)?) as usize;
+                *cursor += 4;
+                let text_bytes = read_exact(data, *cursor, length)?;
+                *cursor += length;
+                let text = String::from_utf8(text_bytes.to_vec())
+                    .map_err(|_| ExecutionError::Execution("invalid utf8 string".to_string()))?;
+                Ok(Value::String(text))
+            }
+            DataType::Timestamp => {
+                let bytes = read_exact(data, *cursor, 8)?;
+                *cursor += 8;
+                Ok(Value::Timestamp(read_u64(bytes) as i64))
+            }
+            DataType::Blob => {
+                let flag = read_exact(data, *cursor, 1)?[0];
+                *cursor += 1;
+                match flag {
+                    BLOB_INLINE => {
+                        let length = read_u32(read_exact(data, *cursor, 4)?) as usize;
+                        *cursor += 4;
+                        let blob_bytes = read_exact(data, *cursor, length)?;
+                        *cursor += length;
+                        Ok(Value::Blob(blob_bytes.to_vec()))
+                    }
+                    BLOB_OUT_OF_LINE | BLOB_OUT_OF_LINE_CHECKSUMMED => {
+                        let first_page_id = read_u64(read_exact(data, *cursor, 8)?);
+                        *cursor += 8;
+                        let length = read_u32(read_exact(data, *cursor, 4)?);
+                        *cursor += 4;
+                        let checksum = if flag == BLOB_OUT_OF_LINE_CHECKSUMMED {
+                            let checksum = read_u64(read_exact(data, *cursor, 8)?);
+                            *cursor += 8;
+                            Some(checksum)
+                        } else {
+                            None
+                        };
+                        Ok(Value::Blob(self.read_blob(
+                            first_page_id,
+                            length,
+                            checksum,
+                        )?))
+                    }
+                    _ => Err(ExecutionError::Execution(
+                        "invalid blob storage flag".to_string(),
+                    )),
+                }
+            }
+            DataType::Integer | DataType::BigInt | DataType::Boolean | DataType::Unknown => {
+                Err(ExecutionError::Execution(format!(
+                    "{:?} is not a shared tuple encoding",
+                    data_type
+                )))
+            }
+        }
+    }
+}
+
+fn io_error(action: &str, err: std::io::Error) -> ExecutionError {
+    ExecutionError::Execution(format!("failed to {} old database file: {}", action, err))
+}
+
+/// The blob checksum of format version 2: FNV-1a over the blob contents.
+fn blob_checksum(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+fn bitmap_len(fields: &[Field]) -> usize {
+    let booleans = fields
+        .iter()
+        .filter(|field| field.data_type == DataType::Boolean)
+        .count();
+    (fields.len() + booleans).div_ceil(8)
+}
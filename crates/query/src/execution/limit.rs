@@ -0,0 +1,78 @@
+use crate::execution::operator::{ExecutionResult, PhysicalOperator};
+use crate::execution::tuple::Tuple;
+use std::any::Any;
+
+/// Skips `offset` rows of its input and returns at most `limit` of the rest.
+pub struct Limit {
+    child: Box<dyn PhysicalOperator>,
+    offset: usize,
+    limit: Option<usize>,
+    skipped: usize,
+    returned: usize,
+}
+
+impl Limit {
+    pub fn new(child: Box<dyn PhysicalOperator>, offset: usize, limit: Option<usize>) -> Self {
+        Self {
+            child,
+            offset,
+            limit,
+            skipped: 0,
+            returned: 0,
+        }
+    }
+
+    pub fn child(&self) -> &dyn PhysicalOperator {
+        &*self.child
+    }
+}
+
+impl PhysicalOperator for Limit {
+    fn open(&mut self) -> ExecutionResult<()> {
+        self.skipped = 0;
+        self.returned = 0;
+        self.child.open()
+    }
+
+    fn next(&mut self) -> ExecutionResult<Option<Tuple>> {
+        if self.limit.is_some_and(|limit| self.returned >= limit) {
+            return Ok(None);
+        }
+        while self.skipped < self.offset {
+            if self.child.next()?.is_none() {
+                return Ok(None);
+            }
+            self.skipped += 1;
+        }
+        let tuple = self.child.next()?;
+        if tuple.is_some() {
+            self.returned += 1;
+        }
+        Ok(tuple)
+    }
+
+    fn close(&mut self) -> ExecutionResult<()> {
+        self.child.close()
+    }
+
+    fn rewind(&mut self) -> ExecutionResult<()> {
+        self.skipped = 0;
+        self.returned = 0;
+        self.child.rewind()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn describe(&self) -> String {
+        match self.limit {
+            Some(limit) => format!("Limit: OFFSET {} LIMIT {}", self.offset, limit),
+            None => format!("Limit: OFFSET {}", self.offset),
+        }
+    }
+
+    fn children(&self) -> Vec<&dyn PhysicalOperator> {
+        vec![&*self.child]
+    }
+}
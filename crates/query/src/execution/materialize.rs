@@ -0,0 +1,113 @@
+use crate::execution::operator::{ExecutionResult, PhysicalOperator};
+use crate::execution::spill::{SpillReader, SpillWriter, TempUsage, DEFAULT_WORK_MEM};
+use crate::execution::tuple::Tuple;
+use std::any::Any;
+
+/// Reads its input once, when opened, and returns the same rows every time
+/// it is rewound, so a parent that rereads its input does not run the
+/// input's plan again. Input beyond `work_mem` bytes goes to a temporary
+/// file, which each pass reads from the start.
+pub struct Materialize {
+    child: Box<dyn PhysicalOperator>,
+    work_mem: usize,
+    temp_usage: TempUsage,
+    rows: Vec<Tuple>,
+    position: usize,
+    spilled: Option<SpillReader>,
+}
+
+impl Materialize {
+    pub fn new(child: Box<dyn PhysicalOperator>) -> Self {
+        Self {
+            child,
+            work_mem: DEFAULT_WORK_MEM,
+            temp_usage: TempUsage::default(),
+            rows: Vec::new(),
+            position: 0,
+            spilled: None,
+        }
+    }
+
+    /// Bytes of rows held in memory before they are written to a file.
+    pub fn with_work_mem(mut self, bytes: usize) -> Self {
+        self.work_mem = bytes;
+        self
+    }
+
+    /// Counts the pages of the file this operator spills in `usage` too.
+    pub fn with_temp_usage(mut self, usage: TempUsage) -> Self {
+        self.temp_usage = usage;
+        self
+    }
+
+    pub fn child(&self) -> &dyn PhysicalOperator {
+        &*self.child
+    }
+}
+
+impl PhysicalOperator for Materialize {
+    fn open(&mut self) -> ExecutionResult<()> {
+        self.rows.clear();
+        self.position = 0;
+        self.spilled = None;
+        self.child.open()?;
+        let mut writer: Option<SpillWriter> = None;
+        let mut memory = 0;
+        while let Some(tuple) = self.child.next()? {
+            if let Some(writer) = &mut writer {
+                writer.write(tuple.values())?;
+                continue;
+            }
+            memory += tuple.memory_size();
+            self.rows.push(tuple);
+            if memory > self.work_mem {
+                let mut spill = SpillWriter::create()?;
+                for row in self.rows.drain(..) {
+                    spill.write(row.values())?;
+                }
+                writer = Some(spill);
+            }
+        }
+        self.child.close()?;
+        if let Some(writer) = writer {
+            self.spilled = Some(writer.finish(&self.temp_usage)?);
+        }
+        Ok(())
+    }
+
+    fn next(&mut self) -> ExecutionResult<Option<Tuple>> {
+        if let Some(spilled) = &mut self.spilled {
+            return spilled.next();
+        }
+        let tuple = self.rows.get(self.position).cloned();
+        self.position += usize::from(tuple.is_some());
+        Ok(tuple)
+    }
+
+    fn close(&mut self) -> ExecutionResult<()> {
+        self.rows.clear();
+        self.position = 0;
+        self.spilled = None;
+        Ok(())
+    }
+
+    fn rewind(&mut self) -> ExecutionResult<()> {
+        self.position = 0;
+        match &mut self.spilled {
+            Some(spilled) => spilled.rewind(),
+            None => Ok(()),
+        }
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn describe(&self) -> String {
+        "Materialize".to_string()
+    }
+
+    fn children(&self) -> Vec<&dyn PhysicalOperator> {
+        vec![&*self.child]
+    }
+}
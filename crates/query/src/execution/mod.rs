@@ -1,24 +1,68 @@
+pub mod aggregate;
+pub mod cancel;
+pub mod cost;
+pub mod count_rows;
+pub mod csv;
+pub mod delete;
+pub mod distinct_on;
+pub mod encoding;
 pub mod executor;
 pub mod filter;
+pub mod hash_join;
 pub mod index_scan;
+pub mod legacy_heap;
+pub mod limit;
+pub mod materialize;
 pub mod nested_loop_join;
 pub mod operator;
 pub mod planner;
+pub mod privileges;
 pub mod projection;
+pub mod random;
+pub mod schema_version;
+pub mod semi_join;
 pub mod seq_scan;
+pub mod sequence;
+pub mod single_row;
+pub mod sort;
+pub mod spill;
+pub mod statistics;
 pub mod tuple;
 pub mod update;
+pub mod values;
+pub mod versions;
 
+pub use aggregate::Aggregate;
+pub use cancel::{CancelOnDrop, CancellationToken};
+pub use count_rows::CountRows;
+pub use delete::Delete;
+pub use distinct_on::DistinctOn;
+pub use encoding::{decode_result, decode_value, encode_result, encode_value};
 pub use executor::Executor;
 pub use filter::Filter;
+pub use hash_join::HashJoin;
 pub use index_scan::{IndexPredicate, IndexScan};
+pub use limit::Limit;
+pub use materialize::Materialize;
 pub use nested_loop_join::NestedLoopJoin;
-pub use operator::{ExecutionError, ExecutionResult, PhysicalOperator};
-pub use planner::{Catalog, PhysicalPlanner, TableInfo};
+pub use operator::{
+    check_columns, explain_physical, ExecutionError, ExecutionResult, PhysicalOperator,
+};
+pub use planner::{Catalog, PhysicalPlanner, PlanLimits, SharedCatalog, TableInfo};
+pub use privileges::{GrantDef, Privileges};
 pub use projection::Projection;
-pub use seq_scan::{Rid, SeqScan, TableHeap};
+pub use random::SessionRandom;
+pub use schema_version::{SchemaVersion, TableVersion};
+pub use semi_join::{HashSemiJoin, IndexSemiJoin};
+pub use seq_scan::{Rid, ScanCursor, SeqScan, TableHeap};
+pub use sequence::{SequenceDef, Sequences};
+pub use single_row::SingleRow;
+pub use sort::Sort;
+pub use spill::{TempUsage, DEFAULT_WORK_MEM};
+pub use statistics::{StatsTracker, TableStats};
 pub use tuple::{Tuple, Value};
 pub use update::Update;
+pub use values::Values;
 
 #[cfg(test)]
 mod tests;
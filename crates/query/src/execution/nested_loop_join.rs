@@ -1,16 +1,44 @@
-use crate::execution::operator::{evaluate_predicate, ExecutionResult, PhysicalOperator};
-use crate::execution::tuple::Tuple;
-use crate::expr::Expr;
-use crate::schema::Schema;
+use crate::execution::operator::{
+    evaluate_predicate, resolve_column_index, ExecutionResult, PhysicalOperator,
+};
+use crate::execution::spill::DEFAULT_WORK_MEM;
+use crate::execution::tuple::{Tuple, Value};
+use crate::expr::{BinaryOperator, Expr};
+use crate::logical_plan::JoinType;
+use crate::schema::{DataType, Schema};
 use std::any::Any;
+use std::collections::HashMap;
+
+/// Inner inputs of at most this many rows, and of at most `work_mem` bytes,
+/// are read into a hash table when the join opens, if the predicate compares
+/// columns of both sides.
+pub const DEFAULT_HASH_BUILD_ROWS: usize = 10_000;
 
 pub struct NestedLoopJoin {
     left: Box<dyn PhysicalOperator>,
     right: Box<dyn PhysicalOperator>,
     predicate: Expr,
+    /// `Inner` or `Left`; a LEFT join pads outer rows no inner row matched
+    /// with NULLs.
+    join_type: JoinType,
     combined_schema: Schema,
+    right_width: usize,
     current_left: Option<Tuple>,
-    right_open: bool,
+    /// Whether an inner row has matched `current_left`.
+    matched: bool,
+    /// Whether the inner input is still at its first row, so the next
+    /// outer row can read it without rewinding it.
+    right_at_start: bool,
+    /// Column pairs `(left, right)` that the predicate requires to be equal.
+    keys: Vec<(usize, usize)>,
+    hash_build_rows: usize,
+    work_mem: usize,
+    /// The inner input by key, when it was small enough to hold. Each outer
+    /// row is then checked only against the inner rows with its key.
+    hash_table: Option<HashMap<Vec<Value>, Vec<Tuple>>>,
+    /// Key of `current_left` and the next inner row to try with that key.
+    probe: Option<(Vec<Value>, usize)>,
+    predicate_evaluations: u64,
 }
 
 impl NestedLoopJoin {
@@ -18,75 +46,278 @@ impl NestedLoopJoin {
         left: Box<dyn PhysicalOperator>,
         right: Box<dyn PhysicalOperator>,
         predicate: Expr,
+        join_type: JoinType,
         left_schema: Schema,
         right_schema: Schema,
     ) -> Self {
-        let mut fields = left_schema.fields.clone();
-        fields.extend(right_schema.fields.clone());
-        let combined_schema = Schema::new(fields);
+        let mut keys = Vec::new();
+        collect_join_keys(&predicate, &left_schema, &right_schema, &mut keys);
+        let combined_schema = join_schema(&left_schema, &right_schema, join_type);
         Self {
             left,
             right,
             predicate,
+            join_type,
             combined_schema,
+            right_width: right_schema.fields.len(),
             current_left: None,
-            right_open: false,
+            matched: false,
+            right_at_start: false,
+            keys,
+            hash_build_rows: DEFAULT_HASH_BUILD_ROWS,
+            work_mem: DEFAULT_WORK_MEM,
+            hash_table: None,
+            probe: None,
+            predicate_evaluations: 0,
         }
     }
+
+    /// Largest inner input read into a hash table; a larger one is rescanned
+    /// for every outer row. `0` always rescans.
+    pub fn with_hash_build_rows(mut self, rows: usize) -> Self {
+        self.hash_build_rows = rows;
+        self
+    }
+
+    /// Largest inner input, in bytes, read into a hash table.
+    pub fn with_work_mem(mut self, bytes: usize) -> Self {
+        self.work_mem = bytes;
+        self
+    }
+
+    /// Times the join predicate was evaluated against a pair of rows.
+    pub fn predicate_evaluations(&self) -> u64 {
+        self.predicate_evaluations
+    }
+
+    /// Reads the inner input into `hash_table`, unless it has more than
+    /// `hash_build_rows` rows or `work_mem` bytes.
+    fn build_hash_table(&mut self) -> ExecutionResult<()> {
+        if self.keys.is_empty() || self.hash_build_rows == 0 {
+            return Ok(());
+        }
+        self.right_at_start = false;
+        let mut rows = Vec::new();
+        let mut memory = 0;
+        while let Some(tuple) = self.right.next()? {
+            memory += tuple.memory_size();
+            if rows.len() == self.hash_build_rows || memory > self.work_mem {
+                return Ok(());
+            }
+            rows.push(tuple);
+        }
+        let mut table: HashMap<Vec<Value>, Vec<Tuple>> = HashMap::new();
+        for tuple in rows {
+            if let Some(key) = join_key(&tuple, self.keys.iter().map(|&(_, right)| right)) {
+                table.entry(key).or_default().push(tuple);
+            }
+        }
+        self.hash_table = Some(table);
+        Ok(())
+    }
+
+    /// Ends the current outer row, returning it padded with NULLs when the
+    /// join is LEFT and no inner row matched it.
+    fn finish_left(&mut self) -> Option<Tuple> {
+        let left_tuple = self.current_left.take()?;
+        let matched = std::mem::take(&mut self.matched);
+        (self.join_type == JoinType::Left && !matched)
+            .then(|| left_tuple.concat(&Tuple::new(vec![Value::Null; self.right_width])))
+    }
+
+    fn next_hashed(&mut self) -> ExecutionResult<Option<Tuple>> {
+        loop {
+            let Some((key, position)) = &mut self.probe else {
+                if let Some(padded) = self.finish_left() {
+                    return Ok(Some(padded));
+                }
+                let Some(left_tuple) = self.left.next()? else {
+                    return Ok(None);
+                };
+                self.probe = join_key(&left_tuple, self.keys.iter().map(|&(left, _)| left))
+                    .map(|key| (key, 0));
+                self.current_left = Some(left_tuple);
+                continue;
+            };
+            let candidate = self
+                .hash_table
+                .as_ref()
+                .and_then(|table| table.get(key))
+                .and_then(|rows| rows.get(*position));
+            let Some(right_tuple) = candidate else {
+                self.probe = None;
+                continue;
+            };
+            *position += 1;
+            let joined = self.current_left.as_ref().unwrap().concat(right_tuple);
+            self.predicate_evaluations += 1;
+            if evaluate_predicate(&self.predicate, &joined, &self.combined_schema)? {
+                self.matched = true;
+                return Ok(Some(joined));
+            }
+        }
+    }
+}
+
+/// Columns of `left` followed by those of `right`, which become nullable
+/// in a LEFT join.
+pub(crate) fn join_schema(left: &Schema, right: &Schema, join_type: JoinType) -> Schema {
+    let mut fields = left.fields.clone();
+    fields.extend(right.fields.iter().map(|field| {
+        let mut field = field.clone();
+        field.nullable |= join_type == JoinType::Left;
+        field
+    }));
+    Schema::new(fields)
+}
+
+/// Adds the column pairs of the equalities ANDed into `predicate` that
+/// compare a column of each side. Pairs whose types could fail to compare
+/// are left out, so a type error still surfaces from the predicate.
+pub(crate) fn collect_join_keys(
+    predicate: &Expr,
+    left: &Schema,
+    right: &Schema,
+    keys: &mut Vec<(usize, usize)>,
+) {
+    let Expr::BinaryOp {
+        left: first,
+        op,
+        right: second,
+    } = predicate
+    else {
+        return;
+    };
+    match op {
+        BinaryOperator::And => {
+            collect_join_keys(first, left, right, keys);
+            collect_join_keys(second, left, right, keys);
+        }
+        BinaryOperator::Eq => {
+            let pair = column_pair(first, second, left, right)
+                .or_else(|| column_pair(second, first, left, right));
+            if let Some((left_index, right_index)) = pair {
+                if comparable_keys(
+                    &left.fields[left_index].data_type,
+                    &right.fields[right_index].data_type,
+                ) {
+                    keys.push((left_index, right_index));
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Indexes of `on_left` and `on_right` when each is a column found only on
+/// its own side.
+fn column_pair(
+    on_left: &Expr,
+    on_right: &Expr,
+    left: &Schema,
+    right: &Schema,
+) -> Option<(usize, usize)> {
+    let column = |expr: &Expr, schema: &Schema| match expr {
+        Expr::Column { table, name } => resolve_column_index(schema, table.as_deref(), name).ok(),
+        _ => None,
+    };
+    if column(on_left, right).is_some() || column(on_right, left).is_some() {
+        return None;
+    }
+    Some((column(on_left, left)?, column(on_right, right)?))
+}
+
+fn comparable_keys(left: &DataType, right: &DataType) -> bool {
+    let numeric = |data_type: &DataType| {
+        matches!(
+            data_type,
+            DataType::Integer | DataType::BigInt | DataType::Real | DataType::Timestamp
+        )
+    };
+    (numeric(left) && numeric(right))
+        || (left == right && matches!(left, DataType::Text | DataType::Boolean))
+}
+
+/// Hash key of `tuple` from the columns at `indexes`; `None` when one is
+/// NULL, which equals nothing. Numbers of any type are keyed as floats, as
+/// they compare; rows sharing a key are still checked with the predicate.
+pub(crate) fn join_key(tuple: &Tuple, indexes: impl Iterator<Item = usize>) -> Option<Vec<Value>> {
+    indexes
+        .map(|index| match tuple.get(index)? {
+            Value::Null => None,
+            Value::Integer(number) | Value::Timestamp(number) => Some(Value::Float(*number as f64)),
+            value => Some(value.clone()),
+        })
+        .collect()
 }
 
 impl PhysicalOperator for NestedLoopJoin {
     fn open(&mut self) -> ExecutionResult<()> {
         self.left.open()?;
         self.right.open()?;
-        self.right_open = true;
+        self.right_at_start = true;
         self.current_left = None;
+        self.matched = false;
+        self.probe = None;
+        self.build_hash_table()?;
         Ok(())
     }
 
     fn next(&mut self) -> ExecutionResult<Option<Tuple>> {
+        if self.hash_table.is_some() {
+            return self.next_hashed();
+        }
         loop {
             if self.current_left.is_none() {
                 self.current_left = self.left.next()?;
                 if self.current_left.is_none() {
                     return Ok(None);
                 }
-                if self.right_open {
-                    self.right.close()?;
+                if !self.right_at_start {
+                    self.right.rewind()?;
                 }
-                self.right.open()?;
-                self.right_open = true;
+                self.right_at_start = false;
             }
 
             match self.right.next()? {
                 Some(right_tuple) => {
                     let joined = self.current_left.as_ref().unwrap().concat(&right_tuple);
+                    self.predicate_evaluations += 1;
                     if evaluate_predicate(&self.predicate, &joined, &self.combined_schema)? {
+                        self.matched = true;
                         return Ok(Some(joined));
                     }
                 }
                 None => {
-                    if self.right_open {
-                        self.right.close()?;
-                        self.right_open = false;
+                    if let Some(padded) = self.finish_left() {
+                        return Ok(Some(padded));
                     }
-                    self.current_left = None;
                 }
             }
         }
     }
 
     fn close(&mut self) -> ExecutionResult<()> {
-        if self.right_open {
-            self.right.close()?;
-            self.right_open = false;
-        }
+        self.right.close()?;
         self.left.close()?;
         self.current_left = None;
+        self.hash_table = None;
+        self.probe = None;
         Ok(())
     }
 
     fn as_any(&self) -> &dyn Any {
         self
     }
+
+    fn describe(&self) -> String {
+        match self.join_type {
+            JoinType::Left => format!("NestedLoopJoin: LEFT ON {}", self.predicate),
+            _ => format!("NestedLoopJoin: ON {}", self.predicate),
+        }
+    }
+
+    fn children(&self) -> Vec<&dyn PhysicalOperator> {
+        vec![&*self.left, &*self.right]
+    }
 }
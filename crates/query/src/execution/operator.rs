@@ -1,3 +1,6 @@
+use crate::execution::random;
+use crate::execution::seq_scan::Rid;
+use crate::execution::sequence;
 use crate::execution::tuple::{Tuple, Value};
 use crate::expr::{BinaryOperator, Expr, UnaryOperator};
 use crate::schema::{DataType, Schema};
@@ -19,6 +22,8 @@ pub enum ExecutionError {
     },
     #[error("expression error: {0}")]
     Expression(String),
+    #[error("division by zero")]
+    DivisionByZero,
     #[error("schema error: {0}")]
     Schema(String),
     #[error("unsupported plan: {0}")]
@@ -27,6 +32,57 @@ pub enum ExecutionError {
     UnsupportedExpression(String),
     #[error("execution error: {0}")]
     Execution(String),
+    #[error("plan too complex: {0}")]
+    PlanTooComplex(String),
+    #[error("query cancelled")]
+    Cancelled,
+    #[error("schema changed: table {0} was altered or dropped after the query was planned")]
+    SchemaChanged(String),
+    #[error("permission denied: {0}")]
+    PermissionDenied(String),
+    /// A page lock was not granted within the deadlock timeout. The
+    /// statement's transaction must be rolled back; retrying it in a new
+    /// transaction is safe.
+    #[error("lock wait timed out, possibly a deadlock; retry the transaction")]
+    LockTimeout,
+    /// The statement's transaction was chosen to break a deadlock. It must
+    /// be rolled back; retrying it in a new transaction is safe.
+    #[error("deadlock detected; retry the transaction")]
+    Deadlock,
+    /// A lock was held by another transaction and the request was not
+    /// allowed to wait for it. Retrying in a new transaction is safe.
+    #[error("could not obtain lock without waiting; retry the transaction")]
+    LockNotAvailable,
+    /// A snapshot transaction tried to change a row that a transaction its
+    /// snapshot does not see has changed. Retrying in a new transaction,
+    /// with a new snapshot, is safe.
+    #[error("could not serialize access due to a concurrent update; retry the transaction")]
+    SerializationFailure,
+}
+
+impl ExecutionError {
+    /// Whether the statement failed only because of contention with other
+    /// transactions, so running the whole transaction again may succeed.
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            ExecutionError::LockTimeout
+                | ExecutionError::Deadlock
+                | ExecutionError::LockNotAvailable
+                | ExecutionError::SerializationFailure
+        )
+    }
+}
+
+impl From<txn::LockError> for ExecutionError {
+    fn from(err: txn::LockError) -> Self {
+        match err {
+            txn::LockError::DeadlockTimeout => ExecutionError::LockTimeout,
+            txn::LockError::Deadlock(_) => ExecutionError::Deadlock,
+            txn::LockError::WouldBlock => ExecutionError::LockNotAvailable,
+            other => ExecutionError::Execution(format!("lock error: {other:?}")),
+        }
+    }
 }
 
 pub type ExecutionResult<T> = Result<T, ExecutionError>;
@@ -36,8 +92,48 @@ pub trait PhysicalOperator {
     fn next(&mut self) -> ExecutionResult<Option<Tuple>>;
     fn close(&mut self) -> ExecutionResult<()>;
     fn as_any(&self) -> &dyn Any;
+
+    /// Restarts an open operator at its first row, for a parent that reads
+    /// its input more than once. The default closes and reopens it;
+    /// operators that hold their rows reread them instead.
+    fn rewind(&mut self) -> ExecutionResult<()> {
+        self.close()?;
+        self.open()
+    }
+
+    /// Where the row `next` last returned is stored, for a scan of a single
+    /// table. UPDATE and DELETE find the rows they change through it.
+    fn last_rid(&self) -> Option<Rid> {
+        None
+    }
+
+    /// One-line summary of the operator, shown by EXPLAIN.
+    fn describe(&self) -> String {
+        "Operator".to_string()
+    }
+
+    /// Input operators, in the order EXPLAIN prints them.
+    fn children(&self) -> Vec<&dyn PhysicalOperator> {
+        Vec::new()
+    }
+}
+
+/// Renders a physical plan as an indented tree, one operator per line.
+pub fn explain_physical(root: &dyn PhysicalOperator) -> Vec<String> {
+    fn walk(operator: &dyn PhysicalOperator, depth: usize, lines: &mut Vec<String>) {
+        lines.push(format!("{}{}", "  ".repeat(depth), operator.describe()));
+        for child in operator.children() {
+            walk(child, depth + 1, lines);
+        }
+    }
+    let mut lines = Vec::new();
+    walk(root, 0, &mut lines);
+    lines
 }
 
+/// Whether `expr` is TRUE for `tuple`. Comparisons with a NULL operand are
+/// UNKNOWN (NULL) and combine under Kleene logic in `tri_and`/`tri_or`, so a
+/// row whose condition ends up UNKNOWN is rejected just like a FALSE one.
 pub fn evaluate_predicate(expr: &Expr, tuple: &Tuple, schema: &Schema) -> ExecutionResult<bool> {
     let value = evaluate_expr(expr, tuple, schema)?;
     match value {
@@ -68,13 +164,26 @@ pub fn evaluate_expr(expr: &Expr, tuple: &Tuple, schema: &Schema) -> ExecutionRe
             let value = evaluate_expr(expr, tuple, schema)?;
             apply_unary_operator(*op, value)
         }
-        Expr::Function { name, .. } => Err(ExecutionError::UnsupportedExpression(format!(
-            "function {} is not supported",
-            name
-        ))),
+        Expr::Function { name, args } => match function_column_index(schema, expr) {
+            // An aggregate computed by an Aggregate operator further down
+            Some(index) => tuple.get(index).cloned().ok_or_else(|| {
+                ExecutionError::Schema(format!("column index {} out of range", index))
+            }),
+            None => evaluate_scalar_function(name, args, tuple, schema),
+        },
         Expr::Wildcard => Err(ExecutionError::UnsupportedExpression(
             "wildcard expression must be expanded in projection".to_string(),
         )),
+        Expr::Default => Err(ExecutionError::UnsupportedExpression(
+            "DEFAULT is only allowed as an INSERT value or UPDATE assignment".to_string(),
+        )),
+        Expr::Parameter(index) => Err(ExecutionError::Execution(format!(
+            "no value is bound to parameter ${}",
+            index + 1
+        ))),
+        Expr::InSubquery { .. } | Expr::ScalarSubquery(_) => Err(ExecutionError::Execution(
+            "subquery was not run before the query".to_string(),
+        )),
         Expr::QualifiedWildcard { table } => Err(ExecutionError::UnsupportedExpression(format!(
             "qualified wildcard {} must be expanded in projection",
             table
@@ -139,7 +248,125 @@ pub fn evaluate_expr(expr: &Expr, tuple: &Tuple, schema: &Schema) -> ExecutionRe
     }
 }
 
-fn resolve_column_index(
+/// Scalar functions. Arguments are evaluated lazily, so
+/// `COALESCE(x, 1 / 0)` only fails when `x` is NULL.
+fn evaluate_scalar_function(
+    name: &str,
+    args: &[Expr],
+    tuple: &Tuple,
+    schema: &Schema,
+) -> ExecutionResult<Value> {
+    match (name, args) {
+        ("COALESCE", [_, ..]) => {
+            for arg in args {
+                let value = evaluate_expr(arg, tuple, schema)?;
+                if !value.is_null() {
+                    return Ok(value);
+                }
+            }
+            Ok(Value::Null)
+        }
+        ("NULLIF", [value, other]) => {
+            let value = evaluate_expr(value, tuple, schema)?;
+            let other = evaluate_expr(other, tuple, schema)?;
+            match apply_comparison(BinaryOperator::Eq, &value, &other)? {
+                Value::Boolean(true) => Ok(Value::Null),
+                _ => Ok(value),
+            }
+        }
+        ("CURRENT_TIMESTAMP", []) => {
+            let now = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map_err(|err| ExecutionError::Execution(err.to_string()))?;
+            Ok(Value::Timestamp(now.as_secs() as i64))
+        }
+        ("RANDOM", []) => Ok(Value::Float(random::random())),
+        ("NEXTVAL", [sequence_name]) | ("CURRVAL", [sequence_name]) => {
+            let sequence_name = match evaluate_expr(sequence_name, tuple, schema)? {
+                Value::String(sequence_name) => sequence_name,
+                other => {
+                    return Err(ExecutionError::Expression(format!(
+                        "{} expects a sequence name, found {:?}",
+                        name, other
+                    )))
+                }
+            };
+            let value = if name == "NEXTVAL" {
+                sequence::next_value(&sequence_name)?
+            } else {
+                sequence::current_value(&sequence_name)?
+            };
+            Ok(Value::Integer(value))
+        }
+        ("COALESCE", _)
+        | ("NULLIF", _)
+        | ("CURRENT_TIMESTAMP", _)
+        | ("RANDOM", _)
+        | ("NEXTVAL", _)
+        | ("CURRVAL", _) => Err(ExecutionError::Expression(format!(
+            "{} called with {} arguments",
+            name,
+            args.len()
+        ))),
+        _ => Err(ExecutionError::UnsupportedExpression(format!(
+            "function {} is not supported",
+            name
+        ))),
+    }
+}
+
+/// Checks that every column `expr` references resolves in `schema`, so a
+/// misspelled column fails at planning time rather than on the first row.
+pub fn check_columns(expr: &Expr, schema: &Schema) -> ExecutionResult<()> {
+    match expr {
+        Expr::Column { table, name } => {
+            resolve_column_index(schema, table.as_deref(), name).map(|_| ())
+        }
+        Expr::Function { args, .. } => {
+            if function_column_index(schema, expr).is_some() {
+                return Ok(());
+            }
+            args.iter().try_for_each(|arg| check_columns(arg, schema))
+        }
+        Expr::BinaryOp { left, right, .. } => {
+            check_columns(left, schema)?;
+            check_columns(right, schema)
+        }
+        Expr::UnaryOp { expr, .. } | Expr::Cast { expr, .. } | Expr::IsNull { expr, .. } => {
+            check_columns(expr, schema)
+        }
+        Expr::Between {
+            expr, low, high, ..
+        } => {
+            check_columns(expr, schema)?;
+            check_columns(low, schema)?;
+            check_columns(high, schema)
+        }
+        Expr::In { expr, list, .. } => {
+            check_columns(expr, schema)?;
+            list.iter().try_for_each(|item| check_columns(item, schema))
+        }
+        Expr::InSubquery { expr, .. } => check_columns(expr, schema),
+        Expr::Literal(_)
+        | Expr::Wildcard
+        | Expr::QualifiedWildcard { .. }
+        | Expr::Default
+        | Expr::Parameter(_)
+        | Expr::ScalarSubquery(_) => Ok(()),
+    }
+}
+
+/// Finds the input column holding the result of a function call, which an
+/// Aggregate operator names after the call itself.
+pub(crate) fn function_column_index(schema: &Schema, expr: &Expr) -> Option<usize> {
+    let name = expr.to_string();
+    schema
+        .fields
+        .iter()
+        .position(|field| field.visible && field.name.eq_ignore_ascii_case(&name))
+}
+
+pub(crate) fn resolve_column_index(
     schema: &Schema,
     table: Option<&str>,
     name: &str,
@@ -240,7 +467,7 @@ fn apply_numeric_operator(
         BinaryOperator::Multiply => numeric_result(left_value * right_value, both_integer),
         BinaryOperator::Divide => {
             if right_value == 0.0 {
-                return Err(ExecutionError::Expression("division by zero".to_string()));
+                return Err(ExecutionError::DivisionByZero);
             }
             Value::Float(left_value / right_value)
         }
@@ -253,7 +480,7 @@ fn apply_numeric_operator(
             let left_int = left_value as i64;
             let right_int = right_value as i64;
             if right_int == 0 {
-                return Err(ExecutionError::Expression("modulo by zero".to_string()));
+                return Err(ExecutionError::DivisionByZero);
             }
             Value::Integer(left_int % right_int)
         }
@@ -366,27 +593,31 @@ fn apply_like(op: BinaryOperator, left: &Value, right: &Value) -> ExecutionResul
     }
 }
 
+/// Matches `value` against a LIKE pattern: `%` matches any run of
+/// characters, `_` exactly one, and a backslash makes the next character
+/// literal (so `50\%` matches only "50%").
 fn like_match(value: &str, pattern: &str) -> bool {
     let value_chars: Vec<char> = value.chars().collect();
-    let pattern_chars: Vec<char> = pattern.chars().collect();
+    let pattern_tokens = like_tokens(pattern);
     let value_len = value_chars.len();
-    let pattern_len = pattern_chars.len();
+    let pattern_len = pattern_tokens.len();
     let mut dp = vec![vec![false; pattern_len + 1]; value_len + 1];
     dp[0][0] = true;
 
     for pattern_index in 1..=pattern_len {
-        if pattern_chars[pattern_index - 1] == '%' {
+        if pattern_tokens[pattern_index - 1] == LikeToken::AnyRun {
             dp[0][pattern_index] = dp[0][pattern_index - 1];
         }
     }
 
     for value_index in 1..=value_len {
         for pattern_index in 1..=pattern_len {
-            let pattern_char = pattern_chars[pattern_index - 1];
-            dp[value_index][pattern_index] = match pattern_char {
-                '%' => dp[value_index][pattern_index - 1] || dp[value_index - 1][pattern_index],
-                '_' => dp[value_index - 1][pattern_index - 1],
-                _ => {
+            dp[value_index][pattern_index] = match pattern_tokens[pattern_index - 1] {
+                LikeToken::AnyRun => {
+                    dp[value_index][pattern_index - 1] || dp[value_index - 1][pattern_index]
+                }
+                LikeToken::AnyOne => dp[value_index - 1][pattern_index - 1],
+                LikeToken::Literal(pattern_char) => {
                     dp[value_index - 1][pattern_index - 1]
                         && value_chars[value_index - 1] == pattern_char
                 }
@@ -397,7 +628,29 @@ fn like_match(value: &str, pattern: &str) -> bool {
     dp[value_len][pattern_len]
 }
 
-fn compare_values(left: &Value, right: &Value) -> ExecutionResult<Option<Ordering>> {
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LikeToken {
+    AnyRun,
+    AnyOne,
+    Literal(char),
+}
+
+fn like_tokens(pattern: &str) -> Vec<LikeToken> {
+    let mut tokens = Vec::with_capacity(pattern.len());
+    let mut chars = pattern.chars();
+    while let Some(ch) = chars.next() {
+        tokens.push(match ch {
+            '%' => LikeToken::AnyRun,
+            '_' => LikeToken::AnyOne,
+            // a trailing backslash has nothing to escape and matches itself
+            '\\' => LikeToken::Literal(chars.next().unwrap_or('\\')),
+            other => LikeToken::Literal(other),
+        });
+    }
+    tokens
+}
+
+pub(crate) fn compare_values(left: &Value, right: &Value) -> ExecutionResult<Option<Ordering>> {
     if left.is_null() || right.is_null() {
         return Ok(None);
     }
@@ -480,7 +733,7 @@ fn numeric_pair(left: &Value, right: &Value) -> ExecutionResult<Option<(f64, f64
     }
 }
 
-fn apply_cast(value: Value, target_type: &DataType) -> ExecutionResult<Value> {
+pub(crate) fn apply_cast(value: Value, target_type: &DataType) -> ExecutionResult<Value> {
     if value.is_null() {
         return Ok(Value::Null);
     }
@@ -559,6 +812,9 @@ fn apply_cast(value: Value, target_type: &DataType) -> ExecutionResult<Value> {
         DataType::Blob => Err(ExecutionError::Expression(
             "BLOB columns do not support CAST".to_string(),
         )),
+        DataType::Unknown => Err(ExecutionError::Expression(
+            "cannot cast to an unknown type".to_string(),
+        )),
     }
 }
 
@@ -1,20 +1,47 @@
+use crate::change_feed::{self, ChangeOp};
+use crate::execution::aggregate::Aggregate;
+use crate::execution::cost::{
+    scale_rows, selectivity, Estimate, Estimated, CPU_OPERATOR_COST, CPU_TUPLE_COST,
+    EQUALITY_SELECTIVITY, RANDOM_PAGE_COST, SEQ_PAGE_COST,
+};
+use crate::execution::count_rows::CountRows;
+use crate::execution::delete::Delete;
+use crate::execution::distinct_on::DistinctOn;
+use crate::execution::executor::Executor;
 use crate::execution::filter::Filter;
+use crate::execution::hash_join::{equijoin_key, HashJoin};
 use crate::execution::index_scan::{IndexPredicate, IndexScan};
-use crate::execution::nested_loop_join::NestedLoopJoin;
+use crate::execution::limit::Limit;
+use crate::execution::materialize::Materialize;
+use crate::execution::nested_loop_join::{join_schema, NestedLoopJoin};
 use crate::execution::operator::{
-    evaluate_expr, evaluate_predicate, ExecutionError, ExecutionResult, PhysicalOperator,
+    apply_cast, check_columns, evaluate_expr, evaluate_predicate, function_column_index,
+    resolve_column_index, ExecutionError, ExecutionResult, PhysicalOperator,
 };
-use crate::execution::projection::Projection;
+use crate::execution::privileges::{self, Privileges};
+use crate::execution::projection::{project_rows, Projection};
+use crate::execution::schema_version::{SchemaVersion, TableVersion};
+use crate::execution::semi_join::{HashSemiJoin, IndexSemiJoin};
 use crate::execution::seq_scan::{Rid, SeqScan, TableHeap};
+use crate::execution::sequence::Sequences;
+use crate::execution::single_row::SingleRow;
+use crate::execution::sort::Sort;
+use crate::execution::spill::{TempUsage, DEFAULT_WORK_MEM};
+use crate::execution::statistics::{StatsTracker, TableStats};
 use crate::execution::tuple::{Tuple, Value};
 use crate::execution::update::Update;
-use crate::expr::{BinaryOperator, Expr};
+use crate::execution::values::Values;
+use crate::expr::{BinaryOperator, Expr, LiteralValue, UnaryOperator};
 use crate::index::{BPlusTree, Index, IndexKey, IndexKeyType};
-use crate::logical_plan::{Assignment, JoinType, LogicalPlan};
-use crate::schema::{ColumnDef, DataType, Field, Schema};
-use parking_lot::Mutex;
-use std::collections::HashMap;
+use crate::logical_plan::{
+    bound_value, AggregateExpr, AggregateFunction, Assignment, InsertConflict, JoinType,
+    LogicalPlan, Returning, SortExpr,
+};
+use crate::schema::{CheckConstraint, Collation, ColumnDef, DataType, DefaultValue, Field, Schema};
+use parking_lot::{Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
+use storage::PageId;
 
 #[derive(Clone)]
 pub struct IndexInfo {
@@ -22,11 +49,37 @@ pub struct IndexInfo {
     pub columns: Vec<String>,
     pub column_indices: Vec<usize>,
     pub key_types: Vec<IndexKeyType>,
+    /// Collation of each key column; text under one is folded before it
+    /// becomes part of a key.
+    pub collations: Vec<Option<Collation>>,
     pub unique: bool,
     pub is_primary: bool,
     pub index: BPlusTree,
 }
 
+impl IndexInfo {
+    /// Whether any key column is folded, so keys no longer order or match
+    /// like the stored values.
+    pub fn folds_keys(&self) -> bool {
+        self.collations.iter().any(Option::is_some)
+    }
+
+    /// The key `tuple` has in this index.
+    pub fn key_for(&self, tuple: &Tuple) -> ExecutionResult<IndexKey> {
+        let mut values = Vec::with_capacity(self.column_indices.len());
+        for (column_index, collation) in self.column_indices.iter().zip(&self.collations) {
+            let value = tuple
+                .get(*column_index)
+                .ok_or_else(|| ExecutionError::Execution("tuple missing column".to_string()))?;
+            values.push(match (value, collation) {
+                (Value::String(text), Some(collation)) => Value::String(collation.fold(text)),
+                _ => value.clone(),
+            });
+        }
+        IndexKey::from_values(&values, &self.key_types)
+    }
+}
+
 #[derive(Clone)]
 pub struct TableInfo {
     pub name: String,
@@ -34,7 +87,10 @@ pub struct TableInfo {
     pub columns: Vec<ColumnDef>,
     pub heap: TableHeap,
     pub indexes: Vec<IndexInfo>,
+    pub checks: Vec<CheckConstraint>,
     pub auto_increment_counter: Arc<Mutex<i64>>,
+    pub stats: Arc<StatsTracker>,
+    version: TableVersion,
 }
 
 impl TableInfo {
@@ -50,6 +106,8 @@ impl TableInfo {
                 unique: false,
                 default_value: None,
                 auto_increment: false,
+                text_format: None,
+                collation: None,
             })
             .collect();
         Self {
@@ -58,7 +116,10 @@ impl TableInfo {
             columns,
             heap,
             indexes: Vec::new(),
+            checks: Vec::new(),
             auto_increment_counter: Arc::new(Mutex::new(0)),
+            stats: Arc::new(StatsTracker::default()),
+            version: TableVersion::default(),
         }
     }
 
@@ -74,7 +135,10 @@ impl TableInfo {
             columns,
             heap,
             indexes: Vec::new(),
+            checks: Vec::new(),
             auto_increment_counter: Arc::new(Mutex::new(0)),
+            stats: Arc::new(StatsTracker::default()),
+            version: TableVersion::default(),
         }
     }
 
@@ -103,6 +167,7 @@ impl TableInfo {
         let mut column_indices = Vec::with_capacity(columns.len());
         let mut column_names = Vec::with_capacity(columns.len());
         let mut key_types = Vec::with_capacity(columns.len());
+        let mut collations = Vec::with_capacity(columns.len());
         for column in columns {
             let column_index = self
                 .schema
@@ -113,6 +178,11 @@ impl TableInfo {
             key_types.push(index_key_type_for_data_type(
                 &self.schema.fields[column_index].data_type,
             )?);
+            collations.push(
+                self.columns
+                    .get(column_index)
+                    .and_then(|column| column.collation),
+            );
         }
 
         let index = if key_types.len() > 1 {
@@ -126,25 +196,154 @@ impl TableInfo {
             BPlusTree::create(self.heap.buffer_pool().clone(), key_types[0], None, unique)?
         };
 
-        for (rid, tuple) in self.heap.scan_tuples(&self.schema)? {
-            let key = Self::key_from_tuple(&tuple, &column_indices, &key_types)?;
-            index.insert(key, rid)?;
-        }
-
-        self.indexes.push(IndexInfo {
+        let info = IndexInfo {
             name: name.into(),
             columns: column_names,
             column_indices,
             key_types,
+            collations,
             unique,
             is_primary,
             index,
-        });
+        };
+        for (rid, tuple) in self.heap.scan_tuples(&self.schema)? {
+            info.index.insert(info.key_for(&tuple)?, rid)?;
+        }
+
+        self.indexes.push(info);
+        self.version.bump();
         Ok(())
     }
 
+    /// Captures the table version for an operator planned against it.
+    pub fn schema_version(&self) -> SchemaVersion {
+        self.version.snapshot(self.name.clone())
+    }
+
     pub fn add_index(&mut self, index: IndexInfo) {
         self.indexes.push(index);
+        self.version.bump();
+    }
+
+    /// Adds a `CHECK` constraint after verifying that its columns exist and
+    /// that every stored row already satisfies it.
+    pub fn add_check(&mut self, check: CheckConstraint) -> ExecutionResult<()> {
+        if let Some(name) = &check.name {
+            if self.checks.iter().any(|existing| {
+                existing
+                    .name
+                    .as_ref()
+                    .is_some_and(|existing| existing.eq_ignore_ascii_case(name))
+            }) {
+                return Err(ExecutionError::Schema(format!(
+                    "constraint {} already exists on table {}",
+                    name, self.name
+                )));
+            }
+        }
+        for column in check.expr.column_names() {
+            if self.visible_column_index(column).is_none() {
+                return Err(ExecutionError::Schema(format!(
+                    "CHECK constraint references unknown column {}",
+                    column
+                )));
+            }
+        }
+        for (_, tuple) in self.heap.scan_tuples(&self.schema)? {
+            self.check_row(&check, &tuple)?;
+        }
+        self.checks.push(check);
+        self.version.bump();
+        Ok(())
+    }
+
+    fn visible_column_index(&self, column: &str) -> Option<usize> {
+        self.schema
+            .fields
+            .iter()
+            .position(|field| field.visible && field.name.eq_ignore_ascii_case(column))
+    }
+
+    fn check_referencing(&self, column: &str) -> Option<&CheckConstraint> {
+        self.checks.iter().find(|check| {
+            check
+                .expr
+                .column_names()
+                .iter()
+                .any(|name| name.eq_ignore_ascii_case(column))
+        })
+    }
+
+    /// Evaluates every `CHECK` constraint against a complete row. A NULL
+    /// result passes, as in standard SQL.
+    fn check_constraints(&self, tuple: &Tuple) -> ExecutionResult<()> {
+        for check in &self.checks {
+            self.check_row(check, tuple)?;
+        }
+        Ok(())
+    }
+
+    /// Rewrites DATE and DATETIME values into canonical text, rejecting
+    /// values that are not valid dates.
+    fn normalize_formatted_text(&self, tuple: Tuple) -> ExecutionResult<Tuple> {
+        if self
+            .columns
+            .iter()
+            .all(|column| column.text_format.is_none())
+        {
+            return Ok(tuple);
+        }
+        let mut values = tuple.values().to_vec();
+        for column in &self.columns {
+            let Some(format) = column.text_format else {
+                continue;
+            };
+            let Some(index) = self.schema.field_index(&column.name) else {
+                continue;
+            };
+            if let Some(Value::String(text)) = values.get(index) {
+                let normalized =
+                    format
+                        .normalize(text)
+                        .ok_or_else(|| ExecutionError::ConstraintViolation {
+                            table: self.name.clone(),
+                            constraint: format!("{} {}", column.name, format.name()),
+                            key: text.clone(),
+                        })?;
+                values[index] = Value::String(normalized);
+            }
+        }
+        Ok(Tuple::new(values))
+    }
+
+    fn check_row(&self, check: &CheckConstraint, tuple: &Tuple) -> ExecutionResult<()> {
+        match evaluate_expr(&check.expr, tuple, &self.schema)? {
+            Value::Boolean(true) | Value::Null => Ok(()),
+            Value::Boolean(false) => {
+                let key = check
+                    .expr
+                    .column_names()
+                    .into_iter()
+                    .filter_map(|column| {
+                        let index = self.schema.field_index(column)?;
+                        Some(format!("{}={:?}", column, tuple.get(index)?))
+                    })
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                Err(ExecutionError::ConstraintViolation {
+                    table: self.name.clone(),
+                    constraint: check
+                        .name
+                        .clone()
+                        .unwrap_or_else(|| format!("CHECK ({})", check.sql)),
+                    key,
+                })
+            }
+            other => Err(ExecutionError::Expression(format!(
+                "CHECK constraint returned non-boolean value: {:?}",
+                other
+            ))),
+        }
     }
 
     pub fn rename_table(&mut self, new_name: &str) {
@@ -154,6 +353,7 @@ impl TableInfo {
                 field.table = Some(new_name.to_string());
             }
         }
+        self.version.bump();
     }
 
     pub fn rename_column(&mut self, old_name: &str, new_name: &str) -> ExecutionResult<()> {
@@ -169,6 +369,12 @@ impl TableInfo {
             .iter()
             .position(|field| field.visible && field.name.eq_ignore_ascii_case(old_name))
             .ok_or_else(|| ExecutionError::Schema(format!("column {} not found", old_name)))?;
+        if let Some(check) = self.check_referencing(old_name) {
+            return Err(ExecutionError::Schema(format!(
+                "cannot rename column {} used by CHECK ({})",
+                old_name, check.sql
+            )));
+        }
         if self
             .schema
             .fields
@@ -194,6 +400,7 @@ impl TableInfo {
                 }
             }
         }
+        self.version.bump();
         Ok(())
     }
 
@@ -229,6 +436,7 @@ impl TableInfo {
             visible: true,
         });
         self.columns.push(column_def);
+        self.version.bump();
         Ok(())
     }
 
@@ -239,6 +447,12 @@ impl TableInfo {
             .iter()
             .position(|field| field.visible && field.name.eq_ignore_ascii_case(column_name))
             .ok_or_else(|| ExecutionError::Schema(format!("column {} not found", column_name)))?;
+        if let Some(check) = self.check_referencing(column_name) {
+            return Err(ExecutionError::Schema(format!(
+                "cannot drop column {} used by CHECK ({})",
+                column_name, check.sql
+            )));
+        }
         if self.indexes.iter().any(|index| {
             index.is_primary
                 && index
@@ -265,9 +479,117 @@ impl TableInfo {
                 .iter()
                 .any(|col| col.eq_ignore_ascii_case(column_name))
         });
+        self.version.bump();
         Ok(())
     }
 
+    /// Changes a column's type by rewriting every row. Each value is
+    /// computed from `using` (or the old value) and cast to `new_type`;
+    /// nothing is written unless every row converts cleanly. Returns the
+    /// pages of the index trees it replaced, for the caller to free.
+    pub fn alter_column_type(
+        &mut self,
+        column_name: &str,
+        new_type: DataType,
+        using: Option<&Expr>,
+    ) -> ExecutionResult<Vec<PageId>> {
+        let column_index = self
+            .schema
+            .fields
+            .iter()
+            .position(|field| field.visible && field.name.eq_ignore_ascii_case(column_name))
+            .ok_or_else(|| ExecutionError::Schema(format!("column {} not found", column_name)))?;
+        if matches!(new_type, DataType::Blob) {
+            return Err(ExecutionError::Schema(format!(
+                "cannot change column {} to BLOB",
+                column_name
+            )));
+        }
+        let indexed = self
+            .indexes
+            .iter()
+            .any(|index| index.column_indices.contains(&column_index));
+        let new_key_type = if indexed {
+            Some(index_key_type_for_data_type(&new_type)?)
+        } else {
+            None
+        };
+
+        let field = &self.schema.fields[column_index];
+        let mut rewritten = Vec::new();
+        for (rid, tuple) in self.heap.scan_tuples(&self.schema)? {
+            let source = match using {
+                Some(expr) => evaluate_expr(expr, &tuple, &self.schema)?,
+                None => tuple.values()[column_index].clone(),
+            };
+            let value = apply_cast(source, &new_type)?;
+            if value.is_null() {
+                if !field.nullable {
+                    return Err(ExecutionError::ConstraintViolation {
+                        table: self.name.clone(),
+                        constraint: format!("{} NOT NULL", field.name),
+                        key: "NULL".to_string(),
+                    });
+                }
+            } else if !value_matches_type(&value, &new_type)
+                || (matches!(new_type, DataType::Integer)
+                    && matches!(value, Value::Integer(n) if i32::try_from(n).is_err()))
+            {
+                return Err(ExecutionError::Schema(format!(
+                    "cannot convert {:?} in column {} to {:?}",
+                    value, field.name, new_type
+                )));
+            }
+            let mut values = tuple.values().to_vec();
+            values[column_index] = value;
+            rewritten.push((rid, tuple, Tuple::new(values)));
+        }
+
+        // the change is made to a copy, which replaces this table only once
+        // every row and index is written
+        let mut altered = self.clone();
+        altered.schema.fields[column_index].data_type = new_type.clone();
+        if let Some(column) = altered
+            .columns
+            .iter_mut()
+            .find(|c| c.name.eq_ignore_ascii_case(column_name))
+        {
+            column.data_type = new_type;
+            column.text_format = None;
+            column.collation = None;
+        }
+        if let Some(key_type) = new_key_type {
+            for index in &mut altered.indexes {
+                for (position, idx) in index.column_indices.iter().enumerate() {
+                    if *idx == column_index {
+                        index.key_types[position] = key_type;
+                        index.collations[position] = None;
+                    }
+                }
+            }
+        }
+        let mut written = Vec::new();
+        let result = (|| {
+            for (rid, original, tuple) in rewritten {
+                let rid = altered.heap.update_tuple(rid, &tuple, &altered.schema)?;
+                written.push((rid, original));
+            }
+            // a row that grew may have moved, so every index is rebuilt
+            altered.rebuild_indexes()
+        })();
+        if let Err(err) = result {
+            // put the rows already rewritten back in the old layout
+            for (rid, original) in written {
+                self.heap.update_tuple(rid, &original, &self.schema)?;
+            }
+            self.rebuild_indexes()?;
+            return Err(err);
+        }
+        let replaced = result?;
+        *self = altered;
+        Ok(replaced)
+    }
+
     pub fn index_for_column(&self, column: &str) -> Option<&IndexInfo> {
         self.indexes
             .iter()
@@ -294,6 +616,21 @@ impl TableInfo {
     }
 
     pub fn insert_tuple(&self, tuple: &Tuple) -> ExecutionResult<Rid> {
+        let inserted = self.insert_tuple_on_conflict(tuple, InsertConflict::Abort)?;
+        // an aborting insert never skips its row
+        inserted.map(|(rid, _)| rid).ok_or_else(|| {
+            ExecutionError::Execution("insert skipped its row without a conflict".to_string())
+        })
+    }
+
+    /// Inserts `tuple`, resolving a key already taken in a unique index as
+    /// `on_conflict` says. Returns the row as stored, with its generated id
+    /// and normalized text, or `None` when the row was skipped.
+    pub fn insert_tuple_on_conflict(
+        &self,
+        tuple: &Tuple,
+        on_conflict: InsertConflict,
+    ) -> ExecutionResult<Option<(Rid, Tuple)>> {
         let mut tuple_with_autoinc: Vec<Value> = tuple.values().to_vec();
 
         for (idx, column) in self.columns.iter().enumerate() {
@@ -311,29 +648,127 @@ impl TableInfo {
             }
         }
 
-        let new_tuple = Tuple::new(tuple_with_autoinc);
+        self.fill_not_null_defaults(&mut tuple_with_autoinc)?;
+        let new_tuple = self.normalize_formatted_text(Tuple::new(tuple_with_autoinc))?;
+        self.check_constraints(&new_tuple)?;
 
+        // every index accepts the row before a replaced row is deleted
         let mut keys = Vec::with_capacity(self.indexes.len());
+        let mut replaced = Vec::new();
         for (idx, index) in self.indexes.iter().enumerate() {
-            let key = Self::key_from_tuple(&new_tuple, &index.column_indices, &index.key_types)?;
-            if index.unique && !index.index.get(&key)?.is_empty() {
-                return Err(ExecutionError::ConstraintViolation {
-                    table: self.name.clone(),
-                    constraint: index.name.clone(),
-                    key: key.display(),
-                });
+            let key = index.key_for(&new_tuple)?;
+            index.index.check_key(&key)?;
+            let existing = if index.unique && !key.has_null() {
+                index.index.get(&key)?
+            } else {
+                Vec::new()
+            };
+            if !existing.is_empty() {
+                match on_conflict {
+                    InsertConflict::Abort => {
+                        return Err(ExecutionError::ConstraintViolation {
+                            table: self.name.clone(),
+                            constraint: index.name.clone(),
+                            key: key.display(),
+                        })
+                    }
+                    InsertConflict::Ignore => return Ok(None),
+                    InsertConflict::Replace => replaced.extend(existing),
+                }
             }
             keys.push((idx, key));
         }
+        self.delete_rids(&replaced)?;
 
         let rid = self.heap.insert_tuple(&new_tuple, &self.schema)?;
-        for (idx, key) in keys {
-            if let Err(error) = self.indexes[idx].index.insert(key, rid) {
+        for (position, (idx, key)) in keys.iter().enumerate() {
+            if let Err(error) = self.indexes[*idx].index.insert(key.clone(), rid) {
+                for (idx, key) in &keys[..position] {
+                    let _ = self.indexes[*idx].index.delete(key, rid);
+                }
                 let _ = self.heap.delete_tuple(rid);
                 return Err(error);
             }
         }
-        Ok(rid)
+        self.log_change(ChangeOp::Insert, &new_tuple, Some(&new_tuple))?;
+        self.stats.record_modifications(1);
+        Ok(Some((rid, new_tuple)))
+    }
+
+    /// Gives a NULL in a NOT NULL column the column's default, evaluated
+    /// now, and rejects the row if such a column has no default.
+    fn fill_not_null_defaults(&self, values: &mut [Value]) -> ExecutionResult<()> {
+        for (index, field) in self.schema.fields.iter().enumerate() {
+            if field.nullable || !field.visible || !values.get(index).is_some_and(Value::is_null) {
+                continue;
+            }
+            let default = evaluate_expr(
+                &self.default_expr(&field.name),
+                &Tuple::new(Vec::new()),
+                &Schema::empty(),
+            )?;
+            if default.is_null() {
+                return Err(ExecutionError::ConstraintViolation {
+                    table: self.name.clone(),
+                    constraint: format!("{} NOT NULL", field.name),
+                    key: "NULL".to_string(),
+                });
+            }
+            values[index] = default;
+        }
+        Ok(())
+    }
+
+    /// Logs a change for the change feed, keyed by the primary key of `row`
+    /// or, without one, by the whole row.
+    fn log_change(&self, op: ChangeOp, row: &Tuple, values: Option<&Tuple>) -> ExecutionResult<()> {
+        let key = match self.indexes.iter().find(|index| index.is_primary) {
+            Some(primary) => primary
+                .column_indices
+                .iter()
+                .map(|&idx| row.values()[idx].clone())
+                .collect(),
+            None => row.values().to_vec(),
+        };
+        change_feed::log_change(&self.name, op, &key, values)
+    }
+
+    /// The expression a column takes when an INSERT omits it or gives it as
+    /// `DEFAULT`: its declared default, or NULL when it has none.
+    pub fn default_expr(&self, column: &str) -> Expr {
+        let default_value = self
+            .columns
+            .iter()
+            .find(|def| def.name.eq_ignore_ascii_case(column))
+            .and_then(|def| def.default_value.as_ref());
+        let literal = match default_value {
+            None | Some(DefaultValue::Null) => LiteralValue::Null,
+            Some(DefaultValue::Integer(n)) => LiteralValue::Integer(*n),
+            Some(DefaultValue::Real(n)) => LiteralValue::Float(*n),
+            Some(DefaultValue::Text(s)) => LiteralValue::String(s.clone()),
+            Some(DefaultValue::Boolean(b)) => LiteralValue::Boolean(*b),
+            Some(DefaultValue::CurrentTimestamp) => {
+                return Expr::Function {
+                    name: "CURRENT_TIMESTAMP".to_string(),
+                    args: Vec::new(),
+                };
+            }
+        };
+        Expr::Literal(literal)
+    }
+
+    /// Replaces each `SET column = DEFAULT` with the column's default.
+    fn resolve_default_assignments(&self, assignments: &[Assignment]) -> Vec<Assignment> {
+        assignments
+            .iter()
+            .map(|assignment| match assignment.value {
+                Expr::Default => Assignment {
+                    column: assignment.column.clone(),
+                    value: self.default_expr(&assignment.column),
+                },
+                _ => assignment.clone(),
+            })
+            .collect()
     }
 
     pub fn update_tuples(
@@ -341,24 +776,102 @@ impl TableInfo {
         assignments: &[Assignment],
         filter: Option<&Expr>,
     ) -> ExecutionResult<Vec<Tuple>> {
-        let mut updated = Vec::new();
-        let tuples = self.heap.scan_tuples(&self.schema)?;
-        for (rid, tuple) in tuples {
+        let mut matching = Vec::new();
+        for (rid, tuple) in self.heap.scan_tuples(&self.schema)? {
             if let Some(predicate) = filter {
                 if !evaluate_predicate(predicate, &tuple, &self.schema)? {
                     continue;
                 }
             }
+            matching.push((rid, tuple));
+        }
+        self.update_known_tuples(assignments, matching)
+    }
 
+    /// Applies `assignments` to rows already read from the heap, such as
+    /// those an index scan found.
+    pub fn update_known_tuples(
+        &self,
+        assignments: &[Assignment],
+        tuples: Vec<(Rid, Tuple)>,
+    ) -> ExecutionResult<Vec<Tuple>> {
+        let assignments = &self.resolve_default_assignments(assignments);
+        let mut pending = Vec::new();
+        for (rid, tuple) in tuples {
             let new_tuple = apply_assignments(&tuple, &self.schema, assignments)?;
+            let new_tuple = self.normalize_formatted_text(new_tuple)?;
+            self.check_assigned_values(&new_tuple, assignments)?;
+            self.check_constraints(&new_tuple)?;
+            pending.push((rid, tuple, new_tuple));
+        }
+        self.apply_updates(pending)
+    }
+
+    /// `UPDATE ... FROM`: every target row is paired with the source rows and
+    /// the first pair satisfying `filter` supplies the new values. The filter
+    /// and assignments see the target columns followed by the source columns.
+    pub fn update_tuples_from(
+        &self,
+        assignments: &[Assignment],
+        filter: Option<&Expr>,
+        source_schema: &Schema,
+        source_rows: &[Tuple],
+    ) -> ExecutionResult<Vec<Tuple>> {
+        let assignments = &self.resolve_default_assignments(assignments);
+        let joined_schema = self.joined_schema(source_schema);
+        let mut pending = Vec::new();
+        for (rid, tuple) in self.heap.scan_tuples(&self.schema)? {
+            for source in source_rows {
+                let joined = join_tuples(&tuple, source);
+                if let Some(predicate) = filter {
+                    if !evaluate_predicate(predicate, &joined, &joined_schema)? {
+                        continue;
+                    }
+                }
+                let new_tuple = apply_joined_assignments(
+                    &tuple,
+                    &self.schema,
+                    &joined,
+                    &joined_schema,
+                    assignments,
+                )?;
+                let new_tuple = self.normalize_formatted_text(new_tuple)?;
+                self.check_assigned_values(&new_tuple, assignments)?;
+                self.check_constraints(&new_tuple)?;
+                pending.push((rid, tuple, new_tuple));
+                break;
+            }
+        }
+        self.apply_updates(pending)
+    }
+
+    /// Target columns, qualified by the table name, followed by the columns
+    /// of a joined source.
+    fn joined_schema(&self, source_schema: &Schema) -> Schema {
+        let mut fields: Vec<Field> = self
+            .schema
+            .fields
+            .iter()
+            .map(|field| Field {
+                table: Some(self.name.clone()),
+                ..field.clone()
+            })
+            .collect();
+        fields.extend(source_schema.fields.iter().cloned());
+        Schema::new(fields)
+    }
+
+    fn apply_updates(&self, pending: Vec<(Rid, Tuple, Tuple)>) -> ExecutionResult<Vec<Tuple>> {
+        let mut updated = Vec::new();
+        for (rid, tuple, new_tuple) in pending {
+            // before the unique checks, which see the latest rows
+            self.heap.check_writable(rid)?;
             let mut old_keys = Vec::with_capacity(self.indexes.len());
             let mut new_keys = Vec::with_capacity(self.indexes.len());
             for index in &self.indexes {
-                let old_key =
-                    Self::key_from_tuple(&tuple, &index.column_indices, &index.key_types)?;
-                let new_key =
-                    Self::key_from_tuple(&new_tuple, &index.column_indices, &index.key_types)?;
-                if index.unique {
+                let old_key = index.key_for(&tuple)?;
+                let new_key = index.key_for(&new_tuple)?;
+                if index.unique && !new_key.has_null() {
                     let existing = index.index.get(&new_key)?;
                     if existing.iter().any(|existing_rid| *existing_rid != rid) {
                         return Err(ExecutionError::ConstraintViolation {
@@ -382,13 +895,50 @@ impl TableInfo {
                 let _ = index.index.delete(&old_key, rid)?;
                 index.index.insert(new_key, new_rid)?;
             }
+            self.log_change(ChangeOp::Update, &tuple, Some(&new_tuple))?;
             updated.push(new_tuple);
         }
+        self.stats.record_modifications(updated.len());
         Ok(updated)
     }
 
+    /// Applies the insert-time NOT NULL and column type checks to the columns
+    /// an UPDATE assigns, before any row or index is touched.
+    fn check_assigned_values(
+        &self,
+        tuple: &Tuple,
+        assignments: &[Assignment],
+    ) -> ExecutionResult<()> {
+        for assignment in assignments {
+            let Some(index) = self.schema.field_index(&assignment.column) else {
+                continue;
+            };
+            let field = &self.schema.fields[index];
+            let value = tuple
+                .get(index)
+                .ok_or_else(|| ExecutionError::Execution("tuple missing column".to_string()))?;
+            if value.is_null() {
+                if !field.nullable {
+                    return Err(ExecutionError::ConstraintViolation {
+                        table: self.name.clone(),
+                        constraint: format!("{} NOT NULL", field.name),
+                        key: "NULL".to_string(),
+                    });
+                }
+                continue;
+            }
+            if !value_matches_type(value, &field.data_type) {
+                return Err(ExecutionError::Schema(format!(
+                    "cannot assign {:?} to column {} of type {:?}",
+                    value, field.name, field.data_type
+                )));
+            }
+        }
+        Ok(())
+    }
+
     pub fn delete_tuples(&self, filter: Option<&Expr>) -> ExecutionResult<usize> {
-        let mut deleted = 0;
+        let mut matching = Vec::new();
         let tuples = self.heap.scan_tuples(&self.schema)?;
         for (rid, tuple) in tuples {
             if let Some(predicate) = filter {
@@ -396,73 +946,186 @@ impl TableInfo {
                     continue;
                 }
             }
+            matching.push((rid, tuple));
+        }
+        self.delete_known_tuples(matching)
+    }
+
+    /// `DELETE ... USING`: removes every target row for which some source row
+    /// satisfies `filter`, evaluated over the target columns followed by the
+    /// source columns.
+    pub fn delete_tuples_using(
+        &self,
+        filter: Option<&Expr>,
+        source_schema: &Schema,
+        source_rows: &[Tuple],
+    ) -> ExecutionResult<usize> {
+        let joined_schema = self.joined_schema(source_schema);
+        let mut rids = Vec::new();
+        for (rid, tuple) in self.heap.scan_tuples(&self.schema)? {
+            for source in source_rows {
+                let matched = match filter {
+                    Some(predicate) => {
+                        evaluate_predicate(predicate, &join_tuples(&tuple, source), &joined_schema)?
+                    }
+                    None => true,
+                };
+                if matched {
+                    rids.push(rid);
+                    break;
+                }
+            }
+        }
+        self.delete_rids(&rids)
+    }
+
+    /// Deletes a known set of rows in one pass, without rescanning the heap.
+    /// Duplicate or already-deleted rids are skipped; returns rows removed.
+    /// Every `DELETE` ends here with the rids of the rows it matched, among
+    /// them the backend's order rollback, as do `DELETE ... USING` and
+    /// `INSERT OR REPLACE`.
+    pub fn delete_rids(&self, rids: &[Rid]) -> ExecutionResult<usize> {
+        let mut rids = rids.to_vec();
+        rids.sort_by_key(|rid| (rid.page_id, rid.slot_id));
+        rids.dedup();
+        let mut known = Vec::with_capacity(rids.len());
+        for rid in rids {
+            if let Some(tuple) = self.heap.get_tuple(rid, &self.schema)? {
+                known.push((rid, tuple));
+            }
+        }
+        self.delete_known_tuples(known)
+    }
+
+    /// Deletes rows already read from the heap, with their index entries.
+    /// Rows deleted since they were read are skipped; returns rows removed.
+    pub fn delete_known_tuples(&self, tuples: Vec<(Rid, Tuple)>) -> ExecutionResult<usize> {
+        let mut deleted = 0;
+        for (rid, tuple) in tuples {
             if !self.heap.delete_tuple(rid)? {
                 continue;
             }
             for index in &self.indexes {
-                let key = Self::key_from_tuple(&tuple, &index.column_indices, &index.key_types)?;
+                let key = index.key_for(&tuple)?;
                 let _ = index.index.delete(&key, rid)?;
             }
+            self.log_change(ChangeOp::Delete, &tuple, None)?;
             deleted += 1;
         }
+        self.stats.record_modifications(deleted);
         Ok(deleted)
     }
 
-    pub fn rebuild_indexes(&mut self) -> ExecutionResult<()> {
+    /// Recomputes the table statistics (`ANALYZE`).
+    pub fn analyze(&self) -> ExecutionResult<TableStats> {
+        self.stats.refresh(&self.heap, &self.indexes)
+    }
+
+    /// Every page the table and its indexes occupy.
+    pub fn page_ids(&self) -> ExecutionResult<Vec<PageId>> {
+        let mut page_ids = self.heap.page_ids(&self.schema)?;
+        for index in &self.indexes {
+            page_ids.extend(index.index.page_ids()?);
+        }
+        Ok(page_ids)
+    }
+
+    /// Rebuilds every index from the heap and returns the pages of the
+    /// trees they replaced, for the caller to free.
+    pub fn rebuild_indexes(&mut self) -> ExecutionResult<Vec<PageId>> {
         let tuples = self.heap.scan_tuples(&self.schema)?;
-        for index in &mut self.indexes {
-            let text_key_size = index.index.text_key_size();
-            let rebuilt = if index.key_types.len() > 1 {
-                BPlusTree::create_composite(
-                    self.heap.buffer_pool().clone(),
-                    index.key_types.clone(),
-                    Some(text_key_size),
-                    index.unique,
-                )?
-            } else {
-                BPlusTree::create(
-                    self.heap.buffer_pool().clone(),
-                    index.key_types[0],
-                    Some(text_key_size),
-                    index.unique,
-                )?
-            };
-            for (rid, tuple) in &tuples {
-                let key = Self::key_from_tuple(tuple, &index.column_indices, &index.key_types)?;
-                rebuilt.insert(key, *rid)?;
-            }
-            index.index = rebuilt;
+        let mut replaced = Vec::new();
+        for position in 0..self.indexes.len() {
+            let rebuilt = self.build_index(&self.indexes[position], &tuples)?;
+            let old = std::mem::replace(&mut self.indexes[position].index, rebuilt);
+            replaced.extend(old.page_ids()?);
         }
-        Ok(())
+        self.version.bump();
+        Ok(replaced)
     }
 
-    fn key_from_tuple(
-        tuple: &Tuple,
-        column_indices: &[usize],
-        key_types: &[IndexKeyType],
-    ) -> ExecutionResult<IndexKey> {
-        let mut values = Vec::with_capacity(column_indices.len());
-        for column_index in column_indices {
-            let value = tuple
-                .get(*column_index)
-                .ok_or_else(|| ExecutionError::Execution("tuple missing column".to_string()))?;
-            values.push(value.clone());
+    /// Rebuilds the index `name` from the heap and returns the pages of the
+    /// tree it replaced, for the caller to free.
+    pub fn rebuild_index(&mut self, name: &str) -> ExecutionResult<Vec<PageId>> {
+        let position = self
+            .indexes
+            .iter()
+            .position(|index| index.name.eq_ignore_ascii_case(name))
+            .ok_or_else(|| ExecutionError::Execution(format!("index {} not found", name)))?;
+        let tuples = self.heap.scan_tuples(&self.schema)?;
+        let rebuilt = self.build_index(&self.indexes[position], &tuples)?;
+        let replaced = std::mem::replace(&mut self.indexes[position].index, rebuilt);
+        self.version.bump();
+        replaced.page_ids()
+    }
+
+    /// A new tree for `index` over `tuples`. Entries go in in key order, so
+    /// the leaves come out full and linked in page order.
+    fn build_index(
+        &self,
+        index: &IndexInfo,
+        tuples: &[(Rid, Tuple)],
+    ) -> ExecutionResult<BPlusTree> {
+        let text_key_size = index.index.text_key_size();
+        let rebuilt = if index.key_types.len() > 1 {
+            BPlusTree::create_composite(
+                self.heap.buffer_pool().clone(),
+                index.key_types.clone(),
+                Some(text_key_size),
+                index.unique,
+            )?
+        } else {
+            BPlusTree::create(
+                self.heap.buffer_pool().clone(),
+                index.key_types[0],
+                Some(text_key_size),
+                index.unique,
+            )?
+        };
+        let mut entries = tuples
+            .iter()
+            .map(|(rid, tuple)| Ok((index.key_for(tuple)?, *rid)))
+            .collect::<ExecutionResult<Vec<_>>>()?;
+        entries.sort_by(|left, right| left.0.cmp(&right.0));
+        for (key, rid) in entries {
+            rebuilt.insert(key, rid)?;
         }
-        IndexKey::from_values(&values, key_types)
+        Ok(rebuilt)
     }
 }
 
 pub struct Catalog {
     tables: HashMap<String, TableInfo>,
+    /// Temporary tables of each transaction. Only statements running in
+    /// that transaction see them, ahead of permanent tables, and they are
+    /// never persisted.
+    temp_tables: HashMap<wal::TxnId, HashMap<String, TableInfo>>,
+    sequences: Sequences,
+    privileges: Privileges,
 }
 
 impl Catalog {
     pub fn new() -> Self {
         Self {
             tables: HashMap::new(),
+            temp_tables: HashMap::new(),
+            sequences: Sequences::default(),
+            privileges: Privileges::default(),
         }
     }
 
+    pub fn sequences(&self) -> &Sequences {
+        &self.sequences
+    }
+
+    pub fn privileges(&self) -> &Privileges {
+        &self.privileges
+    }
+
+    pub fn privileges_mut(&mut self) -> &mut Privileges {
+        &mut self.privileges
+    }
+
     pub fn register_table(
         &mut self,
         table_name: impl Into<String>,
@@ -476,24 +1139,80 @@ impl Catalog {
 
     pub fn register_table_info(&mut self, table: TableInfo) {
         let name = normalize_name(&table.name);
-        self.tables.insert(name, table);
+        if let Some(replaced) = self.tables.insert(name, table) {
+            replaced.version.bump();
+        }
     }
 
-    pub fn table(&self, table_name: &str) -> Option<&TableInfo> {
-        let name = normalize_name(table_name);
-        self.tables.get(&name)
+    /// Registers a temporary table owned by `txn_id`.
+    pub fn register_temp_table(&mut self, txn_id: wal::TxnId, table: TableInfo) {
+        let name = normalize_name(&table.name);
+        self.temp_tables
+            .entry(txn_id)
+            .or_default()
+            .insert(name, table);
     }
 
-    pub fn table_mut(&mut self, table_name: &str) -> Option<&mut TableInfo> {
-        let name = normalize_name(table_name);
-        self.tables.get_mut(&name)
+    /// Removes and returns the temporary tables of `txn_id`, which has ended.
+    pub fn drop_temp_tables(&mut self, txn_id: wal::TxnId) -> Vec<TableInfo> {
+        let dropped = self
+            .temp_tables
+            .remove(&txn_id)
+            .map(|tables| tables.into_values().collect::<Vec<_>>())
+            .unwrap_or_default();
+        for table in &dropped {
+            table.version.bump();
+        }
+        dropped
     }
 
-    pub fn table_names(&self) -> Vec<String> {
-        let mut names = self
-            .tables
-            .values()
-            .map(|table| table.name.clone())
+    pub fn temp_tables(&self, txn_id: wal::TxnId) -> impl Iterator<Item = &TableInfo> {
+        self.temp_tables
+            .get(&txn_id)
+            .into_iter()
+            .flat_map(|tables| tables.values())
+    }
+
+    pub fn temp_tables_mut(&mut self, txn_id: wal::TxnId) -> impl Iterator<Item = &mut TableInfo> {
+        self.temp_tables
+            .get_mut(&txn_id)
+            .into_iter()
+            .flat_map(|tables| tables.values_mut())
+    }
+
+    /// True when `table_name` resolves to a temporary table of the current
+    /// transaction.
+    pub fn is_temp_table(&self, table_name: &str) -> bool {
+        self.current_temp_tables()
+            .is_some_and(|tables| tables.contains_key(&normalize_name(table_name)))
+    }
+
+    fn current_temp_tables(&self) -> Option<&HashMap<String, TableInfo>> {
+        wal::current_txn_id().and_then(|txn_id| self.temp_tables.get(&txn_id))
+    }
+
+    pub fn table(&self, table_name: &str) -> Option<&TableInfo> {
+        let name = normalize_name(table_name);
+        self.current_temp_tables()
+            .and_then(|tables| tables.get(&name))
+            .or_else(|| self.tables.get(&name))
+    }
+
+    pub fn table_mut(&mut self, table_name: &str) -> Option<&mut TableInfo> {
+        let name = normalize_name(table_name);
+        let temp_tables =
+            wal::current_txn_id().and_then(|txn_id| self.temp_tables.get_mut(&txn_id));
+        match temp_tables {
+            Some(tables) if tables.contains_key(&name) => tables.get_mut(&name),
+            _ => self.tables.get_mut(&name),
+        }
+    }
+
+    pub fn table_names(&self) -> Vec<String> {
+        let mut names = self
+            .tables
+            .values()
+            .map(|table| table.name.clone())
             .collect::<Vec<_>>();
         names.sort();
         names
@@ -505,13 +1224,32 @@ impl Catalog {
 
     pub fn drop_table(&mut self, table_name: &str) -> ExecutionResult<()> {
         let name = normalize_name(table_name);
-        if self.tables.remove(&name).is_some() {
+        let temp_tables =
+            wal::current_txn_id().and_then(|txn_id| self.temp_tables.get_mut(&txn_id));
+        if let Some(dropped) = temp_tables.and_then(|tables| tables.remove(&name)) {
+            dropped.version.bump();
+            Ok(())
+        } else if let Some(dropped) = self.tables.remove(&name) {
+            dropped.version.bump();
+            self.privileges.drop_table(table_name);
             Ok(())
         } else {
             Err(ExecutionError::TableNotFound(table_name.to_string()))
         }
     }
 
+    /// Puts `table` back under `table_name`, or removes the table of that
+    /// name when it is `None`; used to undo a rolled back schema change.
+    pub fn restore_table(&mut self, table_name: &str, table: Option<TableInfo>) {
+        let name = normalize_name(table_name);
+        if let Some(replaced) = self.tables.remove(&name) {
+            replaced.version.bump();
+        }
+        if let Some(table) = table {
+            self.tables.insert(name, table);
+        }
+    }
+
     pub fn rename_table(&mut self, table_name: &str, new_name: &str) -> ExecutionResult<()> {
         let current_key = normalize_name(table_name);
         let next_key = normalize_name(new_name);
@@ -533,6 +1271,7 @@ impl Catalog {
         let mut table = self.tables.remove(&current_key).expect("table exists");
         table.rename_table(new_name);
         self.tables.insert(next_key, table);
+        self.privileges.rename_table(table_name, new_name);
         Ok(())
     }
 
@@ -545,7 +1284,10 @@ impl Catalog {
         let table = self
             .table_mut(table_name)
             .ok_or_else(|| ExecutionError::TableNotFound(table_name.to_string()))?;
-        table.rename_column(old_name, new_name)
+        table.rename_column(old_name, new_name)?;
+        self.privileges
+            .rename_column(table_name, old_name, new_name);
+        Ok(())
     }
 
     pub fn add_column(&mut self, table_name: &str, column_def: ColumnDef) -> ExecutionResult<()> {
@@ -559,7 +1301,29 @@ impl Catalog {
         let table = self
             .table_mut(table_name)
             .ok_or_else(|| ExecutionError::TableNotFound(table_name.to_string()))?;
-        table.drop_column(column_name)
+        table.drop_column(column_name)?;
+        self.privileges.drop_column(table_name, column_name);
+        Ok(())
+    }
+
+    pub fn alter_column_type(
+        &mut self,
+        table_name: &str,
+        column_name: &str,
+        new_type: DataType,
+        using: Option<&Expr>,
+    ) -> ExecutionResult<Vec<PageId>> {
+        let table = self
+            .table_mut(table_name)
+            .ok_or_else(|| ExecutionError::TableNotFound(table_name.to_string()))?;
+        table.alter_column_type(column_name, new_type, using)
+    }
+
+    pub fn add_check(&mut self, table_name: &str, check: CheckConstraint) -> ExecutionResult<()> {
+        let table = self
+            .table_mut(table_name)
+            .ok_or_else(|| ExecutionError::TableNotFound(table_name.to_string()))?;
+        table.add_check(check)
     }
 
     pub fn insert_tuple(&self, table_name: &str, tuple: &Tuple) -> ExecutionResult<Rid> {
@@ -576,45 +1340,748 @@ impl Default for Catalog {
     }
 }
 
+/// Catalog behind a read-write lock. Planning and DML hold a read guard
+/// and DDL the write guard, so holders of a clone (see
+/// `Engine::shared_catalog`) read table metadata alongside a running
+/// statement and wait only for DDL. Statements still run one at a time:
+/// the engine executes them through `&mut self`, so the lock does not let
+/// queries themselves proceed in parallel.
+#[derive(Clone, Default)]
+pub struct SharedCatalog {
+    inner: Arc<RwLock<Catalog>>,
+}
+
+impl SharedCatalog {
+    /// Wraps `catalog` for sharing; clones refer to the same catalog.
+    pub fn new(catalog: Catalog) -> Self {
+        Self {
+            inner: Arc::new(RwLock::new(catalog)),
+        }
+    }
+
+    /// Takes a read guard, waiting while DDL holds the write guard.
+    pub fn read(&self) -> RwLockReadGuard<'_, Catalog> {
+        self.inner.read()
+    }
+
+    /// Takes the write guard for DDL, waiting for every reader to finish.
+    pub fn write(&self) -> RwLockWriteGuard<'_, Catalog> {
+        self.inner.write()
+    }
+}
+
+/// Bounds a query must stay within before the planner builds operators for
+/// it. `None` disables a check.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PlanLimits {
+    pub max_joins: Option<usize>,
+    /// Ceiling on the rows a plan is estimated to produce. Estimates use the
+    /// `ANALYZE` row counts and treat every join as a cross product; plans
+    /// over tables without statistics are not checked.
+    pub max_estimated_rows: Option<usize>,
+}
+
 pub struct PhysicalPlanner<'a> {
     catalog: &'a Catalog,
+    limits: PlanLimits,
+    estimates: bool,
+    work_mem: usize,
+    temp_usage: TempUsage,
 }
 
 impl<'a> PhysicalPlanner<'a> {
     pub fn new(catalog: &'a Catalog) -> Self {
-        Self { catalog }
+        Self {
+            catalog,
+            limits: PlanLimits::default(),
+            estimates: false,
+            work_mem: DEFAULT_WORK_MEM,
+            temp_usage: TempUsage::default(),
+        }
+    }
+
+    pub fn with_limits(mut self, limits: PlanLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Bytes of rows a sort or a join's hash table holds in memory. A larger
+    /// sort spills to temporary pages; a larger hash table is not built and
+    /// the join rescans its inner input instead.
+    pub fn with_work_mem(mut self, bytes: usize) -> Self {
+        self.work_mem = bytes;
+        self
+    }
+
+    /// Counts the temporary pages the planned operators spill in `usage`.
+    pub fn with_temp_usage(mut self, usage: TempUsage) -> Self {
+        self.temp_usage = usage;
+        self
+    }
+
+    /// Labels each operator with its estimated rows and cost, for EXPLAIN.
+    /// Operators over tables that were never analyzed are left unlabelled.
+    pub fn with_estimates(mut self) -> Self {
+        self.estimates = true;
+        self
     }
 
     pub fn plan(&self, plan: &LogicalPlan) -> ExecutionResult<Box<dyn PhysicalOperator>> {
-        Ok(self.plan_node(plan)?.operator)
+        Ok(self.plan_with_schema(plan)?.0)
+    }
+
+    /// Plans `plan` and also returns the schema of the rows it produces, with
+    /// column types resolved against the catalog.
+    pub fn plan_with_schema(
+        &self,
+        plan: &LogicalPlan,
+    ) -> ExecutionResult<(Box<dyn PhysicalOperator>, Schema)> {
+        self.check_limits(plan)?;
+        let planned = self.plan_node(plan)?;
+        Ok((planned.operator, planned.schema))
+    }
+
+    /// Evaluates the `RETURNING` list of an INSERT into `table_name` over
+    /// `rows`, the rows as they were stored, and returns the output schema
+    /// with the projected rows.
+    pub fn project_returning(
+        &self,
+        table_name: &str,
+        returning: &Returning,
+        rows: &[Tuple],
+    ) -> ExecutionResult<(Schema, Vec<Tuple>)> {
+        let table = self.table_info(table_name)?;
+        for expr in &returning.expressions {
+            check_columns(expr, &table.schema)?;
+        }
+        let schema = build_projection_schema(
+            &returning.expressions,
+            Some(&returning.aliases),
+            &table.schema,
+        )?;
+        let rows = project_rows(&returning.expressions, &table.schema, rows)?;
+        Ok((schema, rows))
+    }
+
+    /// Resolves `plan` against the catalog without running it or reading
+    /// any rows: tables and columns must exist, and INSERT values must match
+    /// the column count and types.
+    pub fn validate(&self, plan: &LogicalPlan) -> ExecutionResult<()> {
+        match plan {
+            LogicalPlan::Insert {
+                table_name,
+                columns,
+                values,
+                source,
+                returning,
+                ..
+            } => {
+                let table = self.table_info(table_name)?;
+                if let Some(returning) = returning {
+                    for expr in &returning.expressions {
+                        check_columns(expr, &table.schema)?;
+                    }
+                }
+                let indices = match columns {
+                    Some(columns) => columns
+                        .iter()
+                        .map(|column| {
+                            let name = column.split('.').next_back().unwrap_or(column);
+                            table.schema.field_index(name).ok_or_else(|| {
+                                ExecutionError::Schema(format!("column {} not found", column))
+                            })
+                        })
+                        .collect::<ExecutionResult<Vec<_>>>()?,
+                    None => (0..table.schema.fields.len())
+                        .filter(|index| table.schema.fields[*index].visible)
+                        .collect(),
+                };
+                if let Some(source) = source {
+                    let width = self.plan_with_schema(source)?.1.fields.len();
+                    if width != indices.len() {
+                        return Err(ExecutionError::Schema(format!(
+                            "expected {} values, got {}",
+                            indices.len(),
+                            width
+                        )));
+                    }
+                }
+                for row in values {
+                    if row.len() != indices.len() {
+                        return Err(ExecutionError::Schema(format!(
+                            "expected {} values, got {}",
+                            indices.len(),
+                            row.len()
+                        )));
+                    }
+                    for (expr, index) in row.iter().zip(&indices) {
+                        if matches!(expr, Expr::Default) {
+                            continue;
+                        }
+                        let value = evaluate_expr(expr, &Tuple::new(Vec::new()), &Schema::empty())?;
+                        let field = &table.schema.fields[*index];
+                        if !value.is_null() && !value_matches_type(&value, &field.data_type) {
+                            return Err(ExecutionError::Schema(format!(
+                                "cannot insert {:?} into column {} of type {:?}",
+                                value, field.name, field.data_type
+                            )));
+                        }
+                    }
+                }
+                Ok(())
+            }
+            LogicalPlan::Update {
+                table_name,
+                assignments,
+                from,
+                filter,
+                ..
+            } => {
+                let table = self.table_info(table_name)?;
+                let schema = match from {
+                    Some(source) => table.joined_schema(&self.plan_node(source)?.schema),
+                    None => table.schema.clone(),
+                };
+                for assignment in assignments {
+                    if table.schema.field_index(&assignment.column).is_none() {
+                        return Err(ExecutionError::Schema(format!(
+                            "column {} not found",
+                            assignment.column
+                        )));
+                    }
+                    check_columns(&assignment.value, &schema)?;
+                }
+                filter
+                    .iter()
+                    .try_for_each(|predicate| check_columns(predicate, &schema))
+            }
+            LogicalPlan::Delete {
+                table_name,
+                using,
+                filter,
+                ..
+            } => {
+                let table = self.table_info(table_name)?;
+                let schema = match using {
+                    Some(source) => table.joined_schema(&self.plan_node(source)?.schema),
+                    None => table.schema.clone(),
+                };
+                filter
+                    .iter()
+                    .try_for_each(|predicate| check_columns(predicate, &schema))
+            }
+            LogicalPlan::CreateTable {
+                table_name,
+                if_not_exists,
+                ..
+            } => match self.catalog.table(table_name) {
+                Some(_) if !if_not_exists => Err(ExecutionError::Schema(format!(
+                    "table {} already exists",
+                    table_name
+                ))),
+                _ => Ok(()),
+            },
+            LogicalPlan::DropTable {
+                table_name,
+                if_exists,
+            } => {
+                if !if_exists {
+                    self.table_info(table_name)?;
+                }
+                Ok(())
+            }
+            LogicalPlan::Analyze { table_name }
+            | LogicalPlan::AlterTableRename { table_name, .. }
+            | LogicalPlan::AlterTableAddColumn { table_name, .. }
+            | LogicalPlan::AlterTableAddCheck { table_name, .. } => {
+                self.table_info(table_name).map(|_| ())
+            }
+            LogicalPlan::AlterTableRenameColumn {
+                table_name,
+                old_column_name: column_name,
+                ..
+            }
+            | LogicalPlan::AlterTableDropColumn {
+                table_name,
+                column_name,
+            }
+            | LogicalPlan::AlterTableAlterColumnType {
+                table_name,
+                column_name,
+                ..
+            } => {
+                let table = self.table_info(table_name)?;
+                resolve_column_index(&table.schema, None, column_name).map(|_| ())
+            }
+            LogicalPlan::CreateIndex {
+                table_name,
+                column_names,
+                ..
+            } => {
+                let table = self.table_info(table_name)?;
+                for column_name in column_names {
+                    resolve_column_index(&table.schema, None, column_name)?;
+                }
+                Ok(())
+            }
+            LogicalPlan::CreateSequence {
+                sequence_name,
+                if_not_exists,
+                ..
+            } => {
+                if !if_not_exists && self.catalog.sequences().contains(sequence_name) {
+                    return Err(ExecutionError::Schema(format!(
+                        "sequence {} already exists",
+                        sequence_name
+                    )));
+                }
+                Ok(())
+            }
+            LogicalPlan::DropSequence {
+                sequence_name,
+                if_exists,
+            } => {
+                if !if_exists && !self.catalog.sequences().contains(sequence_name) {
+                    return Err(ExecutionError::Schema(format!(
+                        "sequence {} does not exist",
+                        sequence_name
+                    )));
+                }
+                Ok(())
+            }
+            LogicalPlan::Grant {
+                table_names,
+                privileges,
+                ..
+            }
+            | LogicalPlan::Revoke {
+                table_names,
+                privileges,
+                ..
+            } => {
+                for table_name in table_names {
+                    let table = self.table_info(table_name)?;
+                    for column in privileges
+                        .iter()
+                        .flat_map(|spec| spec.columns.iter().flatten())
+                    {
+                        resolve_column_index(&table.schema, None, column)?;
+                    }
+                }
+                Ok(())
+            }
+            LogicalPlan::Copy {
+                table_name,
+                columns,
+                ..
+            } => {
+                let table = self.table_info(table_name)?;
+                columns.iter().flatten().try_for_each(|column| {
+                    resolve_column_index(&table.schema, None, column).map(|_| ())
+                })
+            }
+            LogicalPlan::Transaction { .. } | LogicalPlan::Set { .. } => Ok(()),
+            LogicalPlan::Explain { input } => self.validate(input),
+            _ => self.plan_with_schema(plan).map(|_| ()),
+        }
+    }
+
+    /// Checks that `role` holds the privileges `plan` needs, before it is
+    /// planned or run.
+    pub fn check_privileges(&self, plan: &LogicalPlan, role: &str) -> ExecutionResult<()> {
+        privileges::check_plan(self.catalog, role, plan)
+    }
+
+    /// Runs each `IN (SELECT ...)` and scalar subquery in `plan` once and
+    /// puts what it returned in its place: an `IN` list of the distinct
+    /// values, or the single value. A scalar subquery that returns more
+    /// than one row is an error. Subqueries may not refer to the columns of
+    /// the outer query, which fail to resolve when they run.
+    pub fn run_subqueries(&self, plan: &mut LogicalPlan) -> ExecutionResult<()> {
+        let mut result = Ok(());
+        plan.visit_exprs_mut(&mut |expr| {
+            expr.visit_mut(&mut |expr| {
+                if result.is_ok() {
+                    result = self.run_subquery(expr);
+                }
+            })
+        });
+        result
+    }
+
+    fn run_subquery(&self, expr: &mut Expr) -> ExecutionResult<()> {
+        match expr {
+            Expr::InSubquery {
+                expr: key,
+                subplan,
+                negated,
+            } => {
+                let mut seen = HashSet::new();
+                let list = self
+                    .subquery_rows(subplan)?
+                    .into_iter()
+                    .map(|row| row.values()[0].clone())
+                    .filter(|value| seen.insert(value.clone()))
+                    .map(|value| bound_value(&value))
+                    .collect();
+                *expr = Expr::In {
+                    expr: Box::new(std::mem::replace(key, Expr::Default)),
+                    list,
+                    negated: *negated,
+                };
+            }
+            Expr::ScalarSubquery(subplan) => {
+                let rows = self.subquery_rows(subplan)?;
+                if rows.len() > 1 {
+                    return Err(ExecutionError::Execution(format!(
+                        "scalar subquery returned {} rows, expected at most one",
+                        rows.len()
+                    )));
+                }
+                let value = rows
+                    .first()
+                    .map_or(Value::Null, |row| row.values()[0].clone());
+                *expr = bound_value(&value);
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Runs a subquery, which must return a single column.
+    fn subquery_rows(&self, subplan: &mut LogicalPlan) -> ExecutionResult<Vec<Tuple>> {
+        self.run_subqueries(subplan)?;
+        let (operator, schema) = self.plan_with_schema(subplan)?;
+        if schema.fields.len() != 1 {
+            return Err(ExecutionError::Schema(
+                "subquery must return exactly one column".to_string(),
+            ));
+        }
+        Executor::new(operator).execute()
+    }
+
+    fn table_info(&self, table_name: &str) -> ExecutionResult<&'a TableInfo> {
+        self.catalog
+            .table(table_name)
+            .ok_or_else(|| ExecutionError::TableNotFound(table_name.to_string()))
+    }
+
+    fn check_limits(&self, plan: &LogicalPlan) -> ExecutionResult<()> {
+        if let Some(max_joins) = self.limits.max_joins {
+            let joins = count_joins(plan);
+            if joins > max_joins {
+                return Err(ExecutionError::PlanTooComplex(format!(
+                    "query has {} joins, more than the limit of {}",
+                    joins, max_joins
+                )));
+            }
+        }
+        if let Some(max_rows) = self.limits.max_estimated_rows {
+            if let Some(estimate) = self.estimate_rows(plan) {
+                if estimate > max_rows {
+                    return Err(ExecutionError::PlanTooComplex(format!(
+                        "query is estimated to produce {} rows, more than the limit of {}",
+                        estimate, max_rows
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Upper-bound row estimate, or `None` when a scanned table has no stats.
+    fn estimate_rows(&self, plan: &LogicalPlan) -> Option<usize> {
+        match plan {
+            LogicalPlan::Scan {
+                table_name, sample, ..
+            } => self
+                .catalog
+                .table(table_name)
+                .and_then(|table| table.stats.stats())
+                .map(|stats| match sample {
+                    Some(sample) => {
+                        (stats.row_count as f64 * sample.percent / 100.0).ceil() as usize
+                    }
+                    None => stats.row_count,
+                }),
+            LogicalPlan::SingleRow => Some(1),
+            LogicalPlan::Values { rows, .. } => Some(rows.len()),
+            LogicalPlan::Join {
+                left,
+                join_type: JoinType::Semi,
+                ..
+            } => self.estimate_rows(left),
+            LogicalPlan::Join { left, right, .. } => Some(
+                self.estimate_rows(left)?
+                    .saturating_mul(self.estimate_rows(right)?),
+            ),
+            LogicalPlan::Limit {
+                input,
+                offset,
+                limit: Some(limit),
+            } => {
+                let limit = limit.saturating_add(offset.unwrap_or(0));
+                Some(
+                    self.estimate_rows(input)
+                        .map_or(limit, |rows| rows.min(limit)),
+                )
+            }
+            LogicalPlan::Filter { input, .. }
+            | LogicalPlan::Project { input, .. }
+            | LogicalPlan::Sort { input, .. }
+            | LogicalPlan::DistinctOn { input, .. }
+            | LogicalPlan::Limit { input, .. }
+            | LogicalPlan::Aggregate { input, .. } => self.estimate_rows(input),
+            _ => None,
+        }
+    }
+
+    /// Estimated rows and cost of `plan`, following the access paths
+    /// `plan_node` picks. `None` when a scanned table has no stats.
+    fn estimate(&self, plan: &LogicalPlan) -> Option<Estimate> {
+        match plan {
+            LogicalPlan::Scan {
+                table_name, sample, ..
+            } => {
+                let stats = self.catalog.table(table_name)?.stats.stats()?;
+                let rows = stats.row_count as f64;
+                Some(Estimate {
+                    rows: sample.map_or(rows, |sample| rows * sample.percent / 100.0),
+                    cost: stats.page_count as f64 * SEQ_PAGE_COST + rows * CPU_TUPLE_COST,
+                })
+            }
+            LogicalPlan::SingleRow => Some(Estimate {
+                rows: 1.0,
+                cost: CPU_TUPLE_COST,
+            }),
+            LogicalPlan::Values { rows, .. } => Some(Estimate {
+                rows: rows.len() as f64,
+                cost: rows.len() as f64 * CPU_TUPLE_COST,
+            }),
+            LogicalPlan::Filter { input, predicate } => {
+                let input_estimate = self.estimate(input)?;
+                let scanned = match input.as_ref() {
+                    LogicalPlan::Scan {
+                        table_name,
+                        alias,
+                        sample,
+                        ..
+                    } => Some((table_name, alias, sample)),
+                    _ => None,
+                };
+                let table = scanned.and_then(|(table_name, ..)| self.catalog.table(table_name));
+                let unique = |column: &str| {
+                    table
+                        .and_then(|table| table.index_for_column(column))
+                        .is_some_and(|index| index.unique)
+                };
+                let rows = scale_rows(
+                    input_estimate.rows,
+                    selectivity(predicate, input_estimate.rows, &unique),
+                );
+                let indexed = match scanned {
+                    Some((table_name, alias, None)) => self
+                        .plan_index_scan(table_name, alias.as_deref(), predicate)
+                        .ok()
+                        .flatten()
+                        .is_some(),
+                    _ => false,
+                };
+                let cost = if indexed {
+                    // one descent of the tree, then a heap page per row
+                    let stats = table?.stats.stats()?;
+                    (input_estimate.rows.max(2.0)).log2() * CPU_OPERATOR_COST
+                        + rows.min(stats.page_count as f64) * RANDOM_PAGE_COST
+                        + rows * CPU_TUPLE_COST
+                } else {
+                    input_estimate.cost + input_estimate.rows * CPU_OPERATOR_COST
+                };
+                Some(Estimate { rows, cost })
+            }
+            LogicalPlan::Project {
+                input, expressions, ..
+            } => {
+                let input = self.estimate(input)?;
+                Some(Estimate {
+                    rows: input.rows,
+                    cost: input.cost + input.rows * expressions.len() as f64 * CPU_OPERATOR_COST,
+                })
+            }
+            LogicalPlan::Sort { input, .. } => {
+                let input = self.estimate(input)?;
+                Some(Estimate {
+                    rows: input.rows,
+                    cost: input.cost + input.rows * input.rows.max(2.0).log2() * CPU_OPERATOR_COST,
+                })
+            }
+            LogicalPlan::DistinctOn { input, on } => {
+                let input = self.estimate(input)?;
+                Some(Estimate {
+                    rows: scale_rows(input.rows, EQUALITY_SELECTIVITY),
+                    cost: input.cost + input.rows * on.len() as f64 * CPU_OPERATOR_COST,
+                })
+            }
+            LogicalPlan::Limit {
+                input,
+                offset,
+                limit,
+            } => {
+                let input = self.estimate(input)?;
+                let after_offset = (input.rows - offset.unwrap_or(0) as f64).max(0.0);
+                Some(Estimate {
+                    rows: limit.map_or(after_offset, |limit| after_offset.min(limit as f64)),
+                    cost: input.cost,
+                })
+            }
+            LogicalPlan::Aggregate {
+                input,
+                group_by,
+                aggregates,
+            } => {
+                if unfiltered_count_table(input, group_by, aggregates).is_some() {
+                    // the maintained row count is read without a scan
+                    self.estimate(input)?;
+                    return Some(Estimate {
+                        rows: 1.0,
+                        cost: CPU_TUPLE_COST,
+                    });
+                }
+                let input = self.estimate(input)?;
+                let rows = if group_by.is_empty() {
+                    1.0
+                } else {
+                    scale_rows(input.rows, EQUALITY_SELECTIVITY)
+                };
+                let exprs = (group_by.len() + aggregates.len()) as f64;
+                Some(Estimate {
+                    rows,
+                    cost: input.cost + input.rows * exprs * CPU_OPERATOR_COST,
+                })
+            }
+            LogicalPlan::Join {
+                left,
+                right,
+                join_type,
+                condition,
+            } => {
+                let left = self.estimate(left)?;
+                let right = self.estimate(right)?;
+                let inputs_cost = left.cost + right.cost;
+                if *join_type == JoinType::Semi {
+                    return Some(Estimate {
+                        rows: scale_rows(left.rows, 0.5),
+                        cost: inputs_cost + (left.rows + right.rows) * CPU_OPERATOR_COST,
+                    });
+                }
+                let pairs = left.rows * right.rows;
+                let matched = match condition {
+                    Some(condition) => scale_rows(
+                        pairs,
+                        selectivity(condition, left.rows.max(right.rows), &|_| false),
+                    ),
+                    None => pairs,
+                };
+                let rows = match join_type {
+                    JoinType::Left => matched.max(left.rows),
+                    JoinType::Right => matched.max(right.rows),
+                    JoinType::Full => matched.max(left.rows + right.rows),
+                    _ => matched,
+                };
+                Some(Estimate {
+                    rows,
+                    cost: inputs_cost + pairs * CPU_OPERATOR_COST,
+                })
+            }
+            _ => None,
+        }
     }
 
     fn plan_node(&self, plan: &LogicalPlan) -> ExecutionResult<PlannedOperator> {
+        let planned = self.plan_operator(plan)?;
+        if !self.estimates {
+            return Ok(planned);
+        }
+        Ok(match self.estimate(plan) {
+            Some(estimate) => PlannedOperator {
+                operator: Box::new(Estimated::new(planned.operator, estimate)),
+                schema: planned.schema,
+            },
+            None => planned,
+        })
+    }
+
+    fn plan_operator(&self, plan: &LogicalPlan) -> ExecutionResult<PlannedOperator> {
         match plan {
             LogicalPlan::Scan {
-                table_name, alias, ..
+                table_name,
+                alias,
+                sample,
+                ..
             } => {
                 let table = self
                     .catalog
                     .table(table_name)
                     .ok_or_else(|| ExecutionError::TableNotFound(table_name.clone()))?;
                 let schema = apply_alias(&table.schema, alias.as_deref());
-                let operator = Box::new(SeqScan::new(table.heap.clone(), schema.clone()));
+                let mut scan = SeqScan::new(table.heap.clone(), schema.clone())
+                    .with_schema_version(table.schema_version());
+                if let Some(sample) = sample {
+                    scan = scan.with_sample(*sample);
+                }
+                let operator = Box::new(scan);
                 Ok(PlannedOperator { operator, schema })
             }
+            LogicalPlan::SingleRow => Ok(PlannedOperator {
+                operator: Box::new(SingleRow::new()),
+                schema: Schema::empty(),
+            }),
+            LogicalPlan::Values {
+                rows,
+                alias,
+                columns,
+            } => {
+                let empty = Schema::empty();
+                let fields = columns
+                    .iter()
+                    .enumerate()
+                    .map(|(index, name)| {
+                        // a column's type comes from its first non-NULL literal
+                        let data_type = rows
+                            .iter()
+                            .find_map(|row| projected_type(&row[index], &empty))
+                            .map_or(DataType::Text, |(data_type, _)| data_type);
+                        Field {
+                            name: name.clone(),
+                            table: alias.clone(),
+                            data_type,
+                            nullable: true,
+                            visible: true,
+                        }
+                    })
+                    .collect();
+                Ok(PlannedOperator {
+                    operator: Box::new(Values::new(rows.clone())),
+                    schema: Schema::new(fields),
+                })
+            }
             LogicalPlan::Filter { input, predicate } => {
                 if let LogicalPlan::Scan {
-                    table_name, alias, ..
+                    table_name,
+                    alias,
+                    sample: None,
+                    ..
                 } = input.as_ref()
                 {
                     if let Some(planned) =
                         self.plan_index_scan(table_name, alias.as_deref(), predicate)?
                     {
+                        check_columns(predicate, &planned.schema)?;
                         reject_blob_predicate(predicate, &planned.schema)?;
                         return Ok(planned);
                     }
                 }
                 let input_planned = self.plan_node(input)?;
+                check_columns(predicate, &input_planned.schema)?;
                 reject_blob_predicate(predicate, &input_planned.schema)?;
                 let schema = input_planned.schema.clone();
                 let operator = Box::new(Filter::new(
@@ -630,46 +2097,125 @@ impl<'a> PhysicalPlanner<'a> {
                 aliases,
             } => {
                 let input_planned = self.plan_node(input)?;
-                let output_schema =
-                    build_projection_schema(expressions, aliases.as_ref(), &input_planned.schema)?;
-                let operator = Box::new(Projection::new(
-                    input_planned.operator,
-                    expressions.clone(),
-                    input_planned.schema,
-                ));
+                plan_projection(input_planned, expressions, aliases.as_ref())
+            }
+            LogicalPlan::Sort { input, sort_exprs } => self.plan_sorted(input, sort_exprs, &[]),
+            LogicalPlan::DistinctOn { input, on } => match input.as_ref() {
+                LogicalPlan::Sort { input, sort_exprs } => self.plan_sorted(input, sort_exprs, on),
+                _ => {
+                    let planned = self.plan_node(input)?;
+                    plan_distinct_on(planned, on)
+                }
+            },
+            LogicalPlan::Limit {
+                input,
+                offset,
+                limit,
+            } => {
+                let planned = self.plan_node(input)?;
+                let operator = Box::new(Limit::new(planned.operator, offset.unwrap_or(0), *limit));
                 Ok(PlannedOperator {
                     operator,
-                    schema: output_schema,
+                    schema: planned.schema,
                 })
             }
+            LogicalPlan::Aggregate {
+                input,
+                group_by,
+                aggregates,
+            } => {
+                if let Some(table_name) = unfiltered_count_table(input, group_by, aggregates)
+                    .filter(|_| !wal::current_txn_has_snapshot())
+                {
+                    let table = self
+                        .catalog
+                        .table(table_name)
+                        .ok_or_else(|| ExecutionError::TableNotFound(table_name.to_string()))?;
+                    let schema = build_aggregate_schema(group_by, aggregates, &table.schema);
+                    let operator = Box::new(
+                        CountRows::new(table.heap.clone(), table_name)
+                            .with_schema_version(table.schema_version()),
+                    );
+                    return Ok(PlannedOperator { operator, schema });
+                }
+                let planned = self.plan_node(input)?;
+                for expr in group_by
+                    .iter()
+                    .chain(aggregates.iter().flat_map(|aggregate| &aggregate.args))
+                {
+                    check_columns(expr, &planned.schema)?;
+                }
+                let schema = build_aggregate_schema(group_by, aggregates, &planned.schema);
+                let operator = Box::new(Aggregate::new(
+                    planned.operator,
+                    group_by.clone(),
+                    aggregates.clone(),
+                    planned.schema,
+                ));
+                Ok(PlannedOperator { operator, schema })
+            }
             LogicalPlan::Join {
                 left,
                 right,
                 join_type,
                 condition,
             } => {
-                if *join_type != JoinType::Inner {
+                if *join_type == JoinType::Semi {
+                    return self.plan_semi_join(left, right, condition.as_ref());
+                }
+                if !matches!(join_type, JoinType::Inner | JoinType::Left) {
                     return Err(ExecutionError::UnsupportedPlan(format!(
-                        "only inner joins are supported, found {}",
+                        "only inner and left joins are supported, found {}",
                         join_type
                     )));
                 }
                 let predicate = condition.clone().ok_or_else(|| {
-                    ExecutionError::UnsupportedPlan("inner join requires condition".to_string())
+                    ExecutionError::UnsupportedPlan(format!(
+                        "{} join requires condition",
+                        join_type.to_string().to_lowercase()
+                    ))
                 })?;
                 let left_planned = self.plan_node(left)?;
                 let right_planned = self.plan_node(right)?;
-                let mut fields = left_planned.schema.fields.clone();
-                fields.extend(right_planned.schema.fields.clone());
-                let output_schema = Schema::new(fields);
+                let output_schema =
+                    join_schema(&left_planned.schema, &right_planned.schema, *join_type);
+                check_columns(&predicate, &output_schema)?;
                 reject_blob_predicate(&predicate, &output_schema)?;
-                let operator = Box::new(NestedLoopJoin::new(
-                    left_planned.operator,
-                    right_planned.operator,
-                    predicate,
-                    left_planned.schema,
-                    right_planned.schema,
-                ));
+                let key = equijoin_key(&predicate, &left_planned.schema, &right_planned.schema);
+                let operator: Box<dyn PhysicalOperator> = match key {
+                    Some((left_key, right_key)) => {
+                        let build_left = matches!(
+                            (self.estimate_rows(left), self.estimate_rows(right)),
+                            (Some(left_rows), Some(right_rows)) if left_rows < right_rows
+                        );
+                        Box::new(
+                            HashJoin::new(
+                                left_planned.operator,
+                                right_planned.operator,
+                                predicate,
+                                *join_type,
+                                left_planned.schema,
+                                right_planned.schema,
+                                left_key,
+                                right_key,
+                            )
+                            .with_build_left(build_left)
+                            .with_work_mem(self.work_mem)
+                            .with_temp_usage(self.temp_usage.clone()),
+                        )
+                    }
+                    None => Box::new(
+                        NestedLoopJoin::new(
+                            left_planned.operator,
+                            self.plan_rescanned(right_planned.operator),
+                            predicate,
+                            *join_type,
+                            left_planned.schema,
+                            right_planned.schema,
+                        )
+                        .with_work_mem(self.work_mem),
+                    ),
+                };
                 Ok(PlannedOperator {
                     operator,
                     schema: output_schema,
@@ -678,6 +2224,7 @@ impl<'a> PhysicalPlanner<'a> {
             LogicalPlan::Update {
                 table_name,
                 assignments,
+                from,
                 filter,
                 ..
             } => {
@@ -685,16 +2232,41 @@ impl<'a> PhysicalPlanner<'a> {
                     .catalog
                     .table(table_name)
                     .ok_or_else(|| ExecutionError::TableNotFound(table_name.clone()))?;
-                let operator = Box::new(Update::new(
-                    table.clone(),
-                    assignments.clone(),
-                    filter.clone(),
-                ));
+                let mut update = Update::new(table.clone(), assignments.clone(), filter.clone());
+                if let Some(source) = from {
+                    let source = self.plan_node(source)?;
+                    update = update.with_source(source.operator, source.schema);
+                } else if let Some(scan) = self.plan_dml_scan(table_name, filter.as_ref())? {
+                    update = update.with_scan(scan);
+                }
+                let operator = Box::new(update);
                 Ok(PlannedOperator {
                     operator,
                     schema: table.schema.clone(),
                 })
             }
+            LogicalPlan::Delete {
+                table_name,
+                using,
+                filter,
+                ..
+            } => {
+                let table = self
+                    .catalog
+                    .table(table_name)
+                    .ok_or_else(|| ExecutionError::TableNotFound(table_name.clone()))?;
+                let mut delete = Delete::new(table.clone(), filter.clone());
+                if let Some(source) = using {
+                    let source = self.plan_node(source)?;
+                    delete = delete.with_source(source.operator, source.schema);
+                } else if let Some(scan) = self.plan_dml_scan(table_name, filter.as_ref())? {
+                    delete = delete.with_scan(scan);
+                }
+                Ok(PlannedOperator {
+                    operator: Box::new(delete),
+                    schema: plan.schema(),
+                })
+            }
             _ => Err(ExecutionError::UnsupportedPlan(format!(
                 "logical plan {:?} is not supported in execution",
                 plan
@@ -702,29 +2274,392 @@ impl<'a> PhysicalPlanner<'a> {
         }
     }
 
+    /// Plans `key IN (subquery)`. When `key` is an indexed column of a
+    /// scanned table the index is probed with each value the subquery
+    /// returns; otherwise the subquery is read into a hash set that the
+    /// left input is checked against.
+    fn plan_semi_join(
+        &self,
+        left: &LogicalPlan,
+        right: &LogicalPlan,
+        condition: Option<&Expr>,
+    ) -> ExecutionResult<PlannedOperator> {
+        let Some(
+            condition @ Expr::BinaryOp {
+                left: key,
+                op: BinaryOperator::Eq,
+                ..
+            },
+        ) = condition
+        else {
+            return Err(ExecutionError::UnsupportedPlan(
+                "semi join requires an equality condition".to_string(),
+            ));
+        };
+        let right_planned = self.plan_node(right)?;
+        if right_planned.schema.fields.len() != 1 {
+            return Err(ExecutionError::Schema(
+                "subquery in IN must return exactly one column".to_string(),
+            ));
+        }
+        if let (
+            LogicalPlan::Scan {
+                table_name,
+                alias,
+                sample: None,
+                ..
+            },
+            Expr::Column {
+                table: column_table,
+                name,
+            },
+        ) = (left, key.as_ref())
+        {
+            let table = self
+                .catalog
+                .table(table_name)
+                .ok_or_else(|| ExecutionError::TableNotFound(table_name.clone()))?;
+            if column_matches(column_table.as_deref(), &table.name, alias.as_deref()) {
+                if let Some(index) = table
+                    .index_for_column(name)
+                    .filter(|index| !index.folds_keys() && !wal::current_txn_has_snapshot())
+                {
+                    let schema = apply_alias(&table.schema, alias.as_deref());
+                    check_columns(key, &schema)?;
+                    let operator = IndexSemiJoin::new(
+                        table.heap.clone(),
+                        schema.clone(),
+                        index.index.clone(),
+                        right_planned.operator,
+                        condition.clone(),
+                    )
+                    .with_schema_version(table.schema_version())
+                    .with_index_name(index.name.clone());
+                    return Ok(PlannedOperator {
+                        operator: Box::new(operator),
+                        schema,
+                    });
+                }
+            }
+        }
+        let left_planned = self.plan_node(left)?;
+        check_columns(key, &left_planned.schema)?;
+        reject_blob_predicate(key, &left_planned.schema)?;
+        let schema = left_planned.schema.clone();
+        let operator = Box::new(HashSemiJoin::new(
+            left_planned.operator,
+            right_planned.operator,
+            key.as_ref().clone(),
+            left_planned.schema,
+            condition.clone(),
+        ));
+        Ok(PlannedOperator { operator, schema })
+    }
+
+    /// Plans an index scan for the most selective conjunct of `predicate`
+    /// an index can answer. The scan checks the remaining conjuncts on each
+    /// row it fetches.
     fn plan_index_scan(
         &self,
         table_name: &str,
         alias: Option<&str>,
         predicate: &Expr,
     ) -> ExecutionResult<Option<PlannedOperator>> {
+        // indexes hold only the latest rows, which a snapshot may not see
+        if wal::current_txn_has_snapshot() {
+            return Ok(None);
+        }
         let table = self
             .catalog
             .table(table_name)
             .ok_or_else(|| ExecutionError::TableNotFound(table_name.to_string()))?;
-        let (index, index_predicate) = match extract_index_predicate(predicate, table, alias)? {
-            Some(info) => info,
-            None => return Ok(None),
+        let conjuncts = split_conjunction(predicate);
+        let mut candidates = Vec::new();
+        for (position, conjunct) in conjuncts.iter().enumerate() {
+            if let Some((index, index_predicates)) = extract_index_union(conjunct, table, alias)? {
+                candidates.push((vec![position], index, index_predicates));
+            }
+        }
+        for index in table.indexes.iter().filter(|index| index.columns.len() > 1) {
+            if let Some((positions, index_predicates)) =
+                extract_composite_predicate(&conjuncts, index, &table.name, alias)
+            {
+                candidates.push((positions, index.clone(), index_predicates));
+            }
+        }
+        let mut best: Option<(Vec<usize>, IndexInfo, Vec<IndexPredicate>, f64)> = None;
+        for (positions, index, index_predicates) in candidates {
+            let rows = index_predicates
+                .iter()
+                .map(|predicate| estimate_index_rows(table, &index, predicate))
+                .sum::<f64>();
+            if best
+                .as_ref()
+                .is_none_or(|(_, _, _, best_rows)| rows < *best_rows)
+            {
+                best = Some((positions, index, index_predicates, rows));
+            }
+        }
+        let Some((positions, index, index_predicates, _)) = best else {
+            return Ok(None);
         };
         let schema = apply_alias(&table.schema, alias);
-        let operator = Box::new(IndexScan::new(
+        let mut scan = IndexScan::union(
             table.heap.clone(),
             schema.clone(),
             index.index.clone(),
-            index_predicate,
-        ));
-        Ok(Some(PlannedOperator { operator, schema }))
+            index_predicates,
+        )
+        .with_schema_version(table.schema_version())
+        .with_index_name(index.name.clone());
+        // a folded key also matches values that differ in case, so the
+        // conjuncts it came from stay to recheck them
+        let residual = conjuncts
+            .into_iter()
+            .enumerate()
+            .filter(|(other, _)| !positions.contains(other) || index.folds_keys())
+            .map(|(_, conjunct)| conjunct.clone())
+            .reduce(|left, right| Expr::BinaryOp {
+                left: Box::new(left),
+                op: BinaryOperator::And,
+                right: Box::new(right),
+            });
+        if let Some(residual) = residual {
+            scan = scan.with_residual(residual);
+        }
+        Ok(Some(PlannedOperator {
+            operator: Box::new(scan),
+            schema,
+        }))
+    }
+
+    /// The index scan an UPDATE or DELETE finds its rows with, when an
+    /// index can answer part of its filter.
+    fn plan_dml_scan(
+        &self,
+        table_name: &str,
+        filter: Option<&Expr>,
+    ) -> ExecutionResult<Option<Box<dyn PhysicalOperator>>> {
+        let Some(filter) = filter else {
+            return Ok(None);
+        };
+        Ok(self
+            .plan_index_scan(table_name, None, filter)?
+            .map(|planned| planned.operator))
+    }
+
+    /// An input read again for each outer row, behind a `Materialize`
+    /// unless rewinding it is already cheap.
+    fn plan_rescanned(&self, operator: Box<dyn PhysicalOperator>) -> Box<dyn PhysicalOperator> {
+        if rewinds_cheaply(operator.as_ref()) {
+            return operator;
+        }
+        Box::new(
+            Materialize::new(operator)
+                .with_work_mem(self.work_mem)
+                .with_temp_usage(self.temp_usage.clone()),
+        )
+    }
+
+    /// Plans `input` sorted by `sort_exprs`, keeping only the first row of
+    /// each `distinct_on` group when that is not empty. Over a projection
+    /// the rows are sorted after projecting, or before it when a key is a
+    /// column the select list leaves out.
+    fn plan_sorted(
+        &self,
+        input: &LogicalPlan,
+        sort_exprs: &[SortExpr],
+        distinct_on: &[Expr],
+    ) -> ExecutionResult<PlannedOperator> {
+        let LogicalPlan::Project {
+            input: project_input,
+            expressions,
+            aliases,
+        } = input
+        else {
+            let planned = self.plan_node(input)?;
+            return plan_distinct_on(self.plan_sort(planned, sort_exprs)?, distinct_on);
+        };
+        let inner = self.plan_node(project_input)?;
+        let output_schema = build_projection_schema(expressions, aliases.as_ref(), &inner.schema)?;
+        if sort_keys_resolve(sort_exprs, &output_schema)
+            && distinct_on
+                .iter()
+                .all(|expr| check_columns(expr, &output_schema).is_ok())
+        {
+            let projected = plan_projection(inner, expressions, aliases.as_ref())?;
+            plan_distinct_on(self.plan_sort(projected, sort_exprs)?, distinct_on)
+        } else {
+            // ORDER BY a column the select list leaves out: sort before
+            // projecting
+            let sorted = self.plan_sort(inner, sort_exprs)?;
+            let distinct = plan_distinct_on(sorted, distinct_on)?;
+            plan_projection(distinct, expressions, aliases.as_ref())
+        }
+    }
+
+    fn plan_sort(
+        &self,
+        input: PlannedOperator,
+        sort_exprs: &[SortExpr],
+    ) -> ExecutionResult<PlannedOperator> {
+        for sort_expr in sort_exprs {
+            check_columns(&sort_expr.expr, &input.schema)?;
+        }
+        let operator = Box::new(
+            Sort::new(input.operator, sort_exprs.to_vec(), input.schema.clone())
+                .with_work_mem(self.work_mem)
+                .with_temp_usage(self.temp_usage.clone()),
+        );
+        Ok(PlannedOperator {
+            operator,
+            schema: input.schema,
+        })
+    }
+}
+
+/// Whether rewinding `operator` rereads rows it holds or a table's pages,
+/// rather than running a join or another plan again.
+fn rewinds_cheaply(operator: &dyn PhysicalOperator) -> bool {
+    let any = operator.as_any();
+    if any.is::<SeqScan>()
+        || any.is::<IndexScan>()
+        || any.is::<Values>()
+        || any.is::<SingleRow>()
+        || any.is::<Sort>()
+        || any.is::<Aggregate>()
+        || any.is::<Materialize>()
+    {
+        return true;
+    }
+    (any.is::<Filter>() || any.is::<Projection>() || any.is::<Limit>() || any.is::<DistinctOn>())
+        && operator.children().into_iter().all(rewinds_cheaply)
+}
+
+/// Row count assumed for tables that were never analyzed.
+const DEFAULT_ROW_ESTIMATE: usize = 1000;
+
+/// Most combinations of `IN` values a composite index scan probes; past it
+/// the remaining leading columns are left for the filter.
+const MAX_INDEX_PREFIXES: usize = 64;
+
+/// Rows an index predicate is expected to return. An equality on a whole
+/// unique key matches at most one row. Fixing the leading key columns to
+/// one value keeps the analyzed rows over the distinct values of those
+/// columns, or a tenth of the rows per column when the index was never
+/// analyzed; a range on the next column then keeps a quarter when closed
+/// and a third when open.
+fn estimate_index_rows(table: &TableInfo, index: &IndexInfo, predicate: &IndexPredicate) -> f64 {
+    let stats = table.stats.stats();
+    let rows = stats
+        .as_ref()
+        .map_or(DEFAULT_ROW_ESTIMATE, |stats| stats.row_count) as f64;
+    let (fixed, lower, upper) = match (&predicate.lower, &predicate.upper) {
+        (Some((IndexKey::Composite(low), _)), upper) => {
+            let high = match upper {
+                Some((IndexKey::Composite(high), _)) => high.as_slice(),
+                _ => &[],
+            };
+            let fixed = low.iter().zip(high).take_while(|(l, h)| l == h).count();
+            let fixed = if predicate.is_equality() {
+                low.len()
+            } else {
+                fixed
+            };
+            // a NULL past the fixed columns only skips the NULL keys
+            let lower = low.get(fixed).is_some_and(|key| *key != IndexKey::Null);
+            (fixed, lower, high.len() > fixed)
+        }
+        _ if predicate.is_equality() => (1, false, false),
+        _ => (
+            0,
+            predicate.lower_bound().is_some(),
+            predicate.upper.is_some(),
+        ),
+    };
+    if predicate.is_equality()
+        && fixed == index.columns.len()
+        && index.unique
+        && !predicate.is_null()
+    {
+        return rows.min(1.0);
+    }
+    let distinct = stats
+        .as_ref()
+        .and_then(|stats| stats.distinct_keys.get(&index.name))
+        .and_then(|counts| counts.get(fixed.checked_sub(1)?))
+        .filter(|&&distinct| distinct > 0);
+    let rows = match distinct {
+        Some(&distinct) => rows / distinct as f64,
+        None => rows * 0.1f64.powi(fixed as i32),
+    };
+    match (lower, upper) {
+        (true, true) => rows * 0.25,
+        (false, false) if fixed > 0 => rows,
+        _ => rows / 3.0,
+    }
+}
+
+fn split_conjunction(expr: &Expr) -> Vec<&Expr> {
+    match expr {
+        Expr::BinaryOp {
+            left,
+            op: BinaryOperator::And,
+            right,
+        } => {
+            let mut conjuncts = split_conjunction(left);
+            conjuncts.extend(split_conjunction(right));
+            conjuncts
+        }
+        other => vec![other],
+    }
+}
+
+fn plan_projection(
+    input: PlannedOperator,
+    expressions: &[Expr],
+    aliases: Option<&Vec<String>>,
+) -> ExecutionResult<PlannedOperator> {
+    for expr in expressions {
+        check_columns(expr, &input.schema)?;
+    }
+    let schema = build_projection_schema(expressions, aliases, &input.schema)?;
+    let operator = Box::new(Projection::new(
+        input.operator,
+        expressions.to_vec(),
+        input.schema,
+    ));
+    Ok(PlannedOperator { operator, schema })
+}
+
+/// Wraps `input` in a `DistinctOn` keeping the first row of each `on`
+/// group; returns it unchanged when `on` is empty.
+fn plan_distinct_on(input: PlannedOperator, on: &[Expr]) -> ExecutionResult<PlannedOperator> {
+    if on.is_empty() {
+        return Ok(input);
     }
+    for expr in on {
+        check_columns(expr, &input.schema)?;
+    }
+    let operator = Box::new(DistinctOn::new(
+        input.operator,
+        on.to_vec(),
+        input.schema.clone(),
+    ));
+    Ok(PlannedOperator {
+        operator,
+        schema: input.schema,
+    })
+}
+
+fn sort_keys_resolve(sort_exprs: &[SortExpr], schema: &Schema) -> bool {
+    sort_exprs.iter().all(|sort_expr| {
+        sort_expr
+            .expr
+            .column_refs()
+            .into_iter()
+            .all(|(table, name)| resolve_column_index(schema, table, name).is_ok())
+    })
 }
 
 struct PlannedOperator {
@@ -761,6 +2696,7 @@ fn build_projection_schema(
     aliases: Option<&Vec<String>>,
     input_schema: &Schema,
 ) -> ExecutionResult<Schema> {
+    let qualify = spans_tables(input_schema);
     let mut fields = Vec::new();
     for (index, expr) in expressions.iter().enumerate() {
         match expr {
@@ -770,7 +2706,7 @@ fn build_projection_schema(
                         .fields
                         .iter()
                         .filter(|field| field.visible)
-                        .cloned(),
+                        .map(|field| wildcard_field(field, qualify)),
                 );
             }
             Expr::QualifiedWildcard { table } => {
@@ -792,7 +2728,7 @@ fn build_projection_schema(
                             .unwrap_or(false);
                     if table_matches {
                         matched = true;
-                        fields.push(field.clone());
+                        fields.push(wildcard_field(field, qualify));
                     }
                 }
                 if !matched {
@@ -802,27 +2738,17 @@ fn build_projection_schema(
                     )));
                 }
             }
-            Expr::Literal(crate::expr::LiteralValue::Blob(_)) => {
-                let name = aliases
-                    .and_then(|aliases| aliases.get(index).cloned())
-                    .unwrap_or_else(|| expr.to_string());
-                fields.push(Field {
-                    name,
-                    table: None,
-                    data_type: DataType::Blob,
-                    nullable: true,
-                    visible: true,
-                });
-            }
             _ => {
                 let name = aliases
                     .and_then(|aliases| aliases.get(index).cloned())
                     .unwrap_or_else(|| expr.to_string());
+                let (data_type, nullable) =
+                    projected_type(expr, input_schema).unwrap_or((DataType::Unknown, true));
                 fields.push(Field {
                     name,
                     table: None,
-                    data_type: DataType::Text,
-                    nullable: true,
+                    data_type,
+                    nullable,
                     visible: true,
                 });
             }
@@ -831,6 +2757,278 @@ fn build_projection_schema(
     Ok(Schema::new(fields))
 }
 
+/// Whether the visible columns of `schema` come from more than one table,
+/// as they do after a join.
+fn spans_tables(schema: &Schema) -> bool {
+    let mut tables = schema
+        .fields
+        .iter()
+        .filter(|field| field.visible)
+        .filter_map(|field| field.table.as_deref());
+    let Some(first) = tables.next() else {
+        return false;
+    };
+    tables.any(|table| !table.eq_ignore_ascii_case(first))
+}
+
+/// A column `*` expands to, named `table.column` when `qualify` is set so
+/// that the same column name from both sides of a join stays distinct.
+fn wildcard_field(field: &Field, qualify: bool) -> Field {
+    let mut field = field.clone();
+    if let (true, Some(table)) = (qualify, field.table.as_deref()) {
+        if !field.name.contains('.') {
+            field.name = format!("{}.{}", table, field.name);
+        }
+    }
+    field
+}
+
+/// The table of an aggregate that is exactly `COUNT(*)` over an unfiltered
+/// scan, which [`CountRows`] answers without scanning.
+fn unfiltered_count_table<'a>(
+    input: &'a LogicalPlan,
+    group_by: &[Expr],
+    aggregates: &[AggregateExpr],
+) -> Option<&'a str> {
+    let LogicalPlan::Scan {
+        table_name,
+        sample: None,
+        ..
+    } = input
+    else {
+        return None;
+    };
+    match aggregates {
+        [AggregateExpr {
+            func: AggregateFunction::Count,
+            args,
+            ..
+        }] if group_by.is_empty() && matches!(args.as_slice(), [] | [Expr::Wildcard]) => {
+            Some(table_name)
+        }
+        _ => None,
+    }
+}
+
+/// Group key columns followed by one column per aggregate, named by
+/// [`AggregateExpr::output_name`]. Plain column keys keep their input field
+/// so qualified references such as `u.name` still resolve above the
+/// aggregate.
+fn build_aggregate_schema(
+    group_by: &[Expr],
+    aggregates: &[AggregateExpr],
+    input_schema: &Schema,
+) -> Schema {
+    let mut fields = Vec::new();
+    for expr in group_by {
+        if let Expr::Column { table, name } = expr {
+            if let Ok(index) = resolve_column_index(input_schema, table.as_deref(), name) {
+                fields.push(input_schema.fields[index].clone());
+                continue;
+            }
+        }
+        let (data_type, nullable) =
+            projected_type(expr, input_schema).unwrap_or((DataType::Unknown, true));
+        fields.push(Field {
+            name: expr.to_string(),
+            table: None,
+            data_type,
+            nullable,
+            visible: true,
+        });
+    }
+    for aggregate in aggregates {
+        let arg_type = aggregate
+            .args
+            .first()
+            .and_then(|arg| projected_type(arg, input_schema))
+            .map(|(data_type, _)| data_type);
+        let (data_type, nullable) = match aggregate.func {
+            AggregateFunction::Count => (DataType::Integer, false),
+            AggregateFunction::Avg => (DataType::Real, true),
+            AggregateFunction::Sum => match arg_type {
+                Some(DataType::Real) => (DataType::Real, true),
+                _ => (DataType::BigInt, true),
+            },
+            AggregateFunction::Min | AggregateFunction::Max => {
+                (arg_type.unwrap_or(DataType::Text), true)
+            }
+        };
+        fields.push(Field {
+            name: aggregate.output_name(),
+            table: None,
+            data_type,
+            nullable,
+            visible: true,
+        });
+    }
+    Schema::new(fields)
+}
+
+/// Type and nullability of a projected expression, where it can be inferred
+/// without evaluating it. Other expressions are reported as unknown.
+fn projected_type(expr: &Expr, input_schema: &Schema) -> Option<(DataType, bool)> {
+    match expr {
+        Expr::Column { table, name } => {
+            let index = resolve_column_index(input_schema, table.as_deref(), name).ok()?;
+            let field = &input_schema.fields[index];
+            Some((field.data_type.clone(), field.nullable))
+        }
+        Expr::Cast { expr, target_type } => {
+            let nullable = projected_type(expr, input_schema).is_none_or(|(_, nullable)| nullable);
+            Some((target_type.clone(), nullable))
+        }
+        Expr::Literal(literal) => {
+            let data_type = match literal {
+                LiteralValue::Null => return None,
+                LiteralValue::Integer(_) => DataType::Integer,
+                LiteralValue::Float(_) => DataType::Real,
+                LiteralValue::String(_) => DataType::Text,
+                LiteralValue::Boolean(_) => DataType::Boolean,
+                LiteralValue::Blob(_) => DataType::Blob,
+            };
+            Some((data_type, true))
+        }
+        Expr::BinaryOp { left, op, right } => {
+            let left = projected_type(left, input_schema);
+            let right = projected_type(right, input_schema);
+            let nullable = [&left, &right]
+                .iter()
+                .any(|side| side.as_ref().is_none_or(|(_, nullable)| *nullable));
+            let data_type = match op {
+                BinaryOperator::Plus
+                | BinaryOperator::Minus
+                | BinaryOperator::Multiply
+                | BinaryOperator::Modulo => numeric_result_type(&left?.0, &right?.0)?,
+                BinaryOperator::Divide => DataType::Real,
+                BinaryOperator::Concat => DataType::Text,
+                BinaryOperator::Eq
+                | BinaryOperator::NotEq
+                | BinaryOperator::Lt
+                | BinaryOperator::LtEq
+                | BinaryOperator::Gt
+                | BinaryOperator::GtEq
+                | BinaryOperator::And
+                | BinaryOperator::Or
+                | BinaryOperator::Like
+                | BinaryOperator::NotLike => DataType::Boolean,
+            };
+            Some((data_type, nullable))
+        }
+        Expr::UnaryOp { op, expr } => {
+            let (data_type, nullable) = projected_type(expr, input_schema)?;
+            match op {
+                UnaryOperator::Not => Some((DataType::Boolean, nullable)),
+                UnaryOperator::Minus | UnaryOperator::Plus => match data_type {
+                    DataType::Timestamp => Some((DataType::BigInt, nullable)),
+                    DataType::Integer | DataType::BigInt | DataType::Real => {
+                        Some((data_type, nullable))
+                    }
+                    _ => None,
+                },
+            }
+        }
+        Expr::IsNull { .. } => Some((DataType::Boolean, false)),
+        Expr::In { .. } | Expr::InSubquery { .. } | Expr::Between { .. } => {
+            Some((DataType::Boolean, true))
+        }
+        Expr::Function { name, args } => match function_column_index(input_schema, expr) {
+            Some(index) => {
+                let field = &input_schema.fields[index];
+                Some((field.data_type.clone(), field.nullable))
+            }
+            None if matches!(name.as_str(), "NEXTVAL" | "CURRVAL") => {
+                Some((DataType::BigInt, false))
+            }
+            None if name == "RANDOM" => Some((DataType::Real, false)),
+            None if name == "CURRENT_TIMESTAMP" => Some((DataType::Timestamp, false)),
+            // the first argument that is not NULL, so NULL only when every
+            // argument can be
+            None if name == "COALESCE" => {
+                let args: Vec<_> = args
+                    .iter()
+                    .map(|arg| projected_type(arg, input_schema))
+                    .collect();
+                let data_type = args.iter().flatten().next()?.0.clone();
+                let nullable = args
+                    .iter()
+                    .all(|arg| arg.as_ref().is_none_or(|(_, nullable)| *nullable));
+                Some((data_type, nullable))
+            }
+            None if name == "NULLIF" => {
+                let (data_type, _) = projected_type(args.first()?, input_schema)?;
+                Some((data_type, true))
+            }
+            None => None,
+        },
+        _ => None,
+    }
+}
+
+/// Type of `+`, `-`, `*` or `%` over operands of the given types: REAL if
+/// either is, otherwise the wider integer type. Timestamps take part as
+/// integers.
+fn numeric_result_type(left: &DataType, right: &DataType) -> Option<DataType> {
+    let integer = |data_type: &DataType| {
+        matches!(
+            data_type,
+            DataType::Integer | DataType::BigInt | DataType::Timestamp
+        )
+    };
+    let numeric = |data_type: &DataType| integer(data_type) || *data_type == DataType::Real;
+    if !numeric(left) || !numeric(right) {
+        return None;
+    }
+    Some(if *left == DataType::Real || *right == DataType::Real {
+        DataType::Real
+    } else if *left == DataType::Integer && *right == DataType::Integer {
+        DataType::Integer
+    } else {
+        DataType::BigInt
+    })
+}
+
+/// Joins the plan runs. Matching the target of an UPDATE ... FROM or a
+/// DELETE ... USING against its source rows counts as one.
+fn count_joins(plan: &LogicalPlan) -> usize {
+    match plan {
+        LogicalPlan::Join { left, right, .. } => 1 + count_joins(left) + count_joins(right),
+        LogicalPlan::Filter { input, .. }
+        | LogicalPlan::Project { input, .. }
+        | LogicalPlan::Sort { input, .. }
+        | LogicalPlan::DistinctOn { input, .. }
+        | LogicalPlan::Limit { input, .. }
+        | LogicalPlan::Aggregate { input, .. }
+        | LogicalPlan::Explain { input } => count_joins(input),
+        LogicalPlan::Insert { source, .. } => source.as_deref().map_or(0, count_joins),
+        LogicalPlan::Update { from: source, .. } | LogicalPlan::Delete { using: source, .. } => {
+            source
+                .as_deref()
+                .map_or(0, |source| 1 + count_joins(source))
+        }
+        LogicalPlan::Scan { .. }
+        | LogicalPlan::SingleRow
+        | LogicalPlan::Values { .. }
+        | LogicalPlan::CreateTable { .. }
+        | LogicalPlan::DropTable { .. }
+        | LogicalPlan::Analyze { .. }
+        | LogicalPlan::AlterTableRename { .. }
+        | LogicalPlan::AlterTableRenameColumn { .. }
+        | LogicalPlan::AlterTableAddColumn { .. }
+        | LogicalPlan::AlterTableDropColumn { .. }
+        | LogicalPlan::AlterTableAlterColumnType { .. }
+        | LogicalPlan::AlterTableAddCheck { .. }
+        | LogicalPlan::CreateIndex { .. }
+        | LogicalPlan::CreateSequence { .. }
+        | LogicalPlan::DropSequence { .. }
+        | LogicalPlan::Grant { .. }
+        | LogicalPlan::Revoke { .. }
+        | LogicalPlan::Copy { .. }
+        | LogicalPlan::Transaction { .. }
+        | LogicalPlan::Set { .. } => 0,
+    }
+}
+
 fn index_key_type_for_data_type(data_type: &DataType) -> ExecutionResult<IndexKeyType> {
     match data_type {
         DataType::Integer | DataType::BigInt | DataType::Timestamp => Ok(IndexKeyType::Integer),
@@ -845,6 +3043,36 @@ fn index_key_type_for_data_type(data_type: &DataType) -> ExecutionResult<IndexKe
     }
 }
 
+/// Index predicates answering `predicate`: one range, or one per branch
+/// of an OR whose branches can all use the same index. An OR with any
+/// other branch is left for the filter.
+fn extract_index_union(
+    predicate: &Expr,
+    table: &TableInfo,
+    alias: Option<&str>,
+) -> ExecutionResult<Option<(IndexInfo, Vec<IndexPredicate>)>> {
+    let Expr::BinaryOp {
+        left,
+        op: BinaryOperator::Or,
+        right,
+    } = predicate
+    else {
+        return Ok(extract_index_predicate(predicate, table, alias)?
+            .map(|(index, predicate)| (index, vec![predicate])));
+    };
+    let (Some((index, mut predicates)), Some((right_index, right_predicates))) = (
+        extract_index_union(left, table, alias)?,
+        extract_index_union(right, table, alias)?,
+    ) else {
+        return Ok(None);
+    };
+    if index.name != right_index.name {
+        return Ok(None);
+    }
+    predicates.extend(right_predicates);
+    Ok(Some((index, predicates)))
+}
+
 fn extract_index_predicate(
     predicate: &Expr,
     table: &TableInfo,
@@ -872,7 +3100,21 @@ fn extract_index_predicate(
                 Some(index) => index.clone(),
                 None => return Ok(None),
             };
-            let value = Value::from(literal);
+            let mut value = Value::from(literal);
+            // a comparison with NULL matches no row, which the filter shows
+            if value.is_null() {
+                return Ok(None);
+            }
+            // folded keys find the rows equal under the collation, which
+            // the scan rechecks; they cannot bound a range of stored values
+            if let Some(collation) = index.collations.first().copied().flatten() {
+                if op != BinaryOperator::Eq {
+                    return Ok(None);
+                }
+                if let Value::String(text) = &value {
+                    value = Value::String(collation.fold(text));
+                }
+            }
             let key_type = *index
                 .key_types
                 .first()
@@ -881,14 +3123,15 @@ fn extract_index_predicate(
                 Ok(key) => key,
                 Err(_) => return Ok(None),
             };
+            // NULL keys sort first, so ranges open below start just past them
             let predicate = match op {
                 BinaryOperator::Eq => IndexPredicate::equality(key),
                 BinaryOperator::Lt => IndexPredicate {
-                    lower: None,
+                    lower: Some((IndexKey::Null, false)),
                     upper: Some((key, false)),
                 },
                 BinaryOperator::LtEq => IndexPredicate {
-                    lower: None,
+                    lower: Some((IndexKey::Null, false)),
                     upper: Some((key, true)),
                 },
                 BinaryOperator::Gt => IndexPredicate {
@@ -920,7 +3163,8 @@ fn extract_index_predicate(
                 return Ok(None);
             }
             let index = match table.index_for_column(column_name) {
-                Some(index) => index.clone(),
+                Some(index) if !index.folds_keys() => index.clone(),
+                Some(_) => return Ok(None),
                 None => return Ok(None),
             };
             let low_literal = match low.as_ref() {
@@ -933,6 +3177,9 @@ fn extract_index_predicate(
             };
             let low_value = Value::from(low_literal);
             let high_value = Value::from(high_literal);
+            if low_value.is_null() || high_value.is_null() {
+                return Ok(None);
+            }
             let key_type = *index
                 .key_types
                 .first()
@@ -953,10 +3200,269 @@ fn extract_index_predicate(
                 },
             )))
         }
+        Expr::IsNull { expr, negated } => {
+            let (column_table, column_name) = match expr.as_ref() {
+                Expr::Column { table, name } => (table.as_deref(), name.as_str()),
+                _ => return Ok(None),
+            };
+            if !column_matches(column_table, &table.name, alias) {
+                return Ok(None);
+            }
+            let index = match table.index_for_column(column_name) {
+                Some(index) => index.clone(),
+                None => return Ok(None),
+            };
+            let predicate = if *negated {
+                IndexPredicate {
+                    lower: Some((IndexKey::Null, false)),
+                    upper: None,
+                }
+            } else {
+                IndexPredicate::equality(IndexKey::Null)
+            };
+            Ok(Some((index, predicate)))
+        }
         _ => Ok(None),
     }
 }
 
+/// Builds predicates on a composite `index` from the conjuncts fixing its
+/// leading columns to values, plus any range on the column after them. A
+/// leading column may be fixed to one of an `IN` list instead, giving one
+/// predicate per combination of values. A conjunct on a column past the
+/// first one without an equality cannot use the index. Returns the
+/// positions of the conjuncts the predicates answer.
+fn extract_composite_predicate(
+    conjuncts: &[&Expr],
+    index: &IndexInfo,
+    table_name: &str,
+    alias: Option<&str>,
+) -> Option<(Vec<usize>, Vec<IndexPredicate>)> {
+    let comparisons: Vec<_> = conjuncts
+        .iter()
+        .enumerate()
+        .flat_map(|(position, conjunct)| {
+            column_comparisons(conjunct, table_name, alias)
+                .into_iter()
+                .map(move |(column, op, value)| (position, column, op, value))
+        })
+        .collect();
+    let in_lists: Vec<_> = conjuncts
+        .iter()
+        .enumerate()
+        .filter_map(|(position, conjunct)| {
+            column_in_list(conjunct, table_name, alias)
+                .map(|(column, values)| (position, column, values))
+        })
+        .collect();
+    let mut prefixes = vec![Vec::new()];
+    let mut lower = None;
+    let mut upper = None;
+    let mut used = Vec::new();
+    let mut used_lists = Vec::new();
+    for ((column, key_type), collation) in index
+        .columns
+        .iter()
+        .zip(&index.key_types)
+        .zip(&index.collations)
+    {
+        let key_for = |value: &Value| {
+            let value = match (value, collation) {
+                (Value::String(text), Some(collation)) => Value::String(collation.fold(text)),
+                _ => value.clone(),
+            };
+            IndexKey::from_value(&value, *key_type).ok()
+        };
+        let on_column = comparisons
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, name, _, _))| name.eq_ignore_ascii_case(column));
+        let equality = on_column
+            .clone()
+            .find_map(|(used_at, (_, _, op, value))| match op {
+                BinaryOperator::Eq => key_for(value).map(|key| (used_at, key)),
+                _ => None,
+            });
+        if let Some((used_at, key)) = equality {
+            for prefix in &mut prefixes {
+                prefix.push(key.clone());
+            }
+            used.push(used_at);
+            continue;
+        }
+        let in_list = in_lists.iter().find_map(|(position, name, values)| {
+            if !name.eq_ignore_ascii_case(column) {
+                return None;
+            }
+            let mut keys = values.iter().map(key_for).collect::<Option<Vec<_>>>()?;
+            keys.sort();
+            keys.dedup();
+            Some((*position, keys))
+        });
+        if let Some((position, keys)) = in_list {
+            if prefixes.len() * keys.len() <= MAX_INDEX_PREFIXES {
+                prefixes = prefixes
+                    .iter()
+                    .flat_map(|prefix| {
+                        keys.iter().map(move |key| {
+                            let mut prefix = prefix.clone();
+                            prefix.push(key.clone());
+                            prefix
+                        })
+                    })
+                    .collect();
+                used_lists.push(position);
+                continue;
+            }
+        }
+        // folded keys cannot bound a range of stored values
+        if collation.is_none() {
+            for (used_at, (_, _, op, value)) in on_column {
+                let Some(key) = key_for(value) else {
+                    continue;
+                };
+                match op {
+                    BinaryOperator::Gt | BinaryOperator::GtEq if lower.is_none() => {
+                        lower = Some((key, *op == BinaryOperator::GtEq));
+                    }
+                    BinaryOperator::Lt | BinaryOperator::LtEq if upper.is_none() => {
+                        upper = Some((key, *op == BinaryOperator::LtEq));
+                    }
+                    _ => continue,
+                }
+                used.push(used_at);
+            }
+        }
+        break;
+    }
+    let fixed = !prefixes[0].is_empty();
+    if !fixed && lower.is_none() && upper.is_none() {
+        return None;
+    }
+    let predicates = prefixes
+        .iter()
+        .map(|prefix| {
+            let bound = |last: Option<IndexKey>| {
+                let mut keys = prefix.clone();
+                keys.extend(last);
+                IndexKey::Composite(keys)
+            };
+            // NULL keys sort first, so ranges open below start just past them
+            IndexPredicate {
+                lower: match &lower {
+                    Some((key, inclusive)) => Some((bound(Some(key.clone())), *inclusive)),
+                    None if upper.is_some() => Some((bound(Some(IndexKey::Null)), false)),
+                    None => Some((bound(None), true)),
+                },
+                upper: match &upper {
+                    Some((key, inclusive)) => Some((bound(Some(key.clone())), *inclusive)),
+                    None if fixed => Some((bound(None), true)),
+                    None => None,
+                },
+            }
+        })
+        .collect();
+    // a conjunct is answered once every comparison in it is
+    let positions = (0..conjuncts.len())
+        .filter(|position| {
+            used_lists.contains(position)
+                || (comparisons
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, (at, _, _, _))| at == position)
+                    .all(|(used_at, _)| used.contains(&used_at))
+                    && comparisons.iter().any(|(at, _, _, _)| at == position))
+        })
+        .collect();
+    Some((positions, predicates))
+}
+
+/// The column and values of a `column IN (literal, ...)` conjunct on a
+/// column of the table, unless a value is NULL.
+fn column_in_list<'a>(
+    conjunct: &'a Expr,
+    table_name: &str,
+    alias: Option<&str>,
+) -> Option<(&'a str, Vec<Value>)> {
+    let Expr::In {
+        expr,
+        list,
+        negated: false,
+    } = conjunct
+    else {
+        return None;
+    };
+    let Expr::Column { table, name } = expr.as_ref() else {
+        return None;
+    };
+    if !column_matches(table.as_deref(), table_name, alias) {
+        return None;
+    }
+    let values = list
+        .iter()
+        .map(|item| match item {
+            Expr::Literal(literal) => Some(Value::from(literal)).filter(|value| !value.is_null()),
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>()?;
+    Some((name.as_str(), values))
+}
+
+/// The `column op value` comparisons of `conjunct` on a column of the
+/// table, with the column on the left: one for a comparison with a literal,
+/// two for a `BETWEEN`, none otherwise or when a value is NULL.
+fn column_comparisons<'a>(
+    conjunct: &'a Expr,
+    table_name: &str,
+    alias: Option<&str>,
+) -> Vec<(&'a str, BinaryOperator, Value)> {
+    let comparisons = match conjunct {
+        Expr::BinaryOp { left, op, right } => match (left.as_ref(), right.as_ref()) {
+            (Expr::Column { table, name }, Expr::Literal(literal)) => {
+                vec![(table, name, *op, Value::from(literal))]
+            }
+            (Expr::Literal(literal), Expr::Column { table, name }) => {
+                match flip_comparison_operator(*op) {
+                    Some(op) => vec![(table, name, op, Value::from(literal))],
+                    None => Vec::new(),
+                }
+            }
+            _ => Vec::new(),
+        },
+        Expr::Between {
+            expr,
+            low,
+            high,
+            negated: false,
+        } => match (expr.as_ref(), low.as_ref(), high.as_ref()) {
+            (Expr::Column { table, name }, Expr::Literal(low), Expr::Literal(high)) => vec![
+                (table, name, BinaryOperator::GtEq, Value::from(low)),
+                (table, name, BinaryOperator::LtEq, Value::from(high)),
+            ],
+            _ => Vec::new(),
+        },
+        _ => Vec::new(),
+    };
+    if comparisons.iter().any(|(table, _, op, value)| {
+        value.is_null()
+            || !column_matches(table.as_deref(), table_name, alias)
+            || !matches!(
+                op,
+                BinaryOperator::Eq
+                    | BinaryOperator::Lt
+                    | BinaryOperator::LtEq
+                    | BinaryOperator::Gt
+                    | BinaryOperator::GtEq
+            )
+    }) {
+        return Vec::new();
+    }
+    comparisons
+        .into_iter()
+        .map(|(_, name, op, value)| (name.as_str(), op, value))
+        .collect()
+}
+
 fn column_matches(column_table: Option<&str>, table_name: &str, alias: Option<&str>) -> bool {
     match column_table {
         None => true,
@@ -1012,7 +3518,12 @@ fn expr_uses_blob(expr: &Expr, schema: &Schema) -> ExecutionResult<bool> {
             }
             Ok(false)
         }
-        Expr::Wildcard | Expr::QualifiedWildcard { .. } => Ok(false),
+        Expr::InSubquery { expr, .. } => expr_uses_blob(expr, schema),
+        Expr::Wildcard
+        | Expr::QualifiedWildcard { .. }
+        | Expr::Default
+        | Expr::Parameter(_)
+        | Expr::ScalarSubquery(_) => Ok(false),
     }
 }
 
@@ -1050,23 +3561,56 @@ fn flip_comparison_operator(op: BinaryOperator) -> Option<BinaryOperator> {
     }
 }
 
+/// Mirrors the value/type pairs the tuple encoder accepts.
+fn value_matches_type(value: &Value, data_type: &DataType) -> bool {
+    matches!(
+        (data_type, value),
+        (DataType::Integer, Value::Integer(_))
+            | (DataType::BigInt, Value::Integer(_))
+            | (DataType::Timestamp, Value::Integer(_) | Value::Timestamp(_))
+            | (DataType::Real, Value::Float(_))
+            | (DataType::Boolean, Value::Boolean(_))
+            | (DataType::Text, Value::String(_))
+            | (DataType::Blob, Value::Blob(_))
+    )
+}
+
 fn apply_assignments(
     tuple: &Tuple,
     schema: &Schema,
     assignments: &[Assignment],
 ) -> ExecutionResult<Tuple> {
-    let mut values = tuple.values().to_vec();
+    apply_joined_assignments(tuple, schema, tuple, schema, assignments)
+}
+
+fn join_tuples(target: &Tuple, source: &Tuple) -> Tuple {
+    let mut values = target.values().to_vec();
+    values.extend(source.values().iter().cloned());
+    Tuple::new(values)
+}
+
+/// Assigns into `target`, evaluating the new values against `context`.
+fn apply_joined_assignments(
+    target: &Tuple,
+    target_schema: &Schema,
+    context: &Tuple,
+    context_schema: &Schema,
+    assignments: &[Assignment],
+) -> ExecutionResult<Tuple> {
+    let mut values = target.values().to_vec();
     for assignment in assignments {
-        let index = schema.field_index(&assignment.column).ok_or_else(|| {
-            ExecutionError::Schema(format!("column {} not found", assignment.column))
-        })?;
+        let index = target_schema
+            .field_index(&assignment.column)
+            .ok_or_else(|| {
+                ExecutionError::Schema(format!("column {} not found", assignment.column))
+            })?;
         if index >= values.len() {
             return Err(ExecutionError::Schema(format!(
                 "column index {} out of range",
                 index
             )));
         }
-        values[index] = evaluate_expr(&assignment.value, tuple, schema)?;
+        values[index] = evaluate_expr(&assignment.value, context, context_schema)?;
     }
     Ok(Tuple::new(values))
 }
@@ -0,0 +1,584 @@
+use crate::execution::operator::{ExecutionError, ExecutionResult};
+use crate::execution::planner::{Catalog, TableInfo};
+use crate::expr::Expr;
+use crate::logical_plan::{
+    AggregateFunction, CopyDirection, InsertConflict, JoinType, LogicalPlan, Privilege,
+};
+use std::collections::{BTreeSet, HashMap};
+
+/// Stored form of one grant. `columns` is `None` for a grant on the whole
+/// table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GrantDef {
+    pub role: String,
+    pub table: String,
+    pub privilege: Privilege,
+    pub columns: Option<Vec<String>>,
+}
+
+/// Columns one grant covers: every column, or the listed ones.
+#[derive(Debug, Clone, Default)]
+struct GrantScope {
+    all_columns: bool,
+    columns: BTreeSet<String>,
+}
+
+/// Table and column privileges granted to roles (`GRANT` / `REVOKE`). Role,
+/// table and column names are matched case-insensitively.
+#[derive(Debug, Clone, Default)]
+pub struct Privileges {
+    grants: HashMap<(String, String, Privilege), GrantScope>,
+}
+
+impl Privileges {
+    pub fn grant(&mut self, grant: GrantDef) {
+        let scope = self
+            .grants
+            .entry(grant_key(&grant.role, &grant.table, grant.privilege))
+            .or_default();
+        match grant.columns {
+            Some(columns) => scope
+                .columns
+                .extend(columns.iter().map(|column| column.to_lowercase())),
+            None => scope.all_columns = true,
+        }
+    }
+
+    /// Revokes a whole-table grant together with its column grants, or
+    /// only the listed columns.
+    pub fn revoke(&mut self, grant: &GrantDef) {
+        let key = grant_key(&grant.role, &grant.table, grant.privilege);
+        match &grant.columns {
+            Some(columns) => {
+                if let Some(scope) = self.grants.get_mut(&key) {
+                    for column in columns {
+                        scope.columns.remove(&column.to_lowercase());
+                    }
+                    if !scope.all_columns && scope.columns.is_empty() {
+                        self.grants.remove(&key);
+                    }
+                }
+            }
+            None => {
+                self.grants.remove(&key);
+            }
+        }
+    }
+
+    /// True when `role` holds `privilege` on `column` of `table`, or on any
+    /// column of it when `column` is `None`.
+    pub fn allows(
+        &self,
+        role: &str,
+        table: &str,
+        privilege: Privilege,
+        column: Option<&str>,
+    ) -> bool {
+        self.grants
+            .get(&grant_key(role, table, privilege))
+            .is_some_and(|scope| {
+                scope.all_columns
+                    || match column {
+                        Some(column) => scope.columns.contains(&column.to_lowercase()),
+                        None => !scope.columns.is_empty(),
+                    }
+            })
+    }
+
+    /// Every grant, ordered by role, table and privilege.
+    pub fn definitions(&self) -> Vec<GrantDef> {
+        let mut defs = Vec::new();
+        for ((role, table, privilege), scope) in &self.grants {
+            let def = |columns| GrantDef {
+                role: role.clone(),
+                table: table.clone(),
+                privilege: *privilege,
+                columns,
+            };
+            if scope.all_columns {
+                defs.push(def(None));
+            }
+            if !scope.columns.is_empty() {
+                defs.push(def(Some(scope.columns.iter().cloned().collect())));
+            }
+        }
+        defs.sort_by(|left, right| {
+            (
+                &left.role,
+                &left.table,
+                left.privilege.to_string(),
+                &left.columns,
+            )
+                .cmp(&(
+                    &right.role,
+                    &right.table,
+                    right.privilege.to_string(),
+                    &right.columns,
+                ))
+        });
+        defs
+    }
+
+    pub(crate) fn drop_table(&mut self, table: &str) {
+        let table = table.to_lowercase();
+        self.grants.retain(|(_, granted, _), _| *granted != table);
+    }
+
+    pub(crate) fn rename_table(&mut self, table: &str, new_name: &str) {
+        let table = table.to_lowercase();
+        let renamed = self
+            .grants
+            .keys()
+            .filter(|(_, granted, _)| *granted == table)
+            .cloned()
+            .collect::<Vec<_>>();
+        for key in renamed {
+            if let Some(scope) = self.grants.remove(&key) {
+                self.grants
+                    .insert(grant_key(&key.0, new_name, key.2), scope);
+            }
+        }
+    }
+
+    pub(crate) fn rename_column(&mut self, table: &str, old_name: &str, new_name: &str) {
+        let table = table.to_lowercase();
+        let old_name = old_name.to_lowercase();
+        for ((_, granted, _), scope) in &mut self.grants {
+            if *granted == table && scope.columns.remove(&old_name) {
+                scope.columns.insert(new_name.to_lowercase());
+            }
+        }
+    }
+
+    pub(crate) fn drop_column(&mut self, table: &str, column: &str) {
+        let table = table.to_lowercase();
+        let column = column.to_lowercase();
+        self.grants.retain(|(_, granted, _), scope| {
+            if *granted == table {
+                scope.columns.remove(&column);
+            }
+            scope.all_columns || !scope.columns.is_empty()
+        });
+    }
+}
+
+fn grant_key(role: &str, table: &str, privilege: Privilege) -> (String, String, Privilege) {
+    (role.to_lowercase(), table.to_lowercase(), privilege)
+}
+
+/// Checks that `role` holds every privilege `plan` needs. Column references
+/// are resolved against the scanned tables, so a role granted SELECT on some
+/// columns of a table can read only those. Unknown tables are left for the
+/// planner to report.
+pub(crate) fn check_plan(catalog: &Catalog, role: &str, plan: &LogicalPlan) -> ExecutionResult<()> {
+    PrivilegeCheck { catalog, role }.check(plan)
+}
+
+struct PrivilegeCheck<'a> {
+    catalog: &'a Catalog,
+    role: &'a str,
+}
+
+/// A table read by a query, under its alias if it has one.
+struct ScannedTable<'a> {
+    name: &'a str,
+    alias: Option<&'a str>,
+    table: &'a TableInfo,
+}
+
+impl<'a> PrivilegeCheck<'a> {
+    fn check(&self, plan: &'a LogicalPlan) -> ExecutionResult<()> {
+        match plan {
+            LogicalPlan::Insert {
+                table_name,
+                columns,
+                values,
+                source,
+                returning,
+                on_conflict,
+                ..
+            } => {
+                // OR REPLACE deletes the rows the new ones conflict with
+                if *on_conflict == InsertConflict::Replace {
+                    self.require(table_name, Privilege::Delete, None)?;
+                }
+                if let Some(table) = self.catalog.table(table_name) {
+                    let columns = match columns {
+                        Some(columns) => columns.clone(),
+                        None => visible_columns(table),
+                    };
+                    for column in &columns {
+                        let column = column.split('.').next_back().unwrap_or(column);
+                        self.require(table_name, Privilege::Insert, Some(column))?;
+                    }
+                }
+                // RETURNING reads the inserted rows
+                if let Some(returning) = returning {
+                    self.check_target_reads(
+                        table_name,
+                        returning.expressions.iter().collect(),
+                        None,
+                    )?;
+                }
+                // the listed values may read other tables through subqueries
+                let values = values.iter().flatten().map(|expr| (expr, false));
+                self.check_exprs(&[], &values.collect::<Vec<_>>())?;
+                match source {
+                    Some(source) => self.check_reads(source),
+                    None => Ok(()),
+                }
+            }
+            LogicalPlan::Update {
+                table_name,
+                assignments,
+                from,
+                filter,
+                ..
+            } => {
+                for assignment in assignments {
+                    self.require(table_name, Privilege::Update, Some(&assignment.column))?;
+                }
+                let mut exprs = assignments
+                    .iter()
+                    .map(|assignment| &assignment.value)
+                    .collect::<Vec<_>>();
+                exprs.extend(filter);
+                self.check_target_reads(table_name, exprs, from.as_deref())
+            }
+            LogicalPlan::Delete {
+                table_name,
+                using,
+                filter,
+                ..
+            } => {
+                self.require(table_name, Privilege::Delete, None)?;
+                self.check_target_reads(table_name, filter.iter().collect(), using.as_deref())
+            }
+            LogicalPlan::Copy {
+                table_name,
+                columns,
+                direction,
+                ..
+            } => {
+                let privilege = match direction {
+                    CopyDirection::To => Privilege::Select,
+                    CopyDirection::From => Privilege::Insert,
+                };
+                if let Some(table) = self.catalog.table(table_name) {
+                    let columns = match columns {
+                        Some(columns) => columns.clone(),
+                        None => visible_columns(table),
+                    };
+                    for column in &columns {
+                        self.require(table_name, privilege, Some(column))?;
+                    }
+                }
+                Ok(())
+            }
+            LogicalPlan::Explain { input } => self.check(input),
+            LogicalPlan::Transaction { .. } | LogicalPlan::Set { .. } => Ok(()),
+            LogicalPlan::Scan { .. }
+            | LogicalPlan::SingleRow
+            | LogicalPlan::Values { .. }
+            | LogicalPlan::Filter { .. }
+            | LogicalPlan::Project { .. }
+            | LogicalPlan::Join { .. }
+            | LogicalPlan::Sort { .. }
+            | LogicalPlan::DistinctOn { .. }
+            | LogicalPlan::Limit { .. }
+            | LogicalPlan::Aggregate { .. } => self.check_reads(plan),
+            _ => Err(ExecutionError::PermissionDenied(format!(
+                "role {} cannot change the schema or privileges",
+                self.role
+            ))),
+        }
+    }
+
+    /// Checks the reads of an UPDATE or DELETE: its expressions may refer to
+    /// the target table and to the tables of its FROM or USING clause.
+    fn check_target_reads(
+        &self,
+        table_name: &'a str,
+        exprs: Vec<&'a Expr>,
+        source: Option<&'a LogicalPlan>,
+    ) -> ExecutionResult<()> {
+        let mut scans = Vec::new();
+        let mut exprs = exprs.into_iter().map(|expr| (expr, false)).collect();
+        let mut subqueries = Vec::new();
+        if let Some(source) = source {
+            self.collect(source, &mut scans, &mut exprs, &mut subqueries);
+            self.require_tables(&scans)?;
+        }
+        if let Some(table) = self.catalog.table(table_name) {
+            scans.push(ScannedTable {
+                name: table_name,
+                alias: None,
+                table,
+            });
+        }
+        self.check_exprs(&scans, &exprs)?;
+        subqueries
+            .into_iter()
+            .try_for_each(|subquery| self.check_reads(subquery))
+    }
+
+    fn check_reads(&self, plan: &'a LogicalPlan) -> ExecutionResult<()> {
+        let mut scans = Vec::new();
+        let mut exprs = Vec::new();
+        let mut subqueries = Vec::new();
+        self.collect(plan, &mut scans, &mut exprs, &mut subqueries);
+        self.require_tables(&scans)?;
+        self.check_exprs(&scans, &exprs)?;
+        subqueries
+            .into_iter()
+            .try_for_each(|subquery| self.check_reads(subquery))
+    }
+
+    /// Gathers the scanned tables of `plan` and the expressions that read
+    /// their columns. The flag marks aggregate arguments, where `*` reads no
+    /// column. Subqueries are set aside in `subqueries`, to be checked with
+    /// their own tables.
+    fn collect(
+        &self,
+        plan: &'a LogicalPlan,
+        scans: &mut Vec<ScannedTable<'a>>,
+        exprs: &mut Vec<(&'a Expr, bool)>,
+        subqueries: &mut Vec<&'a LogicalPlan>,
+    ) {
+        match plan {
+            LogicalPlan::Scan {
+                table_name, alias, ..
+            } => {
+                if let Some(table) = self.catalog.table(table_name) {
+                    scans.push(ScannedTable {
+                        name: table_name,
+                        alias: alias.as_deref(),
+                        table,
+                    });
+                }
+            }
+            LogicalPlan::Filter { input, predicate } => {
+                exprs.push((predicate, false));
+                self.collect(input, scans, exprs, subqueries);
+            }
+            LogicalPlan::Project {
+                input, expressions, ..
+            } => {
+                exprs.extend(expressions.iter().map(|expr| (expr, false)));
+                self.collect(input, scans, exprs, subqueries);
+            }
+            // `key IN (subquery)`: only the key reads the outer tables
+            LogicalPlan::Join {
+                left,
+                right,
+                join_type: JoinType::Semi,
+                condition,
+            } => {
+                if let Some(Expr::BinaryOp { left: key, .. }) = condition {
+                    exprs.push((key, false));
+                }
+                self.collect(left, scans, exprs, subqueries);
+                subqueries.push(right);
+            }
+            LogicalPlan::Join {
+                left,
+                right,
+                condition,
+                ..
+            } => {
+                exprs.extend(condition.iter().map(|expr| (expr, false)));
+                self.collect(left, scans, exprs, subqueries);
+                self.collect(right, scans, exprs, subqueries);
+            }
+            LogicalPlan::Sort { input, sort_exprs } => {
+                exprs.extend(sort_exprs.iter().map(|sort| (&sort.expr, false)));
+                self.collect(input, scans, exprs, subqueries);
+            }
+            LogicalPlan::DistinctOn { input, on } => {
+                exprs.extend(on.iter().map(|expr| (expr, false)));
+                self.collect(input, scans, exprs, subqueries);
+            }
+            LogicalPlan::Aggregate {
+                input,
+                group_by,
+                aggregates,
+            } => {
+                exprs.extend(group_by.iter().map(|expr| (expr, false)));
+                for aggregate in aggregates {
+                    exprs.extend(aggregate.args.iter().map(|expr| (expr, true)));
+                }
+                self.collect(input, scans, exprs, subqueries);
+            }
+            LogicalPlan::Limit { input, .. } | LogicalPlan::Explain { input } => {
+                self.collect(input, scans, exprs, subqueries)
+            }
+            // row values may hold scalar subqueries
+            LogicalPlan::Values { rows, .. } => {
+                exprs.extend(rows.iter().flatten().map(|expr| (expr, false)));
+            }
+            LogicalPlan::SingleRow => {}
+            // statements, not queries; `check` handles each of them
+            LogicalPlan::Insert { .. }
+            | LogicalPlan::Update { .. }
+            | LogicalPlan::Delete { .. }
+            | LogicalPlan::CreateTable { .. }
+            | LogicalPlan::DropTable { .. }
+            | LogicalPlan::Analyze { .. }
+            | LogicalPlan::AlterTableRename { .. }
+            | LogicalPlan::AlterTableRenameColumn { .. }
+            | LogicalPlan::AlterTableAddColumn { .. }
+            | LogicalPlan::AlterTableDropColumn { .. }
+            | LogicalPlan::AlterTableAlterColumnType { .. }
+            | LogicalPlan::AlterTableAddCheck { .. }
+            | LogicalPlan::CreateIndex { .. }
+            | LogicalPlan::CreateSequence { .. }
+            | LogicalPlan::DropSequence { .. }
+            | LogicalPlan::Grant { .. }
+            | LogicalPlan::Revoke { .. }
+            | LogicalPlan::Copy { .. }
+            | LogicalPlan::Transaction { .. }
+            | LogicalPlan::Set { .. } => {}
+        }
+    }
+
+    /// Requires SELECT on at least one column of every scanned table.
+    fn require_tables(&self, scans: &[ScannedTable<'a>]) -> ExecutionResult<()> {
+        for scan in scans {
+            self.require(scan.name, Privilege::Select, None)?;
+        }
+        Ok(())
+    }
+
+    /// Requires SELECT on every column the expressions read.
+    fn check_exprs(
+        &self,
+        scans: &[ScannedTable<'a>],
+        exprs: &[(&'a Expr, bool)],
+    ) -> ExecutionResult<()> {
+        for (expr, in_aggregate) in exprs {
+            self.check_expr(scans, expr, *in_aggregate)?;
+        }
+        Ok(())
+    }
+
+    fn check_expr(
+        &self,
+        scans: &[ScannedTable<'a>],
+        expr: &'a Expr,
+        in_aggregate: bool,
+    ) -> ExecutionResult<()> {
+        match expr {
+            Expr::Column { table, name } => {
+                let (table, name) = match (table, name.split_once('.')) {
+                    (None, Some((table, name))) => (Some(table), name),
+                    (table, _) => (table.as_deref(), name.as_str()),
+                };
+                for scan in scans_named(scans, table) {
+                    if visible_columns(scan.table)
+                        .iter()
+                        .any(|column| column.eq_ignore_ascii_case(name))
+                    {
+                        self.require(scan.name, Privilege::Select, Some(name))?;
+                    }
+                }
+                Ok(())
+            }
+            Expr::Wildcard | Expr::QualifiedWildcard { .. } if in_aggregate => Ok(()),
+            Expr::Wildcard | Expr::QualifiedWildcard { .. } => {
+                let table = match expr {
+                    Expr::QualifiedWildcard { table } => Some(table.as_str()),
+                    _ => None,
+                };
+                for scan in scans_named(scans, table) {
+                    for column in visible_columns(scan.table) {
+                        self.require(scan.name, Privilege::Select, Some(&column))?;
+                    }
+                }
+                Ok(())
+            }
+            Expr::Literal(_) | Expr::Default | Expr::Parameter(_) => Ok(()),
+            Expr::Function { name, args } => {
+                let in_aggregate =
+                    in_aggregate || AggregateFunction::from_name(&name.to_uppercase()).is_some();
+                args.iter()
+                    .try_for_each(|arg| self.check_expr(scans, arg, in_aggregate))
+            }
+            Expr::BinaryOp { left, right, .. } => {
+                self.check_expr(scans, left, in_aggregate)?;
+                self.check_expr(scans, right, in_aggregate)
+            }
+            Expr::UnaryOp { expr, .. } | Expr::Cast { expr, .. } | Expr::IsNull { expr, .. } => {
+                self.check_expr(scans, expr, in_aggregate)
+            }
+            Expr::Between {
+                expr, low, high, ..
+            } => {
+                self.check_expr(scans, expr, in_aggregate)?;
+                self.check_expr(scans, low, in_aggregate)?;
+                self.check_expr(scans, high, in_aggregate)
+            }
+            Expr::In { expr, list, .. } => {
+                self.check_expr(scans, expr, in_aggregate)?;
+                list.iter()
+                    .try_for_each(|item| self.check_expr(scans, item, in_aggregate))
+            }
+            Expr::InSubquery { expr, subplan, .. } => {
+                self.check_expr(scans, expr, in_aggregate)?;
+                self.check_reads(subplan)
+            }
+            Expr::ScalarSubquery(subplan) => self.check_reads(subplan),
+        }
+    }
+
+    fn require(
+        &self,
+        table: &str,
+        privilege: Privilege,
+        column: Option<&str>,
+    ) -> ExecutionResult<()> {
+        if self.catalog.table(table).is_none()
+            || self
+                .catalog
+                .privileges()
+                .allows(self.role, table, privilege, column)
+        {
+            return Ok(());
+        }
+        Err(ExecutionError::PermissionDenied(match column {
+            Some(column) => format!(
+                "role {} has no {} privilege on column {}.{}",
+                self.role, privilege, table, column
+            ),
+            None => format!(
+                "role {} has no {} privilege on table {}",
+                self.role, privilege, table
+            ),
+        }))
+    }
+}
+
+/// Scans a column qualifier refers to: the scan with that alias, or with
+/// that table name when it has no alias. An unqualified reference may read
+/// any of them.
+fn scans_named<'s, 'a>(
+    scans: &'s [ScannedTable<'a>],
+    qualifier: Option<&'s str>,
+) -> impl Iterator<Item = &'s ScannedTable<'a>> {
+    scans.iter().filter(move |scan| match qualifier {
+        Some(qualifier) => scan
+            .alias
+            .unwrap_or(scan.name)
+            .eq_ignore_ascii_case(qualifier),
+        None => true,
+    })
+}
+
+fn visible_columns(table: &TableInfo) -> Vec<String> {
+    table
+        .schema
+        .fields
+        .iter()
+        .filter(|field| field.visible)
+        .map(|field| field.name.clone())
+        .collect()
+}
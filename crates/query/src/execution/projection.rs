@@ -44,23 +44,7 @@ impl PhysicalOperator for Projection {
             Some(tuple) => tuple,
             None => return Ok(None),
         };
-
-        let mut values = Vec::with_capacity(self.resolved_items.len());
-        for item in &self.resolved_items {
-            match item {
-                ProjectionItem::FieldIndex(index) => {
-                    let value = tuple.get(*index).ok_or_else(|| {
-                        ExecutionError::Schema(format!("projection index {} out of range", index))
-                    })?;
-                    values.push(value.clone());
-                }
-                ProjectionItem::Expression(expr) => {
-                    values.push(evaluate_expr(expr, &tuple, &self.input_schema)?);
-                }
-            }
-        }
-
-        Ok(Some(Tuple::new(values)))
+        project(&self.resolved_items, &tuple, &self.input_schema).map(Some)
     }
 
     fn close(&mut self) -> ExecutionResult<()> {
@@ -68,9 +52,57 @@ impl PhysicalOperator for Projection {
         self.child.close()
     }
 
+    fn rewind(&mut self) -> ExecutionResult<()> {
+        self.child.rewind()
+    }
+
     fn as_any(&self) -> &dyn Any {
         self
     }
+
+    fn describe(&self) -> String {
+        let expressions = self
+            .expressions
+            .iter()
+            .map(|expr| expr.to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+        format!("Projection: [{}]", expressions)
+    }
+
+    fn children(&self) -> Vec<&dyn PhysicalOperator> {
+        vec![&*self.child]
+    }
+}
+
+/// Evaluates `expressions`, as a select list, over each of `rows`.
+pub(crate) fn project_rows(
+    expressions: &[Expr],
+    input_schema: &Schema,
+    rows: &[Tuple],
+) -> ExecutionResult<Vec<Tuple>> {
+    let items = resolve_projection_items(expressions, input_schema)?;
+    rows.iter()
+        .map(|tuple| project(&items, tuple, input_schema))
+        .collect()
+}
+
+fn project(items: &[ProjectionItem], tuple: &Tuple, schema: &Schema) -> ExecutionResult<Tuple> {
+    let mut values = Vec::with_capacity(items.len());
+    for item in items {
+        match item {
+            ProjectionItem::FieldIndex(index) => {
+                let value = tuple.get(*index).ok_or_else(|| {
+                    ExecutionError::Schema(format!("projection index {} out of range", index))
+                })?;
+                values.push(value.clone());
+            }
+            ProjectionItem::Expression(expr) => {
+                values.push(evaluate_expr(expr, tuple, schema)?);
+            }
+        }
+    }
+    Ok(Tuple::new(values))
 }
 
 #[derive(Debug, Clone)]
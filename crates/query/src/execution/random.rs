@@ -0,0 +1,59 @@
+use parking_lot::Mutex;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::cell::RefCell;
+use std::sync::Arc;
+
+/// Random number generator behind `RANDOM()` for one session. It starts
+/// from OS entropy; seeding it makes the values it returns reproducible.
+#[derive(Clone)]
+pub struct SessionRandom {
+    rng: Arc<Mutex<StdRng>>,
+}
+
+impl SessionRandom {
+    pub fn new() -> Self {
+        Self {
+            rng: Arc::new(Mutex::new(StdRng::from_entropy())),
+        }
+    }
+
+    /// Restarts the generator from `seed`, or from OS entropy when `None`.
+    pub fn reseed(&self, seed: Option<u64>) {
+        *self.rng.lock() = match seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+    }
+
+    /// Returns a value in `[0, 1)`.
+    pub fn next_value(&self) -> f64 {
+        self.rng.lock().gen()
+    }
+
+    /// Runs `f` with `RANDOM()` drawing from this generator.
+    pub fn with_current<R>(&self, f: impl FnOnce() -> R) -> R {
+        let previous = CURRENT_RANDOM.with(|cell| cell.replace(Some(self.clone())));
+        let result = f();
+        CURRENT_RANDOM.with(|cell| cell.replace(previous));
+        result
+    }
+}
+
+impl Default for SessionRandom {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+thread_local! {
+    static CURRENT_RANDOM: RefCell<Option<SessionRandom>> = const { RefCell::new(None) };
+}
+
+/// `RANDOM()` from the generator set by [`SessionRandom::with_current`], or
+/// an unseeded one outside the engine.
+pub fn random() -> f64 {
+    CURRENT_RANDOM
+        .with(|cell| cell.borrow().as_ref().map(SessionRandom::next_value))
+        .unwrap_or_else(rand::random)
+}
@@ -0,0 +1,43 @@
+use crate::execution::operator::{ExecutionError, ExecutionResult};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// Version of a table definition, shared by every clone of its `TableInfo`.
+/// DDL on the table, or dropping it, bumps the counter.
+#[derive(Debug, Clone, Default)]
+pub struct TableVersion(Arc<AtomicU64>);
+
+impl TableVersion {
+    pub fn bump(&self) {
+        self.0.fetch_add(1, Ordering::AcqRel);
+    }
+
+    /// Captures the current version for an operator planned against it.
+    pub fn snapshot(&self, table: impl Into<String>) -> SchemaVersion {
+        SchemaVersion {
+            table: table.into(),
+            planned: self.0.load(Ordering::Acquire),
+            current: self.clone(),
+        }
+    }
+}
+
+/// Table version seen when an operator was planned. Operators check it when
+/// opened so a plan built before a DDL fails instead of reading or writing
+/// through heaps and indexes the catalog no longer uses.
+#[derive(Debug, Clone)]
+pub struct SchemaVersion {
+    table: String,
+    planned: u64,
+    current: TableVersion,
+}
+
+impl SchemaVersion {
+    pub fn check(&self) -> ExecutionResult<()> {
+        if self.current.0.load(Ordering::Acquire) == self.planned {
+            Ok(())
+        } else {
+            Err(ExecutionError::SchemaChanged(self.table.clone()))
+        }
+    }
+}
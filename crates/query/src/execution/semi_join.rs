@@ -0,0 +1,208 @@
+use crate::execution::operator::{evaluate_expr, ExecutionResult, PhysicalOperator};
+use crate::execution::schema_version::SchemaVersion;
+use crate::execution::seq_scan::{Rid, TableHeap};
+use crate::execution::tuple::{Tuple, Value};
+use crate::expr::Expr;
+use crate::index::{BPlusTree, Index, IndexKey, IndexKeyType};
+use crate::schema::Schema;
+use std::any::Any;
+use std::collections::{BTreeSet, HashSet};
+
+/// Reads every value of the one-column `right` input, leaving out NULLs,
+/// which never match.
+fn collect_values(right: &mut dyn PhysicalOperator) -> ExecutionResult<HashSet<Value>> {
+    right.open()?;
+    let mut values = HashSet::new();
+    while let Some(tuple) = right.next()? {
+        match tuple.get(0) {
+            Some(Value::Null) | None => {}
+            Some(value) => {
+                values.insert(value.clone());
+            }
+        }
+    }
+    right.close()?;
+    Ok(values)
+}
+
+/// `left_key IN (right)`: returns each row of `left` whose key equals a
+/// value of the one-column `right` input. `right` is read into a hash set
+/// when the operator opens.
+pub struct HashSemiJoin {
+    left: Box<dyn PhysicalOperator>,
+    right: Box<dyn PhysicalOperator>,
+    left_key: Expr,
+    left_schema: Schema,
+    condition: Expr,
+    values: HashSet<Value>,
+}
+
+impl HashSemiJoin {
+    pub fn new(
+        left: Box<dyn PhysicalOperator>,
+        right: Box<dyn PhysicalOperator>,
+        left_key: Expr,
+        left_schema: Schema,
+        condition: Expr,
+    ) -> Self {
+        Self {
+            left,
+            right,
+            left_key,
+            left_schema,
+            condition,
+            values: HashSet::new(),
+        }
+    }
+}
+
+impl PhysicalOperator for HashSemiJoin {
+    fn open(&mut self) -> ExecutionResult<()> {
+        self.values = collect_values(self.right.as_mut())?;
+        self.left.open()
+    }
+
+    fn next(&mut self) -> ExecutionResult<Option<Tuple>> {
+        while let Some(tuple) = self.left.next()? {
+            let key = evaluate_expr(&self.left_key, &tuple, &self.left_schema)?;
+            if self.values.contains(&key) {
+                return Ok(Some(tuple));
+            }
+        }
+        Ok(None)
+    }
+
+    fn close(&mut self) -> ExecutionResult<()> {
+        self.values.clear();
+        self.left.close()
+    }
+
+    fn rewind(&mut self) -> ExecutionResult<()> {
+        self.left.rewind()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn describe(&self) -> String {
+        format!("HashSemiJoin: ON {}", self.condition)
+    }
+
+    fn children(&self) -> Vec<&dyn PhysicalOperator> {
+        vec![&*self.left, &*self.right]
+    }
+}
+
+/// `column IN (right)` for an indexed column: looks up each distinct value
+/// of the one-column `right` input in the index instead of scanning the
+/// table, returning every matching row once.
+pub struct IndexSemiJoin {
+    table_heap: TableHeap,
+    schema: Schema,
+    index: BPlusTree,
+    key_type: IndexKeyType,
+    right: Box<dyn PhysicalOperator>,
+    condition: Expr,
+    rids: Vec<Rid>,
+    position: usize,
+    schema_version: Option<SchemaVersion>,
+    index_name: Option<String>,
+}
+
+impl IndexSemiJoin {
+    pub fn new(
+        table_heap: TableHeap,
+        schema: Schema,
+        index: BPlusTree,
+        right: Box<dyn PhysicalOperator>,
+        condition: Expr,
+    ) -> Self {
+        let key_type = index.key_type();
+        Self {
+            table_heap,
+            schema,
+            index,
+            key_type,
+            right,
+            condition,
+            rids: Vec::new(),
+            position: 0,
+            schema_version: None,
+            index_name: None,
+        }
+    }
+
+    /// Fails `open` once the probed table has changed since planning.
+    pub fn with_schema_version(mut self, version: SchemaVersion) -> Self {
+        self.schema_version = Some(version);
+        self
+    }
+
+    /// Name shown for the index by EXPLAIN.
+    pub fn with_index_name(mut self, name: impl Into<String>) -> Self {
+        self.index_name = Some(name.into());
+        self
+    }
+}
+
+impl PhysicalOperator for IndexSemiJoin {
+    fn open(&mut self) -> ExecutionResult<()> {
+        if let Some(version) = &self.schema_version {
+            version.check()?;
+        }
+        // probed in key order so rows come back in index order; a value the
+        // index cannot hold, like NULL, matches no row
+        let keys = collect_values(self.right.as_mut())?
+            .iter()
+            .filter_map(|value| IndexKey::from_value(value, self.key_type).ok())
+            .filter(|key| !key.has_null())
+            .collect::<BTreeSet<_>>();
+        self.rids.clear();
+        for key in &keys {
+            self.rids.extend(self.index.get(key)?);
+        }
+        self.position = 0;
+        Ok(())
+    }
+
+    fn next(&mut self) -> ExecutionResult<Option<Tuple>> {
+        while self.position < self.rids.len() {
+            let rid = self.rids[self.position];
+            self.position += 1;
+            if let Some(tuple) = self.table_heap.get_tuple(rid, &self.schema)? {
+                return Ok(Some(tuple));
+            }
+        }
+        Ok(None)
+    }
+
+    fn close(&mut self) -> ExecutionResult<()> {
+        self.rids.clear();
+        self.position = 0;
+        Ok(())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn describe(&self) -> String {
+        let table = self
+            .schema
+            .fields
+            .first()
+            .and_then(|field| field.table.as_deref())
+            .unwrap_or("?");
+        format!(
+            "IndexSemiJoin: {} using {} ON {}",
+            table,
+            self.index_name.as_deref().unwrap_or("?"),
+            self.condition
+        )
+    }
+
+    fn children(&self) -> Vec<&dyn PhysicalOperator> {
+        vec![&*self.right]
+    }
+}
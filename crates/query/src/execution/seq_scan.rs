@@ -1,27 +1,81 @@
 use crate::execution::operator::{ExecutionError, ExecutionResult, PhysicalOperator};
+use crate::execution::schema_version::SchemaVersion;
 use crate::execution::tuple::{Tuple, Value};
-use crate::schema::{DataType, Schema};
+use crate::execution::versions::{SnapshotView, VersionStore};
+use crate::logical_plan::{SampleMethod, TableSample};
+use crate::schema::{DataType, Field, Schema};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 use std::any::Any;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex, MutexGuard};
-use storage::{BufferPoolManager, FlushMode, Page, PageId, PAGE_LSN_SIZE, PAGE_SIZE};
+use storage::{BufferPoolManager, FlushMode, Page, PageId, PAGE_HEADER_SIZE, PAGE_SIZE};
 use txn::{LockKey, LockMode, TxnId};
 
 const HEADER_DATA_SIZE: usize = 16;
-const HEADER_SIZE: usize = PAGE_LSN_SIZE + HEADER_DATA_SIZE;
+const HEADER_SIZE: usize = PAGE_HEADER_SIZE + HEADER_DATA_SIZE;
 const SLOT_SIZE: usize = 8;
 const INVALID_PAGE_ID: PageId = 0;
 const INLINE_BLOB_LIMIT: usize = 512;
 
+// Blob storage flags in the encoded tuple: the bytes follow inline, or the
+// tuple holds the first page, length and checksum of an out-of-line blob.
+const BLOB_INLINE: u8 = 0;
+const BLOB_OUT_OF_LINE_CHECKSUMMED: u8 = 2;
+
+// First byte of every tuple, naming its layout.
+const TUPLE_COMPACT: u8 = 0x80;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Rid {
     pub page_id: PageId,
     pub slot_id: u32,
 }
 
+/// A scan position just past the last row returned, for resuming an
+/// interrupted scan. A resumed scan returns the rows stored after that
+/// position, which are the ones the first scan had not reached yet unless a
+/// row moved in between: an update that grows a row past its slot deletes
+/// it and inserts it again at a new rid. A row moved from before the cursor
+/// to after it is returned twice, and one moved the other way is missed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScanCursor {
+    last: Rid,
+}
+
+impl ScanCursor {
+    pub fn after(rid: Rid) -> Self {
+        Self { last: rid }
+    }
+
+    pub fn last_rid(&self) -> Rid {
+        self.last
+    }
+
+    /// Opaque token form of the cursor, for handing to clients.
+    pub fn to_token(&self) -> String {
+        format!("{:016x}{:08x}", self.last.page_id, self.last.slot_id)
+    }
+
+    pub fn from_token(token: &str) -> ExecutionResult<Self> {
+        let invalid = || ExecutionError::Execution(format!("invalid scan cursor {:?}", token));
+        if token.len() != 24 || !token.is_ascii() {
+            return Err(invalid());
+        }
+        let page_id = PageId::from_str_radix(&token[..16], 16).map_err(|_| invalid())?;
+        let slot_id = u32::from_str_radix(&token[16..], 16).map_err(|_| invalid())?;
+        Ok(Self::after(Rid { page_id, slot_id }))
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 struct BlobPointer {
     first_page_id: PageId,
     length: u32,
+    /// Checksum of the whole blob. Blob pages bypass the WAL, so this is the
+    /// only thing that catches a corrupted payload.
+    checksum: u64,
 }
 
 #[derive(Clone)]
@@ -39,6 +93,7 @@ impl BlobStore {
             return Ok(BlobPointer {
                 first_page_id: INVALID_PAGE_ID,
                 length: 0,
+                checksum: blob_checksum(bytes),
             });
         }
         let total_len = u32::try_from(bytes.len())
@@ -50,6 +105,7 @@ impl BlobStore {
             let page_id = self.buffer_pool.new_page()?.ok_or_else(|| {
                 ExecutionError::Execution("buffer pool has no free frames".to_string())
             })?;
+            self.buffer_pool.unpin_page(page_id, false)?;
             page_ids.push(page_id);
             let chunk_len = remaining.min(payload_capacity);
             remaining -= chunk_len;
@@ -73,12 +129,13 @@ impl BlobStore {
                 page_guard.set_lsn(0);
             }
             self.buffer_pool.unpin_page(*page_id, true)?;
-            self.buffer_pool
-                .flush_page_with_mode(*page_id, FlushMode::Force)?;
         }
+        self.buffer_pool
+            .flush_pages_with_mode(&page_ids, FlushMode::Force)?;
         Ok(BlobPointer {
             first_page_id: page_ids[0],
             length: total_len,
+            checksum: blob_checksum(bytes),
         })
     }
 
@@ -144,9 +201,31 @@ impl BlobStore {
                 "blob payload length mismatch".to_string(),
             ));
         }
+        if blob_checksum(&output) != pointer.checksum {
+            return Err(ExecutionError::Execution(format!(
+                "blob checksum mismatch: blob starting at page {} is corrupted",
+                pointer.first_page_id
+            )));
+        }
         Ok(output)
     }
 
+    /// The pages holding the blob `pointer` refers to.
+    fn page_ids(&self, pointer: BlobPointer) -> ExecutionResult<Vec<PageId>> {
+        let mut page_ids = Vec::new();
+        let mut page_id = pointer.first_page_id;
+        while page_id != INVALID_PAGE_ID {
+            page_ids.push(page_id);
+            let header = {
+                let page_guard = self.fetch_page_with_lock(page_id, LockMode::Shared)?;
+                read_blob_header(&page_guard)
+            };
+            self.buffer_pool.unpin_page(page_id, false)?;
+            page_id = header?.0;
+        }
+        Ok(page_ids)
+    }
+
     fn fetch_page_with_lock(
         &self,
         page_id: PageId,
@@ -155,15 +234,7 @@ impl BlobStore {
         if let (Some(lock_manager), Some(txn_id)) =
             (wal::current_lock_manager(), wal::current_txn_id())
         {
-            let txn_id = TxnId(txn_id);
-            match mode {
-                LockMode::Shared => lock_manager
-                    .lock_shared(txn_id, LockKey::Page(page_id))
-                    .map_err(|err| ExecutionError::Execution(format!("lock error: {err:?}")))?,
-                LockMode::Exclusive => lock_manager
-                    .lock_exclusive(txn_id, LockKey::Page(page_id))
-                    .map_err(|err| ExecutionError::Execution(format!("lock error: {err:?}")))?,
-            }
+            lock_manager.lock(TxnId(txn_id), LockKey::Page(page_id), mode)?;
         }
         self.buffer_pool.fetch_page(page_id)?.ok_or_else(|| {
             ExecutionError::Execution("buffer pool has no available frame".to_string())
@@ -175,11 +246,34 @@ impl BlobStore {
     }
 }
 
+/// Live row count kept alongside a heap so an unfiltered `COUNT(*)` does not
+/// scan. Changes made inside a transaction stay in `pending` until it
+/// commits and are dropped if it aborts, because the WAL undo restores the
+/// pages without going through the heap.
+#[derive(Default)]
+struct RowCount {
+    /// Rows visible to every transaction; `None` until first counted.
+    committed: Option<i64>,
+    pending: HashMap<wal::TxnId, i64>,
+    /// Bumped on every change so a count taken concurrently is discarded.
+    changes: u64,
+}
+
 #[derive(Clone)]
 pub struct TableHeap {
     buffer_pool: BufferPoolManager,
     first_page_id: Arc<Mutex<Option<PageId>>>,
     blob_store: BlobStore,
+    row_count: Arc<Mutex<RowCount>>,
+    /// For each running transaction that changed rows here, its last LSN
+    /// after its latest change, so a rollback to an LSN can tell whether it
+    /// undid any change to this heap.
+    writes: Arc<Mutex<HashMap<wal::TxnId, wal::Lsn>>>,
+    row_locks: Arc<AtomicBool>,
+    /// Held, by every clone, while a page is added to the page chain, so
+    /// inserts that all find the last page full link a single new page.
+    extend_lock: Arc<Mutex<()>>,
+    versions: VersionStore,
 }
 
 impl TableHeap {
@@ -188,6 +282,11 @@ impl TableHeap {
             blob_store: BlobStore::new(buffer_pool.clone()),
             buffer_pool,
             first_page_id: Arc::new(Mutex::new(first_page_id)),
+            row_count: Arc::new(Mutex::new(RowCount::default())),
+            writes: Arc::new(Mutex::new(HashMap::new())),
+            row_locks: Arc::new(AtomicBool::new(false)),
+            extend_lock: Arc::new(Mutex::new(())),
+            versions: VersionStore::default(),
         }
     }
 
@@ -195,6 +294,7 @@ impl TableHeap {
         let heap = Self::new(buffer_pool, None);
         let page_id = heap.allocate_page()?;
         heap.set_first_page_id(Some(page_id))?;
+        heap.row_count_guard()?.committed = Some(0);
         Ok(heap)
     }
 
@@ -215,6 +315,19 @@ impl TableHeap {
         Ok(())
     }
 
+    /// Locks single rows instead of whole pages when reading, updating in
+    /// place and deleting, so transactions touching different rows of a page
+    /// do not wait for each other. Inserts still lock the page exclusively.
+    /// The setting is shared by every clone of this heap.
+    pub fn set_row_locks(&self, enabled: bool) {
+        self.row_locks.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Whether updates and deletes lock rows rather than whole pages.
+    pub fn row_locks(&self) -> bool {
+        self.row_locks.load(Ordering::Relaxed)
+    }
+
     #[allow(dead_code)]
     fn fetch_page(&self, page_id: PageId) -> ExecutionResult<storage::PageGuard<'_>> {
         self.fetch_page_with_lock(page_id, LockMode::Shared)
@@ -229,19 +342,73 @@ impl TableHeap {
         page_id: PageId,
         mode: LockMode,
     ) -> ExecutionResult<storage::PageGuard<'_>> {
+        self.lock(LockKey::Page(page_id), mode)?;
+        self.fetch_page_unlocked(page_id)
+    }
+
+    /// Fetches the page of `rid` to read (`Shared`) or change (`Exclusive`)
+    /// that row alone. With row locks off the whole page is locked instead.
+    fn fetch_row_page(&self, rid: Rid, mode: LockMode) -> ExecutionResult<storage::PageGuard<'_>> {
+        self.lock(self.row_lock_key(rid), mode)?;
+        self.fetch_page_unlocked(rid.page_id)
+    }
+
+    fn row_lock_key(&self, rid: Rid) -> LockKey {
+        if self.row_locks() {
+            LockKey::Row {
+                page_id: rid.page_id,
+                slot_id: rid.slot_id,
+            }
+        } else {
+            LockKey::Page(rid.page_id)
+        }
+    }
+
+    /// In a snapshot transaction, locks the row of `rid` to change it and
+    /// fails if a transaction the snapshot does not see has changed it.
+    pub fn check_writable(&self, rid: Rid) -> ExecutionResult<()> {
+        let Some(snapshot) = wal::current_snapshot() else {
+            return Ok(());
+        };
+        self.lock(self.row_lock_key(rid), LockMode::Exclusive)?;
+        self.versions.check_writable(rid, &snapshot)
+    }
+
+    /// Fetches a page to read its rows from `from_slot` on. With row locks
+    /// on, those rows are locked shared rather than the page; rows appended
+    /// later are not, but appending locks the page exclusively, which waits
+    /// for the intention lock held here.
+    fn fetch_page_for_scan(
+        &self,
+        page_id: PageId,
+        from_slot: usize,
+    ) -> ExecutionResult<storage::PageGuard<'_>> {
+        if !self.row_locks() {
+            return self.fetch_page_with_lock(page_id, LockMode::Shared);
+        }
+        self.lock(LockKey::Page(page_id), LockMode::IntentionShared)?;
+        // no waiting on row locks while the page is pinned
+        let slot_count = {
+            let page_guard = self.fetch_page_unlocked(page_id)?;
+            read_header(&page_guard).map(|header| header.slot_count)
+        };
+        self.buffer_pool.unpin_page(page_id, false)?;
+        for slot_id in from_slot as u32..slot_count? {
+            self.lock(LockKey::Row { page_id, slot_id }, LockMode::Shared)?;
+        }
+        self.fetch_page_unlocked(page_id)
+    }
+
+    fn lock(&self, key: LockKey, mode: LockMode) -> ExecutionResult<()> {
         if let (Some(lock_manager), Some(txn_id)) =
             (wal::current_lock_manager(), wal::current_txn_id())
         {
-            let txn_id = TxnId(txn_id);
-            match mode {
-                LockMode::Shared => lock_manager
-                    .lock_shared(txn_id, LockKey::Page(page_id))
-                    .map_err(|err| ExecutionError::Execution(format!("lock error: {err:?}")))?,
-                LockMode::Exclusive => lock_manager
-                    .lock_exclusive(txn_id, LockKey::Page(page_id))
-                    .map_err(|err| ExecutionError::Execution(format!("lock error: {err:?}")))?,
-            }
+            lock_manager.lock(TxnId(txn_id), key, mode)?;
         }
+        Ok(())
+    }
+
+    fn fetch_page_unlocked(&self, page_id: PageId) -> ExecutionResult<storage::PageGuard<'_>> {
         self.buffer_pool.fetch_page(page_id)?.ok_or_else(|| {
             ExecutionError::Execution("buffer pool has no available frame".to_string())
         })
@@ -251,9 +418,13 @@ impl TableHeap {
         let tuple_bytes = encode_tuple(tuple, schema, &self.blob_store)?;
         let mut current_page_id = self.first_page_id()?;
         if current_page_id.is_none() {
-            let page_id = self.allocate_page()?;
-            self.set_first_page_id(Some(page_id))?;
-            current_page_id = Some(page_id);
+            let _extending = self.extend_guard()?;
+            current_page_id = self.first_page_id()?;
+            if current_page_id.is_none() {
+                let page_id = self.allocate_page()?;
+                self.set_first_page_id(Some(page_id))?;
+                current_page_id = Some(page_id);
+            }
         }
 
         loop {
@@ -275,11 +446,18 @@ impl TableHeap {
                 if available_space >= tuple_bytes.len() + SLOT_SIZE {
                     let tuple_offset =
                         (header.free_space_offset as usize - tuple_bytes.len()) as u32;
+                    let slot_index = header.slot_count as usize;
+                    self.versions.record(
+                        Rid {
+                            page_id,
+                            slot_id: slot_index as u32,
+                        },
+                        None,
+                    );
                     write_bytes_logged(&mut page_guard, tuple_offset as usize, &tuple_bytes)
                         .map_err(|_| {
                             ExecutionError::Execution("failed to write tuple bytes".to_string())
                         })?;
-                    let slot_index = header.slot_count as usize;
                     write_slot(
                         &mut page_guard,
                         slot_index,
@@ -303,6 +481,7 @@ impl TableHeap {
 
             if inserted {
                 self.buffer_pool.unpin_page(page_id, page_dirty)?;
+                self.adjust_row_count(1)?;
                 return Ok(Rid {
                     page_id,
                     slot_id: inserted_slot.expect("inserted slot missing"),
@@ -312,28 +491,44 @@ impl TableHeap {
             self.buffer_pool.unpin_page(page_id, page_dirty)?;
 
             if needs_new_page {
-                let new_page_id = self.allocate_page()?;
-                let mut update_dirty = false;
-                {
-                    let mut page_guard = self.fetch_page_exclusive(page_id)?;
-                    let mut header = read_header(&page_guard)?;
-                    if header.next_page_id.is_none() {
-                        header.next_page_id = Some(new_page_id);
-                        write_header(&mut page_guard, &header)?;
-                        update_dirty = true;
-                    }
-                }
-                self.buffer_pool.unpin_page(page_id, update_dirty)?;
-                current_page_id = Some(new_page_id);
+                current_page_id = Some(self.extend_after(page_id)?);
             } else {
                 current_page_id = next_page_id;
             }
         }
     }
 
+    /// Returns the page after `page_id`, the last page seen full, adding
+    /// one to the chain unless another insert already has.
+    fn extend_after(&self, page_id: PageId) -> ExecutionResult<PageId> {
+        // the page lock is taken before the latch, as the insert that saw
+        // the page full already holds it, so no lock wait happens under it
+        self.lock(LockKey::Page(page_id), LockMode::Exclusive)?;
+        let _extending = self.extend_guard()?;
+        let linked = {
+            let page_guard = self.fetch_page_unlocked(page_id)?;
+            read_header(&page_guard).map(|header| header.next_page_id)
+        };
+        self.buffer_pool.unpin_page(page_id, false)?;
+        if let Some(next_page_id) = linked? {
+            return Ok(next_page_id);
+        }
+        let new_page_id = self.allocate_page()?;
+        let linked = {
+            let mut page_guard = self.fetch_page_unlocked(page_id)?;
+            read_header(&page_guard).and_then(|mut header| {
+                header.next_page_id = Some(new_page_id);
+                write_header(&mut page_guard, &header)
+            })
+        };
+        self.buffer_pool.unpin_page(page_id, true)?;
+        linked?;
+        Ok(new_page_id)
+    }
+
     pub fn get_tuple(&self, rid: Rid, schema: &Schema) -> ExecutionResult<Option<Tuple>> {
         let result = {
-            let page_guard = self.fetch_page_with_lock(rid.page_id, LockMode::Shared)?;
+            let page_guard = self.fetch_row_page(rid, LockMode::Shared)?;
             let result: ExecutionResult<Option<Vec<u8>>> = (|| {
                 let header = read_header(&page_guard)?;
                 if rid.slot_id < header.slot_count {
@@ -355,13 +550,16 @@ impl TableHeap {
     }
 
     pub fn delete_tuple(&self, rid: Rid) -> ExecutionResult<bool> {
+        self.check_writable(rid)?;
         let mut deleted = false;
         {
-            let mut page_guard = self.fetch_page_exclusive(rid.page_id)?;
+            let mut page_guard = self.fetch_row_page(rid, LockMode::Exclusive)?;
             let header = read_header(&page_guard)?;
             if rid.slot_id < header.slot_count {
                 if let Some(mut slot) = read_slot(&page_guard, rid.slot_id as usize)? {
                     if slot.len != 0 {
+                        let before = read_tuple_bytes(&page_guard, &slot)?;
+                        self.versions.record(rid, Some(&before));
                         slot.len = 0;
                         write_slot(&mut page_guard, rid.slot_id as usize, slot)?;
                         deleted = true;
@@ -370,14 +568,18 @@ impl TableHeap {
             }
         }
         self.buffer_pool.unpin_page(rid.page_id, deleted)?;
+        if deleted {
+            self.adjust_row_count(-1)?;
+        }
         Ok(deleted)
     }
 
     pub fn update_tuple(&self, rid: Rid, tuple: &Tuple, schema: &Schema) -> ExecutionResult<Rid> {
         let tuple_bytes = encode_tuple(tuple, schema, &self.blob_store)?;
+        self.check_writable(rid)?;
         let mut updated = false;
         let needs_reinsert = {
-            let mut page_guard = self.fetch_page_exclusive(rid.page_id)?;
+            let mut page_guard = self.fetch_row_page(rid, LockMode::Exclusive)?;
             let header = read_header(&page_guard)?;
             if rid.slot_id >= header.slot_count {
                 return Err(ExecutionError::Execution(
@@ -393,6 +595,8 @@ impl TableHeap {
             }
             let needs_reinsert = tuple_bytes.len() > slot.len as usize;
             if !needs_reinsert {
+                let before = read_tuple_bytes(&page_guard, &slot)?;
+                self.versions.record(rid, Some(&before));
                 write_bytes_logged(&mut page_guard, slot.offset as usize, &tuple_bytes).map_err(
                     |_| ExecutionError::Execution("failed to write updated tuple".to_string()),
                 )?;
@@ -405,6 +609,7 @@ impl TableHeap {
         };
         self.buffer_pool.unpin_page(rid.page_id, updated)?;
         if updated {
+            self.note_write()?;
             return Ok(rid);
         }
         if needs_reinsert {
@@ -414,15 +619,189 @@ impl TableHeap {
         Ok(rid)
     }
 
-    pub fn scan_tuples(&self, schema: &Schema) -> ExecutionResult<Vec<(Rid, Tuple)>> {
-        let mut output = Vec::new();
+    /// Counts live tuples and table pages from the slot directories, without
+    /// decoding any tuple data.
+    pub fn count_tuples(&self) -> ExecutionResult<(usize, usize)> {
+        let mut tuple_count = 0;
+        let mut page_count = 0;
         let mut current_page_id = self.first_page_id()?;
         while let Some(page_id) = current_page_id {
             let result = {
                 let page_guard = self.fetch_page_with_lock(page_id, LockMode::Shared)?;
                 let header = read_header(&page_guard)?;
+                let mut live = 0;
+                for slot_index in 0..header.slot_count as usize {
+                    if read_slot(&page_guard, slot_index)?.is_some() {
+                        live += 1;
+                    }
+                }
+                Ok::<_, ExecutionError>((header, live))
+            };
+            self.buffer_pool.unpin_page(page_id, false)?;
+            let (header, live) = result?;
+            tuple_count += live;
+            page_count += 1;
+            current_page_id = header.next_page_id;
+        }
+        Ok((tuple_count, page_count))
+    }
+
+    /// Every page the table occupies: its page chain and the pages of each
+    /// out-of-line blob of a live row. The heap pages are locked exclusively,
+    /// so the table is quiet once this returns inside a transaction.
+    pub fn page_ids(&self, schema: &Schema) -> ExecutionResult<Vec<PageId>> {
+        let mut page_ids = Vec::new();
+        let mut blobs = Vec::new();
+        let mut current_page_id = self.first_page_id()?;
+        while let Some(page_id) = current_page_id {
+            let result = {
+                let page_guard = self.fetch_page_exclusive(page_id)?;
+                let header = read_header(&page_guard)?;
                 let mut tuples = Vec::new();
                 for slot_index in 0..header.slot_count as usize {
+                    if let Some(slot) = read_slot(&page_guard, slot_index)? {
+                        tuples.push(read_tuple_bytes(&page_guard, &slot)?);
+                    }
+                }
+                Ok::<_, ExecutionError>((header, tuples))
+            };
+            self.buffer_pool.unpin_page(page_id, false)?;
+            let (header, tuples) = result?;
+            for bytes in tuples {
+                decode_tuple_with(schema, &bytes, &mut |pointer| {
+                    blobs.push(pointer);
+                    Ok(Vec::new())
+                })?;
+            }
+            page_ids.push(page_id);
+            current_page_id = header.next_page_id;
+        }
+        for pointer in blobs {
+            page_ids.extend(self.blob_store.page_ids(pointer)?);
+        }
+        Ok(page_ids)
+    }
+
+    /// Returns the number of live rows visible to the current transaction.
+    /// This is O(1) once the table has been counted; it falls back to
+    /// [`count_tuples`](Self::count_tuples) while the count is unknown.
+    pub fn row_count(&self) -> ExecutionResult<usize> {
+        let changes = {
+            let row_count = self.row_count_guard()?;
+            if let Some(committed) = row_count.committed {
+                let own = wal::current_txn_id()
+                    .and_then(|txn_id| row_count.pending.get(&txn_id).copied())
+                    .unwrap_or(0);
+                return Ok((committed + own).max(0) as usize);
+            }
+            row_count.changes
+        };
+        // counted without the mutex, since the scan may wait on page locks
+        let (counted, _) = self.count_tuples()?;
+        let mut row_count = self.row_count_guard()?;
+        // uncommitted rows of a transaction would otherwise be baked in
+        if row_count.changes == changes && row_count.pending.is_empty() {
+            row_count.committed = Some(counted as i64);
+        }
+        Ok(counted)
+    }
+
+    /// Makes the row count changes of a committed transaction visible to
+    /// every transaction.
+    pub fn commit_row_count(&self, txn_id: wal::TxnId) -> ExecutionResult<()> {
+        let mut row_count = self.row_count_guard()?;
+        row_count.changes += 1;
+        if let Some(delta) = row_count.pending.remove(&txn_id) {
+            if let Some(committed) = row_count.committed.as_mut() {
+                *committed += delta;
+            }
+        }
+        Ok(())
+    }
+
+    /// Drops the row count changes of an aborted transaction.
+    pub fn abort_row_count(&self, txn_id: wal::TxnId) -> ExecutionResult<()> {
+        self.row_count_guard()?.pending.remove(&txn_id);
+        Ok(())
+    }
+
+    /// Forgets the maintained count after part of a transaction was undone,
+    /// e.g. by `ROLLBACK TO SAVEPOINT`; the table is recounted on demand.
+    pub fn invalidate_row_count(&self, txn_id: wal::TxnId) -> ExecutionResult<()> {
+        let mut row_count = self.row_count_guard()?;
+        row_count.changes += 1;
+        row_count.committed = None;
+        row_count.pending.remove(&txn_id);
+        Ok(())
+    }
+
+    /// Whether transaction `txn_id` changed rows here after its log reached
+    /// `lsn`; those changes are undone by rolling it back to `lsn`.
+    pub fn written_after(&self, txn_id: wal::TxnId, lsn: wal::Lsn) -> ExecutionResult<bool> {
+        Ok(self
+            .writes_guard()?
+            .get(&txn_id)
+            .is_some_and(|last| *last > lsn))
+    }
+
+    /// Forgets the changes of a transaction that has ended.
+    pub fn forget_writes(&self, txn_id: wal::TxnId) -> ExecutionResult<()> {
+        self.writes_guard()?.remove(&txn_id);
+        Ok(())
+    }
+
+    fn note_write(&self) -> ExecutionResult<()> {
+        let Some(txn) = wal::current_txn_handle() else {
+            return Ok(());
+        };
+        let (txn_id, last_lsn) = {
+            let txn = txn.lock();
+            (txn.txn_id, txn.last_lsn.unwrap_or_default())
+        };
+        self.writes_guard()?.insert(txn_id, last_lsn);
+        Ok(())
+    }
+
+    fn writes_guard(&self) -> ExecutionResult<MutexGuard<'_, HashMap<wal::TxnId, wal::Lsn>>> {
+        self.writes
+            .lock()
+            .map_err(|_| ExecutionError::Execution("table heap lock poisoned".to_string()))
+    }
+
+    fn adjust_row_count(&self, delta: i64) -> ExecutionResult<()> {
+        self.note_write()?;
+        let mut row_count = self.row_count_guard()?;
+        row_count.changes += 1;
+        match wal::current_txn_id() {
+            Some(txn_id) => *row_count.pending.entry(txn_id).or_default() += delta,
+            None => {
+                if let Some(committed) = row_count.committed.as_mut() {
+                    *committed += delta;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn scan_tuples(&self, schema: &Schema) -> ExecutionResult<Vec<(Rid, Tuple)>> {
+        self.scan_tuples_after(schema, None)
+    }
+
+    /// Scans the rows that follow `cursor` in scan order, or the whole table
+    /// when there is no cursor.
+    pub fn scan_tuples_after(
+        &self,
+        schema: &Schema,
+        cursor: Option<ScanCursor>,
+    ) -> ExecutionResult<Vec<(Rid, Tuple)>> {
+        let mut output = Vec::new();
+        let (mut current_page_id, mut start_slot) = self.scan_start(cursor)?;
+        while let Some(page_id) = current_page_id {
+            let result = {
+                let page_guard = self.fetch_page_for_scan(page_id, start_slot)?;
+                let header = read_header(&page_guard)?;
+                let mut tuples = Vec::new();
+                for slot_index in start_slot..header.slot_count as usize {
                     if let Some(slot) = read_slot(&page_guard, slot_index)? {
                         let tuple_bytes = read_tuple_bytes(&page_guard, &slot)?;
                         tuples.push((
@@ -443,14 +822,41 @@ impl TableHeap {
                 output.push((rid, tuple));
             }
             current_page_id = header.next_page_id;
+            start_slot = 0;
         }
         Ok(output)
     }
 
+    /// The page and slot a scan resuming after `cursor` starts from. The
+    /// cursor's page must still be in this table's page chain.
+    fn scan_start(&self, cursor: Option<ScanCursor>) -> ExecutionResult<(Option<PageId>, usize)> {
+        let first_page_id = self.first_page_id()?;
+        let Some(cursor) = cursor else {
+            return Ok((first_page_id, 0));
+        };
+        let mut current_page_id = first_page_id;
+        while let Some(page_id) = current_page_id {
+            if page_id == cursor.last.page_id {
+                return Ok((Some(page_id), cursor.last.slot_id as usize + 1));
+            }
+            let header = {
+                let page_guard = self.fetch_page_with_lock(page_id, LockMode::Shared)?;
+                read_header(&page_guard)
+            };
+            self.buffer_pool.unpin_page(page_id, false)?;
+            current_page_id = header?.next_page_id;
+        }
+        Err(ExecutionError::Execution(format!(
+            "scan cursor page {} is not part of this table",
+            cursor.last.page_id
+        )))
+    }
+
     fn allocate_page(&self) -> ExecutionResult<PageId> {
         let page_id = self.buffer_pool.new_page()?.ok_or_else(|| {
             ExecutionError::Execution("buffer pool has no free frames".to_string())
         })?;
+        self.buffer_pool.unpin_page(page_id, false)?;
         {
             let mut page_guard = self.fetch_page_exclusive(page_id)?;
             initialize_page(&mut page_guard)?;
@@ -464,6 +870,18 @@ impl TableHeap {
             .lock()
             .map_err(|_| ExecutionError::Execution("table heap lock poisoned".to_string()))
     }
+
+    fn extend_guard(&self) -> ExecutionResult<MutexGuard<'_, ()>> {
+        self.extend_lock
+            .lock()
+            .map_err(|_| ExecutionError::Execution("table heap lock poisoned".to_string()))
+    }
+
+    fn row_count_guard(&self) -> ExecutionResult<MutexGuard<'_, RowCount>> {
+        self.row_count
+            .lock()
+            .map_err(|_| ExecutionError::Execution("table heap lock poisoned".to_string()))
+    }
 }
 
 pub struct SeqScan {
@@ -471,6 +889,14 @@ pub struct SeqScan {
     schema: Schema,
     current_page_id: Option<PageId>,
     current_slot: usize,
+    schema_version: Option<SchemaVersion>,
+    start: Option<ScanCursor>,
+    last_rid: Option<Rid>,
+    sampler: Option<Sampler>,
+    /// Snapshot of the transaction the scan was opened in, if it has one.
+    /// The scan then takes no locks and returns the rows as the snapshot
+    /// sees them.
+    snapshot: Option<SnapshotView>,
 }
 
 impl SeqScan {
@@ -480,14 +906,59 @@ impl SeqScan {
             schema,
             current_page_id: None,
             current_slot: 0,
+            schema_version: None,
+            start: None,
+            last_rid: None,
+            sampler: None,
+            snapshot: None,
         }
     }
+
+    /// Returns only the rows `sample` keeps (`TABLESAMPLE`).
+    pub fn with_sample(mut self, sample: TableSample) -> Self {
+        self.sampler = Some(Sampler::new(sample));
+        self
+    }
+
+    /// Starts the scan after `cursor` instead of at the first row.
+    pub fn with_cursor(mut self, cursor: ScanCursor) -> Self {
+        self.start = Some(cursor);
+        self
+    }
+
+    /// Where the scan stands: resuming from this cursor continues with the
+    /// row `next` would have returned. `None` until the scan has a position.
+    pub fn cursor(&self) -> Option<ScanCursor> {
+        self.last_rid.map(ScanCursor::after).or(self.start)
+    }
+
+    /// How many writers of changed rows the scan's snapshot has decided
+    /// whether it sees; each is decided once per scan, however many of its
+    /// rows the scan reads.
+    pub fn visibility_decisions(&self) -> usize {
+        self.snapshot.as_ref().map_or(0, SnapshotView::decisions)
+    }
+
+    /// Fails `open` once the scanned table has changed since planning.
+    pub fn with_schema_version(mut self, version: SchemaVersion) -> Self {
+        self.schema_version = Some(version);
+        self
+    }
 }
 
 impl PhysicalOperator for SeqScan {
     fn open(&mut self) -> ExecutionResult<()> {
-        self.current_page_id = self.table_heap.first_page_id()?;
-        self.current_slot = 0;
+        if let Some(version) = &self.schema_version {
+            version.check()?;
+        }
+        let (page_id, slot) = self.table_heap.scan_start(self.start)?;
+        self.current_page_id = page_id;
+        self.current_slot = slot;
+        self.last_rid = None;
+        self.snapshot = wal::current_snapshot().map(SnapshotView::new);
+        if let Some(sampler) = &mut self.sampler {
+            sampler.reset();
+        }
         Ok(())
     }
 
@@ -499,9 +970,14 @@ impl PhysicalOperator for SeqScan {
             };
 
             let (header, tuple, advance_page) = {
-                let page_guard = self
-                    .table_heap
-                    .fetch_page_with_lock(page_id, LockMode::Shared)?;
+                let rid = Rid {
+                    page_id,
+                    slot_id: self.current_slot as u32,
+                };
+                let page_guard = match self.snapshot {
+                    Some(_) => self.table_heap.fetch_page_unlocked(page_id)?,
+                    None => self.table_heap.fetch_row_page(rid, LockMode::Shared)?,
+                };
                 let header = read_header(&page_guard)?;
                 let mut tuple = None;
                 let mut advance_page = false;
@@ -510,8 +986,18 @@ impl PhysicalOperator for SeqScan {
                 } else {
                     let slot_index = self.current_slot;
                     self.current_slot += 1;
-                    if let Some(slot) = read_slot(&page_guard, slot_index)? {
-                        let tuple_bytes = read_tuple_bytes(&page_guard, &slot)?;
+                    let sampled = self
+                        .sampler
+                        .as_mut()
+                        .is_none_or(|sampler| sampler.keep(page_id));
+                    let mut tuple_bytes = match read_slot(&page_guard, slot_index)? {
+                        Some(slot) if sampled => Some(read_tuple_bytes(&page_guard, &slot)?),
+                        _ => None,
+                    };
+                    if let (Some(snapshot), true) = (&mut self.snapshot, sampled) {
+                        tuple_bytes = self.table_heap.versions.visible(rid, tuple_bytes, snapshot);
+                    }
+                    if let Some(tuple_bytes) = tuple_bytes {
                         tuple = Some(decode_tuple(
                             &self.schema,
                             &tuple_bytes,
@@ -531,6 +1017,10 @@ impl PhysicalOperator for SeqScan {
             }
 
             if let Some(tuple) = tuple {
+                self.last_rid = Some(Rid {
+                    page_id,
+                    slot_id: self.current_slot as u32 - 1,
+                });
                 return Ok(Some(tuple));
             }
         }
@@ -545,6 +1035,70 @@ impl PhysicalOperator for SeqScan {
     fn as_any(&self) -> &dyn Any {
         self
     }
+
+    fn last_rid(&self) -> Option<Rid> {
+        self.last_rid
+    }
+
+    fn describe(&self) -> String {
+        let table = self
+            .schema
+            .fields
+            .first()
+            .and_then(|field| field.table.as_deref())
+            .unwrap_or("?");
+        match &self.sampler {
+            Some(sampler) => format!("SeqScan: {} {}", table, sampler.sample),
+            None => format!("SeqScan: {}", table),
+        }
+    }
+}
+
+/// Random source of a `TABLESAMPLE` scan. It is reseeded on every `open`, so
+/// a `REPEATABLE` seed picks the same rows each time.
+struct Sampler {
+    sample: TableSample,
+    rng: StdRng,
+    /// Keep decision for the current page of a SYSTEM sample.
+    page: Option<(PageId, bool)>,
+}
+
+impl Sampler {
+    fn new(sample: TableSample) -> Self {
+        Self {
+            sample,
+            rng: Self::rng(sample.seed),
+            page: None,
+        }
+    }
+
+    fn rng(seed: Option<u64>) -> StdRng {
+        match seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        }
+    }
+
+    fn reset(&mut self) {
+        self.rng = Self::rng(self.sample.seed);
+        self.page = None;
+    }
+
+    /// Decides whether the next row, stored on `page_id`, is returned.
+    fn keep(&mut self, page_id: PageId) -> bool {
+        let probability = (self.sample.percent / 100.0).clamp(0.0, 1.0);
+        match self.sample.method {
+            SampleMethod::Bernoulli => self.rng.gen_bool(probability),
+            SampleMethod::System => match self.page {
+                Some((sampled, keep)) if sampled == page_id => keep,
+                _ => {
+                    let keep = self.rng.gen_bool(probability);
+                    self.page = Some((page_id, keep));
+                    keep
+                }
+            },
+        }
+    }
 }
 
 #[derive(Clone, Copy)]
@@ -578,7 +1132,7 @@ fn initialize_page(page: &mut Page) -> ExecutionResult<()> {
 
 fn read_header(page: &Page) -> ExecutionResult<TablePageHeader> {
     let header_bytes = page
-        .read_bytes(PAGE_LSN_SIZE, HEADER_DATA_SIZE)
+        .read_bytes(PAGE_HEADER_SIZE, HEADER_DATA_SIZE)
         .ok_or_else(|| ExecutionError::Execution("failed to read table page header".to_string()))?;
     let next_page_id = read_u64(&header_bytes[0..8]);
     let slot_count = read_u32(&header_bytes[8..12]);
@@ -642,7 +1196,7 @@ fn write_header(page: &mut Page, header: &TablePageHeader) -> ExecutionResult<()
     bytes[0..8].copy_from_slice(&next_page_id.to_le_bytes());
     bytes[8..12].copy_from_slice(&header.slot_count.to_le_bytes());
     bytes[12..16].copy_from_slice(&header.free_space_offset.to_le_bytes());
-    write_bytes_logged(page, PAGE_LSN_SIZE, &bytes)
+    write_bytes_logged(page, PAGE_HEADER_SIZE, &bytes)
 }
 
 fn read_slot(page: &Page, slot_index: usize) -> ExecutionResult<Option<TableSlot>> {
@@ -680,13 +1234,23 @@ fn write_slot(page: &mut Page, slot_index: usize, slot: TableSlot) -> ExecutionR
         .map_err(|_| ExecutionError::Execution("failed to write slot".to_string()))
 }
 
+/// FNV-1a over the blob contents.
+fn blob_checksum(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
 fn blob_payload_offset() -> usize {
-    PAGE_LSN_SIZE + 12
+    PAGE_HEADER_SIZE + 12
 }
 
 fn read_blob_header(page: &Page) -> ExecutionResult<(PageId, u32)> {
     let header_bytes = page
-        .read_bytes(PAGE_LSN_SIZE, 12)
+        .read_bytes(PAGE_HEADER_SIZE, 12)
         .ok_or_else(|| ExecutionError::Execution("failed to read blob page header".to_string()))?;
     let next_page = read_u64(&header_bytes[0..8]);
     let payload_len = read_u32(&header_bytes[8..12]);
@@ -697,7 +1261,7 @@ fn write_blob_header(page: &mut Page, next_page: PageId, payload_len: u32) -> Ex
     let mut header = [0u8; 12];
     header[0..8].copy_from_slice(&next_page.to_le_bytes());
     header[8..12].copy_from_slice(&payload_len.to_le_bytes());
-    if !page.write_bytes(PAGE_LSN_SIZE, &header) {
+    if !page.write_bytes(PAGE_HEADER_SIZE, &header) {
         return Err(ExecutionError::Execution(
             "failed to write blob page header".to_string(),
         ));
@@ -715,6 +1279,16 @@ fn read_tuple_bytes(page: &Page, slot: &TableSlot) -> ExecutionResult<Vec<u8>> {
     Ok(data.to_vec())
 }
 
+/// Encodes a tuple in the compact layout:
+///
+/// ```text
+/// TUPLE_COMPACT field_count:varint bitmap { value }*
+/// ```
+///
+/// The bitmap has one null bit per field, plus a second bit per BOOLEAN
+/// field holding its value, so booleans and NULLs take no value bytes.
+/// INTEGER and BIGINT values are zigzag varints; other types keep their
+/// fixed-width or length-prefixed encoding.
 fn encode_tuple(
     tuple: &Tuple,
     schema: &Schema,
@@ -726,24 +1300,31 @@ fn encode_tuple(
         ));
     }
 
-    let mut buffer = Vec::new();
+    let mut buffer = vec![TUPLE_COMPACT];
+    put_varint(&mut buffer, schema.fields.len() as u64);
+    let bitmap_start = buffer.len();
+    buffer.resize(bitmap_start + bitmap_len(&schema.fields), 0);
+    let mut bit = 0usize;
     for (field, value) in schema.fields.iter().zip(tuple.values()) {
+        let null_bit = bit;
+        bit += 1;
+        if field.data_type == DataType::Boolean {
+            bit += 1;
+        }
         if value.is_null() {
-            buffer.push(1);
+            buffer[bitmap_start + null_bit / 8] |= 1 << (null_bit % 8);
             continue;
         }
-        buffer.push(0);
         match (&field.data_type, value) {
             (DataType::Integer, Value::Integer(number)) => {
-                buffer.extend_from_slice(
-                    &(i32::try_from(*number).map_err(|_| {
-                        ExecutionError::Execution("integer out of range".to_string())
-                    })?)
-                    .to_le_bytes(),
-                );
+                i32::try_from(*number)
+                    .map_err(|_| ExecutionError::Execution("integer out of range".to_string()))?;
+                put_varint(&mut buffer, zigzag(*number));
+            }
+            (DataType::BigInt, Value::Integer(number)) => {
+                put_varint(&mut buffer, zigzag(*number));
             }
-            (DataType::BigInt, Value::Integer(number))
-            | (DataType::Timestamp, Value::Integer(number))
+            (DataType::Timestamp, Value::Integer(number))
             | (DataType::Timestamp, Value::Timestamp(number)) => {
                 buffer.extend_from_slice(&number.to_le_bytes());
             }
@@ -751,7 +1332,10 @@ fn encode_tuple(
                 buffer.extend_from_slice(&number.to_le_bytes());
             }
             (DataType::Boolean, Value::Boolean(flag)) => {
-                buffer.push(u8::from(*flag));
+                if *flag {
+                    let value_bit = null_bit + 1;
+                    buffer[bitmap_start + value_bit / 8] |= 1 << (value_bit % 8);
+                }
             }
             (DataType::Text, Value::String(text)) => {
                 let text_bytes = text.as_bytes();
@@ -762,16 +1346,17 @@ fn encode_tuple(
             }
             (DataType::Blob, Value::Blob(bytes)) => {
                 if bytes.len() <= INLINE_BLOB_LIMIT {
-                    buffer.push(0);
+                    buffer.push(BLOB_INLINE);
                     let len = u32::try_from(bytes.len())
                         .map_err(|_| ExecutionError::Execution("blob too large".to_string()))?;
                     buffer.extend_from_slice(&len.to_le_bytes());
                     buffer.extend_from_slice(bytes);
                 } else {
-                    buffer.push(1);
+                    buffer.push(BLOB_OUT_OF_LINE_CHECKSUMMED);
                     let pointer = blob_store.write_blob(bytes)?;
                     buffer.extend_from_slice(&pointer.first_page_id.to_le_bytes());
                     buffer.extend_from_slice(&pointer.length.to_le_bytes());
+                    buffer.extend_from_slice(&pointer.checksum.to_le_bytes());
                 }
             }
             (_, other) => {
@@ -786,116 +1371,193 @@ fn encode_tuple(
     Ok(buffer)
 }
 
+/// Bytes of null and boolean bits for `fields`.
+fn bitmap_len(fields: &[Field]) -> usize {
+    let booleans = fields
+        .iter()
+        .filter(|field| field.data_type == DataType::Boolean)
+        .count();
+    (fields.len() + booleans).div_ceil(8)
+}
+
 fn decode_tuple(schema: &Schema, data: &[u8], blob_store: &BlobStore) -> ExecutionResult<Tuple> {
-    let mut cursor = 0usize;
-    let mut values = Vec::with_capacity(schema.fields.len());
+    decode_tuple_with(schema, data, &mut |pointer| blob_store.read_blob(pointer))
+}
 
-    for field in &schema.fields {
-        if cursor >= data.len() {
-            values.push(Value::Null);
-            continue;
+/// Callback that produces the bytes of an out-of-line blob.
+type BlobReader<'a> = dyn FnMut(BlobPointer) -> ExecutionResult<Vec<u8>> + 'a;
+
+/// Decodes a tuple, handing each out-of-line blob to `read_blob`.
+fn decode_tuple_with(
+    schema: &Schema,
+    data: &[u8],
+    read_blob: &mut BlobReader<'_>,
+) -> ExecutionResult<Tuple> {
+    if data.first() != Some(&TUPLE_COMPACT) {
+        return Err(ExecutionError::Execution(
+            "tuple has an unknown layout".to_string(),
+        ));
+    }
+    let mut cursor = 1usize;
+    let stored = usize::try_from(read_varint(data, &mut cursor)?)
+        .ok()
+        .filter(|stored| *stored <= schema.fields.len())
+        .ok_or_else(|| ExecutionError::Execution("tuple has too many fields".to_string()))?;
+    // columns added after the row was written are NULL
+    let stored_fields = &schema.fields[..stored];
+    let bitmap = read_exact(data, cursor, bitmap_len(stored_fields))?;
+    cursor += bitmap.len();
+    let bit_set = |bit: usize| bitmap[bit / 8] & (1 << (bit % 8)) != 0;
+
+    let mut values = Vec::with_capacity(schema.fields.len());
+    let mut bit = 0usize;
+    for field in stored_fields {
+        let null_bit = bit;
+        bit += 1;
+        if field.data_type == DataType::Boolean {
+            bit += 1;
         }
-        let is_null = data[cursor] == 1;
-        cursor += 1;
-        if is_null {
+        if bit_set(null_bit) {
             values.push(Value::Null);
             continue;
         }
-
-        match field.data_type {
-            DataType::Integer => {
-                let bytes = read_exact(data, cursor, 4)?;
-                let number = i32::from_le_bytes(bytes.try_into().unwrap()) as i64;
-                values.push(Value::Integer(number));
-                cursor += 4;
-            }
-            DataType::BigInt => {
-                let bytes = read_exact(data, cursor, 8)?;
-                let number = i64::from_le_bytes(bytes.try_into().unwrap());
-                values.push(Value::Integer(number));
-                cursor += 8;
+        let value = match field.data_type {
+            DataType::Integer | DataType::BigInt => {
+                Value::Integer(unzigzag(read_varint(data, &mut cursor)?))
             }
-            DataType::Real => {
-                let bytes = read_exact(data, cursor, 8)?;
-                let number = f64::from_le_bytes(bytes.try_into().unwrap());
-                values.push(Value::Float(number));
-                cursor += 8;
-            }
-            DataType::Boolean => {
-                let flag = data.get(cursor).ok_or_else(|| {
-                    ExecutionError::Execution("tuple bytes truncated".to_string())
-                })?;
-                values.push(Value::Boolean(*flag != 0));
-                cursor += 1;
-            }
-            DataType::Text => {
-                let length_bytes = read_exact(data, cursor, 4)?;
-                let length = u32::from_le_bytes(length_bytes.try_into().unwrap()) as usize;
-                cursor += 4;
-                let text_bytes = read_exact(data, cursor, length)?;
-                let text = String::from_utf8(text_bytes.to_vec())
-                    .map_err(|_| ExecutionError::Execution("invalid utf8 string".to_string()))?;
-                values.push(Value::String(text));
-                cursor += length;
-            }
-            DataType::Timestamp => {
-                let bytes = read_exact(data, cursor, 8)?;
-                let number = i64::from_le_bytes(bytes.try_into().unwrap());
-                values.push(Value::Timestamp(number));
-                cursor += 8;
-            }
-            DataType::Blob => {
-                let flag = data.get(cursor).ok_or_else(|| {
-                    ExecutionError::Execution("tuple bytes truncated".to_string())
-                })?;
-                cursor += 1;
-                match flag {
-                    0 => {
-                        let length_bytes = read_exact(data, cursor, 4)?;
-                        let length = u32::from_le_bytes(length_bytes.try_into().unwrap()) as usize;
-                        cursor += 4;
-                        let blob_bytes = read_exact(data, cursor, length)?;
-                        values.push(Value::Blob(blob_bytes.to_vec()));
-                        cursor += length;
-                    }
-                    1 => {
-                        let page_bytes = read_exact(data, cursor, 8)?;
-                        let page_id = u64::from_le_bytes(page_bytes.try_into().unwrap());
-                        cursor += 8;
-                        let length_bytes = read_exact(data, cursor, 4)?;
-                        let length = u32::from_le_bytes(length_bytes.try_into().unwrap());
-                        cursor += 4;
-                        let blob = blob_store.read_blob(BlobPointer {
-                            first_page_id: page_id,
-                            length,
-                        })?;
-                        values.push(Value::Blob(blob));
-                    }
-                    _ => {
-                        return Err(ExecutionError::Execution(
-                            "invalid blob storage flag".to_string(),
-                        ));
-                    }
+            DataType::Boolean => Value::Boolean(bit_set(null_bit + 1)),
+            _ => decode_value(&field.data_type, data, &mut cursor, read_blob)?,
+        };
+        values.push(value);
+    }
+    values.resize(schema.fields.len(), Value::Null);
+
+    Ok(Tuple::new(values))
+}
+
+/// Decodes a REAL, TEXT, TIMESTAMP or BLOB value and advances `cursor` past
+/// it.
+fn decode_value(
+    data_type: &DataType,
+    data: &[u8],
+    cursor: &mut usize,
+    read_blob: &mut BlobReader<'_>,
+) -> ExecutionResult<Value> {
+    match data_type {
+        DataType::Real => {
+            let bytes = read_exact(data, *cursor, 8)?;
+            *cursor += 8;
+            Ok(Value::Float(f64::from_le_bytes(bytes.try_into().unwrap())))
+        }
+        DataType::Text => {
+            let length_bytes = read_exact(data, *cursor, 4)?;
+            let length = u32::from_le_bytes(length_bytes.try_into().unwrap()) as usize;
+            *cursor += 4;
+            let text_bytes = read_exact(data, *cursor, length)?;
+            let text = String::from_utf8(text_bytes.to_vec())
+                .map_err(|_| ExecutionError::Execution("invalid utf8 string".to_string()))?;
+            *cursor += length;
+            Ok(Value::String(text))
+        }
+        DataType::Timestamp => {
+            let bytes = read_exact(data, *cursor, 8)?;
+            *cursor += 8;
+            Ok(Value::Timestamp(i64::from_le_bytes(
+                bytes.try_into().unwrap(),
+            )))
+        }
+        DataType::Blob => {
+            let flag = *data
+                .get(*cursor)
+                .ok_or_else(|| ExecutionError::Execution("tuple bytes truncated".to_string()))?;
+            *cursor += 1;
+            match flag {
+                BLOB_INLINE => {
+                    let length_bytes = read_exact(data, *cursor, 4)?;
+                    let length = u32::from_le_bytes(length_bytes.try_into().unwrap()) as usize;
+                    *cursor += 4;
+                    let blob_bytes = read_exact(data, *cursor, length)?;
+                    *cursor += length;
+                    Ok(Value::Blob(blob_bytes.to_vec()))
                 }
+                BLOB_OUT_OF_LINE_CHECKSUMMED => {
+                    let page_bytes = read_exact(data, *cursor, 8)?;
+                    let page_id = u64::from_le_bytes(page_bytes.try_into().unwrap());
+                    *cursor += 8;
+                    let length_bytes = read_exact(data, *cursor, 4)?;
+                    let length = u32::from_le_bytes(length_bytes.try_into().unwrap());
+                    *cursor += 4;
+                    let checksum_bytes = read_exact(data, *cursor, 8)?;
+                    *cursor += 8;
+                    let checksum = u64::from_le_bytes(checksum_bytes.try_into().unwrap());
+                    let blob = read_blob(BlobPointer {
+                        first_page_id: page_id,
+                        length,
+                        checksum,
+                    })?;
+                    Ok(Value::Blob(blob))
+                }
+                _ => Err(ExecutionError::Execution(
+                    "invalid blob storage flag".to_string(),
+                )),
             }
         }
+        DataType::Integer | DataType::BigInt | DataType::Boolean => Err(ExecutionError::Execution(
+            format!("{:?} values are encoded by the tuple layout", data_type),
+        )),
+        DataType::Unknown => Err(ExecutionError::Execution(
+            "stored columns have a known type".to_string(),
+        )),
     }
+}
 
-    Ok(Tuple::new(values))
+/// Maps signed integers to unsigned ones so small magnitudes of either sign
+/// get short varints.
+fn zigzag(number: i64) -> u64 {
+    ((number << 1) ^ (number >> 63)) as u64
+}
+
+pub(crate) fn unzigzag(encoded: u64) -> i64 {
+    ((encoded >> 1) as i64) ^ -((encoded & 1) as i64)
+}
+
+/// LEB128: seven bits per byte, low bits first, high bit set on every byte
+/// but the last.
+fn put_varint(buffer: &mut Vec<u8>, mut number: u64) {
+    while number >= 0x80 {
+        buffer.push((number as u8) | 0x80);
+        number >>= 7;
+    }
+    buffer.push(number as u8);
+}
+
+pub(crate) fn read_varint(data: &[u8], cursor: &mut usize) -> ExecutionResult<u64> {
+    let mut number = 0u64;
+    for shift in (0..64).step_by(7) {
+        let byte = *data
+            .get(*cursor)
+            .ok_or_else(|| ExecutionError::Execution("tuple bytes truncated".to_string()))?;
+        *cursor += 1;
+        number |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(number);
+        }
+    }
+    Err(ExecutionError::Execution("varint too long".to_string()))
 }
 
-fn read_exact(data: &[u8], offset: usize, len: usize) -> ExecutionResult<&[u8]> {
+pub(crate) fn read_exact(data: &[u8], offset: usize, len: usize) -> ExecutionResult<&[u8]> {
     data.get(offset..offset + len)
         .ok_or_else(|| ExecutionError::Execution("tuple bytes truncated".to_string()))
 }
 
-fn read_u32(bytes: &[u8]) -> u32 {
+pub(crate) fn read_u32(bytes: &[u8]) -> u32 {
     let mut array = [0u8; 4];
     array.copy_from_slice(bytes);
     u32::from_le_bytes(array)
 }
 
-fn read_u64(bytes: &[u8]) -> u64 {
+pub(crate) fn read_u64(bytes: &[u8]) -> u64 {
     let mut array = [0u8; 8];
     array.copy_from_slice(bytes);
     u64::from_le_bytes(array)
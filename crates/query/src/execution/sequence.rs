@@ -0,0 +1,145 @@
+use crate::execution::operator::{ExecutionError, ExecutionResult};
+use parking_lot::Mutex;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Stored form of a sequence: the value the next `NEXTVAL` returns and the
+/// step after it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SequenceDef {
+    pub name: String,
+    pub next_value: i64,
+    pub increment: i64,
+}
+
+struct Sequence {
+    def: SequenceDef,
+    /// Last value `NEXTVAL` returned since the catalog was loaded.
+    current: Option<i64>,
+}
+
+/// Named sequences of a catalog (`CREATE SEQUENCE`). Values are handed out
+/// outside transactions, so a value taken by a rolled back statement is
+/// never reissued.
+#[derive(Clone, Default)]
+pub struct Sequences {
+    sequences: Arc<Mutex<HashMap<String, Sequence>>>,
+    /// Set when `NEXTVAL` advances a sequence, until the catalog persists it.
+    advanced: Arc<AtomicBool>,
+}
+
+impl Sequences {
+    pub fn create(&self, def: SequenceDef) -> ExecutionResult<()> {
+        if def.increment == 0 {
+            return Err(ExecutionError::Schema(format!(
+                "sequence {} must have a non-zero increment",
+                def.name
+            )));
+        }
+        let mut sequences = self.sequences.lock();
+        let key = def.name.to_lowercase();
+        if sequences.contains_key(&key) {
+            return Err(ExecutionError::Schema(format!(
+                "sequence {} already exists",
+                def.name
+            )));
+        }
+        sequences.insert(key, Sequence { def, current: None });
+        Ok(())
+    }
+
+    pub fn drop_sequence(&self, name: &str) -> ExecutionResult<()> {
+        self.sequences
+            .lock()
+            .remove(&name.to_lowercase())
+            .map(|_| ())
+            .ok_or_else(|| sequence_not_found(name))
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.sequences.lock().contains_key(&name.to_lowercase())
+    }
+
+    /// Advances the sequence and returns its new value.
+    pub fn next_value(&self, name: &str) -> ExecutionResult<i64> {
+        let mut sequences = self.sequences.lock();
+        let sequence = sequences
+            .get_mut(&name.to_lowercase())
+            .ok_or_else(|| sequence_not_found(name))?;
+        let value = sequence.def.next_value;
+        sequence.def.next_value = value.checked_add(sequence.def.increment).ok_or_else(|| {
+            ExecutionError::Execution(format!("sequence {} reached its limit", sequence.def.name))
+        })?;
+        sequence.current = Some(value);
+        self.advanced.store(true, Ordering::Release);
+        Ok(value)
+    }
+
+    /// Returns the value `NEXTVAL` last returned for the sequence.
+    pub fn current_value(&self, name: &str) -> ExecutionResult<i64> {
+        let sequences = self.sequences.lock();
+        let sequence = sequences
+            .get(&name.to_lowercase())
+            .ok_or_else(|| sequence_not_found(name))?;
+        sequence.current.ok_or_else(|| {
+            ExecutionError::Execution(format!(
+                "CURRVAL of sequence {} is not yet defined; call NEXTVAL first",
+                sequence.def.name
+            ))
+        })
+    }
+
+    /// Every sequence, ordered by name.
+    pub fn definitions(&self) -> Vec<SequenceDef> {
+        let mut defs = self
+            .sequences
+            .lock()
+            .values()
+            .map(|sequence| sequence.def.clone())
+            .collect::<Vec<_>>();
+        defs.sort_by(|left, right| left.name.cmp(&right.name));
+        defs
+    }
+
+    /// True once if `NEXTVAL` advanced a sequence since the last call.
+    pub fn take_advanced(&self) -> bool {
+        self.advanced.swap(false, Ordering::AcqRel)
+    }
+
+    /// Runs `f` with `NEXTVAL` and `CURRVAL` resolving against these
+    /// sequences on the current thread.
+    pub fn with_current<R>(&self, f: impl FnOnce() -> R) -> R {
+        let previous = CURRENT_SEQUENCES.with(|cell| cell.replace(Some(self.clone())));
+        let result = f();
+        CURRENT_SEQUENCES.with(|cell| cell.replace(previous));
+        result
+    }
+}
+
+thread_local! {
+    static CURRENT_SEQUENCES: RefCell<Option<Sequences>> = const { RefCell::new(None) };
+}
+
+/// `NEXTVAL(name)` against the sequences set by [`Sequences::with_current`].
+pub fn next_value(name: &str) -> ExecutionResult<i64> {
+    current_sequences()?.next_value(name)
+}
+
+/// `CURRVAL(name)` against the sequences set by [`Sequences::with_current`].
+pub fn current_value(name: &str) -> ExecutionResult<i64> {
+    current_sequences()?.current_value(name)
+}
+
+fn current_sequences() -> ExecutionResult<Sequences> {
+    CURRENT_SEQUENCES
+        .with(|cell| cell.borrow().clone())
+        .ok_or_else(|| {
+            ExecutionError::Execution("sequences are not available outside the engine".to_string())
+        })
+}
+
+fn sequence_not_found(name: &str) -> ExecutionError {
+    ExecutionError::Schema(format!("sequence {} does not exist", name))
+}
@@ -0,0 +1,44 @@
+use crate::execution::operator::{ExecutionResult, PhysicalOperator};
+use crate::execution::tuple::Tuple;
+use std::any::Any;
+
+/// Produces a single empty row, so a projection over it evaluates a SELECT
+/// without FROM exactly once.
+#[derive(Default)]
+pub struct SingleRow {
+    done: bool,
+}
+
+impl SingleRow {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl PhysicalOperator for SingleRow {
+    fn open(&mut self) -> ExecutionResult<()> {
+        self.done = false;
+        Ok(())
+    }
+
+    fn next(&mut self) -> ExecutionResult<Option<Tuple>> {
+        if self.done {
+            return Ok(None);
+        }
+        self.done = true;
+        Ok(Some(Tuple::new(Vec::new())))
+    }
+
+    fn close(&mut self) -> ExecutionResult<()> {
+        self.done = true;
+        Ok(())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn describe(&self) -> String {
+        "SingleRow".to_string()
+    }
+}
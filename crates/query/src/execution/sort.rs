@@ -0,0 +1,268 @@
+use crate::execution::operator::{
+    compare_values, evaluate_expr, ExecutionError, ExecutionResult, PhysicalOperator,
+};
+use crate::execution::spill::{SpillReader, SpillWriter, TempUsage, DEFAULT_WORK_MEM};
+use crate::execution::tuple::{Tuple, Value};
+use crate::logical_plan::SortExpr;
+use crate::schema::Schema;
+use std::any::Any;
+use std::cmp::Ordering;
+
+/// Materializes its input and returns it ordered by `sort_exprs`. The sort is
+/// stable, so rows with equal keys keep their input order.
+///
+/// Input beyond `work_mem` bytes is sorted in batches, each spilled to a
+/// temporary file as a run, and the runs are merged as rows are returned.
+pub struct Sort {
+    child: Box<dyn PhysicalOperator>,
+    sort_exprs: Vec<SortExpr>,
+    schema: Schema,
+    work_mem: usize,
+    temp_usage: TempUsage,
+    temp_pages: u64,
+    sorted: Vec<Tuple>,
+    position: usize,
+    runs: Vec<Run>,
+}
+
+/// A spilled run and its next row, split into sort keys and the row.
+struct Run {
+    reader: SpillReader,
+    head: Option<(Vec<Value>, Tuple)>,
+}
+
+impl Run {
+    fn advance(&mut self, key_count: usize) -> ExecutionResult<()> {
+        self.head = self.reader.next()?.map(|row| {
+            let mut keys = row.values().to_vec();
+            let values = keys.split_off(key_count);
+            (keys, Tuple::new(values))
+        });
+        Ok(())
+    }
+}
+
+impl Sort {
+    pub fn new(
+        child: Box<dyn PhysicalOperator>,
+        sort_exprs: Vec<SortExpr>,
+        schema: Schema,
+    ) -> Self {
+        Self {
+            child,
+            sort_exprs,
+            schema,
+            work_mem: DEFAULT_WORK_MEM,
+            temp_usage: TempUsage::default(),
+            temp_pages: 0,
+            sorted: Vec::new(),
+            position: 0,
+            runs: Vec::new(),
+        }
+    }
+
+    /// Bytes of rows held in memory before a sorted run is spilled.
+    pub fn with_work_mem(mut self, bytes: usize) -> Self {
+        self.work_mem = bytes;
+        self
+    }
+
+    /// Counts the pages of the runs this sort spills in `usage` too.
+    pub fn with_temp_usage(mut self, usage: TempUsage) -> Self {
+        self.temp_usage = usage;
+        self
+    }
+
+    /// Temporary pages written by the last `open`; `0` when the input fit
+    /// in `work_mem`.
+    pub fn temp_pages(&self) -> u64 {
+        self.temp_pages
+    }
+
+    pub fn child(&self) -> &dyn PhysicalOperator {
+        &*self.child
+    }
+
+    fn compare_keys(&self, left: &[Value], right: &[Value]) -> ExecutionResult<Ordering> {
+        for (sort_expr, (left, right)) in self.sort_exprs.iter().zip(left.iter().zip(right)) {
+            let ordering = match (left.is_null(), right.is_null()) {
+                (true, true) => Ordering::Equal,
+                (true, false) if sort_expr.nulls_first => Ordering::Less,
+                (true, false) => Ordering::Greater,
+                (false, true) if sort_expr.nulls_first => Ordering::Greater,
+                (false, true) => Ordering::Less,
+                (false, false) => {
+                    let ordering = compare_sort_values(left, right)?;
+                    if sort_expr.asc {
+                        ordering
+                    } else {
+                        ordering.reverse()
+                    }
+                }
+            };
+            if ordering != Ordering::Equal {
+                return Ok(ordering);
+            }
+        }
+        Ok(Ordering::Equal)
+    }
+
+    fn sort_rows(&self, keyed: &mut [(Vec<Value>, Tuple)]) -> ExecutionResult<()> {
+        let mut error = None;
+        keyed.sort_by(|(left, _), (right, _)| {
+            self.compare_keys(left, right).unwrap_or_else(|err| {
+                error.get_or_insert(err);
+                Ordering::Equal
+            })
+        });
+        match error {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+
+    /// Sorts `keyed` and writes it to a temporary file as a new run.
+    fn spill(&mut self, mut keyed: Vec<(Vec<Value>, Tuple)>) -> ExecutionResult<()> {
+        self.sort_rows(&mut keyed)?;
+        let mut writer = SpillWriter::create()?;
+        for (mut keys, tuple) in keyed {
+            keys.extend_from_slice(tuple.values());
+            writer.write(&keys)?;
+        }
+        let before = self.temp_usage.pages();
+        let reader = writer.finish(&self.temp_usage)?;
+        self.temp_pages += self.temp_usage.pages() - before;
+        let mut run = Run { reader, head: None };
+        run.advance(self.sort_exprs.len())?;
+        self.runs.push(run);
+        Ok(())
+    }
+
+    /// Takes the smallest next row of the runs. Ties go to the earliest
+    /// run, which holds the earlier input rows, keeping the sort stable.
+    fn next_merged(&mut self) -> ExecutionResult<Option<Tuple>> {
+        let mut smallest: Option<usize> = None;
+        for (index, run) in self.runs.iter().enumerate() {
+            let Some((keys, _)) = &run.head else {
+                continue;
+            };
+            let is_smaller = match smallest.and_then(|best| self.runs[best].head.as_ref()) {
+                Some((best_keys, _)) => self.compare_keys(keys, best_keys)? == Ordering::Less,
+                None => true,
+            };
+            if is_smaller {
+                smallest = Some(index);
+            }
+        }
+        let Some(index) = smallest else {
+            return Ok(None);
+        };
+        let run = &mut self.runs[index];
+        let Some((_, tuple)) = run.head.take() else {
+            return Err(ExecutionError::Execution(
+                "sort run lost its next row".to_string(),
+            ));
+        };
+        run.advance(self.sort_exprs.len())?;
+        Ok(Some(tuple))
+    }
+}
+
+/// Orders two non-NULL sort keys. Values of one kind compare as they do in
+/// expressions. Across kinds, booleans sort before numbers and numbers before
+/// text, as integer index keys sort before text ones, so a column of mixed
+/// values still has one order.
+fn compare_sort_values(left: &Value, right: &Value) -> ExecutionResult<Ordering> {
+    let rank = |value: &Value| match value {
+        Value::Boolean(_) => 0,
+        Value::Integer(_) | Value::Float(_) | Value::Timestamp(_) => 1,
+        Value::String(_) => 2,
+        Value::Blob(_) | Value::Null => 3,
+    };
+    match rank(left).cmp(&rank(right)) {
+        Ordering::Equal => Ok(compare_values(left, right)?.unwrap_or(Ordering::Equal)),
+        ordering => Ok(ordering),
+    }
+}
+
+impl PhysicalOperator for Sort {
+    fn open(&mut self) -> ExecutionResult<()> {
+        self.child.open()?;
+        self.runs.clear();
+        self.temp_pages = 0;
+        let mut keyed = Vec::new();
+        let mut memory = 0;
+        while let Some(tuple) = self.child.next()? {
+            let keys = self
+                .sort_exprs
+                .iter()
+                .map(|sort_expr| evaluate_expr(&sort_expr.expr, &tuple, &self.schema))
+                .collect::<ExecutionResult<Vec<_>>>()?;
+            memory += tuple.memory_size() + keys.iter().map(Value::memory_size).sum::<usize>();
+            keyed.push((keys, tuple));
+            if memory > self.work_mem {
+                self.spill(std::mem::take(&mut keyed))?;
+                memory = 0;
+            }
+        }
+
+        if !self.runs.is_empty() && !keyed.is_empty() {
+            self.spill(std::mem::take(&mut keyed))?;
+        }
+        self.sort_rows(&mut keyed)?;
+        self.sorted = keyed.into_iter().map(|(_, tuple)| tuple).collect();
+        self.position = 0;
+        Ok(())
+    }
+
+    fn next(&mut self) -> ExecutionResult<Option<Tuple>> {
+        if !self.runs.is_empty() {
+            return self.next_merged();
+        }
+        if self.position >= self.sorted.len() {
+            return Ok(None);
+        }
+        let tuple = self.sorted[self.position].clone();
+        self.position += 1;
+        Ok(Some(tuple))
+    }
+
+    fn close(&mut self) -> ExecutionResult<()> {
+        self.sorted.clear();
+        self.runs.clear();
+        self.position = 0;
+        self.child.close()
+    }
+
+    /// Rereads the sorted rows, or the spilled runs from their start,
+    /// without sorting the input again.
+    fn rewind(&mut self) -> ExecutionResult<()> {
+        self.position = 0;
+        for run in &mut self.runs {
+            run.reader.rewind()?;
+            run.advance(self.sort_exprs.len())?;
+        }
+        Ok(())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn describe(&self) -> String {
+        let keys = self
+            .sort_exprs
+            .iter()
+            .map(|s| {
+                let nulls = if s.nulls_first { " NULLS FIRST" } else { "" };
+                format!("{} {}{}", s.expr, if s.asc { "ASC" } else { "DESC" }, nulls)
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        format!("Sort: [{}]", keys)
+    }
+
+    fn children(&self) -> Vec<&dyn PhysicalOperator> {
+        vec![&*self.child]
+    }
+}
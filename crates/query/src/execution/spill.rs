@@ -0,0 +1,156 @@
+//! Temporary files for operators whose rows outgrow `work_mem`.
+//!
+//! Rows are written in the result encoding to a file in the system temp
+//! directory, which is removed once its reader is dropped. Temporary files
+//! bypass the buffer pool and the WAL: they only live for one query.
+
+use crate::execution::encoding::{decode_value, encode_value};
+use crate::execution::operator::{ExecutionError, ExecutionResult};
+use crate::execution::tuple::{Tuple, Value};
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use storage::PAGE_SIZE;
+
+/// Bytes of rows a sort or a join's hash table holds in memory when the
+/// session has not set `work_mem`.
+pub const DEFAULT_WORK_MEM: usize = 4 * 1024 * 1024;
+
+static SPILL_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Running count of the temporary pages spilled operators wrote. Clones
+/// share the count.
+#[derive(Debug, Clone, Default)]
+pub struct TempUsage {
+    pages: Arc<AtomicU64>,
+}
+
+impl TempUsage {
+    pub fn pages(&self) -> u64 {
+        self.pages.load(Ordering::Relaxed)
+    }
+
+    fn record(&self, pages: u64) {
+        self.pages.fetch_add(pages, Ordering::Relaxed);
+    }
+}
+
+fn spill_error(err: std::io::Error) -> ExecutionError {
+    ExecutionError::Execution(format!("temporary file error: {}", err))
+}
+
+/// Rows being written to a new temporary file.
+pub(crate) struct SpillWriter {
+    path: PathBuf,
+    writer: Option<BufWriter<File>>,
+    bytes: usize,
+}
+
+impl SpillWriter {
+    pub(crate) fn create() -> ExecutionResult<Self> {
+        let path = std::env::temp_dir().join(format!(
+            "rdbms_spill_{}_{}.tmp",
+            std::process::id(),
+            SPILL_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        let file = File::create(&path).map_err(spill_error)?;
+        Ok(Self {
+            path,
+            writer: Some(BufWriter::new(file)),
+            bytes: 0,
+        })
+    }
+
+    pub(crate) fn write(&mut self, values: &[Value]) -> ExecutionResult<()> {
+        let mut row = Vec::new();
+        for value in values {
+            encode_value(value, &mut row);
+        }
+        let len = u32::try_from(row.len())
+            .map_err(|_| ExecutionError::Execution("spilled row is too large".to_string()))?;
+        let writer = self.writer.as_mut().ok_or_else(|| {
+            ExecutionError::Execution("spill file written after it was finished".to_string())
+        })?;
+        writer
+            .write_all(&len.to_le_bytes())
+            .and_then(|()| writer.write_all(&row))
+            .map_err(spill_error)?;
+        self.bytes += 4 + row.len();
+        Ok(())
+    }
+
+    /// Flushes the file, adds its size in pages to `usage`, and opens it
+    /// for reading from the first row.
+    pub(crate) fn finish(mut self, usage: &TempUsage) -> ExecutionResult<SpillReader> {
+        if let Some(writer) = self.writer.take() {
+            writer
+                .into_inner()
+                .map_err(|err| spill_error(err.into_error()))?;
+        }
+        usage.record(self.bytes.div_ceil(PAGE_SIZE) as u64);
+        // the reader removes the file from here on
+        let mut reader = SpillReader {
+            reader: None,
+            path: std::mem::take(&mut self.path),
+        };
+        let file = File::open(&reader.path).map_err(spill_error)?;
+        reader.reader = Some(BufReader::new(file));
+        Ok(reader)
+    }
+}
+
+impl Drop for SpillWriter {
+    fn drop(&mut self) {
+        self.writer = None;
+        if !self.path.as_os_str().is_empty() {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+/// Rows read back from a temporary file, which is removed on drop.
+pub(crate) struct SpillReader {
+    reader: Option<BufReader<File>>,
+    path: PathBuf,
+}
+
+impl SpillReader {
+    pub(crate) fn next(&mut self) -> ExecutionResult<Option<Tuple>> {
+        let Some(reader) = self.reader.as_mut() else {
+            return Ok(None);
+        };
+        let mut len = [0u8; 4];
+        match reader.read_exact(&mut len) {
+            Ok(()) => {}
+            Err(err) if err.kind() == ErrorKind::UnexpectedEof => {
+                self.reader = None;
+                return Ok(None);
+            }
+            Err(err) => return Err(spill_error(err)),
+        }
+        let mut row = vec![0u8; u32::from_le_bytes(len) as usize];
+        reader.read_exact(&mut row).map_err(spill_error)?;
+        let mut input = row.as_slice();
+        let mut values = Vec::new();
+        while !input.is_empty() {
+            values.push(decode_value(&mut input)?);
+        }
+        Ok(Some(Tuple::new(values)))
+    }
+
+    /// Starts reading again from the first row.
+    pub(crate) fn rewind(&mut self) -> ExecutionResult<()> {
+        let file = File::open(&self.path).map_err(spill_error)?;
+        self.reader = Some(BufReader::new(file));
+        Ok(())
+    }
+}
+
+impl Drop for SpillReader {
+    fn drop(&mut self) {
+        self.reader = None;
+        let _ = fs::remove_file(&self.path);
+    }
+}
@@ -0,0 +1,99 @@
+use crate::execution::operator::ExecutionResult;
+use crate::execution::planner::IndexInfo;
+use crate::execution::seq_scan::TableHeap;
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+/// Table statistics gathered by `ANALYZE`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TableStats {
+    pub row_count: usize,
+    pub page_count: usize,
+    /// Distinct keys of each index by name, one count per leading prefix
+    /// of its key columns (see `BPlusTree::distinct_prefixes`).
+    pub distinct_keys: HashMap<String, Vec<usize>>,
+}
+
+/// Holds the latest statistics for a table together with the number of rows
+/// modified since they were gathered.
+#[derive(Debug, Default)]
+pub struct StatsTracker {
+    stats: Mutex<Option<TableStats>>,
+    modifications: AtomicUsize,
+    refreshing: AtomicBool,
+}
+
+impl StatsTracker {
+    pub fn stats(&self) -> Option<TableStats> {
+        self.stats.lock().clone()
+    }
+
+    pub fn modifications(&self) -> usize {
+        self.modifications.load(Ordering::Acquire)
+    }
+
+    pub fn record_modifications(&self, count: usize) {
+        if count > 0 {
+            self.modifications.fetch_add(count, Ordering::AcqRel);
+        }
+    }
+
+    /// Returns true once the rows modified since the last refresh reach
+    /// `churn_threshold` (a fraction of the analyzed row count). Tables that
+    /// were never analyzed are stale after their first modification.
+    pub fn is_stale(&self, churn_threshold: f64) -> bool {
+        let modifications = self.modifications();
+        if modifications == 0 {
+            return false;
+        }
+        match self.stats() {
+            Some(stats) => modifications as f64 >= stats.row_count as f64 * churn_threshold,
+            None => true,
+        }
+    }
+
+    /// Recounts the heap and the distinct keys of `indexes`, and stores the
+    /// result. Modifications made while the scan runs stay counted towards
+    /// the next refresh.
+    pub fn refresh(&self, heap: &TableHeap, indexes: &[IndexInfo]) -> ExecutionResult<TableStats> {
+        let seen = self.modifications();
+        let (row_count, page_count) = heap.count_tuples()?;
+        let mut distinct_keys = HashMap::new();
+        for index in indexes {
+            distinct_keys.insert(index.name.clone(), index.index.distinct_prefixes()?);
+        }
+        let stats = TableStats {
+            row_count,
+            page_count,
+            distinct_keys,
+        };
+        *self.stats.lock() = Some(stats.clone());
+        self.modifications.fetch_sub(seen, Ordering::AcqRel);
+        Ok(stats)
+    }
+
+    /// Refreshes on a background thread. Returns `None` when a background
+    /// refresh for this table is already running.
+    pub fn refresh_in_background(
+        self: &Arc<Self>,
+        heap: TableHeap,
+        indexes: Vec<IndexInfo>,
+    ) -> Option<JoinHandle<()>> {
+        if self
+            .refreshing
+            .compare_exchange(false, true, Ordering::AcqRel, Ordering::Acquire)
+            .is_err()
+        {
+            return None;
+        }
+        let tracker = Arc::clone(self);
+        Some(thread::spawn(move || {
+            // a failed refresh leaves the old stats; the next statement retries
+            let _ = tracker.refresh(&heap, &indexes);
+            tracker.refreshing.store(false, Ordering::Release);
+        }))
+    }
+}
@@ -1,14 +1,21 @@
+use super::hash_join::equijoin_key;
+use super::nested_loop_join::DEFAULT_HASH_BUILD_ROWS;
 use super::{
-    Catalog, ExecutionError, ExecutionResult, Executor, PhysicalOperator, PhysicalPlanner, SeqScan,
-    TableHeap, TableInfo, Tuple, Value,
+    explain_physical, Aggregate, CancellationToken, Catalog, ExecutionError, ExecutionResult,
+    Executor, HashJoin, Materialize, NestedLoopJoin, PhysicalOperator, PhysicalPlanner, SeqScan,
+    SharedCatalog, Sort, TableHeap, TableInfo, TempUsage, Tuple, Value, Values, DEFAULT_WORK_MEM,
 };
 use crate::expr::{BinaryOperator, Expr, LiteralValue};
 use crate::index::{Index, IndexKey};
-use crate::logical_plan::{JoinType, LogicalPlan};
+use crate::logical_plan::{
+    AggregateExpr, AggregateFunction, CopyOptions, JoinType, LogicalPlan, SortExpr,
+};
 use crate::schema::{DataType, Field, Schema};
+use std::collections::HashSet;
 use std::fs;
 use std::path::PathBuf;
 use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
 use storage::{BufferPoolManager, DiskManager, PAGE_SIZE};
 
 static TEST_COUNTER: AtomicUsize = AtomicUsize::new(0);
@@ -31,6 +38,7 @@ impl TestContext {
 impl Drop for TestContext {
     fn drop(&mut self) {
         let _ = fs::remove_file(&self.path);
+        let _ = fs::remove_file(self.path.with_extension("db.dwb"));
     }
 }
 
@@ -88,6 +96,7 @@ fn scan_plan(table: &str) -> LogicalPlan {
         table_name: table.to_string(),
         alias: None,
         schema: None,
+        sample: None,
     }
 }
 
@@ -155,6 +164,162 @@ fn seq_scan_lifecycle_happy_path() -> ExecutionResult<()> {
     Ok(())
 }
 
+/// Cancels the token after handing out the first tuple, as a client
+/// disconnecting mid-query would.
+struct CancelAfterFirst {
+    inner: SeqScan,
+    token: CancellationToken,
+}
+
+impl PhysicalOperator for CancelAfterFirst {
+    fn open(&mut self) -> ExecutionResult<()> {
+        self.inner.open()
+    }
+
+    fn next(&mut self) -> ExecutionResult<Option<Tuple>> {
+        let tuple = self.inner.next()?;
+        self.token.cancel();
+        Ok(tuple)
+    }
+
+    fn close(&mut self) -> ExecutionResult<()> {
+        self.inner.close()
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+
+#[test]
+fn executor_stops_when_cancelled_mid_query() -> ExecutionResult<()> {
+    let (_ctx, bpm) = setup_bpm("executor_cancelled", 8);
+    let (schema, heap, _) = build_table(
+        &bpm,
+        "people",
+        vec![("id", DataType::Integer)],
+        vec![vec![Value::Integer(1)], vec![Value::Integer(2)]],
+    )?;
+    let token = CancellationToken::new();
+    let operator = CancelAfterFirst {
+        inner: SeqScan::new(heap, schema),
+        token: token.clone(),
+    };
+    let mut executor = Executor::new(Box::new(operator)).with_cancellation(token);
+    assert!(matches!(executor.execute(), Err(ExecutionError::Cancelled)));
+    Ok(())
+}
+
+#[test]
+fn shared_catalog_reads_run_concurrently_and_ddl_waits() -> ExecutionResult<()> {
+    let (_ctx, bpm) = setup_bpm("shared_catalog", 8);
+    let (schema, heap, rows) = build_table(
+        &bpm,
+        "people",
+        vec![("id", DataType::Integer)],
+        vec![vec![Value::Integer(1)], vec![Value::Integer(2)]],
+    )?;
+    let mut catalog = Catalog::new();
+    register_table(&mut catalog, "people", schema.clone(), heap);
+    let shared = SharedCatalog::new(catalog);
+
+    let reading = shared.read();
+    let reader = {
+        let shared = shared.clone();
+        std::thread::spawn(move || execute_plan(scan_plan("people"), &shared.read()))
+    };
+    assert_eq!(reader.join().expect("reader thread")?, rows);
+
+    let (done_tx, done_rx) = std::sync::mpsc::channel();
+    let ddl = {
+        let shared = shared.clone();
+        std::thread::spawn(move || {
+            shared.write().drop_table("people")?;
+            done_tx.send(()).expect("signal ddl");
+            Ok::<_, ExecutionError>(())
+        })
+    };
+    assert!(done_rx
+        .recv_timeout(std::time::Duration::from_millis(100))
+        .is_err());
+    assert!(reading.table("people").is_some());
+    drop(reading);
+    ddl.join().expect("ddl thread")?;
+    assert!(shared.read().table("people").is_none());
+    Ok(())
+}
+
+#[test]
+fn concurrent_inserts_and_scans_keep_every_row_whole() -> ExecutionResult<()> {
+    const WRITERS: i64 = 4;
+    const ROWS: i64 = 150;
+    let (_ctx, bpm) = setup_bpm("heap_concurrency", 64);
+    let schema = schema_for(
+        "events",
+        vec![("id", DataType::Integer), ("payload", DataType::Text)],
+    );
+    let heap = TableHeap::create(bpm)?;
+    // a few hundred bytes a row, so the writers fill pages and race to add
+    // the next one
+    let payload = |id: i64| format!("{id:0>300}");
+    // every row a scan returns is whole, and a later scan returns it again
+    let check_scan = |heap: &TableHeap, seen: &mut HashSet<i64>| -> ExecutionResult<()> {
+        let mut ids = HashSet::new();
+        for (_, tuple) in heap.scan_tuples(&schema)? {
+            let Value::Integer(id) = tuple.values()[0] else {
+                panic!("torn row {:?}", tuple);
+            };
+            assert_eq!(tuple.values()[1], Value::String(payload(id)));
+            assert!(ids.insert(id), "row {} returned twice", id);
+        }
+        assert!(seen.is_subset(&ids), "a scan lost rows");
+        *seen = ids;
+        Ok(())
+    };
+
+    let writing = Arc::new(AtomicUsize::new(WRITERS as usize));
+    std::thread::scope(|scope| -> ExecutionResult<()> {
+        let writers = (0..WRITERS)
+            .map(|writer| {
+                let (heap, schema, writing) = (heap.clone(), schema.clone(), writing.clone());
+                scope.spawn(move || {
+                    for row in 0..ROWS {
+                        let id = writer * ROWS + row;
+                        let tuple =
+                            Tuple::new(vec![Value::Integer(id), Value::String(payload(id))]);
+                        heap.insert_tuple(&tuple, &schema)?;
+                    }
+                    writing.fetch_sub(1, Ordering::SeqCst);
+                    Ok::<_, ExecutionError>(())
+                })
+            })
+            .collect::<Vec<_>>();
+        let scanners = (0..2)
+            .map(|_| {
+                let (heap, writing) = (heap.clone(), writing.clone());
+                scope.spawn(move || {
+                    let mut seen = HashSet::new();
+                    while writing.load(Ordering::SeqCst) > 0 {
+                        check_scan(&heap, &mut seen)?;
+                    }
+                    Ok::<_, ExecutionError>(())
+                })
+            })
+            .collect::<Vec<_>>();
+        for handle in writers.into_iter().chain(scanners) {
+            handle.join().expect("heap thread")?;
+        }
+        Ok(())
+    })?;
+
+    let mut seen = HashSet::new();
+    check_scan(&heap, &mut seen)?;
+    assert_eq!(seen, (0..WRITERS * ROWS).collect::<HashSet<_>>());
+    assert_eq!(heap.count_tuples()?.0, (WRITERS * ROWS) as usize);
+    assert_eq!(heap.row_count()?, (WRITERS * ROWS) as usize);
+    Ok(())
+}
+
 #[test]
 fn seq_scan_next_before_open_is_safe() -> ExecutionResult<()> {
     let (_ctx, bpm) = setup_bpm("seq_scan_next_before_open", 8);
@@ -425,6 +590,414 @@ fn join_inner_rewind_handles_late_matches() -> ExecutionResult<()> {
     Ok(())
 }
 
+#[test]
+fn join_rewinds_a_multi_page_inner_input_for_every_outer_row() -> ExecutionResult<()> {
+    let (_ctx, bpm) = setup_bpm("join_rewind_multi_page", 4);
+    let (left_schema, left_heap, _) = build_table(
+        &bpm,
+        "outer_rows",
+        vec![("id", DataType::Integer)],
+        (0..3).map(|i| vec![Value::Integer(i)]).collect(),
+    )?;
+    let (right_schema, right_heap, _) = build_table(
+        &bpm,
+        "inner_rows",
+        vec![("id", DataType::Integer)],
+        (0..400).map(|i| vec![Value::Integer(i)]).collect(),
+    )?;
+    let mut catalog = Catalog::new();
+    register_table(&mut catalog, "outer_rows", left_schema, left_heap);
+    register_table(&mut catalog, "inner_rows", right_schema, right_heap);
+
+    let plan = LogicalPlan::Join {
+        left: Box::new(scan_plan("outer_rows")),
+        right: Box::new(scan_plan("inner_rows")),
+        join_type: JoinType::Inner,
+        condition: Some(lit_bool(true)),
+    };
+    let results = execute_plan(plan, &catalog)?;
+    let expected = (0..3)
+        .flat_map(|outer| (0..400).map(move |inner| (outer, inner)))
+        .map(|(outer, inner)| vec![Value::Integer(outer), Value::Integer(inner)])
+        .collect::<Vec<_>>();
+    assert_eq!(results, tuples(expected));
+    Ok(())
+}
+
+#[test]
+fn sort_rewind_rereads_its_rows_without_sorting_again() -> ExecutionResult<()> {
+    let schema = schema_for("numbers", vec![("id", DataType::Integer)]);
+    let rows = (0..100).rev().map(|i| vec![lit_int(i)]).collect::<Vec<_>>();
+    for work_mem in [DEFAULT_WORK_MEM, 256] {
+        let usage = TempUsage::default();
+        let mut sort = Sort::new(
+            Box::new(Values::new(rows.clone())),
+            vec![SortExpr {
+                expr: col("numbers", "id"),
+                asc: true,
+                nulls_first: false,
+            }],
+            schema.clone(),
+        )
+        .with_work_mem(work_mem)
+        .with_temp_usage(usage.clone());
+        sort.open()?;
+        let spilled = usage.pages();
+        let mut passes = Vec::new();
+        for _ in 0..2 {
+            let mut ids = Vec::new();
+            while let Some(tuple) = sort.next()? {
+                ids.push(tuple.values()[0].clone());
+            }
+            passes.push(ids);
+            sort.rewind()?;
+        }
+        sort.close()?;
+        assert_eq!(passes[0], (0..100).map(Value::Integer).collect::<Vec<_>>());
+        assert_eq!(passes[0], passes[1]);
+        // spilled runs are reread, not written again
+        assert_eq!(usage.pages(), spilled);
+        assert_eq!(spilled > 0, work_mem == 256);
+    }
+    Ok(())
+}
+
+/// Counts how many times its input is opened, which is how often a plan
+/// under it runs from the start.
+struct CountOpens {
+    inner: Box<dyn PhysicalOperator>,
+    opens: std::rc::Rc<std::cell::Cell<usize>>,
+}
+
+impl PhysicalOperator for CountOpens {
+    fn open(&mut self) -> ExecutionResult<()> {
+        self.opens.set(self.opens.get() + 1);
+        self.inner.open()
+    }
+
+    fn next(&mut self) -> ExecutionResult<Option<Tuple>> {
+        self.inner.next()
+    }
+
+    fn close(&mut self) -> ExecutionResult<()> {
+        self.inner.close()
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+
+#[test]
+fn materialized_inner_aggregate_runs_once_for_every_outer_row() -> ExecutionResult<()> {
+    let outer_schema = schema_for("outer_rows", vec![("id", DataType::Integer)]);
+    let inner_schema = schema_for("inner_rows", vec![("id", DataType::Integer)]);
+    let counted_schema = schema_for("counts", vec![("total", DataType::Integer)]);
+    let run = |materialize: bool| -> ExecutionResult<(Vec<Tuple>, usize)> {
+        let opens = std::rc::Rc::new(std::cell::Cell::new(0));
+        let aggregate = Aggregate::new(
+            Box::new(Values::new((0..50).map(|id| vec![lit_int(id)]).collect())),
+            Vec::new(),
+            vec![AggregateExpr {
+                func: AggregateFunction::Count,
+                args: vec![Expr::Wildcard],
+                alias: None,
+            }],
+            inner_schema.clone(),
+        );
+        let mut inner: Box<dyn PhysicalOperator> = Box::new(CountOpens {
+            inner: Box::new(aggregate),
+            opens: opens.clone(),
+        });
+        if materialize {
+            inner = Box::new(Materialize::new(inner));
+        }
+        let mut join = NestedLoopJoin::new(
+            Box::new(Values::new((0..4).map(|id| vec![lit_int(id)]).collect())),
+            inner,
+            lit_bool(true),
+            JoinType::Inner,
+            outer_schema.clone(),
+            counted_schema.clone(),
+        );
+        join.open()?;
+        let mut rows = Vec::new();
+        while let Some(tuple) = join.next()? {
+            rows.push(tuple);
+        }
+        join.close()?;
+        Ok((rows, opens.get()))
+    };
+
+    let (materialized, materialized_runs) = run(true)?;
+    let (rerun, reruns) = run(false)?;
+    assert_eq!(
+        materialized,
+        tuples(
+            (0..4)
+                .map(|id| vec![Value::Integer(id), Value::Integer(50)])
+                .collect()
+        )
+    );
+    assert_eq!(materialized, rerun);
+    assert_eq!(materialized_runs, 1);
+    assert_eq!(reruns, 4);
+    Ok(())
+}
+
+#[test]
+fn materialize_replays_spilled_rows_on_rewind() -> ExecutionResult<()> {
+    let usage = TempUsage::default();
+    let rows = (0..200).map(|id| vec![lit_int(id)]).collect::<Vec<_>>();
+    let mut materialize = Materialize::new(Box::new(Values::new(rows)))
+        .with_work_mem(256)
+        .with_temp_usage(usage.clone());
+    materialize.open()?;
+    let spilled = usage.pages();
+    assert!(spilled > 0);
+    for _ in 0..2 {
+        let mut ids = Vec::new();
+        while let Some(tuple) = materialize.next()? {
+            ids.push(tuple.values()[0].clone());
+        }
+        assert_eq!(ids, (0..200).map(Value::Integer).collect::<Vec<_>>());
+        materialize.rewind()?;
+    }
+    materialize.close()?;
+    assert_eq!(usage.pages(), spilled);
+    Ok(())
+}
+
+#[test]
+fn planner_materializes_a_join_read_again_for_every_outer_row() -> ExecutionResult<()> {
+    let (_ctx, bpm) = setup_bpm("materialize_join_inner", 16);
+    let mut catalog = Catalog::new();
+    for table in ["a", "b", "c"] {
+        let rows = (0..5).map(|id| vec![Value::Integer(id)]).collect();
+        let (schema, heap, _) = build_table(&bpm, table, vec![("id", DataType::Integer)], rows)?;
+        register_table(&mut catalog, table, schema, heap);
+    }
+    let plan = crate::sql_to_logical_plan(
+        "SELECT a.id, b_id FROM a JOIN \
+         (SELECT b.id AS b_id FROM b JOIN c ON b.id < c.id) AS bc ON a.id < b_id",
+    )
+    .map_err(|err| ExecutionError::Execution(err.to_string()))?;
+    let operator = PhysicalPlanner::new(&catalog).plan(&plan)?;
+    let lines = explain_physical(operator.as_ref());
+    let materialize = lines
+        .iter()
+        .position(|line| line.trim() == "Materialize")
+        .unwrap_or_else(|| panic!("no Materialize in {lines:#?}"));
+    assert!(lines[materialize + 1..]
+        .iter()
+        .any(|line| line.contains("NestedLoopJoin")));
+    // scans of a table are read again directly
+    assert_eq!(
+        lines
+            .iter()
+            .filter(|line| line.contains("Materialize"))
+            .count(),
+        1
+    );
+    let rows = Executor::new(operator).execute()?;
+    // b < c pairs: b=0 (4), 1 (3), 2 (2), 3 (1); then a < b
+    assert_eq!(rows.len(), 3 + 2 * 2 + 3);
+    Ok(())
+}
+
+#[test]
+fn join_hashes_a_small_inner_input_instead_of_rescanning_it() -> ExecutionResult<()> {
+    let order_schema = schema_for(
+        "orders",
+        vec![
+            ("id", DataType::Integer),
+            ("customer_id", DataType::Integer),
+        ],
+    );
+    let customer_schema = schema_for(
+        "customers",
+        vec![("id", DataType::Integer), ("name", DataType::Text)],
+    );
+    let mut orders = (0..200)
+        .map(|id| vec![lit_int(id), lit_int(id % 50)])
+        .collect::<Vec<_>>();
+    orders.push(vec![lit_int(200), Expr::Literal(LiteralValue::Null)]);
+    let customers = (0..50)
+        .map(|id| {
+            vec![
+                lit_int(id),
+                Expr::Literal(LiteralValue::String(format!("customer {}", id))),
+            ]
+        })
+        .collect::<Vec<_>>();
+    let predicate = bin(
+        col("orders", "customer_id"),
+        BinaryOperator::Eq,
+        col("customers", "id"),
+    );
+    let run_join =
+        |join_type: JoinType, hash_build_rows: usize| -> ExecutionResult<(Vec<Tuple>, u64)> {
+            let mut join = NestedLoopJoin::new(
+                Box::new(Values::new(orders.clone())),
+                Box::new(Values::new(customers.clone())),
+                predicate.clone(),
+                join_type,
+                order_schema.clone(),
+                customer_schema.clone(),
+            )
+            .with_hash_build_rows(hash_build_rows);
+            join.open()?;
+            let mut rows = Vec::new();
+            while let Some(tuple) = join.next()? {
+                rows.push(tuple);
+            }
+            join.close()?;
+            Ok((rows, join.predicate_evaluations()))
+        };
+    let run = |hash_build_rows: usize| run_join(JoinType::Inner, hash_build_rows);
+
+    let (hashed, hashed_evaluations) = run(DEFAULT_HASH_BUILD_ROWS)?;
+    let (looped, looped_evaluations) = run(0)?;
+    assert_eq!(hashed.len(), 200);
+    assert_eq!(hashed, looped);
+    // one candidate per order with a customer, against every pair
+    assert_eq!(hashed_evaluations, 200);
+    assert_eq!(looped_evaluations, 201 * 50);
+    // an inner input over the limit is rescanned as before
+    let (too_big, too_big_evaluations) = run(49)?;
+    assert_eq!(too_big, looped);
+    assert_eq!(too_big_evaluations, looped_evaluations);
+    // a LEFT join keeps the order without a customer either way
+    let (left_hashed, _) = run_join(JoinType::Left, DEFAULT_HASH_BUILD_ROWS)?;
+    let (left_looped, _) = run_join(JoinType::Left, 0)?;
+    assert_eq!(left_hashed.len(), 201);
+    assert_eq!(left_hashed, left_looped);
+    assert_eq!(left_hashed[200].values()[2..], [Value::Null, Value::Null]);
+    Ok(())
+}
+
+#[test]
+fn hash_join_returns_the_rows_of_a_nested_loop_join() -> ExecutionResult<()> {
+    let order_schema = schema_for(
+        "orders",
+        vec![("id", DataType::Integer), ("customer_id", DataType::Real)],
+    );
+    let customer_schema = schema_for(
+        "customers",
+        vec![("id", DataType::Integer), ("name", DataType::Text)],
+    );
+    // float keys must meet the integer ids they equal
+    let mut orders = (0..300)
+        .map(|id| {
+            vec![
+                lit_int(id),
+                Expr::Literal(LiteralValue::Float((id % 60) as f64)),
+            ]
+        })
+        .collect::<Vec<_>>();
+    orders.push(vec![lit_int(300), Expr::Literal(LiteralValue::Null)]);
+    let customers = (0..50)
+        .map(|id| {
+            vec![
+                lit_int(id % 45),
+                Expr::Literal(LiteralValue::String(format!("customer {}", id))),
+            ]
+        })
+        .collect::<Vec<_>>();
+    let predicate = bin(
+        col("orders", "customer_id"),
+        BinaryOperator::Eq,
+        col("customers", "id"),
+    );
+    assert_eq!(
+        equijoin_key(&predicate, &order_schema, &customer_schema),
+        Some((1, 0))
+    );
+    let two_keys = bin(predicate.clone(), BinaryOperator::And, predicate.clone());
+    assert_eq!(
+        equijoin_key(&two_keys, &order_schema, &customer_schema),
+        None
+    );
+
+    let collect = |mut join: Box<dyn PhysicalOperator>| -> ExecutionResult<Vec<Tuple>> {
+        join.open()?;
+        let mut rows = Vec::new();
+        while let Some(tuple) = join.next()? {
+            rows.push(tuple);
+        }
+        join.close()?;
+        Ok(rows)
+    };
+    let nested = |join_type: JoinType| {
+        collect(Box::new(
+            NestedLoopJoin::new(
+                Box::new(Values::new(orders.clone())),
+                Box::new(Values::new(customers.clone())),
+                predicate.clone(),
+                join_type,
+                order_schema.clone(),
+                customer_schema.clone(),
+            )
+            .with_hash_build_rows(0),
+        ))
+    };
+    let hashed = |join_type: JoinType, build_left: bool, work_mem: usize| {
+        collect(Box::new(
+            HashJoin::new(
+                Box::new(Values::new(orders.clone())),
+                Box::new(Values::new(customers.clone())),
+                predicate.clone(),
+                join_type,
+                order_schema.clone(),
+                customer_schema.clone(),
+                1,
+                0,
+            )
+            .with_build_left(build_left)
+            .with_work_mem(work_mem),
+        ))
+    };
+    let sorted = |mut rows: Vec<Tuple>| {
+        rows.sort_by_key(|tuple| format!("{:?}", tuple));
+        rows
+    };
+
+    let inner = nested(JoinType::Inner)?;
+    assert_eq!(inner.len(), 250);
+    // building on the right keeps the order of the left input
+    assert_eq!(hashed(JoinType::Inner, false, DEFAULT_WORK_MEM)?, inner);
+    assert_eq!(
+        sorted(hashed(JoinType::Inner, true, DEFAULT_WORK_MEM)?),
+        sorted(inner.clone())
+    );
+    // a build side over work_mem is joined partition by partition
+    let usage = TempUsage::default();
+    let spilled = collect(Box::new(
+        HashJoin::new(
+            Box::new(Values::new(orders.clone())),
+            Box::new(Values::new(customers.clone())),
+            predicate.clone(),
+            JoinType::Inner,
+            order_schema.clone(),
+            customer_schema.clone(),
+            1,
+            0,
+        )
+        .with_work_mem(256)
+        .with_temp_usage(usage.clone()),
+    ))?;
+    assert!(usage.pages() > 0);
+    assert_eq!(sorted(spilled), sorted(inner.clone()));
+
+    let left = nested(JoinType::Left)?;
+    assert_eq!(left.len(), 326);
+    assert_eq!(hashed(JoinType::Left, false, DEFAULT_WORK_MEM)?, left);
+    // a LEFT join ignores a request to build on the left
+    assert_eq!(hashed(JoinType::Left, true, DEFAULT_WORK_MEM)?, left);
+    assert_eq!(sorted(hashed(JoinType::Left, false, 256)?), sorted(left));
+    Ok(())
+}
+
 #[test]
 fn acceptance_query_projection_join() -> ExecutionResult<()> {
     let (_ctx, bpm) = setup_bpm("acceptance_query", 8);
@@ -500,6 +1073,146 @@ fn acceptance_query_projection_join() -> ExecutionResult<()> {
     Ok(())
 }
 
+#[test]
+fn left_join_pads_events_without_ticket_types_with_nulls() -> ExecutionResult<()> {
+    let (_ctx, bpm) = setup_bpm("left_join", 8);
+    let (event_schema, event_heap, _event_rows) = build_table(
+        &bpm,
+        "events",
+        vec![("id", DataType::Integer), ("title", DataType::Text)],
+        vec![
+            vec![Value::Integer(1), Value::String("Concert".to_string())],
+            vec![Value::Integer(2), Value::String("Draft".to_string())],
+        ],
+    )?;
+    let (ticket_schema, ticket_heap, _ticket_rows) = build_table(
+        &bpm,
+        "ticket_types",
+        vec![
+            ("id", DataType::Integer),
+            ("event_id", DataType::Integer),
+            ("price", DataType::Integer),
+        ],
+        vec![
+            vec![Value::Integer(100), Value::Integer(1), Value::Integer(50)],
+            vec![Value::Integer(101), Value::Integer(1), Value::Integer(70)],
+        ],
+    )?;
+    let mut ticket_schema = ticket_schema;
+    for field in &mut ticket_schema.fields {
+        field.nullable = false;
+    }
+    let mut catalog = Catalog::new();
+    register_table(&mut catalog, "events", event_schema, event_heap);
+    register_table(&mut catalog, "ticket_types", ticket_schema, ticket_heap);
+
+    let join_plan = LogicalPlan::Join {
+        left: Box::new(scan_plan("events")),
+        right: Box::new(scan_plan("ticket_types")),
+        join_type: JoinType::Left,
+        condition: Some(bin(
+            col("events", "id"),
+            BinaryOperator::Eq,
+            col("ticket_types", "event_id"),
+        )),
+    };
+    let (_, schema) = PhysicalPlanner::new(&catalog).plan_with_schema(&join_plan)?;
+    assert!(schema.fields[2..].iter().all(|field| field.nullable));
+
+    let plan = LogicalPlan::Project {
+        input: Box::new(join_plan),
+        expressions: vec![
+            col("events", "title"),
+            col("ticket_types", "id"),
+            col("ticket_types", "price"),
+        ],
+        aliases: None,
+    };
+    let results = execute_plan(plan, &catalog)?;
+    let concert = |id, price| {
+        Tuple::new(vec![
+            Value::String("Concert".to_string()),
+            Value::Integer(id),
+            Value::Integer(price),
+        ])
+    };
+    let expected = vec![
+        concert(100, 50),
+        concert(101, 70),
+        Tuple::new(vec![
+            Value::String("Draft".to_string()),
+            Value::Null,
+            Value::Null,
+        ]),
+    ];
+    assert_eq!(results, expected);
+    Ok(())
+}
+
+#[test]
+fn select_star_over_a_join_names_columns_by_table() -> ExecutionResult<()> {
+    let (_ctx, bpm) = setup_bpm("select_star_join", 8);
+    let (event_schema, event_heap, _event_rows) = build_table(
+        &bpm,
+        "events",
+        vec![("id", DataType::Integer), ("title", DataType::Text)],
+        vec![vec![
+            Value::Integer(1),
+            Value::String("Concert".to_string()),
+        ]],
+    )?;
+    let (ticket_schema, ticket_heap, _ticket_rows) = build_table(
+        &bpm,
+        "ticket_types",
+        vec![("id", DataType::Integer), ("event_id", DataType::Integer)],
+        vec![vec![Value::Integer(100), Value::Integer(1)]],
+    )?;
+    let mut catalog = Catalog::new();
+    register_table(&mut catalog, "events", event_schema, event_heap);
+    register_table(&mut catalog, "ticket_types", ticket_schema, ticket_heap);
+    let select_star = |input: LogicalPlan| LogicalPlan::Project {
+        input: Box::new(input),
+        expressions: vec![Expr::Wildcard],
+        aliases: None,
+    };
+    let names = |plan: &LogicalPlan| -> ExecutionResult<Vec<String>> {
+        let (_, schema) = PhysicalPlanner::new(&catalog).plan_with_schema(plan)?;
+        Ok(schema.fields.into_iter().map(|field| field.name).collect())
+    };
+
+    let join = select_star(LogicalPlan::Join {
+        left: Box::new(scan_plan("events")),
+        right: Box::new(scan_plan("ticket_types")),
+        join_type: JoinType::Inner,
+        condition: Some(bin(
+            col("events", "id"),
+            BinaryOperator::Eq,
+            col("ticket_types", "event_id"),
+        )),
+    });
+    assert_eq!(
+        names(&join)?,
+        [
+            "events.id",
+            "events.title",
+            "ticket_types.id",
+            "ticket_types.event_id"
+        ]
+    );
+    assert_eq!(
+        execute_plan(join, &catalog)?,
+        vec![Tuple::new(vec![
+            Value::Integer(1),
+            Value::String("Concert".to_string()),
+            Value::Integer(100),
+            Value::Integer(1),
+        ])]
+    );
+    // one table needs no qualifying
+    assert_eq!(names(&select_star(scan_plan("events")))?, ["id", "title"]);
+    Ok(())
+}
+
 #[test]
 fn deep_join_pipeline_is_deterministic() -> ExecutionResult<()> {
     let (_ctx, bpm) = setup_bpm("deep_join", 8);
@@ -669,6 +1382,61 @@ fn unique_index_rejects_duplicate() -> ExecutionResult<()> {
     Ok(())
 }
 
+#[test]
+fn delete_operator_removes_matching_rows_and_their_index_entries() -> ExecutionResult<()> {
+    let (_ctx, bpm) = setup_bpm("delete_operator", 8);
+    let rows = (1..=10)
+        .map(|id| vec![Value::Integer(id), Value::String(format!("user{}", id % 3))])
+        .collect();
+    let columns = vec![("id", DataType::Integer), ("name", DataType::Text)];
+    let (schema, heap, _rows) = build_table(&bpm, "people", columns, rows)?;
+    let mut table = TableInfo::new("people", schema, heap);
+    table.create_index("people_pk", "id", true, true)?;
+    table.create_index("people_name", "name", false, false)?;
+    let mut catalog = Catalog::new();
+    register_table_info(&mut catalog, table);
+
+    let delete = |sql: &str| -> ExecutionResult<Vec<Tuple>> {
+        let plan = crate::sql_to_logical_plan(sql)
+            .map_err(|err| ExecutionError::Execution(err.to_string()))?;
+        let operator = PhysicalPlanner::new(&catalog).plan(&plan)?;
+        assert!(operator.as_any().is::<super::Delete>(), "{}", sql);
+        Executor::new(operator).execute()
+    };
+    let count = |value: i64| vec![Tuple::new(vec![Value::Integer(value)])];
+    assert_eq!(
+        delete("DELETE FROM people WHERE name = 'user1' OR id > 8")?,
+        count(5)
+    );
+    let table = catalog.table("people").unwrap();
+    let remaining = table
+        .heap
+        .scan_tuples(&table.schema)?
+        .into_iter()
+        .map(|(_, tuple)| tuple.values()[0].clone())
+        .collect::<Vec<_>>();
+    assert_eq!(remaining, [2, 3, 5, 6, 8].map(Value::Integer).to_vec());
+    for index in &table.indexes {
+        assert_eq!(index.index.iter_all()?.len(), 5, "{}", index.name);
+    }
+    assert!(table.indexes[0]
+        .index
+        .get(&IndexKey::Integer(1))?
+        .is_empty());
+    assert!(table.indexes[1]
+        .index
+        .get(&IndexKey::Text("user1".to_string()))?
+        .is_empty());
+
+    assert_eq!(delete("DELETE FROM people WHERE id = 42")?, count(0));
+    assert_eq!(delete("DELETE FROM people")?, count(5));
+    assert!(table.heap.scan_tuples(&table.schema)?.is_empty());
+    for index in &table.indexes {
+        assert!(index.index.iter_all()?.is_empty(), "{}", index.name);
+    }
+    Ok(())
+}
+
 #[test]
 fn index_lookup_returns_rid() -> ExecutionResult<()> {
     let (_ctx, bpm) = setup_bpm("index_lookup", 8);
@@ -733,6 +1501,64 @@ fn index_scan_equality_returns_tuple() -> ExecutionResult<()> {
     Ok(())
 }
 
+#[test]
+fn index_scan_ranges_exclude_null_keys_and_is_null_selects_them() -> ExecutionResult<()> {
+    let (_ctx, bpm) = setup_bpm("index_scan_nulls", 16);
+    let values = [Some(3), None, Some(-2), Some(7), None, Some(0)];
+    let rows = values
+        .iter()
+        .enumerate()
+        .map(|(id, value)| {
+            vec![
+                Value::Integer(id as i64),
+                value.map_or(Value::Null, Value::Integer),
+            ]
+        })
+        .collect();
+    let columns = vec![("id", DataType::Integer), ("v", DataType::Integer)];
+    let (schema, heap, _rows) = build_table(&bpm, "readings", columns, rows)?;
+    let mut table = TableInfo::new("readings", schema, heap);
+    table.create_index("readings_v", "v", true, false)?;
+    let mut catalog = Catalog::new();
+    register_table_info(&mut catalog, table);
+
+    let ids = |sql: &str, expected_scan: &str| -> ExecutionResult<Vec<Value>> {
+        let plan = crate::sql_to_logical_plan(sql)
+            .map_err(|err| ExecutionError::Execution(err.to_string()))?;
+        let operator = PhysicalPlanner::new(&catalog).plan(&plan)?;
+        let mut scan: &dyn PhysicalOperator = &*operator;
+        while let Some(child) = scan.children().first() {
+            scan = *child;
+        }
+        assert_eq!(scan.describe(), expected_scan, "{}", sql);
+        let rows = Executor::new(operator).execute()?;
+        Ok(rows.iter().map(|row| row.values()[0].clone()).collect())
+    };
+    let scan = |predicate: &str| format!("IndexScan: readings using readings_v ({})", predicate);
+
+    assert_eq!(
+        ids("SELECT id FROM readings WHERE v > 0", &scan("> 0"))?,
+        vec![Value::Integer(0), Value::Integer(3)]
+    );
+    assert_eq!(
+        ids("SELECT id FROM readings WHERE v < 1", &scan("< 1"))?,
+        vec![Value::Integer(2), Value::Integer(5)]
+    );
+    assert_eq!(
+        ids("SELECT id FROM readings WHERE v IS NULL", &scan("IS NULL"))?,
+        vec![Value::Integer(1), Value::Integer(4)]
+    );
+    assert_eq!(
+        ids(
+            "SELECT id FROM readings WHERE v IS NOT NULL",
+            &scan("IS NOT NULL")
+        )?
+        .len(),
+        4
+    );
+    Ok(())
+}
+
 #[test]
 fn index_scan_projection_and_join() -> ExecutionResult<()> {
     let (_ctx, bpm) = setup_bpm("index_scan_join", 10);
@@ -877,3 +1703,376 @@ fn blob_round_trip_overflow() -> ExecutionResult<()> {
     assert_eq!(loaded.values()[1], Value::Blob(blob));
     Ok(())
 }
+
+#[test]
+fn freed_heap_and_blob_pages_are_reused() -> ExecutionResult<()> {
+    let (_ctx, bpm) = setup_bpm("freed_pages", 16);
+    let schema = schema_for(
+        "files",
+        vec![("id", DataType::Integer), ("payload", DataType::Blob)],
+    );
+    let heap = TableHeap::create(bpm.clone())?;
+    heap.insert_tuple(
+        &Tuple::new(vec![
+            Value::Integer(1),
+            Value::Blob(vec![0xAB; PAGE_SIZE * 2]),
+        ]),
+        &schema,
+    )?;
+    heap.insert_tuple(
+        &Tuple::new(vec![Value::Integer(2), Value::Blob(vec![1, 2])]),
+        &schema,
+    )?;
+    // the heap page plus three blob pages
+    let page_ids = heap.page_ids(&schema)?;
+    assert_eq!(page_ids.len(), 4);
+
+    for page_id in &page_ids {
+        bpm.free_page(*page_id)?;
+    }
+    assert_eq!(bpm.free_page_count()?, 4);
+    let reused = TableHeap::create(bpm.clone())?;
+    let blob = vec![0xCD; PAGE_SIZE];
+    let rid = reused.insert_tuple(
+        &Tuple::new(vec![Value::Integer(3), Value::Blob(blob.clone())]),
+        &schema,
+    )?;
+    assert_eq!(bpm.free_page_count()?, 1);
+    let loaded = reused
+        .get_tuple(rid, &schema)?
+        .ok_or_else(|| ExecutionError::Execution("missing tuple".to_string()))?;
+    assert_eq!(loaded.values()[1], Value::Blob(blob));
+    Ok(())
+}
+
+#[test]
+fn small_integers_and_booleans_are_stored_compactly() -> ExecutionResult<()> {
+    let (_ctx, bpm) = setup_bpm("compact_tuples", 32);
+    let schema = schema_for(
+        "flags",
+        vec![
+            ("id", DataType::Integer),
+            ("qty", DataType::BigInt),
+            ("active", DataType::Boolean),
+            ("paid", DataType::Boolean),
+        ],
+    );
+    let heap = TableHeap::create(bpm.clone())?;
+    let rows = 2000i64;
+    for id in 0..rows {
+        let tuple = Tuple::new(vec![
+            Value::Integer(id % 100),
+            Value::Integer(-(id % 50)),
+            Value::Boolean(id % 2 == 0),
+            if id % 3 == 0 {
+                Value::Null
+            } else {
+                Value::Boolean(id % 5 == 0)
+            },
+        ]);
+        let _ = heap.insert_tuple(&tuple, &schema)?;
+    }
+
+    let (count, pages) = heap.count_tuples()?;
+    assert_eq!(count, rows as usize);
+    // a null byte per column, 4 + 8 bytes of integers and a byte per
+    // boolean, plus the 8-byte slot
+    let fixed_width = (4 + 4 + 8 + 1 + 1) + 8;
+    let fixed_pages = (rows as usize * fixed_width).div_ceil(PAGE_SIZE);
+    assert!(
+        pages * 3 <= fixed_pages * 2,
+        "{pages} of {fixed_pages} pages"
+    );
+
+    for (id, (_, tuple)) in heap.scan_tuples(&schema)?.into_iter().enumerate() {
+        let id = id as i64;
+        assert_eq!(tuple.values()[0], Value::Integer(id % 100));
+        assert_eq!(tuple.values()[1], Value::Integer(-(id % 50)));
+        assert_eq!(tuple.values()[2], Value::Boolean(id % 2 == 0));
+        let paid = if id % 3 == 0 {
+            Value::Null
+        } else {
+            Value::Boolean(id % 5 == 0)
+        };
+        assert_eq!(tuple.values()[3], paid);
+    }
+
+    let extremes = Tuple::new(vec![
+        Value::Integer(i32::MIN as i64),
+        Value::Integer(i64::MIN),
+        Value::Boolean(true),
+        Value::Boolean(false),
+    ]);
+    let rid = heap.insert_tuple(&extremes, &schema)?;
+    assert_eq!(heap.get_tuple(rid, &schema)?, Some(extremes));
+    let widened = Tuple::new(vec![
+        Value::Integer(i32::MAX as i64),
+        Value::Integer(i64::MAX),
+        Value::Null,
+        Value::Boolean(true),
+    ]);
+    let rid = heap.update_tuple(rid, &widened, &schema)?;
+    assert_eq!(heap.get_tuple(rid, &schema)?, Some(widened));
+    Ok(())
+}
+
+fn hash_of<T: std::hash::Hash>(value: &T) -> u64 {
+    use std::hash::{DefaultHasher, Hasher};
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[test]
+fn value_hash_agrees_with_equality() {
+    let equal_pairs = [
+        (Value::Null, Value::Null),
+        (Value::Float(0.0), Value::Float(-0.0)),
+        (Value::Float(f64::NAN), Value::Float(-f64::NAN)),
+        (
+            Value::String("a".to_string()),
+            Value::String("a".to_string()),
+        ),
+    ];
+    for (left, right) in &equal_pairs {
+        assert_eq!(left, right);
+        assert_eq!(hash_of(left), hash_of(right));
+    }
+    assert_ne!(Value::Integer(1), Value::String("1".to_string()));
+    assert_ne!(Value::Integer(5), Value::Timestamp(5));
+    assert_ne!(Value::Integer(1), Value::Float(1.0));
+    assert_ne!(Value::Null, Value::Integer(0));
+
+    let left = Tuple::new(vec![Value::Integer(7), Value::Null, Value::Float(-0.0)]);
+    let right = Tuple::new(vec![Value::Integer(7), Value::Null, Value::Float(0.0)]);
+    assert_eq!(left, right);
+    assert_eq!(hash_of(&left), hash_of(&right));
+}
+
+#[test]
+fn group_by_groups_are_independent_of_input_order() -> ExecutionResult<()> {
+    let (_ctx, bpm) = setup_bpm("group_by_order", 16);
+    let rows = vec![
+        vec![Value::Float(0.0), Value::String("1".to_string())],
+        vec![Value::Null, Value::String("1".to_string())],
+        vec![Value::Float(1.5), Value::String("x".to_string())],
+        vec![Value::Float(-0.0), Value::String("1".to_string())],
+        vec![Value::Null, Value::String("1".to_string())],
+        vec![Value::Float(1.5), Value::String("1".to_string())],
+    ];
+    let mut reversed = rows.clone();
+    reversed.reverse();
+    let mut catalog = Catalog::new();
+    for (table, rows) in [("forward", rows), ("backward", reversed)] {
+        let columns = vec![("amount", DataType::Real), ("tag", DataType::Text)];
+        let (schema, heap, _) = build_table(&bpm, table, columns, rows)?;
+        register_table(&mut catalog, table, schema, heap);
+    }
+
+    let groups = |table: &str| -> ExecutionResult<Vec<Tuple>> {
+        let plan = LogicalPlan::Aggregate {
+            input: Box::new(scan_plan(table)),
+            group_by: vec![col(table, "amount"), col(table, "tag")],
+            aggregates: vec![AggregateExpr {
+                func: AggregateFunction::Count,
+                args: vec![Expr::Wildcard],
+                alias: None,
+            }],
+        };
+        let mut rows = assert_deterministic(&plan, &catalog)?;
+        rows.sort_by_key(|row| format!("{:?}", row.values()));
+        Ok(rows)
+    };
+    let forward = groups("forward")?;
+    assert_eq!(forward, groups("backward")?);
+    assert_eq!(forward.len(), 4);
+    assert!(forward.contains(&Tuple::new(vec![
+        Value::Float(0.0),
+        Value::String("1".to_string()),
+        Value::Integer(2),
+    ])));
+    assert!(forward.contains(&Tuple::new(vec![
+        Value::Null,
+        Value::String("1".to_string()),
+        Value::Integer(2),
+    ])));
+    Ok(())
+}
+
+#[test]
+fn group_by_counts_rows_per_group_and_skips_null_arguments() -> ExecutionResult<()> {
+    let (_ctx, bpm) = setup_bpm("group_by_status", 16);
+    let columns = vec![("status", DataType::Text), ("price", DataType::Integer)];
+    let text = |status: &str| Value::String(status.to_string());
+    let rows = vec![
+        vec![text("sold"), Value::Integer(40)],
+        vec![text("held"), Value::Null],
+        vec![text("sold"), Value::Integer(60)],
+        vec![text("open"), Value::Integer(25)],
+        vec![text("sold"), Value::Null],
+        vec![text("held"), Value::Null],
+    ];
+    let (schema, heap, _) = build_table(&bpm, "tickets", columns, rows)?;
+    let mut catalog = Catalog::new();
+    register_table(&mut catalog, "tickets", schema, heap);
+
+    let plan = crate::sql_to_logical_plan(
+        "SELECT status, COUNT(*), COUNT(price), SUM(price), AVG(price), MIN(price), MAX(price) \
+         FROM tickets GROUP BY status ORDER BY status",
+    )
+    .map_err(|err| ExecutionError::Execution(err.to_string()))?;
+    let rows = assert_deterministic(&plan, &catalog)?;
+    assert_eq!(
+        rows,
+        tuples(vec![
+            vec![
+                text("held"),
+                Value::Integer(2),
+                Value::Integer(0),
+                Value::Null,
+                Value::Null,
+                Value::Null,
+                Value::Null,
+            ],
+            vec![
+                text("open"),
+                Value::Integer(1),
+                Value::Integer(1),
+                Value::Integer(25),
+                Value::Float(25.0),
+                Value::Integer(25),
+                Value::Integer(25),
+            ],
+            vec![
+                text("sold"),
+                Value::Integer(3),
+                Value::Integer(2),
+                Value::Integer(100),
+                Value::Float(50.0),
+                Value::Integer(40),
+                Value::Integer(60),
+            ],
+        ])
+    );
+    Ok(())
+}
+
+#[test]
+fn order_by_sorts_with_explicit_null_placement() -> ExecutionResult<()> {
+    let (_ctx, bpm) = setup_bpm("order_by_nulls", 16);
+    let columns = vec![
+        ("id", DataType::Integer),
+        ("score", DataType::Integer),
+        ("name", DataType::Text),
+    ];
+    let text = |name: &str| Value::String(name.to_string());
+    let rows = vec![
+        vec![Value::Integer(1), Value::Integer(20), text("carol")],
+        vec![Value::Integer(2), Value::Null, text("alice")],
+        vec![Value::Integer(3), Value::Integer(35), Value::Null],
+        vec![Value::Integer(4), Value::Integer(20), text("bob")],
+        vec![Value::Integer(5), Value::Integer(-5), Value::Null],
+    ];
+    let (schema, heap, _) = build_table(&bpm, "players", columns, rows)?;
+    let mut catalog = Catalog::new();
+    register_table(&mut catalog, "players", schema, heap);
+    let ids = |sql: &str| -> ExecutionResult<Vec<Value>> {
+        let plan = crate::sql_to_logical_plan(sql)
+            .map_err(|err| ExecutionError::Execution(err.to_string()))?;
+        let rows = assert_deterministic(&plan, &catalog)?;
+        Ok(rows.iter().map(|row| row.values()[0].clone()).collect())
+    };
+    let expect = |ids: &[i64]| ids.iter().map(|id| Value::Integer(*id)).collect::<Vec<_>>();
+
+    assert_eq!(
+        ids("SELECT id FROM players ORDER BY score DESC")?,
+        expect(&[3, 1, 4, 5, 2])
+    );
+    assert_eq!(
+        ids("SELECT id FROM players ORDER BY score DESC NULLS FIRST")?,
+        expect(&[2, 3, 1, 4, 5])
+    );
+    assert_eq!(
+        ids("SELECT id FROM players ORDER BY name")?,
+        expect(&[2, 4, 1, 3, 5])
+    );
+    assert_eq!(
+        ids("SELECT id FROM players ORDER BY name NULLS FIRST, id DESC")?,
+        expect(&[5, 3, 2, 4, 1])
+    );
+    Ok(())
+}
+
+#[test]
+fn sort_orders_mixed_values_like_index_keys() -> ExecutionResult<()> {
+    let literal = |value: LiteralValue| vec![Expr::Literal(value)];
+    let rows = vec![
+        literal(LiteralValue::String("b".to_string())),
+        literal(LiteralValue::Integer(10)),
+        literal(LiteralValue::Null),
+        literal(LiteralValue::String("a".to_string())),
+        literal(LiteralValue::Float(2.5)),
+        literal(LiteralValue::Boolean(true)),
+    ];
+    let schema = schema_for("mixed", vec![("value", DataType::Text)]);
+    let mut sort = Sort::new(
+        Box::new(Values::new(rows)),
+        vec![SortExpr {
+            expr: col("mixed", "value"),
+            asc: true,
+            nulls_first: false,
+        }],
+        schema,
+    );
+    sort.open()?;
+    let mut sorted = Vec::new();
+    while let Some(tuple) = sort.next()? {
+        sorted.push(tuple.values()[0].clone());
+    }
+    sort.close()?;
+    assert_eq!(
+        sorted,
+        vec![
+            Value::Boolean(true),
+            Value::Float(2.5),
+            Value::Integer(10),
+            Value::String("a".to_string()),
+            Value::String("b".to_string()),
+            Value::Null,
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn csv_records_keep_null_and_empty_string_apart() {
+    let fields = vec![None, Some(""), Some("\\N"), Some("a,\"b\"\nc")];
+    let expected = fields
+        .iter()
+        .map(|field| field.map(str::to_string))
+        .collect::<Vec<_>>();
+    for null in ["", "\\N"] {
+        let options = CopyOptions {
+            null: null.to_string(),
+            ..CopyOptions::default()
+        };
+        let mut out = String::new();
+        super::csv::write_record(fields.clone(), &options, &mut out);
+        let records = super::csv::parse_records(&out, &options).unwrap();
+        assert_eq!(records, vec![expected.clone()], "{:?}", out);
+    }
+
+    // only an unquoted field equal to the NULL string is NULL
+    let options = CopyOptions {
+        null: "\\N".to_string(),
+        ..CopyOptions::default()
+    };
+    let records = super::csv::parse_records("\\N,,\"\\N\"\r\nx", &options).unwrap();
+    assert_eq!(
+        records,
+        vec![
+            vec![None, Some(String::new()), Some("\\N".to_string())],
+            vec![Some("x".to_string())],
+        ]
+    );
+}
@@ -1,6 +1,11 @@
 use crate::expr::LiteralValue;
+use std::hash::{Hash, Hasher};
 
-#[derive(Debug, Clone, PartialEq)]
+/// A single column value. Equality and hashing are canonical, for keying
+/// hash-based operators: NULL equals NULL, values of different variants are
+/// never equal, and floats compare with every NaN equal and `0.0` equal to
+/// `-0.0`. SQL comparison semantics live in `compare_values`.
+#[derive(Debug, Clone)]
 pub enum Value {
     Null,
     Integer(i64),
@@ -29,6 +34,61 @@ impl Value {
             _ => anyhow::bail!("Expected string, got {:?}", self),
         }
     }
+
+    /// Approximate bytes the value takes in memory, counted against
+    /// `work_mem`.
+    pub fn memory_size(&self) -> usize {
+        let heap = match self {
+            Value::String(text) => text.len(),
+            Value::Blob(bytes) => bytes.len(),
+            _ => 0,
+        };
+        std::mem::size_of::<Value>() + heap
+    }
+}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Null, Value::Null) => true,
+            (Value::Integer(left), Value::Integer(right)) => left == right,
+            (Value::Float(left), Value::Float(right)) => {
+                canonical_float_bits(*left) == canonical_float_bits(*right)
+            }
+            (Value::String(left), Value::String(right)) => left == right,
+            (Value::Boolean(left), Value::Boolean(right)) => left == right,
+            (Value::Timestamp(left), Value::Timestamp(right)) => left == right,
+            (Value::Blob(left), Value::Blob(right)) => left == right,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for Value {}
+
+impl Hash for Value {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match self {
+            Value::Null => {}
+            Value::Integer(value) | Value::Timestamp(value) => value.hash(state),
+            Value::Float(value) => canonical_float_bits(*value).hash(state),
+            Value::String(value) => value.hash(state),
+            Value::Boolean(value) => value.hash(state),
+            Value::Blob(value) => value.hash(state),
+        }
+    }
+}
+
+/// Bits of `value` with every NaN and both zeros folded together.
+fn canonical_float_bits(value: f64) -> u64 {
+    if value.is_nan() {
+        f64::NAN.to_bits()
+    } else if value == 0.0 {
+        0
+    } else {
+        value.to_bits()
+    }
 }
 
 impl From<LiteralValue> for Value {
@@ -57,7 +117,7 @@ impl From<&LiteralValue> for Value {
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Tuple {
     values: Vec<Value>,
 }
@@ -83,6 +143,10 @@ impl Tuple {
         self.values.get(index)
     }
 
+    pub fn memory_size(&self) -> usize {
+        std::mem::size_of::<Tuple>() + self.values.iter().map(Value::memory_size).sum::<usize>()
+    }
+
     pub fn concat(&self, other: &Tuple) -> Tuple {
         let mut values = Vec::with_capacity(self.values.len() + other.values.len());
         values.extend(self.values.iter().cloned());
@@ -1,14 +1,24 @@
-use crate::execution::operator::{ExecutionResult, PhysicalOperator};
+use crate::execution::operator::{
+    evaluate_predicate, ExecutionError, ExecutionResult, PhysicalOperator,
+};
 use crate::execution::planner::TableInfo;
+use crate::execution::schema_version::SchemaVersion;
+use crate::execution::seq_scan::{Rid, SeqScan};
 use crate::execution::tuple::Tuple;
 use crate::expr::Expr;
 use crate::logical_plan::Assignment;
+use crate::schema::Schema;
 use std::any::Any;
 
 pub struct Update {
     table: TableInfo,
+    version: SchemaVersion,
     assignments: Vec<Assignment>,
     filter: Option<Expr>,
+    /// Reads the rows to update when there is no source: the whole table,
+    /// or an index scan that checks `filter` itself.
+    scan: Box<dyn PhysicalOperator>,
+    source: Option<(Box<dyn PhysicalOperator>, Schema)>,
     updated: Vec<Tuple>,
     position: usize,
 }
@@ -16,20 +26,56 @@ pub struct Update {
 impl Update {
     pub fn new(table: TableInfo, assignments: Vec<Assignment>, filter: Option<Expr>) -> Self {
         Self {
+            version: table.schema_version(),
+            scan: Box::new(SeqScan::new(table.heap.clone(), table.schema.clone())),
             table,
             assignments,
             filter,
+            source: None,
             updated: Vec::new(),
             position: 0,
         }
     }
+
+    /// Finds the rows to update with `scan`, an index scan that applies the
+    /// whole filter.
+    pub fn with_scan(mut self, scan: Box<dyn PhysicalOperator>) -> Self {
+        self.scan = scan;
+        self.filter = None;
+        self
+    }
+
+    /// Joins the target against the rows of `source` (`UPDATE ... FROM`).
+    pub fn with_source(mut self, source: Box<dyn PhysicalOperator>, schema: Schema) -> Self {
+        self.source = Some((source, schema));
+        self
+    }
 }
 
 impl PhysicalOperator for Update {
     fn open(&mut self) -> ExecutionResult<()> {
-        self.updated = self
-            .table
-            .update_tuples(&self.assignments, self.filter.as_ref())?;
+        self.version.check()?;
+        self.updated = match &mut self.source {
+            Some((source, schema)) => {
+                source.open()?;
+                let mut rows = Vec::new();
+                while let Some(tuple) = source.next()? {
+                    rows.push(tuple);
+                }
+                source.close()?;
+                self.table.update_tuples_from(
+                    &self.assignments,
+                    self.filter.as_ref(),
+                    schema,
+                    &rows,
+                )?
+            }
+            None => {
+                let rows =
+                    read_targets(self.scan.as_mut(), self.filter.as_ref(), &self.table.schema)?;
+                self.table.update_known_tuples(&self.assignments, rows)?
+            }
+        };
         self.position = 0;
         Ok(())
     }
@@ -52,4 +98,51 @@ impl PhysicalOperator for Update {
     fn as_any(&self) -> &dyn Any {
         self
     }
+
+    fn describe(&self) -> String {
+        let assignments = self
+            .assignments
+            .iter()
+            .map(|assignment| format!("{} = {}", assignment.column, assignment.value))
+            .collect::<Vec<_>>()
+            .join(", ");
+        match &self.filter {
+            Some(filter) => format!(
+                "Update: {} set {} filter: {}",
+                self.table.name, assignments, filter
+            ),
+            None => format!("Update: {} set {}", self.table.name, assignments),
+        }
+    }
+
+    fn children(&self) -> Vec<&dyn PhysicalOperator> {
+        match &self.source {
+            Some((source, _)) => vec![source.as_ref()],
+            None => vec![self.scan.as_ref()],
+        }
+    }
+}
+
+/// Reads every row `scan` returns that satisfies `filter`, with the rid it
+/// is stored at, closing the scan afterwards.
+pub(crate) fn read_targets(
+    scan: &mut dyn PhysicalOperator,
+    filter: Option<&Expr>,
+    schema: &Schema,
+) -> ExecutionResult<Vec<(Rid, Tuple)>> {
+    scan.open()?;
+    let mut rows = Vec::new();
+    while let Some(tuple) = scan.next()? {
+        if let Some(predicate) = filter {
+            if !evaluate_predicate(predicate, &tuple, schema)? {
+                continue;
+            }
+        }
+        let rid = scan.last_rid().ok_or_else(|| {
+            ExecutionError::Execution(format!("{} does not report rids", scan.describe()))
+        })?;
+        rows.push((rid, tuple));
+    }
+    scan.close()?;
+    Ok(rows)
 }
@@ -0,0 +1,70 @@
+use crate::execution::operator::{evaluate_expr, ExecutionResult, PhysicalOperator};
+use crate::execution::tuple::Tuple;
+use crate::expr::Expr;
+use crate::schema::Schema;
+use std::any::Any;
+
+/// Emits the literal rows of a `VALUES` list, evaluating each expression
+/// once when the operator is opened.
+pub struct Values {
+    rows: Vec<Vec<Expr>>,
+    evaluated: Vec<Tuple>,
+    position: usize,
+}
+
+impl Values {
+    pub fn new(rows: Vec<Vec<Expr>>) -> Self {
+        Self {
+            rows,
+            evaluated: Vec::new(),
+            position: 0,
+        }
+    }
+}
+
+impl PhysicalOperator for Values {
+    fn open(&mut self) -> ExecutionResult<()> {
+        let empty_row = Tuple::new(Vec::new());
+        let empty_schema = Schema::empty();
+        self.evaluated = self
+            .rows
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|expr| evaluate_expr(expr, &empty_row, &empty_schema))
+                    .collect::<ExecutionResult<Vec<_>>>()
+                    .map(Tuple::new)
+            })
+            .collect::<ExecutionResult<Vec<_>>>()?;
+        self.position = 0;
+        Ok(())
+    }
+
+    fn next(&mut self) -> ExecutionResult<Option<Tuple>> {
+        if self.position >= self.evaluated.len() {
+            return Ok(None);
+        }
+        let tuple = self.evaluated[self.position].clone();
+        self.position += 1;
+        Ok(Some(tuple))
+    }
+
+    fn close(&mut self) -> ExecutionResult<()> {
+        self.evaluated.clear();
+        self.position = 0;
+        Ok(())
+    }
+
+    fn rewind(&mut self) -> ExecutionResult<()> {
+        self.position = 0;
+        Ok(())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn describe(&self) -> String {
+        format!("Values: {} rows", self.rows.len())
+    }
+}
@@ -0,0 +1,154 @@
+use crate::execution::operator::{ExecutionError, ExecutionResult};
+use crate::execution::seq_scan::Rid;
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use wal::Snapshot;
+
+/// Changes recorded between two sweeps for versions no snapshot needs.
+const PRUNE_INTERVAL: usize = 1024;
+
+/// Earlier images of the rows of one table, for snapshot transactions.
+/// Rows change in place, so before a transaction changes a row its old bytes
+/// are kept here, tagged with the transaction; a snapshot reading the row
+/// undoes, newest first, the changes of the transactions it does not see.
+/// Shared by every clone of the table's heap, and kept in memory only: a
+/// snapshot never outlives the process.
+#[derive(Clone, Default)]
+pub(crate) struct VersionStore(Arc<Shared>);
+
+#[derive(Default)]
+struct Shared {
+    versions: Mutex<Versions>,
+    /// Rows with versions, readable without the lock: a table nobody has
+    /// changed lately has none, and its scans skip the lock altogether.
+    rows: AtomicUsize,
+}
+
+#[derive(Default)]
+struct Versions {
+    rows: HashMap<Rid, Vec<Version>>,
+    recorded: usize,
+}
+
+struct Version {
+    writer: wal::TxnId,
+    /// The row before `writer` changed it; `None` when `writer` inserted it.
+    before: Option<Vec<u8>>,
+}
+
+impl VersionStore {
+    /// Keeps `before`, the bytes of the row at `rid`, as the current
+    /// transaction is about to change it. Called with the row's page held,
+    /// so readers never see the change without its version. Nothing is kept
+    /// outside a transaction, nor while no snapshot is open to read it.
+    pub(crate) fn record(&self, rid: Rid, before: Option<&[u8]>) {
+        if !wal::keeps_versions() {
+            return;
+        }
+        let Some(writer) = wal::current_txn_id() else {
+            return;
+        };
+        let mut versions = self.0.versions.lock();
+        versions.rows.entry(rid).or_default().push(Version {
+            writer,
+            before: before.map(<[u8]>::to_vec),
+        });
+        versions.recorded += 1;
+        if versions.recorded >= PRUNE_INTERVAL {
+            versions.recorded = 0;
+            versions.prune();
+        }
+        self.0.rows.store(versions.rows.len(), Ordering::Release);
+    }
+
+    /// The bytes of the row at `rid` that `view` sees, given the row's
+    /// current bytes; `None` when the row does not exist for it.
+    pub(crate) fn visible(
+        &self,
+        rid: Rid,
+        current: Option<Vec<u8>>,
+        view: &mut SnapshotView,
+    ) -> Option<Vec<u8>> {
+        if self.0.rows.load(Ordering::Acquire) == 0 {
+            return current;
+        }
+        let versions = self.0.versions.lock();
+        let mut image = current;
+        if let Some(history) = versions.rows.get(&rid) {
+            for version in history.iter().rev() {
+                if view.sees(version.writer) {
+                    break;
+                }
+                image = version.before.clone();
+            }
+        }
+        image
+    }
+
+    /// Fails when the last transaction to change the row at `rid` is one
+    /// `snapshot` does not see: the snapshot's transaction would overwrite
+    /// a change it never read.
+    pub(crate) fn check_writable(&self, rid: Rid, snapshot: &Snapshot) -> ExecutionResult<()> {
+        if self.0.rows.load(Ordering::Acquire) == 0 {
+            return Ok(());
+        }
+        let versions = self.0.versions.lock();
+        let latest = versions.rows.get(&rid).and_then(|history| history.last());
+        match latest {
+            Some(version) if !snapshot.sees(version.writer) => {
+                Err(ExecutionError::SerializationFailure)
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Versions {
+    /// Drops the versions every snapshot, current or future, reads past.
+    /// A reader stops at the newest version whose writer it sees, so once
+    /// a version is seen by all, it and every older one are unreachable.
+    fn prune(&mut self) {
+        self.rows.retain(|_, history| {
+            if let Some(index) = history
+                .iter()
+                .rposition(|version| wal::seen_by_every_snapshot(version.writer))
+            {
+                history.drain(..=index);
+            }
+            !history.is_empty()
+        });
+    }
+}
+
+/// A snapshot as one scan applies it. A scan meets the same few writers on
+/// row after row, so whether the snapshot sees a writer is decided once per
+/// writer and remembered for the rest of the scan.
+pub(crate) struct SnapshotView {
+    snapshot: Snapshot,
+    seen: HashMap<wal::TxnId, bool>,
+}
+
+impl SnapshotView {
+    pub(crate) fn new(snapshot: Snapshot) -> Self {
+        Self {
+            snapshot,
+            seen: HashMap::new(),
+        }
+    }
+
+    fn sees(&mut self, writer: wal::TxnId) -> bool {
+        if let Some(&seen) = self.seen.get(&writer) {
+            return seen;
+        }
+        let seen = self.snapshot.sees(writer);
+        self.seen.insert(writer, seen);
+        seen
+    }
+
+    /// How many writers the snapshot has been asked about.
+    pub(crate) fn decisions(&self) -> usize {
+        self.seen.len()
+    }
+}
@@ -1,3 +1,4 @@
+use crate::logical_plan::LogicalPlan;
 use crate::schema::DataType;
 use std::fmt;
 
@@ -44,6 +45,24 @@ pub enum Expr {
         list: Vec<Expr>,
         negated: bool,
     },
+    /// `DEFAULT` in an INSERT value list or UPDATE assignment, replaced by
+    /// the column's default when the row is written.
+    Default,
+    /// `?` (or `$n`) placeholder of a prepared statement, by 0-based
+    /// position; replaced by its bound value before the plan runs.
+    Parameter(usize),
+    /// `expr [NOT] IN (SELECT ...)` where it cannot be planned as a
+    /// semi-join. The subquery must not refer to the outer query; it runs
+    /// once and the expression becomes an `In` over its values.
+    InSubquery {
+        expr: Box<Expr>,
+        subplan: Box<LogicalPlan>,
+        negated: bool,
+    },
+    /// `(SELECT ...)` used as a value: the single column of its single row,
+    /// or NULL when it returns no rows. Like `InSubquery`, it runs once
+    /// before the query reads any row.
+    ScalarSubquery(Box<LogicalPlan>),
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -139,6 +158,106 @@ impl fmt::Display for UnaryOperator {
     }
 }
 
+impl Expr {
+    /// Names of every column the expression references.
+    pub fn column_names(&self) -> Vec<&str> {
+        self.column_refs()
+            .into_iter()
+            .map(|(_, name)| name)
+            .collect()
+    }
+
+    /// Every column reference as `(table qualifier, column name)`.
+    pub fn column_refs(&self) -> Vec<(Option<&str>, &str)> {
+        let mut refs = Vec::new();
+        self.collect_column_refs(&mut refs);
+        refs
+    }
+
+    /// Calls `f` on this expression, then on each expression nested in
+    /// what `f` left in its place.
+    pub fn visit_mut(&mut self, f: &mut dyn FnMut(&mut Expr)) {
+        f(self);
+        match self {
+            Expr::Column { .. }
+            | Expr::Literal(_)
+            | Expr::Wildcard
+            | Expr::QualifiedWildcard { .. }
+            | Expr::Default
+            | Expr::Parameter(_) => {}
+            Expr::BinaryOp { left, right, .. } => {
+                left.visit_mut(f);
+                right.visit_mut(f);
+            }
+            Expr::UnaryOp { expr, .. } | Expr::Cast { expr, .. } | Expr::IsNull { expr, .. } => {
+                expr.visit_mut(f)
+            }
+            Expr::Function { args, .. } => {
+                for arg in args {
+                    arg.visit_mut(f);
+                }
+            }
+            Expr::Between {
+                expr, low, high, ..
+            } => {
+                expr.visit_mut(f);
+                low.visit_mut(f);
+                high.visit_mut(f);
+            }
+            Expr::In { expr, list, .. } => {
+                expr.visit_mut(f);
+                for item in list {
+                    item.visit_mut(f);
+                }
+            }
+            Expr::InSubquery { expr, subplan, .. } => {
+                expr.visit_mut(f);
+                subplan.visit_exprs_mut(&mut |expr| expr.visit_mut(f));
+            }
+            Expr::ScalarSubquery(subplan) => subplan.visit_exprs_mut(&mut |expr| expr.visit_mut(f)),
+        }
+    }
+
+    fn collect_column_refs<'a>(&'a self, refs: &mut Vec<(Option<&'a str>, &'a str)>) {
+        match self {
+            Expr::Column { table, name } => refs.push((table.as_deref(), name)),
+            Expr::Literal(_)
+            | Expr::Wildcard
+            | Expr::QualifiedWildcard { .. }
+            | Expr::Default
+            | Expr::Parameter(_) => {}
+            Expr::BinaryOp { left, right, .. } => {
+                left.collect_column_refs(refs);
+                right.collect_column_refs(refs);
+            }
+            Expr::UnaryOp { expr, .. } | Expr::Cast { expr, .. } | Expr::IsNull { expr, .. } => {
+                expr.collect_column_refs(refs)
+            }
+            Expr::Function { args, .. } => {
+                for arg in args {
+                    arg.collect_column_refs(refs);
+                }
+            }
+            Expr::Between {
+                expr, low, high, ..
+            } => {
+                expr.collect_column_refs(refs);
+                low.collect_column_refs(refs);
+                high.collect_column_refs(refs);
+            }
+            Expr::In { expr, list, .. } => {
+                expr.collect_column_refs(refs);
+                for item in list {
+                    item.collect_column_refs(refs);
+                }
+            }
+            // the subquery's columns belong to its own tables
+            Expr::InSubquery { expr, .. } => expr.collect_column_refs(refs),
+            Expr::ScalarSubquery(_) => {}
+        }
+    }
+}
+
 impl fmt::Display for Expr {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -167,6 +286,8 @@ impl fmt::Display for Expr {
                 write!(f, ")")
             }
             Expr::Wildcard => write!(f, "*"),
+            Expr::Default => write!(f, "DEFAULT"),
+            Expr::Parameter(index) => write!(f, "${}", index + 1),
             Expr::QualifiedWildcard { table } => write!(f, "{}.*", table),
             Expr::Cast { expr, target_type } => {
                 write!(f, "CAST({} AS {:?})", expr, target_type)
@@ -208,6 +329,14 @@ impl fmt::Display for Expr {
                 }
                 write!(f, ")")
             }
+            Expr::InSubquery { expr, negated, .. } => {
+                if *negated {
+                    write!(f, "{} NOT IN (subquery)", expr)
+                } else {
+                    write!(f, "{} IN (subquery)", expr)
+                }
+            }
+            Expr::ScalarSubquery(_) => write!(f, "(subquery)"),
         }
     }
 }
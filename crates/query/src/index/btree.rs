@@ -2,7 +2,9 @@ use crate::execution::operator::{ExecutionError, ExecutionResult};
 use crate::execution::seq_scan::Rid;
 use crate::execution::tuple::Value;
 use std::cmp::Ordering;
-use storage::{BufferPoolManager, Page, PageId, PAGE_LSN_SIZE, PAGE_SIZE};
+use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+use std::sync::Arc;
+use storage::{BufferPoolManager, Page, PageId, PAGE_HEADER_SIZE, PAGE_SIZE};
 use txn::{LockKey, LockMode, TxnId};
 
 const INVALID_PAGE_ID: PageId = 0;
@@ -10,23 +12,26 @@ const PAGE_TYPE_HEADER: u8 = 1;
 const PAGE_TYPE_INTERNAL: u8 = 2;
 const PAGE_TYPE_LEAF: u8 = 3;
 
-const PAGE_TYPE_OFFSET: usize = PAGE_LSN_SIZE;
-const KEY_COUNT_OFFSET: usize = PAGE_LSN_SIZE + 1;
-const PARENT_OFFSET: usize = PAGE_LSN_SIZE + 8;
-const SPECIAL_OFFSET: usize = PAGE_LSN_SIZE + 16;
+const PAGE_TYPE_OFFSET: usize = PAGE_HEADER_SIZE;
+const KEY_COUNT_OFFSET: usize = PAGE_HEADER_SIZE + 1;
+const PARENT_OFFSET: usize = PAGE_HEADER_SIZE + 8;
+const SPECIAL_OFFSET: usize = PAGE_HEADER_SIZE + 16;
 
-const LEAF_HEADER_SIZE: usize = PAGE_LSN_SIZE + 24;
-const INTERNAL_HEADER_SIZE: usize = PAGE_LSN_SIZE + 24;
+const LEAF_HEADER_SIZE: usize = PAGE_HEADER_SIZE + 24;
+const INTERNAL_HEADER_SIZE: usize = PAGE_HEADER_SIZE + 24;
 const RID_SIZE: usize = 12;
 const DEFAULT_TEXT_KEY_SIZE: usize = 128;
-
-const HEADER_ROOT_OFFSET: usize = PAGE_LSN_SIZE + 8;
-const HEADER_KEY_TYPE_OFFSET: usize = PAGE_LSN_SIZE + 16;
-const HEADER_KEY_SIZE_OFFSET: usize = PAGE_LSN_SIZE + 17;
-const HEADER_UNIQUE_OFFSET: usize = PAGE_LSN_SIZE + 19;
-const HEADER_COMPOSITE_COUNT_OFFSET: usize = PAGE_LSN_SIZE + 20;
-const HEADER_TEXT_KEY_SIZE_OFFSET: usize = PAGE_LSN_SIZE + 21;
-const HEADER_COMPOSITE_TYPES_OFFSET: usize = PAGE_LSN_SIZE + 23;
+/// Leading byte of every key component: NULL or a value follows.
+const NULL_MARKER: u8 = 0;
+const VALUE_MARKER: u8 = 1;
+
+const HEADER_ROOT_OFFSET: usize = PAGE_HEADER_SIZE + 8;
+const HEADER_KEY_TYPE_OFFSET: usize = PAGE_HEADER_SIZE + 16;
+const HEADER_KEY_SIZE_OFFSET: usize = PAGE_HEADER_SIZE + 17;
+const HEADER_UNIQUE_OFFSET: usize = PAGE_HEADER_SIZE + 19;
+const HEADER_COMPOSITE_COUNT_OFFSET: usize = PAGE_HEADER_SIZE + 20;
+const HEADER_TEXT_KEY_SIZE_OFFSET: usize = PAGE_HEADER_SIZE + 21;
+const HEADER_COMPOSITE_TYPES_OFFSET: usize = PAGE_HEADER_SIZE + 23;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum IndexKeyType {
@@ -59,6 +64,9 @@ impl IndexKeyType {
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum IndexKey {
+    /// A NULL column value. It sorts before every other key, and equals
+    /// only itself in the tree, though never in a unique check.
+    Null,
     Integer(i64),
     Text(String),
     Composite(Vec<IndexKey>),
@@ -73,9 +81,7 @@ impl IndexKey {
             (Value::Integer(number), IndexKeyType::Integer)
             | (Value::Timestamp(number), IndexKeyType::Integer) => Ok(IndexKey::Integer(*number)),
             (Value::String(text), IndexKeyType::Text) => Ok(IndexKey::Text(text.clone())),
-            (Value::Null, _) => Err(ExecutionError::Execution(
-                "cannot use NULL value as index key".to_string(),
-            )),
+            (Value::Null, _) => Ok(IndexKey::Null),
             (other, _) => Err(ExecutionError::Execution(format!(
                 "value {:?} cannot be used as index key",
                 other
@@ -193,8 +199,18 @@ impl IndexKey {
         Ok(IndexKey::Composite(components))
     }
 
+    /// Whether the key or any of its components is NULL.
+    pub fn has_null(&self) -> bool {
+        match self {
+            IndexKey::Null => true,
+            IndexKey::Composite(keys) => keys.iter().any(IndexKey::has_null),
+            _ => false,
+        }
+    }
+
     pub fn display(&self) -> String {
         match self {
+            IndexKey::Null => "NULL".to_string(),
             IndexKey::Integer(number) => number.to_string(),
             IndexKey::Text(text) => text.clone(),
             IndexKey::Composite(keys) => {
@@ -213,6 +229,27 @@ fn encode_component(
     key: &IndexKey,
     key_type: IndexKeyType,
     text_key_size: usize,
+) -> ExecutionResult<Vec<u8>> {
+    if key_type == IndexKeyType::Composite {
+        return Err(ExecutionError::Execution(
+            "composite key component type is invalid".to_string(),
+        ));
+    }
+    let mut buffer = Vec::with_capacity(component_size(key_type, text_key_size)?);
+    if *key == IndexKey::Null {
+        buffer.push(NULL_MARKER);
+        buffer.resize(buffer.capacity(), 0);
+    } else {
+        buffer.push(VALUE_MARKER);
+        buffer.extend(encode_value(key, key_type, text_key_size)?);
+    }
+    Ok(buffer)
+}
+
+fn encode_value(
+    key: &IndexKey,
+    key_type: IndexKeyType,
+    text_key_size: usize,
 ) -> ExecutionResult<Vec<u8>> {
     match (key, key_type) {
         (_, IndexKeyType::Composite) => Err(ExecutionError::Execution(
@@ -251,6 +288,20 @@ fn decode_component(
     bytes: &[u8],
     key_type: IndexKeyType,
     text_key_size: usize,
+) -> ExecutionResult<IndexKey> {
+    match bytes.split_first() {
+        Some((&NULL_MARKER, _)) => Ok(IndexKey::Null),
+        Some((&VALUE_MARKER, value)) => decode_value(value, key_type, text_key_size),
+        _ => Err(ExecutionError::Execution(
+            "invalid index key marker".to_string(),
+        )),
+    }
+}
+
+fn decode_value(
+    bytes: &[u8],
+    key_type: IndexKeyType,
+    text_key_size: usize,
 ) -> ExecutionResult<IndexKey> {
     match key_type {
         IndexKeyType::Composite => Err(ExecutionError::Execution(
@@ -286,7 +337,12 @@ fn decode_component(
     }
 }
 
+/// Encoded size of one key component, its marker byte included.
 fn component_size(key_type: IndexKeyType, text_key_size: usize) -> ExecutionResult<usize> {
+    value_size(key_type, text_key_size).map(|size| size + 1)
+}
+
+fn value_size(key_type: IndexKeyType, text_key_size: usize) -> ExecutionResult<usize> {
     match key_type {
         IndexKeyType::Integer => Ok(8),
         IndexKeyType::Text => {
@@ -335,6 +391,9 @@ fn total_key_size(key_types: &[IndexKeyType], text_key_size: usize) -> Execution
 impl Ord for IndexKey {
     fn cmp(&self, other: &Self) -> Ordering {
         match (self, other) {
+            (IndexKey::Null, IndexKey::Null) => Ordering::Equal,
+            (IndexKey::Null, _) => Ordering::Less,
+            (_, IndexKey::Null) => Ordering::Greater,
             (IndexKey::Integer(left), IndexKey::Integer(right)) => left.cmp(right),
             (IndexKey::Text(left), IndexKey::Text(right)) => left.cmp(right),
             (IndexKey::Composite(left), IndexKey::Composite(right)) => {
@@ -366,6 +425,9 @@ pub struct IndexEntry {
     pub rid: Rid,
 }
 
+/// Keys between `lower` and `upper`, each with whether it is inclusive. On
+/// a composite index a bound may be a prefix of the key columns, matching
+/// every key that starts with it.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct IndexRange {
     pub lower: Option<(IndexKey, bool)>,
@@ -388,6 +450,35 @@ impl IndexRange {
     }
 }
 
+/// Shape of a tree's leaf level, from [`BPlusTree::stats`]. Deletes keep
+/// leaves at least half full by merging them, but the leaves' page order
+/// drifts from key order until the index is rebuilt.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct IndexStats {
+    pub height: usize,
+    pub entries: usize,
+    pub leaf_pages: usize,
+    /// Entries a leaf page holds.
+    pub leaf_capacity: usize,
+    /// Leaf pages holding no entries at all.
+    pub empty_leaves: usize,
+    /// Leaf links pointing back to a lower page id, each a backward seek
+    /// when the index is scanned in key order.
+    pub backward_links: usize,
+}
+
+impl IndexStats {
+    /// Share of the leaf pages beyond the fewest that could hold every
+    /// entry: near 0 for a packed index, towards 1 as deletes empty it.
+    pub fn fragmentation(&self) -> f64 {
+        if self.leaf_pages == 0 {
+            return 0.0;
+        }
+        let needed = self.entries.div_ceil(self.leaf_capacity.max(1)).max(1);
+        1.0 - needed.min(self.leaf_pages) as f64 / self.leaf_pages as f64
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum PageType {
     Header,
@@ -425,6 +516,8 @@ pub struct BPlusTree {
     key_size: usize,
     text_key_size: usize,
     unique: bool,
+    /// Entries deleted since [`BPlusTree::take_deletes`] was last called.
+    deletes: Arc<AtomicUsize>,
 }
 
 impl BPlusTree {
@@ -510,6 +603,7 @@ impl BPlusTree {
             key_size,
             text_key_size,
             unique,
+            deletes: Arc::default(),
         })
     }
 
@@ -569,6 +663,7 @@ impl BPlusTree {
             key_size,
             text_key_size,
             unique,
+            deletes: Arc::default(),
         })
     }
 
@@ -600,6 +695,12 @@ impl BPlusTree {
         self.unique
     }
 
+    /// Fails if `key` cannot be stored in this tree, such as text longer
+    /// than the key size, without touching any page.
+    pub fn check_key(&self, key: &IndexKey) -> ExecutionResult<()> {
+        key.encode(&self.key_types, self.text_key_size).map(drop)
+    }
+
     pub fn root_is_leaf(&self) -> ExecutionResult<bool> {
         let root_page_id = self.root_page_id()?;
         let page_type = {
@@ -653,6 +754,206 @@ impl BPlusTree {
         (PAGE_SIZE - INTERNAL_HEADER_SIZE) / entry_size
     }
 
+    /// Every page the tree occupies: the header page and each node reachable
+    /// from the root.
+    pub fn page_ids(&self) -> ExecutionResult<Vec<PageId>> {
+        let mut page_ids = vec![self.header_page_id];
+        let mut pending = vec![self.root_page_id()?];
+        while let Some(page_id) = pending.pop() {
+            page_ids.push(page_id);
+            let children = {
+                let page_guard = fetch_page(&self.buffer_pool, page_id, LockMode::Shared)?;
+                match read_page_type(&page_guard) {
+                    Ok(PageType::Internal) => read_internal_page(
+                        &page_guard,
+                        &self.key_types,
+                        self.text_key_size,
+                        self.key_size,
+                    )
+                    .map(|internal| internal.children),
+                    Ok(_) => Ok(Vec::new()),
+                    Err(err) => Err(err),
+                }
+            };
+            self.buffer_pool.unpin_page(page_id, false)?;
+            pending.extend(children?);
+        }
+        Ok(page_ids)
+    }
+
+    /// Entries deleted since [`BPlusTree::take_deletes`] last reset the count.
+    pub fn deletes(&self) -> usize {
+        self.deletes.load(AtomicOrdering::Acquire)
+    }
+
+    /// Entries deleted since the last call, which resets the count.
+    pub fn take_deletes(&self) -> usize {
+        self.deletes.swap(0, AtomicOrdering::AcqRel)
+    }
+
+    /// Walks the leaf level to measure how full and how ordered it is.
+    pub fn stats(&self) -> ExecutionResult<IndexStats> {
+        let mut stats = IndexStats {
+            height: self.height()?,
+            leaf_capacity: self.max_leaf_entries(),
+            ..IndexStats::default()
+        };
+        let mut page_id = self.find_leaf_page(None, false)?;
+        loop {
+            let (leaf_page, entries) = self.read_leaf_entries(page_id)?;
+            stats.leaf_pages += 1;
+            stats.entries += entries.len();
+            if entries.is_empty() {
+                stats.empty_leaves += 1;
+            }
+            match leaf_page.next {
+                Some(next_page) => {
+                    if next_page < page_id {
+                        stats.backward_links += 1;
+                    }
+                    page_id = next_page;
+                }
+                None => return Ok(stats),
+            }
+        }
+    }
+
+    /// Walks the leaf level counting distinct keys: entry `n` of the result
+    /// counts the distinct values of the first `n + 1` key columns, so a
+    /// single-column index returns one count.
+    pub fn distinct_prefixes(&self) -> ExecutionResult<Vec<usize>> {
+        let columns = self.key_types.len().max(1);
+        let mut counts = vec![0; columns];
+        let mut previous: Option<Vec<IndexKey>> = None;
+        let mut page_id = Some(self.find_leaf_page(None, false)?);
+        while let Some(current) = page_id {
+            let (leaf_page, entries) = self.read_leaf_entries(current)?;
+            for entry in entries {
+                let parts = match entry.key {
+                    IndexKey::Composite(parts) => parts,
+                    key => vec![key],
+                };
+                // keys are sorted, so a prefix is new when it differs from
+                // the one before
+                let shared = previous.as_ref().map_or(0, |previous| {
+                    previous
+                        .iter()
+                        .zip(&parts)
+                        .take_while(|(left, right)| left == right)
+                        .count()
+                });
+                for count in counts.iter_mut().take(parts.len()).skip(shared) {
+                    *count += 1;
+                }
+                previous = Some(parts);
+            }
+            page_id = leaf_page.next;
+        }
+        Ok(counts)
+    }
+
+    /// Checks the tree's structure: parent pointers, key order within and
+    /// across nodes, separator keys bounding their children, and a leaf
+    /// chain linking every leaf in key order.
+    pub fn verify(&self) -> ExecutionResult<()> {
+        let mut leaves = Vec::new();
+        self.verify_node(self.root_page_id()?, None, None, None, &mut leaves)?;
+        let mut page_id = Some(self.find_leaf_page(None, false)?);
+        let mut previous: Option<IndexKey> = None;
+        for &leaf in &leaves {
+            if page_id != Some(leaf) {
+                return Err(corrupt(format!(
+                    "leaf chain reaches page {:?} where page {} was expected",
+                    page_id, leaf
+                )));
+            }
+            let (leaf_page, entries) = self.read_leaf_entries(leaf)?;
+            if let (Some(previous), Some(first)) = (&previous, entries.first()) {
+                if first.key < *previous {
+                    return Err(corrupt(format!(
+                        "leaf {} starts below its predecessor",
+                        leaf
+                    )));
+                }
+            }
+            if let Some(last) = entries.last() {
+                previous = Some(last.key.clone());
+            }
+            page_id = leaf_page.next;
+        }
+        match page_id {
+            None => Ok(()),
+            Some(extra) => Err(corrupt(format!(
+                "leaf chain continues to page {} outside the tree",
+                extra
+            ))),
+        }
+    }
+
+    /// Verifies the subtree at `page_id`, whose keys must lie within
+    /// `[lower, upper]`, appending its leaves in key order.
+    fn verify_node(
+        &self,
+        page_id: PageId,
+        parent: Option<PageId>,
+        lower: Option<&IndexKey>,
+        upper: Option<&IndexKey>,
+        leaves: &mut Vec<PageId>,
+    ) -> ExecutionResult<()> {
+        let page_type = {
+            let page_guard = fetch_page(&self.buffer_pool, page_id, LockMode::Shared)?;
+            read_page_type(&page_guard)
+        };
+        self.buffer_pool.unpin_page(page_id, false)?;
+        let in_bounds = |key: &IndexKey| {
+            lower.is_none_or(|lower| key >= lower) && upper.is_none_or(|upper| key <= upper)
+        };
+        match page_type? {
+            PageType::Leaf => {
+                let (leaf_page, entries) = self.read_leaf_entries(page_id)?;
+                if leaf_page.parent != parent {
+                    return Err(corrupt(format!("leaf {} has the wrong parent", page_id)));
+                }
+                if entries.windows(2).any(|pair| pair[0].key > pair[1].key) {
+                    return Err(corrupt(format!("leaf {} is out of order", page_id)));
+                }
+                if !entries.iter().all(|entry| in_bounds(&entry.key)) {
+                    return Err(corrupt(format!(
+                        "leaf {} holds a key outside its parent's separators",
+                        page_id
+                    )));
+                }
+                leaves.push(page_id);
+                Ok(())
+            }
+            PageType::Internal => {
+                let internal = self.read_internal_page(page_id)?;
+                if internal.parent != parent {
+                    return Err(corrupt(format!("node {} has the wrong parent", page_id)));
+                }
+                if internal.children.len() != internal.keys.len() + 1
+                    || internal.keys.windows(2).any(|pair| pair[0] > pair[1])
+                    || !internal.keys.iter().all(in_bounds)
+                {
+                    return Err(corrupt(format!("node {} has malformed keys", page_id)));
+                }
+                for (position, &child) in internal.children.iter().enumerate() {
+                    let child_lower = match position {
+                        0 => lower,
+                        _ => Some(&internal.keys[position - 1]),
+                    };
+                    let child_upper = internal.keys.get(position).or(upper);
+                    self.verify_node(child, Some(page_id), child_lower, child_upper, leaves)?;
+                }
+                Ok(())
+            }
+            PageType::Header => Err(corrupt(format!(
+                "header page {} found inside the tree",
+                page_id
+            ))),
+        }
+    }
+
     fn root_page_id(&self) -> ExecutionResult<PageId> {
         let root = {
             let header_guard =
@@ -738,7 +1039,7 @@ impl BPlusTree {
         leaf_page: &LeafPage,
         entries: &[IndexEntry],
     ) -> ExecutionResult<()> {
-        {
+        let written = {
             let mut page_guard = fetch_page(&self.buffer_pool, page_id, LockMode::Exclusive)?;
             write_leaf_page(
                 &mut page_guard,
@@ -747,10 +1048,11 @@ impl BPlusTree {
                 &self.key_types,
                 self.text_key_size,
                 self.key_size,
-            )?;
-        }
+            )
+        };
+        // a failed write still releases the page
         self.buffer_pool.unpin_page(page_id, true)?;
-        Ok(())
+        written
     }
 
     fn read_internal_page(&self, page_id: PageId) -> ExecutionResult<InternalPage> {
@@ -810,7 +1112,13 @@ impl BPlusTree {
             return Ok(());
         }
 
-        let split_index = entries.len() / 2;
+        // appending past the last leaf keeps it full and starts a new one,
+        // so keys inserted in order pack the leaves
+        let split_index = if leaf_page.next.is_none() && insert_position == entries.len() - 1 {
+            entries.len() - 1
+        } else {
+            entries.len() / 2
+        };
         let right_entries = entries.split_off(split_index);
         let separator_key = right_entries
             .first()
@@ -900,28 +1208,180 @@ impl BPlusTree {
         self.insert_into_parent(page_id, separator_key, right_page_id)
     }
 
-    fn scan_entries(&self, range: IndexRange) -> ExecutionResult<Vec<IndexEntry>> {
-        let mut results = Vec::new();
-        let mut page_id = if let Some((ref lower_key, _)) = range.lower {
-            self.find_leaf_page(Some(lower_key), false)?
+    /// Fewest entries a leaf other than the root keeps after a delete.
+    fn min_leaf_entries(&self) -> usize {
+        self.max_leaf_entries() / 2
+    }
+
+    /// Fewest keys an internal node other than the root keeps after a merge
+    /// below it.
+    fn min_internal_entries(&self) -> usize {
+        self.max_internal_entries() / 2
+    }
+
+    /// The sibling a node under `parent` borrows from or merges with: the
+    /// one to its left, or to its right for the first child. Returns the
+    /// left and right page of the pair and the position of the separator
+    /// between them.
+    fn sibling_pair(
+        &self,
+        parent: &InternalPage,
+        page_id: PageId,
+    ) -> ExecutionResult<(PageId, PageId, usize)> {
+        let position = parent
+            .children
+            .iter()
+            .position(|&child| child == page_id)
+            .ok_or_else(|| ExecutionError::Execution("missing parent child pointer".to_string()))?;
+        match position {
+            0 if parent.children.len() > 1 => Ok((page_id, parent.children[1], 0)),
+            0 => Err(corrupt(format!("node {} has no sibling", page_id))),
+            _ => Ok((parent.children[position - 1], page_id, position - 1)),
+        }
+    }
+
+    /// Refills the leaf `page_id` after a delete left it under half full,
+    /// by moving entries over from a sibling or, when both fit in one page,
+    /// merging the pair and removing the right page from their parent.
+    fn rebalance_leaf(&self, page_id: PageId, parent_id: PageId) -> ExecutionResult<()> {
+        let mut parent = self.read_internal_page(parent_id)?;
+        let (left_id, right_id, separator) = self.sibling_pair(&parent, page_id)?;
+        let (mut left_page, mut left) = self.read_leaf_entries(left_id)?;
+        let (right_page, mut right) = self.read_leaf_entries(right_id)?;
+        if left.len() + right.len() <= self.max_leaf_entries() {
+            left.append(&mut right);
+            left_page.next = right_page.next;
+            self.write_leaf_entries(left_id, &left_page, &left)?;
+            self.free_page(right_id)?;
+            parent.keys.remove(separator);
+            parent.children.remove(separator + 1);
+            return self.rebalance_internal(parent_id, parent);
+        }
+        left.append(&mut right);
+        let right = left.split_off(left.len() / 2);
+        parent.keys[separator] = right[0].key.clone();
+        self.write_leaf_entries(left_id, &left_page, &left)?;
+        self.write_leaf_entries(right_id, &right_page, &right)?;
+        self.write_internal_page(parent_id, &parent)
+    }
+
+    /// Writes the internal node `page_id` after it lost a child, merging or
+    /// refilling it like a leaf when it is under half full. A root left
+    /// with a single child is freed and the child becomes the root, so
+    /// merges cascading up shrink the tree's height.
+    fn rebalance_internal(&self, page_id: PageId, node: InternalPage) -> ExecutionResult<()> {
+        let Some(parent_id) = node.parent else {
+            if !node.keys.is_empty() {
+                return self.write_internal_page(page_id, &node);
+            }
+            let child = node.children[0];
+            self.set_parent(child, None)?;
+            self.set_root_page_id(child)?;
+            return self.free_page(page_id);
+        };
+        if node.keys.len() >= self.min_internal_entries() {
+            return self.write_internal_page(page_id, &node);
+        }
+        let mut parent = self.read_internal_page(parent_id)?;
+        let (left_id, right_id, separator) = self.sibling_pair(&parent, page_id)?;
+        let (mut left, mut right) = if left_id == page_id {
+            (node, self.read_internal_page(right_id)?)
         } else {
-            self.find_leaf_page(None, false)?
+            (self.read_internal_page(left_id)?, node)
         };
+        if left.keys.len() + right.keys.len() < self.max_internal_entries() {
+            left.keys.push(parent.keys.remove(separator));
+            left.keys.append(&mut right.keys);
+            for &child in &right.children {
+                self.set_parent(child, Some(left_id))?;
+            }
+            left.children.append(&mut right.children);
+            parent.children.remove(separator + 1);
+            self.write_internal_page(left_id, &left)?;
+            self.free_page(right_id)?;
+            return self.rebalance_internal(parent_id, parent);
+        }
+        // rotate keys through the parent's separator until both halves match
+        let left_children = left.children.len();
+        let mut keys = std::mem::take(&mut left.keys);
+        keys.push(parent.keys[separator].clone());
+        keys.append(&mut right.keys);
+        let mut children = std::mem::take(&mut left.children);
+        children.append(&mut right.children);
+        let middle = keys.len() / 2;
+        right.keys = keys.split_off(middle + 1);
+        parent.keys[separator] = keys.remove(middle);
+        left.keys = keys;
+        right.children = children.split_off(middle + 1);
+        left.children = children;
+        if left.children.len() > left_children {
+            for &child in &left.children[left_children..] {
+                self.set_parent(child, Some(left_id))?;
+            }
+        } else {
+            for &child in &right.children[..left_children - left.children.len()] {
+                self.set_parent(child, Some(right_id))?;
+            }
+        }
+        self.write_internal_page(left_id, &left)?;
+        self.write_internal_page(right_id, &right)?;
+        self.write_internal_page(parent_id, &parent)
+    }
+
+    fn free_page(&self, page_id: PageId) -> ExecutionResult<()> {
+        self.buffer_pool.free_page(page_id)?;
+        Ok(())
+    }
+
+    fn scan_entries(&self, range: IndexRange) -> ExecutionResult<Vec<IndexEntry>> {
+        crate::index::Index::scan(self, range)?.collect()
+    }
+}
+
+/// The entries of a [`BPlusTree`] within a range, in key order, read one
+/// leaf page at a time by following the leaves' `next` pointers. Only the
+/// current leaf's entries are held, and no page stays pinned between calls
+/// to `next`; the tree must not change while the iterator is in use.
+pub struct BPlusTreeIter {
+    tree: BPlusTree,
+    range: IndexRange,
+    /// The leaf to read once `entries` runs out.
+    next_page: Option<PageId>,
+    entries: std::vec::IntoIter<IndexEntry>,
+}
 
+impl BPlusTreeIter {
+    fn finish(&mut self) {
+        self.next_page = None;
+        self.entries = Vec::new().into_iter();
+    }
+}
+
+impl Iterator for BPlusTreeIter {
+    type Item = ExecutionResult<IndexEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
         loop {
-            let (leaf_page, entries) = self.read_leaf_entries(page_id)?;
-            for entry in entries {
-                if !matches_lower_bound(&entry.key, &range) {
+            if let Some(entry) = self.entries.next() {
+                if !matches_lower_bound(&entry.key, &self.range) {
                     continue;
                 }
-                if matches_upper_stop(&entry.key, &range) {
-                    return Ok(results);
+                if matches_upper_stop(&entry.key, &self.range) {
+                    self.finish();
+                    return None;
                 }
-                results.push(entry);
+                return Some(Ok(entry));
             }
-            match leaf_page.next {
-                Some(next_page) => page_id = next_page,
-                None => return Ok(results),
+            let page_id = self.next_page.take()?;
+            match self.tree.read_leaf_entries(page_id) {
+                Ok((leaf_page, entries)) => {
+                    self.next_page = leaf_page.next;
+                    self.entries = entries.into_iter();
+                }
+                Err(err) => {
+                    self.finish();
+                    return Some(Err(err));
+                }
             }
         }
     }
@@ -929,7 +1389,7 @@ impl BPlusTree {
 
 impl crate::index::Index for BPlusTree {
     fn insert(&self, key: IndexKey, rid: Rid) -> ExecutionResult<()> {
-        if self.unique {
+        if self.unique && !key.has_null() {
             let existing = self.get(&key)?;
             if !existing.is_empty() {
                 return Err(ExecutionError::Execution("duplicate index key".to_string()));
@@ -939,9 +1399,7 @@ impl crate::index::Index for BPlusTree {
         if let (Some(lock_manager), Some(txn_id)) =
             (wal::current_lock_manager(), wal::current_txn_id())
         {
-            lock_manager
-                .lock_exclusive(TxnId(txn_id), LockKey::Page(leaf_page_id))
-                .map_err(|err| ExecutionError::Execution(format!("lock error: {err:?}")))?;
+            lock_manager.lock_exclusive(TxnId(txn_id), LockKey::Page(leaf_page_id))?;
         }
         self.insert_into_leaf(leaf_page_id, key, rid)
     }
@@ -952,9 +1410,7 @@ impl crate::index::Index for BPlusTree {
             if let (Some(lock_manager), Some(txn_id)) =
                 (wal::current_lock_manager(), wal::current_txn_id())
             {
-                lock_manager
-                    .lock_exclusive(TxnId(txn_id), LockKey::Page(page_id))
-                    .map_err(|err| ExecutionError::Execution(format!("lock error: {err:?}")))?;
+                lock_manager.lock_exclusive(TxnId(txn_id), LockKey::Page(page_id))?;
             }
             let (leaf_page, mut entries) = self.read_leaf_entries(page_id)?;
             if let Some(position) = entries
@@ -963,6 +1419,12 @@ impl crate::index::Index for BPlusTree {
             {
                 entries.remove(position);
                 self.write_leaf_entries(page_id, &leaf_page, &entries)?;
+                self.deletes.fetch_add(1, AtomicOrdering::AcqRel);
+                if let Some(parent_id) = leaf_page.parent {
+                    if entries.len() < self.min_leaf_entries() {
+                        self.rebalance_leaf(page_id, parent_id)?;
+                    }
+                }
                 return Ok(true);
             }
             let should_advance = match entries.last() {
@@ -990,6 +1452,17 @@ impl crate::index::Index for BPlusTree {
     fn iter_all(&self) -> ExecutionResult<Vec<IndexEntry>> {
         self.scan_entries(IndexRange::full())
     }
+
+    fn scan(&self, range: IndexRange) -> ExecutionResult<BPlusTreeIter> {
+        let lower_key = range.lower.as_ref().map(|(key, _)| key);
+        let first_page = self.find_leaf_page(lower_key, false)?;
+        Ok(BPlusTreeIter {
+            tree: self.clone(),
+            range,
+            next_page: Some(first_page),
+            entries: Vec::new().into_iter(),
+        })
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -1005,6 +1478,10 @@ struct InternalPage {
     children: Vec<PageId>,
 }
 
+fn corrupt(message: String) -> ExecutionError {
+    ExecutionError::Execution(format!("index is corrupt: {}", message))
+}
+
 fn allocate_page(buffer_pool: &BufferPoolManager) -> ExecutionResult<PageId> {
     let page_id = buffer_pool
         .new_page()?
@@ -1020,15 +1497,7 @@ fn fetch_page<'a>(
 ) -> ExecutionResult<storage::PageGuard<'a>> {
     if let (Some(lock_manager), Some(txn_id)) = (wal::current_lock_manager(), wal::current_txn_id())
     {
-        let txn_id = TxnId(txn_id);
-        match mode {
-            LockMode::Shared => lock_manager
-                .lock_shared(txn_id, LockKey::Page(page_id))
-                .map_err(|err| ExecutionError::Execution(format!("lock error: {err:?}")))?,
-            LockMode::Exclusive => lock_manager
-                .lock_exclusive(txn_id, LockKey::Page(page_id))
-                .map_err(|err| ExecutionError::Execution(format!("lock error: {err:?}")))?,
-        }
+        lock_manager.lock(TxnId(txn_id), LockKey::Page(page_id), mode)?;
     }
     buffer_pool
         .fetch_page(page_id)?
@@ -1315,9 +1784,22 @@ fn pick_child_index(keys: &[IndexKey], key: &IndexKey, use_upper: bool) -> usize
     index
 }
 
+/// Orders `key` against a range bound. A composite bound with fewer
+/// components than the key is a prefix: only the key's leading components
+/// are compared, so `(1)` is both the lowest and the highest key starting
+/// with 1.
+fn compare_to_bound(key: &IndexKey, bound: &IndexKey) -> Ordering {
+    match (key, bound) {
+        (IndexKey::Composite(keys), IndexKey::Composite(prefix)) if prefix.len() < keys.len() => {
+            keys[..prefix.len()].cmp(prefix)
+        }
+        _ => key.cmp(bound),
+    }
+}
+
 fn matches_lower_bound(key: &IndexKey, range: &IndexRange) -> bool {
     match &range.lower {
-        Some((lower, inclusive)) => match key.cmp(lower) {
+        Some((lower, inclusive)) => match compare_to_bound(key, lower) {
             Ordering::Less => false,
             Ordering::Equal => *inclusive,
             Ordering::Greater => true,
@@ -1328,7 +1810,7 @@ fn matches_lower_bound(key: &IndexKey, range: &IndexRange) -> bool {
 
 fn matches_upper_stop(key: &IndexKey, range: &IndexRange) -> bool {
     match &range.upper {
-        Some((upper, inclusive)) => match key.cmp(upper) {
+        Some((upper, inclusive)) => match compare_to_bound(key, upper) {
             Ordering::Greater => true,
             Ordering::Equal => !*inclusive,
             Ordering::Less => false,
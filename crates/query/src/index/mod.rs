@@ -1,6 +1,8 @@
 mod btree;
 
-pub use btree::{BPlusTree, IndexEntry, IndexKey, IndexKeyType, IndexRange};
+pub use btree::{
+    BPlusTree, BPlusTreeIter, IndexEntry, IndexKey, IndexKeyType, IndexRange, IndexStats,
+};
 
 use crate::execution::operator::ExecutionResult;
 use crate::execution::seq_scan::Rid;
@@ -11,6 +13,8 @@ pub trait Index {
     fn get(&self, key: &IndexKey) -> ExecutionResult<Vec<Rid>>;
     fn range_scan(&self, range: IndexRange) -> ExecutionResult<Vec<Rid>>;
     fn iter_all(&self) -> ExecutionResult<Vec<IndexEntry>>;
+    /// The entries in `range`, read lazily in key order.
+    fn scan(&self, range: IndexRange) -> ExecutionResult<BPlusTreeIter>;
 }
 
 #[cfg(test)]
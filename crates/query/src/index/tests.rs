@@ -26,6 +26,7 @@ impl TestContext {
 impl Drop for TestContext {
     fn drop(&mut self) {
         let _ = fs::remove_file(&self.path);
+        let _ = fs::remove_file(self.path.with_extension("db.dwb"));
     }
 }
 
@@ -124,6 +125,46 @@ fn btree_range_scan_returns_sorted_slice() -> ExecutionResult<()> {
     Ok(())
 }
 
+#[test]
+fn btree_scan_reads_one_leaf_at_a_time() -> ExecutionResult<()> {
+    // far more leaves than the pool has frames
+    let (_ctx, bpm) = setup_bpm("btree_scan_lazy", 16);
+    let index = BPlusTree::create(bpm.clone(), IndexKeyType::Integer, None, false)?;
+    for key in 0..20_000 {
+        index.insert(IndexKey::Integer(key), rid_for(key))?;
+    }
+    let leaves = index.stats()?.leaf_pages;
+    assert!(leaves > 16 * 4, "{leaves} leaves");
+    let height = index.height()?;
+
+    bpm.enable_pin_tracking()?;
+    bpm.reset_fetch_count();
+    let range = IndexRange {
+        lower: Some((IndexKey::Integer(3), true)),
+        upper: None,
+    };
+    let mut entries = index.scan(range)?;
+    let first: Vec<Rid> = entries
+        .by_ref()
+        .take(5)
+        .map(|entry| entry.map(|entry| entry.rid))
+        .collect::<ExecutionResult<_>>()?;
+    assert_eq!(first, (3..8).map(rid_for).collect::<Vec<_>>());
+    // the header, the descent to the first leaf and its read, and nothing
+    // past it
+    assert_eq!(bpm.fetch_count(), 2 + height);
+    bpm.check_pins_released()?;
+
+    let mut expected = 8;
+    for entry in entries {
+        assert_eq!(entry?.rid, rid_for(expected));
+        expected += 1;
+    }
+    assert_eq!(expected, 20_000);
+    assert_eq!(bpm.fetch_count(), 1 + height + leaves);
+    Ok(())
+}
+
 #[test]
 fn btree_splits_increase_height() -> ExecutionResult<()> {
     let (_ctx, bpm) = setup_bpm("btree_splits", 64);
@@ -142,3 +183,91 @@ fn btree_splits_increase_height() -> ExecutionResult<()> {
     assert!(index.height()? >= 3);
     Ok(())
 }
+
+#[test]
+fn btree_deletes_merge_underfull_leaves() -> ExecutionResult<()> {
+    let (_ctx, bpm) = setup_bpm("btree_stats", 64);
+    let index = BPlusTree::create(bpm, IndexKeyType::Integer, None, false)?;
+    let keys: Vec<i64> = (0..2000).map(|i| (i * 7919) % 2000).collect();
+    for key in &keys {
+        index.insert(IndexKey::Integer(*key), rid_for(*key))?;
+    }
+    index.verify()?;
+    let loaded = index.stats()?;
+    assert_eq!(loaded.entries, 2000);
+    assert_eq!(loaded.empty_leaves, 0);
+
+    for key in keys.iter().filter(|key| *key % 20 != 0) {
+        assert!(index.delete(&IndexKey::Integer(*key), rid_for(*key))?);
+    }
+    assert_eq!(index.take_deletes(), 1900);
+    assert_eq!(index.deletes(), 0);
+    index.verify()?;
+    let churned = index.stats()?;
+    assert_eq!(churned.entries, 100);
+    assert_eq!(churned.empty_leaves, 0);
+    assert!(churned.leaf_pages < loaded.leaf_pages, "{churned:?}");
+    assert!(churned.fragmentation() < 0.5, "{churned:?}");
+    Ok(())
+}
+
+#[test]
+fn btree_merges_cascade_up_to_shrink_height() -> ExecutionResult<()> {
+    let (_ctx, bpm) = setup_bpm("btree_merges", 64);
+    let index = BPlusTree::create(bpm.clone(), IndexKeyType::Integer, None, false)?;
+    let target = index.max_leaf_entries() * (index.max_internal_entries() + 1) + 5;
+    for key in 0..target as i64 {
+        index.insert(IndexKey::Integer(key), rid_for(key))?;
+    }
+    assert_eq!(index.height()?, 3);
+    let pages = index.page_ids()?.len();
+
+    // delete from both ends and the middle, so leaves borrow and merge in
+    // either direction
+    let kept = |key: i64| key % 97 == 0;
+    let (low, high): (Vec<i64>, Vec<i64>) = (0..target as i64).partition(|key| key % 2 == 0);
+    for key in low.into_iter().chain(high.into_iter().rev()) {
+        if !kept(key) {
+            assert!(index.delete(&IndexKey::Integer(key), rid_for(key))?);
+        }
+    }
+    index.verify()?;
+    assert_eq!(index.height()?, 2);
+    assert!(index.page_ids()?.len() < pages / 10);
+    assert!(bpm.free_page_count()? > pages / 2);
+
+    for key in 0..target as i64 {
+        let expected = if kept(key) {
+            vec![rid_for(key)]
+        } else {
+            vec![]
+        };
+        assert_eq!(index.get(&IndexKey::Integer(key))?, expected, "key {key}");
+    }
+
+    for key in (0..target as i64).filter(|key| kept(*key)) {
+        assert!(index.delete(&IndexKey::Integer(key), rid_for(key))?);
+    }
+    index.verify()?;
+    assert_eq!(index.height()?, 1);
+    assert!(index.iter_all()?.is_empty());
+    Ok(())
+}
+
+#[test]
+fn btree_ascending_inserts_pack_leaves_in_page_order() -> ExecutionResult<()> {
+    let (_ctx, bpm) = setup_bpm("btree_packed", 64);
+    let index = BPlusTree::create(bpm, IndexKeyType::Integer, None, false)?;
+    for key in 0..2000 {
+        index.insert(IndexKey::Integer(key), rid_for(key))?;
+    }
+    index.verify()?;
+    let stats = index.stats()?;
+    assert_eq!(
+        stats.leaf_pages,
+        2000usize.div_ceil(index.max_leaf_entries())
+    );
+    assert_eq!(stats.fragmentation(), 0.0);
+    assert_eq!(stats.backward_links, 0);
+    Ok(())
+}
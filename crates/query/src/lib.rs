@@ -1,3 +1,4 @@
+pub mod change_feed;
 pub mod execution;
 pub mod expr;
 pub mod index;
@@ -7,16 +8,25 @@ pub mod planner;
 pub mod recovery;
 pub mod schema;
 
-pub use execution::{Catalog, Executor, PhysicalPlanner, Rid, TableHeap, TableInfo, Tuple, Value};
+pub use change_feed::{ChangeEvent, ChangeFeed, ChangeOp};
+pub use execution::{
+    CancellationToken, Catalog, Executor, GrantDef, PhysicalPlanner, PlanLimits, Rid, SequenceDef,
+    SharedCatalog, TableHeap, TableInfo, TableStats, TempUsage, Tuple, Value, DEFAULT_WORK_MEM,
+};
 pub use expr::{BinaryOperator, Expr, LiteralValue, UnaryOperator};
-pub use index::{BPlusTree, IndexEntry, IndexKey, IndexKeyType, IndexRange};
+pub use index::{BPlusTree, IndexEntry, IndexKey, IndexKeyType, IndexRange, IndexStats};
 pub use logical_plan::{
-    AggregateExpr, AggregateFunction, Assignment, JoinType, LogicalPlan, SortExpr,
+    AggregateExpr, AggregateFunction, Assignment, CopyDirection, CopyOptions, InsertConflict,
+    JoinType, LogicalPlan, Privilege, PrivilegeSpec, Returning, SampleMethod, Setting, SortExpr,
+    TableSample, TransactionControl,
 };
 pub use parser::SqlParser;
 pub use planner::LogicalPlanner;
 pub use recovery::RecoveryManager;
-pub use schema::{ColumnDef, DataType, DefaultValue, Field, Schema, TableSchema};
+pub use schema::{
+    CheckConstraint, Collation, ColumnDef, DataType, DefaultValue, Field, Schema, TableSchema,
+    TextFormat, UniqueConstraint,
+};
 
 use anyhow::Result;
 
@@ -27,6 +37,14 @@ pub fn sql_to_logical_plan(sql: &str) -> Result<LogicalPlan> {
     planner.plan_statement(stmt)
 }
 
+/// Plans a standalone SQL expression, such as a stored `CHECK` predicate.
+pub fn sql_to_expr(sql: &str) -> Result<Expr> {
+    let parser = SqlParser::new();
+    let expr = parser.parse_expr(sql)?;
+    let mut planner = LogicalPlanner::new();
+    planner.plan_expr(expr)
+}
+
 pub fn explain_sql(sql: &str) -> Result<String> {
     let plan = sql_to_logical_plan(sql)?;
     Ok(plan.explain())
@@ -1,5 +1,6 @@
-use crate::expr::Expr;
-use crate::schema::{ColumnDef, DataType, Field, Schema};
+use crate::execution::tuple::Value;
+use crate::expr::{Expr, LiteralValue};
+use crate::schema::{CheckConstraint, ColumnDef, DataType, Field, Schema, UniqueConstraint};
 use std::fmt;
 
 #[derive(Debug, Clone, PartialEq)]
@@ -8,6 +9,16 @@ pub enum LogicalPlan {
         table_name: String,
         alias: Option<String>,
         schema: Option<Schema>,
+        sample: Option<TableSample>,
+    },
+    /// One row with no columns: the input of a SELECT without FROM.
+    SingleRow,
+    /// Literal rows from a `VALUES` list. Columns are named `column1`,
+    /// `column2`, ... unless the derived table alias renames them.
+    Values {
+        rows: Vec<Vec<Expr>>,
+        alias: Option<String>,
+        columns: Vec<String>,
     },
     Filter {
         input: Box<LogicalPlan>,
@@ -28,6 +39,13 @@ pub enum LogicalPlan {
         input: Box<LogicalPlan>,
         sort_exprs: Vec<SortExpr>,
     },
+    /// `SELECT DISTINCT ON (on) ...`: the first row of each run of rows
+    /// with equal `on` values. The input is a `Sort` leading with them, so
+    /// each group is one run and its first row is first in the ORDER BY.
+    DistinctOn {
+        input: Box<LogicalPlan>,
+        on: Vec<Expr>,
+    },
     Limit {
         input: Box<LogicalPlan>,
         offset: Option<usize>,
@@ -42,28 +60,43 @@ pub enum LogicalPlan {
         table_name: String,
         columns: Option<Vec<String>>,
         values: Vec<Vec<Expr>>,
+        /// Rows from the query of `INSERT ... SELECT`; `values` is empty.
+        source: Option<Box<LogicalPlan>>,
+        on_conflict: InsertConflict,
+        returning: Option<Returning>,
         schema: Option<Schema>,
     },
     Update {
         table_name: String,
         assignments: Vec<Assignment>,
+        /// Rows joined against the target by `UPDATE ... FROM`.
+        from: Option<Box<LogicalPlan>>,
         filter: Option<Expr>,
         schema: Option<Schema>,
     },
     Delete {
         table_name: String,
+        /// Rows joined against the target by `DELETE ... USING`.
+        using: Option<Box<LogicalPlan>>,
         filter: Option<Expr>,
         schema: Option<Schema>,
     },
     CreateTable {
         table_name: String,
         columns: Vec<ColumnDef>,
+        constraints: Vec<UniqueConstraint>,
+        checks: Vec<CheckConstraint>,
         if_not_exists: bool,
+        /// Dropped when the creating transaction ends and seen only by it.
+        temporary: bool,
     },
     DropTable {
         table_name: String,
         if_exists: bool,
     },
+    Analyze {
+        table_name: String,
+    },
     AlterTableRename {
         table_name: String,
         new_table_name: String,
@@ -81,13 +114,209 @@ pub enum LogicalPlan {
         table_name: String,
         column_name: String,
     },
+    AlterTableAlterColumnType {
+        table_name: String,
+        column_name: String,
+        data_type: DataType,
+        using: Option<Expr>,
+    },
+    /// `ALTER TABLE ... ADD [CONSTRAINT name] CHECK (...)`; fails when a
+    /// stored row does not satisfy it.
+    AlterTableAddCheck {
+        table_name: String,
+        check: CheckConstraint,
+    },
     CreateIndex {
         table_name: String,
         index_name: String,
-        column_name: String,
+        /// Key columns in order; more than one makes a composite index.
+        column_names: Vec<String>,
         if_not_exists: bool,
         unique: bool,
     },
+    CreateSequence {
+        sequence_name: String,
+        start: i64,
+        increment: i64,
+        if_not_exists: bool,
+    },
+    DropSequence {
+        sequence_name: String,
+        if_exists: bool,
+    },
+    /// `GRANT <privileges> ON <tables> TO <roles>`.
+    Grant {
+        table_names: Vec<String>,
+        privileges: Vec<PrivilegeSpec>,
+        roles: Vec<String>,
+    },
+    /// `REVOKE <privileges> ON <tables> FROM <roles>`.
+    Revoke {
+        table_names: Vec<String>,
+        privileges: Vec<PrivilegeSpec>,
+        roles: Vec<String>,
+    },
+    /// `COPY <table> [(<columns>)] TO|FROM '<file>'` in CSV format.
+    Copy {
+        table_name: String,
+        columns: Option<Vec<String>>,
+        direction: CopyDirection,
+        path: String,
+        options: CopyOptions,
+    },
+    Transaction {
+        control: TransactionControl,
+    },
+    /// `SET <name> = <value>`: changes a setting for the rest of the session.
+    Set {
+        setting: Setting,
+    },
+    /// `EXPLAIN <statement>`: shows the physical plan without running it.
+    Explain {
+        input: Box<LogicalPlan>,
+    },
+}
+
+/// What an INSERT does with a row whose key is already taken in a unique
+/// index (`INSERT OR IGNORE`, `INSERT OR REPLACE`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum InsertConflict {
+    /// Fails the statement.
+    #[default]
+    Abort,
+    /// Skips the row.
+    Ignore,
+    /// Deletes the rows holding the key, then inserts.
+    Replace,
+}
+
+impl InsertConflict {
+    /// How EXPLAIN names an INSERT with this conflict action.
+    pub fn verb(self) -> &'static str {
+        match self {
+            InsertConflict::Abort => "Insert",
+            InsertConflict::Ignore => "Insert or ignore",
+            InsertConflict::Replace => "Insert or replace",
+        }
+    }
+}
+
+/// The expression a bound parameter, or a value a subquery returned, is
+/// replaced with.
+pub(crate) fn bound_value(value: &Value) -> Expr {
+    let literal = match value {
+        Value::Null => LiteralValue::Null,
+        Value::Integer(number) => LiteralValue::Integer(*number),
+        Value::Float(number) => LiteralValue::Float(*number),
+        Value::String(text) => LiteralValue::String(text.clone()),
+        Value::Boolean(flag) => LiteralValue::Boolean(*flag),
+        Value::Blob(bytes) => LiteralValue::Blob(bytes.clone()),
+        Value::Timestamp(number) => {
+            return Expr::Cast {
+                expr: Box::new(Expr::Literal(LiteralValue::Integer(*number))),
+                target_type: DataType::Timestamp,
+            }
+        }
+    };
+    Expr::Literal(literal)
+}
+
+/// Select list of `INSERT ... RETURNING`, evaluated over each row as it was
+/// stored, so defaults and generated ids appear in the output.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Returning {
+    pub expressions: Vec<Expr>,
+    /// Output column names, as `Project` takes them.
+    pub aliases: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CopyDirection {
+    /// Writes the table's rows to the file.
+    To,
+    /// Appends the file's rows to the table.
+    From,
+}
+
+/// CSV layout of a `COPY` file. A field is NULL only when it is unquoted and
+/// equal to `null`; a quoted field is always a value, so an empty string is
+/// written as `""` while the NULL string is empty.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CopyOptions {
+    pub delimiter: char,
+    pub quote: char,
+    pub null: String,
+    pub header: bool,
+}
+
+impl Default for CopyOptions {
+    fn default() -> Self {
+        Self {
+            delimiter: ',',
+            quote: '"',
+            null: String::new(),
+            header: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TransactionControl {
+    /// `snapshot` is set by `ISOLATION LEVEL REPEATABLE READ`: the
+    /// transaction reads from a snapshot taken when it begins instead of
+    /// locking the rows it reads.
+    Begin {
+        read_only: bool,
+        snapshot: bool,
+    },
+    Commit,
+    Rollback,
+    Savepoint(String),
+    RollbackToSavepoint(String),
+    ReleaseSavepoint(String),
+}
+
+impl fmt::Display for TransactionControl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TransactionControl::Begin {
+                read_only,
+                snapshot,
+            } => {
+                write!(f, "BEGIN")?;
+                if *read_only {
+                    write!(f, " READ ONLY")?;
+                }
+                if *snapshot {
+                    write!(f, " ISOLATION LEVEL REPEATABLE READ")?;
+                }
+                Ok(())
+            }
+            TransactionControl::Commit => write!(f, "COMMIT"),
+            TransactionControl::Rollback => write!(f, "ROLLBACK"),
+            TransactionControl::Savepoint(name) => write!(f, "SAVEPOINT {}", name),
+            TransactionControl::RollbackToSavepoint(name) => {
+                write!(f, "ROLLBACK TO SAVEPOINT {}", name)
+            }
+            TransactionControl::ReleaseSavepoint(name) => write!(f, "RELEASE SAVEPOINT {}", name),
+        }
+    }
+}
+
+/// A session setting changed with `SET`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Setting {
+    /// Bytes a sort or a join's hash table may hold in memory before it
+    /// spills to temporary pages or falls back to rescanning.
+    WorkMem(usize),
+}
+
+impl fmt::Display for Setting {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Setting::WorkMem(bytes) => write!(f, "work_mem = {}kB", bytes / 1024),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -97,6 +326,10 @@ pub enum JoinType {
     Right,
     Full,
     Cross,
+    /// Rows of the left input with at least one match on the right, each
+    /// once and without the right input's columns; planned from
+    /// `IN (subquery)`.
+    Semi,
 }
 
 impl fmt::Display for JoinType {
@@ -107,10 +340,98 @@ impl fmt::Display for JoinType {
             JoinType::Right => write!(f, "RIGHT"),
             JoinType::Full => write!(f, "FULL"),
             JoinType::Cross => write!(f, "CROSS"),
+            JoinType::Semi => write!(f, "SEMI"),
         }
     }
 }
 
+/// `TABLESAMPLE` on a scanned table. Each row (BERNOULLI) or each page
+/// (SYSTEM) is kept with probability `percent / 100`; a `REPEATABLE` seed
+/// makes the sample the same on every run over unchanged data.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TableSample {
+    pub method: SampleMethod,
+    pub percent: f64,
+    pub seed: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SampleMethod {
+    Bernoulli,
+    System,
+}
+
+impl fmt::Display for TableSample {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let method = match self.method {
+            SampleMethod::Bernoulli => "BERNOULLI",
+            SampleMethod::System => "SYSTEM",
+        };
+        write!(f, "TABLESAMPLE {} ({} PERCENT)", method, self.percent)?;
+        if let Some(seed) = self.seed {
+            write!(f, " REPEATABLE ({})", seed)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Privilege {
+    Select,
+    Insert,
+    Update,
+    Delete,
+}
+
+impl Privilege {
+    pub const ALL: [Privilege; 4] = [
+        Privilege::Select,
+        Privilege::Insert,
+        Privilege::Update,
+        Privilege::Delete,
+    ];
+
+    /// Maps an upper-case SQL privilege name to a privilege, if it is one.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "SELECT" => Some(Privilege::Select),
+            "INSERT" => Some(Privilege::Insert),
+            "UPDATE" => Some(Privilege::Update),
+            "DELETE" => Some(Privilege::Delete),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Privilege {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Privilege::Select => write!(f, "SELECT"),
+            Privilege::Insert => write!(f, "INSERT"),
+            Privilege::Update => write!(f, "UPDATE"),
+            Privilege::Delete => write!(f, "DELETE"),
+        }
+    }
+}
+
+/// One privilege of a GRANT or REVOKE, on the whole table or on the listed
+/// columns.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrivilegeSpec {
+    pub privilege: Privilege,
+    pub columns: Option<Vec<String>>,
+}
+
+impl fmt::Display for PrivilegeSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.privilege)?;
+        if let Some(columns) = &self.columns {
+            write!(f, " ({})", columns.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct Assignment {
     pub column: String,
@@ -140,6 +461,33 @@ pub enum AggregateFunction {
     Max,
 }
 
+impl AggregateFunction {
+    /// Maps an upper-case SQL function name to an aggregate, if it is one.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "COUNT" => Some(AggregateFunction::Count),
+            "SUM" => Some(AggregateFunction::Sum),
+            "AVG" => Some(AggregateFunction::Avg),
+            "MIN" => Some(AggregateFunction::Min),
+            "MAX" => Some(AggregateFunction::Max),
+            _ => None,
+        }
+    }
+}
+
+impl AggregateExpr {
+    /// Column name of the aggregate in its operator's output. It matches the
+    /// rendering of the call in the select list, so `SUM(price)` in a
+    /// projection or HAVING clause resolves to this column.
+    pub fn output_name(&self) -> String {
+        Expr::Function {
+            name: self.func.to_string(),
+            args: self.args.clone(),
+        }
+        .to_string()
+    }
+}
+
 impl std::fmt::Display for AggregateFunction {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -182,6 +530,19 @@ impl LogicalPlan {
                     Schema::empty()
                 }
             }
+            LogicalPlan::SingleRow => Schema::empty(),
+            LogicalPlan::Values { alias, columns, .. } => Schema::new(
+                columns
+                    .iter()
+                    .map(|name| Field {
+                        name: name.clone(),
+                        table: alias.clone(),
+                        data_type: DataType::Text, // Simplified
+                        nullable: true,
+                        visible: true,
+                    })
+                    .collect(),
+            ),
             LogicalPlan::Filter { input, .. } => input.schema(),
             LogicalPlan::Project {
                 expressions,
@@ -209,16 +570,70 @@ impl LogicalPlan {
                         .collect(),
                 )
             }
-            LogicalPlan::Join { left, right, .. } => {
+            LogicalPlan::Join {
+                left,
+                join_type: JoinType::Semi,
+                ..
+            } => left.schema(),
+            LogicalPlan::Join {
+                left,
+                right,
+                join_type,
+                ..
+            } => {
                 let left_schema = left.schema();
                 let right_schema = right.schema();
                 let mut fields = left_schema.fields.clone();
-                fields.extend(right_schema.fields);
+                fields.extend(right_schema.fields.into_iter().map(|mut field| {
+                    field.nullable |= *join_type == JoinType::Left;
+                    field
+                }));
                 Schema::new(fields)
             }
             LogicalPlan::Sort { input, .. } => input.schema(),
+            LogicalPlan::DistinctOn { input, .. } => input.schema(),
             LogicalPlan::Limit { input, .. } => input.schema(),
-            LogicalPlan::Aggregate { .. } => Schema::empty(),
+            LogicalPlan::Aggregate {
+                group_by,
+                aggregates,
+                ..
+            } => Schema::new(
+                group_by
+                    .iter()
+                    .map(|expr| (expr.to_string(), DataType::Text))
+                    .chain(aggregates.iter().map(|aggregate| {
+                        let data_type = match aggregate.func {
+                            AggregateFunction::Count => DataType::Integer,
+                            AggregateFunction::Avg => DataType::Real,
+                            _ => DataType::Text, // Simplified
+                        };
+                        (aggregate.output_name(), data_type)
+                    }))
+                    .map(|(name, data_type)| Field {
+                        name,
+                        table: None,
+                        data_type,
+                        nullable: true,
+                        visible: true,
+                    })
+                    .collect(),
+            ),
+            LogicalPlan::Insert {
+                returning: Some(returning),
+                ..
+            } => Schema::new(
+                returning
+                    .aliases
+                    .iter()
+                    .map(|alias| Field {
+                        name: alias.clone(),
+                        table: None,
+                        data_type: DataType::Text, // Simplified
+                        nullable: true,
+                        visible: true,
+                    })
+                    .collect(),
+            ),
             LogicalPlan::Insert { schema: _, .. }
             | LogicalPlan::Update { schema: _, .. }
             | LogicalPlan::Delete { schema: _, .. } => Schema::new(vec![Field {
@@ -231,16 +646,219 @@ impl LogicalPlan {
             LogicalPlan::CreateTable { .. }
             | LogicalPlan::CreateIndex { .. }
             | LogicalPlan::DropTable { .. }
+            | LogicalPlan::Analyze { .. }
             | LogicalPlan::AlterTableRename { .. }
             | LogicalPlan::AlterTableRenameColumn { .. }
             | LogicalPlan::AlterTableAddColumn { .. }
-            | LogicalPlan::AlterTableDropColumn { .. } => Schema::new(vec![Field {
+            | LogicalPlan::AlterTableDropColumn { .. }
+            | LogicalPlan::AlterTableAlterColumnType { .. }
+            | LogicalPlan::AlterTableAddCheck { .. }
+            | LogicalPlan::CreateSequence { .. }
+            | LogicalPlan::DropSequence { .. }
+            | LogicalPlan::Grant { .. }
+            | LogicalPlan::Revoke { .. }
+            | LogicalPlan::Copy { .. }
+            | LogicalPlan::Transaction { .. }
+            | LogicalPlan::Set { .. } => Schema::new(vec![Field {
                 name: "status".to_string(),
                 table: None,
                 data_type: DataType::Text,
                 nullable: false,
                 visible: true,
             }]),
+            LogicalPlan::Explain { .. } => Schema::new(vec![Field {
+                name: "plan".to_string(),
+                table: None,
+                data_type: DataType::Text,
+                nullable: false,
+                visible: true,
+            }]),
+        }
+    }
+
+    /// Replaces each `?` parameter with the value bound at its position.
+    /// Bound values become literals, so index scans are chosen for them as
+    /// for values written in the SQL. Fails unless exactly one value is
+    /// bound per parameter.
+    pub fn bind_parameters(&mut self, params: &[Value]) -> anyhow::Result<()> {
+        let mut parameters = 0;
+        self.visit_exprs_mut(&mut |expr| {
+            expr.visit_mut(&mut |expr| {
+                if let Expr::Parameter(index) = *expr {
+                    parameters = parameters.max(index + 1);
+                    if let Some(value) = params.get(index) {
+                        *expr = bound_value(value);
+                    }
+                }
+            })
+        });
+        if parameters != params.len() {
+            anyhow::bail!(
+                "statement has {} parameters but {} values were bound",
+                parameters,
+                params.len()
+            );
+        }
+        Ok(())
+    }
+
+    /// Calls `f` on each expression of this plan and of the plans it reads.
+    pub(crate) fn visit_exprs_mut(&mut self, f: &mut dyn FnMut(&mut Expr)) {
+        match self {
+            LogicalPlan::Values { rows, .. } => rows.iter_mut().flatten().for_each(f),
+            LogicalPlan::Filter { input, predicate } => {
+                f(predicate);
+                input.visit_exprs_mut(f);
+            }
+            LogicalPlan::Project {
+                input, expressions, ..
+            } => {
+                expressions.iter_mut().for_each(&mut *f);
+                input.visit_exprs_mut(f);
+            }
+            LogicalPlan::Join {
+                left,
+                right,
+                condition,
+                ..
+            } => {
+                condition.iter_mut().for_each(&mut *f);
+                left.visit_exprs_mut(f);
+                right.visit_exprs_mut(f);
+            }
+            LogicalPlan::Sort { input, sort_exprs } => {
+                for sort_expr in sort_exprs {
+                    f(&mut sort_expr.expr);
+                }
+                input.visit_exprs_mut(f);
+            }
+            LogicalPlan::DistinctOn { input, on } => {
+                on.iter_mut().for_each(&mut *f);
+                input.visit_exprs_mut(f);
+            }
+            LogicalPlan::Aggregate {
+                input,
+                group_by,
+                aggregates,
+            } => {
+                group_by.iter_mut().for_each(&mut *f);
+                for aggregate in aggregates {
+                    aggregate.args.iter_mut().for_each(&mut *f);
+                }
+                input.visit_exprs_mut(f);
+            }
+            LogicalPlan::Insert {
+                values,
+                source,
+                returning,
+                ..
+            } => {
+                values.iter_mut().flatten().for_each(&mut *f);
+                if let Some(returning) = returning {
+                    returning.expressions.iter_mut().for_each(&mut *f);
+                }
+                if let Some(source) = source {
+                    source.visit_exprs_mut(f);
+                }
+            }
+            LogicalPlan::Update {
+                assignments,
+                from,
+                filter,
+                ..
+            } => {
+                for assignment in assignments {
+                    f(&mut assignment.value);
+                }
+                filter.iter_mut().for_each(&mut *f);
+                if let Some(from) = from {
+                    from.visit_exprs_mut(f);
+                }
+            }
+            LogicalPlan::Delete { using, filter, .. } => {
+                filter.iter_mut().for_each(&mut *f);
+                if let Some(using) = using {
+                    using.visit_exprs_mut(f);
+                }
+            }
+            LogicalPlan::AlterTableAlterColumnType { using, .. } => {
+                using.iter_mut().for_each(f);
+            }
+            LogicalPlan::Limit { input, .. } | LogicalPlan::Explain { input } => {
+                input.visit_exprs_mut(f)
+            }
+            _ => {}
+        }
+    }
+
+    /// True for plans that never modify data, the catalog or a sequence,
+    /// the only ones allowed in a read-only transaction.
+    pub fn is_read_only(&self) -> bool {
+        match self {
+            LogicalPlan::Scan { .. }
+            | LogicalPlan::SingleRow
+            | LogicalPlan::Values { .. }
+            | LogicalPlan::Filter { .. }
+            | LogicalPlan::Project { .. }
+            | LogicalPlan::Join { .. }
+            | LogicalPlan::Sort { .. }
+            | LogicalPlan::DistinctOn { .. }
+            | LogicalPlan::Limit { .. }
+            | LogicalPlan::Aggregate { .. } => !self.calls_nextval(),
+            LogicalPlan::Analyze { .. }
+            | LogicalPlan::Transaction { .. }
+            | LogicalPlan::Set { .. }
+            | LogicalPlan::Explain { .. } => true,
+            LogicalPlan::Copy { direction, .. } => *direction == CopyDirection::To,
+            LogicalPlan::Insert { .. }
+            | LogicalPlan::Update { .. }
+            | LogicalPlan::Delete { .. }
+            | LogicalPlan::CreateTable { .. }
+            | LogicalPlan::CreateIndex { .. }
+            | LogicalPlan::DropTable { .. }
+            | LogicalPlan::AlterTableRename { .. }
+            | LogicalPlan::AlterTableRenameColumn { .. }
+            | LogicalPlan::AlterTableAddColumn { .. }
+            | LogicalPlan::AlterTableDropColumn { .. }
+            | LogicalPlan::AlterTableAlterColumnType { .. }
+            | LogicalPlan::AlterTableAddCheck { .. }
+            | LogicalPlan::CreateSequence { .. }
+            | LogicalPlan::DropSequence { .. }
+            | LogicalPlan::Grant { .. }
+            | LogicalPlan::Revoke { .. } => false,
+        }
+    }
+
+    /// True when an expression of the plan, or of a subquery in it, calls
+    /// `NEXTVAL` and so advances a sequence.
+    fn calls_nextval(&self) -> bool {
+        let mut calls = false;
+        // the expression walkers take the plan mutably
+        self.clone().visit_exprs_mut(&mut |expr| {
+            expr.visit_mut(&mut |expr| {
+                if let Expr::Function { name, .. } = expr {
+                    calls |= name.eq_ignore_ascii_case("NEXTVAL");
+                }
+            })
+        });
+        calls
+    }
+
+    /// True when the plan applies LIMIT/OFFSET to rows with no ORDER BY,
+    /// so which rows come back depends on storage order.
+    pub fn has_unordered_limit(&self) -> bool {
+        match self {
+            LogicalPlan::Limit { input, .. } => {
+                let mut ordered = input.as_ref();
+                while let LogicalPlan::Project { input, .. }
+                | LogicalPlan::DistinctOn { input, .. } = ordered
+                {
+                    ordered = input;
+                }
+                !matches!(ordered, LogicalPlan::Sort { .. })
+            }
+            LogicalPlan::Project { input, .. } => input.has_unordered_limit(),
+            _ => false,
         }
     }
 
@@ -253,14 +871,30 @@ impl LogicalPlan {
 
         match self {
             LogicalPlan::Scan {
-                table_name, alias, ..
+                table_name,
+                alias,
+                sample,
+                ..
             } => {
+                let sample = sample
+                    .map(|sample| format!(" {}", sample))
+                    .unwrap_or_default();
                 if let Some(alias_name) = alias {
-                    format!("{}Scan: {} (alias: {})", prefix, table_name, alias_name)
+                    format!(
+                        "{}Scan: {} (alias: {}){}",
+                        prefix, table_name, alias_name, sample
+                    )
                 } else {
-                    format!("{}Scan: {}", prefix, table_name)
+                    format!("{}Scan: {}{}", prefix, table_name, sample)
                 }
             }
+            LogicalPlan::SingleRow => format!("{}SingleRow", prefix),
+            LogicalPlan::Values { rows, columns, .. } => format!(
+                "{}Values: {} rows, columns=[{}]",
+                prefix,
+                rows.len(),
+                columns.join(", ")
+            ),
             LogicalPlan::Filter { input, predicate } => {
                 format!(
                     "{}Filter: {}\n{}",
@@ -327,6 +961,19 @@ impl LogicalPlan {
                     input.explain_with_indent(child_indent)
                 )
             }
+            LogicalPlan::DistinctOn { input, on } => {
+                let on_str = on
+                    .iter()
+                    .map(|e| format!("{}", e))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                format!(
+                    "{}DistinctOn: [{}]\n{}",
+                    prefix,
+                    on_str,
+                    input.explain_with_indent(child_indent)
+                )
+            }
             LogicalPlan::Limit {
                 input,
                 offset,
@@ -379,23 +1026,37 @@ impl LogicalPlan {
                 table_name,
                 columns,
                 values,
+                source,
+                on_conflict,
                 ..
             } => {
                 let col_str = columns
                     .as_ref()
                     .map(|c| format!("({})", c.join(", ")))
                     .unwrap_or_else(|| "(all columns)".to_string());
-                format!(
-                    "{}Insert into {}{}: {} rows",
-                    prefix,
-                    table_name,
-                    col_str,
-                    values.len()
-                )
+                match source {
+                    Some(source) => format!(
+                        "{}{} into {}{}\n{}",
+                        prefix,
+                        on_conflict.verb(),
+                        table_name,
+                        col_str,
+                        source.explain_with_indent(child_indent)
+                    ),
+                    None => format!(
+                        "{}{} into {}{}: {} rows",
+                        prefix,
+                        on_conflict.verb(),
+                        table_name,
+                        col_str,
+                        values.len()
+                    ),
+                }
             }
             LogicalPlan::Update {
                 table_name,
                 assignments,
+                from,
                 filter,
                 ..
             } => {
@@ -408,29 +1069,47 @@ impl LogicalPlan {
                     .as_ref()
                     .map(|f| format!(" WHERE {}", f))
                     .unwrap_or_default();
+                let from_str = from
+                    .as_ref()
+                    .map(|source| format!("\n{}", source.explain_with_indent(child_indent)))
+                    .unwrap_or_default();
                 format!(
-                    "{}Update {}: SET {}{}",
-                    prefix, table_name, assign_str, filter_str
+                    "{}Update {}: SET {}{}{}",
+                    prefix, table_name, assign_str, filter_str, from_str
                 )
             }
             LogicalPlan::Delete {
-                table_name, filter, ..
+                table_name,
+                using,
+                filter,
+                ..
             } => {
                 let filter_str = filter
                     .as_ref()
                     .map(|f| format!(" WHERE {}", f))
                     .unwrap_or_default();
-                format!("{}Delete from {}{}", prefix, table_name, filter_str)
+                let using_str = using
+                    .as_ref()
+                    .map(|source| format!("\n{}", source.explain_with_indent(child_indent)))
+                    .unwrap_or_default();
+                format!(
+                    "{}Delete from {}{}{}",
+                    prefix, table_name, filter_str, using_str
+                )
             }
             LogicalPlan::CreateTable {
                 table_name,
                 columns,
                 if_not_exists,
+                temporary,
+                ..
             } => {
+                let temp = if *temporary { " TEMPORARY" } else { "" };
                 let ine = if *if_not_exists { " IF NOT EXISTS" } else { "" };
                 format!(
-                    "{}CreateTable{} {}: {} columns",
+                    "{}CreateTable{}{} {}: {} columns",
                     prefix,
+                    temp,
                     ine,
                     table_name,
                     columns.len()
@@ -443,6 +1122,7 @@ impl LogicalPlan {
                 let ie = if *if_exists { " IF EXISTS" } else { "" };
                 format!("{}DropTable{} {}", prefix, ie, table_name)
             }
+            LogicalPlan::Analyze { table_name } => format!("{}Analyze {}", prefix, table_name),
             LogicalPlan::AlterTableRename {
                 table_name,
                 new_table_name,
@@ -472,10 +1152,37 @@ impl LogicalPlan {
                 "{}AlterTable {} DROP COLUMN {}",
                 prefix, table_name, column_name
             ),
+            LogicalPlan::AlterTableAlterColumnType {
+                table_name,
+                column_name,
+                data_type,
+                using,
+            } => format!(
+                "{}AlterTable {} ALTER COLUMN {} TYPE {:?}{}",
+                prefix,
+                table_name,
+                column_name,
+                data_type,
+                using
+                    .as_ref()
+                    .map(|expr| format!(" USING {}", expr))
+                    .unwrap_or_default()
+            ),
+            LogicalPlan::AlterTableAddCheck { table_name, check } => format!(
+                "{}AlterTable {} ADD {}CHECK ({})",
+                prefix,
+                table_name,
+                check
+                    .name
+                    .as_ref()
+                    .map(|name| format!("CONSTRAINT {} ", name))
+                    .unwrap_or_default(),
+                check.sql
+            ),
             LogicalPlan::CreateIndex {
                 table_name,
                 index_name,
-                column_name,
+                column_names,
                 unique,
                 ..
             } => format!(
@@ -484,7 +1191,67 @@ impl LogicalPlan {
                 index_name,
                 table_name,
                 if *unique { "UNIQUE " } else { "" },
-                column_name
+                column_names.join(", ")
+            ),
+            LogicalPlan::CreateSequence {
+                sequence_name,
+                start,
+                increment,
+                if_not_exists,
+            } => {
+                let ine = if *if_not_exists { " IF NOT EXISTS" } else { "" };
+                format!(
+                    "{}CreateSequence{} {} START {} INCREMENT {}",
+                    prefix, ine, sequence_name, start, increment
+                )
+            }
+            LogicalPlan::DropSequence {
+                sequence_name,
+                if_exists,
+            } => {
+                let ie = if *if_exists { " IF EXISTS" } else { "" };
+                format!("{}DropSequence{} {}", prefix, ie, sequence_name)
+            }
+            LogicalPlan::Grant {
+                table_names,
+                privileges,
+                roles,
+            } => format!(
+                "{}Grant {} ON {} TO {}",
+                prefix,
+                join_display(privileges),
+                table_names.join(", "),
+                roles.join(", ")
+            ),
+            LogicalPlan::Revoke {
+                table_names,
+                privileges,
+                roles,
+            } => format!(
+                "{}Revoke {} ON {} FROM {}",
+                prefix,
+                join_display(privileges),
+                table_names.join(", "),
+                roles.join(", ")
+            ),
+            LogicalPlan::Copy {
+                table_name,
+                direction,
+                path,
+                ..
+            } => {
+                let direction = match direction {
+                    CopyDirection::To => "TO",
+                    CopyDirection::From => "FROM",
+                };
+                format!("{}Copy {} {} {}", prefix, table_name, direction, path)
+            }
+            LogicalPlan::Transaction { control } => format!("{}Transaction {}", prefix, control),
+            LogicalPlan::Set { setting } => format!("{}Set {}", prefix, setting),
+            LogicalPlan::Explain { input } => format!(
+                "{}Explain\n{}",
+                prefix,
+                input.explain_with_indent(child_indent)
             ),
         }
     }
@@ -531,6 +1298,8 @@ impl LogicalPlan {
                 );
                 (label, vec![])
             }
+            LogicalPlan::SingleRow => ("SingleRow".to_string(), vec![]),
+            LogicalPlan::Values { rows, .. } => (format!("Values: {} rows", rows.len()), vec![]),
             LogicalPlan::Filter { predicate, input } => {
                 let label = format!("Filter: {}", predicate);
                 (label, vec![input])
@@ -577,6 +1346,15 @@ impl LogicalPlan {
                 let label = format!("Sort: [{}]", sort_str);
                 (label, vec![input])
             }
+            LogicalPlan::DistinctOn { on, input } => {
+                let on_str = on
+                    .iter()
+                    .map(|e| format!("{}", e))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                let label = format!("DistinctOn: [{}]", on_str);
+                (label, vec![input])
+            }
             LogicalPlan::Limit {
                 offset,
                 limit,
@@ -638,15 +1416,24 @@ impl LogicalPlan {
                 table_name,
                 columns,
                 values,
+                source,
+                on_conflict,
                 ..
             } => {
                 let col_str = columns
                     .as_ref()
                     .map(|c| format!("({})", c.join(", ")))
                     .unwrap_or_default();
+                if let Some(source) = source {
+                    return (
+                        format!("{} into {}{}", on_conflict.verb(), table_name, col_str),
+                        vec![source.as_ref()],
+                    );
+                }
                 let val_count = values.first().map(|v| v.len()).unwrap_or(0);
                 let label = format!(
-                    "Insert into {}{}\n{} rows, {} values each",
+                    "{} into {}{}\n{} rows, {} values each",
+                    on_conflict.verb(),
                     table_name,
                     col_str,
                     values.len(),
@@ -657,6 +1444,7 @@ impl LogicalPlan {
             LogicalPlan::Update {
                 table_name,
                 assignments,
+                from,
                 filter,
                 ..
             } => {
@@ -670,17 +1458,20 @@ impl LogicalPlan {
                     .map(|f| format!(" WHERE {}", f))
                     .unwrap_or_default();
                 let label = format!("Update {}\nSet: {}{}", table_name, assign_str, filter_str);
-                (label, vec![])
+                (label, from.iter().map(|source| source.as_ref()).collect())
             }
             LogicalPlan::Delete {
-                table_name, filter, ..
+                table_name,
+                using,
+                filter,
+                ..
             } => {
                 let filter_str = filter
                     .as_ref()
                     .map(|f| format!(" WHERE {}", f))
                     .unwrap_or_default();
                 let label = format!("Delete from {}{}", table_name, filter_str);
-                (label, vec![])
+                (label, using.iter().map(|source| source.as_ref()).collect())
             }
             LogicalPlan::CreateTable {
                 table_name,
@@ -699,6 +1490,10 @@ impl LogicalPlan {
                 let label = format!("DropTable: {}", table_name);
                 (label, vec![])
             }
+            LogicalPlan::Analyze { table_name } => {
+                let label = format!("Analyze: {}", table_name);
+                (label, vec![])
+            }
             LogicalPlan::AlterTableRename {
                 table_name,
                 new_table_name,
@@ -731,10 +1526,26 @@ impl LogicalPlan {
                 let label = format!("AlterTable {} Drop Column {}", table_name, column_name);
                 (label, vec![])
             }
+            LogicalPlan::AlterTableAlterColumnType {
+                table_name,
+                column_name,
+                data_type,
+                ..
+            } => {
+                let label = format!(
+                    "AlterTable {} Alter Column {} Type {:?}",
+                    table_name, column_name, data_type
+                );
+                (label, vec![])
+            }
+            LogicalPlan::AlterTableAddCheck { table_name, check } => {
+                let label = format!("AlterTable {} Add Check ({})", table_name, check.sql);
+                (label, vec![])
+            }
             LogicalPlan::CreateIndex {
                 table_name,
                 index_name,
-                column_name,
+                column_names,
                 unique,
                 ..
             } => {
@@ -743,10 +1554,34 @@ impl LogicalPlan {
                     if *unique { "UNIQUE " } else { "" },
                     index_name,
                     table_name,
-                    column_name
+                    column_names.join(", ")
                 );
                 (label, vec![])
             }
+            LogicalPlan::CreateSequence { sequence_name, .. } => {
+                (format!("CreateSequence: {}", sequence_name), vec![])
+            }
+            LogicalPlan::DropSequence { sequence_name, .. } => {
+                (format!("DropSequence: {}", sequence_name), vec![])
+            }
+            LogicalPlan::Grant { table_names, .. } => {
+                (format!("Grant: {}", table_names.join(", ")), vec![])
+            }
+            LogicalPlan::Revoke { table_names, .. } => {
+                (format!("Revoke: {}", table_names.join(", ")), vec![])
+            }
+            LogicalPlan::Copy { table_name, .. } => (format!("Copy: {}", table_name), vec![]),
+            LogicalPlan::Transaction { control } => (format!("Transaction {}", control), vec![]),
+            LogicalPlan::Set { setting } => (format!("Set {}", setting), vec![]),
+            LogicalPlan::Explain { input } => ("Explain".to_string(), vec![input.as_ref()]),
         }
     }
 }
+
+fn join_display(privileges: &[PrivilegeSpec]) -> String {
+    privileges
+        .iter()
+        .map(|privilege| privilege.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
@@ -1,6 +1,7 @@
-use sqlparser::ast::Statement;
-use sqlparser::dialect::GenericDialect;
+use sqlparser::ast::{Expr, Statement};
+use sqlparser::dialect::{GenericDialect, PostgreSqlDialect, SQLiteDialect};
 use sqlparser::parser::{Parser, ParserError};
+use sqlparser::tokenizer::{Location, Token, TokenWithLocation, Tokenizer};
 
 pub struct SqlParser {
     dialect: GenericDialect,
@@ -13,7 +14,22 @@ impl SqlParser {
         }
     }
     pub fn parse(&self, sql: &str) -> Result<Vec<Statement>, ParserError> {
-        Parser::parse_sql(&self.dialect, sql)
+        let rewritten = self.rewrite_tablesample(sql);
+        let sql = rewritten.as_deref().unwrap_or(sql);
+        let numbered = self.number_placeholders(sql);
+        let sql = numbered.as_deref().unwrap_or(sql);
+        Parser::parse_sql(&self.dialect, sql).or_else(|err| {
+            // `ALTER COLUMN c TYPE t [USING expr]` is only parsed by the
+            // PostgreSQL dialect, and `INSERT OR IGNORE | REPLACE` by the
+            // SQLite one.
+            if starts_with_keyword(sql, "ALTER") {
+                Parser::parse_sql(&PostgreSqlDialect {}, sql).map_err(|_| err)
+            } else if starts_with_keyword(sql, "INSERT") {
+                Parser::parse_sql(&SQLiteDialect {}, sql).map_err(|_| err)
+            } else {
+                Err(err)
+            }
+        })
     }
     pub fn parse_one(&self, sql: &str) -> Result<Statement, ParserError> {
         let statements = self.parse(sql)?;
@@ -29,6 +45,150 @@ impl SqlParser {
         }
         Ok(statements.into_iter().next().unwrap())
     }
+    pub fn parse_expr(&self, sql: &str) -> Result<Expr, ParserError> {
+        Parser::new(&self.dialect).try_with_sql(sql)?.parse_expr()
+    }
+
+    /// sqlparser 0.52 does not parse `TABLESAMPLE`, so each
+    /// `TABLESAMPLE [BERNOULLI | SYSTEM] (p [PERCENT]) [REPEATABLE (seed)]`
+    /// clause is rewritten into the table hint
+    /// `WITH (TABLESAMPLE(method, p[, seed]))`, which the logical planner
+    /// reads back. Returns `None` when there is nothing to rewrite.
+    fn rewrite_tablesample(&self, sql: &str) -> Option<String> {
+        if !sql.to_ascii_uppercase().contains("TABLESAMPLE") {
+            return None;
+        }
+        let tokens = Tokenizer::new(&self.dialect, sql)
+            .tokenize_with_location()
+            .ok()?
+            .into_iter()
+            .filter(|token| !matches!(token.token, Token::Whitespace(_)))
+            .collect::<Vec<_>>();
+        let mut rewrites = Vec::new();
+        for (position, token) in tokens.iter().enumerate() {
+            if is_word(&token.token, "TABLESAMPLE") {
+                if let Some((end, hint)) = parse_tablesample(&tokens[position + 1..]) {
+                    rewrites.push((token.location, end, hint));
+                }
+            }
+        }
+        if rewrites.is_empty() {
+            return None;
+        }
+        let mut rewritten = sql.to_string();
+        for (start, end, hint) in rewrites.into_iter().rev() {
+            let start = byte_offset(sql, start)?;
+            // the clause always ends with a one-character `)`
+            let end = byte_offset(sql, end)? + 1;
+            rewritten.replace_range(start..end, &hint);
+        }
+        Some(rewritten)
+    }
+
+    /// Numbers each `?` placeholder in the order it appears, as `$1`, `$2`,
+    /// ..., so that parameters bind by position however the planner walks
+    /// the statement. Returns `None` when there is none.
+    fn number_placeholders(&self, sql: &str) -> Option<String> {
+        if !sql.contains('?') {
+            return None;
+        }
+        let placeholders = Tokenizer::new(&self.dialect, sql)
+            .tokenize_with_location()
+            .ok()?
+            .into_iter()
+            .filter(|token| matches!(&token.token, Token::Placeholder(text) if text == "?"))
+            .map(|token| token.location)
+            .collect::<Vec<_>>();
+        if placeholders.is_empty() {
+            return None;
+        }
+        let mut rewritten = sql.to_string();
+        for (index, location) in placeholders.into_iter().enumerate().rev() {
+            let start = byte_offset(sql, location)?;
+            rewritten.replace_range(start..start + 1, &format!("${}", index + 1));
+        }
+        Some(rewritten)
+    }
+}
+
+/// Parses the tokens following `TABLESAMPLE` and returns the location of the
+/// clause's closing parenthesis together with its table hint.
+fn parse_tablesample(tokens: &[TokenWithLocation]) -> Option<(Location, String)> {
+    let mut tokens = tokens.iter().peekable();
+    let mut method = "BERNOULLI";
+    if let Some(token) = tokens.peek() {
+        if let Some(named) = ["BERNOULLI", "SYSTEM"]
+            .into_iter()
+            .find(|named| is_word(&token.token, named))
+        {
+            method = named;
+            tokens.next();
+        }
+    }
+    expect_token(&mut tokens, &Token::LParen)?;
+    let percent = expect_number(&mut tokens)?;
+    if tokens
+        .peek()
+        .is_some_and(|token| is_word(&token.token, "PERCENT"))
+    {
+        tokens.next();
+    }
+    let mut end = expect_token(&mut tokens, &Token::RParen)?;
+    let mut hint = format!("WITH (TABLESAMPLE({}, {}", method, percent);
+    if tokens
+        .peek()
+        .is_some_and(|token| is_word(&token.token, "REPEATABLE"))
+    {
+        tokens.next();
+        expect_token(&mut tokens, &Token::LParen)?;
+        let seed = expect_number(&mut tokens)?;
+        end = expect_token(&mut tokens, &Token::RParen)?;
+        hint.push_str(&format!(", {}", seed));
+    }
+    hint.push_str("))");
+    Some((end, hint))
+}
+
+fn expect_token<'a>(
+    tokens: &mut impl Iterator<Item = &'a TokenWithLocation>,
+    expected: &Token,
+) -> Option<Location> {
+    let token = tokens.next()?;
+    (&token.token == expected).then_some(token.location)
+}
+
+fn expect_number<'a>(tokens: &mut impl Iterator<Item = &'a TokenWithLocation>) -> Option<String> {
+    match &tokens.next()?.token {
+        Token::Number(number, false) => Some(number.clone()),
+        _ => None,
+    }
+}
+
+fn is_word(token: &Token, word: &str) -> bool {
+    matches!(token, Token::Word(w) if w.quote_style.is_none() && w.value.eq_ignore_ascii_case(word))
+}
+
+/// Byte offset of a tokenizer location, whose line and column count
+/// characters from 1.
+fn byte_offset(sql: &str, location: Location) -> Option<usize> {
+    let line_start = if location.line <= 1 {
+        0
+    } else {
+        sql.match_indices('\n')
+            .nth(location.line as usize - 2)
+            .map(|(index, _)| index + 1)?
+    };
+    let column = sql[line_start..]
+        .char_indices()
+        .nth(location.column as usize - 1)
+        .map(|(index, _)| index)?;
+    Some(line_start + column)
+}
+
+fn starts_with_keyword(sql: &str, keyword: &str) -> bool {
+    sql.split_whitespace()
+        .next()
+        .is_some_and(|word| word.eq_ignore_ascii_case(keyword))
 }
 
 impl Default for SqlParser {
@@ -66,6 +226,37 @@ mod tests {
         assert!(result.is_err());
     }
     #[test]
+    fn test_parse_alter_column_type_using() {
+        let parser = SqlParser::new();
+        let result = parser.parse_one("ALTER TABLE t ALTER COLUMN c TYPE INT USING CAST(c AS INT)");
+        assert!(result.is_ok());
+    }
+    #[test]
+    fn test_parse_tablesample() {
+        let parser = SqlParser::new();
+        let result = parser.parse_one(
+            "SELECT * FROM t AS x TABLESAMPLE SYSTEM (10 PERCENT) REPEATABLE (7) WHERE id > 1",
+        );
+        assert_eq!(
+            result.expect("tablesample").to_string(),
+            "SELECT * FROM t AS x WITH (TABLESAMPLE(SYSTEM, 10, 7)) WHERE id > 1"
+        );
+        let result = parser.parse_one("SELECT 'TABLESAMPLE (1)' FROM t tablesample (2.5)");
+        assert_eq!(
+            result.expect("tablesample").to_string(),
+            "SELECT 'TABLESAMPLE (1)' FROM t WITH (TABLESAMPLE(BERNOULLI, 2.5))"
+        );
+    }
+    #[test]
+    fn test_parse_placeholders() {
+        let parser = SqlParser::new();
+        let result = parser.parse_one("SELECT '?' FROM t WHERE a = ? AND b IN (?, ?)");
+        assert_eq!(
+            result.expect("placeholders").to_string(),
+            "SELECT '?' FROM t WHERE a = $1 AND b IN ($2, $3)"
+        );
+    }
+    #[test]
     fn test_parse_empty() {
         let parser = SqlParser::new();
         let result = parser.parse_one("");
@@ -5,16 +5,25 @@ use crate::expr::{
     UnaryOperator as LocalUnaryOperator,
 };
 use crate::logical_plan::{
-    AggregateExpr, AggregateFunction, Assignment, JoinType, LogicalPlan, SortExpr,
+    AggregateExpr, AggregateFunction, Assignment, CopyDirection, CopyOptions, InsertConflict,
+    JoinType, LogicalPlan, Privilege, PrivilegeSpec, Returning, SampleMethod, Setting, SortExpr,
+    TableSample, TransactionControl,
+};
+use crate::schema::{
+    CheckConstraint, Collation, ColumnDef, DataType as LocalDataType, DefaultValue, TextFormat,
+    UniqueConstraint,
 };
-use crate::schema::{ColumnDef, DataType as LocalDataType, DefaultValue};
 use anyhow::{bail, Context, Result};
 use sqlparser::ast::{
-    AlterTableOperation, AssignmentTarget, BinaryOperator as SqlBinaryOp, ColumnOption,
-    CreateTable, DataType as SqlDataType, Delete, Expr as SqlExpr, FromTable, FunctionArg,
-    FunctionArgExpr, FunctionArguments, GroupByExpr, Insert, JoinConstraint, JoinOperator,
-    ObjectName, OrderByExpr, Query, SelectItem, SetExpr, Statement, TableFactor, TableWithJoins,
-    UnaryOperator as SqlUnaryOp, Value,
+    Action, AlterColumnOperation, AlterTableOperation, AssignmentTarget,
+    BinaryOperator as SqlBinaryOp, ColumnOption, CopyLegacyCsvOption, CopyLegacyOption, CopyOption,
+    CopySource, CopyTarget, CreateTable, DataType as SqlDataType, Delete, Distinct,
+    Expr as SqlExpr, FromTable, FunctionArg, FunctionArgExpr, FunctionArguments, GrantObjects,
+    GroupByExpr, Ident, Insert, JoinConstraint, JoinOperator, ObjectName, OnCommit,
+    OneOrManyWithParens, OrderByExpr, Privileges, Query, Select, SelectItem, SequenceOptions,
+    SetExpr, SqliteOnConflict, Statement, TableConstraint, TableFactor, TableWithJoins,
+    TransactionAccessMode, TransactionIsolationLevel, TransactionMode, UnaryOperator as SqlUnaryOp,
+    Value, Values,
 };
 use std::collections::HashMap;
 
@@ -37,6 +46,7 @@ impl LogicalPlanner {
             Statement::Update {
                 table,
                 assignments,
+                from,
                 selection,
                 ..
             } => {
@@ -45,7 +55,7 @@ impl LogicalPlanner {
                     TableWithJoins { relation, joins } if joins.is_empty() => relation,
                     _ => bail!("UPDATE only supports simple table references"),
                 };
-                self.plan_update(tf, assignments, selection)
+                self.plan_update(tf, assignments, from, selection)
             }
             Statement::Delete(delete) => self.plan_delete(delete),
             Statement::CreateTable(ct) => self.plan_create_table(ct),
@@ -56,6 +66,48 @@ impl LogicalPlanner {
                 create_index.if_not_exists,
                 create_index.unique,
             ),
+            Statement::CreateSequence {
+                temporary,
+                if_not_exists,
+                name,
+                sequence_options,
+                ..
+            } => {
+                if temporary {
+                    bail!("temporary sequences are not supported");
+                }
+                plan_create_sequence(name, sequence_options, if_not_exists)
+            }
+            Statement::Grant {
+                privileges,
+                objects,
+                grantees,
+                with_grant_option,
+                ..
+            } => {
+                if with_grant_option {
+                    bail!("WITH GRANT OPTION is not supported");
+                }
+                let (table_names, privileges) = plan_privileges(privileges, objects)?;
+                Ok(LogicalPlan::Grant {
+                    table_names,
+                    privileges,
+                    roles: grantees.into_iter().map(|role| role.value).collect(),
+                })
+            }
+            Statement::Revoke {
+                privileges,
+                objects,
+                grantees,
+                ..
+            } => {
+                let (table_names, privileges) = plan_privileges(privileges, objects)?;
+                Ok(LogicalPlan::Revoke {
+                    table_names,
+                    privileges,
+                    roles: grantees.into_iter().map(|role| role.value).collect(),
+                })
+            }
             Statement::Drop {
                 object_type,
                 if_exists,
@@ -65,6 +117,58 @@ impl LogicalPlanner {
             Statement::AlterTable {
                 name, operations, ..
             } => self.plan_alter_table(name, operations),
+            Statement::Analyze { table_name, .. } => Ok(LogicalPlan::Analyze {
+                table_name: table_name_of(&table_name)?,
+            }),
+            Statement::StartTransaction { modes, .. } => Ok(LogicalPlan::Transaction {
+                control: TransactionControl::Begin {
+                    read_only: modes.contains(&TransactionMode::AccessMode(
+                        TransactionAccessMode::ReadOnly,
+                    )),
+                    snapshot: modes.contains(&TransactionMode::IsolationLevel(
+                        TransactionIsolationLevel::RepeatableRead,
+                    )),
+                },
+            }),
+            Statement::Commit { .. } => Ok(LogicalPlan::Transaction {
+                control: TransactionControl::Commit,
+            }),
+            Statement::Rollback { savepoint, .. } => Ok(LogicalPlan::Transaction {
+                control: match savepoint {
+                    Some(name) => TransactionControl::RollbackToSavepoint(name.value),
+                    None => TransactionControl::Rollback,
+                },
+            }),
+            Statement::Savepoint { name } => Ok(LogicalPlan::Transaction {
+                control: TransactionControl::Savepoint(name.value),
+            }),
+            Statement::ReleaseSavepoint { name } => Ok(LogicalPlan::Transaction {
+                control: TransactionControl::ReleaseSavepoint(name.value),
+            }),
+            Statement::SetVariable {
+                local: false,
+                hivevar: false,
+                variables: OneOrManyWithParens::One(name),
+                value,
+            } => plan_set(&name, &value),
+            Statement::Copy {
+                source,
+                to,
+                target,
+                options,
+                legacy_options,
+                ..
+            } => plan_copy(source, to, target, options, legacy_options),
+            Statement::Explain {
+                analyze, statement, ..
+            } => {
+                if analyze {
+                    bail!("EXPLAIN ANALYZE is not supported");
+                }
+                Ok(LogicalPlan::Explain {
+                    input: Box::new(self.plan_statement(*statement)?),
+                })
+            }
             _ => bail!("Unsupported statement type: {:?}", stmt),
         }
     }
@@ -76,42 +180,154 @@ impl LogicalPlanner {
             .offset
             .map(|o| self.parse_limit_expr(o.value))
             .transpose()?;
-        let select = match *query.body {
-            SetExpr::Select(s) => s,
+        let mut distinct_on = Vec::new();
+        let mut plan = match *query.body {
+            SetExpr::Select(mut select) => {
+                if let Some(Distinct::On(exprs)) = select.distinct.take() {
+                    distinct_on = exprs;
+                }
+                self.plan_select(*select)?
+            }
             SetExpr::Query(q) => return self.plan_query(*q),
+            SetExpr::Values(values) => self.plan_values(values)?,
             _ => bail!("UNION/INTERSECT/EXCEPT not yet supported"),
         };
-        let mut plan = self.plan_from_clause(&select.from)?;
-        if let Some(selection) = select.selection {
-            let predicate = self.plan_expr(selection)?;
-            self.validate_filter_predicate(&predicate, &plan)?;
-            plan = LogicalPlan::Filter {
+        let select_list = match &plan {
+            LogicalPlan::Project { expressions, .. } => expressions.clone(),
+            LogicalPlan::Values { columns, .. } => columns
+                .iter()
+                .map(|column| LocalExpr::Column {
+                    table: None,
+                    name: column.clone(),
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+        let mut sort_exprs = Vec::new();
+        if let Some(order) = order_by {
+            for o in &order.exprs {
+                let mut sort_expr = self.plan_order_by_expr(o)?;
+                sort_expr.expr = resolve_ordinal(sort_expr.expr, &select_list, "ORDER BY")?;
+                sort_exprs.push(sort_expr);
+            }
+        }
+        if !distinct_on.is_empty() {
+            let on = distinct_on
+                .into_iter()
+                .map(|e| resolve_ordinal(self.plan_expr(e)?, &select_list, "DISTINCT ON"))
+                .collect::<Result<Vec<_>>>()?;
+            sort_by_distinct_on(&mut sort_exprs, &on)?;
+            plan = LogicalPlan::DistinctOn {
+                input: Box::new(LogicalPlan::Sort {
+                    input: Box::new(plan),
+                    sort_exprs,
+                }),
+                on,
+            };
+        } else if !sort_exprs.is_empty() {
+            plan = LogicalPlan::Sort {
                 input: Box::new(plan),
-                predicate,
+                sort_exprs,
             };
         }
-        match &select.group_by {
-            GroupByExpr::Expressions(exprs, _) if !exprs.is_empty() => {
-                let group_by_exprs: Result<Vec<_>> =
-                    exprs.iter().map(|e| self.plan_expr(e.clone())).collect();
-                let group_by_exprs = group_by_exprs?;
-                let aggregates = self.extract_aggregates(&select.projection)?;
-                plan = LogicalPlan::Aggregate {
+        if limit.is_some() || offset.is_some() {
+            plan = LogicalPlan::Limit {
+                input: Box::new(plan),
+                offset,
+                limit,
+            };
+        }
+        Ok(plan)
+    }
+
+    /// Plans `expr IN (subquery)` as a semi-join of `input` with the
+    /// subquery, which must return a single column.
+    fn plan_in_subquery(
+        &mut self,
+        input: LogicalPlan,
+        expr: SqlExpr,
+        subquery: Query,
+    ) -> Result<LogicalPlan> {
+        let key = self.plan_expr(expr)?;
+        let subplan = self.plan_query(subquery)?;
+        let column = subquery_column(&subplan)?;
+        Ok(LogicalPlan::Join {
+            left: Box::new(input),
+            right: Box::new(subplan),
+            join_type: JoinType::Semi,
+            condition: Some(LocalExpr::BinaryOp {
+                left: Box::new(key),
+                op: LocalBinaryOperator::Eq,
+                right: Box::new(column),
+            }),
+        })
+    }
+
+    fn plan_select(&mut self, select: Select) -> Result<LogicalPlan> {
+        let mut plan = self.plan_from_clause(&select.from)?;
+        if let Some(selection) = select.selection {
+            let mut conditions = Vec::new();
+            for conjunct in split_sql_conjunction(selection) {
+                match conjunct {
+                    SqlExpr::InSubquery {
+                        expr,
+                        subquery,
+                        negated: false,
+                    } => plan = self.plan_in_subquery(plan, *expr, *subquery)?,
+                    other => conditions.push(other),
+                }
+            }
+            let selection = conditions
+                .into_iter()
+                .reduce(|left, right| SqlExpr::BinaryOp {
+                    left: Box::new(left),
+                    op: SqlBinaryOp::And,
+                    right: Box::new(right),
+                });
+            if let Some(selection) = selection {
+                let predicate = self.plan_expr(selection)?;
+                self.validate_filter_predicate(&predicate, &plan)?;
+                plan = LogicalPlan::Filter {
                     input: Box::new(plan),
-                    group_by: group_by_exprs,
-                    aggregates,
+                    predicate,
                 };
             }
-            _ => {}
         }
-        if let Some(having) = select.having {
-            let predicate = self.plan_expr(having)?;
+        let (expressions, aliases) = self.plan_select_items(&select.projection)?;
+        let group_by = match &select.group_by {
+            GroupByExpr::Expressions(exprs, _) => exprs
+                .iter()
+                .map(|e| {
+                    let expr =
+                        resolve_ordinal(self.plan_expr(e.clone())?, &expressions, "GROUP BY")?;
+                    let mut aggregates = Vec::new();
+                    collect_aggregates(&expr, &mut aggregates);
+                    if !aggregates.is_empty() {
+                        bail!("aggregate functions are not allowed in GROUP BY");
+                    }
+                    Ok(expr)
+                })
+                .collect::<Result<Vec<_>>>()?,
+            _ => Vec::new(),
+        };
+        let having = select.having.map(|h| self.plan_expr(h)).transpose()?;
+        let mut aggregates = Vec::new();
+        for expr in expressions.iter().chain(having.iter()) {
+            collect_aggregates(expr, &mut aggregates);
+        }
+        if !group_by.is_empty() || !aggregates.is_empty() {
+            plan = LogicalPlan::Aggregate {
+                input: Box::new(plan),
+                group_by,
+                aggregates,
+            };
+        }
+        if let Some(predicate) = having {
             plan = LogicalPlan::Filter {
                 input: Box::new(plan),
                 predicate,
             };
         }
-        let (expressions, aliases) = self.plan_select_items(&select.projection)?;
         plan = LogicalPlan::Project {
             input: Box::new(plan),
             expressions,
@@ -121,31 +337,31 @@ impl LogicalPlanner {
                 Some(aliases)
             },
         };
-        if let Some(order) = order_by {
-            let sort_exprs: Result<Vec<_>> = order
-                .exprs
-                .iter()
-                .map(|o| self.plan_order_by_expr(o))
-                .collect();
-            let sort_exprs = sort_exprs?;
-            plan = LogicalPlan::Sort {
-                input: Box::new(plan),
-                sort_exprs,
-            };
-        }
-        if limit.is_some() || offset.is_some() {
-            plan = LogicalPlan::Limit {
-                input: Box::new(plan),
-                offset,
-                limit,
-            };
-        }
         Ok(plan)
     }
 
+    /// Plans a `VALUES` list. Every row must have the same number of
+    /// expressions; columns get the default names `column1`, `column2`, ...
+    fn plan_values(&mut self, values: Values) -> Result<LogicalPlan> {
+        let width = values.rows.first().map_or(0, Vec::len);
+        if values.rows.iter().any(|row| row.len() != width) {
+            bail!("VALUES lists must all be the same length");
+        }
+        let rows = values
+            .rows
+            .into_iter()
+            .map(|row| row.into_iter().map(|e| self.plan_expr(e)).collect())
+            .collect::<Result<Vec<Vec<LocalExpr>>>>()?;
+        Ok(LogicalPlan::Values {
+            rows,
+            alias: None,
+            columns: (1..=width).map(|i| format!("column{}", i)).collect(),
+        })
+    }
+
     fn plan_from_clause(&mut self, from: &[TableWithJoins]) -> Result<LogicalPlan> {
         if from.is_empty() {
-            bail!("SELECT without FROM clause not supported");
+            return Ok(LogicalPlan::SingleRow);
         }
         if from.len() > 1 {
             bail!("Multiple comma-separated tables not supported [use JOIN]");
@@ -185,8 +401,13 @@ impl LogicalPlanner {
 
     fn plan_table_factor(&mut self, tf: &TableFactor) -> Result<LogicalPlan> {
         match tf {
-            TableFactor::Table { name, alias, .. } => {
-                let tbl = object_name_to_string(name);
+            TableFactor::Table {
+                name,
+                alias,
+                with_hints,
+                ..
+            } => {
+                let tbl = table_name_of(name)?;
                 let alias_name = alias.as_ref().map(|a| a.name.value.clone());
                 if let Some(ref a) = alias_name {
                     self.table_aliases.insert(a.clone(), tbl.clone());
@@ -195,16 +416,38 @@ impl LogicalPlanner {
                     table_name: tbl,
                     alias: alias_name,
                     schema: None,
+                    sample: plan_table_sample(with_hints)?,
                 })
             }
             TableFactor::Derived {
                 subquery, alias, ..
             } => {
-                let subplan = self.plan_query(*subquery.clone())?;
+                let mut subplan = self.plan_query(*subquery.clone())?;
                 if let Some(a) = alias {
                     let alias_name = a.name.value.clone();
                     self.table_aliases
                         .insert(alias_name.clone(), "subquery".to_string());
+                    if let LogicalPlan::Values {
+                        alias: values_alias,
+                        columns,
+                        ..
+                    } = &mut subplan
+                    {
+                        if a.columns.len() > columns.len() {
+                            bail!(
+                                "{} has {} columns but {} column aliases were given",
+                                alias_name,
+                                columns.len(),
+                                a.columns.len()
+                            );
+                        }
+                        for (column, name) in columns.iter_mut().zip(&a.columns) {
+                            *column = name.value.clone();
+                        }
+                        *values_alias = Some(alias_name);
+                    } else if !a.columns.is_empty() {
+                        bail!("column aliases are only supported for VALUES");
+                    }
                 }
                 Ok(subplan)
             }
@@ -241,7 +484,7 @@ impl LogicalPlanner {
                     aliases.push("*".to_string());
                 }
                 SelectItem::QualifiedWildcard(obj_name, _) => {
-                    let table = object_name_to_string(obj_name);
+                    let table = table_name_of(obj_name)?;
                     expressions.push(LocalExpr::QualifiedWildcard {
                         table: table.clone(),
                     });
@@ -252,7 +495,7 @@ impl LogicalPlanner {
         Ok((expressions, aliases))
     }
 
-    fn plan_expr(&mut self, expr: SqlExpr) -> Result<LocalExpr> {
+    pub fn plan_expr(&mut self, expr: SqlExpr) -> Result<LocalExpr> {
         match expr {
             SqlExpr::Identifier(ident) => Ok(LocalExpr::Column {
                 table: None,
@@ -265,6 +508,10 @@ impl LogicalPlanner {
                         name: idents[1].value.clone(),
                     })
                 } else if idents.len() == 3 {
+                    // database.table.column
+                    if !idents[0].value.eq_ignore_ascii_case(MAIN_DATABASE) {
+                        bail!("unknown database {}", idents[0].value);
+                    }
                     Ok(LocalExpr::Column {
                         table: Some(idents[1].value.clone()),
                         name: idents[2].value.clone(),
@@ -276,6 +523,7 @@ impl LogicalPlanner {
                     );
                 }
             }
+            SqlExpr::Value(Value::Placeholder(placeholder)) => plan_parameter(&placeholder),
             SqlExpr::Value(value) => Ok(LocalExpr::Literal(self.plan_value(value)?)),
             SqlExpr::BinaryOp { left, op, right } => Ok(LocalExpr::BinaryOp {
                 left: Box::new(self.plan_expr(*left)?),
@@ -324,6 +572,32 @@ impl LogicalPlanner {
                     negated,
                 })
             }
+            SqlExpr::Like {
+                negated,
+                any,
+                expr,
+                pattern,
+                escape_char,
+            } => {
+                if any {
+                    bail!("LIKE ANY is not supported");
+                }
+                let pattern = match escape_char {
+                    None => self.plan_expr(*pattern)?,
+                    Some(escape) => LocalExpr::Literal(LiteralValue::String(rewrite_like_escape(
+                        *pattern, &escape,
+                    )?)),
+                };
+                Ok(LocalExpr::BinaryOp {
+                    left: Box::new(self.plan_expr(*expr)?),
+                    op: if negated {
+                        LocalBinaryOperator::NotLike
+                    } else {
+                        LocalBinaryOperator::Like
+                    },
+                    right: Box::new(pattern),
+                })
+            }
             SqlExpr::Function(func) => {
                 let name = object_name_to_string(&func.name);
                 let args = match &func.args {
@@ -343,6 +617,23 @@ impl LogicalPlanner {
                 })
             }
             SqlExpr::Nested(expr) => self.plan_expr(*expr),
+            SqlExpr::InSubquery {
+                expr,
+                subquery,
+                negated,
+            } => {
+                let expr = self.plan_expr(*expr)?;
+                let subplan = self.plan_query(*subquery)?;
+                subquery_column(&subplan)?;
+                Ok(LocalExpr::InSubquery {
+                    expr: Box::new(expr),
+                    subplan: Box::new(subplan),
+                    negated,
+                })
+            }
+            SqlExpr::Subquery(query) => Ok(LocalExpr::ScalarSubquery(Box::new(
+                self.plan_query(*query)?,
+            ))),
             _ => bail!("Unsupported expression type: {:?}", expr),
         }
     }
@@ -353,7 +644,7 @@ impl LogicalPlanner {
                 FunctionArgExpr::Expr(e) => self.plan_expr(e.clone()),
                 FunctionArgExpr::Wildcard => Ok(LocalExpr::Wildcard),
                 FunctionArgExpr::QualifiedWildcard(name) => {
-                    let table = object_name_to_string(name);
+                    let table = table_name_of(name)?;
                     Ok(LocalExpr::QualifiedWildcard { table })
                 }
             },
@@ -361,7 +652,7 @@ impl LogicalPlanner {
                 FunctionArgExpr::Expr(e) => self.plan_expr(e.clone()),
                 FunctionArgExpr::Wildcard => Ok(LocalExpr::Wildcard),
                 FunctionArgExpr::QualifiedWildcard(name) => {
-                    let table = object_name_to_string(name);
+                    let table = table_name_of(name)?;
                     Ok(LocalExpr::QualifiedWildcard { table })
                 }
             },
@@ -377,9 +668,12 @@ impl LogicalPlanner {
                     Ok(LiteralValue::Integer(s.parse().context("Invalid integer")?))
                 }
             }
-            Value::SingleQuotedString(s) | Value::DoubleQuotedString(s) => {
-                Ok(LiteralValue::String(s))
-            }
+            // the tokenizer has already folded `''` and, for `E'...'`,
+            // backslash escapes into the string
+            Value::SingleQuotedString(s)
+            | Value::DoubleQuotedString(s)
+            | Value::EscapedStringLiteral(s)
+            | Value::NationalStringLiteral(s) => Ok(LiteralValue::String(s)),
             Value::Boolean(b) => Ok(LiteralValue::Boolean(b)),
             Value::Null => Ok(LiteralValue::Null),
             Value::HexStringLiteral(hex) => Ok(LiteralValue::Blob(self.parse_hex_literal(&hex)?)),
@@ -455,39 +749,80 @@ impl LogicalPlanner {
     }
 
     fn plan_insert(&mut self, ins: Insert) -> Result<LogicalPlan> {
-        let table = object_name_to_string(&ins.table_name);
+        let table = table_name_of(&ins.table_name)?;
+        let on_conflict = match ins.or {
+            None | Some(SqliteOnConflict::Abort) => InsertConflict::Abort,
+            Some(SqliteOnConflict::Ignore) => InsertConflict::Ignore,
+            Some(SqliteOnConflict::Replace) => InsertConflict::Replace,
+            Some(other) => bail!("INSERT OR {} is not supported", other),
+        };
         let column_names = if ins.columns.is_empty() {
             None
         } else {
             Some(ins.columns.into_iter().map(|c| c.value).collect())
         };
+        let returning = ins
+            .returning
+            .map(|items| {
+                self.plan_select_items(&items)
+                    .map(|(expressions, aliases)| Returning {
+                        expressions,
+                        aliases,
+                    })
+            })
+            .transpose()?;
         if let Some(query) = ins.source {
             if let SetExpr::Values(values) = *query.body {
                 let rows: Result<Vec<Vec<LocalExpr>>> = values
                     .rows
                     .into_iter()
-                    .map(|row| row.into_iter().map(|e| self.plan_expr(e)).collect())
+                    .map(|row| row.into_iter().map(|e| self.plan_value_expr(e)).collect())
                     .collect();
                 return Ok(LogicalPlan::Insert {
                     table_name: table,
                     columns: column_names,
                     values: rows?,
+                    source: None,
+                    on_conflict,
+                    returning,
                     schema: None,
                 });
             }
-            bail!("INSERT ... SELECT not yet supported");
+            return Ok(LogicalPlan::Insert {
+                table_name: table,
+                columns: column_names,
+                values: Vec::new(),
+                source: Some(Box::new(self.plan_query(*query)?)),
+                on_conflict,
+                returning,
+                schema: None,
+            });
         }
         bail!("INSERT requires VALUES clause");
     }
 
+    /// Plans an INSERT value or UPDATE assignment, where a bare `DEFAULT`
+    /// stands for the column's default.
+    fn plan_value_expr(&mut self, expr: SqlExpr) -> Result<LocalExpr> {
+        match expr {
+            SqlExpr::Identifier(ident)
+                if ident.quote_style.is_none() && ident.value.eq_ignore_ascii_case("DEFAULT") =>
+            {
+                Ok(LocalExpr::Default)
+            }
+            expr => self.plan_expr(expr),
+        }
+    }
+
     fn plan_update(
         &mut self,
         table: &TableFactor,
         assignments: Vec<sqlparser::ast::Assignment>,
+        from: Option<TableWithJoins>,
         selection: Option<SqlExpr>,
     ) -> Result<LogicalPlan> {
         let table_name = match table {
-            TableFactor::Table { name, .. } => object_name_to_string(name),
+            TableFactor::Table { name, .. } => table_name_of(name)?,
             _ => bail!("UPDATE only supports simple table references"),
         };
         let planned_assignments: Result<Vec<_>> = assignments
@@ -497,14 +832,18 @@ impl LogicalPlanner {
                     AssignmentTarget::ColumnName(name) => object_name_to_string(name),
                     _ => bail!("Only column assignments supported"),
                 };
-                let value = self.plan_expr(a.value)?;
+                let value = self.plan_value_expr(a.value)?;
                 Ok(Assignment { column: col, value })
             })
             .collect();
+        let from = from
+            .map(|source| self.plan_from_clause(&[source]).map(Box::new))
+            .transpose()?;
         let filter = selection.map(|e| self.plan_expr(e)).transpose()?;
         Ok(LogicalPlan::Update {
             table_name,
             assignments: planned_assignments?,
+            from,
             filter,
             schema: None,
         })
@@ -522,29 +861,108 @@ impl LogicalPlanner {
             TableWithJoins {
                 relation: TableFactor::Table { name, .. },
                 joins,
-            } if joins.is_empty() => object_name_to_string(name),
+            } if joins.is_empty() => table_name_of(name)?,
             _ => bail!("DELETE only supports simple table references"),
         };
+        let using = del
+            .using
+            .map(|sources| self.plan_from_clause(&sources).map(Box::new))
+            .transpose()?;
         let filter = del.selection.map(|e| self.plan_expr(e)).transpose()?;
         Ok(LogicalPlan::Delete {
             table_name,
+            using,
             filter,
             schema: None,
         })
     }
 
     fn plan_create_table(&mut self, ct: CreateTable) -> Result<LogicalPlan> {
-        let table_name = object_name_to_string(&ct.name);
+        let table_name = table_name_of(&ct.name)?;
+        if !ct.temporary && ct.on_commit.is_some() {
+            bail!("ON COMMIT can only be used on temporary tables");
+        }
+        if matches!(
+            ct.on_commit,
+            Some(OnCommit::DeleteRows | OnCommit::PreserveRows)
+        ) {
+            bail!("temporary tables are dropped when their transaction ends; only ON COMMIT DROP is supported");
+        }
+        let mut checks = Vec::new();
+        for col in &ct.columns {
+            for option in &col.options {
+                if let ColumnOption::Check(expr) = &option.option {
+                    checks.push(self.plan_check(option.name.clone(), expr.clone())?);
+                }
+            }
+        }
         let column_defs: Result<Vec<_>> = ct
             .columns
             .into_iter()
             .map(|col| self.plan_column_def(col))
             .collect();
-        let column_defs = column_defs?;
+        let mut column_defs = column_defs?;
+        let mut constraints = Vec::new();
+        for constraint in ct.constraints {
+            let (name, columns, is_primary) = match constraint {
+                TableConstraint::Unique { name, columns, .. } => (name, columns, false),
+                TableConstraint::PrimaryKey { name, columns, .. } => (name, columns, true),
+                TableConstraint::Check { name, expr } => {
+                    checks.push(self.plan_check(name, *expr)?);
+                    continue;
+                }
+                _ => continue,
+            };
+            let columns: Vec<String> = columns.into_iter().map(|ident| ident.value).collect();
+            if columns.is_empty() {
+                bail!("UNIQUE constraint requires at least one column");
+            }
+            let mut positions = Vec::with_capacity(columns.len());
+            for column in &columns {
+                let position = column_defs
+                    .iter()
+                    .position(|def| def.name.eq_ignore_ascii_case(column))
+                    .with_context(|| format!("constraint references unknown column {}", column))?;
+                let def = &mut column_defs[position];
+                if def.data_type == LocalDataType::Blob {
+                    bail!("BLOB columns cannot be PRIMARY KEY or UNIQUE");
+                }
+                if is_primary {
+                    def.nullable = false;
+                }
+                positions.push(position);
+            }
+            if let [position] = positions[..] {
+                let def = &mut column_defs[position];
+                def.unique = true;
+                def.primary_key |= is_primary;
+                continue;
+            }
+            constraints.push(UniqueConstraint {
+                name: name.map(|ident| ident.value),
+                columns,
+                is_primary,
+            });
+        }
         Ok(LogicalPlan::CreateTable {
             table_name,
             columns: column_defs,
+            constraints,
+            checks,
             if_not_exists: ct.if_not_exists,
+            temporary: ct.temporary,
+        })
+    }
+
+    fn plan_check(
+        &mut self,
+        name: Option<sqlparser::ast::Ident>,
+        expr: SqlExpr,
+    ) -> Result<CheckConstraint> {
+        Ok(CheckConstraint {
+            name: name.map(|ident| ident.value),
+            sql: expr.to_string(),
+            expr: self.plan_expr(expr)?,
         })
     }
 
@@ -556,7 +974,7 @@ impl LogicalPlanner {
         if_not_exists: bool,
         unique: bool,
     ) -> Result<LogicalPlan> {
-        let table_name = object_name_to_string(&table_name);
+        let table_name = table_name_of(&table_name)?;
         let index_name = index_name
             .map(|name| object_name_to_string(&name))
             .unwrap_or_else(|| format!("idx_{}", table_name));
@@ -565,22 +983,40 @@ impl LogicalPlanner {
             bail!("CREATE INDEX requires at least one column");
         }
 
-        let column = match &columns[0].expr {
-            SqlExpr::Identifier(ident) => ident.value.clone(),
-            _ => bail!("CREATE INDEX only supports simple column references"),
-        };
+        let column_names = columns
+            .iter()
+            .map(|column| match &column.expr {
+                SqlExpr::Identifier(ident) => Ok(ident.value.clone()),
+                _ => bail!("CREATE INDEX only supports simple column references"),
+            })
+            .collect::<Result<Vec<_>>>()?;
 
         Ok(LogicalPlan::CreateIndex {
             table_name,
             index_name,
-            column_name: column,
+            column_names,
             if_not_exists,
             unique,
         })
     }
 
     fn plan_column_def(&mut self, col: sqlparser::ast::ColumnDef) -> Result<ColumnDef> {
-        let data_type = self.convert_data_type(&col.data_type)?;
+        // DATE and DATETIME columns store canonical text until real date
+        // types exist
+        let (data_type, text_format) = match &col.data_type {
+            SqlDataType::Date => (LocalDataType::Text, Some(TextFormat::Date)),
+            SqlDataType::Datetime(_) => (LocalDataType::Text, Some(TextFormat::DateTime)),
+            other => (self.convert_data_type(other)?, None),
+        };
+        let collation = match &col.collation {
+            None => None,
+            Some(name) => match name.to_string().to_uppercase().as_str() {
+                "BINARY" => None,
+                "NOCASE" if data_type == LocalDataType::Text => Some(Collation::NoCase),
+                "NOCASE" => bail!("COLLATE NOCASE applies only to TEXT columns"),
+                other => bail!("unsupported collation {}", other),
+            },
+        };
         let mut nullable = true;
         let mut primary_key = false;
         let mut unique = false;
@@ -633,6 +1069,8 @@ impl LogicalPlanner {
             unique,
             default_value,
             auto_increment,
+            text_format,
+            collation,
         })
     }
 
@@ -648,11 +1086,20 @@ impl LogicalPlanner {
                     bail!("DROP TABLE only supports single table");
                 }
                 Ok(LogicalPlan::DropTable {
-                    table_name: object_name_to_string(&names[0]),
+                    table_name: table_name_of(&names[0])?,
+                    if_exists,
+                })
+            }
+            sqlparser::ast::ObjectType::Sequence => {
+                if names.len() != 1 {
+                    bail!("DROP SEQUENCE only supports single sequence");
+                }
+                Ok(LogicalPlan::DropSequence {
+                    sequence_name: object_name_to_string(&names[0]),
                     if_exists,
                 })
             }
-            _ => bail!("Only DROP TABLE supported"),
+            _ => bail!("Only DROP TABLE and DROP SEQUENCE supported"),
         }
     }
 
@@ -661,7 +1108,7 @@ impl LogicalPlanner {
         name: ObjectName,
         operations: Vec<AlterTableOperation>,
     ) -> Result<LogicalPlan> {
-        let table_name = object_name_to_string(&name);
+        let table_name = table_name_of(&name)?;
         if operations.len() != 1 {
             bail!("ALTER TABLE only supports a single operation per statement");
         }
@@ -674,7 +1121,7 @@ impl LogicalPlanner {
                 table_name: new_name,
             } => Ok(LogicalPlan::AlterTableRename {
                 table_name,
-                new_table_name: object_name_to_string(&new_name),
+                new_table_name: table_name_of(&new_name)?,
             }),
             AlterTableOperation::RenameColumn {
                 old_column_name,
@@ -697,6 +1144,21 @@ impl LogicalPlanner {
                     column_name: column_name.value,
                 })
             }
+            AlterTableOperation::AlterColumn {
+                column_name,
+                op: AlterColumnOperation::SetDataType { data_type, using },
+            } => Ok(LogicalPlan::AlterTableAlterColumnType {
+                table_name,
+                column_name: column_name.value,
+                data_type: self.convert_data_type(&data_type)?,
+                using: using.map(|expr| self.plan_expr(expr)).transpose()?,
+            }),
+            AlterTableOperation::AddConstraint(TableConstraint::Check { name, expr }) => {
+                Ok(LogicalPlan::AlterTableAddCheck {
+                    table_name,
+                    check: self.plan_check(name, *expr)?,
+                })
+            }
             _ => bail!("Unsupported ALTER TABLE operation: {:?}", operation),
         }
     }
@@ -711,7 +1173,8 @@ impl LogicalPlanner {
                     Ok(DefaultValue::Integer(s.parse()?))
                 }
             }
-            SqlExpr::Value(Value::SingleQuotedString(s)) => Ok(DefaultValue::Text(s)),
+            SqlExpr::Value(Value::SingleQuotedString(s))
+            | SqlExpr::Value(Value::EscapedStringLiteral(s)) => Ok(DefaultValue::Text(s)),
             SqlExpr::Value(Value::Boolean(b)) => Ok(DefaultValue::Boolean(b)),
             SqlExpr::Function(f)
                 if object_name_to_string(&f.name).to_uppercase() == "CURRENT_TIMESTAMP" =>
@@ -739,52 +1202,6 @@ impl LogicalPlanner {
         })
     }
 
-    fn extract_aggregates(&mut self, items: &[SelectItem]) -> Result<Vec<AggregateExpr>> {
-        let mut aggregates = Vec::new();
-        for item in items {
-            match item {
-                SelectItem::UnnamedExpr(expr) | SelectItem::ExprWithAlias { expr, .. } => {
-                    if let Some(agg) = self.extract_aggregate_from_expr(expr)? {
-                        aggregates.push(agg);
-                    }
-                }
-                _ => {}
-            }
-        }
-        Ok(aggregates)
-    }
-
-    fn extract_aggregate_from_expr(&mut self, expr: &SqlExpr) -> Result<Option<AggregateExpr>> {
-        match expr {
-            SqlExpr::Function(func) => {
-                let name = object_name_to_string(&func.name).to_uppercase();
-                let agg_func = match name.as_str() {
-                    "COUNT" => AggregateFunction::Count,
-                    "SUM" => AggregateFunction::Sum,
-                    "AVG" => AggregateFunction::Avg,
-                    "MIN" => AggregateFunction::Min,
-                    "MAX" => AggregateFunction::Max,
-                    _ => return Ok(None),
-                };
-                let args = match &func.args {
-                    FunctionArguments::List(args) => args
-                        .args
-                        .iter()
-                        .map(|arg| self.plan_function_arg(arg))
-                        .collect::<Result<Vec<_>>>()?,
-                    FunctionArguments::None => Vec::new(),
-                    FunctionArguments::Subquery(_) => bail!("Subquery in aggregate not supported"),
-                };
-                Ok(Some(AggregateExpr {
-                    func: agg_func,
-                    args,
-                    alias: None,
-                }))
-            }
-            _ => Ok(None),
-        }
-    }
-
     fn validate_filter_predicate(&self, predicate: &LocalExpr, _input: &LogicalPlan) -> Result<()> {
         self.validate_expr_well_formed(predicate)
     }
@@ -803,7 +1220,10 @@ impl LogicalPlanner {
             LocalExpr::Column { .. }
             | LocalExpr::Literal(_)
             | LocalExpr::Wildcard
-            | LocalExpr::QualifiedWildcard { .. } => Ok(()),
+            | LocalExpr::QualifiedWildcard { .. }
+            | LocalExpr::Default
+            | LocalExpr::Parameter(_)
+            | LocalExpr::ScalarSubquery(_) => Ok(()),
             LocalExpr::BinaryOp { left, right, .. } => {
                 self.validate_expr_well_formed(left)?;
                 self.validate_expr_well_formed(right)
@@ -831,6 +1251,7 @@ impl LogicalPlanner {
                 }
                 Ok(())
             }
+            LocalExpr::InSubquery { expr, .. } => self.validate_expr_well_formed(expr),
         }
     }
 }
@@ -841,6 +1262,23 @@ impl Default for LogicalPlanner {
     }
 }
 
+/// The database every table lives in. A table may be named with it, as
+/// `main.events`, which is the same table as `events`.
+pub const MAIN_DATABASE: &str = "main";
+
+/// The table `name` refers to, without the database it may be qualified
+/// with; a database other than [`MAIN_DATABASE`] is an error.
+fn table_name_of(name: &ObjectName) -> Result<String> {
+    match name.0.as_slice() {
+        [table] => Ok(table.value.clone()),
+        [database, table] if database.value.eq_ignore_ascii_case(MAIN_DATABASE) => {
+            Ok(table.value.clone())
+        }
+        [database, _] => bail!("unknown database {}", database.value),
+        _ => bail!("invalid table name {}", name),
+    }
+}
+
 fn object_name_to_string(name: &ObjectName) -> String {
     name.0
         .iter()
@@ -848,3 +1286,448 @@ fn object_name_to_string(name: &ObjectName) -> String {
         .collect::<Vec<_>>()
         .join(".")
 }
+
+/// `COPY table [(columns)] TO|FROM 'file'` with the CSV options of either
+/// `WITH (FORMAT csv, NULL '\N', HEADER, ...)` or the older
+/// `NULL AS '\N' CSV HEADER` form.
+fn plan_copy(
+    source: CopySource,
+    to: bool,
+    target: CopyTarget,
+    options: Vec<CopyOption>,
+    legacy_options: Vec<CopyLegacyOption>,
+) -> Result<LogicalPlan> {
+    let (table_name, columns) = match source {
+        CopySource::Table {
+            table_name,
+            columns,
+        } => (table_name, columns),
+        CopySource::Query(_) => bail!("COPY of a query is not supported"),
+    };
+    let path = match target {
+        CopyTarget::File { filename } => filename,
+        other => bail!("COPY {} is not supported, only files", other),
+    };
+    let mut csv = false;
+    let mut copy_options = CopyOptions::default();
+    for option in options {
+        match option {
+            CopyOption::Format(format) if format.value.eq_ignore_ascii_case("csv") => csv = true,
+            CopyOption::Delimiter(delimiter) => copy_options.delimiter = delimiter,
+            CopyOption::Null(null) => copy_options.null = null,
+            CopyOption::Header(header) => copy_options.header = header,
+            CopyOption::Quote(quote) => copy_options.quote = quote,
+            other => bail!("COPY option {} is not supported", other),
+        }
+    }
+    for option in legacy_options {
+        match option {
+            CopyLegacyOption::Csv(csv_options) => {
+                csv = true;
+                for option in csv_options {
+                    match option {
+                        CopyLegacyCsvOption::Header => copy_options.header = true,
+                        CopyLegacyCsvOption::Quote(quote) => copy_options.quote = quote,
+                        other => bail!("COPY option {} is not supported", other),
+                    }
+                }
+            }
+            CopyLegacyOption::Delimiter(delimiter) => copy_options.delimiter = delimiter,
+            CopyLegacyOption::Null(null) => copy_options.null = null,
+            other => bail!("COPY option {} is not supported", other),
+        }
+    }
+    if !csv {
+        bail!("COPY only supports the CSV format");
+    }
+    if copy_options.delimiter == copy_options.quote {
+        bail!("COPY delimiter and quote must be different");
+    }
+    if copy_options
+        .null
+        .contains([copy_options.delimiter, copy_options.quote, '\r', '\n'])
+    {
+        bail!("COPY NULL string must not contain the delimiter, quote or a newline");
+    }
+    Ok(LogicalPlan::Copy {
+        table_name: table_name_of(&table_name)?,
+        columns: (!columns.is_empty())
+            .then(|| columns.into_iter().map(|column| column.value).collect()),
+        direction: if to {
+            CopyDirection::To
+        } else {
+            CopyDirection::From
+        },
+        path,
+        options: copy_options,
+    })
+}
+
+/// `SET work_mem = '64MB'`. Sizes take a `kB`, `MB` or `GB` unit; a bare
+/// number is in kB, as in PostgreSQL.
+fn plan_set(name: &ObjectName, value: &[SqlExpr]) -> Result<LogicalPlan> {
+    let name = object_name_to_string(name);
+    if !name.eq_ignore_ascii_case("work_mem") {
+        bail!("SET {} is not supported", name);
+    }
+    let text = match value {
+        [SqlExpr::Value(Value::SingleQuotedString(text) | Value::Number(text, _))] => text,
+        _ => bail!("work_mem must be a size such as '64MB'"),
+    };
+    let digits = text.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+    let unit = match text[digits.len()..].to_ascii_lowercase().as_str() {
+        "" | "kb" => 1024,
+        "mb" => 1024 * 1024,
+        "gb" => 1024 * 1024 * 1024,
+        other => bail!("unknown work_mem unit {}, expected kB, MB or GB", other),
+    };
+    let bytes = digits
+        .trim()
+        .parse::<usize>()
+        .ok()
+        .and_then(|size| size.checked_mul(unit))
+        .filter(|bytes| *bytes > 0)
+        .with_context(|| format!("invalid work_mem {}", text))?;
+    Ok(LogicalPlan::Set {
+        setting: Setting::WorkMem(bytes),
+    })
+}
+
+/// `CREATE SEQUENCE name [INCREMENT [BY] n] [START [WITH] n]`, in that
+/// order. Without START an ascending sequence begins at 1 and a descending
+/// one at -1.
+fn plan_create_sequence(
+    name: ObjectName,
+    options: Vec<SequenceOptions>,
+    if_not_exists: bool,
+) -> Result<LogicalPlan> {
+    let mut start = None;
+    let mut increment = 1;
+    for option in options {
+        match option {
+            SequenceOptions::StartWith(value, _) => start = Some(sequence_option_value(&value)?),
+            SequenceOptions::IncrementBy(value, _) => increment = sequence_option_value(&value)?,
+            other => bail!("sequence option {} is not supported", other),
+        }
+    }
+    if increment == 0 {
+        bail!("INCREMENT must not be zero");
+    }
+    Ok(LogicalPlan::CreateSequence {
+        sequence_name: object_name_to_string(&name),
+        start: start.unwrap_or(increment.signum()),
+        increment,
+        if_not_exists,
+    })
+}
+
+fn sequence_option_value(expr: &SqlExpr) -> Result<i64> {
+    let (digits, negative) = match expr {
+        SqlExpr::Value(Value::Number(digits, _)) => (digits, false),
+        SqlExpr::UnaryOp {
+            op: SqlUnaryOp::Minus,
+            expr,
+        } => match expr.as_ref() {
+            SqlExpr::Value(Value::Number(digits, _)) => (digits, true),
+            _ => bail!("sequence options must be integer literals, found {}", expr),
+        },
+        _ => bail!("sequence options must be integer literals, found {}", expr),
+    };
+    let value = if negative {
+        format!("-{}", digits)
+    } else {
+        digits.clone()
+    };
+    value
+        .parse::<i64>()
+        .with_context(|| format!("invalid sequence option value {}", value))
+}
+
+/// Splits a WHERE clause into the conditions joined by top-level ANDs.
+fn split_sql_conjunction(expr: SqlExpr) -> Vec<SqlExpr> {
+    match expr {
+        SqlExpr::BinaryOp {
+            left,
+            op: SqlBinaryOp::And,
+            right,
+        } => {
+            let mut conjuncts = split_sql_conjunction(*left);
+            conjuncts.extend(split_sql_conjunction(*right));
+            conjuncts
+        }
+        SqlExpr::Nested(inner)
+            if matches!(
+                inner.as_ref(),
+                SqlExpr::BinaryOp {
+                    op: SqlBinaryOp::And,
+                    ..
+                }
+            ) =>
+        {
+            split_sql_conjunction(*inner)
+        }
+        other => vec![other],
+    }
+}
+
+/// The column an `IN` subquery returns, as the semi-join condition refers
+/// to it.
+fn subquery_column(plan: &LogicalPlan) -> Result<LocalExpr> {
+    match plan {
+        LogicalPlan::Sort { input, .. }
+        | LogicalPlan::DistinctOn { input, .. }
+        | LogicalPlan::Limit { input, .. } => subquery_column(input),
+        LogicalPlan::Project {
+            expressions,
+            aliases,
+            ..
+        } => match (expressions.as_slice(), aliases.as_deref()) {
+            ([LocalExpr::Wildcard | LocalExpr::QualifiedWildcard { .. }], _) => {
+                bail!("subquery in IN must select one column, not *")
+            }
+            ([column @ LocalExpr::Column { .. }], _) => Ok(column.clone()),
+            ([_], Some([alias])) => Ok(LocalExpr::Column {
+                table: None,
+                name: alias.clone(),
+            }),
+            ([expr], _) => Ok(LocalExpr::Column {
+                table: None,
+                name: expr.to_string(),
+            }),
+            _ => bail!("subquery in IN must return exactly one column"),
+        },
+        LogicalPlan::Values { columns, .. } if columns.len() == 1 => Ok(LocalExpr::Column {
+            table: None,
+            name: columns[0].clone(),
+        }),
+        _ => bail!("subquery in IN must return exactly one column"),
+    }
+}
+
+/// A `$n` placeholder, which the parser also makes of each `?`.
+fn plan_parameter(placeholder: &str) -> Result<LocalExpr> {
+    placeholder
+        .strip_prefix('$')
+        .and_then(|number| number.parse::<usize>().ok())
+        .and_then(|number| number.checked_sub(1))
+        .map(LocalExpr::Parameter)
+        .with_context(|| format!("unsupported placeholder {}", placeholder))
+}
+
+/// Resolves `ORDER BY 2` or `GROUP BY 1` to the select-list expression at
+/// that 1-based position. Other expressions are returned unchanged.
+fn resolve_ordinal(expr: LocalExpr, select_list: &[LocalExpr], clause: &str) -> Result<LocalExpr> {
+    let LocalExpr::Literal(LiteralValue::Integer(position)) = expr else {
+        return Ok(expr);
+    };
+    let index = usize::try_from(position)
+        .ok()
+        .and_then(|position| position.checked_sub(1))
+        .filter(|index| *index < select_list.len())
+        .with_context(|| format!("{} position {} is not in select list", clause, position))?;
+    if select_list[..=index].iter().any(|item| {
+        matches!(
+            item,
+            LocalExpr::Wildcard | LocalExpr::QualifiedWildcard { .. }
+        )
+    }) {
+        bail!(
+            "{} position {} cannot be resolved after a * in the select list",
+            clause,
+            position
+        );
+    }
+    Ok(select_list[index].clone())
+}
+
+/// Maps the privileges and objects of a GRANT or REVOKE to table names and
+/// privileges. `ALL` stands for SELECT, INSERT, UPDATE and DELETE.
+fn plan_privileges(
+    privileges: Privileges,
+    objects: GrantObjects,
+) -> Result<(Vec<String>, Vec<PrivilegeSpec>)> {
+    let table_names = match objects {
+        GrantObjects::Tables(names) => names.iter().map(table_name_of).collect::<Result<_>>()?,
+        other => bail!("privileges can only be granted on tables, not {}", other),
+    };
+    let column_names = |columns: Option<Vec<Ident>>| {
+        columns.map(|columns| columns.into_iter().map(|c| c.value).collect())
+    };
+    let privileges = match privileges {
+        Privileges::All { .. } => Privilege::ALL
+            .into_iter()
+            .map(|privilege| PrivilegeSpec {
+                privilege,
+                columns: None,
+            })
+            .collect(),
+        Privileges::Actions(actions) => actions
+            .into_iter()
+            .map(|action| {
+                let (privilege, columns) = match action {
+                    Action::Select { columns } => (Privilege::Select, column_names(columns)),
+                    Action::Insert { columns } => (Privilege::Insert, column_names(columns)),
+                    Action::Update { columns } => (Privilege::Update, column_names(columns)),
+                    Action::Delete => (Privilege::Delete, None),
+                    other => bail!("privilege {} is not supported", other),
+                };
+                Ok(PrivilegeSpec { privilege, columns })
+            })
+            .collect::<Result<_>>()?,
+    };
+    Ok((table_names, privileges))
+}
+
+/// Reads back the `TABLESAMPLE(method, percent[, seed])` hint that
+/// [`SqlParser`](crate::SqlParser) rewrites a `TABLESAMPLE` clause into.
+fn plan_table_sample(hints: &[SqlExpr]) -> Result<Option<TableSample>> {
+    let Some(args) = hints.iter().find_map(|hint| match hint {
+        SqlExpr::Function(func) if object_name_to_string(&func.name) == "TABLESAMPLE" => {
+            match &func.args {
+                FunctionArguments::List(list) => Some(&list.args),
+                _ => None,
+            }
+        }
+        _ => None,
+    }) else {
+        return Ok(None);
+    };
+    let arg = |index: usize| match args.get(index) {
+        Some(FunctionArg::Unnamed(FunctionArgExpr::Expr(expr))) => Some(expr),
+        _ => None,
+    };
+    let method = match arg(0) {
+        Some(SqlExpr::Identifier(ident)) if ident.value == "BERNOULLI" => SampleMethod::Bernoulli,
+        Some(SqlExpr::Identifier(ident)) if ident.value == "SYSTEM" => SampleMethod::System,
+        _ => bail!("TABLESAMPLE supports only BERNOULLI and SYSTEM"),
+    };
+    let percent = match arg(1) {
+        Some(SqlExpr::Value(Value::Number(percent, _))) => percent
+            .parse::<f64>()
+            .with_context(|| format!("invalid TABLESAMPLE percentage {}", percent))?,
+        _ => bail!("TABLESAMPLE requires a numeric percentage"),
+    };
+    if !(0.0..=100.0).contains(&percent) {
+        bail!(
+            "TABLESAMPLE percentage must be between 0 and 100, found {}",
+            percent
+        );
+    }
+    let seed = match arg(2) {
+        Some(SqlExpr::Value(Value::Number(seed, _))) => Some(
+            seed.parse::<u64>()
+                .with_context(|| format!("invalid REPEATABLE seed {}", seed))?,
+        ),
+        _ => None,
+    };
+    Ok(Some(TableSample {
+        method,
+        percent,
+        seed,
+    }))
+}
+
+/// Rewrites a `LIKE ... ESCAPE` pattern into the executor's form, where a
+/// backslash escapes the next character. An empty escape string turns
+/// escaping off.
+fn rewrite_like_escape(pattern: SqlExpr, escape: &str) -> Result<String> {
+    let pattern = match pattern {
+        SqlExpr::Value(Value::SingleQuotedString(pattern)) => pattern,
+        other => bail!("ESCAPE requires a string literal pattern, found {}", other),
+    };
+    let mut escape_chars = escape.chars();
+    let escape = match (escape_chars.next(), escape_chars.next()) {
+        (escape, None) => escape,
+        _ => bail!("ESCAPE must be a single character, found '{}'", escape),
+    };
+    let mut rewritten = String::with_capacity(pattern.len());
+    let mut chars = pattern.chars();
+    while let Some(ch) = chars.next() {
+        if Some(ch) == escape {
+            let Some(escaped) = chars.next() else {
+                bail!("LIKE pattern must not end with the escape character");
+            };
+            rewritten.push('\\');
+            rewritten.push(escaped);
+        } else if ch == '\\' {
+            rewritten.push_str("\\\\");
+        } else {
+            rewritten.push(ch);
+        }
+    }
+    Ok(rewritten)
+}
+
+/// Makes `sort_exprs` group the rows by the `DISTINCT ON` expressions `on`:
+/// the leading sort keys must be among `on`, as in PostgreSQL, and any `on`
+/// expression they leave out is appended as an ascending key.
+fn sort_by_distinct_on(sort_exprs: &mut Vec<SortExpr>, on: &[LocalExpr]) -> Result<()> {
+    let mut missing = on.to_vec();
+    for sort_expr in sort_exprs.iter() {
+        if missing.is_empty() {
+            break;
+        }
+        if !on.contains(&sort_expr.expr) {
+            bail!("SELECT DISTINCT ON expressions must match initial ORDER BY expressions");
+        }
+        missing.retain(|expr| *expr != sort_expr.expr);
+    }
+    sort_exprs.extend(missing.into_iter().map(|expr| SortExpr {
+        expr,
+        asc: true,
+        nulls_first: false,
+    }));
+    Ok(())
+}
+
+/// Appends each distinct aggregate call in `expr`, including calls nested
+/// inside larger expressions such as `SUM(a) * 2`.
+fn collect_aggregates(expr: &LocalExpr, aggregates: &mut Vec<AggregateExpr>) {
+    match expr {
+        LocalExpr::Function { name, args } => match AggregateFunction::from_name(name) {
+            Some(func) => {
+                let aggregate = AggregateExpr {
+                    func,
+                    args: args.clone(),
+                    alias: None,
+                };
+                if !aggregates.contains(&aggregate) {
+                    aggregates.push(aggregate);
+                }
+            }
+            None => {
+                for arg in args {
+                    collect_aggregates(arg, aggregates);
+                }
+            }
+        },
+        LocalExpr::BinaryOp { left, right, .. } => {
+            collect_aggregates(left, aggregates);
+            collect_aggregates(right, aggregates);
+        }
+        LocalExpr::UnaryOp { expr, .. }
+        | LocalExpr::Cast { expr, .. }
+        | LocalExpr::IsNull { expr, .. }
+        | LocalExpr::InSubquery { expr, .. } => collect_aggregates(expr, aggregates),
+        LocalExpr::Between {
+            expr, low, high, ..
+        } => {
+            collect_aggregates(expr, aggregates);
+            collect_aggregates(low, aggregates);
+            collect_aggregates(high, aggregates);
+        }
+        LocalExpr::In { expr, list, .. } => {
+            collect_aggregates(expr, aggregates);
+            for item in list {
+                collect_aggregates(item, aggregates);
+            }
+        }
+        LocalExpr::Column { .. }
+        | LocalExpr::Literal(_)
+        | LocalExpr::Wildcard
+        | LocalExpr::QualifiedWildcard { .. }
+        | LocalExpr::Default
+        | LocalExpr::Parameter(_)
+        | LocalExpr::ScalarSubquery(_) => {}
+    }
+}
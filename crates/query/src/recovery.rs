@@ -20,10 +20,13 @@ struct TransactionState {
     last_lsn: Option<wal::Lsn>,
 }
 
+/// Records read from the log, the transactions still open at its end, and
+/// for each page the LSN of its first update and of its latest free.
 type AnalysisResult = (
     Vec<LogRecord>,
     HashMap<wal::TxnId, TransactionState>,
     HashMap<wal::PageId, wal::Lsn>,
+    HashMap<wal::PageId, wal::Lsn>,
 );
 
 pub struct RecoveryManager {
@@ -40,8 +43,8 @@ impl RecoveryManager {
     }
 
     pub fn recover(&self, buffer_pool: &BufferPoolManager) -> ExecutionResult<()> {
-        let (records, txn_table, dirty_pages) = self.analyze()?;
-        self.redo(buffer_pool, &records, &dirty_pages)?;
+        let (records, txn_table, dirty_pages, freed_at) = self.analyze()?;
+        self.redo(buffer_pool, &records, &dirty_pages, &freed_at)?;
         self.undo(buffer_pool, &records, &txn_table)?;
         buffer_pool
             .flush_all_pages_with_mode(storage::FlushMode::Force)
@@ -66,7 +69,7 @@ impl RecoveryManager {
 
         let records = self.load_records()?;
         let record_map = build_record_map(&records);
-        self.undo_single(buffer_pool, &record_map, txn_id, last_lsn, txn)?;
+        self.undo_single(buffer_pool, &record_map, txn_id, last_lsn, None, txn)?;
         let end_lsn = self
             .log_manager
             .append(LogRecord::end(0, txn_id, txn.lock().last_lsn))
@@ -76,11 +79,104 @@ impl RecoveryManager {
         Ok(())
     }
 
+    /// Undoes the updates `txn` logged after `savepoint_lsn`, leaving the
+    /// transaction running. Compensation records keep a later full rollback
+    /// from undoing the same changes twice.
+    pub fn rollback_to_lsn(
+        &self,
+        buffer_pool: &BufferPoolManager,
+        txn: &TransactionHandle,
+        savepoint_lsn: wal::Lsn,
+    ) -> ExecutionResult<()> {
+        let txn_guard = txn.lock();
+        let last_lsn = txn_guard.last_lsn;
+        let txn_id = txn_guard.txn_id;
+        drop(txn_guard);
+
+        if let Some(lsn) = last_lsn {
+            self.log_manager.flush(lsn).map_err(map_wal_error)?;
+        }
+
+        let records = self.load_records()?;
+        let record_map = build_record_map(&records);
+        self.undo_single(
+            buffer_pool,
+            &record_map,
+            txn_id,
+            last_lsn,
+            Some(savepoint_lsn),
+            txn,
+        )
+    }
+
+    /// Returns the catalog snapshot logged by the most recent schema change,
+    /// if the log holds one. It is never older than the catalog file, which
+    /// is only rewritten after the snapshot is durable.
+    pub fn latest_catalog(&self) -> ExecutionResult<Option<Vec<u8>>> {
+        Ok(self
+            .load_records()?
+            .into_iter()
+            .rev()
+            .find_map(|record| match record.payload {
+                LogPayload::Catalog { snapshot } => Some(snapshot),
+                _ => None,
+            }))
+    }
+
+    /// Starts from the latest checkpoint when there is one: its tables
+    /// describe the log before it began, so only later records are scanned.
     fn analyze(&self) -> ExecutionResult<AnalysisResult> {
         let records = self.load_records()?;
         let mut txn_table: HashMap<wal::TxnId, TransactionState> = HashMap::new();
         let mut dirty_pages: HashMap<wal::PageId, wal::Lsn> = HashMap::new();
+        let mut freed_at: HashMap<wal::PageId, wal::Lsn> = HashMap::new();
+        let mut analyze_from = 0;
+        let checkpoint_lsn = LogReader::open(&self.log_path)
+            .map_err(map_wal_error)?
+            .last_checkpoint();
+        let checkpoint = records
+            .iter()
+            .find(|record| Some(record.lsn) == checkpoint_lsn);
+        if let Some(LogPayload::Checkpoint {
+            begin_lsn,
+            active_txns,
+            dirty_pages: checkpoint_pages,
+        }) = checkpoint.map(|record| &record.payload)
+        {
+            analyze_from = *begin_lsn;
+            for txn in active_txns {
+                let status = if txn.committed {
+                    TransactionStatus::Committed
+                } else {
+                    TransactionStatus::Running
+                };
+                txn_table.insert(
+                    txn.txn_id,
+                    TransactionState {
+                        status,
+                        last_lsn: Some(txn.last_lsn),
+                    },
+                );
+            }
+            for page in checkpoint_pages {
+                dirty_pages.insert(page.page_id, page.rec_lsn);
+            }
+        }
         for record in &records {
+            if let LogPayload::FreePages { page_ids } = &record.payload {
+                for page_id in page_ids {
+                    freed_at.insert(*page_id, record.lsn);
+                }
+            }
+            if record.lsn < analyze_from {
+                continue;
+            }
+            if matches!(
+                record.record_type,
+                LogRecordType::Catalog | LogRecordType::FreePages | LogRecordType::Checkpoint
+            ) {
+                continue;
+            }
             let entry = txn_table.entry(record.txn_id).or_insert(TransactionState {
                 status: TransactionStatus::Running,
                 last_lsn: None,
@@ -99,7 +195,10 @@ impl RecoveryManager {
                 LogRecordType::End => {
                     txn_table.remove(&record.txn_id);
                 }
-                LogRecordType::Checkpoint => {}
+                LogRecordType::Checkpoint
+                | LogRecordType::Catalog
+                | LogRecordType::FreePages
+                | LogRecordType::RowChange => {}
                 LogRecordType::PageUpdate | LogRecordType::Compensation => {
                     if let Some(page_id) = record_page_id(record) {
                         dirty_pages.entry(page_id).or_insert(record.lsn);
@@ -107,7 +206,7 @@ impl RecoveryManager {
                 }
             }
         }
-        Ok((records, txn_table, dirty_pages))
+        Ok((records, txn_table, dirty_pages, freed_at))
     }
 
     fn redo(
@@ -115,6 +214,7 @@ impl RecoveryManager {
         buffer_pool: &BufferPoolManager,
         records: &[LogRecord],
         dirty_pages: &HashMap<wal::PageId, wal::Lsn>,
+        freed_at: &HashMap<wal::PageId, wal::Lsn>,
     ) -> ExecutionResult<()> {
         let Some(start_lsn) = dirty_pages.values().min().copied() else {
             return Ok(());
@@ -136,6 +236,11 @@ impl RecoveryManager {
                     if *page_id == 0 {
                         continue;
                     }
+                    // the page was freed after this change and may since
+                    // belong to another table or hold blob bytes
+                    if freed_at.get(page_id).is_some_and(|&lsn| record.lsn < lsn) {
+                        continue;
+                    }
                     self.apply_redo(buffer_pool, *page_id, record.lsn, *offset, after)?;
                 }
                 _ => {}
@@ -158,12 +263,15 @@ impl RecoveryManager {
             let txn_handle = Arc::new(parking_lot::Mutex::new(Transaction {
                 txn_id: *txn_id,
                 last_lsn: state.last_lsn,
+                read_only: false,
+                snapshot: None,
             }));
             self.undo_single(
                 buffer_pool,
                 &record_map,
                 *txn_id,
                 state.last_lsn,
+                None,
                 &txn_handle,
             )?;
             let end_lsn = self
@@ -182,10 +290,14 @@ impl RecoveryManager {
         records: &HashMap<wal::Lsn, LogRecord>,
         txn_id: wal::TxnId,
         start_lsn: Option<wal::Lsn>,
+        stop_lsn: Option<wal::Lsn>,
         txn_handle: &TransactionHandle,
     ) -> ExecutionResult<()> {
         let mut current_lsn = start_lsn;
         while let Some(lsn) = current_lsn {
+            if stop_lsn.is_some_and(|stop| lsn <= stop) {
+                break;
+            }
             let record = match records.get(&lsn) {
                 Some(rec) => rec.clone(),
                 None => {
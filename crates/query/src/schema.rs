@@ -1,3 +1,4 @@
+use crate::expr::Expr;
 use serde::{Deserialize, Serialize};
 
 /// Represents a SQL data type
@@ -10,6 +11,9 @@ pub enum DataType {
     Boolean,
     Timestamp,
     Blob,
+    /// Type of a computed result column that cannot be inferred without
+    /// evaluating it. Never the type of a stored column.
+    Unknown,
 }
 
 impl DataType {
@@ -20,7 +24,7 @@ impl DataType {
             DataType::Real => Some(8),
             DataType::Boolean => Some(1),
             DataType::Timestamp => Some(8),
-            DataType::Text | DataType::Blob => None,
+            DataType::Text | DataType::Blob | DataType::Unknown => None,
         }
     }
     pub fn is_nullable_by_default(&self) -> bool {
@@ -37,6 +41,136 @@ pub struct ColumnDef {
     pub unique: bool,
     pub default_value: Option<DefaultValue>,
     pub auto_increment: bool,
+    /// Set for TEXT columns declared as `DATE` or `DATETIME`.
+    #[serde(default)]
+    pub text_format: Option<TextFormat>,
+    /// Set for TEXT columns declared `COLLATE NOCASE`.
+    #[serde(default)]
+    pub collation: Option<Collation>,
+}
+
+/// Layout enforced on a TEXT column declared as `DATE` or `DATETIME`.
+/// Values are stored zero-padded (`2025-06-01 09:05:00`), so comparing them
+/// as text orders them chronologically.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TextFormat {
+    Date,
+    DateTime,
+}
+
+impl TextFormat {
+    pub fn name(&self) -> &'static str {
+        match self {
+            TextFormat::Date => "DATE",
+            TextFormat::DateTime => "DATETIME",
+        }
+    }
+
+    /// Returns `text` in canonical form, or `None` when it is not a valid
+    /// date (or date and time). Accepts unpadded fields and a `T` separator;
+    /// a DATETIME given only a date gets midnight.
+    pub fn normalize(&self, text: &str) -> Option<String> {
+        let text = text.trim();
+        let (date, time) = match text.split_once([' ', 'T']) {
+            Some((date, time)) => (date, Some(time.trim())),
+            None => (text, None),
+        };
+        let [year, month, day] = parse_fields::<3>(date, '-')?;
+        if !(1..=9999).contains(&year)
+            || !(1..=12).contains(&month)
+            || day < 1
+            || day > days_in_month(year, month)
+        {
+            return None;
+        }
+        let date = format!("{:04}-{:02}-{:02}", year, month, day);
+        match (self, time) {
+            (TextFormat::Date, None) => Some(date),
+            (TextFormat::Date, Some(_)) => None,
+            (TextFormat::DateTime, None) => Some(format!("{} 00:00:00", date)),
+            (TextFormat::DateTime, Some(time)) => {
+                let (hour, minute, second) = match parse_fields::<3>(time, ':') {
+                    Some([hour, minute, second]) => (hour, minute, second),
+                    None => {
+                        let [hour, minute] = parse_fields::<2>(time, ':')?;
+                        (hour, minute, 0)
+                    }
+                };
+                if hour > 23 || minute > 59 || second > 59 {
+                    return None;
+                }
+                Some(format!("{} {:02}:{:02}:{:02}", date, hour, minute, second))
+            }
+        }
+    }
+}
+
+/// How the index keys of a TEXT column are compared. Without one a column
+/// compares byte by byte (`BINARY`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Collation {
+    /// ASCII letters compare without regard to case, so `'A@x.com'` and
+    /// `'a@x.com'` are the same key of a unique index.
+    NoCase,
+}
+
+impl Collation {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Collation::NoCase => "NOCASE",
+        }
+    }
+
+    /// Returns the form of `text` this collation compares.
+    pub fn fold(&self, text: &str) -> String {
+        match self {
+            Collation::NoCase => text.to_ascii_lowercase(),
+        }
+    }
+}
+
+fn parse_fields<const N: usize>(text: &str, separator: char) -> Option<[u32; N]> {
+    let mut fields = [0; N];
+    let mut parts = text.split(separator);
+    for field in &mut fields {
+        let part = parts.next()?;
+        if part.is_empty() || part.len() > 4 || !part.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        *field = part.parse().ok()?;
+    }
+    parts.next().is_none().then_some(fields)
+}
+
+fn days_in_month(year: u32, month: u32) -> u32 {
+    match month {
+        2 if year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400)) => {
+            29
+        }
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Table-level `UNIQUE (...)` or `PRIMARY KEY (...)` constraint spanning one or
+/// more columns. Enforced by a unique index created alongside the table.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UniqueConstraint {
+    pub name: Option<String>,
+    pub columns: Vec<String>,
+    pub is_primary: bool,
+}
+
+/// `CHECK (...)` constraint, declared on a column or on the table. The
+/// predicate is evaluated against the complete row on INSERT and UPDATE and
+/// rejects it only when it is false; `sql` is the original text, kept so the
+/// constraint can be persisted and planned again.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CheckConstraint {
+    pub name: Option<String>,
+    pub sql: String,
+    pub expr: Expr,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -2,7 +2,11 @@ mod common;
 
 use common::temp_buffer_pool;
 use query::execution::ExecutionError;
-use query::{Catalog, ColumnDef, DataType, Field, Schema, TableHeap, TableInfo};
+use query::index::{Index, IndexKey};
+use query::{
+    Catalog, ColumnDef, DataType, Expr, Field, LiteralValue, Schema, TableHeap, TableInfo, Tuple,
+    Value,
+};
 
 fn people_schema(table_name: &str) -> Schema {
     Schema::new(vec![
@@ -86,6 +90,8 @@ fn catalog_add_column_appends() {
                 unique: false,
                 default_value: None,
                 auto_increment: false,
+                text_format: None,
+                collation: None,
             },
         )
         .unwrap();
@@ -128,3 +134,61 @@ fn catalog_drop_primary_key_is_rejected() {
     let result = catalog.drop_column("accounts", "id");
     assert!(matches!(result, Err(ExecutionError::Schema(_))));
 }
+
+#[test]
+fn catalog_failed_column_type_change_leaves_table_unchanged() {
+    let buffer_pool = temp_buffer_pool();
+    let schema = people_schema("people");
+    let heap = TableHeap::create(buffer_pool).expect("create heap");
+    let mut table = TableInfo::new("people", schema, heap);
+    table
+        .create_index("people_email_uk", "email", true, false)
+        .expect("create index");
+    let rows = vec![
+        Tuple::new(vec![
+            Value::Integer(1),
+            Value::String("ada".to_string()),
+            Value::String("ada@example.com".to_string()),
+        ]),
+        Tuple::new(vec![
+            Value::Integer(2),
+            Value::String("bob".to_string()),
+            Value::String("bob@example.com".to_string()),
+        ]),
+    ];
+    for row in &rows {
+        table.insert_tuple(row).expect("insert row");
+    }
+    let mut catalog = Catalog::new();
+    catalog.register_table_info(table);
+
+    // every row converts, but the unique index cannot take the new values,
+    // which only shows once the rows are rewritten
+    let result = catalog.alter_column_type(
+        "people",
+        "email",
+        DataType::Integer,
+        Some(&Expr::Literal(LiteralValue::Integer(0))),
+    );
+    assert!(result.is_err());
+
+    let table = catalog.table("people").expect("people table");
+    assert_eq!(table.schema.fields[2].data_type, DataType::Text);
+    let stored = table
+        .heap
+        .scan_tuples(&table.schema)
+        .expect("scan")
+        .into_iter()
+        .map(|(_, tuple)| tuple)
+        .collect::<Vec<_>>();
+    assert_eq!(stored, rows);
+    let index = &table.indexes[0].index;
+    assert_eq!(index.iter_all().expect("index entries").len(), 2);
+    assert_eq!(
+        index
+            .get(&IndexKey::Text("bob@example.com".to_string()))
+            .expect("lookup")
+            .len(),
+        1
+    );
+}
@@ -56,6 +56,23 @@ fn plan_alter_table_drop_column() {
     ));
 }
 
+#[test]
+fn plan_alter_table_add_check() {
+    let plan = sql_to_logical_plan(
+        "ALTER TABLE events ADD CONSTRAINT events_time_range CHECK (start_time < end_time)",
+    )
+    .unwrap();
+    match plan {
+        LogicalPlan::AlterTableAddCheck { table_name, check } => {
+            assert_eq!(table_name, "events");
+            assert_eq!(check.name.as_deref(), Some("events_time_range"));
+            assert_eq!(check.sql, "start_time < end_time");
+        }
+        other => panic!("unexpected plan: {other:?}"),
+    }
+    assert!(sql_to_logical_plan("ALTER TABLE events ADD UNIQUE (title)").is_err());
+}
+
 #[test]
 fn plan_alter_table_rejects_multiple_operations() {
     let err =
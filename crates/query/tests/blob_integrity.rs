@@ -0,0 +1,56 @@
+use query::{DataType, Field, Schema, TableHeap, Tuple, Value};
+use storage::{BufferPoolManager, DiskManager, PAGE_HEADER_SIZE};
+use tempfile::TempDir;
+
+fn blob_schema() -> Schema {
+    Schema::new(vec![
+        Field {
+            name: "id".to_string(),
+            table: Some("files".to_string()),
+            data_type: DataType::Integer,
+            nullable: false,
+            visible: true,
+        },
+        Field {
+            name: "payload".to_string(),
+            table: Some("files".to_string()),
+            data_type: DataType::Blob,
+            nullable: true,
+            visible: true,
+        },
+    ])
+}
+
+#[test]
+fn corrupted_blob_page_fails_checksum() {
+    let dir = TempDir::new().expect("temp dir");
+    let disk_manager = DiskManager::open(dir.path().join("db")).expect("open db");
+    let buffer_pool = BufferPoolManager::new(disk_manager, 32);
+    let schema = blob_schema();
+    let heap = TableHeap::create(buffer_pool.clone()).expect("heap");
+    let first_page = heap.first_page_id().unwrap().expect("first page");
+
+    let blob: Vec<u8> = (0..8_192u32).map(|i| (i % 251) as u8).collect();
+    let tuple = Tuple::new(vec![Value::Integer(1), Value::Blob(blob.clone())]);
+    heap.insert_tuple(&tuple, &schema).expect("insert");
+    let rows = heap.scan_tuples(&schema).expect("scan");
+    assert_eq!(rows[0].1.values()[1], Value::Blob(blob));
+
+    // The blob chain is allocated right after the heap's first page; flip a
+    // payload byte without touching the page header.
+    let blob_page = first_page + 1;
+    {
+        let mut page = buffer_pool
+            .fetch_page(blob_page)
+            .expect("fetch")
+            .expect("blob page");
+        assert_eq!(page.lsn(), 0, "expected a blob page");
+        let offset = PAGE_HEADER_SIZE + 12 + 100;
+        let byte = page.read_bytes(offset, 1).unwrap()[0];
+        assert!(page.write_bytes(offset, &[byte ^ 0xFF]));
+    }
+    buffer_pool.unpin_page(blob_page, true).expect("unpin");
+
+    let err = heap.scan_tuples(&schema).expect_err("corrupted blob");
+    assert!(err.to_string().contains("checksum"), "{err}");
+}
@@ -138,7 +138,7 @@ fn composite_key_ordering_is_lexicographic() -> ExecutionResult<()> {
         None,
         false,
     )?;
-    let entries = vec![
+    let entries = [
         ("Smith", "Bob"),
         ("Smith", "Alice"),
         ("Adams", "Zoe"),
@@ -60,10 +60,12 @@ pub fn insert_user(heap: &TableHeap, schema: &Schema, id: i64, name: &str, email
 }
 
 pub fn run_sql(catalog: &Catalog, sql: &str) -> Vec<Tuple> {
-    let logical = sql_to_logical_plan(sql).expect("logical plan");
-    let root = PhysicalPlanner::new(catalog)
-        .plan(&logical)
-        .expect("physical plan");
+    let mut logical = sql_to_logical_plan(sql).expect("logical plan");
+    let planner = PhysicalPlanner::new(catalog);
+    planner
+        .run_subqueries(&mut logical)
+        .expect("run subqueries");
+    let root = planner.plan(&logical).expect("physical plan");
     let mut executor = Executor::new(root);
     executor.execute().expect("execute")
 }
@@ -3,7 +3,10 @@ mod common;
 use common::{make_catalog_with_users_table, temp_buffer_pool};
 use query::execution::{ExecutionError, ExecutionResult};
 use query::index::{Index, IndexKey};
-use query::{sql_to_logical_plan, Executor, PhysicalPlanner, Tuple, Value};
+use query::{
+    sql_to_logical_plan, Catalog, ColumnDef, DataType, DefaultValue, Executor, Field,
+    InsertConflict, PhysicalPlanner, Schema, TableHeap, TableInfo, Tuple, Value,
+};
 
 fn user_tuple(id: i64, name: &str, email: &str) -> Tuple {
     Tuple::new(vec![
@@ -93,6 +96,52 @@ fn duplicate_unique_insert_fails_without_heap_leak() -> ExecutionResult<()> {
     Ok(())
 }
 
+#[test]
+fn failed_insert_or_replace_keeps_the_rows_it_would_replace() -> ExecutionResult<()> {
+    let buffer_pool = temp_buffer_pool();
+    let (mut catalog, _) = make_catalog_with_users_table(buffer_pool);
+    let table = catalog.table_mut("users").unwrap();
+    table.create_index("users_pk", "id", true, true)?;
+    table.create_index("users_email_unique", "email", true, false)?;
+    table.create_index("users_name", "name", false, false)?;
+    catalog.insert_tuple("users", &user_tuple(1, "Alice", "alice@example.com"))?;
+    catalog.insert_tuple("users", &user_tuple(2, "Bob", "bob@example.com"))?;
+
+    // conflicts with both rows, but its name is too long for users_name
+    let table = catalog.table("users").unwrap();
+    let replacement = user_tuple(1, &"x".repeat(4096), "bob@example.com");
+    assert!(table
+        .insert_tuple_on_conflict(&replacement, InsertConflict::Replace)
+        .is_err());
+
+    let tuples = table.heap.scan_tuples(&table.schema)?;
+    assert_eq!(tuples.len(), 2);
+    for (name, key) in [
+        ("users_pk", IndexKey::Integer(1)),
+        ("users_pk", IndexKey::Integer(2)),
+        (
+            "users_email_unique",
+            IndexKey::Text("bob@example.com".to_string()),
+        ),
+        ("users_name", IndexKey::Text("Alice".to_string())),
+    ] {
+        let index = table
+            .indexes
+            .iter()
+            .find(|index| index.name == name)
+            .unwrap();
+        assert_eq!(index.index.get(&key)?.len(), 1, "{name} {key:?}");
+    }
+
+    // once every index takes the row, both conflicting rows make way
+    let replacement = user_tuple(1, "Carol", "bob@example.com");
+    table.insert_tuple_on_conflict(&replacement, InsertConflict::Replace)?;
+    let tuples = table.heap.scan_tuples(&table.schema)?;
+    assert_eq!(tuples.len(), 1);
+    assert_eq!(tuples[0].1, replacement);
+    Ok(())
+}
+
 #[test]
 fn update_moves_index_entry() -> ExecutionResult<()> {
     let buffer_pool = temp_buffer_pool();
@@ -159,3 +208,159 @@ fn update_rejects_duplicate_key() -> ExecutionResult<()> {
     assert!(ids.contains(&7));
     Ok(())
 }
+
+#[test]
+fn update_rejects_null_in_not_null_column() -> ExecutionResult<()> {
+    let buffer_pool = temp_buffer_pool();
+    let (catalog, _) = make_catalog_with_users_table(buffer_pool);
+    catalog.insert_tuple("users", &user_tuple(1, "Alice", "alice@example.com"))?;
+    catalog.insert_tuple("users", &user_tuple(2, "Bob", "bob@example.com"))?;
+
+    let logical = sql_to_logical_plan("UPDATE users SET email = NULL WHERE id = 2").unwrap();
+    let root = PhysicalPlanner::new(&catalog).plan(&logical)?;
+    let result = Executor::new(root).execute();
+    match result {
+        Err(ExecutionError::ConstraintViolation {
+            table, constraint, ..
+        }) => {
+            assert_eq!(table, "users");
+            assert_eq!(constraint, "email NOT NULL");
+        }
+        other => {
+            return Err(ExecutionError::Execution(format!(
+                "expected constraint violation, got {:?}",
+                other
+            )));
+        }
+    }
+
+    let table = catalog.table("users").unwrap();
+    let tuples: Vec<Tuple> = table
+        .heap
+        .scan_tuples(&table.schema)?
+        .into_iter()
+        .map(|(_, tuple)| tuple)
+        .collect();
+    assert!(tuples.contains(&user_tuple(2, "Bob", "bob@example.com")));
+    Ok(())
+}
+
+#[test]
+fn update_rejects_type_mismatch_before_writing() -> ExecutionResult<()> {
+    let buffer_pool = temp_buffer_pool();
+    let (catalog, _) = make_catalog_with_users_table(buffer_pool);
+    catalog.insert_tuple("users", &user_tuple(1, "Alice", "alice@example.com"))?;
+
+    let logical = sql_to_logical_plan("UPDATE users SET name = 42").unwrap();
+    let root = PhysicalPlanner::new(&catalog).plan(&logical)?;
+    let result = Executor::new(root).execute();
+    assert!(matches!(result, Err(ExecutionError::Schema(_))));
+
+    let table = catalog.table("users").unwrap();
+    let tuples = table.heap.scan_tuples(&table.schema)?;
+    assert_eq!(tuples[0].1, user_tuple(1, "Alice", "alice@example.com"));
+    Ok(())
+}
+
+/// `posts (id INT NOT NULL, title TEXT NOT NULL, status TEXT NOT NULL
+/// DEFAULT 'draft', created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP)`
+fn make_catalog_with_posts_table() -> Catalog {
+    let column = |name: &str, data_type: DataType, default_value: Option<DefaultValue>| ColumnDef {
+        name: name.to_string(),
+        data_type,
+        nullable: false,
+        primary_key: false,
+        unique: false,
+        default_value,
+        auto_increment: false,
+        text_format: None,
+        collation: None,
+    };
+    let columns = vec![
+        column("id", DataType::Integer, None),
+        column("title", DataType::Text, None),
+        column(
+            "status",
+            DataType::Text,
+            Some(DefaultValue::Text("draft".to_string())),
+        ),
+        column(
+            "created_at",
+            DataType::Timestamp,
+            Some(DefaultValue::CurrentTimestamp),
+        ),
+    ];
+    let schema = Schema::new(
+        columns
+            .iter()
+            .map(|column| Field {
+                name: column.name.clone(),
+                table: Some("posts".to_string()),
+                data_type: column.data_type.clone(),
+                nullable: column.nullable,
+                visible: true,
+            })
+            .collect(),
+    );
+    let heap = TableHeap::create(temp_buffer_pool()).expect("create table heap");
+    let mut catalog = Catalog::new();
+    catalog.register_table_info(TableInfo::with_columns("posts", schema, columns, heap));
+    catalog
+}
+
+#[test]
+fn insert_rejects_null_in_not_null_column_without_default() -> ExecutionResult<()> {
+    let catalog = make_catalog_with_posts_table();
+    let post = Tuple::new(vec![
+        Value::Integer(1),
+        Value::Null,
+        Value::Null,
+        Value::Null,
+    ]);
+    match catalog.insert_tuple("posts", &post) {
+        Err(ExecutionError::ConstraintViolation {
+            table, constraint, ..
+        }) => {
+            assert_eq!(table, "posts");
+            assert_eq!(constraint, "title NOT NULL");
+        }
+        other => {
+            return Err(ExecutionError::Execution(format!(
+                "expected constraint violation, got {:?}",
+                other
+            )));
+        }
+    }
+
+    let table = catalog.table("posts").unwrap();
+    assert!(table.heap.scan_tuples(&table.schema)?.is_empty());
+    Ok(())
+}
+
+#[test]
+fn insert_fills_null_not_null_columns_with_their_defaults() -> ExecutionResult<()> {
+    let catalog = make_catalog_with_posts_table();
+    let post = Tuple::new(vec![
+        Value::Integer(1),
+        Value::String("hello".to_string()),
+        Value::Null,
+        Value::Null,
+    ]);
+    catalog.insert_tuple("posts", &post)?;
+    let published = Tuple::new(vec![
+        Value::Integer(2),
+        Value::String("news".to_string()),
+        Value::String("published".to_string()),
+        Value::Timestamp(7),
+    ]);
+    catalog.insert_tuple("posts", &published)?;
+
+    let table = catalog.table("posts").unwrap();
+    let rows = table.heap.scan_tuples(&table.schema)?;
+    let values = rows[0].1.values();
+    assert_eq!(values[2], Value::String("draft".to_string()));
+    // the timestamp default is taken when the row is inserted
+    assert!(matches!(values[3], Value::Timestamp(seconds) if seconds > 1_600_000_000));
+    assert_eq!(rows[1].1, published);
+    Ok(())
+}
@@ -0,0 +1,76 @@
+mod common;
+
+use common::{make_catalog_with_users_table, temp_buffer_pool};
+use query::execution::ExecutionResult;
+use query::index::{Index, IndexKey};
+use query::{BinaryOperator, Expr, LiteralValue, Rid, Tuple, Value};
+
+fn user_tuple(id: i64) -> Tuple {
+    Tuple::new(vec![
+        Value::Integer(id),
+        Value::String(format!("user{}", id)),
+        Value::String(format!("user{}@example.com", id)),
+    ])
+}
+
+fn id_equals(id: i64) -> Expr {
+    Expr::BinaryOp {
+        left: Box::new(Expr::Column {
+            table: None,
+            name: "id".to_string(),
+        }),
+        op: BinaryOperator::Eq,
+        right: Box::new(Expr::Literal(LiteralValue::Integer(id))),
+    }
+}
+
+#[test]
+fn delete_rids_removes_rows_and_index_entries() -> ExecutionResult<()> {
+    let buffer_pool = temp_buffer_pool();
+    let (mut catalog, _) = make_catalog_with_users_table(buffer_pool.clone());
+    let table = catalog.table_mut("users").unwrap();
+    table.create_index("users_pk", "id", true, true)?;
+    table.create_index("users_email_uk", "email", true, false)?;
+
+    let mut rids: Vec<Rid> = Vec::new();
+    for id in 0..1_500 {
+        rids.push(table.insert_tuple(&user_tuple(id))?);
+    }
+    let doomed: Vec<Rid> = rids[..1_000].to_vec();
+
+    buffer_pool.reset_fetch_count();
+    let mut with_duplicates = doomed.clone();
+    with_duplicates.extend_from_slice(&doomed[..10]);
+    assert_eq!(table.delete_rids(&with_duplicates)?, 1_000);
+    let bulk_fetches = buffer_pool.fetch_count();
+
+    assert_eq!(table.heap.scan_tuples(&table.schema)?.len(), 500);
+    for index in &table.indexes {
+        assert_eq!(index.index.iter_all()?.len(), 500);
+    }
+    let pk = &table.indexes[0].index;
+    assert!(pk.get(&IndexKey::Integer(999))?.is_empty());
+    assert_eq!(pk.get(&IndexKey::Integer(1_000))?, vec![rids[1_000]]);
+    assert_eq!(table.delete_rids(&doomed)?, 0);
+
+    // each filtered delete rescans the heap; a handful already costs more
+    buffer_pool.reset_fetch_count();
+    for id in 1_000..1_010 {
+        assert_eq!(table.delete_tuples(Some(&id_equals(id)))?, 1);
+    }
+    let individual_fetches = buffer_pool.fetch_count();
+    assert!(
+        bulk_fetches * 2 < individual_fetches * 100,
+        "bulk delete fetched {} pages, 1000 single deletes would fetch ~{}",
+        bulk_fetches,
+        individual_fetches * 100
+    );
+    // each row is read and deleted once, plus a descent of each of its two
+    // indexes and the leaf merges the deletes set off
+    assert!(
+        bulk_fetches < 16 * 1_000,
+        "bulk delete fetched {} pages for 1000 rows",
+        bulk_fetches
+    );
+    Ok(())
+}
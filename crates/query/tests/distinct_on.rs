@@ -0,0 +1,61 @@
+mod common;
+
+use common::{make_catalog_with_users_table, run_sql, temp_buffer_pool};
+use query::{sql_to_logical_plan, Value};
+
+/// Updates to events 1 and 2, and two with no event.
+const UPDATES: &str = "(VALUES (1, 1, 10, 'a'), (2, 1, 30, 'b'), (3, 2, 20, 'c'), \
+     (4, 1, 20, 'd'), (5, 2, 5, 'e'), (6, NULL, 1, 'f'), (7, NULL, 2, 'g')) \
+     AS updates(id, event_id, created_at, note)";
+
+/// The notes, the last column of each row, that `SELECT DISTINCT ON
+/// {query}` returns with `updates` replaced by [`UPDATES`].
+fn notes(query: &str) -> Vec<String> {
+    let (catalog, _) = make_catalog_with_users_table(temp_buffer_pool());
+    let sql = format!("SELECT DISTINCT ON {}", query.replace("updates", UPDATES));
+    run_sql(&catalog, &sql)
+        .iter()
+        .map(|row| match row.values().last() {
+            Some(Value::String(note)) => note.clone(),
+            other => panic!("unexpected note {other:?}"),
+        })
+        .collect()
+}
+
+#[test]
+fn distinct_on_keeps_the_first_row_of_each_group_in_order() {
+    assert_eq!(
+        notes("(event_id) event_id, note FROM updates ORDER BY event_id, created_at DESC"),
+        ["b", "c", "g"]
+    );
+    assert_eq!(
+        notes("(event_id) event_id, note FROM updates ORDER BY event_id, created_at"),
+        ["a", "e", "f"]
+    );
+    assert_eq!(
+        notes("(event_id) note FROM updates ORDER BY event_id DESC, created_at LIMIT 2"),
+        ["e", "a"]
+    );
+}
+
+#[test]
+fn distinct_on_columns_need_not_be_selected_or_ordered() {
+    assert_eq!(
+        notes("(event_id) note FROM updates ORDER BY event_id, created_at DESC"),
+        ["b", "c", "g"]
+    );
+    // without ORDER BY any row of each group may be kept
+    assert_eq!(notes("(event_id) event_id, note FROM updates").len(), 3);
+    assert_eq!(
+        notes("(1) event_id, note FROM updates WHERE created_at > 5").len(),
+        2
+    );
+}
+
+#[test]
+fn order_by_must_start_with_the_distinct_on_expressions() {
+    let error =
+        sql_to_logical_plan("SELECT DISTINCT ON (event_id) note FROM updates ORDER BY created_at")
+            .expect_err("ORDER BY does not lead with event_id");
+    assert!(error.to_string().contains("DISTINCT ON"), "{error}");
+}
@@ -0,0 +1,68 @@
+mod common;
+
+use common::{make_catalog_with_users_table, run_sql, temp_buffer_pool};
+use query::Value;
+
+/// Ids of the rows of a small table with a NULL row that `condition` keeps.
+fn matching_ids(condition: &str) -> Vec<i64> {
+    let (catalog, _) = make_catalog_with_users_table(temp_buffer_pool());
+    let sql = format!(
+        "SELECT id FROM (VALUES (1, 1, 'alpha'), (2, NULL, NULL), (3, 3, 'gamma')) \
+         AS t(id, v, s) WHERE {condition}"
+    );
+    run_sql(&catalog, &sql)
+        .iter()
+        .map(|row| match row.values()[0] {
+            Value::Integer(id) => id,
+            ref other => panic!("unexpected id {other:?}"),
+        })
+        .collect()
+}
+
+#[test]
+fn comparison_with_null_keeps_no_rows() {
+    assert_eq!(matching_ids("v = NULL"), Vec::<i64>::new());
+    assert_eq!(matching_ids("v <> NULL"), Vec::<i64>::new());
+    assert_eq!(matching_ids("NOT (v = NULL)"), Vec::<i64>::new());
+    assert_eq!(matching_ids("NULL"), Vec::<i64>::new());
+    assert_eq!(matching_ids("NOT NULL"), Vec::<i64>::new());
+}
+
+#[test]
+fn is_null_stays_two_valued() {
+    assert_eq!(matching_ids("v IS NULL"), [2]);
+    assert_eq!(matching_ids("v IS NOT NULL"), [1, 3]);
+    assert_eq!(matching_ids("NOT (v IS NULL)"), [1, 3]);
+}
+
+#[test]
+fn unknown_comparisons_follow_kleene_logic() {
+    // NOT UNKNOWN is UNKNOWN, so the NULL row is dropped either way
+    assert_eq!(matching_ids("v = 1"), [1]);
+    assert_eq!(matching_ids("NOT (v = 1)"), [3]);
+    // UNKNOWN OR TRUE is TRUE; UNKNOWN AND FALSE is FALSE
+    assert_eq!(matching_ids("v = 1 OR id = 2"), [1, 2]);
+    assert_eq!(matching_ids("NOT (v = 1 AND id = 1)"), [2, 3]);
+    // UNKNOWN OR FALSE and UNKNOWN AND TRUE stay UNKNOWN
+    assert_eq!(matching_ids("v = 3 OR id = 1"), [1, 3]);
+    assert_eq!(matching_ids("NOT (v = 3 OR id = 1)"), Vec::<i64>::new());
+    assert_eq!(matching_ids("NOT (v = 3 AND id = 2)"), [1, 3]);
+}
+
+#[test]
+fn like_on_null_text_is_unknown() {
+    assert_eq!(matching_ids("s LIKE '%a%' OR v = 3"), [1, 3]);
+    assert_eq!(matching_ids("s NOT LIKE '%alpha%'"), [3]);
+    assert_eq!(
+        matching_ids("NOT (s LIKE '%alpha%' OR s LIKE '%gamma%')"),
+        Vec::<i64>::new()
+    );
+    assert_eq!(matching_ids("s LIKE '%alpha%' OR s IS NULL"), [1, 2]);
+}
+
+#[test]
+fn in_and_between_with_null_are_unknown() {
+    assert_eq!(matching_ids("v IN (1, NULL)"), [1]);
+    assert_eq!(matching_ids("v NOT IN (1, NULL)"), Vec::<i64>::new());
+    assert_eq!(matching_ids("v NOT BETWEEN 0 AND 2"), [3]);
+}
@@ -0,0 +1,95 @@
+mod common;
+
+use common::{insert_user, make_catalog_with_users_table, temp_buffer_pool};
+use query::execution::ExecutionError;
+use query::{sql_to_logical_plan, PhysicalPlanner, PlanLimits};
+
+const THREE_WAY_JOIN: &str = "SELECT * FROM users a \
+     JOIN users b ON a.id = b.id \
+     JOIN users c ON b.id = c.id";
+
+#[test]
+fn join_count_over_limit_is_rejected() {
+    let (catalog, _) = make_catalog_with_users_table(temp_buffer_pool());
+    let logical = sql_to_logical_plan(THREE_WAY_JOIN).unwrap();
+
+    let limits = PlanLimits {
+        max_joins: Some(1),
+        ..PlanLimits::default()
+    };
+    let err = PhysicalPlanner::new(&catalog)
+        .with_limits(limits)
+        .plan(&logical)
+        .err()
+        .expect("plan should be rejected");
+    assert!(matches!(err, ExecutionError::PlanTooComplex(_)));
+    assert!(err
+        .to_string()
+        .contains("2 joins, more than the limit of 1"));
+
+    let limits = PlanLimits {
+        max_joins: Some(2),
+        ..PlanLimits::default()
+    };
+    assert!(PhysicalPlanner::new(&catalog)
+        .with_limits(limits)
+        .plan(&logical)
+        .is_ok());
+}
+
+#[test]
+fn joins_in_dml_sources_count_toward_the_limit() {
+    let (catalog, _) = make_catalog_with_users_table(temp_buffer_pool());
+    let limits = PlanLimits {
+        max_joins: Some(1),
+        ..PlanLimits::default()
+    };
+    for sql in [
+        "UPDATE users SET name = b.name FROM users b JOIN users c ON b.id = c.id \
+         WHERE users.id = b.id",
+        "DELETE FROM users USING users b JOIN users c ON b.id = c.id WHERE users.id = b.id",
+        "INSERT INTO users SELECT a.* FROM users a JOIN users b ON a.id = b.id \
+         JOIN users c ON b.id = c.id",
+    ] {
+        let logical = sql_to_logical_plan(sql).unwrap();
+        let err = PhysicalPlanner::new(&catalog)
+            .with_limits(limits)
+            .plan(&logical)
+            .err()
+            .unwrap_or_else(|| panic!("plan should be rejected: {}", sql));
+        assert!(
+            err.to_string()
+                .contains("2 joins, more than the limit of 1"),
+            "{}: {}",
+            sql,
+            err
+        );
+    }
+}
+
+#[test]
+fn estimated_cardinality_over_ceiling_is_rejected() {
+    let (catalog, table) = make_catalog_with_users_table(temp_buffer_pool());
+    for id in 0..10 {
+        insert_user(&table.heap, &table.schema, id, "user", "user@example.com");
+    }
+    let logical = sql_to_logical_plan(THREE_WAY_JOIN).unwrap();
+    let limits = PlanLimits {
+        max_estimated_rows: Some(500),
+        ..PlanLimits::default()
+    };
+
+    // without statistics the estimate is unknown and the plan is allowed
+    assert!(PhysicalPlanner::new(&catalog)
+        .with_limits(limits)
+        .plan(&logical)
+        .is_ok());
+
+    table.analyze().unwrap();
+    let err = PhysicalPlanner::new(&catalog)
+        .with_limits(limits)
+        .plan(&logical)
+        .err()
+        .expect("plan should be rejected");
+    assert!(err.to_string().contains("estimated to produce 1000 rows"));
+}
@@ -1,14 +1,16 @@
 mod common;
 
-use common::{make_catalog_with_users_table, temp_buffer_pool};
-use query::execution::{Filter, IndexScan, PhysicalOperator, Projection, SeqScan};
-use query::{sql_to_logical_plan, PhysicalPlanner};
+use common::{insert_user, make_catalog_with_users_table, run_sql, temp_buffer_pool};
+use query::execution::{
+    explain_physical, Filter, IndexScan, PhysicalOperator, Projection, SeqScan,
+};
+use query::{sql_to_logical_plan, PhysicalPlanner, Tuple};
 
-fn unwrap_projection<'a>(root: &'a Box<dyn PhysicalOperator>) -> &'a dyn PhysicalOperator {
+fn unwrap_projection(root: &dyn PhysicalOperator) -> &dyn PhysicalOperator {
     if let Some(projection) = root.as_any().downcast_ref::<Projection>() {
         projection.child()
     } else {
-        &**root
+        root
     }
 }
 
@@ -24,12 +26,12 @@ fn indexscan_selected_for_equality_predicate() {
 
     let logical = sql_to_logical_plan("SELECT * FROM users WHERE id = 42").unwrap();
     let root = PhysicalPlanner::new(&catalog).plan(&logical).unwrap();
-    let operator = unwrap_projection(&root);
+    let operator = unwrap_projection(root.as_ref());
     assert!(operator.as_any().is::<IndexScan>());
 
     let logical = sql_to_logical_plan("SELECT * FROM users u WHERE u.id = 42").unwrap();
     let root = PhysicalPlanner::new(&catalog).plan(&logical).unwrap();
-    let operator = unwrap_projection(&root);
+    let operator = unwrap_projection(root.as_ref());
     assert!(operator.as_any().is::<IndexScan>());
 }
 
@@ -40,7 +42,7 @@ fn no_index_falls_back_to_filter_seqscan() {
     let logical = sql_to_logical_plan("SELECT * FROM users WHERE id = 7").unwrap();
     let root = PhysicalPlanner::new(&catalog).plan(&logical).unwrap();
 
-    let operator = unwrap_projection(&root);
+    let operator = unwrap_projection(root.as_ref());
     let filter = operator
         .as_any()
         .downcast_ref::<Filter>()
@@ -60,10 +62,239 @@ fn nonsargable_predicate_not_indexscan() {
 
     let logical = sql_to_logical_plan("SELECT * FROM users WHERE id + 1 = 43").unwrap();
     let root = PhysicalPlanner::new(&catalog).plan(&logical).unwrap();
-    let operator = unwrap_projection(&root);
+    let operator = unwrap_projection(root.as_ref());
     let filter = operator
         .as_any()
         .downcast_ref::<Filter>()
         .expect("expected filter");
     assert!(filter.child().as_any().is::<SeqScan>());
 }
+
+#[test]
+fn most_selective_index_chosen_with_residual_filter() {
+    let buffer_pool = temp_buffer_pool();
+    let (mut catalog, _) = make_catalog_with_users_table(buffer_pool);
+    let table = catalog.table_mut("users").unwrap();
+    table
+        .create_index("users_name_idx", "name", false, false)
+        .unwrap();
+    table
+        .create_index("users_email_idx", "email", true, false)
+        .unwrap();
+
+    let logical =
+        sql_to_logical_plan("SELECT * FROM users WHERE name = 'ann' AND email = 'ann@x.io'")
+            .unwrap();
+    let root = PhysicalPlanner::new(&catalog).plan(&logical).unwrap();
+    let operator = unwrap_projection(root.as_ref());
+    let scan = operator
+        .as_any()
+        .downcast_ref::<IndexScan>()
+        .expect("expected index scan");
+    assert_eq!(
+        scan.residual().map(ToString::to_string).as_deref(),
+        Some("(name = 'ann')")
+    );
+    assert_eq!(
+        explain_physical(root.as_ref()),
+        vec![
+            "Projection: [*]",
+            "  IndexScan: users using users_email_idx (= ann@x.io) filter: (name = 'ann')",
+        ]
+    );
+
+    // An equality on the non-unique index still beats an open range.
+    let logical =
+        sql_to_logical_plan("SELECT * FROM users WHERE email > 'm' AND name = 'ann'").unwrap();
+    let root = PhysicalPlanner::new(&catalog).plan(&logical).unwrap();
+    let lines = explain_physical(root.as_ref());
+    assert_eq!(
+        lines[1],
+        "  IndexScan: users using users_name_idx (= ann) filter: (email > 'm')"
+    );
+}
+
+#[test]
+fn index_scan_applies_residual_filter_to_fetched_rows() {
+    let buffer_pool = temp_buffer_pool();
+    let (mut catalog, table) = make_catalog_with_users_table(buffer_pool);
+    for id in 0..20 {
+        let name = if id % 2 == 0 { "Ann" } else { "bob" };
+        insert_user(&table.heap, &table.schema, id, name, &format!("{id}@x.io"));
+    }
+    catalog
+        .table_mut("users")
+        .unwrap()
+        .create_index("users_pk", "id", true, true)
+        .unwrap();
+
+    let ids = |sql: &str| {
+        let mut ids = run_sql(&catalog, sql)
+            .iter()
+            .map(|row| row.values()[0].as_i64().unwrap())
+            .collect::<Vec<_>>();
+        ids.sort_unstable();
+        ids
+    };
+    // the id conjunct is read from the index, LIKE checked on each row
+    let sql = "SELECT * FROM users WHERE id = 6 AND name LIKE 'A%'";
+    let root = PhysicalPlanner::new(&catalog)
+        .plan(&sql_to_logical_plan(sql).unwrap())
+        .unwrap();
+    let scan = unwrap_projection(root.as_ref())
+        .as_any()
+        .downcast_ref::<IndexScan>()
+        .expect("expected index scan without a filter above it");
+    assert_eq!(
+        scan.residual().map(ToString::to_string).as_deref(),
+        Some("(name LIKE 'A%')")
+    );
+    assert_eq!(ids(sql), vec![6]);
+    assert!(ids("SELECT * FROM users WHERE id = 5 AND name LIKE 'A%'").is_empty());
+
+    let sql = "SELECT * FROM users WHERE id >= 13 AND name LIKE 'A%'";
+    assert_eq!(
+        explain_physical(
+            PhysicalPlanner::new(&catalog)
+                .plan(&sql_to_logical_plan(sql).unwrap())
+                .unwrap()
+                .as_ref()
+        ),
+        vec![
+            "Projection: [*]",
+            "  IndexScan: users using users_pk (>= 13) filter: (name LIKE 'A%')",
+        ]
+    );
+    assert_eq!(ids(sql), vec![14, 16, 18]);
+}
+
+#[test]
+fn composite_index_scans_in_lists_with_residual_filter() {
+    let buffer_pool = temp_buffer_pool();
+    let (mut catalog, table) = make_catalog_with_users_table(buffer_pool);
+    for id in 0..60 {
+        let name = format!("user{}", id % 5);
+        insert_user(&table.heap, &table.schema, id, &name, &format!("{id}@x.io"));
+    }
+    let queries = [
+        "SELECT * FROM users WHERE name IN ('user3', 'user1') AND id > 40",
+        "SELECT * FROM users WHERE name IN ('user1', 'user3') AND id > 40 AND email LIKE '4%'",
+        "SELECT * FROM users WHERE name = 'user2' AND id IN (50, 2, 7, 12)",
+    ];
+    let ids = |catalog: &query::Catalog, sql: &str| {
+        let mut ids = run_sql(catalog, sql)
+            .iter()
+            .map(|row| row.values()[0].as_i64().unwrap())
+            .collect::<Vec<_>>();
+        ids.sort_unstable();
+        ids
+    };
+    let filtered = queries.map(|sql| ids(&catalog, sql));
+    catalog
+        .table_mut("users")
+        .unwrap()
+        .create_composite_index("users_name_id", vec!["name", "id"], false, false)
+        .unwrap();
+    let explain = |sql: &str| {
+        explain_physical(
+            PhysicalPlanner::new(&catalog)
+                .plan(&sql_to_logical_plan(sql).unwrap())
+                .unwrap()
+                .as_ref(),
+        )
+    };
+
+    assert_eq!(
+        explain(queries[0]),
+        vec![
+            "Projection: [*]",
+            "  IndexScan: users using users_name_id (> (user1, 40) AND <= (user1) OR > (user3, 40) AND <= (user3))",
+        ]
+    );
+    assert_eq!(
+        explain(queries[1]),
+        vec![
+            "Projection: [*]",
+            "  IndexScan: users using users_name_id (> (user1, 40) AND <= (user1) OR > (user3, 40) AND <= (user3)) filter: (email LIKE '4%')",
+        ]
+    );
+    // an IN list on the second column follows the equality on the first
+    assert_eq!(
+        explain(queries[2]),
+        vec![
+            "Projection: [*]",
+            "  IndexScan: users using users_name_id (= (user2, 2) OR = (user2, 7) OR = (user2, 12) OR = (user2, 50))",
+        ]
+    );
+    for (sql, expected) in queries.iter().zip(&filtered) {
+        assert_eq!(&ids(&catalog, sql), expected, "{sql}");
+    }
+    assert_eq!(filtered[0], vec![41, 43, 46, 48, 51, 53, 56, 58]);
+    assert_eq!(filtered[1], vec![41, 43, 46, 48]);
+    assert_eq!(filtered[2], vec![2, 7, 12]);
+
+    // an IN list on the second column alone is not a prefix of the key
+    let logical = sql_to_logical_plan("SELECT * FROM users WHERE id IN (2, 7)").unwrap();
+    let root = PhysicalPlanner::new(&catalog).plan(&logical).unwrap();
+    let filter = unwrap_projection(root.as_ref())
+        .as_any()
+        .downcast_ref::<Filter>()
+        .expect("expected filter");
+    assert!(filter.child().as_any().is::<SeqScan>());
+}
+
+#[test]
+fn or_of_indexed_comparisons_unions_index_scans() {
+    let buffer_pool = temp_buffer_pool();
+    let (mut catalog, table) = make_catalog_with_users_table(buffer_pool);
+    for id in 0..200 {
+        let name = format!("user{}", id % 7);
+        insert_user(&table.heap, &table.schema, id, &name, &format!("{id}@x.io"));
+    }
+    let queries = [
+        "SELECT * FROM users WHERE id = 1 OR id = 2",
+        "SELECT * FROM users WHERE id = 150 OR id < 10 OR id BETWEEN 5 AND 12",
+        "SELECT * FROM users WHERE (id = 3 OR id >= 190) AND name = 'user3'",
+        "SELECT * FROM users WHERE id = 4 OR id = 4 OR id IS NULL",
+    ];
+    let sorted = |mut rows: Vec<Tuple>| {
+        rows.sort_by_key(|row| format!("{:?}", row.values()));
+        rows
+    };
+    let filtered = queries.map(|sql| sorted(run_sql(&catalog, sql)));
+    catalog
+        .table_mut("users")
+        .unwrap()
+        .create_index("users_pk", "id", true, true)
+        .unwrap();
+
+    for (sql, expected) in queries.iter().zip(&filtered) {
+        let logical = sql_to_logical_plan(sql).unwrap();
+        let root = PhysicalPlanner::new(&catalog).plan(&logical).unwrap();
+        assert!(
+            unwrap_projection(root.as_ref()).as_any().is::<IndexScan>(),
+            "{sql}"
+        );
+        assert_eq!(&sorted(run_sql(&catalog, sql)), expected, "{sql}");
+    }
+    assert_eq!(filtered[1].len(), 14);
+    assert_eq!(
+        explain_physical(
+            PhysicalPlanner::new(&catalog)
+                .plan(&sql_to_logical_plan(queries[0]).unwrap())
+                .unwrap()
+                .as_ref()
+        )[1],
+        "  IndexScan: users using users_pk (= 1 OR = 2)"
+    );
+
+    // one branch the index cannot answer leaves the whole OR to a filter
+    let logical =
+        sql_to_logical_plan("SELECT * FROM users WHERE id = 1 OR name = 'user2'").unwrap();
+    let root = PhysicalPlanner::new(&catalog).plan(&logical).unwrap();
+    let filter = unwrap_projection(root.as_ref())
+        .as_any()
+        .downcast_ref::<Filter>()
+        .expect("expected filter");
+    assert!(filter.child().as_any().is::<SeqScan>());
+}
@@ -0,0 +1,48 @@
+mod common;
+
+use common::{insert_user, make_catalog_with_users_table, run_sql, temp_buffer_pool};
+use query::{sql_to_logical_plan, LogicalPlan, Value};
+
+#[test]
+fn main_database_qualifies_table_names() {
+    let (catalog, table) = make_catalog_with_users_table(temp_buffer_pool());
+    insert_user(&table.heap, &table.schema, 1, "ada", "ada@example.com");
+    insert_user(&table.heap, &table.schema, 2, "bob", "bob@example.com");
+
+    let rows = run_sql(
+        &catalog,
+        "SELECT main.users.id, users.name FROM main.users WHERE main.users.id = 2",
+    );
+    assert_eq!(rows.len(), 1);
+    assert_eq!(
+        rows[0].values(),
+        [Value::Integer(2), Value::String("bob".to_string())]
+    );
+    assert_eq!(run_sql(&catalog, "SELECT * FROM MAIN.users u").len(), 2);
+}
+
+#[test]
+fn qualified_name_plans_as_the_table_itself() {
+    let plan = sql_to_logical_plan("DELETE FROM main.users WHERE id = 1").unwrap();
+    assert!(
+        matches!(&plan, LogicalPlan::Delete { table_name, .. } if table_name == "users"),
+        "{plan:?}"
+    );
+    // two parts in an expression stay table.column
+    assert!(sql_to_logical_plan("SELECT main.id FROM main").is_ok());
+}
+
+#[test]
+fn unknown_database_is_rejected() {
+    for sql in [
+        "SELECT * FROM other.users",
+        "SELECT other.users.id FROM users",
+        "INSERT INTO other.users VALUES (1, 'a', 'b')",
+    ] {
+        let error = sql_to_logical_plan(sql).expect_err(sql);
+        assert!(
+            error.to_string().contains("unknown database other"),
+            "{sql}: {error}"
+        );
+    }
+}
@@ -0,0 +1,172 @@
+mod common;
+
+use std::sync::mpsc::{self, TryRecvError};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use common::users_schema;
+use query::execution::{PhysicalOperator, SeqScan};
+use query::{Rid, Schema, TableHeap, Tuple, Value};
+use storage::{BufferPoolManager, DiskManager};
+use tempfile::TempDir;
+use txn::{DeadlockPolicy, LockManager};
+use wal::{LogManager, TransactionManager};
+
+struct Env {
+    _dir: TempDir,
+    heap: TableHeap,
+    schema: Schema,
+    txn_manager: Arc<TransactionManager>,
+    rids: Vec<Rid>,
+}
+
+/// A heap with row locks on and three rows on its first page.
+fn setup() -> Env {
+    let dir = TempDir::new().expect("temp dir");
+    let disk_manager = DiskManager::open(dir.path().join("db")).expect("open db");
+    let log_manager = Arc::new(LogManager::open(dir.path().join("db.wal")).expect("open wal"));
+    let buffer_pool =
+        BufferPoolManager::new_with_log(disk_manager, 32, Some(Arc::clone(&log_manager)));
+    let lock_manager = Arc::new(LockManager::new(DeadlockPolicy::Timeout(
+        Duration::from_secs(5),
+    )));
+    let txn_manager = Arc::new(TransactionManager::with_lock_manager(
+        log_manager,
+        lock_manager,
+    ));
+    let schema = users_schema();
+    let heap = TableHeap::create(buffer_pool).expect("heap");
+    heap.set_row_locks(true);
+    let rids: Vec<Rid> = (0..3)
+        .map(|id| {
+            heap.insert_tuple(&user(id, "before"), &schema)
+                .expect("insert")
+        })
+        .collect();
+    assert!(rids.iter().all(|rid| rid.page_id == rids[0].page_id));
+    Env {
+        _dir: dir,
+        heap,
+        schema,
+        txn_manager,
+        rids,
+    }
+}
+
+fn user(id: i64, name: &str) -> Tuple {
+    Tuple::new(vec![
+        Value::Integer(id),
+        Value::String(name.to_string()),
+        Value::String(format!("user{}@example.com", id)),
+    ])
+}
+
+/// Updates `rid` in a new transaction on another thread and commits it,
+/// reporting on the returned channel once the update went through.
+fn update_in_background(
+    env: &Env,
+    rid: Rid,
+    tuple: Tuple,
+) -> (mpsc::Receiver<()>, thread::JoinHandle<()>) {
+    let (sender, receiver) = mpsc::channel();
+    let heap = env.heap.clone();
+    let schema = env.schema.clone();
+    let txn_manager = Arc::clone(&env.txn_manager);
+    let handle = thread::spawn(move || {
+        let txn = txn_manager.begin().expect("begin");
+        txn_manager.with_transaction(&txn, || {
+            heap.update_tuple(rid, &tuple, &schema).expect("update");
+        });
+        sender.send(()).expect("report update");
+        txn_manager.commit(&txn).expect("commit");
+    });
+    (receiver, handle)
+}
+
+#[test]
+fn updates_of_different_rows_on_one_page_run_concurrently() {
+    let env = setup();
+    let holder = env.txn_manager.begin().expect("begin");
+    env.txn_manager.with_transaction(&holder, || {
+        env.heap
+            .update_tuple(env.rids[0], &user(0, "first"), &env.schema)
+            .expect("update");
+    });
+
+    let (other, handle) = update_in_background(&env, env.rids[1], user(1, "second"));
+    other
+        .recv_timeout(Duration::from_secs(2))
+        .expect("update of another row waited for the first transaction");
+    env.txn_manager.commit(&holder).expect("commit");
+    handle.join().expect("other transaction");
+
+    let rows = env.heap.scan_tuples(&env.schema).expect("scan");
+    let names: Vec<_> = rows
+        .iter()
+        .map(|(_, tuple)| tuple.values()[1].clone())
+        .collect();
+    assert_eq!(
+        names,
+        ["first", "second", "before"].map(|name| Value::String(name.to_string()))
+    );
+}
+
+#[test]
+fn updates_of_the_same_row_serialize() {
+    let env = setup();
+    let holder = env.txn_manager.begin().expect("begin");
+    env.txn_manager.with_transaction(&holder, || {
+        env.heap
+            .update_tuple(env.rids[0], &user(0, "first"), &env.schema)
+            .expect("update");
+    });
+
+    let (other, handle) = update_in_background(&env, env.rids[0], user(0, "later"));
+    thread::sleep(Duration::from_millis(200));
+    assert_eq!(other.try_recv(), Err(TryRecvError::Empty));
+    env.txn_manager.commit(&holder).expect("commit");
+    other
+        .recv_timeout(Duration::from_secs(2))
+        .expect("update went through once the row was released");
+    handle.join().expect("other transaction");
+    let row = env.heap.get_tuple(env.rids[0], &env.schema).expect("get");
+    assert_eq!(row, Some(user(0, "later")));
+}
+
+#[test]
+fn snapshot_scan_decides_visibility_once_per_writer() {
+    let env = setup();
+    let scan_rows = |txn: &wal::TransactionHandle| {
+        env.txn_manager.with_transaction(txn, || {
+            let mut scan = SeqScan::new(env.heap.clone(), env.schema.clone());
+            scan.open().expect("open");
+            let mut rows = 0;
+            while scan.next().expect("next").is_some() {
+                rows += 1;
+            }
+            (rows, scan.visibility_decisions())
+        })
+    };
+
+    // many rows from one transaction the snapshot does not see
+    let writer = env.txn_manager.begin().expect("begin");
+    let reader = env.txn_manager.begin().expect("begin");
+    env.txn_manager.take_snapshot(&reader);
+    env.txn_manager.with_transaction(&writer, || {
+        for id in 10..210 {
+            env.heap
+                .insert_tuple(&user(id, "new"), &env.schema)
+                .expect("insert");
+        }
+    });
+    assert_eq!(scan_rows(&reader), (3, 1));
+
+    // and once it has committed, many rows it sees
+    env.txn_manager.commit(&writer).expect("commit");
+    let later = env.txn_manager.begin().expect("begin");
+    env.txn_manager.take_snapshot(&later);
+    assert_eq!(scan_rows(&later), (203, 1));
+    env.txn_manager.commit(&reader).expect("commit");
+    env.txn_manager.commit(&later).expect("commit");
+}
@@ -0,0 +1,121 @@
+mod common;
+
+use common::{insert_user, make_catalog_with_users_table, temp_buffer_pool};
+use query::execution::{ExecutionResult, PhysicalOperator, ScanCursor, SeqScan};
+use query::Value;
+
+fn ids(tuples: impl IntoIterator<Item = query::Tuple>) -> Vec<i64> {
+    tuples
+        .into_iter()
+        .map(|tuple| match tuple.get(0) {
+            Some(Value::Integer(id)) => *id,
+            other => panic!("unexpected id {:?}", other),
+        })
+        .collect()
+}
+
+#[test]
+fn resumed_scan_returns_remaining_rows_exactly_once() -> ExecutionResult<()> {
+    let (_, table) = make_catalog_with_users_table(temp_buffer_pool());
+    for id in 0..600 {
+        insert_user(
+            &table.heap,
+            &table.schema,
+            id,
+            &format!("user{}", id),
+            &format!("user{}@example.com", id),
+        );
+    }
+    let deleted = table.heap.scan_tuples(&table.schema)?[250].0;
+    table.heap.delete_tuple(deleted)?;
+    let expected = ids(table
+        .heap
+        .scan_tuples(&table.schema)?
+        .into_iter()
+        .map(|(_, tuple)| tuple));
+    assert_eq!(expected.len(), 599);
+
+    let mut scan = SeqScan::new(table.heap.clone(), table.schema.clone());
+    scan.open()?;
+    let mut first = Vec::new();
+    for _ in 0..400 {
+        first.push(scan.next()?.expect("row"));
+    }
+    let token = scan.cursor().expect("cursor").to_token();
+    scan.close()?;
+
+    let cursor = ScanCursor::from_token(&token)?;
+    let mut resumed = SeqScan::new(table.heap.clone(), table.schema.clone()).with_cursor(cursor);
+    resumed.open()?;
+    let mut rest = Vec::new();
+    while let Some(tuple) = resumed.next()? {
+        rest.push(tuple);
+    }
+    resumed.close()?;
+
+    let mut combined = ids(first);
+    combined.extend(ids(rest));
+    assert_eq!(combined, expected);
+
+    let from_heap = table.heap.scan_tuples_after(&table.schema, Some(cursor))?;
+    assert_eq!(
+        ids(from_heap.into_iter().map(|(_, tuple)| tuple)),
+        expected[400..]
+    );
+    Ok(())
+}
+
+#[test]
+fn row_grown_past_its_slot_moves_past_the_cursor() -> ExecutionResult<()> {
+    let (_, table) = make_catalog_with_users_table(temp_buffer_pool());
+    let mut rids = Vec::new();
+    for id in 0..600 {
+        rids.push(insert_user(
+            &table.heap,
+            &table.schema,
+            id,
+            &format!("user{}", id),
+            &format!("user{}@example.com", id),
+        ));
+    }
+
+    let mut scan = SeqScan::new(table.heap.clone(), table.schema.clone());
+    scan.open()?;
+    for _ in 0..400 {
+        scan.next()?.expect("row");
+    }
+    let cursor = scan.cursor().expect("cursor");
+    scan.close()?;
+
+    // the longer row no longer fits its slot, so it is stored again at the
+    // end of the table
+    let grown = query::Tuple::new(vec![
+        Value::Integer(10),
+        Value::String("x".repeat(200)),
+        Value::String("user10@example.com".to_string()),
+    ]);
+    let moved = table.heap.update_tuple(rids[10], &grown, &table.schema)?;
+    assert_ne!(moved, rids[10]);
+
+    let rest = ids(table
+        .heap
+        .scan_tuples_after(&table.schema, Some(cursor))?
+        .into_iter()
+        .map(|(_, tuple)| tuple));
+    let mut expected: Vec<i64> = (400..600).collect();
+    expected.push(10);
+    assert_eq!(rest, expected);
+    Ok(())
+}
+
+#[test]
+fn cursor_from_another_table_is_rejected() {
+    let (_, table) = make_catalog_with_users_table(temp_buffer_pool());
+    insert_user(&table.heap, &table.schema, 1, "ann", "ann@example.com");
+
+    assert!(ScanCursor::from_token("not-a-cursor").is_err());
+    let foreign = ScanCursor::from_token("00000000000f424000000000").unwrap();
+    let mut scan = SeqScan::new(table.heap.clone(), table.schema.clone()).with_cursor(foreign);
+    let err = scan.open().expect_err("foreign page");
+    assert!(err.to_string().contains("not part of this table"), "{err}");
+}
@@ -0,0 +1,66 @@
+mod common;
+
+use common::{insert_user, make_catalog_with_users_table, temp_buffer_pool};
+use query::execution::{explain_physical, ExecutionError};
+use query::{sql_to_logical_plan, ColumnDef, DataType, PhysicalPlanner};
+
+#[test]
+fn plan_fails_after_table_is_dropped() {
+    let (mut catalog, table) = make_catalog_with_users_table(temp_buffer_pool());
+    insert_user(&table.heap, &table.schema, 1, "Ada", "ada@example.com");
+
+    let select = sql_to_logical_plan("SELECT * FROM users").unwrap();
+    let update = sql_to_logical_plan("UPDATE users SET name = 'Grace' WHERE id = 1").unwrap();
+    let count = sql_to_logical_plan("SELECT COUNT(*) FROM users").unwrap();
+    let mut select = PhysicalPlanner::new(&catalog).plan(&select).unwrap();
+    let mut update = PhysicalPlanner::new(&catalog).plan(&update).unwrap();
+    let mut count = PhysicalPlanner::new(&catalog).plan(&count).unwrap();
+    assert!(explain_physical(count.as_ref())
+        .iter()
+        .any(|line| line.trim_start().starts_with("CountRows")));
+
+    catalog.drop_table("users").unwrap();
+
+    let err = select.open().expect_err("stale scan");
+    assert!(matches!(err, ExecutionError::SchemaChanged(ref name) if name == "users"));
+    assert!(err.to_string().contains("schema changed"));
+    assert!(matches!(
+        update.open(),
+        Err(ExecutionError::SchemaChanged(_))
+    ));
+    assert!(matches!(
+        count.open(),
+        Err(ExecutionError::SchemaChanged(ref name)) if name == "users"
+    ));
+}
+
+#[test]
+fn plan_fails_after_alter_but_replanning_succeeds() {
+    let (mut catalog, _) = make_catalog_with_users_table(temp_buffer_pool());
+    let logical = sql_to_logical_plan("SELECT * FROM users").unwrap();
+    let mut stale = PhysicalPlanner::new(&catalog).plan(&logical).unwrap();
+
+    catalog
+        .add_column(
+            "users",
+            ColumnDef {
+                name: "age".to_string(),
+                data_type: DataType::Integer,
+                nullable: true,
+                primary_key: false,
+                unique: false,
+                default_value: None,
+                auto_increment: false,
+                text_format: None,
+                collation: None,
+            },
+        )
+        .unwrap();
+
+    assert!(matches!(
+        stale.open(),
+        Err(ExecutionError::SchemaChanged(_))
+    ));
+    let mut fresh = PhysicalPlanner::new(&catalog).plan(&logical).unwrap();
+    fresh.open().unwrap();
+}
@@ -0,0 +1,42 @@
+use query::{sql_to_logical_plan, Expr, LiteralValue, LogicalPlan};
+
+fn filter_literal(sql: &str) -> LiteralValue {
+    let plan = sql_to_logical_plan(sql).expect("logical plan");
+    let LogicalPlan::Project { input, .. } = plan else {
+        panic!("expected projection");
+    };
+    let LogicalPlan::Filter { predicate, .. } = *input else {
+        panic!("expected filter");
+    };
+    let Expr::BinaryOp { right, .. } = predicate else {
+        panic!("expected comparison");
+    };
+    let Expr::Literal(literal) = *right else {
+        panic!("expected literal");
+    };
+    literal
+}
+
+#[test]
+fn doubled_single_quote_is_unescaped() {
+    assert_eq!(
+        filter_literal("SELECT * FROM users WHERE name = 'it''s a test'"),
+        LiteralValue::String("it's a test".to_string())
+    );
+}
+
+#[test]
+fn escape_string_constant_decodes_backslash_escapes() {
+    assert_eq!(
+        filter_literal(r"SELECT * FROM users WHERE name = E'a\tb\n\\c\'d'"),
+        LiteralValue::String("a\tb\n\\c'd".to_string())
+    );
+}
+
+#[test]
+fn plain_string_keeps_backslashes() {
+    assert_eq!(
+        filter_literal(r"SELECT * FROM users WHERE name = 'C:\temp'"),
+        LiteralValue::String(r"C:\temp".to_string())
+    );
+}
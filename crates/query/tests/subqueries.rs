@@ -0,0 +1,155 @@
+mod common;
+
+use common::{run_sql, temp_buffer_pool};
+use query::{
+    sql_to_logical_plan, Catalog, DataType, Field, PhysicalPlanner, Schema, TableHeap, TableInfo,
+    Tuple, Value,
+};
+
+fn integer_field(table: &str, name: &str, nullable: bool) -> Field {
+    Field {
+        name: name.to_string(),
+        table: Some(table.to_string()),
+        data_type: DataType::Integer,
+        nullable,
+        visible: true,
+    }
+}
+
+fn add_table(
+    catalog: &mut Catalog,
+    heap: TableHeap,
+    name: &str,
+    columns: &[&str],
+    rows: &[[Value; 2]],
+) {
+    let schema = Schema::new(
+        columns
+            .iter()
+            .enumerate()
+            .map(|(i, column)| integer_field(name, column, i > 0))
+            .collect(),
+    );
+    for row in rows {
+        heap.insert_tuple(&Tuple::new(row.to_vec()), &schema)
+            .expect("insert row");
+    }
+    catalog.register_table_info(TableInfo::new(name, schema, heap));
+}
+
+/// Event 1 has ticket types 1 and 2, event 2 has type 3; ticket 13 has no
+/// type.
+fn ticket_catalog() -> Catalog {
+    let buffer_pool = temp_buffer_pool();
+    let mut catalog = Catalog::new();
+    add_table(
+        &mut catalog,
+        TableHeap::create(buffer_pool.clone()).expect("create heap"),
+        "ticket_types",
+        &["id", "event_id"],
+        &[
+            [Value::Integer(1), Value::Integer(1)],
+            [Value::Integer(2), Value::Integer(1)],
+            [Value::Integer(3), Value::Integer(2)],
+        ],
+    );
+    add_table(
+        &mut catalog,
+        TableHeap::create(buffer_pool).expect("create heap"),
+        "tickets",
+        &["id", "ticket_type_id"],
+        &[
+            [Value::Integer(10), Value::Integer(1)],
+            [Value::Integer(11), Value::Integer(2)],
+            [Value::Integer(12), Value::Integer(3)],
+            [Value::Integer(13), Value::Null],
+        ],
+    );
+    catalog
+}
+
+fn ticket_ids(condition: &str) -> Vec<i64> {
+    let sql = format!("SELECT id FROM tickets WHERE {condition} ORDER BY id");
+    run_sql(&ticket_catalog(), &sql)
+        .iter()
+        .map(|row| match row.values()[0] {
+            Value::Integer(id) => id,
+            ref other => panic!("unexpected id {other:?}"),
+        })
+        .collect()
+}
+
+#[test]
+fn in_subquery_filters_by_the_subquery_values() {
+    let event_types = "(SELECT id FROM ticket_types WHERE event_id = 1)";
+    assert_eq!(
+        ticket_ids(&format!("ticket_type_id IN {event_types}")),
+        [10, 11]
+    );
+    // outside a top-level AND the subquery runs once into an IN list
+    assert_eq!(
+        ticket_ids(&format!("ticket_type_id IN {event_types} OR id = 13")),
+        [10, 11, 13]
+    );
+    assert_eq!(
+        ticket_ids(&format!("ticket_type_id NOT IN {event_types}")),
+        [12]
+    );
+    assert_eq!(
+        ticket_ids("ticket_type_id IN (SELECT id FROM ticket_types WHERE event_id = 9)"),
+        Vec::<i64>::new()
+    );
+}
+
+#[test]
+fn not_in_a_subquery_returning_null_keeps_no_rows() {
+    assert_eq!(
+        ticket_ids("id NOT IN (SELECT ticket_type_id FROM tickets) AND id > 11"),
+        Vec::<i64>::new()
+    );
+}
+
+#[test]
+fn scalar_subquery_is_its_single_value() {
+    assert_eq!(
+        ticket_ids("ticket_type_id = (SELECT MAX(id) FROM ticket_types)"),
+        [12]
+    );
+    // no rows is NULL, which matches nothing
+    assert_eq!(
+        ticket_ids("ticket_type_id = (SELECT id FROM ticket_types WHERE event_id = 9)"),
+        Vec::<i64>::new()
+    );
+    let rows = run_sql(
+        &ticket_catalog(),
+        "SELECT id, (SELECT COUNT(*) FROM ticket_types) FROM tickets WHERE id = 10",
+    );
+    assert_eq!(rows[0].values(), [Value::Integer(10), Value::Integer(3)]);
+}
+
+#[test]
+fn scalar_subquery_with_more_than_one_row_fails() {
+    let catalog = ticket_catalog();
+    let mut plan = sql_to_logical_plan(
+        "SELECT id FROM tickets WHERE ticket_type_id = \
+         (SELECT id FROM ticket_types WHERE event_id = 1)",
+    )
+    .unwrap();
+    let error = PhysicalPlanner::new(&catalog)
+        .run_subqueries(&mut plan)
+        .expect_err("two rows");
+    assert!(error.to_string().contains("returned 2 rows"), "{error}");
+}
+
+#[test]
+fn correlated_subquery_is_rejected() {
+    let catalog = ticket_catalog();
+    let mut plan = sql_to_logical_plan(
+        "SELECT id FROM tickets WHERE id = 13 OR ticket_type_id IN \
+         (SELECT id FROM ticket_types WHERE ticket_types.id = tickets.ticket_type_id)",
+    )
+    .unwrap();
+    assert!(PhysicalPlanner::new(&catalog)
+        .run_subqueries(&mut plan)
+        .is_err());
+}
@@ -0,0 +1,31 @@
+use query::TextFormat;
+
+#[test]
+fn datetime_values_are_zero_padded() {
+    let format = TextFormat::DateTime;
+    assert_eq!(
+        format.normalize("2025-6-1 9:05").as_deref(),
+        Some("2025-06-01 09:05:00")
+    );
+    assert_eq!(
+        format.normalize("2025-06-01T18:30:15").as_deref(),
+        Some("2025-06-01 18:30:15")
+    );
+    assert_eq!(
+        format.normalize("2024-2-29").as_deref(),
+        Some("2024-02-29 00:00:00")
+    );
+    assert_eq!(format.normalize("2025-02-29 10:00"), None);
+    assert_eq!(format.normalize("2025-13-01 10:00"), None);
+    assert_eq!(format.normalize("2025-06-01 24:00"), None);
+    assert_eq!(format.normalize("June 1st"), None);
+    assert_eq!(format.normalize("2025-06-01 10:00:00Z"), None);
+}
+
+#[test]
+fn date_values_reject_a_time_part() {
+    let format = TextFormat::Date;
+    assert_eq!(format.normalize("2025-6-1").as_deref(), Some("2025-06-01"));
+    assert_eq!(format.normalize("2025-06-01 10:00"), None);
+    assert_eq!(format.normalize("2025-6"), None);
+}
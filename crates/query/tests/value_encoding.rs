@@ -0,0 +1,70 @@
+use query::execution::{decode_result, decode_value, encode_result, encode_value};
+use query::{DataType, Field, Schema, Tuple, Value};
+
+fn field(name: &str, data_type: DataType) -> Field {
+    Field {
+        name: name.to_string(),
+        table: None,
+        data_type,
+        nullable: true,
+        visible: true,
+    }
+}
+
+#[test]
+fn every_value_kind_round_trips() {
+    let values = vec![
+        Value::Null,
+        Value::Integer(-42),
+        Value::Integer(i64::MAX),
+        Value::Float(3.5),
+        Value::String("héllo".to_string()),
+        Value::String(String::new()),
+        Value::Boolean(true),
+        Value::Boolean(false),
+        Value::Timestamp(1_700_000_000),
+        Value::Blob(vec![0, 1, 255]),
+    ];
+    let mut bytes = Vec::new();
+    for value in &values {
+        encode_value(value, &mut bytes);
+    }
+
+    let mut input = bytes.as_slice();
+    let decoded: Vec<Value> = values
+        .iter()
+        .map(|_| decode_value(&mut input).unwrap())
+        .collect();
+    assert_eq!(decoded, values);
+    assert!(input.is_empty());
+}
+
+#[test]
+fn result_round_trips_with_schema() {
+    let schema = Schema::new(vec![
+        field("id", DataType::Integer),
+        field("name", DataType::Text),
+        field("score", DataType::Real),
+    ]);
+    let rows = vec![
+        Tuple::new(vec![
+            Value::Integer(1),
+            Value::String("a".to_string()),
+            Value::Float(1.5),
+        ]),
+        Tuple::new(vec![Value::Integer(2), Value::Null, Value::Null]),
+    ];
+
+    let bytes = encode_result(&schema, &rows);
+    let (decoded_schema, decoded_rows) = decode_result(&bytes).unwrap();
+    assert_eq!(decoded_schema, schema);
+    assert_eq!(decoded_rows, rows);
+}
+
+#[test]
+fn truncated_or_foreign_input_is_rejected() {
+    let schema = Schema::new(vec![field("id", DataType::Integer)]);
+    let bytes = encode_result(&schema, &[Tuple::new(vec![Value::Integer(9)])]);
+    assert!(decode_result(&bytes[..bytes.len() - 1]).is_err());
+    assert!(decode_result(b"{\"rows\":[]}").is_err());
+}
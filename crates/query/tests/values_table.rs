@@ -0,0 +1,60 @@
+mod common;
+
+use common::{insert_user, make_catalog_with_users_table, run_sql, temp_buffer_pool};
+use query::execution::PhysicalPlanner;
+use query::{sql_to_logical_plan, Value};
+
+fn values(rows: Vec<query::Tuple>) -> Vec<Vec<Value>> {
+    rows.iter().map(|row| row.values().to_vec()).collect()
+}
+
+#[test]
+fn values_list_with_column_aliases() {
+    let (catalog, _) = make_catalog_with_users_table(temp_buffer_pool());
+    let sql = "SELECT * FROM (VALUES (1, 'a'), (2, 'b')) AS t(id, name)";
+
+    let (_, schema) = PhysicalPlanner::new(&catalog)
+        .plan_with_schema(&sql_to_logical_plan(sql).unwrap())
+        .unwrap();
+    let names: Vec<_> = schema.fields.iter().map(|f| f.name.as_str()).collect();
+    assert_eq!(names, ["id", "name"]);
+    assert_eq!(
+        values(run_sql(&catalog, sql)),
+        vec![
+            vec![Value::Integer(1), Value::String("a".to_string())],
+            vec![Value::Integer(2), Value::String("b".to_string())],
+        ]
+    );
+    assert_eq!(
+        values(run_sql(&catalog, "VALUES (1, 2), (3, 4)")),
+        vec![
+            vec![Value::Integer(1), Value::Integer(2)],
+            vec![Value::Integer(3), Value::Integer(4)],
+        ]
+    );
+}
+
+#[test]
+fn values_list_joins_a_table() {
+    let (catalog, table) = make_catalog_with_users_table(temp_buffer_pool());
+    insert_user(&table.heap, &table.schema, 1, "ann", "ann@example.com");
+    insert_user(&table.heap, &table.schema, 2, "bob", "bob@example.com");
+
+    let rows = run_sql(
+        &catalog,
+        "SELECT users.name, r.role FROM users JOIN (VALUES (2, 'admin')) AS r(id, role) ON users.id = r.id",
+    );
+    assert_eq!(
+        values(rows),
+        vec![vec![
+            Value::String("bob".to_string()),
+            Value::String("admin".to_string()),
+        ]]
+    );
+}
+
+#[test]
+fn ragged_values_list_is_rejected() {
+    assert!(sql_to_logical_plan("VALUES (1, 2), (3)").is_err());
+    assert!(sql_to_logical_plan("SELECT * FROM (VALUES (1)) AS t(a, b)").is_err());
+}
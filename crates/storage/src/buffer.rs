@@ -1,15 +1,20 @@
+use std::backtrace::Backtrace;
 use std::collections::HashMap;
 use std::ops::{Deref, DerefMut};
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::{self, ThreadId};
 
 use thiserror::Error;
 
 use crate::page::Page;
 use crate::replacer::{FrameId, LRUReplacer, Replacer};
-use crate::{DiskManager, PageId};
+use crate::{DiskIoStats, DiskManager, PageId};
 use wal::LogManager;
 
+/// Most pages written back together when a dirty page is evicted.
+const EVICTION_BATCH: usize = 16;
+
 /// Errors returned by the buffer pool manager.
 #[derive(Debug, Error)]
 pub enum BufferPoolError {
@@ -18,10 +23,47 @@ pub enum BufferPoolError {
     LockPoisoned,
     /// The underlying disk manager failed.
     #[error("disk manager error: {0}")]
-    Io(#[from] std::io::Error),
+    Io(std::io::Error),
+    /// A page write found the disk full. The page stays dirty in the pool,
+    /// so it is written once there is space again.
+    #[error("disk full: {0}")]
+    DiskFull(std::io::Error),
     /// WAL flush failed.
     #[error("wal error: {0}")]
     Wal(#[from] wal::WalError),
+    /// A page cannot be freed while it is pinned.
+    #[error("page {0} is pinned and cannot be freed")]
+    PagePinned(PageId),
+    /// A page read from disk failed its checksum.
+    #[error("page {0} is corrupt: checksum mismatch")]
+    Corrupt(PageId),
+    /// A page pinned during the statement was never unpinned.
+    #[error("page {page_id} is still pinned ({leaked} leaked pin(s)); pinned at:\n{backtrace}")]
+    PinLeak {
+        page_id: PageId,
+        leaked: usize,
+        backtrace: String,
+    },
+}
+
+impl From<std::io::Error> for BufferPoolError {
+    fn from(err: std::io::Error) -> Self {
+        if err.kind() == std::io::ErrorKind::StorageFull {
+            BufferPoolError::DiskFull(err)
+        } else {
+            BufferPoolError::Io(err)
+        }
+    }
+}
+
+impl BufferPoolError {
+    /// Whether the page or log write behind this error found the disk full.
+    pub fn is_disk_full(&self) -> bool {
+        matches!(
+            self,
+            BufferPoolError::DiskFull(_) | BufferPoolError::Wal(wal::WalError::DiskFull(_))
+        )
+    }
 }
 
 /// Convenience alias for buffer pool results.
@@ -61,6 +103,48 @@ struct BufferPoolState {
     page_table: HashMap<PageId, FrameId>,
     free_list: Vec<FrameId>,
     log_manager: Option<Arc<LogManager>>,
+    pin_tracker: Option<PinTracker>,
+}
+
+/// Records where each outstanding pin was taken so a leak can be traced back
+/// to the code that forgot to unpin.
+#[derive(Default)]
+struct PinTracker {
+    pins: HashMap<PageId, Vec<PinSite>>,
+}
+
+struct PinSite {
+    thread: ThreadId,
+    backtrace: Backtrace,
+}
+
+impl PinTracker {
+    fn pinned(&mut self, page_id: PageId) {
+        self.pins.entry(page_id).or_default().push(PinSite {
+            thread: thread::current().id(),
+            backtrace: Backtrace::force_capture(),
+        });
+    }
+
+    /// Drops the most recent pin of `page_id`, preferring one taken on the
+    /// calling thread.
+    fn unpinned(&mut self, page_id: PageId) {
+        let Some(sites) = self.pins.get_mut(&page_id) else {
+            return;
+        };
+        let current = thread::current().id();
+        match sites.iter().rposition(|site| site.thread == current) {
+            Some(index) => {
+                sites.remove(index);
+            }
+            None => {
+                sites.pop();
+            }
+        }
+        if sites.is_empty() {
+            self.pins.remove(&page_id);
+        }
+    }
 }
 
 #[derive(Default)]
@@ -104,6 +188,7 @@ impl BufferPoolManager {
             page_table: HashMap::new(),
             free_list,
             log_manager,
+            pin_tracker: None,
         };
         Self {
             inner: Arc::new(Mutex::new(state)),
@@ -125,40 +210,135 @@ impl BufferPoolManager {
         self.metrics.fetch_count.store(0, Ordering::Relaxed);
     }
 
+    /// Returns the number of cached pages modified since they were last
+    /// written to disk.
+    pub fn dirty_page_count(&self) -> BufferPoolResult<usize> {
+        let state = self.lock_state()?;
+        Ok(state
+            .pages
+            .iter()
+            .filter(|page| page.page_id.is_some() && page.is_dirty)
+            .count())
+    }
+
+    /// Starts recording a backtrace for every pin so that
+    /// [`check_pins_released`](Self::check_pins_released) can report leaks.
+    /// Capturing backtraces is slow; this is meant for debug builds and tests.
+    pub fn enable_pin_tracking(&self) -> BufferPoolResult<()> {
+        let mut state = self.lock_state()?;
+        if state.pin_tracker.is_none() {
+            state.pin_tracker = Some(PinTracker::default());
+        }
+        Ok(())
+    }
+
+    /// Fails with [`BufferPoolError::PinLeak`] if a pin taken on the calling
+    /// thread is still outstanding, e.g. at the end of a statement. The
+    /// reported pins are forgotten so each leak is reported once. Always
+    /// succeeds when pin tracking is off.
+    pub fn check_pins_released(&self) -> BufferPoolResult<()> {
+        let mut state = self.lock_state()?;
+        let Some(tracker) = state.pin_tracker.as_mut() else {
+            return Ok(());
+        };
+        let current = thread::current().id();
+        let mut leaks = Vec::new();
+        for (&page_id, sites) in tracker.pins.iter_mut() {
+            let (mine, others): (Vec<_>, Vec<_>) =
+                sites.drain(..).partition(|site| site.thread == current);
+            *sites = others;
+            if !mine.is_empty() {
+                leaks.push((page_id, mine));
+            }
+        }
+        tracker.pins.retain(|_, sites| !sites.is_empty());
+        leaks.sort_by_key(|(page_id, _)| *page_id);
+        match leaks.into_iter().next() {
+            None => Ok(()),
+            Some((page_id, sites)) => Err(BufferPoolError::PinLeak {
+                page_id,
+                leaked: sites.len(),
+                backtrace: sites[0].backtrace.to_string(),
+            }),
+        }
+    }
+
+    /// Writes back a dirty victim together with up to `EVICTION_BATCH - 1`
+    /// other dirty unpinned pages, which are the next likely victims, so a
+    /// run of evictions shares the double-write syncs of one batch.
     fn evict_if_needed(state: &mut BufferPoolState, frame_id: FrameId) -> BufferPoolResult<()> {
-        let (disk_manager, pages, page_table) = (
-            &mut state.disk_manager,
-            &mut state.pages,
-            &mut state.page_table,
-        );
-        if let Some(old_page_id) = pages[frame_id].page_id {
-            if pages[frame_id].is_dirty {
-                if let Some(log_manager) = &state.log_manager {
-                    log_manager.flush(pages[frame_id].lsn())?;
-                }
-                let data = pages[frame_id].data();
-                disk_manager.write_page(old_page_id, data)?;
+        let Some(old_page_id) = state.pages[frame_id].page_id else {
+            return Ok(());
+        };
+        if state.pages[frame_id].is_dirty {
+            let mut frames = vec![frame_id];
+            frames.extend(
+                state
+                    .pages
+                    .iter()
+                    .enumerate()
+                    .filter(|(other, page)| {
+                        *other != frame_id
+                            && page.page_id.is_some()
+                            && page.is_dirty
+                            && page.pin_count == 0
+                    })
+                    .map(|(other, _)| other)
+                    .take(EVICTION_BATCH - 1),
+            );
+            if let Err(error) = Self::write_back(state, &frames, false) {
+                // the page stays cached, and its frame can be chosen again
+                state.replacer.unpin(frame_id);
+                return Err(error);
             }
-            page_table.remove(&old_page_id);
         }
+        state.page_table.remove(&old_page_id);
         Ok(())
     }
 
-    fn flush_page_data(
+    /// Writes the pages cached in `frames` as one batch, after the log up to
+    /// the newest of their LSNs. On failure each page keeps its dirty flag,
+    /// so it is written again later.
+    fn write_back(
         state: &mut BufferPoolState,
-        page_id: PageId,
-        data: &[u8; crate::PAGE_SIZE],
-        lsn: u64,
+        frames: &[FrameId],
         force_disk: bool,
     ) -> BufferPoolResult<()> {
-        if let Some(log_manager) = &state.log_manager {
-            log_manager.flush(lsn)?;
+        let mut was_dirty = Vec::with_capacity(frames.len());
+        for &frame_id in frames {
+            let page = &mut state.pages[frame_id];
+            page.update_checksum();
+            was_dirty.push(page.is_dirty);
+            page.is_dirty = false;
         }
-        state.disk_manager.write_page(page_id, data)?;
-        if force_disk {
-            state.disk_manager.sync_data()?;
+        let written = (|| -> BufferPoolResult<()> {
+            let newest_lsn = frames
+                .iter()
+                .map(|&frame_id| state.pages[frame_id].lsn())
+                .max();
+            if let (Some(log_manager), Some(lsn)) = (&state.log_manager, newest_lsn) {
+                log_manager.flush(lsn)?;
+            }
+            let cached = &state.pages;
+            let pages = frames
+                .iter()
+                .filter_map(|&frame_id| {
+                    let page = &cached[frame_id];
+                    page.page_id.map(|page_id| (page_id, &page.data()[..]))
+                })
+                .collect::<Vec<_>>();
+            state.disk_manager.write_pages(&pages)?;
+            if force_disk {
+                state.disk_manager.sync_data()?;
+            }
+            Ok(())
+        })();
+        if written.is_err() {
+            for (&frame_id, dirty) in frames.iter().zip(was_dirty) {
+                state.pages[frame_id].is_dirty = dirty;
+            }
         }
-        Ok(())
+        written
     }
 
     /// Allocates a new page on disk and pins it in the buffer pool.
@@ -174,18 +354,66 @@ impl BufferPoolManager {
 
         Self::evict_if_needed(&mut state, frame_id)?;
 
-        let page_id = state.disk_manager.allocate_page()?;
+        let page_id = match state.disk_manager.allocate_page() {
+            Ok(page_id) => page_id,
+            Err(error) => {
+                state.pages[frame_id].reset_memory();
+                state.free_list.push(frame_id);
+                return Err(error.into());
+            }
+        };
         {
             let page = &mut state.pages[frame_id];
             page.reset_memory();
+            page.update_checksum();
             page.page_id = Some(page_id);
             page.pin_count = 1;
         }
         state.page_table.insert(page_id, frame_id);
         state.replacer.pin(frame_id);
+        if let Some(tracker) = state.pin_tracker.as_mut() {
+            tracker.pinned(page_id);
+        }
         Ok(Some(page_id))
     }
 
+    /// Returns a page to the disk manager's free list. A cached copy is
+    /// discarded without being written back, so nothing may refer to the
+    /// page any more; recovery must also know to skip its older log records.
+    pub fn free_page(&self, page_id: PageId) -> BufferPoolResult<()> {
+        self.free_pages(&[page_id])
+    }
+
+    /// Returns several pages to the free list as one batch, like
+    /// [`free_page`](Self::free_page). Nothing is freed if any is pinned.
+    pub fn free_pages(&self, page_ids: &[PageId]) -> BufferPoolResult<()> {
+        let mut state = self.lock_state()?;
+        let pinned = page_ids.iter().find(|page_id| {
+            state
+                .page_table
+                .get(page_id)
+                .is_some_and(|&frame_id| state.pages[frame_id].pin_count > 0)
+        });
+        if let Some(&page_id) = pinned {
+            return Err(BufferPoolError::PagePinned(page_id));
+        }
+        for page_id in page_ids {
+            if let Some(frame_id) = state.page_table.remove(page_id) {
+                state.replacer.pin(frame_id);
+                state.pages[frame_id].reset_memory();
+                state.free_list.push(frame_id);
+            }
+        }
+        state.disk_manager.free_pages(page_ids)?;
+        Ok(())
+    }
+
+    /// Returns the number of freed pages waiting to be reused.
+    pub fn free_page_count(&self) -> BufferPoolResult<usize> {
+        let state = self.lock_state()?;
+        Ok(state.disk_manager.free_page_count()?)
+    }
+
     /// Fetches a page into memory and pins it, returning a guarded reference.
     pub fn fetch_page(&self, page_id: PageId) -> BufferPoolResult<Option<PageGuard<'_>>> {
         self.metrics.fetch_count.fetch_add(1, Ordering::Relaxed);
@@ -194,6 +422,9 @@ impl BufferPoolManager {
             let page = &mut state.pages[frame_id];
             page.pin_count += 1;
             state.replacer.pin(frame_id);
+            if let Some(tracker) = state.pin_tracker.as_mut() {
+                tracker.pinned(page_id);
+            }
             return Ok(Some(PageGuard { state, frame_id }));
         }
 
@@ -211,12 +442,21 @@ impl BufferPoolManager {
             let (disk_manager, pages) = (&mut state.disk_manager, &mut state.pages);
             let page = &mut pages[frame_id];
             page.reset_memory();
-            disk_manager.read_page(page_id, page.data_mut())?;
+            let read = disk_manager.read_page(page_id, page.data_mut());
+            if read.is_err() || !page.checksum_is_valid() {
+                page.reset_memory();
+                state.free_list.push(frame_id);
+                read?;
+                return Err(BufferPoolError::Corrupt(page_id));
+            }
             page.page_id = Some(page_id);
             page.pin_count = 1;
         }
         state.page_table.insert(page_id, frame_id);
         state.replacer.pin(frame_id);
+        if let Some(tracker) = state.pin_tracker.as_mut() {
+            tracker.pinned(page_id);
+        }
         Ok(Some(PageGuard { state, frame_id }))
     }
 
@@ -238,6 +478,9 @@ impl BufferPoolManager {
         if page.pin_count == 0 {
             state.replacer.unpin(frame_id);
         }
+        if let Some(tracker) = state.pin_tracker.as_mut() {
+            tracker.unpinned(page_id);
+        }
         Ok(true)
     }
 
@@ -252,56 +495,58 @@ impl BufferPoolManager {
             Some(&frame_id) => frame_id,
             None => return Ok(false),
         };
-        let (data, lsn) = {
-            let page = &mut state.pages[frame_id];
-            let data = *page.data();
-            let lsn = page.lsn();
-            page.is_dirty = false;
-            (data, lsn)
-        };
-        Self::flush_page_data(&mut state, page_id, &data, lsn, mode == FlushMode::Force)?;
+        Self::write_back(&mut state, &[frame_id], mode == FlushMode::Force)?;
         Ok(true)
     }
 
+    /// Flushes the cached pages among `page_ids` to disk as one batch, so
+    /// they share the double-write syncs. Pages not in the pool are skipped.
+    pub fn flush_pages_with_mode(
+        &self,
+        page_ids: &[PageId],
+        mode: FlushMode,
+    ) -> BufferPoolResult<()> {
+        let mut state = self.lock_state()?;
+        let mut frames = page_ids
+            .iter()
+            .filter_map(|page_id| state.page_table.get(page_id).copied())
+            .collect::<Vec<_>>();
+        frames.sort_unstable();
+        frames.dedup();
+        Self::write_back(&mut state, &frames, mode == FlushMode::Force)
+    }
+
     /// Flushes all dirty pages to disk.
     pub fn flush_all_pages(&self) -> BufferPoolResult<()> {
         self.flush_all_pages_with_mode(FlushMode::Lazy)
     }
 
+    /// Writes every dirty page as one batch, after the log up to the newest
+    /// of their LSNs.
     pub fn flush_all_pages_with_mode(&self, mode: FlushMode) -> BufferPoolResult<()> {
         let mut state = self.lock_state()?;
-        let page_ids = state
+        let dirty = state
             .pages
             .iter()
-            .filter_map(|page| page.page_id)
+            .enumerate()
+            .filter(|(_, page)| page.page_id.is_some() && page.is_dirty)
+            .map(|(frame_id, _)| frame_id)
             .collect::<Vec<_>>();
-        for page_id in page_ids {
-            let frame_id = match state.page_table.get(&page_id) {
-                Some(&frame_id) => frame_id,
-                None => continue,
-            };
-            let (data, lsn, is_dirty) = {
-                let page = &mut state.pages[frame_id];
-                let data = *page.data();
-                let lsn = page.lsn();
-                let is_dirty = page.is_dirty;
-                page.is_dirty = false;
-                (data, lsn, is_dirty)
-            };
-            if !is_dirty {
-                continue;
-            }
-            Self::flush_page_data(&mut state, page_id, &data, lsn, mode == FlushMode::Force)?;
-        }
-        Ok(())
+        Self::write_back(&mut state, &dirty, mode == FlushMode::Force)
+    }
+
+    /// Returns the disk manager's write counters.
+    pub fn disk_io_stats(&self) -> BufferPoolResult<DiskIoStats> {
+        Ok(self.lock_state()?.disk_manager.io_stats())
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{PAGE_LSN_SIZE, PAGE_SIZE};
+    use crate::{PAGE_HEADER_SIZE, PAGE_LSN_SIZE, PAGE_SIZE};
     use std::fs;
+    use std::os::unix::fs::FileExt;
     use std::path::PathBuf;
 
     struct TestContext {
@@ -321,6 +566,7 @@ mod tests {
     impl Drop for TestContext {
         fn drop(&mut self) {
             let _ = fs::remove_file(&self.path);
+            let _ = fs::remove_file(self.path.with_extension("db.dwb"));
         }
     }
 
@@ -376,7 +622,7 @@ mod tests {
 
         {
             let mut guard = bpm.fetch_page(page_id).unwrap().unwrap();
-            guard.write_bytes(PAGE_LSN_SIZE, b"hi");
+            guard.write_bytes(PAGE_HEADER_SIZE, b"hi");
         }
         assert!(bpm.unpin_page(page_id, true).unwrap());
 
@@ -390,7 +636,7 @@ mod tests {
         assert!(bpm.unpin_page(second_id, false).unwrap());
 
         let guard = bpm.fetch_page(page_id).unwrap().unwrap();
-        assert_eq!(guard.read_bytes(PAGE_LSN_SIZE, 2).unwrap(), b"hi");
+        assert_eq!(guard.read_bytes(PAGE_HEADER_SIZE, 2).unwrap(), b"hi");
         drop(guard);
         assert!(bpm.unpin_page(page_id, false).unwrap());
     }
@@ -412,12 +658,163 @@ mod tests {
         assert!(bpm.unpin_page(page_id, true).unwrap());
         bpm.flush_page(page_id).unwrap();
 
+        // the flush stamped the checksum between the LSN and the contents
+        let guard = bpm.fetch_page(page_id).unwrap().unwrap();
+        assert_eq!(guard.data()[..PAGE_LSN_SIZE], payload[..PAGE_LSN_SIZE]);
+        assert_eq!(
+            guard.data()[PAGE_HEADER_SIZE..],
+            payload[PAGE_HEADER_SIZE..]
+        );
+        drop(guard);
+        assert!(bpm.unpin_page(page_id, false).unwrap());
+    }
+
+    #[test]
+    fn test_corrupt_page_fails_checksum_on_fetch() {
+        let (ctx, bpm) = setup_bpm("corrupt_page", 1);
+        let page_id = bpm.new_page().unwrap().unwrap();
+        assert!(
+            bpm.fetch_page(page_id)
+                .unwrap()
+                .unwrap()
+                .checksum_is_valid()
+        );
+        assert!(bpm.unpin_page(page_id, false).unwrap());
+        bpm.fetch_page(page_id)
+            .unwrap()
+            .unwrap()
+            .write_bytes(PAGE_HEADER_SIZE, b"intact");
+        assert!(bpm.unpin_page(page_id, true).unwrap());
+        assert!(bpm.unpin_page(page_id, false).unwrap());
+        let other = bpm.new_page().unwrap().unwrap();
+        assert!(bpm.unpin_page(other, true).unwrap());
+
+        // a clean round trip through the disk passes the checksum
+        let guard = bpm.fetch_page(page_id).unwrap().unwrap();
+        assert_eq!(guard.read_bytes(PAGE_HEADER_SIZE, 6).unwrap(), b"intact");
+        drop(guard);
+        assert!(bpm.unpin_page(page_id, false).unwrap());
+        bpm.flush_all_pages().unwrap();
+
+        let file = fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&ctx.path)
+            .unwrap();
+        let offset = page_id * PAGE_SIZE as u64 + PAGE_HEADER_SIZE as u64 + 2;
+        let mut byte = [0u8; 1];
+        file.read_exact_at(&mut byte, offset).unwrap();
+        file.write_all_at(&[byte[0] ^ 0x01], offset).unwrap();
+        drop(file);
+
+        // evict the good cached copy, then read the damaged one back
+        let third = bpm.new_page().unwrap().unwrap();
+        assert!(bpm.unpin_page(third, false).unwrap());
+        let err = match bpm.fetch_page(page_id) {
+            Ok(_) => panic!("page {page_id} was read despite the flipped byte"),
+            Err(err) => err,
+        };
+        assert!(
+            matches!(err, BufferPoolError::Corrupt(corrupt) if corrupt == page_id),
+            "{err}"
+        );
+        // the frame was released, so other pages are still served
+        assert!(bpm.fetch_page(other).unwrap().is_some());
+        assert!(bpm.unpin_page(other, false).unwrap());
+    }
+
+    #[test]
+    fn test_evictions_and_page_flushes_share_double_write_syncs() {
+        let (_ctx, bpm) = setup_bpm("eviction_batch", 4);
+        let mut dirty = Vec::new();
+        for fill in 1..=4u8 {
+            let page_id = bpm.new_page().unwrap().unwrap();
+            bpm.fetch_page(page_id).unwrap().unwrap().data_mut()[0] = fill;
+            assert!(bpm.unpin_page(page_id, true).unwrap());
+            assert!(bpm.unpin_page(page_id, false).unwrap());
+            dirty.push(page_id);
+        }
+
+        // the first eviction writes back every dirty unpinned page at once
+        let before = bpm.disk_io_stats().unwrap();
+        for _ in 0..4 {
+            let page_id = bpm.new_page().unwrap().unwrap();
+            assert!(bpm.unpin_page(page_id, false).unwrap());
+        }
+        let after = bpm.disk_io_stats().unwrap();
+        assert_eq!(after.double_writes - before.double_writes, 4);
+        assert_eq!(after.double_write_syncs - before.double_write_syncs, 1);
+        for (fill, &page_id) in (1..=4u8).zip(&dirty) {
+            assert_eq!(bpm.fetch_page(page_id).unwrap().unwrap().data()[0], fill);
+            assert!(bpm.unpin_page(page_id, false).unwrap());
+        }
+
+        // listed pages flush as one batch as well
+        for &page_id in &dirty {
+            bpm.fetch_page(page_id).unwrap().unwrap().data_mut()[1] = 9;
+            assert!(bpm.unpin_page(page_id, true).unwrap());
+        }
+        let before = bpm.disk_io_stats().unwrap();
+        bpm.flush_pages_with_mode(&dirty, FlushMode::Force).unwrap();
+        let after = bpm.disk_io_stats().unwrap();
+        assert_eq!(after.double_writes - before.double_writes, 4);
+        assert_eq!(after.double_write_syncs - before.double_write_syncs, 1);
+        assert_eq!(bpm.dirty_page_count().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_disk_full_keeps_pages_dirty_until_space_is_freed() {
+        let (ctx, bpm) = setup_bpm("disk_full", 1);
+        let page_id = bpm.new_page().unwrap().unwrap();
+        bpm.fetch_page(page_id).unwrap().unwrap().data_mut()[0] = 7;
+        assert!(bpm.unpin_page(page_id, true).unwrap());
+        assert!(bpm.unpin_page(page_id, false).unwrap());
+
+        wal::fault::fill_disk(&ctx.path);
+        let err = bpm.flush_page(page_id).unwrap_err();
+        assert!(err.is_disk_full(), "{err}");
+        // evicting the only frame cannot write it back either
+        assert!(bpm.new_page().unwrap_err().is_disk_full());
+        assert_eq!(bpm.dirty_page_count().unwrap(), 1);
+
+        wal::fault::free_disk(&ctx.path);
+        let other = bpm.new_page().unwrap().unwrap();
+        assert!(bpm.unpin_page(other, false).unwrap());
         let guard = bpm.fetch_page(page_id).unwrap().unwrap();
-        assert_eq!(guard.data(), &payload);
+        assert_eq!(guard.data()[0], 7);
         drop(guard);
         assert!(bpm.unpin_page(page_id, false).unwrap());
     }
 
+    #[test]
+    fn test_pin_tracking_reports_leaked_pin() {
+        let (_ctx, bpm) = setup_bpm("pin_tracking", 4);
+        bpm.enable_pin_tracking().unwrap();
+
+        let balanced = bpm.new_page().unwrap().unwrap();
+        assert!(bpm.unpin_page(balanced, false).unwrap());
+        drop(bpm.fetch_page(balanced).unwrap().unwrap());
+        assert!(bpm.unpin_page(balanced, false).unwrap());
+        bpm.check_pins_released().unwrap();
+
+        let leaked = bpm.new_page().unwrap().unwrap();
+        assert!(bpm.unpin_page(leaked, false).unwrap());
+        drop(bpm.fetch_page(leaked).unwrap().unwrap());
+        match bpm.check_pins_released() {
+            Err(BufferPoolError::PinLeak {
+                page_id,
+                leaked: count,
+                backtrace,
+            }) => {
+                assert_eq!(page_id, leaked);
+                assert_eq!(count, 1);
+                assert!(!backtrace.is_empty());
+            }
+            other => panic!("expected pin leak, got {:?}", other),
+        }
+        bpm.check_pins_released().unwrap();
+    }
+
     #[test]
     fn test_buffer_exhaustion() {
         let (_ctx, bpm) = setup_bpm("buffer_exhaustion", 5);
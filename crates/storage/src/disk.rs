@@ -1,71 +1,259 @@
 //! DiskManager: Monotonic, crash-safe page allocation/storage for simple RDBMS.
 //!
 //! Invariants:
-//! - Page 0 is a reserved header storing next_page_id as u64 (format: bytes 0..8)
+//! - Page 0 is a reserved header storing next_page_id as u64 (format: bytes 0..8),
+//!   followed by magic (8..16), format version (16..20), page size (20..24)
+//!   and the head of the free page list (24..32, 0 when empty)
+//! - Files written by any other format version are refused on open;
+//!   `stored_format_version` reads the version without opening the file
 //! - All page writes/allocations persist header to disk
-//! - No page id ever reused, no uninitialized garbage pages created
+//! - A page id is reused only after `free_page` returns it; freed pages form
+//!   a list, each holding the next free page id in its first 8 bytes
+//! - No uninitialized garbage pages created
 //! - On open, header is loaded (created if absent)
+//! - Page writes are staged in a double-write file (`<db>.dwb`) unless the
+//!   device is trusted to write a whole page atomically (see `PageWriteMode`)
 
 use std::fs::{File, OpenOptions};
 use std::io::{Error, ErrorKind, Result};
 use std::os::unix::fs::FileExt;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 pub type PageId = u64;
 pub const PAGE_SIZE: usize = 4096;
 pub const HEADER_SIZE: usize = PAGE_SIZE; // header occupies page 0
 
-// const HEADER_MAGIC: u64 = 0xD15CAD0BADC0FFEE; // optional, for future extension
+// double-write record: page_id (8) | checksum (8) | page image
+const DWB_RECORD_SIZE: usize = 16 + PAGE_SIZE;
+
+/// How page writes are protected against torn (partial) writes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PageWriteMode {
+    /// Stage the page images of each write in the double-write file and
+    /// sync it before the in-place writes, so a torn page can be repaired on
+    /// the next open. Pages written together share both syncs.
+    #[default]
+    DoubleWrite,
+    /// Trust the device to write `sector_size` bytes atomically. Pages are
+    /// written once when `sector_size` equals `PAGE_SIZE`; any other size
+    /// falls back to the double-write path.
+    AtomicSector { sector_size: usize },
+}
+
+impl PageWriteMode {
+    /// Returns true when page writes bypass the double-write file.
+    pub fn skips_double_write(&self) -> bool {
+        matches!(self, PageWriteMode::AtomicSector { sector_size } if *sector_size == PAGE_SIZE)
+    }
+}
+
+/// Write counters, used to verify which write path pages take.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DiskIoStats {
+    /// In-place data page writes.
+    pub page_writes: u64,
+    /// Page images staged in the double-write file.
+    pub double_writes: u64,
+    /// Syncs of the double-write file, one per batch of staged pages.
+    pub double_write_syncs: u64,
+    /// Syncs spent returning pages to the free list.
+    pub free_list_syncs: u64,
+}
+
+const HEADER_MAGIC: u64 = 0xD15CAD0BADC0FFEE;
+/// On-disk layout version; bump whenever page or tuple layout changes.
+/// Version 2 added compact tuples; version 3 page checksums, which moved
+/// every page's contents past a larger page header.
+/// Files from earlier versions are refused here; the engine converts them
+/// before opening (see docs/run.md).
+pub const FORMAT_VERSION: u32 = 3;
+/// Version of files written before the format was versioned.
+const UNVERSIONED: u32 = 1;
 
 struct Header {
     next_page_id: u64, // always points to next free (monotonic, persistent)
+    free_list_head: PageId,
 }
 
 impl Header {
     fn to_bytes(&self) -> [u8; HEADER_SIZE] {
         let mut buf = [0u8; HEADER_SIZE];
         buf[..8].copy_from_slice(&self.next_page_id.to_le_bytes());
+        buf[8..16].copy_from_slice(&HEADER_MAGIC.to_le_bytes());
+        buf[16..20].copy_from_slice(&FORMAT_VERSION.to_le_bytes());
+        buf[20..24].copy_from_slice(&(PAGE_SIZE as u32).to_le_bytes());
+        buf[24..32].copy_from_slice(&self.free_list_head.to_le_bytes());
         buf
     }
     fn from_bytes(buf: &[u8]) -> Self {
         let mut b = [0u8; 8];
         b.copy_from_slice(&buf[..8]);
         let next_page_id = u64::from_le_bytes(b);
-        Self { next_page_id }
+        let free_list_head = u64::from_le_bytes(buf[24..32].try_into().unwrap());
+        Self {
+            next_page_id,
+            free_list_head,
+        }
+    }
+    /// Checks the magic and returns the format version and page size.
+    /// Headers written before the format was versioned carry only
+    /// next_page_id and report version 1.
+    fn version(buf: &[u8]) -> Result<(u32, u32)> {
+        let magic = u64::from_le_bytes(buf[8..16].try_into().unwrap());
+        let version = u32::from_le_bytes(buf[16..20].try_into().unwrap());
+        let page_size = u32::from_le_bytes(buf[20..24].try_into().unwrap());
+        if magic == 0 && version == 0 && page_size == 0 {
+            return Ok((UNVERSIONED, PAGE_SIZE as u32));
+        }
+        if magic != HEADER_MAGIC {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "file is not an rdbms database (bad header magic)",
+            ));
+        }
+        Ok((version, page_size))
+    }
+    /// Checks magic, version and page size.
+    fn validate(buf: &[u8]) -> Result<()> {
+        let (version, page_size) = Self::version(buf)?;
+        if version != FORMAT_VERSION {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "database format version {} is not supported (this build reads version {})",
+                    version, FORMAT_VERSION
+                ),
+            ));
+        }
+        if page_size as usize != PAGE_SIZE {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "database page size {} does not match this build's page size {}",
+                    page_size, PAGE_SIZE
+                ),
+            ));
+        }
+        Ok(())
     }
 }
 
 pub struct DiskManager {
     file: File,
+    dwb: File,
+    dwb_staged: bool, // the double-write file holds a batch's records
+    write_mode: PageWriteMode,
+    io_stats: DiskIoStats,
     header: Header, // in-memory header (synced on every allocation)
-    #[allow(dead_code)]
-    path: String, // for possible reopen/use // stored for debugging and future reopen/diagnostics
+    path: PathBuf,
 }
 
 impl DiskManager {
     /// Opens or creates the file; loads or initializes a valid header
     pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::open_with_mode(path, PageWriteMode::default())
+    }
+
+    /// Opens the file with an explicit torn-write protection mode.
+    ///
+    /// A page image left in the double-write file by an interrupted write is
+    /// restored before the header is loaded, whatever `write_mode` is.
+    pub fn open_with_mode<P: AsRef<Path>>(path: P, write_mode: PageWriteMode) -> Result<Self> {
         let file = OpenOptions::new()
             .read(true)
             .write(true)
             .create(true)
             .truncate(false)
             .open(&path)?;
+        let dwb = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(Self::dwb_path(path.as_ref()))?;
         let mut dm = DiskManager {
             file,
-            header: Header { next_page_id: 1 }, // default (if new file)
-            path: path.as_ref().to_str().unwrap().to_string(),
+            dwb,
+            dwb_staged: false,
+            write_mode,
+            io_stats: DiskIoStats::default(),
+            header: Header {
+                next_page_id: 1,
+                free_list_head: 0,
+            }, // default (if new file)
+            path: path.as_ref().to_path_buf(),
         };
+        let existing = dm.file.metadata()?.len() >= HEADER_SIZE as u64;
         dm.header = dm.load_or_init_header()?;
+        dm.restore_double_write(existing)?;
         Ok(dm)
     }
 
+    /// Returns the format version recorded in the file's header without
+    /// opening it, or `None` when there is no database file yet. Used to
+    /// find files that need converting before they can be opened.
+    pub fn stored_format_version<P: AsRef<Path>>(path: P) -> Result<Option<u32>> {
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err),
+        };
+        if file.metadata()?.len() < HEADER_SIZE as u64 {
+            return Ok(None);
+        }
+        let mut buf = [0u8; HEADER_SIZE];
+        file.read_exact_at(&mut buf, 0)?;
+        Header::version(&buf).map(|(version, _)| Some(version))
+    }
+
+    fn dwb_path(path: &Path) -> PathBuf {
+        let mut name = path.as_os_str().to_owned();
+        name.push(".dwb");
+        PathBuf::from(name)
+    }
+
+    /// Rewrites the pages staged in the double-write file, if every record's
+    /// checksum is intact, then clears the file. A record that fails the
+    /// checksum was itself torn, which means none of the batch's in-place
+    /// writes had started. Records left beside a freshly created data file
+    /// are stale and discarded.
+    fn restore_double_write(&mut self, existing: bool) -> Result<()> {
+        let staged = self.dwb.metadata()?.len() as usize / DWB_RECORD_SIZE;
+        if existing && staged > 0 {
+            let mut records = vec![0u8; staged * DWB_RECORD_SIZE];
+            self.dwb.read_exact_at(&mut records, 0)?;
+            let pages = records
+                .chunks_exact(DWB_RECORD_SIZE)
+                .map(|record| {
+                    let page_id = u64::from_le_bytes(record[0..8].try_into().unwrap());
+                    let checksum = u64::from_le_bytes(record[8..16].try_into().unwrap());
+                    let image = &record[16..];
+                    let intact = page_id != 0
+                        && page_id < self.header.next_page_id
+                        && checksum == page_checksum(&[image]);
+                    intact.then_some((page_id, image))
+                })
+                .collect::<Option<Vec<_>>>();
+            if let Some(pages) = pages {
+                for (page_id, image) in pages {
+                    self.file.write_at(image, page_id * PAGE_SIZE as u64)?;
+                }
+                self.file.sync_data()?;
+            }
+        }
+        self.dwb.set_len(0)?;
+        self.dwb.sync_data()
+    }
+
     /// Loads or initializes the header page (page 0)
     fn load_or_init_header(&mut self) -> Result<Header> {
         let meta = self.file.metadata()?;
         if meta.len() < HEADER_SIZE as u64 {
             // brand new file; initialize header (page 0)
-            let header = Header { next_page_id: 1 };
+            let header = Header {
+                next_page_id: 1,
+                free_list_head: 0,
+            };
             let buf = header.to_bytes();
             self.file.write_at(&buf, 0)?;
             Ok(header)
@@ -73,6 +261,7 @@ impl DiskManager {
             // load header from disk (always exactly one page)
             let mut buf = [0u8; HEADER_SIZE];
             self.file.read_at(&mut buf, 0)?;
+            Header::validate(&buf)?;
             Ok(Header::from_bytes(&buf))
         }
     }
@@ -89,21 +278,80 @@ impl DiskManager {
 
     /// Write a page at page_id from buf
     pub fn write_page(&mut self, page_id: PageId, buf: &[u8]) -> Result<()> {
-        if buf.len() != PAGE_SIZE {
+        self.write_pages(&[(page_id, buf)])
+    }
+
+    /// Writes several pages. Under double-write they are staged together,
+    /// so the batch costs two syncs however many pages it holds.
+    pub fn write_pages(&mut self, pages: &[(PageId, &[u8])]) -> Result<()> {
+        if pages.iter().any(|(_, buf)| buf.len() != PAGE_SIZE) {
             return Err(Error::new(ErrorKind::InvalidInput, "buf wrong size"));
         }
-        let offset = page_id * PAGE_SIZE as u64;
-        self.file.write_at(buf, offset)?;
+        if pages.is_empty() {
+            return Ok(());
+        }
+        wal::fault::check_space(&self.path)?;
+        let double_write = !self.write_mode.skips_double_write();
+        if double_write {
+            let mut records = Vec::with_capacity(pages.len() * DWB_RECORD_SIZE);
+            for (page_id, buf) in pages {
+                records.extend_from_slice(&page_id.to_le_bytes());
+                records.extend_from_slice(&page_checksum(&[buf]).to_le_bytes());
+                records.extend_from_slice(buf);
+            }
+            // a longer file would still hold records of an earlier batch
+            self.dwb.set_len(records.len() as u64)?;
+            self.dwb.write_all_at(&records, 0)?;
+            self.dwb.sync_data()?;
+            self.io_stats.double_writes += pages.len() as u64;
+            self.io_stats.double_write_syncs += 1;
+            self.dwb_staged = true;
+        }
+        for (page_id, buf) in pages {
+            self.file.write_at(buf, page_id * PAGE_SIZE as u64)?;
+        }
+        if double_write {
+            // the staged images may only be replaced once these are durable
+            self.file.sync_data()?;
+        }
+        self.io_stats.page_writes += pages.len() as u64;
         Ok(())
     }
 
+    /// Returns the torn-write protection mode in use.
+    pub fn write_mode(&self) -> PageWriteMode {
+        self.write_mode
+    }
+
+    /// Returns write counters accumulated since open.
+    pub fn io_stats(&self) -> DiskIoStats {
+        self.io_stats
+    }
+
     /// Forces buffered data to disk.
     pub fn sync_data(&self) -> Result<()> {
         self.file.sync_data()
     }
 
-    /// Allocates a new page: extends file, writes zero page, updates + persists header
+    /// Allocates a page: reuses the most recently freed page if there is one,
+    /// otherwise extends the file. The page is zeroed and the header persisted.
     pub fn allocate_page(&mut self) -> Result<PageId> {
+        wal::fault::check_space(&self.path)?;
+        if self.header.free_list_head != 0 {
+            let page_id = self.header.free_list_head;
+            let mut next = [0u8; 8];
+            self.file
+                .read_exact_at(&mut next, page_id * PAGE_SIZE as u64)?;
+            // unlink before zeroing, so a crash in between leaks the page
+            // rather than leaving a zeroed page on the list
+            self.header.free_list_head = u64::from_le_bytes(next);
+            self.file.write_at(&self.header.to_bytes(), 0)?;
+            self.file.sync_data()?;
+            self.file
+                .write_at(&[0u8; PAGE_SIZE], page_id * PAGE_SIZE as u64)?;
+            self.file.sync_data()?;
+            return Ok(page_id);
+        }
         let page_id = self.header.next_page_id;
         // Write virgin zeroed page at offset (never reused!)
         let offset = page_id * PAGE_SIZE as u64;
@@ -117,12 +365,89 @@ impl DiskManager {
         Ok(page_id)
     }
 
+    /// Returns `page_id` to the free list so a later `allocate_page` reuses
+    /// it. The caller must make sure nothing refers to the page any more.
+    pub fn free_page(&mut self, page_id: PageId) -> Result<()> {
+        self.free_pages(&[page_id])
+    }
+
+    /// Returns several pages to the free list. The pages are chained to each
+    /// other and to the old list in place, then made the list's head, so the
+    /// batch costs one sync for the chain and one for the header.
+    pub fn free_pages(&mut self, page_ids: &[PageId]) -> Result<()> {
+        for (index, &page_id) in page_ids.iter().enumerate() {
+            if page_id == 0 || page_id >= self.header.next_page_id {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("cannot free page {} that was never allocated", page_id),
+                ));
+            }
+            if page_ids[..index].contains(&page_id) {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("page {} is freed twice", page_id),
+                ));
+            }
+        }
+        let Some(&head) = page_ids.first() else {
+            return Ok(());
+        };
+        if self.dwb_staged {
+            // every staged write has completed in place; a staged image of
+            // these pages must not be restored over the list after a crash
+            self.dwb.set_len(0)?;
+            self.dwb.sync_data()?;
+            self.dwb_staged = false;
+            self.io_stats.free_list_syncs += 1;
+        }
+        for (index, &page_id) in page_ids.iter().enumerate() {
+            let next = page_ids
+                .get(index + 1)
+                .copied()
+                .unwrap_or(self.header.free_list_head);
+            let mut node = [0u8; PAGE_SIZE];
+            node[..8].copy_from_slice(&next.to_le_bytes());
+            self.file.write_at(&node, page_id * PAGE_SIZE as u64)?;
+        }
+        self.file.sync_data()?;
+        self.header.free_list_head = head;
+        self.file.write_at(&self.header.to_bytes(), 0)?;
+        self.file.sync_data()?;
+        self.io_stats.free_list_syncs += 2;
+        Ok(())
+    }
+
+    /// Returns the number of pages on the free list.
+    pub fn free_page_count(&self) -> Result<usize> {
+        let mut count = 0;
+        let mut page_id = self.header.free_list_head;
+        while page_id != 0 {
+            count += 1;
+            let mut next = [0u8; 8];
+            self.file
+                .read_exact_at(&mut next, page_id * PAGE_SIZE as u64)?;
+            page_id = u64::from_le_bytes(next);
+        }
+        Ok(count)
+    }
+
     /// For tests: returns current next_page_id
     pub fn get_next_page_id(&self) -> PageId {
         self.header.next_page_id
     }
 }
 
+/// FNV-1a over the given slices of a page image, in order; detects a torn
+/// double-write record, and a damaged page in the data file.
+pub(crate) fn page_checksum(parts: &[&[u8]]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in parts.iter().flat_map(|part| part.iter()) {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -150,6 +475,7 @@ mod tests {
         fn drop(&mut self) {
             // Clean up file on test exit (pass or fail)
             let _ = fs::remove_file(&self.path);
+            let _ = fs::remove_file(self.path.with_extension("db.dwb"));
         }
     }
 
@@ -270,4 +596,243 @@ mod tests {
             assert_eq!(metadata.len(), expected_size, "Physical file size mismatch");
         }
     }
+
+    #[test]
+    fn test_freed_pages_are_reused_after_reopen() {
+        let ctx = TestContext::new("free_list");
+        let path = ctx.path.to_str().unwrap();
+        {
+            let mut dm = DiskManager::open(path).unwrap();
+            for _ in 0..4 {
+                dm.allocate_page().unwrap();
+            }
+            dm.write_page(2, &[0x77; PAGE_SIZE]).unwrap();
+            dm.free_page(2).unwrap();
+            dm.free_page(3).unwrap();
+            assert_eq!(dm.free_page_count().unwrap(), 2);
+            assert!(dm.free_page(9).is_err());
+        }
+
+        let mut dm = DiskManager::open(path).unwrap();
+        assert_eq!(dm.free_page_count().unwrap(), 2);
+        assert_eq!(dm.allocate_page().unwrap(), 3);
+        assert_eq!(dm.allocate_page().unwrap(), 2);
+        let mut buffer = [0xFFu8; PAGE_SIZE];
+        dm.read_page(2, &mut buffer).unwrap();
+        assert_eq!(buffer, [0u8; PAGE_SIZE], "reused page must be zeroed");
+        assert_eq!(dm.allocate_page().unwrap(), 5);
+        assert_eq!(dm.get_next_page_id(), 6);
+    }
+
+    #[test]
+    fn test_freed_batch_shares_syncs() {
+        let ctx = TestContext::new("free_batch");
+        let path = ctx.path.to_str().unwrap();
+        {
+            let mut dm = DiskManager::open(path).unwrap();
+            for _ in 0..10 {
+                dm.allocate_page().unwrap();
+            }
+            dm.free_page(1).unwrap();
+            assert_eq!(dm.io_stats().free_list_syncs, 2);
+            dm.write_page(4, &[0x77; PAGE_SIZE]).unwrap();
+            // the staged write is cleared once, then the chain and header
+            // are synced once each, however many pages the batch holds
+            dm.free_pages(&[2, 3, 4, 5, 6, 7, 8, 9]).unwrap();
+            assert_eq!(dm.io_stats().free_list_syncs, 5);
+            assert_eq!(dm.free_page_count().unwrap(), 9);
+            assert!(dm.free_pages(&[10, 10]).is_err());
+            assert_eq!(dm.free_page_count().unwrap(), 9);
+        }
+
+        let mut dm = DiskManager::open(path).unwrap();
+        assert_eq!(dm.free_page_count().unwrap(), 9);
+        let reused = (0..9)
+            .map(|_| dm.allocate_page().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(reused, vec![2, 3, 4, 5, 6, 7, 8, 9, 1]);
+        assert_eq!(dm.allocate_page().unwrap(), 11);
+    }
+
+    // --- 5. Torn-Write Protection ---
+    #[test]
+    fn test_atomic_sector_mode_skips_double_write() {
+        let ctx = TestContext::new("atomic_sector");
+        let mode = PageWriteMode::AtomicSector {
+            sector_size: PAGE_SIZE,
+        };
+        let mut dm = DiskManager::open_with_mode(ctx.path.to_str().unwrap(), mode).unwrap();
+        let p1 = dm.allocate_page().unwrap();
+        dm.write_page(p1, &[0x11; PAGE_SIZE]).unwrap();
+        dm.write_page(p1, &[0x22; PAGE_SIZE]).unwrap();
+        assert_eq!(
+            dm.io_stats(),
+            DiskIoStats {
+                page_writes: 2,
+                double_writes: 0,
+                double_write_syncs: 0,
+                free_list_syncs: 0,
+            }
+        );
+
+        // a smaller atomic unit cannot cover a page, so pages are staged
+        let mode = PageWriteMode::AtomicSector { sector_size: 512 };
+        let mut dm = DiskManager::open_with_mode(ctx.path.to_str().unwrap(), mode).unwrap();
+        dm.write_page(p1, &[0x33; PAGE_SIZE]).unwrap();
+        assert_eq!(dm.io_stats().double_writes, 1);
+    }
+
+    #[test]
+    fn test_torn_page_restored_from_double_write() {
+        let ctx = TestContext::new("torn_page");
+        let path = ctx.path.to_str().unwrap();
+        let page_id = {
+            let mut dm = DiskManager::open(path).unwrap();
+            let page_id = dm.allocate_page().unwrap();
+            dm.write_page(page_id, &[0x5A; PAGE_SIZE]).unwrap();
+            assert_eq!(dm.io_stats().double_writes, 1);
+            page_id
+        };
+
+        // simulate a crash that tore the in-place write halfway through
+        let file = OpenOptions::new().write(true).open(path).unwrap();
+        file.write_at(&[0u8; PAGE_SIZE / 2], page_id * PAGE_SIZE as u64)
+            .unwrap();
+        drop(file);
+
+        let dm = DiskManager::open(path).unwrap();
+        let mut buffer = [0u8; PAGE_SIZE];
+        dm.read_page(page_id, &mut buffer).unwrap();
+        assert_eq!(buffer, [0x5A; PAGE_SIZE], "torn page not repaired");
+    }
+
+    #[test]
+    fn test_batched_pages_share_double_write_syncs() {
+        let ctx = TestContext::new("double_write_batch");
+        let path = ctx.path.to_str().unwrap();
+        let images: Vec<[u8; PAGE_SIZE]> = (1..=8u8).map(|fill| [fill; PAGE_SIZE]).collect();
+        let page_ids = {
+            let mut dm = DiskManager::open(path).unwrap();
+            let page_ids = (0..images.len())
+                .map(|_| dm.allocate_page().unwrap())
+                .collect::<Vec<_>>();
+            let pages = page_ids
+                .iter()
+                .zip(&images)
+                .map(|(&page_id, image)| (page_id, &image[..]))
+                .collect::<Vec<_>>();
+            dm.write_pages(&pages).unwrap();
+            assert_eq!(
+                dm.io_stats(),
+                DiskIoStats {
+                    page_writes: 8,
+                    double_writes: 8,
+                    double_write_syncs: 1,
+                    free_list_syncs: 0,
+                }
+            );
+            page_ids
+        };
+
+        // tear two of the batch's in-place writes; the staged batch repairs
+        // both
+        let file = OpenOptions::new().write(true).open(path).unwrap();
+        for &page_id in &page_ids[2..4] {
+            file.write_at(&[0u8; PAGE_SIZE / 2], page_id * PAGE_SIZE as u64)
+                .unwrap();
+        }
+        drop(file);
+
+        let mut dm = DiskManager::open(path).unwrap();
+        for (&page_id, image) in page_ids.iter().zip(&images) {
+            let mut buffer = [0u8; PAGE_SIZE];
+            dm.read_page(page_id, &mut buffer).unwrap();
+            assert_eq!(&buffer, image, "page {} not repaired", page_id);
+        }
+
+        // a single later write leaves no record of the earlier batch behind
+        dm.write_page(page_ids[0], &[0xEE; PAGE_SIZE]).unwrap();
+        drop(dm);
+        assert_eq!(
+            fs::metadata(DiskManager::dwb_path(&ctx.path))
+                .unwrap()
+                .len(),
+            DWB_RECORD_SIZE as u64
+        );
+    }
+
+    // --- 6. Format Versioning ---
+    #[test]
+    fn test_incompatible_format_version_refused() {
+        let ctx = TestContext::new("format_version");
+        let path = ctx.path.to_str().unwrap();
+        DiskManager::open(path).unwrap();
+
+        let file = OpenOptions::new().write(true).open(path).unwrap();
+        file.write_at(&(FORMAT_VERSION + 1).to_le_bytes(), 16)
+            .unwrap();
+        drop(file);
+
+        let err = DiskManager::open(path).err().expect("open should fail");
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        assert!(err.to_string().contains(&format!(
+            "format version {} is not supported",
+            FORMAT_VERSION + 1
+        )));
+    }
+
+    #[test]
+    fn test_formats_before_page_checksums_refused() {
+        let ctx = TestContext::new("pre_checksum_format");
+        let path = ctx.path.to_str().unwrap();
+        DiskManager::open(path).unwrap();
+
+        let file = OpenOptions::new().write(true).open(path).unwrap();
+        file.write_at(&2u32.to_le_bytes(), 16).unwrap();
+        drop(file);
+
+        let err = DiskManager::open(path).err().expect("open should fail");
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        assert!(
+            err.to_string()
+                .contains("format version 2 is not supported")
+        );
+    }
+
+    #[test]
+    fn test_unversioned_header_refused() {
+        let ctx = TestContext::new("unversioned_header");
+        let path = ctx.path.to_str().unwrap();
+        let mut legacy = [0u8; HEADER_SIZE];
+        legacy[..8].copy_from_slice(&3u64.to_le_bytes());
+        fs::write(path, legacy).unwrap();
+
+        let err = DiskManager::open(path).err().expect("open should fail");
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        assert!(
+            err.to_string()
+                .contains(&format!("format version {} is not supported", UNVERSIONED))
+        );
+    }
+
+    #[test]
+    fn test_stored_format_version_read_without_opening() {
+        let ctx = TestContext::new("stored_format_version");
+        let path = ctx.path.to_str().unwrap();
+        assert_eq!(DiskManager::stored_format_version(path).unwrap(), None);
+
+        DiskManager::open(path).unwrap();
+        assert_eq!(
+            DiskManager::stored_format_version(path).unwrap(),
+            Some(FORMAT_VERSION)
+        );
+
+        let mut legacy = [0u8; HEADER_SIZE];
+        legacy[..8].copy_from_slice(&3u64.to_le_bytes());
+        fs::write(path, legacy).unwrap();
+        assert_eq!(
+            DiskManager::stored_format_version(path).unwrap(),
+            Some(UNVERSIONED)
+        );
+    }
 }
@@ -8,6 +8,6 @@ mod replacer;
 // PUBLIC API EXPORTS
 // Users of this crate (like the main DB server) can access these directly.
 pub use buffer::{BufferPoolError, BufferPoolManager, BufferPoolResult, FlushMode, PageGuard};
-pub use disk::{DiskManager, PAGE_SIZE, PageId};
-pub use page::{PAGE_LSN_SIZE, Page};
+pub use disk::{DiskIoStats, DiskManager, FORMAT_VERSION, PAGE_SIZE, PageId, PageWriteMode};
+pub use page::{PAGE_HEADER_SIZE, PAGE_LSN_SIZE, Page};
 pub use replacer::{FrameId, LRUReplacer, Replacer};
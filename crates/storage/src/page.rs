@@ -1,3 +1,4 @@
+use crate::disk::page_checksum;
 use crate::{PAGE_SIZE, PageId};
 
 /// In-memory page container with metadata for buffer management.
@@ -10,6 +11,10 @@ pub struct Page {
 }
 
 pub const PAGE_LSN_SIZE: usize = 8;
+/// Bytes after the LSN holding the page checksum.
+const PAGE_CHECKSUM_SIZE: usize = 8;
+/// Bytes every page reserves for the storage layer; page contents start here.
+pub const PAGE_HEADER_SIZE: usize = PAGE_LSN_SIZE + PAGE_CHECKSUM_SIZE;
 
 impl Page {
     /// Creates a zeroed page with no identity.
@@ -39,6 +44,29 @@ impl Page {
         self.data[..PAGE_LSN_SIZE].copy_from_slice(&lsn.to_le_bytes());
     }
 
+    /// Stores the checksum of the page's current contents in its header.
+    pub(crate) fn update_checksum(&mut self) {
+        let checksum = self.compute_checksum();
+        self.data[PAGE_LSN_SIZE..PAGE_HEADER_SIZE].copy_from_slice(&checksum.to_le_bytes());
+    }
+
+    /// Whether the stored checksum matches the page's contents. An all-zero
+    /// page passes: it was allocated but never written.
+    pub(crate) fn checksum_is_valid(&self) -> bool {
+        let stored = u64::from_le_bytes(
+            self.data[PAGE_LSN_SIZE..PAGE_HEADER_SIZE]
+                .try_into()
+                .unwrap(),
+        );
+        stored == self.compute_checksum() || self.data.iter().all(|byte| *byte == 0)
+    }
+
+    /// Digest of everything but the checksum field. The LSN is covered:
+    /// recovery trusts it to decide which log records to redo.
+    fn compute_checksum(&self) -> u64 {
+        page_checksum(&[&self.data[..PAGE_LSN_SIZE], &self.data[PAGE_HEADER_SIZE..]])
+    }
+
     /// Returns whether the page has been modified.
     pub fn is_dirty(&self) -> bool {
         self.is_dirty
@@ -13,22 +13,87 @@ pub struct TxnId(pub u64);
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum LockKey {
     Page(u64),
+    /// One row of a heap page. Locking it first takes the matching intention
+    /// lock on the page, so a page lock conflicts with row locks inside it.
+    Row {
+        page_id: u64,
+        slot_id: u32,
+    },
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum LockMode {
     Shared,
     Exclusive,
+    /// Held on a page while rows of it are locked shared.
+    IntentionShared,
+    /// Held on a page while rows of it are locked exclusive.
+    IntentionExclusive,
+}
+
+impl LockMode {
+    /// Whether two transactions may hold `self` and `other` on one key.
+    fn compatible(self, other: LockMode) -> bool {
+        use LockMode::*;
+        match (self, other) {
+            (Exclusive, _) | (_, Exclusive) => false,
+            (IntentionShared, _) | (_, IntentionShared) => true,
+            (Shared, Shared) | (IntentionExclusive, IntentionExclusive) => true,
+            (Shared, IntentionExclusive) | (IntentionExclusive, Shared) => false,
+        }
+    }
+
+    /// Whether holding `self` grants everything `other` does.
+    fn covers(self, other: LockMode) -> bool {
+        use LockMode::*;
+        self == other
+            || matches!(
+                (self, other),
+                (Exclusive, _) | (Shared | IntentionExclusive, IntentionShared)
+            )
+    }
+
+    /// Weakest mode granting both; shared with intention-exclusive takes
+    /// exclusive.
+    fn combine(self, other: LockMode) -> LockMode {
+        if self.covers(other) {
+            self
+        } else if other.covers(self) {
+            other
+        } else {
+            LockMode::Exclusive
+        }
+    }
+
+    /// Mode a page is locked in before one of its rows is locked in `self`.
+    fn intention(self) -> LockMode {
+        match self {
+            LockMode::Shared | LockMode::IntentionShared => LockMode::IntentionShared,
+            LockMode::Exclusive | LockMode::IntentionExclusive => LockMode::IntentionExclusive,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum DeadlockPolicy {
     Timeout(Duration),
+    /// Waits without a deadline, but a request that would close a cycle in
+    /// the wait-for graph aborts the youngest transaction on the cycle.
+    WaitForGraph,
+    /// Never waits: a request that cannot be granted at once fails with
+    /// [`LockError::WouldBlock`].
+    NoWait,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum LockError {
     DeadlockTimeout,
+    /// The transaction was chosen as the victim of a deadlock. Its other
+    /// locks stay held until `unlock_all`.
+    Deadlock(TxnId),
+    /// The lock could not be granted without waiting, and the request was
+    /// not allowed to wait.
+    WouldBlock,
     LockAlreadyHeld,
 }
 
@@ -42,8 +107,7 @@ struct LockRequest {
 
 #[derive(Debug, Default)]
 struct LockState {
-    mode: Option<LockMode>,
-    holders: HashSet<TxnId>,
+    holders: HashMap<TxnId, LockMode>,
     waiters: VecDeque<LockRequest>,
 }
 
@@ -51,6 +115,22 @@ struct LockState {
 struct LockManagerState {
     locks: HashMap<LockKey, LockState>,
     held_keys: HashMap<TxnId, HashSet<LockKey>>,
+    /// Transactions whose last lock request gave up waiting, on timeout or
+    /// as a deadlock victim.
+    timed_out: HashSet<TxnId>,
+    /// Waiting transactions chosen as deadlock victims by another
+    /// transaction's request; each withdraws its request once it wakes.
+    victims: HashSet<TxnId>,
+    stats: LockStats,
+}
+
+/// Lock requests granted since the lock manager was created, by mode.
+/// Re-requesting a lock that is already held is not counted, nor are
+/// intention locks.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct LockStats {
+    pub shared_grants: u64,
+    pub exclusive_grants: u64,
 }
 
 pub struct LockManager {
@@ -76,17 +156,40 @@ impl LockManager {
         self.lock(txn_id, key, LockMode::Exclusive)
     }
 
+    /// Blocks until `txn_id` holds `key` in at least `mode`, waiting as long
+    /// as the policy allows. A lock it already holds is upgraded.
+    pub fn lock(&self, txn_id: TxnId, key: LockKey, mode: LockMode) -> LockResult<()> {
+        self.lock_with_deadline(txn_id, key, mode, self.deadline())
+    }
+
+    /// Like [`lock`](Self::lock), but waits until `deadline` instead of the
+    /// policy's timeout, or indefinitely when it is `None`. A deadline that
+    /// has already passed fails a request that would wait with
+    /// [`LockError::WouldBlock`]. Deadlock detection still follows the
+    /// policy.
+    pub fn lock_with_deadline(
+        &self,
+        txn_id: TxnId,
+        key: LockKey,
+        mode: LockMode,
+        deadline: Option<Instant>,
+    ) -> LockResult<()> {
+        if let LockKey::Row { page_id, .. } = key {
+            self.lock_with_deadline(txn_id, LockKey::Page(page_id), mode.intention(), deadline)?;
+        }
+        self.lock_key(txn_id, key, mode, deadline)
+    }
+
     pub fn unlock_all(&self, txn_id: TxnId) {
         let mut state = self.state.lock();
+        state.timed_out.remove(&txn_id);
+        state.victims.remove(&txn_id);
         let Some(keys) = state.held_keys.remove(&txn_id) else {
             return;
         };
         for key in keys {
             let lock_state = state.locks.get_mut(&key).expect("lock state exists");
             lock_state.holders.remove(&txn_id);
-            if lock_state.holders.is_empty() {
-                lock_state.mode = None;
-            }
         }
         self.process_waiters(&mut state);
         self.condvar.notify_all();
@@ -101,24 +204,50 @@ impl LockManager {
             .unwrap_or_default()
     }
 
-    fn lock(&self, txn_id: TxnId, key: LockKey, mode: LockMode) -> LockResult<()> {
+    pub fn stats(&self) -> LockStats {
+        self.state.lock().stats
+    }
+
+    /// Whether a lock request of `txn_id` failed with
+    /// [`LockError::DeadlockTimeout`] or [`LockError::Deadlock`] since its
+    /// locks were last released.
+    pub fn timed_out(&self, txn_id: TxnId) -> bool {
+        self.state.lock().timed_out.contains(&txn_id)
+    }
+
+    fn lock_key(
+        &self,
+        txn_id: TxnId,
+        key: LockKey,
+        mode: LockMode,
+        deadline: Option<Instant>,
+    ) -> LockResult<()> {
         let mut state = self.state.lock();
-        if self.holds_lock(&state, txn_id, &key, mode) {
-            return Ok(());
-        }
-        let deadline = self.deadline();
         loop {
+            // also true once a release has promoted this request
+            if self.holds_lock(&state, txn_id, &key, mode) {
+                return Ok(());
+            }
+            if state.victims.remove(&txn_id) {
+                drop(state);
+                self.abandon_wait(txn_id, &key);
+                return Err(LockError::Deadlock(txn_id));
+            }
             let should_wait;
             {
                 let lock_state = state.locks.entry(key.clone()).or_default();
                 if self.can_grant(lock_state, txn_id, mode) && lock_state.waiters.is_empty() {
-                    lock_state.mode = Some(mode);
-                    lock_state.holders.insert(txn_id);
+                    grant(lock_state, txn_id, mode);
                     state
                         .held_keys
                         .entry(txn_id)
                         .or_default()
                         .insert(key.clone());
+                    match mode {
+                        LockMode::Shared => state.stats.shared_grants += 1,
+                        LockMode::Exclusive => state.stats.exclusive_grants += 1,
+                        LockMode::IntentionShared | LockMode::IntentionExclusive => {}
+                    }
                     return Ok(());
                 }
                 if !lock_state
@@ -126,16 +255,55 @@ impl LockManager {
                     .iter()
                     .any(|waiter| waiter.txn_id == txn_id)
                 {
+                    // not queued at all, so there is nothing to withdraw
+                    if deadline.is_some_and(|deadline| deadline <= Instant::now()) {
+                        return Err(LockError::WouldBlock);
+                    }
                     lock_state.waiters.push_back(LockRequest { txn_id, mode });
                 }
                 should_wait = true;
             }
+            if should_wait && self.policy == DeadlockPolicy::WaitForGraph {
+                // the graph was acyclic before this request, so any cycle
+                // now runs through it
+                match deadlock_victim(&state, txn_id) {
+                    Some(victim) if victim == txn_id => {
+                        drop(state);
+                        self.abandon_wait(txn_id, &key);
+                        return Err(LockError::Deadlock(txn_id));
+                    }
+                    Some(victim) => {
+                        state.victims.insert(victim);
+                        self.condvar.notify_all();
+                    }
+                    None => {}
+                }
+            }
             if should_wait {
-                state = self.wait_for_lock(state, deadline)?;
+                state = match self.wait_for_lock(state, deadline) {
+                    Ok(state) => state,
+                    Err(error) => {
+                        self.abandon_wait(txn_id, &key);
+                        return Err(error);
+                    }
+                };
             }
         }
     }
 
+    /// Withdraws a timed-out or deadlocked request from the queue of `key`, so the lock is
+    /// never granted to a transaction that stopped waiting for it, and lets
+    /// the requests queued behind it proceed.
+    fn abandon_wait(&self, txn_id: TxnId, key: &LockKey) {
+        let mut state = self.state.lock();
+        state.timed_out.insert(txn_id);
+        if let Some(lock_state) = state.locks.get_mut(key) {
+            lock_state.waiters.retain(|waiter| waiter.txn_id != txn_id);
+            self.promote_waiters(&mut state, key.clone());
+        }
+        self.condvar.notify_all();
+    }
+
     fn wait_for_lock<'a>(
         &self,
         mut state: MutexGuard<'a, LockManagerState>,
@@ -164,18 +332,19 @@ impl LockManager {
     fn deadline(&self) -> Option<Instant> {
         match self.policy {
             DeadlockPolicy::Timeout(duration) => Some(Instant::now() + duration),
+            DeadlockPolicy::WaitForGraph => None,
+            DeadlockPolicy::NoWait => Some(Instant::now()),
         }
     }
 
+    /// Whether `txn_id` may hold `mode`, together with any mode it holds
+    /// already, alongside the other holders.
     fn can_grant(&self, lock_state: &LockState, txn_id: TxnId, mode: LockMode) -> bool {
-        match lock_state.mode {
-            None => true,
-            Some(LockMode::Shared) => {
-                mode == LockMode::Shared
-                    || (lock_state.holders.len() == 1 && lock_state.holders.contains(&txn_id))
-            }
-            Some(LockMode::Exclusive) => lock_state.holders.contains(&txn_id),
-        }
+        let wanted = wanted_mode(lock_state, txn_id, mode);
+        lock_state
+            .holders
+            .iter()
+            .all(|(&holder, &held)| holder == txn_id || wanted.compatible(held))
     }
 
     fn holds_lock(
@@ -185,26 +354,17 @@ impl LockManager {
         key: &LockKey,
         mode: LockMode,
     ) -> bool {
-        let Some(lock_state) = state.locks.get(key) else {
-            return false;
-        };
-        if !lock_state.holders.contains(&txn_id) {
-            return false;
-        }
-        matches!(
-            (lock_state.mode, mode),
-            (Some(LockMode::Exclusive), _) | (Some(LockMode::Shared), LockMode::Shared)
-        )
+        state
+            .locks
+            .get(key)
+            .and_then(|lock_state| lock_state.holders.get(&txn_id))
+            .is_some_and(|held| held.covers(mode))
     }
 
     fn process_waiters(&self, state: &mut LockManagerState) {
         let keys: Vec<LockKey> = state.locks.keys().cloned().collect();
         for key in keys {
-            let lock_state = state.locks.get_mut(&key).expect("lock state exists");
-            if lock_state.holders.is_empty() {
-                lock_state.mode = None;
-            }
-            self.promote_waiters(state, key.clone());
+            self.promote_waiters(state, key);
         }
     }
 
@@ -216,17 +376,13 @@ impl LockManager {
                 break;
             }
             let request = lock_state.waiters.pop_front().expect("waiter exists");
-            lock_state.mode = Some(request.mode);
-            lock_state.holders.insert(request.txn_id);
+            grant(lock_state, request.txn_id, request.mode);
             state
                 .held_keys
                 .entry(request.txn_id)
                 .or_default()
                 .insert(key.clone());
             promoted_any = true;
-            if request.mode == LockMode::Exclusive {
-                break;
-            }
         }
         if promoted_any {
             self.condvar.notify_all();
@@ -234,6 +390,78 @@ impl LockManager {
     }
 }
 
+/// Mode `txn_id` holds `key` in once `mode` is granted to it.
+fn wanted_mode(lock_state: &LockState, txn_id: TxnId, mode: LockMode) -> LockMode {
+    lock_state
+        .holders
+        .get(&txn_id)
+        .map_or(mode, |held| held.combine(mode))
+}
+
+fn grant(lock_state: &mut LockState, txn_id: TxnId, mode: LockMode) {
+    let held = wanted_mode(lock_state, txn_id, mode);
+    lock_state.holders.insert(txn_id, held);
+}
+
+/// Youngest transaction on a cycle of the wait-for graph through `txn_id`,
+/// if there is one. A waiter waits for the holders of its lock and for the
+/// requests queued ahead of it, which are granted first, whose modes
+/// conflict with the one it asks for.
+fn deadlock_victim(state: &LockManagerState, txn_id: TxnId) -> Option<TxnId> {
+    let mut waits_for: HashMap<TxnId, Vec<TxnId>> = HashMap::new();
+    for lock_state in state.locks.values() {
+        for (position, waiter) in lock_state.waiters.iter().enumerate() {
+            let wanted = wanted_mode(lock_state, waiter.txn_id, waiter.mode);
+            let holders = lock_state
+                .holders
+                .iter()
+                .map(|(&holder, &held)| (holder, held));
+            let ahead = lock_state
+                .waiters
+                .iter()
+                .take(position)
+                .map(|ahead| (ahead.txn_id, ahead.mode));
+            waits_for.entry(waiter.txn_id).or_default().extend(
+                holders
+                    .chain(ahead)
+                    .filter(|&(other, mode)| other != waiter.txn_id && !wanted.compatible(mode))
+                    .map(|(other, _)| other),
+            );
+        }
+    }
+    let mut path = vec![txn_id];
+    let mut visited = HashSet::from([txn_id]);
+    find_cycle(&waits_for, txn_id, &mut path, &mut visited)
+        .then(|| path.into_iter().max_by_key(|txn| txn.0))
+        .flatten()
+}
+
+/// Depth-first search from the end of `path` back to its start, leaving
+/// the cycle in `path` when one is found.
+fn find_cycle(
+    waits_for: &HashMap<TxnId, Vec<TxnId>>,
+    start: TxnId,
+    path: &mut Vec<TxnId>,
+    visited: &mut HashSet<TxnId>,
+) -> bool {
+    let Some(&node) = path.last() else {
+        return false;
+    };
+    for &next in waits_for.get(&node).into_iter().flatten() {
+        if next == start {
+            return true;
+        }
+        if visited.insert(next) {
+            path.push(next);
+            if find_cycle(waits_for, start, path, visited) {
+                return true;
+            }
+            path.pop();
+        }
+    }
+    false
+}
+
 #[test]
 fn shared_shared_is_compatible() {
     let manager = LockManager::new(DeadlockPolicy::Timeout(Duration::from_millis(200)));
@@ -322,3 +550,157 @@ fn unlock_all_releases_keys() {
     manager.unlock_all(txn);
     assert!(manager.held_keys_for(txn).is_empty());
 }
+
+#[test]
+fn stats_count_new_grants_by_mode() {
+    let manager = LockManager::new(DeadlockPolicy::Timeout(Duration::from_millis(200)));
+    let txn = TxnId(1);
+    manager.lock_shared(txn, LockKey::Page(1)).unwrap();
+    manager.lock_shared(txn, LockKey::Page(1)).unwrap();
+    manager.lock_exclusive(txn, LockKey::Page(1)).unwrap();
+    manager.lock_exclusive(txn, LockKey::Page(2)).unwrap();
+    assert_eq!(
+        manager.stats(),
+        LockStats {
+            shared_grants: 1,
+            exclusive_grants: 2,
+        }
+    );
+}
+
+#[test]
+fn timed_out_waiter_is_never_granted_the_lock() {
+    let manager = LockManager::new(DeadlockPolicy::Timeout(Duration::from_millis(50)));
+    let key = LockKey::Page(3);
+    manager.lock_exclusive(TxnId(1), key.clone()).unwrap();
+    assert_eq!(
+        manager.lock_exclusive(TxnId(2), key.clone()),
+        Err(LockError::DeadlockTimeout)
+    );
+    manager.unlock_all(TxnId(1));
+    assert!(manager.held_keys_for(TxnId(2)).is_empty());
+    assert!(manager.lock_exclusive(TxnId(3), key.clone()).is_ok());
+}
+
+#[test]
+fn wait_for_graph_aborts_one_transaction_of_a_cycle() {
+    let manager = Arc::new(LockManager::new(DeadlockPolicy::WaitForGraph));
+    manager.lock_exclusive(TxnId(1), LockKey::Page(1)).unwrap();
+    manager.lock_exclusive(TxnId(2), LockKey::Page(2)).unwrap();
+    let barrier = Arc::new(Barrier::new(2));
+    let handles = [(TxnId(1), LockKey::Page(2)), (TxnId(2), LockKey::Page(1))].map(|(txn, key)| {
+        let manager = Arc::clone(&manager);
+        let barrier = Arc::clone(&barrier);
+        thread::spawn(move || {
+            barrier.wait();
+            let result = manager.lock_exclusive(txn, key);
+            // the victim rolls back, which lets the other transaction on
+            // the cycle proceed
+            if result.is_err() {
+                manager.unlock_all(txn);
+            }
+            result
+        })
+    });
+    let results = handles.map(|handle| handle.join().unwrap());
+    assert_eq!(results, [Ok(()), Err(LockError::Deadlock(TxnId(2)))]);
+    let mut held = manager.held_keys_for(TxnId(1));
+    held.sort_by_key(|key| format!("{:?}", key));
+    assert_eq!(held, vec![LockKey::Page(1), LockKey::Page(2)]);
+}
+
+#[test]
+fn intention_locks_follow_the_compatibility_matrix() {
+    let manager = LockManager::new(DeadlockPolicy::Timeout(Duration::from_millis(50)));
+    let key = LockKey::Page(5);
+    manager
+        .lock(TxnId(1), key.clone(), LockMode::IntentionShared)
+        .unwrap();
+    manager
+        .lock(TxnId(2), key.clone(), LockMode::IntentionExclusive)
+        .unwrap();
+    assert_eq!(
+        manager.lock_shared(TxnId(3), key.clone()),
+        Err(LockError::DeadlockTimeout)
+    );
+    manager
+        .lock(TxnId(4), key.clone(), LockMode::IntentionShared)
+        .unwrap();
+    manager.unlock_all(TxnId(2));
+    manager.lock_shared(TxnId(5), key.clone()).unwrap();
+    assert_eq!(
+        manager.lock(TxnId(6), key.clone(), LockMode::IntentionExclusive),
+        Err(LockError::DeadlockTimeout)
+    );
+    // an upgrade waits for the other holders it conflicts with
+    assert_eq!(
+        manager.lock(TxnId(1), key.clone(), LockMode::IntentionExclusive),
+        Err(LockError::DeadlockTimeout)
+    );
+    let state = manager.state.lock();
+    assert!(manager.holds_lock(&state, TxnId(5), &key, LockMode::IntentionShared));
+    assert!(!manager.holds_lock(&state, TxnId(5), &key, LockMode::IntentionExclusive));
+}
+
+#[test]
+fn row_locks_take_intention_locks_on_their_page() {
+    let manager = LockManager::new(DeadlockPolicy::Timeout(Duration::from_millis(50)));
+    let row = |slot_id| LockKey::Row {
+        page_id: 8,
+        slot_id,
+    };
+    manager.lock_exclusive(TxnId(1), row(0)).unwrap();
+    manager.lock_exclusive(TxnId(2), row(1)).unwrap();
+    assert!(manager.holds_lock(
+        &manager.state.lock(),
+        TxnId(1),
+        &LockKey::Page(8),
+        LockMode::IntentionExclusive
+    ));
+    assert_eq!(
+        manager.lock_exclusive(TxnId(3), row(0)),
+        Err(LockError::DeadlockTimeout)
+    );
+    assert_eq!(
+        manager.lock_shared(TxnId(4), LockKey::Page(8)),
+        Err(LockError::DeadlockTimeout)
+    );
+    manager.lock_shared(TxnId(5), row(2)).unwrap();
+    manager.unlock_all(TxnId(1));
+    manager.lock_exclusive(TxnId(6), row(0)).unwrap();
+}
+
+#[test]
+fn no_wait_fails_at_once_on_a_held_exclusive_lock() {
+    let manager = LockManager::new(DeadlockPolicy::NoWait);
+    let key = LockKey::Page(4);
+    manager.lock_exclusive(TxnId(1), key.clone()).unwrap();
+    assert_eq!(
+        manager.lock_shared(TxnId(2), key.clone()),
+        Err(LockError::WouldBlock)
+    );
+    assert!(!manager.timed_out(TxnId(2)));
+    assert!(manager.state.lock().locks[&key].waiters.is_empty());
+    manager.unlock_all(TxnId(1));
+    assert!(manager.held_keys_for(TxnId(2)).is_empty());
+    manager.lock_exclusive(TxnId(3), key.clone()).unwrap();
+}
+
+#[test]
+fn per_request_deadline_overrides_the_policy_timeout() {
+    let manager = LockManager::new(DeadlockPolicy::Timeout(Duration::from_secs(30)));
+    let key = LockKey::Page(6);
+    manager.lock_exclusive(TxnId(1), key.clone()).unwrap();
+    let started = Instant::now();
+    let deadline = started + Duration::from_millis(50);
+    assert_eq!(
+        manager.lock_with_deadline(TxnId(2), key.clone(), LockMode::Shared, Some(deadline)),
+        Err(LockError::DeadlockTimeout)
+    );
+    assert!(started.elapsed() < Duration::from_secs(5));
+    assert_eq!(
+        manager.lock_with_deadline(TxnId(3), key.clone(), LockMode::Shared, Some(started)),
+        Err(LockError::WouldBlock)
+    );
+    assert!(manager.state.lock().locks[&key].waiters.is_empty());
+}
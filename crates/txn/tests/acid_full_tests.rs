@@ -121,7 +121,7 @@ mod atomicity_tests {
         let handle = thread::spawn(move || {
             barrier_clone.wait();
             let result = lock_manager1.lock_exclusive(TxnId(1), key.clone());
-            let _ = started_clone.store(true, std::sync::atomic::Ordering::SeqCst);
+            started_clone.store(true, std::sync::atomic::Ordering::SeqCst);
             barrier_clone.wait();
             result
         });
@@ -417,7 +417,7 @@ mod durability_tests {
         let mut reader = wal::LogReader::open(temp_dir.path().join("flush_test.wal"))
             .expect("Failed to open log reader");
         let mut count = 0;
-        while let Some(_) = reader.next_record().expect("Read error") {
+        while reader.next_record().expect("Read error").is_some() {
             count += 1;
         }
         assert_eq!(
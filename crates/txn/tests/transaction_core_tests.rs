@@ -155,7 +155,7 @@ mod lock_manager_tests {
     fn test_unlock_all_releases_everything() {
         let manager = create_lock_manager();
         let txn = TxnId(1);
-        let keys: Vec<LockKey> = (1..=5).map(|i| LockKey::Page(i)).collect();
+        let keys: Vec<LockKey> = (1..=5).map(LockKey::Page).collect();
 
         // Acquire multiple locks
         for key in &keys {
@@ -316,8 +316,8 @@ mod lock_manager_tests {
         );
 
         // Clean up any remaining locks
-        let _ = manager.unlock_all(txn1);
-        let _ = manager.unlock_all(txn2);
+        manager.unlock_all(txn1);
+        manager.unlock_all(txn2);
     }
 }
 
@@ -403,6 +403,6 @@ mod transaction_integration_tests {
         let result3 = handle3.join().unwrap();
         assert!(result3.is_ok(), "T2 should succeed after T1 releases");
 
-        let _ = handle1.join().unwrap();
+        handle1.join().unwrap();
     }
 }
@@ -0,0 +1,47 @@
+//! Simulated disk faults for tests. While a prefix is registered with
+//! [`fill_disk`], every log or page write to a file whose path begins with
+//! it fails with `ErrorKind::StorageFull`, as writes to a full disk fail
+//! with ENOSPC, until [`free_disk`] removes it again.
+
+use std::ffi::OsString;
+use std::io::{Error, ErrorKind, Result};
+use std::path::Path;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static FULL: Mutex<Vec<OsString>> = Mutex::new(Vec::new());
+/// Set while any prefix is registered, so writes skip the lock otherwise.
+static ANY_FULL: AtomicBool = AtomicBool::new(false);
+
+/// Fails writes to files whose path begins with `prefix`.
+pub fn fill_disk(prefix: impl AsRef<Path>) {
+    let mut full = FULL.lock().unwrap_or_else(|e| e.into_inner());
+    full.push(prefix.as_ref().as_os_str().to_owned());
+    ANY_FULL.store(true, Ordering::SeqCst);
+}
+
+/// Lets writes under `prefix` succeed again.
+pub fn free_disk(prefix: impl AsRef<Path>) {
+    let mut full = FULL.lock().unwrap_or_else(|e| e.into_inner());
+    full.retain(|registered| registered != prefix.as_ref().as_os_str());
+    ANY_FULL.store(!full.is_empty(), Ordering::SeqCst);
+}
+
+/// Returns the error a write to `path` gets while its disk is full.
+pub fn check_space(path: &Path) -> Result<()> {
+    if !ANY_FULL.load(Ordering::SeqCst) {
+        return Ok(());
+    }
+    let path = path.as_os_str().as_encoded_bytes();
+    let full = FULL.lock().unwrap_or_else(|e| e.into_inner());
+    if full
+        .iter()
+        .any(|prefix| path.starts_with(prefix.as_encoded_bytes()))
+    {
+        return Err(Error::new(
+            ErrorKind::StorageFull,
+            "no space left on device (simulated)",
+        ));
+    }
+    Ok(())
+}
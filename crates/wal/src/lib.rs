@@ -1,32 +1,62 @@
 use parking_lot::{Condvar, Mutex};
 use std::cell::RefCell;
-use std::fs::{File, OpenOptions};
-use std::io::{Read, Seek, SeekFrom, Write};
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::fs::{self, File, OpenOptions};
+use std::io::{ErrorKind, Read, Seek, SeekFrom, Write};
 use std::path::{Path, PathBuf};
-use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, mpsc};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use thiserror::Error;
 use txn::LockManager;
 
+pub mod fault;
+
 pub type Lsn = u64;
 pub type TxnId = u64;
 pub type PageId = u64;
 
 const INVALID_LSN: Lsn = u64::MAX;
+/// Layout version stored in every record, after its length and checksum.
+/// Logs written before records carried a checksum have no version and fail
+/// the checksum instead of being misread.
+const LOG_FORMAT_VERSION: u8 = 1;
+/// Length prefix and CRC32 checksum; the checksum covers everything after.
+const RECORD_PREFIX_LEN: usize = 8;
 const DEFAULT_LOG_BUFFER_SIZE: usize = 1024 * 1024;
+/// Bytes of log held by each segment file. Segment 0 is the log path itself
+/// and segment `n` the path with `.n` appended, so an LSN stays a byte
+/// offset into the log as a whole and whole segments can be deleted once
+/// recovery no longer needs them.
+pub const SEGMENT_SIZE: u64 = 1024 * 1024;
+/// Finished transactions whose durations are kept for percentiles.
+const RECENT_DURATIONS: usize = 1024;
 
 #[derive(Debug, Error)]
 pub enum WalError {
     #[error("wal io error: {0}")]
-    Io(#[from] std::io::Error),
+    Io(std::io::Error),
+    /// A log write found the disk full. The unwritten records are kept and
+    /// written again by the next flush, so logging resumes once there is
+    /// space.
+    #[error("wal disk full: {0}")]
+    DiskFull(String),
     #[error("wal corruption: {0}")]
     Corrupt(String),
     #[error("wal channel closed")]
     ChannelClosed,
 }
 
+impl From<std::io::Error> for WalError {
+    fn from(err: std::io::Error) -> Self {
+        if err.kind() == ErrorKind::StorageFull {
+            WalError::DiskFull(err.to_string())
+        } else {
+            WalError::Io(err)
+        }
+    }
+}
+
 pub type WalResult<T> = Result<T, WalError>;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -38,6 +68,15 @@ pub enum LogRecordType {
     PageUpdate,
     Compensation,
     Checkpoint,
+    /// Snapshot of the catalog written by a schema change, so a crash before
+    /// the catalog file is rewritten does not lose the change.
+    Catalog,
+    /// Pages returned to the free list. Records for those pages older than
+    /// this one describe their previous owner and must not be redone.
+    FreePages,
+    /// Logical description of a row inserted, updated or deleted by a
+    /// transaction, read by the change feed. Recovery ignores it.
+    RowChange,
 }
 
 impl LogRecordType {
@@ -50,6 +89,9 @@ impl LogRecordType {
             LogRecordType::PageUpdate => 5,
             LogRecordType::Compensation => 6,
             LogRecordType::Checkpoint => 7,
+            LogRecordType::Catalog => 8,
+            LogRecordType::FreePages => 9,
+            LogRecordType::RowChange => 10,
         }
     }
 
@@ -62,6 +104,9 @@ impl LogRecordType {
             5 => Ok(LogRecordType::PageUpdate),
             6 => Ok(LogRecordType::Compensation),
             7 => Ok(LogRecordType::Checkpoint),
+            8 => Ok(LogRecordType::Catalog),
+            9 => Ok(LogRecordType::FreePages),
+            10 => Ok(LogRecordType::RowChange),
             _ => Err(WalError::Corrupt(format!(
                 "invalid log record type {}",
                 value
@@ -85,6 +130,41 @@ pub enum LogPayload {
         after: Vec<u8>,
         undo_next_lsn: Option<Lsn>,
     },
+    Catalog {
+        snapshot: Vec<u8>,
+    },
+    FreePages {
+        page_ids: Vec<PageId>,
+    },
+    RowChange {
+        change: Vec<u8>,
+    },
+    /// State at `begin_lsn`, when the checkpoint started: the transactions
+    /// that had not ended and the pages whose latest changes may not be on
+    /// disk. Records from `begin_lsn` on bring both up to date.
+    Checkpoint {
+        begin_lsn: Lsn,
+        active_txns: Vec<ActiveTxn>,
+        dirty_pages: Vec<DirtyPage>,
+    },
+}
+
+/// A transaction that had begun but not ended when a checkpoint was taken.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ActiveTxn {
+    pub txn_id: TxnId,
+    pub first_lsn: Lsn,
+    pub last_lsn: Lsn,
+    /// Committed but waiting for its END record; recovery must not undo it.
+    pub committed: bool,
+}
+
+/// A page changed in memory but not yet written back. `rec_lsn` is no later
+/// than the first change since it was last written, so redo starts there.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DirtyPage {
+    pub page_id: PageId,
+    pub rec_lsn: Lsn,
 }
 
 #[derive(Debug, Clone)]
@@ -137,13 +217,69 @@ impl LogRecord {
         }
     }
 
-    pub fn checkpoint(lsn: Lsn, prev_lsn: Option<Lsn>) -> Self {
+    pub fn checkpoint(
+        lsn: Lsn,
+        begin_lsn: Lsn,
+        active_txns: Vec<ActiveTxn>,
+        dirty_pages: Vec<DirtyPage>,
+    ) -> Self {
         Self {
             lsn,
             txn_id: 0,
-            prev_lsn,
+            prev_lsn: None,
             record_type: LogRecordType::Checkpoint,
-            payload: LogPayload::None,
+            payload: LogPayload::Checkpoint {
+                begin_lsn,
+                active_txns,
+                dirty_pages,
+            },
+        }
+    }
+
+    /// For a checkpoint, the oldest LSN recovery from it reads: redo starts
+    /// at the earliest dirty page and undo follows each open transaction
+    /// back to its first record.
+    pub fn oldest_required_lsn(&self) -> Option<Lsn> {
+        let LogPayload::Checkpoint {
+            begin_lsn,
+            active_txns,
+            dirty_pages,
+        } = &self.payload
+        else {
+            return None;
+        };
+        let first_lsns = active_txns.iter().map(|txn| txn.first_lsn);
+        let rec_lsns = dirty_pages.iter().map(|page| page.rec_lsn);
+        first_lsns.chain(rec_lsns).chain([*begin_lsn]).min()
+    }
+
+    pub fn catalog(lsn: Lsn, snapshot: Vec<u8>) -> Self {
+        Self {
+            lsn,
+            txn_id: 0,
+            prev_lsn: None,
+            record_type: LogRecordType::Catalog,
+            payload: LogPayload::Catalog { snapshot },
+        }
+    }
+
+    pub fn free_pages(lsn: Lsn, page_ids: Vec<PageId>) -> Self {
+        Self {
+            lsn,
+            txn_id: 0,
+            prev_lsn: None,
+            record_type: LogRecordType::FreePages,
+            payload: LogPayload::FreePages { page_ids },
+        }
+    }
+
+    pub fn row_change(lsn: Lsn, txn_id: TxnId, prev_lsn: Option<Lsn>, change: Vec<u8>) -> Self {
+        Self {
+            lsn,
+            txn_id,
+            prev_lsn,
+            record_type: LogRecordType::RowChange,
+            payload: LogPayload::RowChange { change },
         }
     }
 
@@ -194,8 +330,8 @@ impl LogRecord {
     }
 
     pub fn to_bytes(&self) -> Vec<u8> {
-        let mut buffer = Vec::new();
-        buffer.extend_from_slice(&0u32.to_le_bytes());
+        let mut buffer = vec![0u8; RECORD_PREFIX_LEN];
+        buffer.push(LOG_FORMAT_VERSION);
         buffer.push(self.record_type.to_byte());
         buffer.extend_from_slice(&self.lsn.to_le_bytes());
         buffer.extend_from_slice(&self.txn_id.to_le_bytes());
@@ -229,27 +365,72 @@ impl LogRecord {
                 buffer.extend_from_slice(&undo_next.to_le_bytes());
                 buffer.extend_from_slice(after);
             }
+            LogPayload::Catalog { snapshot } => {
+                buffer.extend_from_slice(&(snapshot.len() as u32).to_le_bytes());
+                buffer.extend_from_slice(snapshot);
+            }
+            LogPayload::RowChange { change } => {
+                buffer.extend_from_slice(&(change.len() as u32).to_le_bytes());
+                buffer.extend_from_slice(change);
+            }
+            LogPayload::FreePages { page_ids } => {
+                buffer.extend_from_slice(&(page_ids.len() as u32).to_le_bytes());
+                for page_id in page_ids {
+                    buffer.extend_from_slice(&page_id.to_le_bytes());
+                }
+            }
+            LogPayload::Checkpoint {
+                begin_lsn,
+                active_txns,
+                dirty_pages,
+            } => {
+                buffer.extend_from_slice(&begin_lsn.to_le_bytes());
+                buffer.extend_from_slice(&(active_txns.len() as u32).to_le_bytes());
+                for txn in active_txns {
+                    buffer.extend_from_slice(&txn.txn_id.to_le_bytes());
+                    buffer.extend_from_slice(&txn.first_lsn.to_le_bytes());
+                    buffer.extend_from_slice(&txn.last_lsn.to_le_bytes());
+                    buffer.push(u8::from(txn.committed));
+                }
+                buffer.extend_from_slice(&(dirty_pages.len() as u32).to_le_bytes());
+                for page in dirty_pages {
+                    buffer.extend_from_slice(&page.page_id.to_le_bytes());
+                    buffer.extend_from_slice(&page.rec_lsn.to_le_bytes());
+                }
+            }
         }
         let len = buffer.len() as u32;
         buffer[0..4].copy_from_slice(&len.to_le_bytes());
+        let checksum = crc32fast::hash(&buffer[RECORD_PREFIX_LEN..]);
+        buffer[4..8].copy_from_slice(&checksum.to_le_bytes());
         buffer
     }
 
     pub fn from_bytes(bytes: &[u8]) -> WalResult<Self> {
-        if bytes.len() < 4 + 1 + 8 + 8 + 8 {
+        if bytes.len() < RECORD_PREFIX_LEN + 1 + 1 + 8 + 8 + 8 {
             return Err(WalError::Corrupt("log record too small".to_string()));
         }
-        let _len = u32::from_le_bytes(bytes[0..4].try_into().unwrap());
-        let record_type = LogRecordType::from_byte(bytes[4])?;
-        let lsn = read_u64(&bytes[5..13]);
-        let txn_id = read_u64(&bytes[13..21]);
-        let prev_raw = read_u64(&bytes[21..29]);
+        if !checksum_matches(bytes) {
+            return Err(WalError::Corrupt(
+                "log record checksum mismatch".to_string(),
+            ));
+        }
+        if bytes[8] != LOG_FORMAT_VERSION {
+            return Err(WalError::Corrupt(format!(
+                "unsupported log format version {}",
+                bytes[8]
+            )));
+        }
+        let record_type = LogRecordType::from_byte(bytes[9])?;
+        let lsn = read_u64(&bytes[10..18]);
+        let txn_id = read_u64(&bytes[18..26]);
+        let prev_raw = read_u64(&bytes[26..34]);
         let prev_lsn = if prev_raw == INVALID_LSN {
             None
         } else {
             Some(prev_raw)
         };
-        let mut offset = 29;
+        let mut offset = 34;
         let payload = match record_type {
             LogRecordType::PageUpdate => {
                 if bytes.len() < offset + 8 + 4 + 4 + 4 {
@@ -310,6 +491,87 @@ impl LogRecord {
                     undo_next_lsn,
                 }
             }
+            LogRecordType::Catalog => {
+                if bytes.len() < offset + 4 {
+                    return Err(WalError::Corrupt("catalog record truncated".to_string()));
+                }
+                let snapshot_len = read_u32(&bytes[offset..offset + 4]) as usize;
+                offset += 4;
+                if bytes.len() < offset + snapshot_len {
+                    return Err(WalError::Corrupt("catalog bytes truncated".to_string()));
+                }
+                LogPayload::Catalog {
+                    snapshot: bytes[offset..offset + snapshot_len].to_vec(),
+                }
+            }
+            LogRecordType::RowChange => {
+                if bytes.len() < offset + 4 {
+                    return Err(WalError::Corrupt("row change record truncated".to_string()));
+                }
+                let change_len = read_u32(&bytes[offset..offset + 4]) as usize;
+                offset += 4;
+                if bytes.len() < offset + change_len {
+                    return Err(WalError::Corrupt("row change bytes truncated".to_string()));
+                }
+                LogPayload::RowChange {
+                    change: bytes[offset..offset + change_len].to_vec(),
+                }
+            }
+            LogRecordType::FreePages => {
+                if bytes.len() < offset + 4 {
+                    return Err(WalError::Corrupt("free pages record truncated".to_string()));
+                }
+                let count = read_u32(&bytes[offset..offset + 4]) as usize;
+                offset += 4;
+                if bytes.len() < offset + count * 8 {
+                    return Err(WalError::Corrupt("free pages ids truncated".to_string()));
+                }
+                let page_ids = bytes[offset..offset + count * 8]
+                    .chunks_exact(8)
+                    .map(read_u64)
+                    .collect();
+                LogPayload::FreePages { page_ids }
+            }
+            LogRecordType::Checkpoint => {
+                let truncated = || WalError::Corrupt("checkpoint record truncated".to_string());
+                if bytes.len() < offset + 8 + 4 {
+                    return Err(truncated());
+                }
+                let begin_lsn = read_u64(&bytes[offset..offset + 8]);
+                offset += 8;
+                let txn_count = read_u32(&bytes[offset..offset + 4]) as usize;
+                offset += 4;
+                if bytes.len() < offset + txn_count * 25 + 4 {
+                    return Err(truncated());
+                }
+                let active_txns = bytes[offset..offset + txn_count * 25]
+                    .chunks_exact(25)
+                    .map(|entry| ActiveTxn {
+                        txn_id: read_u64(&entry[0..8]),
+                        first_lsn: read_u64(&entry[8..16]),
+                        last_lsn: read_u64(&entry[16..24]),
+                        committed: entry[24] != 0,
+                    })
+                    .collect();
+                offset += txn_count * 25;
+                let page_count = read_u32(&bytes[offset..offset + 4]) as usize;
+                offset += 4;
+                if bytes.len() < offset + page_count * 16 {
+                    return Err(truncated());
+                }
+                let dirty_pages = bytes[offset..offset + page_count * 16]
+                    .chunks_exact(16)
+                    .map(|entry| DirtyPage {
+                        page_id: read_u64(&entry[0..8]),
+                        rec_lsn: read_u64(&entry[8..16]),
+                    })
+                    .collect();
+                LogPayload::Checkpoint {
+                    begin_lsn,
+                    active_txns,
+                    dirty_pages,
+                }
+            }
             _ => LogPayload::None,
         };
         Ok(LogRecord {
@@ -326,15 +588,171 @@ impl LogRecord {
 pub struct Transaction {
     pub txn_id: TxnId,
     pub last_lsn: Option<Lsn>,
+    /// Read-only transactions never write, so they log nothing.
+    pub read_only: bool,
+    /// Set for a snapshot (`REPEATABLE READ`) transaction, whose reads see
+    /// only the transactions committed before it began. Without one, reads
+    /// lock the rows they read and see the latest committed rows.
+    pub snapshot: Option<Snapshot>,
+}
+
+/// The transactions whose writes a snapshot transaction sees: those that
+/// had committed when the snapshot was taken, and its own.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    pub txn_id: TxnId,
+    /// The first transaction id not yet handed out when the snapshot was
+    /// taken.
+    pub next_txn_id: TxnId,
+    /// Transactions running when the snapshot was taken.
+    pub running: BTreeSet<TxnId>,
+}
+
+impl Snapshot {
+    /// Whether the changes of `writer` are visible to the snapshot.
+    pub fn sees(&self, writer: TxnId) -> bool {
+        writer == self.txn_id || (writer < self.next_txn_id && !self.running.contains(&writer))
+    }
+
+    /// The oldest transaction the snapshot does not see, apart from those
+    /// begun after it.
+    fn horizon(&self) -> TxnId {
+        self.running
+            .first()
+            .copied()
+            .unwrap_or(self.next_txn_id)
+            .min(self.next_txn_id)
+    }
+}
+
+/// Transactions that have begun and not yet finished, and the horizons of
+/// the running snapshot transactions.
+#[derive(Debug)]
+struct RunningTxns {
+    next_txn_id: TxnId,
+    running: BTreeSet<TxnId>,
+    /// Horizon of each running snapshot transaction.
+    horizons: BTreeMap<TxnId, TxnId>,
+    /// Running transactions that changed rows while no snapshot was open,
+    /// and so kept no earlier images of them.
+    unversioned: BTreeSet<TxnId>,
+}
+
+impl Default for RunningTxns {
+    fn default() -> Self {
+        Self {
+            next_txn_id: 1,
+            running: BTreeSet::new(),
+            horizons: BTreeMap::new(),
+            unversioned: BTreeSet::new(),
+        }
+    }
+}
+
+impl RunningTxns {
+    fn begin(&mut self) -> TxnId {
+        let txn_id = self.next_txn_id;
+        self.next_txn_id += 1;
+        self.running.insert(txn_id);
+        txn_id
+    }
+
+    fn finish(&mut self, txn_id: TxnId) {
+        self.running.remove(&txn_id);
+        self.horizons.remove(&txn_id);
+        self.unversioned.remove(&txn_id);
+    }
+
+    /// Whether `writer` must keep the earlier image of a row it is about to
+    /// change: only while some snapshot is open. Otherwise `writer` is noted
+    /// as unversioned until it finishes.
+    fn keeps_versions(&mut self, writer: TxnId) -> bool {
+        if self.horizons.is_empty() {
+            self.unversioned.insert(writer);
+            return false;
+        }
+        true
+    }
+
+    /// Whether every snapshot, running or yet to be taken, sees `writer`.
+    fn seen_by_every_snapshot(&self, writer: TxnId) -> bool {
+        !self.running.contains(&writer) && self.horizons.values().all(|&horizon| writer < horizon)
+    }
 }
 
 pub type TransactionHandle = Arc<Mutex<Transaction>>;
 
+/// Transactions begun and finished since the transaction manager was
+/// created, read-only ones included. Durations run from `begin` to commit or
+/// abort; the percentiles cover the most recent transactions only.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct TxnStats {
+    pub begun: u64,
+    pub committed: u64,
+    pub aborted: u64,
+    /// Aborts of transactions that gave up waiting for a lock; these are
+    /// also counted in `aborted`.
+    pub deadlock_aborted: u64,
+    pub avg_duration: Duration,
+    pub p50_duration: Duration,
+    pub p99_duration: Duration,
+}
+
+#[derive(Debug, Default)]
+struct TxnStatsState {
+    stats: TxnStats,
+    started: HashMap<TxnId, Instant>,
+    timed: u64,
+    total_duration: Duration,
+    recent: VecDeque<Duration>,
+}
+
+impl TxnStatsState {
+    fn begin(&mut self, txn_id: TxnId) {
+        self.stats.begun += 1;
+        self.started.insert(txn_id, Instant::now());
+    }
+
+    fn finish(&mut self, txn_id: TxnId) {
+        let Some(started) = self.started.remove(&txn_id) else {
+            return;
+        };
+        let duration = started.elapsed();
+        self.timed += 1;
+        self.total_duration += duration;
+        if self.recent.len() == RECENT_DURATIONS {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(duration);
+    }
+
+    fn snapshot(&self) -> TxnStats {
+        let mut recent = self.recent.iter().copied().collect::<Vec<_>>();
+        recent.sort();
+        let percentile = |p: usize| {
+            recent
+                .get((recent.len() * p / 100).min(recent.len().saturating_sub(1)))
+                .copied()
+                .unwrap_or_default()
+        };
+        TxnStats {
+            avg_duration: match self.timed {
+                0 => Duration::ZERO,
+                n => Duration::from_nanos((self.total_duration.as_nanos() / n as u128) as u64),
+            },
+            p50_duration: percentile(50),
+            p99_duration: percentile(99),
+            ..self.stats
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct TransactionManager {
     log_manager: Arc<LogManager>,
     lock_manager: Option<Arc<LockManager>>,
-    next_txn_id: Arc<AtomicU64>,
+    running: Arc<Mutex<RunningTxns>>,
+    stats: Arc<Mutex<TxnStatsState>>,
 }
 
 impl TransactionManager {
@@ -342,7 +760,8 @@ impl TransactionManager {
         Self {
             log_manager,
             lock_manager: None,
-            next_txn_id: Arc::new(AtomicU64::new(1)),
+            running: Arc::default(),
+            stats: Arc::default(),
         }
     }
 
@@ -350,7 +769,8 @@ impl TransactionManager {
         Self {
             log_manager,
             lock_manager: Some(lock_manager),
-            next_txn_id: Arc::new(AtomicU64::new(1)),
+            running: Arc::default(),
+            stats: Arc::default(),
         }
     }
 
@@ -362,18 +782,85 @@ impl TransactionManager {
         self.lock_manager.clone()
     }
 
+    pub fn stats(&self) -> TxnStats {
+        self.stats.lock().snapshot()
+    }
+
     pub fn begin(&self) -> WalResult<TransactionHandle> {
-        let txn_id = self.next_txn_id.fetch_add(1, Ordering::SeqCst);
+        let txn_id = self.running.lock().begin();
         let mut txn = Transaction {
             txn_id,
             last_lsn: None,
+            read_only: false,
+            snapshot: None,
+        };
+        let lsn = match self.log_manager.append(LogRecord::begin(0, txn_id, None)) {
+            Ok(lsn) => lsn,
+            Err(error) => {
+                self.running.lock().finish(txn_id);
+                return Err(error);
+            }
         };
-        let lsn = self.log_manager.append(LogRecord::begin(0, txn_id, None))?;
         txn.last_lsn = Some(lsn);
+        self.stats.lock().begin(txn_id);
         Ok(Arc::new(Mutex::new(txn)))
     }
 
+    /// Begins a transaction that will only read. No BEGIN, COMMIT or ABORT
+    /// records are written for it and committing does not flush the log.
+    pub fn begin_read_only(&self) -> TransactionHandle {
+        let txn_id = self.running.lock().begin();
+        self.stats.lock().begin(txn_id);
+        Arc::new(Mutex::new(Transaction {
+            txn_id,
+            last_lsn: None,
+            read_only: true,
+            snapshot: None,
+        }))
+    }
+
+    /// Makes `txn` a snapshot transaction: from now on its reads see the
+    /// transactions committed so far and its own writes, and take no locks.
+    /// Rows keep earlier images only while a snapshot is open, so while a
+    /// transaction that changed rows without them is still running no
+    /// snapshot can leave its changes out; `txn` is then left a locking
+    /// transaction, whose reads wait for writers instead.
+    pub fn take_snapshot(&self, txn: &TransactionHandle) {
+        let txn_id = txn.lock().txn_id;
+        let snapshot = {
+            let mut running = self.running.lock();
+            if running.unversioned.iter().any(|&writer| writer != txn_id) {
+                return;
+            }
+            let snapshot = Snapshot {
+                txn_id,
+                next_txn_id: running.next_txn_id,
+                running: running
+                    .running
+                    .iter()
+                    .copied()
+                    .filter(|&running| running != txn_id)
+                    .collect(),
+            };
+            running.horizons.insert(txn_id, snapshot.horizon());
+            snapshot
+        };
+        txn.lock().snapshot = Some(snapshot);
+    }
+
+    /// Records that the changes of an aborted transaction have been undone,
+    /// so snapshots taken from now on no longer wait to see them.
+    pub fn finish_rollback(&self, txn: &TransactionHandle) {
+        self.running.lock().finish(txn.lock().txn_id);
+    }
+
     pub fn commit(&self, txn: &TransactionHandle) -> WalResult<()> {
+        if txn.lock().read_only {
+            self.release_locks(txn);
+            self.record_commit(txn);
+            self.finish_rollback(txn);
+            return Ok(());
+        }
         let txn_id = txn.lock().txn_id;
         let mut guard = txn.lock();
         let lsn = self
@@ -381,21 +868,43 @@ impl TransactionManager {
             .append(LogRecord::commit(0, guard.txn_id, guard.last_lsn))?;
         guard.last_lsn = Some(lsn);
         drop(guard);
-        self.log_manager.flush(lsn)?;
+        // a commit record left unwritten by a full disk is written ahead of
+        // anything logged after it, so the transaction has committed and
+        // only its durability is unconfirmed; its locks must not outlive it
+        if let Err(error) = self.log_manager.flush(lsn) {
+            if matches!(error, WalError::DiskFull(_)) {
+                self.release_locks(txn);
+                self.record_commit(txn);
+                self.running.lock().finish(txn_id);
+            }
+            return Err(error);
+        }
         let mut guard = txn.lock();
         let end_lsn = self
             .log_manager
             .append(LogRecord::end(0, guard.txn_id, guard.last_lsn))?;
         guard.last_lsn = Some(end_lsn);
+        // the commit is durable, so snapshots taken from now on see it
+        self.running.lock().finish(txn_id);
         self.log_manager.flush(end_lsn)?;
         drop(guard);
         if let Some(lock_manager) = &self.lock_manager {
             lock_manager.unlock_all(txn::TxnId(txn_id));
         }
+        self.record_commit(txn);
         Ok(())
     }
 
+    /// Logs the abort and releases the locks of `txn`. Until
+    /// [`finish_rollback`](Self::finish_rollback) it still counts as
+    /// running, so snapshots do not see the changes being undone.
     pub fn abort(&self, txn: &TransactionHandle) -> WalResult<()> {
+        self.record_abort(txn);
+        if txn.lock().read_only {
+            self.release_locks(txn);
+            self.finish_rollback(txn);
+            return Ok(());
+        }
         let txn_id = txn.lock().txn_id;
         let mut guard = txn.lock();
         let lsn = self
@@ -417,18 +926,52 @@ impl TransactionManager {
             .append(LogRecord::end(0, guard.txn_id, guard.last_lsn))?;
         guard.last_lsn = Some(lsn);
         drop(guard);
+        self.running.lock().finish(txn_id);
         if let Some(lock_manager) = &self.lock_manager {
             lock_manager.unlock_all(txn::TxnId(txn_id));
         }
         Ok(())
     }
 
+    fn record_commit(&self, txn: &TransactionHandle) {
+        let txn_id = txn.lock().txn_id;
+        let mut stats = self.stats.lock();
+        stats.stats.committed += 1;
+        stats.finish(txn_id);
+    }
+
+    /// Counts an abort; called before the locks are released so a lock
+    /// timeout can still be seen.
+    fn record_abort(&self, txn: &TransactionHandle) {
+        let txn_id = txn.lock().txn_id;
+        let timed_out = self
+            .lock_manager
+            .as_ref()
+            .is_some_and(|lock_manager| lock_manager.timed_out(txn::TxnId(txn_id)));
+        let mut stats = self.stats.lock();
+        stats.stats.aborted += 1;
+        if timed_out {
+            stats.stats.deadlock_aborted += 1;
+        }
+        stats.finish(txn_id);
+    }
+
+    fn release_locks(&self, txn: &TransactionHandle) {
+        if let Some(lock_manager) = &self.lock_manager {
+            lock_manager.unlock_all(txn::TxnId(txn.lock().txn_id));
+        }
+    }
+
     pub fn with_transaction<F, R>(&self, txn: &TransactionHandle, f: F) -> R
     where
         F: FnOnce() -> R,
     {
-        let guard =
-            set_transaction_context(self.log_manager(), Arc::clone(txn), self.lock_manager());
+        let guard = set_transaction_context(TransactionContext {
+            log_manager: self.log_manager(),
+            transaction: Arc::clone(txn),
+            lock_manager: self.lock_manager(),
+            running: Arc::clone(&self.running),
+        });
         let result = f();
         drop(guard);
         result
@@ -444,24 +987,15 @@ struct TransactionContext {
     log_manager: Arc<LogManager>,
     transaction: TransactionHandle,
     lock_manager: Option<Arc<LockManager>>,
+    running: Arc<Mutex<RunningTxns>>,
 }
 
 thread_local! {
     static CURRENT_TXN: RefCell<Option<TransactionContext>> = const { RefCell::new(None) };
 }
 
-fn set_transaction_context(
-    log_manager: Arc<LogManager>,
-    transaction: TransactionHandle,
-    lock_manager: Option<Arc<LockManager>>,
-) -> TransactionGuard {
-    let previous = CURRENT_TXN.with(|cell| {
-        cell.replace(Some(TransactionContext {
-            log_manager,
-            transaction,
-            lock_manager,
-        }))
-    });
+fn set_transaction_context(context: TransactionContext) -> TransactionGuard {
+    let previous = CURRENT_TXN.with(|cell| cell.replace(Some(context)));
     TransactionGuard { previous }
 }
 
@@ -481,6 +1015,58 @@ pub fn current_txn_handle() -> Option<TransactionHandle> {
     })
 }
 
+/// True when the statement runs inside a read-only transaction.
+pub fn current_txn_read_only() -> bool {
+    CURRENT_TXN.with(|cell| {
+        cell.borrow()
+            .as_ref()
+            .is_some_and(|ctx| ctx.transaction.lock().read_only)
+    })
+}
+
+/// The snapshot of the current transaction, if it is a snapshot
+/// transaction.
+pub fn current_snapshot() -> Option<Snapshot> {
+    CURRENT_TXN.with(|cell| {
+        cell.borrow()
+            .as_ref()
+            .and_then(|ctx| ctx.transaction.lock().snapshot.clone())
+    })
+}
+
+/// True when the statement runs inside a snapshot transaction.
+pub fn current_txn_has_snapshot() -> bool {
+    CURRENT_TXN.with(|cell| {
+        cell.borrow()
+            .as_ref()
+            .is_some_and(|ctx| ctx.transaction.lock().snapshot.is_some())
+    })
+}
+
+/// Whether the current transaction must keep the earlier image of a row it
+/// is about to change, for the snapshots that do not see the change. True
+/// only while a snapshot transaction is running; false outside a
+/// transaction.
+pub fn keeps_versions() -> bool {
+    CURRENT_TXN.with(|cell| {
+        cell.borrow().as_ref().is_some_and(|ctx| {
+            let writer = ctx.transaction.lock().txn_id;
+            ctx.running.lock().keeps_versions(writer)
+        })
+    })
+}
+
+/// Whether every snapshot of the current transaction's manager, running
+/// or yet to be taken, sees the changes of `writer`. False outside a
+/// transaction.
+pub fn seen_by_every_snapshot(writer: TxnId) -> bool {
+    CURRENT_TXN.with(|cell| {
+        cell.borrow()
+            .as_ref()
+            .is_some_and(|ctx| ctx.running.lock().seen_by_every_snapshot(writer))
+    })
+}
+
 pub fn current_lock_manager() -> Option<Arc<LockManager>> {
     CURRENT_TXN.with(|cell| {
         cell.borrow()
@@ -525,6 +1111,22 @@ pub fn log_page_update(
     })
 }
 
+/// Logs a row change made by the current transaction. Like page updates it
+/// is dropped outside a transaction.
+pub fn log_row_change(change: Vec<u8>) -> WalResult<Option<Lsn>> {
+    CURRENT_TXN.with(|cell| {
+        let mut context = cell.borrow_mut();
+        let Some(context) = context.as_mut() else {
+            return Ok(None);
+        };
+        let mut txn_guard = context.transaction.lock();
+        let record = LogRecord::row_change(0, txn_guard.txn_id, txn_guard.last_lsn, change);
+        let lsn = context.log_manager.append(record)?;
+        txn_guard.last_lsn = Some(lsn);
+        Ok(Some(lsn))
+    })
+}
+
 pub fn log_compensation(
     txn: &TransactionHandle,
     log_manager: &LogManager,
@@ -554,8 +1156,32 @@ pub struct LogManager {
     condvar: Arc<Condvar>,
     sender: mpsc::Sender<FlushRequest>,
     path: PathBuf,
+    /// Held by checkpoints and truncation, never by `append`.
+    checkpoints: Arc<Mutex<CheckpointState>>,
+}
+
+/// Group commit: while another transaction is still running, the flush
+/// thread holds the first request of a batch for up to `window`, or until
+/// the batch reaches `max_batch_bytes`, so that the commits arriving
+/// meanwhile are written with it and share one sync. With no other
+/// transaction to wait for, a lone commit is written at once.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GroupCommit {
+    pub window: Duration,
+    pub max_batch_bytes: usize,
 }
 
+impl Default for GroupCommit {
+    fn default() -> Self {
+        Self {
+            window: Duration::from_millis(1),
+            max_batch_bytes: DEFAULT_LOG_BUFFER_SIZE,
+        }
+    }
+}
+
+/// Bytes handed to the flush thread. Requests are sent in LSN order, so
+/// each one starts where the previous one ended.
 struct FlushRequest {
     start_lsn: Lsn,
     end_lsn: Lsn,
@@ -564,14 +1190,29 @@ struct FlushRequest {
 
 struct LogState {
     active: Vec<u8>,
-    flushing: Vec<u8>,
+    /// LSN of the first byte in `active`; everything before it has been
+    /// handed to the flush thread.
     active_start_lsn: Lsn,
     next_lsn: Lsn,
     flushed_lsn: Lsn,
-    flushing_in_progress: bool,
     buffer_size: usize,
     last_error: Option<WalError>,
-    requested_flush_to: Lsn,
+    /// Set when the flush thread's last write found the disk full; it keeps
+    /// the bytes and writes them ahead of the next request.
+    disk_full: Option<String>,
+    group_commit: GroupCommit,
+    /// Syncs of log files by the flush thread since the log was opened.
+    syncs: u64,
+    /// Transactions begun since the log was opened that have not ended.
+    /// Those begun earlier are finished by recovery before anything else
+    /// is logged.
+    active_txns: HashMap<TxnId, ActiveTxn>,
+}
+
+struct CheckpointState {
+    master: MasterRecord,
+    /// Oldest LSN that recovery from the latest checkpoint reads.
+    oldest_required_lsn: Option<Lsn>,
 }
 
 impl LogManager {
@@ -581,40 +1222,82 @@ impl LogManager {
 
     pub fn open_with_buffer(path: impl AsRef<Path>, buffer_size: usize) -> WalResult<Self> {
         let path_ref = path.as_ref().to_path_buf();
-        let mut file = OpenOptions::new()
-            .create(true)
-            .read(true)
-            .write(true)
-            .truncate(false)
-            .open(&path_ref)?;
-        let len = file.metadata()?.len();
-        file.seek(SeekFrom::End(0))?;
+        let mut master = MasterRecord::read(&path_ref)?;
+        let mut files = LogFiles::writer(&path_ref);
+        files.segment(master.start_lsn / SEGMENT_SIZE)?;
+        // drop a record torn by a crash mid-write, so appended records
+        // follow the last complete one
+        let mut reader = LogReader::open(&path_ref)?;
+        let mut oldest_required_lsn = None;
+        while let Some(record) = reader.next_record()? {
+            if Some(record.lsn) == master.checkpoint_lsn {
+                oldest_required_lsn = record.oldest_required_lsn();
+            }
+        }
+        let len = reader.offset();
+        if len < files.end_lsn(len)? {
+            files.truncate_at(len)?;
+        }
+        // the checkpoint is gone if the log was cut short before it
+        if master.checkpoint_lsn.is_some() && oldest_required_lsn.is_none() {
+            master.checkpoint_lsn = None;
+            master.write(&path_ref)?;
+        }
         let state = Arc::new(Mutex::new(LogState {
             active: Vec::with_capacity(buffer_size),
-            flushing: Vec::with_capacity(buffer_size),
             active_start_lsn: len,
             next_lsn: len,
             flushed_lsn: len,
-            flushing_in_progress: false,
             buffer_size,
             last_error: None,
-            requested_flush_to: len,
+            disk_full: None,
+            group_commit: GroupCommit::default(),
+            syncs: 0,
+            active_txns: HashMap::new(),
         }));
         let condvar = Arc::new(Condvar::new());
-        let (sender, receiver) = mpsc::channel();
+        let (sender, receiver) = mpsc::channel::<FlushRequest>();
         let state_clone = Arc::clone(&state);
         let condvar_clone = Arc::clone(&condvar);
         std::thread::spawn(move || {
-            for request in receiver {
-                let result = write_flush_request(&mut file, &request);
-                let mut state = state_clone.lock();
-                if let Err(error) = result {
-                    state.last_error = Some(error);
+            // requests a full disk left unwritten, retried in order
+            let mut unwritten: Vec<FlushRequest> = Vec::new();
+            while let Ok(first) = receiver.recv() {
+                let (group_commit, others_running) = {
+                    let state = state_clone.lock();
+                    let running = state.active_txns.values().any(|txn| !txn.committed);
+                    (state.group_commit, running)
+                };
+                let window = if others_running {
+                    group_commit.window
                 } else {
-                    state.flushed_lsn = state.flushed_lsn.max(request.end_lsn);
+                    Duration::ZERO
+                };
+                let deadline = Instant::now() + window;
+                let mut batch_bytes = first.bytes.len();
+                let mut batch = vec![first];
+                while batch_bytes < group_commit.max_batch_bytes {
+                    let wait = deadline.saturating_duration_since(Instant::now());
+                    let Ok(request) = receiver.recv_timeout(wait) else {
+                        break;
+                    };
+                    batch_bytes += request.bytes.len();
+                    batch.push(request);
+                }
+                unwritten.extend(batch);
+                let end_lsn = unwritten[unwritten.len() - 1].end_lsn;
+                let result = write_flush_requests(&mut files, &unwritten);
+                let mut state = state_clone.lock();
+                match result {
+                    Ok(syncs) => {
+                        unwritten.clear();
+                        state.flushed_lsn = state.flushed_lsn.max(end_lsn);
+                        state.syncs += syncs;
+                        state.disk_full = None;
+                    }
+                    Err(WalError::DiskFull(message)) => state.disk_full = Some(message),
+                    Err(error) => state.last_error = Some(error),
                 }
-                state.flushing.clear();
-                state.flushing_in_progress = false;
                 condvar_clone.notify_all();
             }
         });
@@ -623,6 +1306,10 @@ impl LogManager {
             condvar,
             sender,
             path: path_ref,
+            checkpoints: Arc::new(Mutex::new(CheckpointState {
+                master,
+                oldest_required_lsn,
+            })),
         })
     }
 
@@ -637,47 +1324,25 @@ impl LogManager {
         let lsn = record.lsn;
         state.active.extend_from_slice(&bytes);
         state.next_lsn += bytes.len() as u64;
+        state.track(&record);
         Ok(lsn)
     }
-
+    /// Blocks until the record starting at `lsn` is durable. `flushed_lsn` is
+    /// the end offset of flushed bytes, so it must move past `lsn`. Callers
+    /// flushing at the same time wait for the same sync; see
+    /// [`GroupCommit`].
     pub fn flush(&self, lsn: Lsn) -> WalResult<()> {
         let mut state = self.state.lock();
         state.ensure_ok()?;
-        if lsn <= state.flushed_lsn {
-            return Ok(());
-        }
-        state.requested_flush_to = lsn;
-        if lsn >= state.active_start_lsn && !state.active.is_empty() && !state.flushing_in_progress
-        {
+        if lsn >= state.active_start_lsn || state.disk_full.is_some() {
             self.flush_active_locked(&mut state)?;
         }
-        while state.flushed_lsn < lsn {
-            let flushed_before = state.flushed_lsn;
-            let active_start = state.active_start_lsn;
-            let flushing_in_progress = state.flushing_in_progress;
-            let active_empty = state.active.is_empty();
-            drop(state);
-            std::thread::sleep(Duration::from_millis(2));
-            state = self.state.lock();
-            if state.flushed_lsn >= lsn {
-                return Ok(());
-            }
-            if state.flushed_lsn > flushed_before {
-                continue;
-            }
-            if !flushing_in_progress && active_empty && state.flushed_lsn >= active_start {
-                return Ok(());
-            }
-            if lsn >= state.active_start_lsn
-                && !state.active.is_empty()
-                && !state.flushing_in_progress
-            {
-                self.flush_active_locked(&mut state)?;
-            }
-            state.ensure_ok()?;
+        // bytes past the end of the log are never written
+        let target = (lsn + 1).min(state.active_start_lsn);
+        while state.flushed_lsn < target {
             self.condvar
                 .wait_for(&mut state, Duration::from_millis(200));
-            state.ensure_ok()?;
+            state.ensure_written()?;
         }
         Ok(())
     }
@@ -685,14 +1350,11 @@ impl LogManager {
     pub fn force_flush(&self) -> WalResult<()> {
         let mut state = self.state.lock();
         state.ensure_ok()?;
-        if !state.active.is_empty() && !state.flushing_in_progress {
-            self.flush_active_locked(&mut state)?;
-        }
-        while state.flushing_in_progress || !state.active.is_empty() {
-            state.ensure_ok()?;
+        self.flush_active_locked(&mut state)?;
+        while state.flushed_lsn < state.active_start_lsn {
             self.condvar
                 .wait_for(&mut state, Duration::from_millis(200));
-            state.ensure_ok()?;
+            state.ensure_written()?;
         }
         Ok(())
     }
@@ -701,28 +1363,38 @@ impl LogManager {
         self.state.lock().flushed_lsn
     }
 
+    pub fn set_group_commit(&self, group_commit: GroupCommit) {
+        self.state.lock().group_commit = group_commit;
+    }
+
+    /// Number of times the log has been synced to disk since it was opened.
+    pub fn sync_count(&self) -> u64 {
+        self.state.lock().syncs
+    }
+
+    /// Hands the active buffer to the flush thread. Waits first while a full
+    /// buffer's worth of earlier bytes is still unwritten. After a write
+    /// found the disk full, the flush thread is woken even with nothing new
+    /// to write, so it tries the bytes it kept again.
     fn flush_active_locked(
         &self,
         state: &mut parking_lot::MutexGuard<'_, LogState>,
     ) -> WalResult<()> {
-        if state.active.is_empty() {
+        let retry = state.disk_full.take().is_some();
+        if state.active.is_empty() && !retry {
             return Ok(());
         }
-        while state.flushing_in_progress {
-            self.condvar.wait(state);
-            state.ensure_ok()?;
+        while !state.active.is_empty()
+            && state.active_start_lsn.saturating_sub(state.flushed_lsn) >= state.buffer_size as u64
+        {
+            self.condvar.wait_for(state, Duration::from_millis(200));
+            state.ensure_written()?;
         }
         let start_lsn = state.active_start_lsn;
-        let mut flush_buffer = std::mem::take(&mut state.active);
-        std::mem::swap(&mut flush_buffer, &mut state.flushing);
-        flush_buffer.clear();
-        state.active = flush_buffer;
-        let end_lsn = start_lsn + state.flushing.len() as u64;
-
+        let bytes = state.active.clone();
+        state.active.clear();
+        let end_lsn = start_lsn + bytes.len() as u64;
         state.active_start_lsn = end_lsn;
-        state.flushing_in_progress = true;
-        let mut bytes = Vec::with_capacity(state.flushing.len());
-        bytes.extend_from_slice(&state.flushing);
         self.sender
             .send(FlushRequest {
                 start_lsn,
@@ -733,13 +1405,70 @@ impl LogManager {
         Ok(())
     }
 
-    pub fn checkpoint(&self) -> WalResult<Lsn> {
-        self.force_flush()?;
-        let lsn = self.append(LogRecord::checkpoint(self.state.lock().next_lsn, None))?;
+    /// Takes a fuzzy checkpoint: the open transactions are copied under the
+    /// state lock, which `append` holds only briefly, and the record is then
+    /// logged like any other while appends go on. `dirty_pages` comes from
+    /// the caller, which owns the pages. Once the record is durable the
+    /// master record points at it, so recovery starts there. Returns the
+    /// checkpoint record's LSN.
+    pub fn checkpoint(&self, dirty_pages: Vec<DirtyPage>) -> WalResult<Lsn> {
+        let mut checkpoints = self.checkpoints.lock();
+        let (begin_lsn, active_txns) = {
+            let state = self.state.lock();
+            state.ensure_ok()?;
+            let active_txns = state.active_txns.values().copied().collect();
+            (state.next_lsn, active_txns)
+        };
+        let record = LogRecord::checkpoint(0, begin_lsn, active_txns, dirty_pages);
+        let oldest_required_lsn = record.oldest_required_lsn();
+        let lsn = self.append(record)?;
         self.flush(lsn)?;
+        let mut master = checkpoints.master;
+        master.checkpoint_lsn = Some(lsn);
+        master.write(&self.path)?;
+        checkpoints.master = master;
+        checkpoints.oldest_required_lsn = oldest_required_lsn;
         Ok(lsn)
     }
 
+    /// Deletes the segment files that lie wholly before `lsn`, but never
+    /// one holding a record that recovery from the latest checkpoint still
+    /// reads; without a checkpoint nothing is deleted. Readers then start at
+    /// the first record in the oldest segment kept. Returns that LSN.
+    pub fn truncate_before(&self, lsn: Lsn) -> WalResult<Lsn> {
+        let mut checkpoints = self.checkpoints.lock();
+        let Some(oldest_required_lsn) = checkpoints.oldest_required_lsn else {
+            return Ok(checkpoints.master.start_lsn);
+        };
+        let keep_from = lsn.min(oldest_required_lsn) / SEGMENT_SIZE * SEGMENT_SIZE;
+        if keep_from <= checkpoints.master.start_lsn {
+            return Ok(checkpoints.master.start_lsn);
+        }
+        // a record may straddle the segment boundary, so find the first one
+        // that starts after it
+        let mut reader = LogReader::open(&self.path)?;
+        let mut start_lsn = oldest_required_lsn;
+        while let Some(record) = reader.next_record()? {
+            if record.lsn >= keep_from {
+                start_lsn = record.lsn;
+                break;
+            }
+        }
+        // readers must stop starting in the discarded segments before they
+        // are deleted
+        let mut master = checkpoints.master;
+        master.start_lsn = start_lsn;
+        master.write(&self.path)?;
+        checkpoints.master = master;
+        LogFiles::writer(&self.path).remove_before(start_lsn)?;
+        Ok(start_lsn)
+    }
+
+    /// LSN of the first record still in the log.
+    pub fn start_lsn(&self) -> Lsn {
+        self.checkpoints.lock().master.start_lsn
+    }
+
     pub fn truncate(&self, up_to_lsn: Lsn) -> WalResult<()> {
         let mut state = self.state.lock();
         state.active_start_lsn = up_to_lsn;
@@ -748,22 +1477,7 @@ impl LogManager {
         }
         drop(state);
 
-        if up_to_lsn > 0 {
-            let file = OpenOptions::new()
-                .write(true)
-                .truncate(false)
-                .open(&self.path)?;
-            file.set_len(up_to_lsn)?;
-        } else {
-            let file = OpenOptions::new()
-                .write(true)
-                .truncate(false)
-                .create(true)
-                .open(&self.path)?;
-            file.set_len(0)?;
-        }
-
-        Ok(())
+        LogFiles::writer(&self.path).truncate_at(up_to_lsn)
     }
 
     pub fn active_start_lsn(&self) -> Lsn {
@@ -778,52 +1492,318 @@ impl LogState {
         }
         Ok(())
     }
+
+    /// Like `ensure_ok`, but also fails while the last write found the disk
+    /// full; used when waiting for bytes to be written.
+    fn ensure_written(&self) -> WalResult<()> {
+        self.ensure_ok()?;
+        if let Some(message) = &self.disk_full {
+            return Err(WalError::DiskFull(message.clone()));
+        }
+        Ok(())
+    }
+
+    /// Keeps the active transaction table current with an appended record.
+    fn track(&mut self, record: &LogRecord) {
+        match record.record_type {
+            LogRecordType::Begin => {
+                self.active_txns.insert(
+                    record.txn_id,
+                    ActiveTxn {
+                        txn_id: record.txn_id,
+                        first_lsn: record.lsn,
+                        last_lsn: record.lsn,
+                        committed: false,
+                    },
+                );
+            }
+            LogRecordType::End => {
+                self.active_txns.remove(&record.txn_id);
+            }
+            _ => {
+                if let Some(txn) = self.active_txns.get_mut(&record.txn_id) {
+                    txn.last_lsn = record.lsn;
+                    txn.committed |= record.record_type == LogRecordType::Commit;
+                }
+            }
+        }
+    }
+}
+
+/// Points at the latest complete checkpoint and at the first record kept
+/// after truncation. It is stored beside the log and replaced by rename, so
+/// a crash leaves either the old or the new one.
+#[derive(Debug, Clone, Copy, Default)]
+struct MasterRecord {
+    checkpoint_lsn: Option<Lsn>,
+    start_lsn: Lsn,
+}
+
+impl MasterRecord {
+    fn read(log_path: &Path) -> WalResult<Self> {
+        let bytes = match fs::read(with_suffix(log_path, ".master")) {
+            Ok(bytes) => bytes,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Self::default()),
+            Err(err) => return Err(err.into()),
+        };
+        if bytes.len() != 16 {
+            return Err(WalError::Corrupt("invalid log master record".to_string()));
+        }
+        let checkpoint_lsn = read_u64(&bytes[0..8]);
+        Ok(Self {
+            checkpoint_lsn: (checkpoint_lsn != INVALID_LSN).then_some(checkpoint_lsn),
+            start_lsn: read_u64(&bytes[8..16]),
+        })
+    }
+
+    fn write(&self, log_path: &Path) -> WalResult<()> {
+        let mut bytes = Vec::with_capacity(16);
+        bytes.extend_from_slice(&self.checkpoint_lsn.unwrap_or(INVALID_LSN).to_le_bytes());
+        bytes.extend_from_slice(&self.start_lsn.to_le_bytes());
+        let temp_path = with_suffix(log_path, ".master.tmp");
+        let mut file = File::create(&temp_path)?;
+        file.write_all(&bytes)?;
+        file.sync_all()?;
+        fs::rename(&temp_path, with_suffix(log_path, ".master"))?;
+        Ok(())
+    }
+}
+
+/// The segment files of one log, read and written by LSN. The file last
+/// used stays open.
+struct LogFiles {
+    path: PathBuf,
+    writable: bool,
+    open: Option<(u64, File)>,
+}
+
+impl LogFiles {
+    fn reader(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            writable: false,
+            open: None,
+        }
+    }
+
+    fn writer(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            writable: true,
+            open: None,
+        }
+    }
+
+    /// Opens a segment, creating it first when writing.
+    fn segment(&mut self, segment: u64) -> WalResult<&mut File> {
+        let file = match self.open.take() {
+            Some((open, file)) if open == segment => file,
+            _ => OpenOptions::new()
+                .read(true)
+                .write(self.writable)
+                .create(self.writable)
+                .truncate(false)
+                .open(segment_path(&self.path, segment))?,
+        };
+        Ok(&mut self.open.insert((segment, file)).1)
+    }
+
+    fn read_at(&mut self, mut lsn: Lsn, mut buffer: &mut [u8]) -> WalResult<()> {
+        while !buffer.is_empty() {
+            let within = lsn % SEGMENT_SIZE;
+            let len = buffer.len().min((SEGMENT_SIZE - within) as usize);
+            let (head, rest) = std::mem::take(&mut buffer).split_at_mut(len);
+            let file = self.segment(lsn / SEGMENT_SIZE)?;
+            file.seek(SeekFrom::Start(within))?;
+            file.read_exact(head)?;
+            buffer = rest;
+            lsn += len as u64;
+        }
+        Ok(())
+    }
+
+    /// Writes `bytes` at `lsn` and syncs every segment it touched. Returns
+    /// the number of syncs.
+    fn write_at(&mut self, mut lsn: Lsn, mut bytes: &[u8]) -> WalResult<u64> {
+        let mut syncs = 0;
+        while !bytes.is_empty() {
+            let within = lsn % SEGMENT_SIZE;
+            let len = bytes.len().min((SEGMENT_SIZE - within) as usize);
+            fault::check_space(&segment_path(&self.path, lsn / SEGMENT_SIZE))?;
+            let file = self.segment(lsn / SEGMENT_SIZE)?;
+            file.seek(SeekFrom::Start(within))?;
+            file.write_all(&bytes[..len])?;
+            file.sync_data()?;
+            syncs += 1;
+            bytes = &bytes[len..];
+            lsn += len as u64;
+        }
+        Ok(syncs)
+    }
+
+    /// End of the log, found by following full segments on from the one
+    /// holding `from`.
+    fn end_lsn(&self, from: Lsn) -> WalResult<Lsn> {
+        let mut segment = from / SEGMENT_SIZE;
+        let mut end = from;
+        loop {
+            let len = match fs::metadata(segment_path(&self.path, segment)) {
+                Ok(metadata) => metadata.len().min(SEGMENT_SIZE),
+                Err(err) if err.kind() == ErrorKind::NotFound => return Ok(end),
+                Err(err) => return Err(err.into()),
+            };
+            end = end.max(segment * SEGMENT_SIZE + len);
+            if len < SEGMENT_SIZE {
+                return Ok(end);
+            }
+            segment += 1;
+        }
+    }
+
+    /// Cuts the log off at `lsn`, deleting the segments after it.
+    fn truncate_at(&mut self, lsn: Lsn) -> WalResult<()> {
+        let segment = lsn / SEGMENT_SIZE;
+        let file = self.segment(segment)?;
+        file.set_len(lsn % SEGMENT_SIZE)?;
+        file.sync_data()?;
+        for later in segment + 1.. {
+            match fs::remove_file(segment_path(&self.path, later)) {
+                Ok(()) => {}
+                Err(err) if err.kind() == ErrorKind::NotFound => break,
+                Err(err) => return Err(err.into()),
+            }
+        }
+        Ok(())
+    }
+
+    /// Deletes the segments that end at or before `lsn`.
+    fn remove_before(&self, lsn: Lsn) -> WalResult<()> {
+        for segment in (0..lsn / SEGMENT_SIZE).rev() {
+            match fs::remove_file(segment_path(&self.path, segment)) {
+                Ok(()) => {}
+                Err(err) if err.kind() == ErrorKind::NotFound => break,
+                Err(err) => return Err(err.into()),
+            }
+        }
+        Ok(())
+    }
+}
+
+fn segment_path(log_path: &Path, segment: u64) -> PathBuf {
+    if segment == 0 {
+        log_path.to_path_buf()
+    } else {
+        with_suffix(log_path, &format!(".{}", segment))
+    }
+}
+
+/// `path` with `suffix` appended to its file name, as the log's segments
+/// and master record are named after it.
+pub fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(suffix);
+    PathBuf::from(name)
 }
 
 pub struct LogReader {
-    file: File,
+    files: LogFiles,
     offset: u64,
+    checkpoint_lsn: Option<Lsn>,
 }
 
 impl LogReader {
+    /// Opens the log at its first record; records discarded by truncation
+    /// are skipped.
     pub fn open(path: impl AsRef<Path>) -> WalResult<Self> {
-        let file = OpenOptions::new().read(true).open(path)?;
-        Ok(Self { file, offset: 0 })
+        let path = path.as_ref();
+        let master = MasterRecord::read(path)?;
+        Ok(Self {
+            files: LogFiles::reader(path),
+            offset: master.start_lsn,
+            checkpoint_lsn: master.checkpoint_lsn,
+        })
+    }
+
+    /// LSN of the latest complete checkpoint record, if one was taken.
+    pub fn last_checkpoint(&self) -> Option<Lsn> {
+        self.checkpoint_lsn
     }
 
     pub fn seek(&mut self, lsn: Lsn) -> WalResult<()> {
         self.offset = lsn;
-        self.file.seek(SeekFrom::Start(lsn))?;
         Ok(())
     }
 
+    /// Returns the next record, or `None` at the end of the log. A record
+    /// cut short or garbled by a crash mid-write (a torn write) also ends the
+    /// log, and the reader stays before it. A record that fails its checksum
+    /// with more of the log after it is corruption and an error.
     pub fn next_record(&mut self) -> WalResult<Option<LogRecord>> {
-        let mut len_bytes = [0u8; 4];
-        let bytes_read = self.file.read(&mut len_bytes)?;
-        if bytes_read == 0 {
+        let remaining = self.files.end_lsn(self.offset)?.saturating_sub(self.offset);
+        if remaining < 4 {
             return Ok(None);
         }
-        if bytes_read < 4 {
-            return Err(WalError::Corrupt("log record length truncated".to_string()));
-        }
+        let mut len_bytes = [0u8; 4];
+        self.files.read_at(self.offset, &mut len_bytes)?;
         let len = u32::from_le_bytes(len_bytes) as usize;
-        if len < 4 {
+        // a zero length is space the file was extended by but that was
+        // never written
+        if len == 0 || len as u64 > remaining {
+            return Ok(None);
+        }
+        if len < RECORD_PREFIX_LEN {
             return Err(WalError::Corrupt("invalid log record length".to_string()));
         }
         let mut full_record = vec![0u8; len];
         full_record[0..4].copy_from_slice(&len_bytes);
-        self.file.read_exact(&mut full_record[4..])?;
-        self.offset += len as u64;
+        self.files.read_at(self.offset + 4, &mut full_record[4..])?;
+        if !checksum_matches(&full_record) {
+            if len as u64 == remaining {
+                return Ok(None);
+            }
+            return Err(WalError::Corrupt(format!(
+                "log record at lsn {} fails its checksum",
+                self.offset
+            )));
+        }
         let record = LogRecord::from_bytes(&full_record)?;
+        self.offset += len as u64;
         Ok(Some(record))
     }
+
+    /// End of the last complete record read so far.
+    pub fn offset(&self) -> Lsn {
+        self.offset
+    }
+
+    /// End of the bytes in the log, which lies past [`Self::offset`] once
+    /// reading stops at a torn or unreadable record.
+    pub fn end_lsn(&self) -> WalResult<Lsn> {
+        self.files.end_lsn(self.offset)
+    }
 }
 
-fn write_flush_request(file: &mut File, request: &FlushRequest) -> WalResult<()> {
-    file.seek(SeekFrom::Start(request.start_lsn))?;
-    file.write_all(&request.bytes)?;
-    file.sync_data()?;
-    Ok(())
+/// Writes a batch of requests, joining consecutive ones into one write so
+/// each segment it touches is synced once. Returns the number of syncs.
+fn write_flush_requests(files: &mut LogFiles, batch: &[FlushRequest]) -> WalResult<u64> {
+    let mut syncs = 0;
+    let mut start_lsn = batch[0].start_lsn;
+    let mut bytes = Vec::new();
+    for request in batch {
+        // `truncate` may move later requests back over earlier ones
+        if request.start_lsn != start_lsn + bytes.len() as u64 {
+            syncs += files.write_at(start_lsn, &bytes)?;
+            start_lsn = request.start_lsn;
+            bytes.clear();
+        }
+        bytes.extend_from_slice(&request.bytes);
+    }
+    Ok(syncs + files.write_at(start_lsn, &bytes)?)
+}
+
+fn checksum_matches(record: &[u8]) -> bool {
+    record.len() >= RECORD_PREFIX_LEN
+        && read_u32(&record[4..8]) == crc32fast::hash(&record[RECORD_PREFIX_LEN..])
 }
 
 fn read_u64(bytes: &[u8]) -> u64 {
@@ -865,4 +1845,323 @@ mod tests {
         assert!(seen.contains(&LogRecordType::Commit));
         let _ = fs::remove_file(&path);
     }
+
+    #[test]
+    fn torn_tail_record_ends_the_log_and_is_overwritten() {
+        let path = std::env::temp_dir().join("wal_torn_tail.log");
+        let _ = fs::remove_file(&path);
+        let torn_lsn = {
+            let manager = LogManager::open(&path).unwrap();
+            manager.append(LogRecord::begin(0, 1, None)).unwrap();
+            let lsn = manager.append(LogRecord::commit(0, 1, None)).unwrap();
+            manager.flush(lsn).unwrap();
+            lsn
+        };
+        // the commit record was only partly written when the machine died
+        let file = OpenOptions::new().write(true).open(&path).unwrap();
+        file.set_len(torn_lsn + 6).unwrap();
+        drop(file);
+
+        let mut reader = LogReader::open(&path).unwrap();
+        assert_eq!(
+            reader.next_record().unwrap().map(|r| r.record_type),
+            Some(LogRecordType::Begin)
+        );
+        assert!(reader.next_record().unwrap().is_none());
+        assert!(reader.next_record().unwrap().is_none());
+        assert_eq!(reader.offset(), torn_lsn);
+
+        let manager = LogManager::open(&path).unwrap();
+        let lsn = manager.append(LogRecord::abort(0, 1, None)).unwrap();
+        assert_eq!(lsn, torn_lsn);
+        manager.flush(lsn).unwrap();
+        let mut reader = LogReader::open(&path).unwrap();
+        let mut seen = Vec::new();
+        while let Some(record) = reader.next_record().unwrap() {
+            seen.push(record.record_type);
+        }
+        assert_eq!(seen, vec![LogRecordType::Begin, LogRecordType::Abort]);
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn full_disk_fails_flushes_until_space_is_freed() {
+        let path = std::env::temp_dir().join("wal_disk_full.log");
+        let _ = fs::remove_file(&path);
+        let manager = LogManager::open(&path).unwrap();
+        manager.append(LogRecord::begin(0, 1, None)).unwrap();
+        fault::fill_disk(&path);
+        let lsn = manager.append(LogRecord::commit(0, 1, None)).unwrap();
+        assert!(matches!(manager.flush(lsn), Err(WalError::DiskFull(_))));
+        // the log stays usable: later records are buffered behind the kept ones
+        let end_lsn = manager.append(LogRecord::end(0, 1, None)).unwrap();
+        assert!(matches!(manager.flush(end_lsn), Err(WalError::DiskFull(_))));
+
+        fault::free_disk(&path);
+        manager.flush(lsn).unwrap();
+        manager.flush(end_lsn).unwrap();
+        let mut reader = LogReader::open(&path).unwrap();
+        let mut seen = Vec::new();
+        while let Some(record) = reader.next_record().unwrap() {
+            seen.push(record.record_type);
+        }
+        assert_eq!(
+            seen,
+            vec![
+                LogRecordType::Begin,
+                LogRecordType::Commit,
+                LogRecordType::End
+            ]
+        );
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn flipped_byte_in_a_page_update_is_reported_as_corruption() {
+        let path = std::env::temp_dir().join("wal_checksum.log");
+        let _ = fs::remove_file(&path);
+        let (update_lsn, commit_lsn) = {
+            let manager = LogManager::open(&path).unwrap();
+            manager.append(LogRecord::begin(0, 1, None)).unwrap();
+            let update = LogRecord::page_update(0, 1, None, 7, 16, vec![0; 4], vec![9; 4]);
+            let update_lsn = manager.append(update).unwrap();
+            let commit_lsn = manager.append(LogRecord::commit(0, 1, None)).unwrap();
+            manager.flush(commit_lsn).unwrap();
+            (update_lsn, commit_lsn)
+        };
+        let flip = |position: u64| {
+            let mut bytes = fs::read(&path).unwrap();
+            bytes[position as usize] ^= 0x40;
+            fs::write(&path, bytes).unwrap();
+        };
+        let read_all = || -> WalResult<Vec<LogRecordType>> {
+            let mut reader = LogReader::open(&path)?;
+            let mut seen = Vec::new();
+            while let Some(record) = reader.next_record()? {
+                seen.push(record.record_type);
+            }
+            Ok(seen)
+        };
+
+        // the last byte of the update is in its after image
+        flip(commit_lsn - 1);
+        assert!(matches!(read_all(), Err(WalError::Corrupt(_))));
+        let bytes = fs::read(&path).unwrap();
+        assert!(matches!(
+            LogRecord::from_bytes(&bytes[update_lsn as usize..commit_lsn as usize]),
+            Err(WalError::Corrupt(_))
+        ));
+
+        // with nothing after it, the damaged record is a torn tail
+        let file = OpenOptions::new().write(true).open(&path).unwrap();
+        file.set_len(commit_lsn).unwrap();
+        drop(file);
+        assert_eq!(read_all().unwrap(), vec![LogRecordType::Begin]);
+        flip(commit_lsn - 1);
+        assert_eq!(
+            read_all().unwrap(),
+            vec![LogRecordType::Begin, LogRecordType::PageUpdate]
+        );
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn checkpoint_lists_open_transactions_and_truncation_keeps_their_records() {
+        let path = std::env::temp_dir().join("wal_checkpoint.log");
+        let remove_log = || {
+            for segment in 0..8 {
+                let _ = fs::remove_file(segment_path(&path, segment));
+            }
+            let _ = fs::remove_file(with_suffix(&path, ".master"));
+        };
+        remove_log();
+        let manager = LogManager::open(&path).unwrap();
+        for txn_id in 1..=3000 {
+            manager.append(LogRecord::begin(0, txn_id, None)).unwrap();
+            let update = LogRecord::page_update(0, txn_id, None, 7, 0, vec![0; 256], vec![1; 256]);
+            manager.append(update).unwrap();
+            manager.append(LogRecord::commit(0, txn_id, None)).unwrap();
+            manager.append(LogRecord::end(0, txn_id, None)).unwrap();
+        }
+        let open_lsn = manager.append(LogRecord::begin(0, 3001, None)).unwrap();
+        let checkpoint_lsn = manager.checkpoint(Vec::new()).unwrap();
+
+        let mut reader = LogReader::open(&path).unwrap();
+        assert_eq!(reader.last_checkpoint(), Some(checkpoint_lsn));
+        reader.seek(checkpoint_lsn).unwrap();
+        let checkpoint = reader.next_record().unwrap().unwrap();
+        let LogPayload::Checkpoint { active_txns, .. } = &checkpoint.payload else {
+            panic!("expected a checkpoint, got {:?}", checkpoint.payload);
+        };
+        assert_eq!(
+            active_txns,
+            &vec![ActiveTxn {
+                txn_id: 3001,
+                first_lsn: open_lsn,
+                last_lsn: open_lsn,
+                committed: false,
+            }]
+        );
+        assert_eq!(checkpoint.oldest_required_lsn(), Some(open_lsn));
+
+        let start_lsn = manager.truncate_before(checkpoint_lsn).unwrap();
+        assert!(start_lsn > 0 && start_lsn <= open_lsn);
+        assert!(!path.exists());
+        let mut reader = LogReader::open(&path).unwrap();
+        let first = reader.next_record().unwrap().unwrap();
+        assert_eq!(first.lsn, start_lsn);
+        let mut last = first;
+        while let Some(record) = reader.next_record().unwrap() {
+            last = record;
+        }
+        assert_eq!(last.lsn, checkpoint_lsn);
+        drop(manager);
+
+        let manager = LogManager::open(&path).unwrap();
+        assert_eq!(manager.start_lsn(), start_lsn);
+        assert_eq!(
+            manager.append(LogRecord::end(0, 3001, None)).unwrap(),
+            reader.offset()
+        );
+        remove_log();
+    }
+
+    #[test]
+    fn concurrent_commits_share_syncs() {
+        let path = std::env::temp_dir().join("wal_group_commit.log");
+        let _ = fs::remove_file(&path);
+        let _ = fs::remove_file(with_suffix(&path, ".master"));
+        let txn_manager = TransactionManager::new(Arc::new(LogManager::open(&path).unwrap()));
+        let threads = 64;
+        let commits_per_thread = 10;
+        let workers = (0..threads)
+            .map(|_| {
+                let txn_manager = txn_manager.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..commits_per_thread {
+                        let txn = txn_manager.begin().unwrap();
+                        txn_manager.commit(&txn).unwrap();
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for worker in workers {
+            worker.join().unwrap();
+        }
+
+        let log_manager = txn_manager.log_manager();
+        let commits = (threads * commits_per_thread) as u64;
+        let syncs = log_manager.sync_count();
+        // each commit flushes twice, once for COMMIT and once for END
+        assert!(
+            syncs * 4 < commits,
+            "{} syncs for {} commits",
+            syncs,
+            commits
+        );
+        let mut reader = LogReader::open(&path).unwrap();
+        let mut ended = 0;
+        while let Some(record) = reader.next_record().unwrap() {
+            ended += u64::from(record.record_type == LogRecordType::End);
+        }
+        assert_eq!(ended, commits);
+        drop(txn_manager);
+        drop(log_manager);
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn lone_commit_skips_the_group_commit_window() {
+        let path = std::env::temp_dir().join("wal_group_commit_window.log");
+        let _ = fs::remove_file(&path);
+        let _ = fs::remove_file(with_suffix(&path, ".master"));
+        let log_manager = Arc::new(LogManager::open(&path).unwrap());
+        let window = Duration::from_secs(1);
+        log_manager.set_group_commit(GroupCommit {
+            window,
+            ..GroupCommit::default()
+        });
+        let txn_manager = TransactionManager::new(Arc::clone(&log_manager));
+
+        // the COMMIT and END flushes would each wait out the window
+        let lone = txn_manager.begin().unwrap();
+        let started = Instant::now();
+        txn_manager.commit(&lone).unwrap();
+        assert!(started.elapsed() < window, "{:?}", started.elapsed());
+
+        // a running transaction may commit soon, so the window is kept
+        let running = txn_manager.begin().unwrap();
+        let committing = txn_manager.begin().unwrap();
+        let started = Instant::now();
+        txn_manager.commit(&committing).unwrap();
+        assert!(started.elapsed() >= window, "{:?}", started.elapsed());
+        txn_manager.abort(&running).unwrap();
+        drop(txn_manager);
+        drop(log_manager);
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn snapshot_sees_transactions_committed_before_it() {
+        let path = std::env::temp_dir().join("wal_snapshot.log");
+        let _ = fs::remove_file(&path);
+        let _ = fs::remove_file(with_suffix(&path, ".master"));
+        let txn_manager = TransactionManager::new(Arc::new(LogManager::open(&path).unwrap()));
+        let committed = txn_manager.begin().unwrap();
+        txn_manager.commit(&committed).unwrap();
+        let running = txn_manager.begin().unwrap();
+        let reader = txn_manager.begin_read_only();
+        txn_manager.take_snapshot(&reader);
+        let later = txn_manager.begin().unwrap();
+        txn_manager.commit(&running).unwrap();
+        txn_manager.commit(&later).unwrap();
+
+        let snapshot = reader.lock().snapshot.clone().unwrap();
+        let id = |txn: &TransactionHandle| txn.lock().txn_id;
+        assert!(snapshot.sees(id(&committed)));
+        assert!(snapshot.sees(id(&reader)));
+        assert!(!snapshot.sees(id(&running)));
+        assert!(!snapshot.sees(id(&later)));
+
+        // versions of `committed` are no longer needed by any snapshot;
+        // those of `running` are until the reader finishes
+        txn_manager.with_transaction(&reader, || {
+            assert!(seen_by_every_snapshot(id(&committed)));
+            assert!(!seen_by_every_snapshot(id(&running)));
+        });
+        txn_manager.commit(&reader).unwrap();
+        txn_manager.with_transaction(&later, || assert!(seen_by_every_snapshot(id(&running))));
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn versions_are_kept_only_while_a_snapshot_is_open() {
+        let path = std::env::temp_dir().join("wal_keeps_versions.log");
+        let _ = fs::remove_file(&path);
+        let _ = fs::remove_file(with_suffix(&path, ".master"));
+        let txn_manager = TransactionManager::new(Arc::new(LogManager::open(&path).unwrap()));
+        let has_snapshot = |txn: &TransactionHandle| txn.lock().snapshot.is_some();
+
+        // a writer with no snapshot open keeps nothing, and no snapshot can
+        // be taken until it finishes
+        let writer = txn_manager.begin().unwrap();
+        assert!(!txn_manager.with_transaction(&writer, keeps_versions));
+        let reader = txn_manager.begin().unwrap();
+        txn_manager.take_snapshot(&reader);
+        assert!(!has_snapshot(&reader));
+        txn_manager.commit(&writer).unwrap();
+        txn_manager.commit(&reader).unwrap();
+
+        // once a snapshot is open, writers keep versions for it
+        let reader = txn_manager.begin().unwrap();
+        txn_manager.take_snapshot(&reader);
+        assert!(has_snapshot(&reader));
+        let writer = txn_manager.begin().unwrap();
+        assert!(txn_manager.with_transaction(&writer, keeps_versions));
+        txn_manager.commit(&reader).unwrap();
+        assert!(!txn_manager.with_transaction(&writer, keeps_versions));
+        txn_manager.commit(&writer).unwrap();
+        assert!(!keeps_versions());
+        let _ = fs::remove_file(&path);
+    }
 }
@@ -0,0 +1,2 @@
+-- Tickets are looked up by order, and by order and ticket type together
+CREATE INDEX IF NOT EXISTS tickets_order_type ON tickets (order_id, ticket_type_id);
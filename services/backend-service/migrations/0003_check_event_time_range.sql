@@ -0,0 +1,4 @@
+-- Events must end after they start. Added here rather than in 0001 so that
+-- events tables created before migrations existed get the constraint too;
+-- it fails if one already holds an event that does not.
+ALTER TABLE events ADD CONSTRAINT events_time_range CHECK (start_time < end_time);
@@ -1,11 +1,159 @@
+use actix_web::{http::StatusCode, HttpResponse, ResponseError};
 use db::engine::Engine;
-use parking_lot::Mutex;
+use parking_lot::{Mutex, MutexGuard};
 use std::collections::HashMap;
+use std::fmt;
 use std::sync::Arc;
+use std::time::Duration;
 use wal::Transaction;
 
+use crate::models::ErrorResponse;
+
+/// How long a request waits for the engine before it is answered with
+/// [`EngineBusy`].
+pub const DEFAULT_BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Longest SQL string, in bytes, a request may carry by default.
+pub const DEFAULT_MAX_SQL_BYTES: usize = 1024 * 1024;
+
+/// Most statements a batch request may carry by default.
+pub const DEFAULT_MAX_BATCH_STATEMENTS: usize = 1000;
+
 #[derive(Clone)]
 pub struct AppState {
     pub engine: Arc<Mutex<Engine>>,
     pub transactions: Arc<Mutex<HashMap<String, Arc<Mutex<Transaction>>>>>,
+    /// How long a request waits for another request to release `engine`.
+    pub busy_timeout: Duration,
+    pub limits: RequestLimits,
+}
+
+impl AppState {
+    /// Locks the engine, giving up after `busy_timeout` so a request stuck
+    /// behind other writers fails fast instead of hanging.
+    pub fn lock_engine(&self) -> Result<MutexGuard<'_, Engine>, EngineBusy> {
+        self.engine
+            .try_lock_for(self.busy_timeout)
+            .ok_or(EngineBusy)
+    }
+}
+
+/// The engine stayed locked by other requests for the whole busy-timeout.
+/// Answered with `503` and `retryable: true`; nothing was run, so the
+/// request can be sent again as is.
+#[derive(Debug)]
+pub struct EngineBusy;
+
+impl fmt::Display for EngineBusy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "database is busy; retry the request")
+    }
+}
+
+impl std::error::Error for EngineBusy {}
+
+impl ResponseError for EngineBusy {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::SERVICE_UNAVAILABLE
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::build(self.status_code()).json(ErrorResponse {
+            error_code: "DATABASE_BUSY".to_string(),
+            message: self.to_string(),
+            retryable: true,
+        })
+    }
+}
+
+/// Size limits checked before any SQL in a request is parsed, so an
+/// oversized request cannot tie up the engine lock while it is parsed.
+#[derive(Debug, Clone, Copy)]
+pub struct RequestLimits {
+    /// Longest SQL string accepted, in bytes.
+    pub max_sql_bytes: usize,
+    /// Most statements accepted in one batch request.
+    pub max_batch_statements: usize,
+}
+
+impl Default for RequestLimits {
+    fn default() -> Self {
+        Self {
+            max_sql_bytes: DEFAULT_MAX_SQL_BYTES,
+            max_batch_statements: DEFAULT_MAX_BATCH_STATEMENTS,
+        }
+    }
+}
+
+impl RequestLimits {
+    pub fn check_sql(&self, sql: &str) -> Result<(), LimitExceeded> {
+        if sql.len() > self.max_sql_bytes {
+            return Err(LimitExceeded::SqlTooLong {
+                bytes: sql.len(),
+                limit: self.max_sql_bytes,
+            });
+        }
+        Ok(())
+    }
+
+    /// Checks the statement count of a batch and the length of each of its
+    /// statements.
+    pub fn check_batch(&self, statements: &[String]) -> Result<(), LimitExceeded> {
+        if statements.len() > self.max_batch_statements {
+            return Err(LimitExceeded::TooManyStatements {
+                count: statements.len(),
+                limit: self.max_batch_statements,
+            });
+        }
+        statements.iter().try_for_each(|sql| self.check_sql(sql))
+    }
+}
+
+/// A request broke one of its [`RequestLimits`]. Answered with `413`
+/// (`SQL_TOO_LONG`) or `400` (`TOO_MANY_STATEMENTS`) before anything is
+/// parsed or run.
+#[derive(Debug)]
+pub enum LimitExceeded {
+    SqlTooLong { bytes: usize, limit: usize },
+    TooManyStatements { count: usize, limit: usize },
+}
+
+impl fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LimitExceeded::SqlTooLong { bytes, limit } => write!(
+                f,
+                "SQL is {} bytes, over the limit of {} bytes",
+                bytes, limit
+            ),
+            LimitExceeded::TooManyStatements { count, limit } => write!(
+                f,
+                "batch has {} statements, over the limit of {}",
+                count, limit
+            ),
+        }
+    }
+}
+
+impl std::error::Error for LimitExceeded {}
+
+impl ResponseError for LimitExceeded {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            LimitExceeded::SqlTooLong { .. } => StatusCode::PAYLOAD_TOO_LARGE,
+            LimitExceeded::TooManyStatements { .. } => StatusCode::BAD_REQUEST,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let error_code = match self {
+            LimitExceeded::SqlTooLong { .. } => "SQL_TOO_LONG",
+            LimitExceeded::TooManyStatements { .. } => "TOO_MANY_STATEMENTS",
+        };
+        HttpResponse::build(self.status_code()).json(ErrorResponse {
+            error_code: error_code.to_string(),
+            message: self.to_string(),
+            retryable: false,
+        })
+    }
 }
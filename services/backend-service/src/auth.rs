@@ -7,6 +7,7 @@ use std::time::Duration;
 use url::form_urlencoded;
 
 use crate::app_state::AppState;
+use crate::handlers::execute_logged;
 use crate::jwt::JwtService;
 use crate::models::*;
 use query::Tuple;
@@ -468,16 +469,14 @@ async fn get_google_user_info(access_token: &str) -> anyhow::Result<GoogleUserIn
 }
 
 async fn upsert_user(data: &AppState, google_user: &GoogleUserInfo) -> anyhow::Result<User> {
-    let mut engine = data.engine.lock();
-
-    create_tables(&mut engine)?;
+    let mut engine = data.lock_engine()?;
 
     let check_sql = format!(
         "SELECT id, google_sub, email, name, avatar_url, role, phone, created_at, updated_at FROM users WHERE google_sub = '{}'",
         escape_sql_string(&google_user.sub)
     );
 
-    match engine.execute_sql(&check_sql) {
+    match execute_logged(&mut engine, &check_sql) {
         Ok(db::printer::ReplOutput::Rows { mut rows, .. }) => {
             if let Some(row) = rows.pop() {
                 let update_sql = format!(
@@ -496,9 +495,7 @@ async fn upsert_user(data: &AppState, google_user: &GoogleUserInfo) -> anyhow::R
                     format_value(&row.values()[0])
                 );
 
-                engine
-                    .execute_sql(&update_sql)
-                    .context("Failed to update user")?;
+                execute_logged(&mut engine, &update_sql).context("Failed to update user")?;
 
                 let user_id = row.values()[0].as_i64().unwrap();
                 load_user_by_id_locked(&mut engine, user_id)
@@ -512,7 +509,7 @@ async fn upsert_user(data: &AppState, google_user: &GoogleUserInfo) -> anyhow::R
 }
 
 pub async fn load_user_by_id(data: &AppState, user_id: i64) -> anyhow::Result<User> {
-    let mut engine = data.engine.lock();
+    let mut engine = data.lock_engine()?;
     load_user_by_id_locked(&mut engine, user_id)
 }
 
@@ -525,7 +522,7 @@ pub fn load_user_by_id_locked(
         user_id
     );
 
-    let output = engine.execute_sql(&sql).context("Failed to query user")?;
+    let output = execute_logged(engine, &sql).context("Failed to query user")?;
 
     match output {
         db::printer::ReplOutput::Rows { mut rows, .. } => {
@@ -539,105 +536,6 @@ pub fn load_user_by_id_locked(
     }
 }
 
-pub fn create_tables(engine: &mut db::engine::Engine) -> anyhow::Result<()> {
-    log::debug!("Creating tables...");
-
-    let users_sql = r#"
-        CREATE TABLE IF NOT EXISTS users (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            google_sub TEXT UNIQUE NOT NULL,
-            email TEXT UNIQUE NOT NULL,
-            name TEXT,
-            avatar_url TEXT,
-            role TEXT DEFAULT 'CUSTOMER',
-            phone TEXT,
-            created_at TEXT NOT NULL,
-            updated_at TEXT NOT NULL
-        )
-    "#;
-
-    log::debug!("Executing users table creation...");
-    engine.execute_sql(users_sql)?;
-    log::debug!("Users table created successfully");
-
-    let events_sql = r#"
-        CREATE TABLE IF NOT EXISTS events (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            organizer_user_id INTEGER NOT NULL,
-            title TEXT NOT NULL,
-            description TEXT,
-            venue TEXT,
-            location TEXT,
-            start_time TEXT NOT NULL,
-            end_time TEXT NOT NULL,
-            status TEXT DEFAULT 'DRAFT',
-            created_at TEXT NOT NULL,
-            updated_at TEXT NOT NULL,
-            FOREIGN KEY (organizer_user_id) REFERENCES users(id)
-        )
-    "#;
-
-    engine.execute_sql(events_sql)?;
-
-    let ticket_types_sql = r#"
-        CREATE TABLE IF NOT EXISTS ticket_types (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            event_id INTEGER NOT NULL,
-            name TEXT NOT NULL,
-            price INTEGER NOT NULL,
-            capacity INTEGER NOT NULL,
-            sales_start TEXT,
-            sales_end TEXT,
-            created_at TEXT NOT NULL,
-            updated_at TEXT NOT NULL,
-            FOREIGN KEY (event_id) REFERENCES events(id)
-        )
-    "#;
-
-    engine.execute_sql(ticket_types_sql)?;
-
-    let orders_sql = r#"
-        CREATE TABLE IF NOT EXISTS orders (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            customer_user_id INTEGER NOT NULL,
-            status TEXT DEFAULT 'PENDING',
-            total_amount INTEGER NOT NULL,
-            created_at TEXT NOT NULL,
-            updated_at TEXT NOT NULL,
-            FOREIGN KEY (customer_user_id) REFERENCES users(id)
-        )
-    "#;
-
-    engine.execute_sql(orders_sql)?;
-
-    let tickets_sql = r#"
-        CREATE TABLE IF NOT EXISTS tickets (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            order_id INTEGER NOT NULL,
-            ticket_type_id INTEGER NOT NULL,
-            unit_price INTEGER NOT NULL,
-            status TEXT DEFAULT 'HELD',
-            created_at TEXT NOT NULL,
-            FOREIGN KEY (order_id) REFERENCES orders(id),
-            FOREIGN KEY (ticket_type_id) REFERENCES ticket_types(id)
-        )
-    "#;
-
-    engine.execute_sql(tickets_sql)?;
-
-    // Verify the table was created
-    let verify_sql = "SELECT sql FROM sqlite_master WHERE type='table' AND name='users'";
-    if let Ok(db::printer::ReplOutput::Rows { rows, .. }) = engine.execute_sql(verify_sql) {
-        if let Some(row) = rows.first() {
-            let schema = row.get(0).map(|v| v.as_str().unwrap_or("")).unwrap_or("");
-            log::debug!("Users table schema: {}", schema);
-        }
-    }
-
-    log::debug!("All tables created successfully");
-    Ok(())
-}
-
 fn create_user(
     engine: &mut db::engine::Engine,
     google_user: &GoogleUserInfo,
@@ -665,7 +563,7 @@ fn create_user(
         now
     );
 
-    if let Err(e) = engine.execute_sql(&insert_sql) {
+    if let Err(e) = execute_logged(engine, &insert_sql) {
         return Err(anyhow!("Failed to insert user: {}", e));
     }
 
@@ -674,7 +572,7 @@ fn create_user(
         escape_sql_string(&google_user.sub)
     );
 
-    match engine.execute_sql(&select_sql) {
+    match execute_logged(engine, &select_sql) {
         Ok(db::printer::ReplOutput::Rows { mut rows, .. }) => {
             if let Some(row) = rows.pop() {
                 load_user_by_db_row(&row)
@@ -688,7 +586,7 @@ fn create_user(
 }
 
 async fn update_user_role(data: &AppState, user_id: i64, role: UserRole) -> anyhow::Result<User> {
-    let mut engine = data.engine.lock();
+    let mut engine = data.lock_engine()?;
 
     let role_str = match role {
         UserRole::CUSTOMER => "CUSTOMER",
@@ -703,7 +601,7 @@ async fn update_user_role(data: &AppState, user_id: i64, role: UserRole) -> anyh
         user_id
     );
 
-    engine.execute_sql(&update_sql)?;
+    execute_logged(&mut engine, &update_sql)?;
 
     load_user_by_id_locked(&mut engine, user_id)
 }
@@ -714,7 +612,7 @@ async fn update_user_profile(
     name: Option<String>,
     phone: Option<String>,
 ) -> anyhow::Result<User> {
-    let mut engine = data.engine.lock();
+    let mut engine = data.lock_engine()?;
 
     let mut set_parts = Vec::new();
 
@@ -737,7 +635,7 @@ async fn update_user_profile(
         user_id
     );
 
-    engine.execute_sql(&update_sql)?;
+    execute_logged(&mut engine, &update_sql)?;
 
     load_user_by_id_locked(&mut engine, user_id)
 }
@@ -805,6 +703,20 @@ pub(crate) fn escape_sql_string(input: &str) -> String {
     input.replace('\'', "''")
 }
 
+/// Escapes LIKE metacharacters so user input matches literally inside a
+/// pattern; `%`, `_` and backslash are prefixed with the default backslash
+/// escape. The result still needs `escape_sql_string` before interpolation.
+pub fn escape_like_pattern(input: &str) -> String {
+    let mut escaped = String::with_capacity(input.len());
+    for ch in input.chars() {
+        if matches!(ch, '%' | '_' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(ch);
+    }
+    escaped
+}
+
 pub fn grant_organizer_role(
     engine: &mut db::engine::Engine,
     target_user_id: i64,
@@ -816,7 +728,7 @@ pub fn grant_organizer_role(
         now, target_user_id
     );
 
-    engine.execute_sql(&update_sql)?;
+    execute_logged(engine, &update_sql)?;
 
     load_user_by_id_locked(engine, target_user_id)
 }
@@ -833,6 +745,7 @@ pub fn check_dev_secret(dev_secret_header: Option<&str>) -> bool {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::app_state::{RequestLimits, DEFAULT_BUSY_TIMEOUT};
     use parking_lot::Mutex;
     use std::sync::Arc;
     use tempfile;
@@ -853,7 +766,7 @@ mod tests {
         let engine = db::engine::Engine::new(db_path).unwrap();
         let mut engine = engine;
 
-        let result = create_tables(&mut engine);
+        let result = crate::migrations::run_migrations(&mut engine);
         assert!(result.is_ok());
 
         let result = engine.execute_sql("SELECT COUNT(*) FROM users");
@@ -865,11 +778,14 @@ mod tests {
         let temp_file = tempfile::NamedTempFile::new().unwrap();
         let db_path = temp_file.path();
 
-        let engine = db::engine::Engine::new(db_path).unwrap();
+        let mut engine = db::engine::Engine::new(db_path).unwrap();
+        crate::migrations::run_migrations(&mut engine).unwrap();
         let transactions = Arc::new(Mutex::new(HashMap::new()));
         let app_state = AppState {
             engine: Arc::new(Mutex::new(engine)),
             transactions,
+            busy_timeout: DEFAULT_BUSY_TIMEOUT,
+            limits: RequestLimits::default(),
         };
 
         let google_user = GoogleUserInfo {
@@ -894,11 +810,14 @@ mod tests {
         let temp_file = tempfile::NamedTempFile::new().unwrap();
         let db_path = temp_file.path();
 
-        let engine = db::engine::Engine::new(db_path).unwrap();
+        let mut engine = db::engine::Engine::new(db_path).unwrap();
+        crate::migrations::run_migrations(&mut engine).unwrap();
         let transactions = Arc::new(Mutex::new(HashMap::new()));
         let app_state = AppState {
             engine: Arc::new(Mutex::new(engine)),
             transactions,
+            busy_timeout: DEFAULT_BUSY_TIMEOUT,
+            limits: RequestLimits::default(),
         };
 
         let google_user = GoogleUserInfo {
@@ -930,11 +849,14 @@ mod tests {
         let temp_dir = tempfile::Builder::new().prefix("test").tempdir().unwrap();
         let db_path = temp_dir.path().join("test.db");
 
-        let engine = db::engine::Engine::new(&db_path).unwrap();
+        let mut engine = db::engine::Engine::new(&db_path).unwrap();
+        crate::migrations::run_migrations(&mut engine).unwrap();
         let transactions = Arc::new(Mutex::new(HashMap::new()));
         let app_state = AppState {
             engine: Arc::new(Mutex::new(engine)),
             transactions,
+            busy_timeout: DEFAULT_BUSY_TIMEOUT,
+            limits: RequestLimits::default(),
         };
 
         let google_user = GoogleUserInfo {
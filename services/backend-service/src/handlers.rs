@@ -1,18 +1,23 @@
 use actix_web::{
-    error::ErrorBadRequest, error::InternalError, web, HttpRequest, HttpResponse, Result,
+    error::ErrorBadRequest, error::ErrorInternalServerError, error::InternalError, http::header,
+    web, HttpRequest, HttpResponse, Result,
 };
 use anyhow::anyhow;
 use chrono::{DateTime, NaiveDateTime, Utc};
 use serde::Deserialize;
 use serde_json::json;
 use std::collections::HashMap;
-use std::sync::Arc;
+use std::time::Instant;
 
-use crate::app_state::AppState;
-use crate::auth::{check_dev_secret, create_tables, escape_sql_string, load_user_by_id};
+use crate::app_state::{AppState, EngineBusy};
+use crate::auth::{check_dev_secret, escape_like_pattern, escape_sql_string, load_user_by_id};
 use crate::jwt::JwtService;
+use crate::logging::RequestId;
 use crate::models::*;
+use db::engine::Engine;
 use db::printer::ReplOutput;
+use query::execution::{encode_result, CancellationToken, ExecutionError, Value};
+use wal::TransactionHandle;
 
 pub async fn health() -> Result<HttpResponse> {
     let response = HealthResponse {
@@ -26,71 +31,291 @@ pub async fn health() -> Result<HttpResponse> {
 pub async fn execute_sql(
     req: web::Json<SqlRequest>,
     data: web::Data<AppState>,
+    req_http: HttpRequest,
 ) -> Result<HttpResponse> {
-    let SqlRequest { sql, tx_id } = req.into_inner();
+    let req = req.into_inner();
+    data.limits.check_sql(&req.sql)?;
+    let params = match req.bound_params() {
+        Ok(params) => params,
+        Err(e) => {
+            return Ok(HttpResponse::BadRequest().json(ErrorResponse {
+                error_code: "INVALID_PARAMS".to_string(),
+                message: e.to_string(),
+                retryable: false,
+            }));
+        }
+    };
+    let SqlRequest { sql, tx_id, .. } = req;
+    let binary = accepts_binary(&req_http);
+    let request_id = RequestId::of(&req_http);
 
     if let Some(tx_id) = tx_id {
-        execute_in_transaction(&data, &tx_id, &sql).await
+        execute_in_transaction(&data, &request_id, &tx_id, &sql, params, binary).await
     } else {
-        execute_autocommit(&data, &sql).await
+        execute_autocommit(&data, &request_id, &sql, params, binary).await
     }
 }
 
-async fn execute_autocommit(data: &AppState, sql: &str) -> Result<HttpResponse> {
-    let mut engine = data.engine.lock();
+/// Runs each statement in order and reports a result per statement. Without
+/// a `tx_id` every statement commits on its own, as with `/api/sql`; the
+/// batch stops at the first failing statement.
+pub async fn execute_batch(
+    req: web::Json<BatchRequest>,
+    data: web::Data<AppState>,
+    req_http: HttpRequest,
+) -> Result<HttpResponse> {
+    let BatchRequest { statements, tx_id } = req.into_inner();
+    let request_id = RequestId::of(&req_http);
+    data.limits.check_batch(&statements)?;
+    let txn = match &tx_id {
+        Some(tx_id) => match data.transactions.lock().get(tx_id).cloned() {
+            Some(txn) => Some(txn),
+            None => {
+                return Ok(HttpResponse::NotFound().json(ErrorResponse {
+                    error_code: "TX_NOT_FOUND".to_string(),
+                    message: format!("Transaction {} not found", tx_id),
+                    retryable: false,
+                }));
+            }
+        },
+        None => None,
+    };
 
-    match engine.execute_sql(sql) {
-        Ok(output) => {
-            let response = convert_repl_output_to_sql_response(output);
-            Ok(HttpResponse::Ok().json(response))
-        }
-        Err(e) => {
-            let error_code = categorize_error(&e);
-            Ok(HttpResponse::BadRequest().json(ErrorResponse {
-                error_code,
-                message: e.to_string(),
-            }))
+    let mut results = Vec::with_capacity(statements.len());
+    for (statement_index, sql) in statements.into_iter().enumerate() {
+        let txn = txn.clone();
+        let started = Instant::now();
+        let statement = sql.clone();
+        let result = run_cancellable(&data, move |engine| match &txn {
+            Some(txn) => execute_in_client_transaction(engine, &statement, &[], txn),
+            None => engine.execute_sql(&statement),
+        })
+        .await?;
+        log_statement(&request_id, tx_id.as_deref(), &sql, &result, started);
+        match result {
+            Ok(output) => results.push(convert_repl_output_to_sql_response(output)),
+            Err(e) => {
+                let retryable = is_retryable(&e);
+                if retryable {
+                    if let Some(tx_id) = &tx_id {
+                        data.transactions.lock().remove(tx_id);
+                    }
+                }
+                let response = BatchResponse {
+                    results,
+                    error: Some(BatchError {
+                        statement_index,
+                        error_code: categorize_error(&e),
+                        message: e.to_string(),
+                        retryable,
+                    }),
+                };
+                return Ok(if retryable {
+                    HttpResponse::Conflict().json(response)
+                } else {
+                    HttpResponse::BadRequest().json(response)
+                });
+            }
         }
     }
+    Ok(HttpResponse::Ok().json(BatchResponse {
+        results,
+        error: None,
+    }))
 }
 
-async fn execute_in_transaction(data: &AppState, tx_id: &str, sql: &str) -> Result<HttpResponse> {
-    let transactions = data.transactions.lock();
+const BINARY_CONTENT_TYPE: &str = "application/octet-stream";
+
+/// Row results are sent in the binary encoding of
+/// `query::execution::encoding` when the client asks for
+/// `application/octet-stream`; messages and errors stay JSON.
+fn accepts_binary(req: &HttpRequest) -> bool {
+    req.headers()
+        .get(header::ACCEPT)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|accept| {
+            accept
+                .split(',')
+                .any(|media| media.trim().starts_with(BINARY_CONTENT_TYPE))
+        })
+}
+
+async fn execute_autocommit(
+    data: &AppState,
+    request_id: &RequestId,
+    sql: &str,
+    params: Vec<Value>,
+    binary: bool,
+) -> Result<HttpResponse> {
+    let started = Instant::now();
+    let statement = sql.to_string();
+    let result = run_cancellable(data, move |engine| {
+        engine.execute_prepared(&statement, &params)
+    })
+    .await?;
+    log_statement(request_id, None, sql, &result, started);
+    Ok(sql_result_response(result, binary))
+}
 
-    let txn = match transactions.get(tx_id) {
+async fn execute_in_transaction(
+    data: &AppState,
+    request_id: &RequestId,
+    tx_id: &str,
+    sql: &str,
+    params: Vec<Value>,
+    binary: bool,
+) -> Result<HttpResponse> {
+    let txn = match data.transactions.lock().get(tx_id).cloned() {
         Some(txn) => txn,
         None => {
             return Ok(HttpResponse::NotFound().json(ErrorResponse {
                 error_code: "TX_NOT_FOUND".to_string(),
                 message: format!("Transaction {} not found", tx_id),
+                retryable: false,
             }));
         }
     };
 
-    let txn_clone = Arc::clone(txn);
-    drop(transactions);
+    let started = Instant::now();
+    let statement = sql.to_string();
+    let result = run_cancellable(data, move |engine| {
+        execute_in_client_transaction(engine, &statement, &params, &txn)
+    })
+    .await?;
+    log_statement(request_id, Some(tx_id), sql, &result, started);
+    if result.as_ref().is_err_and(is_retryable) {
+        data.transactions.lock().remove(tx_id);
+    }
+    Ok(sql_result_response(result, binary))
+}
+
+/// Logs a statement a client sent, under the id of its request, with how
+/// it ended and how long it took.
+fn log_statement(
+    request_id: &RequestId,
+    tx_id: Option<&str>,
+    sql: &str,
+    result: &anyhow::Result<ReplOutput>,
+    started: Instant,
+) {
+    let duration_ms = started.elapsed().as_millis() as u64;
+    let tx_id = tx_id.unwrap_or("-");
+    match result {
+        Ok(output) => {
+            let outcome = match output {
+                ReplOutput::Rows { rows, .. } => format!("{} rows", rows.len()),
+                ReplOutput::Message(message) => message.clone(),
+            };
+            log::info!(
+                target: "sql",
+                request_id = request_id.as_str(),
+                tx_id = tx_id,
+                sql = sql,
+                outcome = outcome.as_str(),
+                duration_ms = duration_ms;
+                "statement finished"
+            );
+        }
+        Err(e) => {
+            let error_code = categorize_error(e);
+            let error = e.to_string();
+            log::warn!(
+                target: "sql",
+                request_id = request_id.as_str(),
+                tx_id = tx_id,
+                sql = sql,
+                error_code = error_code.as_str(),
+                error = error.as_str(),
+                duration_ms = duration_ms;
+                "statement failed"
+            );
+        }
+    }
+}
 
-    let mut engine = data.engine.lock();
+/// Runs SQL an endpoint builds itself, logging it as [`log_statement`] does
+/// a client's statement, under the id of the request being served.
+pub(crate) fn execute_logged(engine: &mut Engine, sql: &str) -> anyhow::Result<ReplOutput> {
+    let started = Instant::now();
+    let result = engine.execute_sql(sql);
+    log_statement(&RequestId::current(), None, sql, &result, started);
+    result
+}
+
+/// Runs a statement on the blocking pool. When the client disconnects, actix
+/// drops this future, which cancels the statement so it stops at its next
+/// row and releases the engine lock.
+async fn run_cancellable<F>(data: &AppState, f: F) -> Result<anyhow::Result<ReplOutput>>
+where
+    F: FnOnce(&mut Engine) -> anyhow::Result<ReplOutput> + Send + 'static,
+{
+    let token = CancellationToken::new();
+    let _cancel_on_drop = token.cancel_on_drop();
+    let data = data.clone();
+    let result = web::block(move || {
+        let mut engine = data.lock_engine()?;
+        Ok::<_, EngineBusy>(engine.with_cancellation(token, f))
+    })
+    .await
+    .map_err(ErrorInternalServerError)?;
+    Ok(result?)
+}
 
-    match engine.execute_sql_in_transaction(sql, &txn_clone) {
+fn sql_result_response(result: anyhow::Result<ReplOutput>, binary: bool) -> HttpResponse {
+    match result {
+        Ok(ReplOutput::Rows { schema, rows }) if binary => HttpResponse::Ok()
+            .content_type(BINARY_CONTENT_TYPE)
+            .body(encode_result(&schema, &rows)),
         Ok(output) => {
             let response = convert_repl_output_to_sql_response(output);
-            Ok(HttpResponse::Ok().json(response))
+            HttpResponse::Ok().json(response)
         }
         Err(e) => {
-            let error_code = categorize_error(&e);
-            Ok(HttpResponse::BadRequest().json(ErrorResponse {
-                error_code,
+            let body = ErrorResponse {
+                error_code: categorize_error(&e),
                 message: e.to_string(),
-            }))
+                retryable: is_retryable(&e),
+            };
+            if body.retryable {
+                HttpResponse::Conflict().json(body)
+            } else {
+                HttpResponse::BadRequest().json(body)
+            }
+        }
+    }
+}
+
+/// Whether `error` aborted its transaction because of contention, such as
+/// a lock wait that timed out in a deadlock. These are reported as `409`
+/// with `retryable: true` so the client runs the transaction again.
+fn is_retryable(error: &anyhow::Error) -> bool {
+    error
+        .downcast_ref::<ExecutionError>()
+        .is_some_and(ExecutionError::is_retryable)
+}
+
+/// Runs `sql` in the client's transaction. A retryable failure rolls the
+/// transaction back at once so its locks are released before the client
+/// retries; the caller then forgets its `tx_id`.
+fn execute_in_client_transaction(
+    engine: &mut Engine,
+    sql: &str,
+    params: &[Value],
+    txn: &TransactionHandle,
+) -> anyhow::Result<ReplOutput> {
+    let result = engine.execute_prepared_in_transaction(sql, params, txn);
+    if let Err(e) = &result {
+        if is_retryable(e) {
+            engine.abort_transaction(txn)?;
         }
     }
+    result
 }
 
 pub async fn begin_transaction(data: web::Data<AppState>) -> Result<HttpResponse> {
     let tx_id = uuid::Uuid::new_v4().to_string();
 
-    let mut engine = data.engine.lock();
+    let mut engine = data.lock_engine()?;
 
     let txn = match engine.begin_transaction() {
         Ok(txn) => txn,
@@ -98,6 +323,7 @@ pub async fn begin_transaction(data: web::Data<AppState>) -> Result<HttpResponse
             return Ok(HttpResponse::InternalServerError().json(ErrorResponse {
                 error_code: "TX_BEGIN_FAILED".to_string(),
                 message: format!("Failed to begin transaction: {}", e),
+                retryable: false,
             }));
         }
     };
@@ -122,18 +348,20 @@ pub async fn commit_transaction(
             return Ok(HttpResponse::NotFound().json(ErrorResponse {
                 error_code: "TX_NOT_FOUND".to_string(),
                 message: format!("Transaction {} not found", tx_id),
+                retryable: false,
             }));
         }
     };
 
     drop(transactions);
 
-    let mut engine = data.engine.lock();
+    let mut engine = data.lock_engine()?;
 
     if let Err(e) = engine.commit_transaction(&txn) {
         return Ok(HttpResponse::InternalServerError().json(ErrorResponse {
             error_code: "TX_COMMIT_FAILED".to_string(),
             message: format!("Failed to commit transaction: {}", e),
+            retryable: false,
         }));
     }
 
@@ -156,18 +384,20 @@ pub async fn abort_transaction(
             return Ok(HttpResponse::NotFound().json(ErrorResponse {
                 error_code: "TX_NOT_FOUND".to_string(),
                 message: format!("Transaction {} not found", tx_id),
+                retryable: false,
             }));
         }
     };
 
     drop(transactions);
 
-    let mut engine = data.engine.lock();
+    let mut engine = data.lock_engine()?;
 
     if let Err(e) = engine.abort_transaction(&txn) {
         return Ok(HttpResponse::InternalServerError().json(ErrorResponse {
             error_code: "TX_ABORT_FAILED".to_string(),
             message: format!("Failed to abort transaction: {}", e),
+            retryable: false,
         }));
     }
 
@@ -180,6 +410,13 @@ fn convert_repl_output_to_sql_response(output: ReplOutput) -> SqlResponse {
     match output {
         ReplOutput::Rows { schema, rows } => {
             let columns = Some(schema.fields.iter().map(|f| f.name.clone()).collect());
+            let column_types = Some(
+                schema
+                    .fields
+                    .iter()
+                    .map(|f| format!("{:?}", f.data_type))
+                    .collect(),
+            );
             let rows_serialized = Some(
                 rows.into_iter()
                     .map(|row| {
@@ -193,25 +430,27 @@ fn convert_repl_output_to_sql_response(output: ReplOutput) -> SqlResponse {
 
             SqlResponse {
                 columns,
+                column_types,
                 rows: rows_serialized,
                 rows_affected: None,
                 message: None,
             }
         }
         ReplOutput::Message(msg) => {
-            // Try to parse rows affected from message
-            let rows_affected = if msg.starts_with("INSERT ") {
-                msg.split_whitespace().nth(1).and_then(|s| s.parse().ok())
-            } else if msg.starts_with("DELETE ") {
-                msg.split_whitespace().nth(1).and_then(|s| s.parse().ok())
-            } else if msg.starts_with("UPDATE ") {
-                msg.split_whitespace().nth(1).and_then(|s| s.parse().ok())
+            // The count is the last word of the command tag: "INSERT 0 3",
+            // "UPDATE 2", "DELETE 1"
+            let rows_affected = if msg.starts_with("INSERT ")
+                || msg.starts_with("DELETE ")
+                || msg.starts_with("UPDATE ")
+            {
+                msg.split_whitespace().last().and_then(|s| s.parse().ok())
             } else {
                 None
             };
 
             SqlResponse {
                 columns: None,
+                column_types: None,
                 rows: None,
                 rows_affected,
                 message: Some(msg),
@@ -221,6 +460,19 @@ fn convert_repl_output_to_sql_response(output: ReplOutput) -> SqlResponse {
 }
 
 fn categorize_error(error: &anyhow::Error) -> String {
+    match error.downcast_ref::<ExecutionError>() {
+        Some(ExecutionError::Cancelled) => return "QUERY_CANCELLED".to_string(),
+        Some(ExecutionError::DivisionByZero) => return "DIVISION_BY_ZERO".to_string(),
+        Some(
+            ExecutionError::LockTimeout
+            | ExecutionError::Deadlock
+            | ExecutionError::LockNotAvailable
+            | ExecutionError::SerializationFailure,
+        ) => {
+            return "TRANSACTION_CONFLICT".to_string();
+        }
+        _ => {}
+    }
     let error_string = error.to_string().to_lowercase();
 
     if error_string.contains("sql") || error_string.contains("syntax") {
@@ -236,6 +488,21 @@ fn categorize_error(error: &anyhow::Error) -> String {
     }
 }
 
+/// The events table rejects `end_time <= start_time` through its
+/// `events_time_range` CHECK constraint; report that as a validation error.
+fn time_range_violation(error: &anyhow::Error) -> Option<HttpResponse> {
+    match error.downcast_ref::<ExecutionError>() {
+        Some(ExecutionError::ConstraintViolation { constraint, .. })
+            if constraint == "events_time_range" =>
+        {
+            Some(HttpResponse::BadRequest().json(
+                json!({"error": "VALIDATION_ERROR", "message": "End time must be after start time"}),
+            ))
+        }
+        _ => None,
+    }
+}
+
 async fn extract_user_from_request(req: &HttpRequest, data: &AppState) -> Result<(i64, User)> {
     let auth_header = req
         .headers()
@@ -306,9 +573,32 @@ fn check_organizer_role(user: &User) -> Result<()> {
     }
 }
 
-async fn load_event_by_id(data: &AppState, event_id: i64) -> Result<Event, String> {
-    let mut engine = data.engine.lock();
-    load_event_by_id_locked(&mut engine, event_id)
+/// Why a lookup helper returned nothing. `Busy` keeps [`EngineBusy`] so the
+/// handler answers `503 DATABASE_BUSY` rather than "not found".
+#[derive(Debug)]
+enum LoadError {
+    Busy(EngineBusy),
+    Query(String),
+}
+
+impl From<EngineBusy> for LoadError {
+    fn from(busy: EngineBusy) -> Self {
+        LoadError::Busy(busy)
+    }
+}
+
+impl std::fmt::Display for LoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LoadError::Busy(busy) => busy.fmt(f),
+            LoadError::Query(message) => f.write_str(message),
+        }
+    }
+}
+
+async fn load_event_by_id(data: &AppState, event_id: i64) -> Result<Event, LoadError> {
+    let mut engine = data.lock_engine()?;
+    load_event_by_id_locked(&mut engine, event_id).map_err(LoadError::Query)
 }
 
 fn load_event_by_id_locked(
@@ -320,7 +610,7 @@ fn load_event_by_id_locked(
         event_id
     );
 
-    match engine.execute_sql(&sql) {
+    match execute_logged(engine, &sql) {
         Ok(ReplOutput::Rows { mut rows, .. }) => {
             if let Some(row) = rows.pop() {
                 load_event_by_db_row(&row).map_err(|e: anyhow::Error| e.to_string())
@@ -383,9 +673,9 @@ fn load_event_by_db_row(row: &query::Tuple) -> anyhow::Result<Event> {
 async fn load_ticket_type_by_id(
     data: &AppState,
     ticket_type_id: i64,
-) -> Result<TicketType, String> {
-    let mut engine = data.engine.lock();
-    load_ticket_type_by_id_locked(&mut engine, ticket_type_id)
+) -> Result<TicketType, LoadError> {
+    let mut engine = data.lock_engine()?;
+    load_ticket_type_by_id_locked(&mut engine, ticket_type_id).map_err(LoadError::Query)
 }
 
 fn load_ticket_type_by_id_locked(
@@ -397,7 +687,7 @@ fn load_ticket_type_by_id_locked(
         ticket_type_id
     );
 
-    match engine.execute_sql(&sql) {
+    match execute_logged(engine, &sql) {
         Ok(ReplOutput::Rows { mut rows, .. }) => {
             if let Some(row) = rows.pop() {
                 load_ticket_type_by_db_row(&row).map_err(|e: anyhow::Error| e.to_string())
@@ -445,9 +735,9 @@ fn load_ticket_type_by_db_row(row: &query::Tuple) -> anyhow::Result<TicketType>
     })
 }
 
-async fn load_order_by_id(data: &AppState, order_id: i64) -> Result<Order, String> {
-    let mut engine = data.engine.lock();
-    load_order_by_id_locked(&mut engine, order_id)
+async fn load_order_by_id(data: &AppState, order_id: i64) -> Result<Order, LoadError> {
+    let mut engine = data.lock_engine()?;
+    load_order_by_id_locked(&mut engine, order_id).map_err(LoadError::Query)
 }
 
 fn load_order_by_id_locked(
@@ -459,7 +749,7 @@ fn load_order_by_id_locked(
         order_id
     );
 
-    match engine.execute_sql(&sql) {
+    match execute_logged(engine, &sql) {
         Ok(ReplOutput::Rows { mut rows, .. }) => {
             if let Some(row) = rows.pop() {
                 load_order_by_db_row(&row).map_err(|e: anyhow::Error| e.to_string())
@@ -523,16 +813,8 @@ pub async fn create_event(
         return Ok(HttpResponse::BadRequest()
             .json(json!({"error": "VALIDATION_ERROR", "message": "Title is required"})));
     }
-    if create_req.start_time >= create_req.end_time {
-        return Ok(HttpResponse::BadRequest().json(
-            json!({"error": "VALIDATION_ERROR", "message": "End time must be after start time"}),
-        ));
-    }
 
-    let mut engine = data.engine.lock();
-    if let Err(e) = create_tables(&mut engine) {
-        return Ok(HttpResponse::InternalServerError().json(json!({"error": "DATABASE_ERROR", "message": format!("Failed to create tables: {}", e)})));
-    }
+    let mut engine = data.lock_engine()?;
 
     let now = Utc::now().format("%Y-%m-%d %H:%M:%S");
     let insert_sql = format!(
@@ -560,13 +842,13 @@ pub async fn create_event(
         now
     );
 
-    match engine.execute_sql(&insert_sql) {
+    match execute_logged(&mut engine, &insert_sql) {
         Ok(_) => {
             let select_sql = format!(
                 "SELECT id, organizer_user_id, title, description, venue, location, start_time, end_time, status, created_at, updated_at FROM events WHERE organizer_user_id = {}",
                 user.id.unwrap()
             );
-            match engine.execute_sql(&select_sql) {
+            match execute_logged(&mut engine, &select_sql) {
                 Ok(ReplOutput::Rows { mut rows, .. }) => {
                     let mut events = Vec::new();
                     for row in rows.drain(..) {
@@ -585,9 +867,11 @@ pub async fn create_event(
                 Err(e) => Ok(HttpResponse::InternalServerError().json(json!({"error": "CREATION_ERROR", "message": format!("Failed to query created event: {}", e)}))),
             }
         }
-        Err(e) => Ok(HttpResponse::InternalServerError().json(
-            json!({"error": "CREATION_ERROR", "message": format!("Failed to create event: {}", e)}),
-        )),
+        Err(e) => Ok(time_range_violation(&e).unwrap_or_else(|| {
+            HttpResponse::InternalServerError().json(
+                json!({"error": "CREATION_ERROR", "message": format!("Failed to create event: {}", e)}),
+            )
+        })),
     }
 }
 
@@ -595,10 +879,7 @@ pub async fn list_events(
     query: web::Query<HashMap<String, String>>,
     data: web::Data<AppState>,
 ) -> Result<HttpResponse> {
-    let mut engine = data.engine.lock();
-    if let Err(e) = create_tables(&mut engine) {
-        return Ok(HttpResponse::InternalServerError().json(json!({"error": "DATABASE_ERROR", "message": format!("Failed to create tables: {}", e)})));
-    }
+    let mut engine = data.lock_engine()?;
 
     let mut sql = String::from(
         "SELECT id, organizer_user_id, title, description, venue, location, start_time, end_time, status, created_at, updated_at FROM events WHERE 1=1",
@@ -613,14 +894,14 @@ pub async fn list_events(
         sql.push_str(&format!(" AND end_time <= '{}'", escape_sql_string(to)));
     }
     if let Some(q) = query.get("q") {
+        let needle = escape_sql_string(&escape_like_pattern(q));
         sql.push_str(&format!(
             " AND (title LIKE '%{}%' OR description LIKE '%{}%')",
-            escape_sql_string(q),
-            escape_sql_string(q)
+            needle, needle
         ));
     }
 
-    match engine.execute_sql(&sql) {
+    match execute_logged(&mut engine, &sql) {
         Ok(ReplOutput::Rows { mut rows, .. }) => {
             let mut events = Vec::new();
             for row in rows.drain(..) {
@@ -631,7 +912,8 @@ pub async fn list_events(
 
             let ticket_sql = "SELECT id, event_id, name, price, capacity, sales_start, sales_end, created_at, updated_at FROM ticket_types".to_string();
             let mut ticket_by_event: HashMap<i64, Vec<TicketType>> = HashMap::new();
-            if let Ok(ReplOutput::Rows { mut rows, .. }) = engine.execute_sql(&ticket_sql) {
+            if let Ok(ReplOutput::Rows { mut rows, .. }) = execute_logged(&mut engine, &ticket_sql)
+            {
                 for row in rows.drain(..) {
                     if let Ok(tt) = load_ticket_type_by_db_row(&row) {
                         ticket_by_event.entry(tt.event_id).or_default().push(tt);
@@ -686,6 +968,7 @@ pub async fn get_event(path: web::Path<i64>, data: web::Data<AppState>) -> Resul
         Ok(event) => {
             let ticket_types = match list_ticket_types_for_event(&data, event_id).await {
                 Ok(types) => types,
+                Err(LoadError::Busy(busy)) => return Err(busy.into()),
                 Err(e) => return Ok(HttpResponse::InternalServerError().json(json!({"error": "QUERY_ERROR", "message": format!("Failed to load ticket types: {}", e)}))),
             };
 
@@ -718,6 +1001,7 @@ pub async fn get_event(path: web::Path<i64>, data: web::Data<AppState>) -> Resul
             });
             Ok(HttpResponse::Ok().json(event_response))
         }
+        Err(LoadError::Busy(busy)) => Err(busy.into()),
         Err(_) => Ok(HttpResponse::NotFound()
             .json(json!({"error": "NOT_FOUND", "message": "Event not found"}))),
     }
@@ -726,14 +1010,14 @@ pub async fn get_event(path: web::Path<i64>, data: web::Data<AppState>) -> Resul
 async fn list_ticket_types_for_event(
     data: &AppState,
     event_id: i64,
-) -> Result<Vec<TicketType>, String> {
-    let mut engine = data.engine.lock();
+) -> Result<Vec<TicketType>, LoadError> {
+    let mut engine = data.lock_engine()?;
     let sql = format!(
         "SELECT id, event_id, name, price, capacity, sales_start, sales_end, created_at, updated_at FROM ticket_types WHERE event_id = {}",
         event_id
     );
 
-    match engine.execute_sql(&sql) {
+    match execute_logged(&mut engine, &sql) {
         Ok(ReplOutput::Rows { mut rows, .. }) => {
             let mut ticket_types = Vec::new();
             for row in rows.drain(..) {
@@ -763,6 +1047,7 @@ pub async fn update_event(
 
     let event = match load_event_by_id(&data, event_id).await {
         Ok(e) => e,
+        Err(LoadError::Busy(busy)) => return Err(busy.into()),
         Err(_) => {
             return Ok(HttpResponse::NotFound()
                 .json(json!({"error": "NOT_FOUND", "message": "Event not found"})));
@@ -811,21 +1096,25 @@ pub async fn update_event(
         Utc::now().format("%Y-%m-%d %H:%M:%S")
     ));
 
-    let mut engine = data.engine.lock();
+    let mut engine = data.lock_engine()?;
     let sql = format!(
         "UPDATE events SET {} WHERE id = {}",
         updates.join(", "),
         event_id
     );
 
-    match engine.execute_sql(&sql) {
+    match execute_logged(&mut engine, &sql) {
         Ok(_) => {
             match load_event_by_id_locked(&mut engine, event_id) {
                 Ok(updated_event) => Ok(HttpResponse::Ok().json(json!({"event": updated_event, "message": "Event updated successfully"}))),
                 Err(e) => Ok(HttpResponse::InternalServerError().json(json!({"error": "UPDATE_ERROR", "message": format!("Failed to reload event: {}", e)}))),
             }
         }
-        Err(e) => Ok(HttpResponse::InternalServerError().json(json!({"error": "UPDATE_ERROR", "message": format!("Failed to update event: {}", e)}))),
+        Err(e) => Ok(time_range_violation(&e).unwrap_or_else(|| {
+            HttpResponse::InternalServerError().json(
+                json!({"error": "UPDATE_ERROR", "message": format!("Failed to update event: {}", e)}),
+            )
+        })),
     }
 }
 
@@ -844,6 +1133,7 @@ pub async fn delete_event(
 
     let event = match load_event_by_id(&data, event_id).await {
         Ok(e) => e,
+        Err(LoadError::Busy(busy)) => return Err(busy.into()),
         Err(_) => {
             return Ok(HttpResponse::NotFound()
                 .json(json!({"error": "NOT_FOUND", "message": "Event not found"})));
@@ -855,10 +1145,10 @@ pub async fn delete_event(
             .json(json!({"error": "NOT_OWNER", "message": "You do not own this event"})));
     }
 
-    let mut engine = data.engine.lock();
+    let mut engine = data.lock_engine()?;
     let sql = format!("DELETE FROM events WHERE id = {}", event_id);
 
-    match engine.execute_sql(&sql) {
+    match execute_logged(&mut engine, &sql) {
         Ok(_) => Ok(HttpResponse::NoContent().finish()),
         Err(e) => Ok(HttpResponse::InternalServerError().json(
             json!({"error": "DELETE_ERROR", "message": format!("Failed to delete event: {}", e)}),
@@ -881,6 +1171,7 @@ pub async fn publish_event(
 
     let event = match load_event_by_id(&data, event_id).await {
         Ok(e) => e,
+        Err(LoadError::Busy(busy)) => return Err(busy.into()),
         Err(_) => {
             return Ok(HttpResponse::NotFound()
                 .json(json!({"error": "NOT_FOUND", "message": "Event not found"})));
@@ -892,14 +1183,14 @@ pub async fn publish_event(
             .json(json!({"error": "NOT_OWNER", "message": "You do not own this event"})));
     }
 
-    let mut engine = data.engine.lock();
+    let mut engine = data.lock_engine()?;
     let sql = format!(
         "UPDATE events SET status = 'PUBLISHED', updated_at = '{}' WHERE id = {}",
         Utc::now().format("%Y-%m-%d %H:%M:%S"),
         event_id
     );
 
-    match engine.execute_sql(&sql) {
+    match execute_logged(&mut engine, &sql) {
         Ok(_) => {
             match load_event_by_id_locked(&mut engine, event_id) {
                 Ok(updated_event) => Ok(HttpResponse::Ok().json(json!({"event": updated_event, "message": "Event published successfully"}))),
@@ -926,6 +1217,7 @@ pub async fn create_ticket_type(
 
     let event = match load_event_by_id(&data, event_id).await {
         Ok(e) => e,
+        Err(LoadError::Busy(busy)) => return Err(busy.into()),
         Err(_) => {
             return Ok(HttpResponse::NotFound()
                 .json(json!({"error": "NOT_FOUND", "message": "Event not found"})));
@@ -951,7 +1243,7 @@ pub async fn create_ticket_type(
             .json(json!({"error": "VALIDATION_ERROR", "message": "Capacity must be positive"})));
     }
 
-    let mut engine = data.engine.lock();
+    let mut engine = data.lock_engine()?;
     let now = Utc::now().format("%Y-%m-%d %H:%M:%S");
     let insert_sql = format!(
         "INSERT INTO ticket_types (event_id, name, price, capacity, sales_start, sales_end, created_at, updated_at) VALUES ({}, '{}', {}, {}, {}, {}, '{}', '{}')",
@@ -973,10 +1265,10 @@ pub async fn create_ticket_type(
         now
     );
 
-    match engine.execute_sql(&insert_sql) {
+    match execute_logged(&mut engine, &insert_sql) {
         Ok(_) => {
             let select_sql = format!("SELECT id, event_id, name, price, capacity, sales_start, sales_end, created_at, updated_at FROM ticket_types WHERE event_id = {}", event_id);
-            match engine.execute_sql(&select_sql) {
+            match execute_logged(&mut engine, &select_sql) {
                 Ok(ReplOutput::Rows { mut rows, .. }) => {
                     let mut ticket_types = Vec::new();
                     for row in rows.drain(..) {
@@ -1009,9 +1301,11 @@ pub async fn list_ticket_types(
         Ok(_) => {
             match list_ticket_types_for_event(&data, event_id).await {
                 Ok(ticket_types) => Ok(HttpResponse::Ok().json(json!({"ticket_types": ticket_types, "count": ticket_types.len()}))),
+                Err(LoadError::Busy(busy)) => Err(busy.into()),
                 Err(e) => Ok(HttpResponse::InternalServerError().json(json!({"error": "QUERY_ERROR", "message": format!("Failed to load ticket types: {}", e)}))),
             }
         }
+        Err(LoadError::Busy(busy)) => Err(busy.into()),
         Err(_) => Ok(HttpResponse::NotFound().json(json!({"error": "NOT_FOUND", "message": "Event not found"}))),
     }
 }
@@ -1032,6 +1326,7 @@ pub async fn update_ticket_type(
 
     let event = match load_event_by_id(&data, event_id).await {
         Ok(e) => e,
+        Err(LoadError::Busy(busy)) => return Err(busy.into()),
         Err(_) => {
             return Ok(HttpResponse::NotFound()
                 .json(json!({"error": "NOT_FOUND", "message": "Event not found"})));
@@ -1045,6 +1340,7 @@ pub async fn update_ticket_type(
 
     let ticket_type = match load_ticket_type_by_id(&data, ticket_type_id).await {
         Ok(tt) => tt,
+        Err(LoadError::Busy(busy)) => return Err(busy.into()),
         Err(_) => {
             return Ok(HttpResponse::NotFound()
                 .json(json!({"error": "NOT_FOUND", "message": "Ticket type not found"})));
@@ -1081,6 +1377,7 @@ pub async fn update_ticket_type(
         }
         let sold = match get_tickets_sold_for_ticket_type(&data, ticket_type_id).await {
             Ok(s) => s,
+            Err(LoadError::Busy(busy)) => return Err(busy.into()),
             Err(e) => return Ok(HttpResponse::InternalServerError().json(json!({"error": "QUERY_ERROR", "message": format!("Failed to check sold count: {}", e)}))),
         };
         if capacity < sold {
@@ -1099,14 +1396,14 @@ pub async fn update_ticket_type(
         Utc::now().format("%Y-%m-%d %H:%M:%S")
     ));
 
-    let mut engine = data.engine.lock();
+    let mut engine = data.lock_engine()?;
     let sql = format!(
         "UPDATE ticket_types SET {} WHERE id = {}",
         updates.join(", "),
         ticket_type_id
     );
 
-    match engine.execute_sql(&sql) {
+    match execute_logged(&mut engine, &sql) {
         Ok(_) => {
             match load_ticket_type_by_id_locked(&mut engine, ticket_type_id) {
                 Ok(updated_tt) => Ok(HttpResponse::Ok().json(json!({"ticket_type": updated_tt, "message": "Ticket type updated successfully"}))),
@@ -1132,6 +1429,7 @@ pub async fn delete_ticket_type(
 
     let event = match load_event_by_id(&data, event_id).await {
         Ok(e) => e,
+        Err(LoadError::Busy(busy)) => return Err(busy.into()),
         Err(_) => {
             return Ok(HttpResponse::NotFound()
                 .json(json!({"error": "NOT_FOUND", "message": "Event not found"})));
@@ -1145,6 +1443,7 @@ pub async fn delete_ticket_type(
 
     let ticket_type = match load_ticket_type_by_id(&data, ticket_type_id).await {
         Ok(tt) => tt,
+        Err(LoadError::Busy(busy)) => return Err(busy.into()),
         Err(_) => {
             return Ok(HttpResponse::NotFound()
                 .json(json!({"error": "NOT_FOUND", "message": "Ticket type not found"})));
@@ -1159,16 +1458,17 @@ pub async fn delete_ticket_type(
 
     let sold = match get_tickets_sold_for_ticket_type(&data, ticket_type_id).await {
         Ok(s) => s,
+        Err(LoadError::Busy(busy)) => return Err(busy.into()),
         Err(e) => return Ok(HttpResponse::InternalServerError().json(json!({"error": "QUERY_ERROR", "message": format!("Failed to check sold count: {}", e)}))),
     };
     if sold > 0 {
         return Ok(HttpResponse::Conflict().json(json!({"error": "CANNOT_DELETE_HAS_SALES", "message": format!("Cannot delete ticket type with {} tickets sold", sold)})));
     }
 
-    let mut engine = data.engine.lock();
+    let mut engine = data.lock_engine()?;
     let sql = format!("DELETE FROM ticket_types WHERE id = {}", ticket_type_id);
 
-    match engine.execute_sql(&sql) {
+    match execute_logged(&mut engine, &sql) {
         Ok(_) => Ok(HttpResponse::NoContent().finish()),
         Err(e) => Ok(HttpResponse::InternalServerError().json(json!({"error": "DELETE_ERROR", "message": format!("Failed to delete ticket type: {}", e)}))),
     }
@@ -1177,9 +1477,9 @@ pub async fn delete_ticket_type(
 async fn get_tickets_sold_for_ticket_type(
     data: &AppState,
     ticket_type_id: i64,
-) -> Result<i64, String> {
-    let mut engine = data.engine.lock();
-    get_tickets_sold_for_ticket_type_locked(&mut engine, ticket_type_id)
+) -> Result<i64, LoadError> {
+    let mut engine = data.lock_engine()?;
+    get_tickets_sold_for_ticket_type_locked(&mut engine, ticket_type_id).map_err(LoadError::Query)
 }
 
 fn get_tickets_sold_for_ticket_type_locked(
@@ -1191,7 +1491,7 @@ fn get_tickets_sold_for_ticket_type_locked(
         ticket_type_id
     );
 
-    match engine.execute_sql(&sql) {
+    match execute_logged(engine, &sql) {
         Ok(ReplOutput::Rows { mut rows, .. }) => {
             if let Some(row) = rows.pop() {
                 row.values()[0].as_i64().map_err(|e| e.to_string())
@@ -1221,7 +1521,7 @@ pub async fn create_order(
     }
 
     #[allow(clippy::await_holding_lock)]
-    let mut engine = data.engine.lock();
+    let mut engine = data.lock_engine()?;
     let mut total_amount: i64 = 0;
     let mut ticket_type_ids = Vec::new();
 
@@ -1277,13 +1577,13 @@ pub async fn create_order(
         user_id, total_amount, now, now
     );
 
-    let order_id = match engine.execute_sql(&insert_order_sql) {
+    let order_id = match execute_logged(&mut engine, &insert_order_sql) {
         Ok(_) => {
             let select_sql = format!(
                 "SELECT id FROM orders WHERE customer_user_id = {} ORDER BY id DESC",
                 user_id
             );
-            match engine.execute_sql(&select_sql) {
+            match execute_logged(&mut engine, &select_sql) {
                 Ok(ReplOutput::Rows { mut rows, .. }) => {
                     if let Some(row) = rows.pop() {
                         row.values()[0].as_i64().unwrap_or(0)
@@ -1309,12 +1609,12 @@ pub async fn create_order(
                 ticket_type.price,
                 now
             );
-            if let Err(e) = engine.execute_sql(&insert_ticket_sql) {
+            if let Err(e) = execute_logged(&mut engine, &insert_ticket_sql) {
                 let delete_tickets_sql =
                     format!("DELETE FROM tickets WHERE order_id = {}", order_id);
-                let _ = engine.execute_sql(&delete_tickets_sql);
+                let _ = execute_logged(&mut engine, &delete_tickets_sql);
                 let rollback_sql = format!("DELETE FROM orders WHERE id = {}", order_id);
-                let _ = engine.execute_sql(&rollback_sql);
+                let _ = execute_logged(&mut engine, &rollback_sql);
                 return Ok(HttpResponse::InternalServerError().json(json!({"error": "CREATION_ERROR", "message": format!("Failed to create tickets: {}", e)})));
             }
         }
@@ -1339,6 +1639,7 @@ pub async fn confirm_order(
 
     let order = match load_order_by_id(&data, order_id).await {
         Ok(o) => o,
+        Err(LoadError::Busy(busy)) => return Err(busy.into()),
         Err(_) => {
             return Ok(HttpResponse::NotFound()
                 .json(json!({"error": "NOT_FOUND", "message": "Order not found"})));
@@ -1356,7 +1657,7 @@ pub async fn confirm_order(
         OrderStatus::PENDING => {}
     }
 
-    let mut engine = data.engine.lock();
+    let mut engine = data.lock_engine()?;
     let sql = format!(
         "UPDATE orders SET status = 'PAID', updated_at = '{}' WHERE id = {}",
         Utc::now().format("%Y-%m-%d %H:%M:%S"),
@@ -1367,15 +1668,15 @@ pub async fn confirm_order(
         order_id
     );
 
-    match engine.execute_sql(&sql) {
+    match execute_logged(&mut engine, &sql) {
         Ok(_) => {
-            if let Err(e) = engine.execute_sql(&update_tickets_sql) {
+            if let Err(e) = execute_logged(&mut engine, &update_tickets_sql) {
                 let rollback_sql = format!(
                     "UPDATE orders SET status = 'PENDING', updated_at = '{}' WHERE id = {}",
                     Utc::now().format("%Y-%m-%d %H:%M:%S"),
                     order_id
                 );
-                let _ = engine.execute_sql(&rollback_sql);
+                let _ = execute_logged(&mut engine, &rollback_sql);
                 return Ok(HttpResponse::InternalServerError().json(
                     json!({"error": "CONFIRM_ERROR", "message": format!("Failed to update tickets: {}", e)}),
                 ));
@@ -1397,13 +1698,13 @@ pub async fn list_orders(data: web::Data<AppState>, req_http: HttpRequest) -> Re
         Err(e) => return Err(e),
     };
 
-    let mut engine = data.engine.lock();
+    let mut engine = data.lock_engine()?;
     let sql = format!(
         "SELECT id, customer_user_id, status, total_amount, created_at, updated_at FROM orders WHERE customer_user_id = {} ORDER BY created_at DESC, id DESC",
         user_id
     );
 
-    match engine.execute_sql(&sql) {
+    match execute_logged(&mut engine, &sql) {
         Ok(ReplOutput::Rows { mut rows, .. }) => {
             let mut orders_with_details = Vec::new();
             for row in rows.drain(..) {
@@ -1445,6 +1746,7 @@ pub async fn get_order(
 
     let order = match load_order_by_id(&data, order_id).await {
         Ok(o) => o,
+        Err(LoadError::Busy(busy)) => return Err(busy.into()),
         Err(_) => {
             return Ok(HttpResponse::NotFound()
                 .json(json!({"error": "NOT_FOUND", "message": "Order not found"})));
@@ -1456,9 +1758,11 @@ pub async fn get_order(
             .json(json!({"error": "NOT_OWNER", "message": "You do not own this order"})));
     }
 
-    let tickets = load_tickets_for_order(&data, order_id)
-        .await
-        .unwrap_or_default();
+    let tickets = match load_tickets_for_order(&data, order_id).await {
+        Ok(tickets) => tickets,
+        Err(LoadError::Busy(busy)) => return Err(busy.into()),
+        Err(LoadError::Query(_)) => Vec::new(),
+    };
     let order_with_details = OrderWithDetails { order, tickets };
 
     Ok(HttpResponse::Ok().json(order_with_details))
@@ -1467,9 +1771,9 @@ pub async fn get_order(
 async fn load_tickets_for_order(
     data: &AppState,
     order_id: i64,
-) -> Result<Vec<TicketWithDetails>, String> {
-    let mut engine = data.engine.lock();
-    load_tickets_for_order_locked(&mut engine, order_id)
+) -> Result<Vec<TicketWithDetails>, LoadError> {
+    let mut engine = data.lock_engine()?;
+    load_tickets_for_order_locked(&mut engine, order_id).map_err(LoadError::Query)
 }
 
 fn load_tickets_for_order_locked(
@@ -1481,7 +1785,7 @@ fn load_tickets_for_order_locked(
         order_id
     );
 
-    match engine.execute_sql(&sql) {
+    match execute_logged(engine, &sql) {
         Ok(ReplOutput::Rows { rows, .. }) => {
             let mut tickets = Vec::new();
             for row in rows {
@@ -1545,6 +1849,7 @@ pub async fn list_tickets(
 
     let tickets = match load_all_tickets_for_user(&data, user_id).await {
         Ok(t) => t,
+        Err(LoadError::Busy(busy)) => return Err(busy.into()),
         Err(e) => return Ok(HttpResponse::InternalServerError().json(
             json!({"error": "QUERY_ERROR", "message": format!("Failed to load tickets: {}", e)}),
         )),
@@ -1556,9 +1861,9 @@ pub async fn list_tickets(
 async fn load_all_tickets_for_user(
     data: &AppState,
     user_id: i64,
-) -> Result<Vec<TicketWithDetails>, String> {
-    let mut engine = data.engine.lock();
-    load_all_tickets_for_user_locked(&mut engine, user_id)
+) -> Result<Vec<TicketWithDetails>, LoadError> {
+    let mut engine = data.lock_engine()?;
+    load_all_tickets_for_user_locked(&mut engine, user_id).map_err(LoadError::Query)
 }
 
 fn load_all_tickets_for_user_locked(
@@ -1570,7 +1875,7 @@ fn load_all_tickets_for_user_locked(
         user_id
     );
 
-    match engine.execute_sql(&sql) {
+    match execute_logged(engine, &sql) {
         Ok(ReplOutput::Rows { rows, .. }) => {
             let mut tickets = Vec::new();
             for row in rows {
@@ -1672,8 +1977,8 @@ pub async fn update_user_role(
         escaped_role, now, escaped_user_id
     );
 
-    let mut engine = data.engine.lock();
-    match engine.execute_sql(&update_sql) {
+    let mut engine = data.lock_engine()?;
+    match execute_logged(&mut engine, &update_sql) {
         Ok(_) => Ok(HttpResponse::Ok().json(json!({"message": "User role updated successfully"}))),
         Err(e) => Ok(HttpResponse::InternalServerError().json(
             json!({"error": "UPDATE_ERROR", "message": format!("Failed to update user role: {}", e)}),
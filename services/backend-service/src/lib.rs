@@ -2,10 +2,14 @@ pub mod app_state;
 pub mod auth;
 pub mod handlers;
 pub mod jwt;
+pub mod logging;
+pub mod migrations;
 pub mod models;
 
 pub use app_state::*;
 pub use auth::*;
 pub use handlers::*;
 pub use jwt::*;
+pub use logging::*;
+pub use migrations::*;
 pub use models::*;
@@ -0,0 +1,212 @@
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{HeaderName, HeaderValue};
+use actix_web::middleware::Next;
+use actix_web::{Error, HttpMessage, HttpRequest};
+use chrono::{SecondsFormat, Utc};
+use log::kv::{self, VisitSource};
+use serde_json::{json, Map};
+use std::io::Write;
+use std::time::Instant;
+
+/// Header a client may set to choose the id of its request; the response
+/// always carries the id used.
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Longest request id accepted from a client; longer ones are replaced.
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+tokio::task_local! {
+    /// Id of the request the current task serves, set by [`assign_request_id`].
+    static CURRENT_REQUEST_ID: RequestId;
+}
+
+/// Correlation id of one HTTP request. Every log line written for the
+/// request carries it as `request_id`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestId(String);
+
+impl RequestId {
+    fn new() -> Self {
+        Self(uuid::Uuid::new_v4().to_string())
+    }
+
+    /// The id [`assign_request_id`] gave `req`, or a new one for a request
+    /// that did not pass through it.
+    pub fn of(req: &HttpRequest) -> Self {
+        req.extensions()
+            .get::<Self>()
+            .cloned()
+            .unwrap_or_else(Self::new)
+    }
+
+    /// The id of the request the current task serves, or a new one outside
+    /// of a request, such as for work done at startup.
+    pub fn current() -> Self {
+        CURRENT_REQUEST_ID
+            .try_with(Self::clone)
+            .unwrap_or_else(|_| Self::new())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// A client's id, if it is printable ASCII and not too long to log.
+    fn from_header(value: &HeaderValue) -> Option<Self> {
+        let id = value.to_str().ok()?;
+        let valid = !id.is_empty()
+            && id.len() <= MAX_REQUEST_ID_LEN
+            && id.bytes().all(|byte| byte.is_ascii_graphic());
+        valid.then(|| Self(id.to_string()))
+    }
+}
+
+/// Middleware giving every request a [`RequestId`], taken from the
+/// `X-Request-Id` header when the client sent a usable one, and serves the
+/// request with it as [`RequestId::current`]. It logs the
+/// request's method, path, status and duration when it finishes, and
+/// returns the id in the response's `X-Request-Id` header.
+pub async fn assign_request_id(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let request_id = req
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(RequestId::from_header)
+        .unwrap_or_else(RequestId::new);
+    req.extensions_mut().insert(request_id.clone());
+    let method = req.method().to_string();
+    let path = req.path().to_string();
+    let started = Instant::now();
+
+    let result = CURRENT_REQUEST_ID
+        .scope(request_id.clone(), next.call(req))
+        .await;
+    let status = match &result {
+        Ok(response) => response.status(),
+        Err(error) => error.as_response_error().status_code(),
+    };
+    log::info!(
+        target: "http",
+        request_id = request_id.as_str(),
+        method = method.as_str(),
+        path = path.as_str(),
+        status = status.as_u16(),
+        duration_ms = started.elapsed().as_millis() as u64;
+        "request finished"
+    );
+    let mut response = result?;
+    if let Ok(value) = HeaderValue::from_str(request_id.as_str()) {
+        response
+            .headers_mut()
+            .insert(HeaderName::from_static(REQUEST_ID_HEADER), value);
+    }
+    Ok(response)
+}
+
+/// How log lines are written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    /// env_logger's text lines, with the fields as `key=value` pairs.
+    Text,
+    /// One JSON object per line, with the fields as its members.
+    Json,
+}
+
+impl LogFormat {
+    /// `Json` when `LOG_FORMAT=json`, `Text` otherwise.
+    pub fn from_env() -> Self {
+        match std::env::var("LOG_FORMAT") {
+            Ok(format) if format.eq_ignore_ascii_case("json") => LogFormat::Json,
+            _ => LogFormat::Text,
+        }
+    }
+}
+
+/// Installs the logger. `RUST_LOG` picks what is logged, `info` and above
+/// by default.
+pub fn init_logging(format: LogFormat) {
+    let mut builder =
+        env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info"));
+    if format == LogFormat::Json {
+        builder.format(|buf, record| writeln!(buf, "{}", json_line(record)));
+    }
+    builder.init();
+}
+
+fn json_line(record: &log::Record<'_>) -> serde_json::Value {
+    let mut line = Map::new();
+    line.insert(
+        "ts".to_string(),
+        json!(Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true)),
+    );
+    line.insert("level".to_string(), json!(record.level().as_str()));
+    line.insert("target".to_string(), json!(record.target()));
+    line.insert("message".to_string(), json!(record.args().to_string()));
+    let _ = record.key_values().visit(&mut JsonFields(&mut line));
+    serde_json::Value::Object(line)
+}
+
+/// Copies the fields of a log record into a JSON object, keeping numbers
+/// and booleans as such.
+struct JsonFields<'a>(&'a mut Map<String, serde_json::Value>);
+
+impl<'kvs> VisitSource<'kvs> for JsonFields<'_> {
+    fn visit_pair(&mut self, key: kv::Key<'kvs>, value: kv::Value<'kvs>) -> Result<(), kv::Error> {
+        let value = if let Some(number) = value.to_u64() {
+            json!(number)
+        } else if let Some(number) = value.to_i64() {
+            json!(number)
+        } else if let Some(flag) = value.to_bool() {
+            json!(flag)
+        } else {
+            json!(value.to_string())
+        };
+        self.0.insert(key.to_string(), value);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_line_keeps_fields_as_members() {
+        let fields: &[(&str, kv::Value)] = &[
+            ("request_id", kv::Value::from("abc")),
+            ("status", kv::Value::from(200u16)),
+            ("sql", kv::Value::from("SELECT \"x\"")),
+        ];
+        let line = json_line(
+            &log::Record::builder()
+                .level(log::Level::Info)
+                .target("sql")
+                .args(format_args!("statement finished"))
+                .key_values(&fields)
+                .build(),
+        );
+        assert_eq!(line["level"], "INFO");
+        assert_eq!(line["target"], "sql");
+        assert_eq!(line["message"], "statement finished");
+        assert_eq!(line["request_id"], "abc");
+        assert_eq!(line["status"], 200);
+        assert_eq!(line["sql"], "SELECT \"x\"");
+        let text = line.to_string();
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&text).unwrap(),
+            line
+        );
+    }
+
+    #[test]
+    fn client_request_ids_must_be_short_printable_ascii() {
+        let id = |value: &str| RequestId::from_header(&HeaderValue::from_str(value).unwrap());
+        assert_eq!(id("req-42").unwrap().as_str(), "req-42");
+        assert!(id("").is_none());
+        assert!(id("two words").is_none());
+        assert!(id(&"x".repeat(MAX_REQUEST_ID_LEN + 1)).is_none());
+    }
+}
@@ -8,21 +8,26 @@ use std::collections::HashMap;
 use std::env;
 use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 
 pub mod app_state;
 pub mod auth;
 pub mod handlers;
 pub mod jwt;
+pub mod logging;
+pub mod migrations;
 pub mod models;
 
-use crate::app_state::AppState;
+use crate::app_state::{AppState, RequestLimits, DEFAULT_BUSY_TIMEOUT};
 use crate::auth::{get_me, google_auth_callback, google_auth_start, update_profile, update_role};
 use crate::handlers::{
     abort_transaction, begin_transaction, commit_transaction, confirm_order, create_event,
-    create_order, create_ticket_type, delete_event, delete_ticket_type, execute_sql, get_event,
-    get_order, health, list_events, list_orders, list_ticket_types, list_tickets, publish_event,
-    update_event, update_ticket_type, update_user_role,
+    create_order, create_ticket_type, delete_event, delete_ticket_type, execute_batch, execute_sql,
+    get_event, get_order, health, list_events, list_orders, list_ticket_types, list_tickets,
+    publish_event, update_event, update_ticket_type, update_user_role,
 };
+use crate::logging::{assign_request_id, init_logging, LogFormat};
+use crate::migrations::run_migrations;
 
 #[derive(Parser, Debug)]
 #[command(name = "backend-service")]
@@ -36,11 +41,24 @@ struct Args {
 
     #[arg(long, default_value = "0.0.0.0")]
     bind: String,
+
+    /// Milliseconds a request waits for the engine before failing with
+    /// `DATABASE_BUSY`.
+    #[arg(long)]
+    busy_timeout_ms: Option<u64>,
+
+    /// Longest SQL string, in bytes, a request may carry.
+    #[arg(long)]
+    max_sql_bytes: Option<usize>,
+
+    /// Most statements a batch request may carry.
+    #[arg(long)]
+    max_batch_statements: Option<usize>,
 }
 
 #[actix_web::main]
 async fn main() -> AnyhowResult<()> {
-    env_logger::init();
+    init_logging(LogFormat::from_env());
 
     let args = Args::parse();
 
@@ -62,42 +80,61 @@ async fn main() -> AnyhowResult<()> {
         args.bind
     };
 
-    println!("Starting RDBMS Backend Service");
-    println!("Database path: {:?}", db_path);
-    println!("Listening on: {}:{}", bind, port);
+    let busy_timeout = if let Ok(ms) = env::var("BUSY_TIMEOUT_MS") {
+        Duration::from_millis(ms.parse().context("Invalid BUSY_TIMEOUT_MS value")?)
+    } else {
+        args.busy_timeout_ms
+            .map_or(DEFAULT_BUSY_TIMEOUT, Duration::from_millis)
+    };
 
-    // Debug: Print environment variables (mask secrets)
-    println!("\n=== Environment Debug ===");
-    println!(
-        "GOOGLE_CLIENT_ID: {}",
-        std::env::var("GOOGLE_CLIENT_ID").unwrap_or_else(|_| "NOT SET".to_string())
-    );
-    println!(
-        "GOOGLE_REDIRECT_URI: {}",
-        std::env::var("GOOGLE_REDIRECT_URI")
-            .unwrap_or_else(|_| "NOT SET (using default)".to_string())
+    let mut limits = RequestLimits::default();
+    if let Ok(bytes) = env::var("MAX_SQL_BYTES") {
+        limits.max_sql_bytes = bytes.parse().context("Invalid MAX_SQL_BYTES value")?;
+    } else if let Some(bytes) = args.max_sql_bytes {
+        limits.max_sql_bytes = bytes;
+    }
+    if let Ok(count) = env::var("MAX_BATCH_STATEMENTS") {
+        limits.max_batch_statements = count
+            .parse()
+            .context("Invalid MAX_BATCH_STATEMENTS value")?;
+    } else if let Some(count) = args.max_batch_statements {
+        limits.max_batch_statements = count;
+    }
+
+    log::info!(
+        db_path:? = db_path,
+        bind = bind.as_str(),
+        port = port;
+        "starting RDBMS backend service"
     );
-    println!(
-        "JWT_SECRET: {}",
-        if std::env::var("JWT_SECRET").is_ok() {
+    let google_client_id = env::var("GOOGLE_CLIENT_ID").unwrap_or_else(|_| "NOT SET".to_string());
+    let google_redirect_uri =
+        env::var("GOOGLE_REDIRECT_URI").unwrap_or_else(|_| "NOT SET (using default)".to_string());
+    log::info!(
+        google_client_id = google_client_id.as_str(),
+        google_redirect_uri = google_redirect_uri.as_str(),
+        jwt_secret = if env::var("JWT_SECRET").is_ok() {
             "SET (hidden)"
         } else {
             "NOT SET"
-        }
+        };
+        "environment"
     );
-    println!("=========================\n");
 
     if let Some(parent) = db_path.parent() {
         std::fs::create_dir_all(parent).context("create db directory")?;
     }
 
-    let engine = Arc::new(Mutex::new(
-        Engine::new(&db_path).context("Failed to initialize database engine")?,
-    ));
+    let mut engine = Engine::new(&db_path).context("Failed to initialize database engine")?;
+    let applied = run_migrations(&mut engine).context("Failed to apply migrations")?;
+    log::info!(applied = applied.len(); "applied pending migrations");
+    let engine = Arc::new(Mutex::new(engine));
 
     let app_state = AppState {
         engine,
         transactions: Arc::new(Mutex::new(HashMap::new())),
+        busy_timeout,
+        limits,
     };
 
     let bind_addr = format!("{}:{}", bind, port);
@@ -112,11 +149,12 @@ async fn main() -> AnyhowResult<()> {
         App::new()
             .app_data(web::Data::new(app_state.clone()))
             .wrap(cors)
-            .wrap(middleware::Logger::default())
+            .wrap(middleware::from_fn(assign_request_id))
             .service(
                 web::scope("/api")
                     .route("/health", web::get().to(health))
                     .route("/sql", web::post().to(execute_sql))
+                    .route("/sql/batch", web::post().to(execute_batch))
                     .route("/tx/begin", web::post().to(begin_transaction))
                     .route("/tx/{tx_id}/commit", web::post().to(commit_transaction))
                     .route("/tx/{tx_id}/abort", web::post().to(abort_transaction)),
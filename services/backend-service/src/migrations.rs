@@ -0,0 +1,233 @@
+use anyhow::{anyhow, Context};
+use chrono::Utc;
+use db::engine::Engine;
+use db::printer::ReplOutput;
+use db::sql::split_statements;
+use query::Value;
+use std::collections::HashSet;
+
+/// A versioned SQL script. Scripts run in version order and each runs once
+/// per database; applied versions are recorded in the `migrations` table.
+pub struct Migration {
+    pub version: i64,
+    pub name: &'static str,
+    pub sql: &'static str,
+}
+
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "create_domain_tables",
+        sql: include_str!("../migrations/0001_create_domain_tables.sql"),
+    },
+    Migration {
+        version: 2,
+        name: "index_tickets_by_order",
+        sql: include_str!("../migrations/0002_index_tickets_by_order.sql"),
+    },
+    Migration {
+        version: 3,
+        name: "check_event_time_range",
+        sql: include_str!("../migrations/0003_check_event_time_range.sql"),
+    },
+];
+
+const MIGRATIONS_TABLE_SQL: &str = "CREATE TABLE IF NOT EXISTS migrations (
+    version INTEGER PRIMARY KEY,
+    name TEXT NOT NULL,
+    applied_at TEXT NOT NULL
+)";
+
+/// Applies every migration not yet recorded and returns the versions it
+/// applied, oldest first. Each script runs in one transaction with the row
+/// recording it, so a failed script is rolled back, schema changes
+/// included, and not recorded.
+pub fn run_migrations(engine: &mut Engine) -> anyhow::Result<Vec<i64>> {
+    apply_pending(engine, MIGRATIONS)
+}
+
+fn apply_pending(engine: &mut Engine, migrations: &[Migration]) -> anyhow::Result<Vec<i64>> {
+    engine
+        .execute_sql(MIGRATIONS_TABLE_SQL)
+        .context("Failed to create migrations table")?;
+    let applied = applied_versions(engine)?;
+
+    let mut newly_applied = Vec::new();
+    for migration in migrations {
+        if applied.contains(&migration.version) {
+            continue;
+        }
+        log::info!(
+            "Applying migration {} ({})",
+            migration.version,
+            migration.name
+        );
+        engine
+            .execute_sql("BEGIN")
+            .context("Failed to begin migration")?;
+        if let Err(e) = apply_migration(engine, migration) {
+            if let Err(rollback) = engine.execute_sql("ROLLBACK") {
+                log::error!(
+                    "Failed to roll back migration {}: {:#}",
+                    migration.version,
+                    rollback
+                );
+            }
+            return Err(e);
+        }
+        engine
+            .execute_sql("COMMIT")
+            .with_context(|| format!("Failed to commit migration {}", migration.version))?;
+        newly_applied.push(migration.version);
+    }
+    Ok(newly_applied)
+}
+
+/// Runs a migration's statements and records it, inside the caller's
+/// transaction.
+fn apply_migration(engine: &mut Engine, migration: &Migration) -> anyhow::Result<()> {
+    for statement in split_statements(migration.sql).statements {
+        engine.execute_sql(&statement).with_context(|| {
+            format!(
+                "Migration {} ({}) failed",
+                migration.version, migration.name
+            )
+        })?;
+    }
+    engine
+        .execute_prepared(
+            "INSERT INTO migrations (version, name, applied_at) VALUES (?, ?, ?)",
+            &[
+                Value::Integer(migration.version),
+                Value::String(migration.name.to_string()),
+                Value::String(Utc::now().format("%Y-%m-%d %H:%M:%S").to_string()),
+            ],
+        )
+        .context("Failed to record migration")?;
+    Ok(())
+}
+
+pub fn applied_versions(engine: &mut Engine) -> anyhow::Result<HashSet<i64>> {
+    match engine
+        .execute_sql("SELECT version FROM migrations")
+        .context("Failed to read applied migrations")?
+    {
+        ReplOutput::Rows { rows, .. } => rows.iter().map(|row| row.values()[0].as_i64()).collect(),
+        _ => Err(anyhow!("Unexpected response from database")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn migrations_apply_once() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let db_path = temp_dir.path().join("test.db");
+
+        let mut engine = Engine::new(&db_path).unwrap();
+        assert_eq!(run_migrations(&mut engine).unwrap(), vec![1, 2, 3]);
+        assert!(engine.execute_sql("SELECT COUNT(*) FROM users").is_ok());
+        assert!(engine.execute_sql("SELECT COUNT(*) FROM tickets").is_ok());
+        assert!(run_migrations(&mut engine).unwrap().is_empty());
+        drop(engine);
+
+        let mut engine = Engine::new(&db_path).unwrap();
+        assert!(run_migrations(&mut engine).unwrap().is_empty());
+        assert_eq!(
+            applied_versions(&mut engine).unwrap(),
+            HashSet::from([1, 2, 3])
+        );
+    }
+
+    #[test]
+    fn failed_migration_is_rolled_back_and_not_recorded() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut engine = Engine::new(&temp_dir.path().join("test.db")).unwrap();
+        run_migrations(&mut engine).unwrap();
+
+        let migrations = [
+            Migration {
+                version: 4,
+                name: "seed_o'brien",
+                sql: "CREATE TABLE IF NOT EXISTS notes (id INT PRIMARY KEY);
+                      INSERT INTO notes VALUES (1);",
+            },
+            Migration {
+                version: 5,
+                name: "broken",
+                sql: "INSERT INTO notes VALUES (2);
+                      CREATE TABLE tags (id INT PRIMARY KEY);
+                      INSERT INTO missing_table VALUES (1);",
+            },
+        ];
+        assert!(apply_pending(&mut engine, &migrations).is_err());
+        assert!(!engine.in_transaction());
+        assert_eq!(
+            applied_versions(&mut engine).unwrap(),
+            HashSet::from([1, 2, 3, 4])
+        );
+        match engine.execute_sql("SELECT id FROM notes").unwrap() {
+            ReplOutput::Rows { rows, .. } => assert_eq!(rows.len(), 1),
+            _ => panic!("expected rows"),
+        }
+        assert!(engine.execute_sql("SELECT id FROM tags").is_err());
+        match engine
+            .execute_sql("SELECT name FROM migrations WHERE version = 4")
+            .unwrap()
+        {
+            ReplOutput::Rows { rows, .. } => {
+                assert_eq!(
+                    rows[0].values()[0],
+                    Value::String("seed_o'brien".to_string())
+                )
+            }
+            _ => panic!("expected rows"),
+        }
+    }
+
+    #[test]
+    fn events_created_before_migrations_get_the_time_range_check() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let db_path = temp_dir.path().join("test.db");
+
+        // the events table as the service created it before migrations
+        let mut engine = Engine::new(&db_path).unwrap();
+        engine
+            .execute_sql(
+                "CREATE TABLE IF NOT EXISTS events (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    organizer_user_id INTEGER NOT NULL,
+                    title TEXT NOT NULL,
+                    description TEXT,
+                    venue TEXT,
+                    location TEXT,
+                    start_time TEXT NOT NULL,
+                    end_time TEXT NOT NULL,
+                    status TEXT DEFAULT 'DRAFT',
+                    created_at TEXT NOT NULL,
+                    updated_at TEXT NOT NULL
+                )",
+            )
+            .unwrap();
+        drop(engine);
+
+        let mut engine = Engine::new(&db_path).unwrap();
+        assert_eq!(run_migrations(&mut engine).unwrap(), vec![1, 2, 3]);
+        let insert = |start: &str, end: &str| {
+            format!(
+                "INSERT INTO events (organizer_user_id, title, start_time, end_time, created_at, updated_at) \
+                 VALUES (1, 'Launch', '{}', '{}', 'now', 'now')",
+                start, end
+            )
+        };
+        engine
+            .execute_sql(&insert("2025-06-01 18:00:00", "2025-06-01 20:00:00"))
+            .unwrap();
+        let err = engine
+            .execute_sql(&insert("2025-06-01 20:00:00", "2025-06-01 18:00:00"))
+            .unwrap_err();
+        assert!(err.to_string().contains("events_time_range"));
+    }
+}
@@ -1,25 +1,81 @@
 use anyhow::anyhow;
 use chrono::{DateTime, Utc};
+use query::execution::Value;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Deserialize)]
 pub struct SqlRequest {
     pub sql: String,
     pub tx_id: Option<String>,
+    /// Values bound, in order, to the `?` placeholders of `sql`: JSON
+    /// numbers, strings, booleans or nulls.
+    pub params: Option<Vec<serde_json::Value>>,
+}
+
+impl SqlRequest {
+    /// `params` as engine values; none when the request has no `params`.
+    pub fn bound_params(&self) -> anyhow::Result<Vec<Value>> {
+        self.params
+            .iter()
+            .flatten()
+            .enumerate()
+            .map(|(index, param)| match param {
+                serde_json::Value::Null => Ok(Value::Null),
+                serde_json::Value::Bool(flag) => Ok(Value::Boolean(*flag)),
+                serde_json::Value::String(text) => Ok(Value::String(text.clone())),
+                serde_json::Value::Number(number) => Ok(match number.as_i64() {
+                    Some(number) => Value::Integer(number),
+                    None => Value::Float(number.as_f64().unwrap_or(f64::NAN)),
+                }),
+                other => Err(anyhow!(
+                    "parameter {} must be a number, string, boolean or null, not {}",
+                    index + 1,
+                    other
+                )),
+            })
+            .collect()
+    }
 }
 
 #[derive(Debug, Serialize)]
 pub struct SqlResponse {
     pub columns: Option<Vec<String>>,
+    pub column_types: Option<Vec<String>>,
     pub rows: Option<Vec<Vec<SerializableValue>>>,
     pub rows_affected: Option<usize>,
     pub message: Option<String>,
 }
 
+/// Statements run in order by `POST /api/sql/batch`.
+#[derive(Debug, Deserialize)]
+pub struct BatchRequest {
+    pub statements: Vec<String>,
+    pub tx_id: Option<String>,
+}
+
+/// One result per statement that ran. When a statement fails, `error`
+/// reports it and the statements after it are not run.
+#[derive(Debug, Serialize)]
+pub struct BatchResponse {
+    pub results: Vec<SqlResponse>,
+    pub error: Option<BatchError>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BatchError {
+    pub statement_index: usize,
+    pub error_code: String,
+    pub message: String,
+    pub retryable: bool,
+}
+
 #[derive(Debug, Serialize)]
 pub struct ErrorResponse {
     pub error_code: String,
     pub message: String,
+    /// The transaction was rolled back because of contention with other
+    /// transactions; running it again from the start may succeed.
+    pub retryable: bool,
 }
 
 #[derive(Debug, Serialize)]
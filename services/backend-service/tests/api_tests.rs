@@ -1,4 +1,17 @@
+use actix_web::test::TestRequest;
+use actix_web::web;
+use backend_service::{
+    assign_request_id, begin_transaction, commit_transaction, execute_batch, execute_sql,
+    get_event, list_events, run_migrations, AppState, BatchRequest, RequestLimits, SqlRequest,
+    DEFAULT_BUSY_TIMEOUT, REQUEST_ID_HEADER,
+};
 use db::engine::Engine;
+use db::printer::ReplOutput;
+use parking_lot::Mutex;
+use query::execution::{decode_result, Value};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
 use tempfile::TempDir;
 
 #[test]
@@ -178,3 +191,833 @@ fn test_persistence_via_engine() {
         assert!(output.contains("second"));
     }
 }
+
+#[actix_rt::test]
+#[allow(clippy::await_holding_lock)]
+async fn test_abandoned_sql_request_is_cancelled() {
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("test.db");
+
+    let mut engine = Engine::new(&db_path).unwrap();
+    engine.execute_sql("CREATE TABLE jobs (id INT)").unwrap();
+    let state = AppState {
+        engine: Arc::new(Mutex::new(engine)),
+        transactions: Arc::new(Mutex::new(HashMap::new())),
+        busy_timeout: DEFAULT_BUSY_TIMEOUT,
+        limits: RequestLimits::default(),
+    };
+
+    // keep the engine busy so the request is still in flight when the
+    // client goes away
+    let busy = state.engine.lock();
+    let request = execute_sql(
+        web::Json(SqlRequest {
+            sql: "INSERT INTO jobs VALUES (1)".to_string(),
+            tx_id: None,
+            params: None,
+        }),
+        web::Data::new(state.clone()),
+        TestRequest::default().to_http_request(),
+    );
+    let outcome = tokio::time::timeout(Duration::from_millis(100), request).await;
+    assert!(outcome.is_err(), "request should still be waiting");
+    drop(busy);
+
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    let mut engine = state.engine.lock();
+    match engine.execute_sql("SELECT id FROM jobs").unwrap() {
+        ReplOutput::Rows { rows, .. } => assert!(rows.is_empty()),
+        _ => panic!("expected rows output"),
+    }
+}
+
+#[actix_rt::test]
+async fn test_select_response_includes_column_types() {
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("test.db");
+
+    let mut engine = Engine::new(&db_path).unwrap();
+    engine
+        .execute_sql("CREATE TABLE typed (id INT, name TEXT, score REAL, active BOOLEAN)")
+        .unwrap();
+    engine
+        .execute_sql("INSERT INTO typed VALUES (1, 'a', 1.5, true)")
+        .unwrap();
+    let data = web::Data::new(AppState {
+        engine: Arc::new(Mutex::new(engine)),
+        transactions: Arc::new(Mutex::new(HashMap::new())),
+        busy_timeout: DEFAULT_BUSY_TIMEOUT,
+        limits: RequestLimits::default(),
+    });
+
+    let column_types = |sql: &str| {
+        let data = data.clone();
+        let request = SqlRequest {
+            sql: sql.to_string(),
+            tx_id: None,
+            params: None,
+        };
+        async move {
+            let response = execute_sql(
+                web::Json(request),
+                data,
+                TestRequest::default().to_http_request(),
+            )
+            .await
+            .unwrap();
+            let body = actix_web::body::to_bytes(response.into_body())
+                .await
+                .unwrap();
+            let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+            json["column_types"].clone()
+        }
+    };
+
+    assert_eq!(
+        column_types("SELECT id, name, score, active FROM typed").await,
+        serde_json::json!(["Integer", "Text", "Real", "Boolean"])
+    );
+    // computed columns are typed from their operands, or reported unknown
+    assert_eq!(
+        column_types("SELECT id + 1, score * 2, id / 2, COALESCE(id, 0), id > 0 FROM typed").await,
+        serde_json::json!(["Integer", "Real", "Real", "Integer", "Boolean"])
+    );
+    assert_eq!(
+        column_types("SELECT COALESCE(NULL, name), -score, name IS NULL, NULL FROM typed").await,
+        serde_json::json!(["Text", "Real", "Boolean", "Unknown"])
+    );
+}
+
+#[actix_rt::test]
+async fn test_sql_binds_params_to_placeholders() {
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("test.db");
+
+    let mut engine = Engine::new(&db_path).unwrap();
+    engine
+        .execute_sql("CREATE TABLE people (id INT PRIMARY KEY, name TEXT)")
+        .unwrap();
+    let data = web::Data::new(AppState {
+        engine: Arc::new(Mutex::new(engine)),
+        transactions: Arc::new(Mutex::new(HashMap::new())),
+        busy_timeout: DEFAULT_BUSY_TIMEOUT,
+        limits: RequestLimits::default(),
+    });
+
+    let run = |sql: &str, params: serde_json::Value| {
+        let data = data.clone();
+        let request = SqlRequest {
+            sql: sql.to_string(),
+            tx_id: None,
+            params: serde_json::from_value(params).unwrap(),
+        };
+        async move {
+            let response = execute_sql(
+                web::Json(request),
+                data,
+                TestRequest::default().to_http_request(),
+            )
+            .await
+            .unwrap();
+            let status = response.status();
+            let body = actix_web::body::to_bytes(response.into_body())
+                .await
+                .unwrap();
+            (
+                status,
+                serde_json::from_slice::<serde_json::Value>(&body).unwrap(),
+            )
+        }
+    };
+
+    run(
+        "INSERT INTO people VALUES (?, ?), (?, ?)",
+        serde_json::json!([1, "O'Brien", 2, "Smith"]),
+    )
+    .await;
+    let (_, json) = run(
+        "SELECT id FROM people WHERE name = ?",
+        serde_json::json!(["O'Brien"]),
+    )
+    .await;
+    assert_eq!(
+        json["rows"],
+        serde_json::json!([[{"type": "int", "value": 1}]])
+    );
+    let (_, json) = run(
+        "SELECT name FROM people WHERE id = ?",
+        serde_json::json!([2]),
+    )
+    .await;
+    assert_eq!(
+        json["rows"],
+        serde_json::json!([[{"type": "text", "value": "Smith"}]])
+    );
+
+    let (status, json) = run(
+        "SELECT name FROM people WHERE id = ?",
+        serde_json::json!([[2]]),
+    )
+    .await;
+    assert_eq!(status, actix_web::http::StatusCode::BAD_REQUEST);
+    assert_eq!(json["error_code"], "INVALID_PARAMS");
+}
+
+#[test]
+fn test_events_reject_end_before_start() {
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("test.db");
+
+    let mut engine = Engine::new(&db_path).unwrap();
+    run_migrations(&mut engine).unwrap();
+
+    let insert = |start: &str, end: &str| {
+        format!(
+            "INSERT INTO events (organizer_user_id, title, start_time, end_time, created_at, updated_at) \
+             VALUES (1, 'Launch', '{}', '{}', 'now', 'now')",
+            start, end
+        )
+    };
+    engine
+        .execute_sql(&insert("2025-06-01 18:00:00", "2025-06-01 20:00:00"))
+        .unwrap();
+    let err = engine
+        .execute_sql(&insert("2025-06-01 20:00:00", "2025-06-01 18:00:00"))
+        .unwrap_err();
+    assert!(err.to_string().contains("events_time_range"));
+}
+
+#[actix_rt::test]
+async fn test_event_search_treats_wildcards_literally() {
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("test.db");
+
+    let mut engine = Engine::new(&db_path).unwrap();
+    run_migrations(&mut engine).unwrap();
+    for title in ["50% off launch", "500 seat gala", "early_bird"] {
+        engine
+            .execute_sql(&format!(
+                "INSERT INTO events (organizer_user_id, title, start_time, end_time, status, created_at, updated_at) \
+                 VALUES (1, '{}', '2025-06-01 18:00:00', '2025-06-01 20:00:00', 'PUBLISHED', \
+                 '2025-05-01 09:00:00', '2025-05-01 09:00:00')",
+                title
+            ))
+            .unwrap();
+    }
+    let data = web::Data::new(AppState {
+        engine: Arc::new(Mutex::new(engine)),
+        transactions: Arc::new(Mutex::new(HashMap::new())),
+        busy_timeout: DEFAULT_BUSY_TIMEOUT,
+        limits: RequestLimits::default(),
+    });
+
+    let search = |q: &str| {
+        let data = data.clone();
+        let query = HashMap::from([("q".to_string(), q.to_string())]);
+        async move {
+            let response = list_events(web::Query(query), data).await.unwrap();
+            let body = actix_web::body::to_bytes(response.into_body())
+                .await
+                .unwrap();
+            let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+            json["data"]
+                .as_array()
+                .unwrap_or_else(|| panic!("unexpected response {}", json))
+                .iter()
+                .map(|event| event["title"].as_str().unwrap().to_string())
+                .collect::<Vec<_>>()
+        }
+    };
+    assert_eq!(search("50%").await, vec!["50% off launch"]);
+    assert_eq!(search("y_b").await, vec!["early_bird"]);
+    assert_eq!(search("_").await, vec!["early_bird"]);
+}
+
+#[actix_rt::test]
+async fn test_binary_result_matches_json_and_is_smaller() {
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("test.db");
+
+    let mut engine = Engine::new(&db_path).unwrap();
+    engine
+        .execute_sql("CREATE TABLE metrics (id INT, reading REAL, total BIGINT)")
+        .unwrap();
+    for id in 0..200 {
+        engine
+            .execute_sql(&format!(
+                "INSERT INTO metrics VALUES ({}, {}.25, {})",
+                id,
+                id,
+                id * 1_000_000
+            ))
+            .unwrap();
+    }
+    let state = web::Data::new(AppState {
+        engine: Arc::new(Mutex::new(engine)),
+        transactions: Arc::new(Mutex::new(HashMap::new())),
+        busy_timeout: DEFAULT_BUSY_TIMEOUT,
+        limits: RequestLimits::default(),
+    });
+
+    let query = || {
+        web::Json(SqlRequest {
+            sql: "SELECT id, reading, total FROM metrics".to_string(),
+            tx_id: None,
+            params: None,
+        })
+    };
+    let binary = execute_sql(
+        query(),
+        state.clone(),
+        TestRequest::default()
+            .insert_header(("Accept", "application/octet-stream"))
+            .to_http_request(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        binary.headers().get("content-type").unwrap(),
+        "application/octet-stream"
+    );
+    let binary = actix_web::body::to_bytes(binary.into_body()).await.unwrap();
+    let json = execute_sql(query(), state, TestRequest::default().to_http_request())
+        .await
+        .unwrap();
+    let json = actix_web::body::to_bytes(json.into_body()).await.unwrap();
+
+    let (schema, rows) = decode_result(&binary).unwrap();
+    let names: Vec<&str> = schema.fields.iter().map(|f| f.name.as_str()).collect();
+    assert_eq!(names, ["id", "reading", "total"]);
+    assert_eq!(rows.len(), 200);
+    assert_eq!(
+        rows[7].values(),
+        &[
+            Value::Integer(7),
+            Value::Float(7.25),
+            Value::Integer(7_000_000)
+        ]
+    );
+    assert!(binary.len() < json.len());
+}
+
+#[actix_rt::test]
+async fn test_batch_returns_a_result_per_statement() {
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("test.db");
+
+    let mut engine = Engine::new(&db_path).unwrap();
+    engine
+        .execute_sql("CREATE TABLE items (id INT PRIMARY KEY, name TEXT)")
+        .unwrap();
+    let state = web::Data::new(AppState {
+        engine: Arc::new(Mutex::new(engine)),
+        transactions: Arc::new(Mutex::new(HashMap::new())),
+        busy_timeout: DEFAULT_BUSY_TIMEOUT,
+        limits: RequestLimits::default(),
+    });
+
+    let batch = |statements: &[&str]| {
+        web::Json(BatchRequest {
+            statements: statements.iter().map(|sql| sql.to_string()).collect(),
+            tx_id: None,
+        })
+    };
+    let response = execute_batch(
+        batch(&[
+            "INSERT INTO items VALUES (1, 'a'), (2, 'b'), (3, 'c')",
+            "UPDATE items SET name = 'z' WHERE id > 1",
+            "SELECT id, name FROM items ORDER BY id",
+        ]),
+        state.clone(),
+        TestRequest::default().to_http_request(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(response.status(), 200);
+    let body = actix_web::body::to_bytes(response.into_body())
+        .await
+        .unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    let results = json["results"].as_array().unwrap();
+    assert_eq!(results.len(), 3);
+    assert_eq!(results[0]["rows_affected"], 3);
+    assert_eq!(results[1]["rows_affected"], 2);
+    assert_eq!(results[2]["rows"].as_array().unwrap().len(), 3);
+    assert_eq!(results[2]["rows"][2][1]["value"], "z");
+    assert!(json["error"].is_null());
+
+    let response = execute_batch(
+        batch(&[
+            "INSERT INTO items VALUES (4, 'd')",
+            "INSERT INTO items VALUES (1, 'dup')",
+            "DELETE FROM items",
+        ]),
+        state.clone(),
+        TestRequest::default().to_http_request(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(response.status(), 400);
+    let body = actix_web::body::to_bytes(response.into_body())
+        .await
+        .unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["results"].as_array().unwrap().len(), 1);
+    assert_eq!(json["error"]["statement_index"], 1);
+    let remaining = match state
+        .engine
+        .lock()
+        .execute_sql("SELECT * FROM items")
+        .unwrap()
+    {
+        ReplOutput::Rows { rows, .. } => rows.len(),
+        _ => panic!("expected rows"),
+    };
+    assert_eq!(remaining, 4, "statements after the failure must not run");
+}
+
+#[actix_rt::test]
+async fn test_busy_engine_fails_fast_with_retryable_error() {
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("test.db");
+
+    let mut engine = Engine::new(&db_path).unwrap();
+    engine
+        .execute_sql("CREATE TABLE seats (id INT PRIMARY KEY, holder TEXT)")
+        .unwrap();
+    let state = web::Data::new(AppState {
+        engine: Arc::new(Mutex::new(engine)),
+        transactions: Arc::new(Mutex::new(HashMap::new())),
+        busy_timeout: Duration::from_millis(50),
+        limits: RequestLimits::default(),
+    });
+    let run = || {
+        execute_sql(
+            web::Json(SqlRequest {
+                sql: "INSERT INTO seats VALUES (1, 'ann')".to_string(),
+                tx_id: None,
+                params: None,
+            }),
+            state.clone(),
+            TestRequest::default().to_http_request(),
+        )
+    };
+
+    // another request holds the engine for longer than the busy-timeout
+    let (locked_tx, locked_rx) = std::sync::mpsc::channel();
+    let (release_tx, release_rx) = std::sync::mpsc::channel::<()>();
+    let engine = Arc::clone(&state.engine);
+    let holder = std::thread::spawn(move || {
+        let _guard = engine.lock();
+        locked_tx.send(()).unwrap();
+        release_rx.recv().ok();
+    });
+    locked_rx.recv().unwrap();
+    let error = run().await.unwrap_err();
+    release_tx.send(()).unwrap();
+    holder.join().unwrap();
+    let response = error.error_response();
+    assert_eq!(response.status(), 503);
+    let body = actix_web::body::to_bytes(response.into_body())
+        .await
+        .unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["error_code"], "DATABASE_BUSY");
+    assert_eq!(json["retryable"], true);
+
+    // nothing ran, so the same request succeeds once the engine is free
+    assert_eq!(run().await.unwrap().status(), 200);
+}
+
+#[actix_rt::test]
+async fn test_busy_engine_is_not_reported_as_a_missing_row() {
+    let temp_dir = TempDir::new().unwrap();
+    let mut engine = Engine::new(&temp_dir.path().join("test.db")).unwrap();
+    run_migrations(&mut engine).unwrap();
+    let state = web::Data::new(AppState {
+        engine: Arc::new(Mutex::new(engine)),
+        transactions: Arc::new(Mutex::new(HashMap::new())),
+        busy_timeout: Duration::from_millis(50),
+        limits: RequestLimits::default(),
+    });
+
+    let (locked_tx, locked_rx) = std::sync::mpsc::channel();
+    let (release_tx, release_rx) = std::sync::mpsc::channel::<()>();
+    let engine = Arc::clone(&state.engine);
+    let holder = std::thread::spawn(move || {
+        let _guard = engine.lock();
+        locked_tx.send(()).unwrap();
+        release_rx.recv().ok();
+    });
+    locked_rx.recv().unwrap();
+    let error = get_event(web::Path::from(1), state.clone())
+        .await
+        .unwrap_err();
+    release_tx.send(()).unwrap();
+    holder.join().unwrap();
+    let response = error.error_response();
+    assert_eq!(response.status(), 503);
+    let body = actix_web::body::to_bytes(response.into_body())
+        .await
+        .unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["error_code"], "DATABASE_BUSY");
+    assert_eq!(json["retryable"], true);
+
+    // with the engine free, the same lookup finds no such event
+    let response = get_event(web::Path::from(1), state).await.unwrap();
+    assert_eq!(response.status(), 404);
+}
+
+#[actix_rt::test]
+async fn test_lock_timeout_is_a_retryable_conflict() {
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("test.db");
+
+    let mut engine = Engine::new(&db_path).unwrap();
+    engine
+        .execute_sql("CREATE TABLE seats (id INT PRIMARY KEY, holder TEXT)")
+        .unwrap();
+    engine
+        .execute_sql("INSERT INTO seats VALUES (1, NULL)")
+        .unwrap();
+    let state = web::Data::new(AppState {
+        engine: Arc::new(Mutex::new(engine)),
+        transactions: Arc::new(Mutex::new(HashMap::new())),
+        busy_timeout: DEFAULT_BUSY_TIMEOUT,
+        limits: RequestLimits::default(),
+    });
+
+    let begin = || async {
+        let response = begin_transaction(state.clone()).await.unwrap();
+        let body = actix_web::body::to_bytes(response.into_body())
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        json["tx_id"].as_str().unwrap().to_string()
+    };
+    let run = |sql: &str, tx_id: Option<&str>| {
+        execute_sql(
+            web::Json(SqlRequest {
+                sql: sql.to_string(),
+                tx_id: tx_id.map(str::to_string),
+                params: None,
+            }),
+            state.clone(),
+            TestRequest::default().to_http_request(),
+        )
+    };
+
+    let holder = begin().await;
+    let response = run(
+        "UPDATE seats SET holder = 'ann' WHERE id = 1",
+        Some(&holder),
+    )
+    .await
+    .unwrap();
+    assert_eq!(response.status(), 200);
+
+    // the waiter times out on the holder's lock and is rolled back
+    let waiter = begin().await;
+    let response = run(
+        "UPDATE seats SET holder = 'bob' WHERE id = 1",
+        Some(&waiter),
+    )
+    .await
+    .unwrap();
+    assert_eq!(response.status(), 409);
+    let body = actix_web::body::to_bytes(response.into_body())
+        .await
+        .unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["error_code"], "TRANSACTION_CONFLICT");
+    assert_eq!(json["retryable"], true);
+    assert!(!state.transactions.lock().contains_key(&waiter));
+
+    let response = commit_transaction(web::Path::from(holder), state.clone())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+
+    // retrying in a new transaction succeeds and sees the holder's commit
+    let retry = begin().await;
+    let response = run("UPDATE seats SET holder = 'bob' WHERE id = 1", Some(&retry))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    commit_transaction(web::Path::from(retry), state.clone())
+        .await
+        .unwrap();
+    let holder = match state
+        .engine
+        .lock()
+        .execute_sql("SELECT holder FROM seats")
+        .unwrap()
+    {
+        ReplOutput::Rows { rows, .. } => rows[0].values()[0].clone(),
+        _ => panic!("expected rows"),
+    };
+    assert_eq!(holder, Value::String("bob".to_string()));
+
+    let response = run("SELECT * FROM missing", None).await.unwrap();
+    assert_eq!(response.status(), 400);
+    let body = actix_web::body::to_bytes(response.into_body())
+        .await
+        .unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["retryable"], false);
+}
+
+#[actix_rt::test]
+async fn test_oversized_requests_are_rejected_before_parsing() {
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("test.db");
+
+    let mut engine = Engine::new(&db_path).unwrap();
+    engine
+        .execute_sql("CREATE TABLE items (id INT PRIMARY KEY)")
+        .unwrap();
+    let state = web::Data::new(AppState {
+        engine: Arc::new(Mutex::new(engine)),
+        transactions: Arc::new(Mutex::new(HashMap::new())),
+        busy_timeout: DEFAULT_BUSY_TIMEOUT,
+        limits: RequestLimits {
+            max_sql_bytes: 64,
+            max_batch_statements: 2,
+        },
+    });
+    let error_json = |error: actix_web::Error| async move {
+        let response = error.error_response();
+        let status = response.status();
+        let body = actix_web::body::to_bytes(response.into_body())
+            .await
+            .unwrap();
+        (
+            status,
+            serde_json::from_slice::<serde_json::Value>(&body).unwrap(),
+        )
+    };
+
+    // not valid SQL either, so a parse would fail with a different code
+    let sql = format!("SELECT {}", "x".repeat(64));
+    let error = execute_sql(
+        web::Json(SqlRequest {
+            sql,
+            tx_id: None,
+            params: None,
+        }),
+        state.clone(),
+        TestRequest::default().to_http_request(),
+    )
+    .await
+    .expect_err("SQL over the byte limit");
+    let (status, json) = error_json(error).await;
+    assert_eq!(status, actix_web::http::StatusCode::PAYLOAD_TOO_LARGE);
+    assert_eq!(json["error_code"], "SQL_TOO_LONG");
+    assert_eq!(
+        json["message"],
+        "SQL is 71 bytes, over the limit of 64 bytes"
+    );
+
+    let batch = |statements: &[&str]| {
+        web::Json(BatchRequest {
+            statements: statements.iter().map(|sql| sql.to_string()).collect(),
+            tx_id: None,
+        })
+    };
+    let error = execute_batch(
+        batch(&[
+            "INSERT INTO items VALUES (1)",
+            "INSERT INTO items VALUES (2)",
+            "INSERT INTO items VALUES (3)",
+        ]),
+        state.clone(),
+        TestRequest::default().to_http_request(),
+    )
+    .await
+    .expect_err("batch over the statement limit");
+    let (status, json) = error_json(error).await;
+    assert_eq!(status, actix_web::http::StatusCode::BAD_REQUEST);
+    assert_eq!(json["error_code"], "TOO_MANY_STATEMENTS");
+
+    let response = execute_batch(
+        batch(&[
+            "INSERT INTO items VALUES (1)",
+            "INSERT INTO items VALUES (2)",
+        ]),
+        state.clone(),
+        TestRequest::default().to_http_request(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(response.status(), 200);
+    let remaining = match state
+        .engine
+        .lock()
+        .execute_sql("SELECT * FROM items")
+        .unwrap()
+    {
+        ReplOutput::Rows { rows, .. } => rows.len(),
+        _ => panic!("expected rows"),
+    };
+    assert_eq!(remaining, 2, "the rejected batch must not run");
+}
+
+#[actix_rt::test]
+async fn test_responses_carry_the_request_id() {
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("test.db");
+    let engine = Engine::new(&db_path).unwrap();
+    let state = web::Data::new(AppState {
+        engine: Arc::new(Mutex::new(engine)),
+        transactions: Arc::new(Mutex::new(HashMap::new())),
+        busy_timeout: DEFAULT_BUSY_TIMEOUT,
+        limits: RequestLimits::default(),
+    });
+    let app = actix_web::test::init_service(
+        actix_web::App::new()
+            .app_data(state)
+            .wrap(actix_web::middleware::from_fn(assign_request_id))
+            .route("/api/sql", web::post().to(execute_sql)),
+    )
+    .await;
+    let sql = |sql: &str| {
+        TestRequest::post()
+            .uri("/api/sql")
+            .set_json(serde_json::json!({ "sql": sql }))
+    };
+
+    // the client's id is kept, for failed statements too
+    let response = actix_web::test::call_service(
+        &app,
+        sql("SELECT * FROM missing")
+            .insert_header((REQUEST_ID_HEADER, "client-7"))
+            .to_request(),
+    )
+    .await;
+    assert_eq!(response.status(), 400);
+    assert_eq!(request_id(&response), "client-7");
+
+    // otherwise each request gets a new one
+    let first = actix_web::test::call_service(&app, sql("SELECT 1").to_request()).await;
+    let second = actix_web::test::call_service(
+        &app,
+        sql("SELECT 1")
+            .insert_header((REQUEST_ID_HEADER, "not a valid id"))
+            .to_request(),
+    )
+    .await;
+    assert_eq!(first.status(), 200);
+    assert!(uuid_like(&request_id(&first)));
+    assert!(uuid_like(&request_id(&second)));
+    assert_ne!(request_id(&first), request_id(&second));
+}
+
+#[actix_rt::test]
+async fn test_domain_endpoints_log_their_sql_with_the_request_id() {
+    let logs = captured_logs();
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("test.db");
+    let mut engine = Engine::new(&db_path).unwrap();
+    run_migrations(&mut engine).unwrap();
+    let state = web::Data::new(AppState {
+        engine: Arc::new(Mutex::new(engine)),
+        transactions: Arc::new(Mutex::new(HashMap::new())),
+        busy_timeout: DEFAULT_BUSY_TIMEOUT,
+        limits: RequestLimits::default(),
+    });
+    let app = actix_web::test::init_service(
+        actix_web::App::new()
+            .app_data(state)
+            .wrap(actix_web::middleware::from_fn(assign_request_id))
+            .route("/api/events", web::get().to(list_events)),
+    )
+    .await;
+
+    let response = actix_web::test::call_service(
+        &app,
+        TestRequest::get()
+            .uri("/api/events?status=PUBLISHED")
+            .insert_header((REQUEST_ID_HEADER, "events-list-1"))
+            .to_request(),
+    )
+    .await;
+    assert_eq!(response.status(), 200);
+
+    let statements = logs
+        .lock()
+        .iter()
+        .filter(|fields| {
+            fields.get("target").map(String::as_str) == Some("sql")
+                && fields.get("request_id").map(String::as_str) == Some("events-list-1")
+        })
+        .map(|fields| fields["sql"].clone())
+        .collect::<Vec<_>>();
+    assert!(
+        statements
+            .iter()
+            .any(|sql| sql.contains("FROM events") && sql.contains("status = 'PUBLISHED'")),
+        "{statements:?}"
+    );
+    assert!(
+        statements
+            .iter()
+            .any(|sql| sql.contains("FROM ticket_types")),
+        "{statements:?}"
+    );
+}
+
+/// Fields of every record logged by this test binary, with the record's
+/// target under `target`.
+fn captured_logs() -> &'static Mutex<Vec<HashMap<String, String>>> {
+    struct Capture(Mutex<Vec<HashMap<String, String>>>);
+
+    impl log::Log for Capture {
+        fn enabled(&self, _: &log::Metadata<'_>) -> bool {
+            true
+        }
+
+        fn log(&self, record: &log::Record<'_>) {
+            struct Fields<'a>(&'a mut HashMap<String, String>);
+            impl<'kvs> log::kv::VisitSource<'kvs> for Fields<'_> {
+                fn visit_pair(
+                    &mut self,
+                    key: log::kv::Key<'kvs>,
+                    value: log::kv::Value<'kvs>,
+                ) -> Result<(), log::kv::Error> {
+                    self.0.insert(key.to_string(), value.to_string());
+                    Ok(())
+                }
+            }
+            let mut fields = HashMap::new();
+            fields.insert("target".to_string(), record.target().to_string());
+            let _ = record.key_values().visit(&mut Fields(&mut fields));
+            self.0.lock().push(fields);
+        }
+
+        fn flush(&self) {}
+    }
+
+    static CAPTURE: std::sync::OnceLock<&'static Capture> = std::sync::OnceLock::new();
+    let capture = CAPTURE.get_or_init(|| {
+        let capture = Box::leak(Box::new(Capture(Mutex::new(Vec::new()))));
+        log::set_logger(capture).expect("no other logger in this test binary");
+        log::set_max_level(log::LevelFilter::Info);
+        capture
+    });
+    &capture.0
+}
+
+fn request_id<B>(response: &actix_web::dev::ServiceResponse<B>) -> String {
+    response
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .expect("request id header")
+        .to_str()
+        .unwrap()
+        .to_string()
+}
+
+fn uuid_like(id: &str) -> bool {
+    id.len() == 36 && id.chars().filter(|c| *c == '-').count() == 4
+}